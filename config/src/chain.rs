@@ -0,0 +1,116 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Chain presets, the defaults and checks that differ between Grin
+//! mainnet, testnet (floonet) and a custom chain
+
+use types::MinerConfig;
+
+/// Heights at which mainnet header versions were bumped by hard forks
+const MAINNET_HARD_FORKS: &[u64] = &[262_080, 524_160, 786_240, 1_048_320];
+
+/// Heights at which testnet header versions were bumped by hard forks
+const TESTNET_HARD_FORKS: &[u64] = &[185_040, 298_080, 552_960, 642_240];
+
+/// Chain the miner is mining on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChainType {
+	/// Grin mainnet
+	Mainnet,
+	/// Grin testnet (floonet)
+	Testnet,
+	/// Anything else, nothing is assumed about it
+	Custom,
+}
+
+impl ChainType {
+	/// Parse a chain type name, mainnet if none is given
+	pub fn from_name(name: Option<&str>) -> Result<ChainType, String> {
+		match name.map(|n| n.to_lowercase()) {
+			None => Ok(ChainType::Mainnet),
+			Some(ref n) if n == "mainnet" => Ok(ChainType::Mainnet),
+			Some(ref n) if n == "testnet" || n == "floonet" => Ok(ChainType::Testnet),
+			Some(ref n) if n == "custom" => Ok(ChainType::Custom),
+			Some(n) => Err(format!(
+				"Unknown chain_type {}, expected mainnet, testnet or custom",
+				n
+			)),
+		}
+	}
+
+	/// Stratum port of a Grin node on this chain
+	pub fn default_port(&self) -> Option<u16> {
+		match *self {
+			ChainType::Mainnet => Some(3416),
+			ChainType::Testnet => Some(13416),
+			ChainType::Custom => None,
+		}
+	}
+
+	/// Whether blocks can be mined on this chain with the given plugin
+	/// type and edge bits: cuckaroo 29 or cuckatoo 31 and up on Grin
+	/// chains
+	pub fn accepts(&self, type_filter: &str, edge_bits: u8) -> bool {
+		match *self {
+			ChainType::Custom => true,
+			_ if type_filter.starts_with("cuckaroo") => edge_bits == 29,
+			_ => edge_bits >= 31,
+		}
+	}
+
+	/// Header version expected at the given height, if known
+	pub fn header_version(&self, height: u64) -> Option<u16> {
+		let forks = match *self {
+			ChainType::Mainnet => MAINNET_HARD_FORKS,
+			ChainType::Testnet => TESTNET_HARD_FORKS,
+			ChainType::Custom => return None,
+		};
+		// past the last fork, the version stays the last fork's
+		Some(1 + forks.iter().filter(|f| height >= **f).count() as u16)
+	}
+}
+
+impl MinerConfig {
+	/// The configured chain type
+	pub fn chain_type(&self) -> Result<ChainType, String> {
+		ChainType::from_name(self.chain_type.as_ref().map(|c| c.as_str()))
+	}
+
+	/// Fill in what the chain type presets, i.e. the stratum port if the
+	/// server address doesn't have one, returning warnings about plugins
+	/// that can't mine on the chain
+	pub fn apply_chain_defaults(&mut self) -> Result<Vec<String>, String> {
+		let chain = self.chain_type()?;
+		let host = match self.stratum_server_addr.find("://") {
+			Some(i) => &self.stratum_server_addr[i + 3..],
+			None => &self.stratum_server_addr[..],
+		}.to_string();
+		if !host.contains(':') {
+			if let Some(port) = chain.default_port() {
+				self.stratum_server_addr = format!("{}:{}", self.stratum_server_addr, port);
+			}
+		}
+		Ok(self
+			.miner_plugin_config
+			.iter()
+			.filter(|p| !chain.accepts(&p.type_filter, p.edge_bits))
+			.map(|p| {
+				format!(
+					"plugin {} with edge_bits {} can't mine blocks on {:?}",
+					p.type_filter, p.edge_bits, chain
+				)
+			})
+			.collect())
+	}
+}
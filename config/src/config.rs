@@ -15,11 +15,12 @@
 //! Configuration file management
 
 use std::env;
-use std::io::Read;
-use std::path::PathBuf;
-use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
 
 use toml;
+use migrate::{self, CONFIG_VERSION};
 use types::MinerConfig;
 use util::LoggingConfig;
 use types::{ConfigError, ConfigMembers, GlobalConfig};
@@ -32,13 +33,41 @@ extern crate dirs;
 const CONFIG_FILE_NAME: &'static str = "grin-miner.toml";
 const GRIN_HOME: &'static str = ".grin";
 
+/// The commented default config file, as shipped
+pub const DEFAULT_CONFIG: &'static str = include_str!("../../grin-miner.toml");
+
 /// Returns the defaults, as strewn throughout the code
 
 impl Default for ConfigMembers {
 	fn default() -> ConfigMembers {
 		ConfigMembers {
+			config_version: CONFIG_VERSION,
 			mining: MinerConfig::default(),
 			logging: Some(LoggingConfig::default()),
+			alerts: None,
+			api: None,
+			notifications: None,
+			stats_log: None,
+			shared_stats: None,
+			telemetry: None,
+			mqtt: None,
+			history_store: None,
+			scheduler: None,
+			hardware_monitor: None,
+			thermal: None,
+			recovery: None,
+			watchdog: None,
+			rig: None,
+			control_socket: None,
+			grpc: None,
+			fleet: None,
+			submit_limit: None,
+			reject_breaker: None,
+			failure_policy: None,
+			desktop: None,
+			solo: None,
+			usage_stats: None,
+			update: None,
 		}
 	}
 }
@@ -48,6 +77,8 @@ impl Default for GlobalConfig {
 		GlobalConfig {
 			config_file_path: None,
 			using_config_file: false,
+			migrations: vec![],
+			merged: vec![],
 			members: Some(ConfigMembers::default()),
 		}
 	}
@@ -124,34 +155,202 @@ impl GlobalConfig {
 		return_value.read_config()
 	}
 
-	/// Read config
+	fn parse_error(&self, message: String) -> ConfigError {
+		ConfigError::ParseError(self.file_name(), message)
+	}
+
+	fn file_name(&self) -> String {
+		self.config_file_path
+			.as_ref()
+			.map(|p| p.to_string_lossy().into_owned())
+			.unwrap_or_default()
+	}
+
+	/// Read config, upgrading it first if it was written for an older
+	/// release, and refusing keys that aren't understood
 	pub fn read_config(mut self) -> Result<GlobalConfig, ConfigError> {
 		let mut file = File::open(self.config_file_path.as_mut().unwrap())?;
 		let mut contents = String::new();
 		file.read_to_string(&mut contents)?;
-		let decoded: Result<ConfigMembers, toml::de::Error> = toml::from_str(&contents);
-		match decoded {
-			Ok(gc) => {
-				// Put the struct back together, because the config
-				// file was flattened a bit
-				self.using_config_file = true;
-				self.members = Some(gc);
-				return Ok(self);
-			}
-			Err(e) => {
+		let mut root: toml::Value =
+			toml::from_str(&contents).map_err(|e| self.parse_error(format!("{}", e)))?;
+		let original = root.clone();
+		let version = migrate::file_version(&root).map_err(|e| self.parse_error(e))?;
+		self.migrations = migrate::migrate(&mut root)
+			.map_err(|e| ConfigError::MigrationError(self.file_name(), e))?;
+
+		let mut merged = root.clone();
+		let includes = self.includes(&mut merged)?;
+		for path in includes {
+			self.merge_file(&mut merged, &path)?;
+		}
+
+		let decoded: ConfigMembers = merged
+			.clone()
+			.try_into()
+			.map_err(|e| self.parse_error(format!("{}", e)))?;
+		let parsed = toml::Value::try_from(&decoded)
+			.map_err(|e| ConfigError::SerializationError(format!("{}", e)))?;
+		let unknown = migrate::unknown_keys(&merged, &parsed);
+		if !unknown.is_empty() {
+			return Err(ConfigError::UnknownKeysError(self.file_name(), unknown));
+		}
+
+		if !self.migrations.is_empty() {
+			self.write_migrated(&contents, version, &original, &root)?;
+		}
+		self.using_config_file = true;
+		self.members = Some(decoded);
+		Ok(self)
+	}
+
+	/// Take the include directive out of the config, returning the files
+	/// it names, relative to the config file's directory
+	fn includes(&self, root: &mut toml::Value) -> Result<Vec<PathBuf>, ConfigError> {
+		let include = match root.as_table_mut().and_then(|t| t.remove("include")) {
+			Some(i) => i,
+			None => return Ok(vec![]),
+		};
+		let base_dir = self.config_file_path
+			.as_ref()
+			.and_then(|p| p.parent())
+			.map(|p| p.to_path_buf())
+			.unwrap_or_default();
+		let files = match include {
+			toml::Value::String(s) => vec![s],
+			toml::Value::Array(a) => a
+				.into_iter()
+				.map(|v| match v {
+					toml::Value::String(s) => Ok(s),
+					_ => Err(self.parse_error("include must list file names".to_string())),
+				})
+				.collect::<Result<Vec<String>, ConfigError>>()?,
+			_ => return Err(self.parse_error("include must list file names".to_string())),
+		};
+		Ok(files.into_iter().map(|f| base_dir.join(f)).collect())
+	}
+
+	/// Merge the config fragment at path over root, noting the keys it set
+	fn merge_file(&mut self, root: &mut toml::Value, path: &Path) -> Result<(), ConfigError> {
+		let file_name = path.display().to_string();
+		let mut contents = String::new();
+		File::open(path)
+			.and_then(|mut f| f.read_to_string(&mut contents))
+			.map_err(|e| ConfigError::FileIOError(file_name.clone(), format!("{}", e)))?;
+		let mut fragment: toml::Value = toml::from_str(&contents)
+			.map_err(|e| ConfigError::ParseError(file_name.clone(), format!("{}", e)))?;
+		if let Some(t) = fragment.as_table_mut() {
+			if t.contains_key("include") {
 				return Err(ConfigError::ParseError(
-					String::from(
-						self.config_file_path
-							.as_mut()
-							.unwrap()
-							.to_str()
-							.unwrap()
-							.clone(),
-					),
-					String::from(format!("{}", e)),
+					file_name,
+					"included files can't include others".to_string(),
 				));
 			}
+			t.remove("config_version");
 		}
+		let mut keys = vec![];
+		merge_value(root, fragment, "", &mut keys);
+		self.merged.push(format!("{} set {}", file_name, keys.join(", ")));
+		Ok(())
+	}
+
+	/// Merge the given config files over the config, in order, as with
+	/// the include directive
+	pub fn merge_files(&mut self, paths: &[String]) -> Result<(), ConfigError> {
+		if paths.is_empty() {
+			return Ok(());
+		}
+		let members = self.members.take().unwrap_or_default();
+		let mut root = toml::Value::try_from(&members)
+			.map_err(|e| ConfigError::SerializationError(format!("{}", e)))?;
+		for p in paths {
+			self.merge_file(&mut root, Path::new(p))?;
+		}
+		let decoded: ConfigMembers = root
+			.clone()
+			.try_into()
+			.map_err(|e| ConfigError::ParseError(paths.join(", "), format!("{}", e)))?;
+		let parsed = toml::Value::try_from(&decoded)
+			.map_err(|e| ConfigError::SerializationError(format!("{}", e)))?;
+		let unknown = migrate::unknown_keys(&root, &parsed);
+		if !unknown.is_empty() {
+			return Err(ConfigError::UnknownKeysError(paths.join(", "), unknown));
+		}
+		self.members = Some(decoded);
+		Ok(())
+	}
+
+	/// Back up the config file as it was, and write the upgraded one. If
+	/// only the version changed, the file is kept as is (with its comments)
+	/// apart from the new config_version line.
+	fn write_migrated(
+		&mut self,
+		contents: &str,
+		version: u32,
+		original: &toml::Value,
+		migrated: &toml::Value,
+	) -> Result<(), ConfigError> {
+		let path = self.config_file_path.clone().unwrap();
+		let backup = PathBuf::from(format!("{}.v{}.bak", path.display(), version));
+		let io_error =
+			|e: ::std::io::Error| ConfigError::MigrationError(path.display().to_string(), format!("{}", e));
+		fs::copy(&path, &backup).map_err(&io_error)?;
+
+		let mut unversioned = migrated.clone();
+		if let Some(t) = unversioned.as_table_mut() {
+			t.remove("config_version");
+		}
+		let new_contents = if unversioned == *original {
+			format!("config_version = {}\n\n{}", CONFIG_VERSION, contents)
+		} else {
+			let body = toml::to_string(migrated)
+				.map_err(|e| ConfigError::SerializationError(format!("{}", e)))?;
+			format!(
+				"# Upgraded from config_version {} by grin-miner, the original (with its\n\
+				 # comments) is in {}\n\n{}",
+				version,
+				backup.display(),
+				body
+			)
+		};
+		File::create(&path)
+			.and_then(|mut f| f.write_all(new_contents.as_bytes()))
+			.map_err(&io_error)?;
+		self.migrations
+			.push(format!("previous config file backed up to {}", backup.display()));
+		Ok(())
+	}
+
+	/// Override config values, given as (key, value) with dotted keys
+	/// such as mining.stratum_server_addr or
+	/// mining.miner_plugin_config.0.device_parameters.0.USE_DEVICE.
+	/// Keys without a section are looked up in the mining section. Values
+	/// are parsed as TOML values, falling back to plain strings.
+	pub fn apply_overrides(&mut self, overrides: &[(String, String)]) -> Result<(), ConfigError> {
+		if overrides.is_empty() {
+			return Ok(());
+		}
+		let members = self.members.take().unwrap_or_default();
+		let mut root = toml::Value::try_from(&members)
+			.map_err(|e| ConfigError::SerializationError(format!("{}", e)))?;
+		for &(ref key, ref value) in overrides {
+			let mut path: Vec<&str> = key.split('.').collect();
+			let in_root = root.get(path[0]).is_some();
+			let in_mining = root
+				.get("mining")
+				.and_then(|m| m.get(path[0]))
+				.is_some();
+			if !in_root && (in_mining || path.len() == 1) {
+				path.insert(0, "mining");
+			}
+			set_value(&mut root, &path, parse_value(value))
+				.map_err(|e| ConfigError::OverrideError(key.clone(), e))?;
+		}
+		let members: ConfigMembers = root
+			.try_into()
+			.map_err(|e| ConfigError::OverrideError(String::from("config"), format!("{}", e)))?;
+		self.members = Some(members);
+		Ok(())
 	}
 
 	/// Serialize config
@@ -169,3 +368,218 @@ impl GlobalConfig {
 		}
 	}
 }
+
+/// Prefix of environment variables overriding config values
+pub const ENV_PREFIX: &'static str = "GRIN_MINER__";
+
+/// Config overrides from the environment, e.g.
+/// GRIN_MINER__MINING__STRATUM_SERVER_ADDR for mining.stratum_server_addr.
+/// Parts are lowercased, except plugin parameter names
+/// (GRIN_MINER__MINING__MINER_PLUGIN_CONFIG__0__DEVICE_PARAMETERS__0__USE_DEVICE).
+pub fn env_overrides() -> Vec<(String, String)> {
+	let mut overrides: Vec<(String, String)> = env::vars()
+		.filter_map(|(k, v)| env_key(&k).map(|key| (key, v)))
+		.collect();
+	// apply in a stable order, so array elements are created in order
+	overrides.sort();
+	overrides
+}
+
+/// Config key an environment variable overrides, none if it isn't one
+fn env_key(name: &str) -> Option<String> {
+	if !name.starts_with(ENV_PREFIX) || name.len() == ENV_PREFIX.len() {
+		return None;
+	}
+	let parts: Vec<&str> = name[ENV_PREFIX.len()..].split("__").collect();
+	let key: Vec<String> = parts
+		.iter()
+		.enumerate()
+		.map(|(i, p)| {
+			if i >= 2 && parts[i - 2].eq_ignore_ascii_case("device_parameters") {
+				p.to_string()
+			} else {
+				p.to_lowercase()
+			}
+		})
+		.collect();
+	Some(key.join("."))
+}
+
+/// Deep merge fragment over target: tables are merged key by key, any
+/// other value (arrays included) replaces what was there
+fn merge_value(target: &mut toml::Value, fragment: toml::Value, prefix: &str, keys: &mut Vec<String>) {
+	match (target, fragment) {
+		(&mut toml::Value::Table(ref mut t), toml::Value::Table(f)) => for (k, v) in f {
+			let key = if prefix.is_empty() {
+				k.clone()
+			} else {
+				format!("{}.{}", prefix, k)
+			};
+			match t.get_mut(&k) {
+				Some(existing) if existing.is_table() && v.is_table() => {
+					merge_value(existing, v, &key, keys);
+					continue;
+				}
+				_ => {}
+			}
+			keys.push(key);
+			t.insert(k, v);
+		},
+		(target, fragment) => {
+			keys.push(prefix.to_string());
+			*target = fragment;
+		}
+	}
+}
+
+/// Parse an override value as a TOML value, or take it as a string
+fn parse_value(value: &str) -> toml::Value {
+	match toml::from_str::<toml::Value>(&format!("v = {}", value)) {
+		Ok(mut v) => match v.as_table_mut().and_then(|t| t.remove("v")) {
+			Some(v) => v,
+			None => toml::Value::String(value.to_string()),
+		},
+		Err(_) => toml::Value::String(value.to_string()),
+	}
+}
+
+/// Set the value at the given path, creating missing tables (and array
+/// elements, one past the end) along the way
+fn set_value(target: &mut toml::Value, path: &[&str], value: toml::Value) -> Result<(), String> {
+	if path.is_empty() {
+		*target = value;
+		return Ok(());
+	}
+	let next = match *target {
+		toml::Value::Table(ref mut t) => t
+			.entry(path[0].to_string())
+			.or_insert(toml::Value::Table(toml::value::Table::new())),
+		toml::Value::Array(ref mut a) => {
+			let index = path[0]
+				.parse::<usize>()
+				.map_err(|_| format!("{} is not an array index", path[0]))?;
+			if index == a.len() {
+				a.push(toml::Value::Table(toml::value::Table::new()));
+			}
+			a.get_mut(index)
+				.ok_or(format!("index {} out of range", index))?
+		}
+		_ => return Err(format!("{} is not a table or array", path[0])),
+	};
+	set_value(next, &path[1..], value)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn overrides(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+		pairs
+			.iter()
+			.map(|&(k, v)| (k.to_string(), v.to_string()))
+			.collect()
+	}
+
+	#[test]
+	fn test_parse_value() {
+		assert_eq!(parse_value("3"), toml::Value::Integer(3));
+		assert_eq!(parse_value("1.5"), toml::Value::Float(1.5));
+		assert_eq!(parse_value("true"), toml::Value::Boolean(true));
+		assert_eq!(
+			parse_value("[1, 2]"),
+			toml::Value::Array(vec![toml::Value::Integer(1), toml::Value::Integer(2)])
+		);
+		assert_eq!(parse_value("\"3\""), toml::Value::String("3".to_string()));
+		// not TOML, taken as it is
+		assert_eq!(
+			parse_value("pool.example.com:3416"),
+			toml::Value::String("pool.example.com:3416".to_string())
+		);
+	}
+
+	#[test]
+	fn test_set_value() {
+		let mut root: toml::Value = toml::from_str("[mining]\nlist = [1]").unwrap();
+		set_value(&mut root, &["mining", "a", "b"], toml::Value::Integer(1)).unwrap();
+		assert_eq!(root["mining"]["a"]["b"].as_integer(), Some(1));
+		// one past the end of an array adds an element
+		set_value(&mut root, &["mining", "list", "1"], toml::Value::Integer(2)).unwrap();
+		assert_eq!(root["mining"]["list"][1].as_integer(), Some(2));
+		assert!(set_value(&mut root, &["mining", "list", "5"], toml::Value::Integer(3)).is_err());
+		assert!(set_value(&mut root, &["mining", "list", "x"], toml::Value::Integer(3)).is_err());
+		assert!(set_value(&mut root, &["mining", "a", "b", "c"], toml::Value::Integer(3)).is_err());
+	}
+
+	#[test]
+	fn test_apply_overrides() {
+		let mut config = GlobalConfig::default();
+		config
+			.apply_overrides(&overrides(&[
+				("mining.stratum_server_addr", "pool.example.com:3416"),
+				// no section, the mining one
+				("run_tui", "true"),
+				("mining.miner_plugin_config.0.type_filter", "lean_cpu"),
+				("mining.miner_plugin_config.0.edge_bits", "29"),
+				("mining.miner_plugin_config.0.device_parameters.0.NUM_THREADS", "4"),
+			]))
+			.unwrap();
+		let mining = config.members.unwrap().mining;
+		assert_eq!(mining.stratum_server_addr, "pool.example.com:3416");
+		assert!(mining.run_tui);
+		assert_eq!(mining.miner_plugin_config[0].type_filter, "lean_cpu");
+		assert_eq!(mining.miner_plugin_config[0].edge_bits, 29);
+		let parameters = mining.miner_plugin_config[0].device_parameters.clone().unwrap();
+		assert_eq!(parameters["0"]["NUM_THREADS"], 4);
+	}
+
+	#[test]
+	fn test_env_key() {
+		assert_eq!(
+			env_key("GRIN_MINER__MINING__STRATUM_SERVER_ADDR"),
+			Some("mining.stratum_server_addr".to_string())
+		);
+		assert_eq!(env_key("GRIN_MINER__RUN_TUI"), Some("run_tui".to_string()));
+		// plugin parameter names keep their case
+		assert_eq!(
+			env_key("GRIN_MINER__MINING__MINER_PLUGIN_CONFIG__0__DEVICE_PARAMETERS__0__USE_DEVICE"),
+			Some("mining.miner_plugin_config.0.device_parameters.0.USE_DEVICE".to_string())
+		);
+		assert_eq!(env_key("GRIN_MINER__"), None);
+		assert_eq!(env_key("GRIN_MINING__RUN_TUI"), None);
+		assert_eq!(env_key("PATH"), None);
+	}
+
+	#[test]
+	fn test_env_overrides() {
+		env::set_var("GRIN_MINER__MINING__STRATUM_SERVER_LOGIN", "rig1");
+		let overrides = env_overrides();
+		env::remove_var("GRIN_MINER__MINING__STRATUM_SERVER_LOGIN");
+		let login = ("mining.stratum_server_login".to_string(), "rig1".to_string());
+		assert!(overrides.contains(&login));
+		let mut config = GlobalConfig::default();
+		config.apply_overrides(&[login]).unwrap();
+		assert_eq!(
+			config.members.unwrap().mining.stratum_server_login,
+			Some("rig1".to_string())
+		);
+	}
+
+	#[test]
+	fn test_bad_overrides() {
+		let bad = [
+			// below a value
+			("mining.run_tui.x", "1"),
+			// not an index
+			("mining.miner_plugin_config.x.edge_bits", "29"),
+			// of the wrong type
+			("mining.run_tui", "maybe"),
+		];
+		for &(key, value) in bad.iter() {
+			let mut config = GlobalConfig::default();
+			match config.apply_overrides(&overrides(&[(key, value)])) {
+				Err(ConfigError::OverrideError(..)) => {}
+				r => panic!("expected {} = {} to fail, got {:?}", key, value, r),
+			}
+		}
+	}
+}
@@ -27,7 +27,20 @@ extern crate toml;
 
 extern crate grin_miner_util as util;
 
+pub mod chain;
 pub mod config;
+pub mod migrate;
 pub mod types;
 
-pub use types::{ConfigError, ConfigMembers, GlobalConfig, MinerConfig};
+pub use chain::ChainType;
+pub use config::{env_overrides, DEFAULT_CONFIG};
+pub use migrate::CONFIG_VERSION;
+
+pub use types::{AlertConfig, ApiConfig, ConfigError, ConfigMembers, ControlSocketConfig,
+                DesktopConfig, FailoverPool, FailurePolicyConfig, FailureRule, FleetConfig,
+                FleetRigConfig, GlobalConfig, GrpcConfig, HardwareMonitorConfig,
+                HistoryStoreConfig, MinerConfig, MqttConfig, NotificationConfig, RecoveryConfig,
+                RejectBreakerConfig, RigConfig, ScheduleWindow, SchedulerConfig,
+                SharedStatsConfig, SoloConfig, StatsLogConfig, SubmitLimitConfig,
+                TelemetryConfig, ThermalConfig, ThermalLimits, UpdateConfig, UsageStatsConfig,
+                WatchdogConfig, WebhookConfig};
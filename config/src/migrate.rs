@@ -0,0 +1,272 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Config file versioning, upgrading files written for older releases
+//! and finding keys that aren't (or are no longer) understood
+
+use toml::value::Table;
+use toml::Value;
+
+/// Version of the config file layout understood by this release. Files
+/// without a config_version are taken as version 1.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Mining keys renamed in version 2, as (old, new)
+const RENAMED_V2: &[(&str, &str)] = &[
+	("cuckoo_miner_plugin_dir", "miner_plugin_dir"),
+	("cuckoo_miner_plugin_config", "miner_plugin_config"),
+];
+
+/// Mining keys no longer used as of version 2
+const OBSOLETE_V2: &[&str] = &[
+	"enable_mining",
+	"use_cuckoo_miner",
+	"miner_async_mode",
+	"slow_down_in_millis",
+	"burn_reward",
+	"wallet_listener_url",
+	"attempt_time_per_block",
+];
+
+/// Version 1 to 2: plugin keys lost their cuckoo_miner_ prefix, and the
+/// settings of the old built in miner were dropped
+fn migrate_v1(root: &mut Table, notes: &mut Vec<String>) {
+	let mining = match root.get_mut("mining").and_then(|m| m.as_table_mut()) {
+		Some(m) => m,
+		None => return,
+	};
+	for &(old, new) in RENAMED_V2 {
+		if let Some(v) = mining.remove(old) {
+			notes.push(format!("renamed mining.{} to mining.{}", old, new));
+			mining.entry(new.to_string()).or_insert(v);
+		}
+	}
+	for &key in OBSOLETE_V2 {
+		if mining.remove(key).is_some() {
+			notes.push(format!("removed obsolete mining.{}", key));
+		}
+	}
+}
+
+/// Migrations, the first upgrading version 1 files
+const MIGRATIONS: &[fn(&mut Table, &mut Vec<String>)] = &[migrate_v1];
+
+/// Version of a parsed config file
+pub fn file_version(root: &Value) -> Result<u32, String> {
+	match root.get("config_version") {
+		None => Ok(1),
+		Some(v) => match v.as_integer() {
+			Some(n) if n >= 1 => Ok(n as u32),
+			_ => Err(format!("config_version must be a positive number, got {}", v)),
+		},
+	}
+}
+
+/// Upgrade a parsed config file to the current version, returning what
+/// was changed (nothing if it's already current)
+pub fn migrate(root: &mut Value) -> Result<Vec<String>, String> {
+	let version = file_version(root)?;
+	if version > CONFIG_VERSION {
+		return Err(format!(
+			"config_version {} was written by a newer release, this one understands up to {}",
+			version, CONFIG_VERSION
+		));
+	}
+	let mut notes = vec![];
+	if version == CONFIG_VERSION {
+		return Ok(notes);
+	}
+	let table = root
+		.as_table_mut()
+		.ok_or("config file is not a table".to_string())?;
+	for (i, m) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+		m(table, &mut notes);
+		notes.push(format!("upgraded from config_version {} to {}", i + 1, i + 2));
+	}
+	table.insert(
+		"config_version".to_string(),
+		Value::Integer(CONFIG_VERSION as i64),
+	);
+	Ok(notes)
+}
+
+/// Keys in the file that didn't make it into the parsed config, i.e. that
+/// aren't understood, marking the ones known to be obsolete
+pub fn unknown_keys(file: &Value, parsed: &Value) -> Vec<String> {
+	let mut keys = vec![];
+	collect_unknown("", file, parsed, &mut keys);
+	keys
+}
+
+fn collect_unknown(prefix: &str, file: &Value, parsed: &Value, keys: &mut Vec<String>) {
+	match (file, parsed) {
+		(&Value::Table(ref f), &Value::Table(ref p)) => for (k, v) in f {
+			let key = if prefix.is_empty() {
+				k.clone()
+			} else {
+				format!("{}.{}", prefix, k)
+			};
+			match p.get(k) {
+				Some(pv) => collect_unknown(&key, v, pv, keys),
+				None if prefix == "mining" && OBSOLETE_V2.contains(&k.as_str()) => {
+					keys.push(format!("{} (obsolete)", key))
+				}
+				None => keys.push(key),
+			}
+		},
+		(&Value::Array(ref f), &Value::Array(ref p)) => for (i, (fv, pv)) in f.iter().zip(p.iter()).enumerate() {
+			collect_unknown(&format!("{}.{}", prefix, i), fv, pv, keys);
+		},
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs::{self, File};
+	use std::io::{Read, Write};
+	use std::path::PathBuf;
+	use std::process;
+
+	use toml;
+
+	use types::{ConfigError, GlobalConfig};
+
+	const V1_FILE: &str = "[mining]
+run_tui = false
+stratum_server_addr = \"127.0.0.1:3416\"
+enable_mining = true
+cuckoo_miner_plugin_dir = \"plugins\"
+
+[[mining.cuckoo_miner_plugin_config]]
+type_filter = \"lean_cpu\"
+edge_bits = 29
+";
+
+	const V2_FILE: &str = "# mining on the CPU
+config_version = 2
+
+[mining]
+run_tui = false
+stratum_server_addr = \"127.0.0.1:3416\"
+miner_plugin_dir = \"plugins\"
+
+[[mining.miner_plugin_config]]
+type_filter = \"lean_cpu\"
+edge_bits = 29
+";
+
+	/// A config file of the test in the temp directory
+	fn write_file(name: &str, contents: &str) -> PathBuf {
+		let name = format!("grin-miner-migrate-{}-{}.toml", process::id(), name);
+		let path = env::temp_dir().join(name);
+		File::create(&path)
+			.and_then(|mut f| f.write_all(contents.as_bytes()))
+			.unwrap();
+		path
+	}
+
+	fn read_file(path: &PathBuf) -> String {
+		let mut contents = String::new();
+		File::open(path)
+			.and_then(|mut f| f.read_to_string(&mut contents))
+			.unwrap();
+		contents
+	}
+
+	fn load(path: &PathBuf) -> Result<GlobalConfig, ConfigError> {
+		GlobalConfig::new(Some(path.to_str().unwrap()))
+	}
+
+	#[test]
+	fn test_migrate_v1() {
+		let mut root: Value = toml::from_str(V1_FILE).unwrap();
+		assert_eq!(file_version(&root).unwrap(), 1);
+		let notes = migrate(&mut root).unwrap();
+		let noted = |note: &str| notes.iter().any(|n| n == note);
+		assert!(noted("renamed mining.cuckoo_miner_plugin_dir to mining.miner_plugin_dir"));
+		assert!(noted("removed obsolete mining.enable_mining"));
+		assert!(noted("upgraded from config_version 1 to 2"));
+		assert_eq!(file_version(&root).unwrap(), CONFIG_VERSION);
+		let mining = &root["mining"];
+		assert_eq!(mining["miner_plugin_dir"].as_str(), Some("plugins"));
+		assert!(mining.get("miner_plugin_config").is_some());
+		assert!(mining.get("cuckoo_miner_plugin_config").is_none());
+		assert!(mining.get("enable_mining").is_none());
+	}
+
+	#[test]
+	fn test_newer_version_refused() {
+		let mut root: Value = toml::from_str("config_version = 99").unwrap();
+		assert!(migrate(&mut root).is_err());
+	}
+
+	#[test]
+	fn test_unknown_key_rejected() {
+		let path = write_file("unknown", &format!("{}no_such_key = 1\n", V2_FILE));
+		let result = load(&path);
+		let _ = fs::remove_file(&path);
+		match result {
+			Err(ConfigError::UnknownKeysError(_, keys)) => {
+				assert_eq!(keys, vec!["mining.miner_plugin_config.0.no_such_key".to_string()])
+			}
+			r => panic!("expected unknown keys, got {:?}", r.map(|_| ())),
+		}
+
+		// obsolete keys left in a current file are marked as such
+		let file: Value = toml::from_str("[mining]\nburn_reward = true").unwrap();
+		let parsed: Value = toml::from_str("[mining]").unwrap();
+		assert_eq!(
+			unknown_keys(&file, &parsed),
+			vec!["mining.burn_reward (obsolete)".to_string()]
+		);
+	}
+
+	#[test]
+	fn test_current_file_untouched() {
+		let path = write_file("current", V2_FILE);
+		let config = load(&path).unwrap();
+		let contents = read_file(&path);
+		let backup = PathBuf::from(format!("{}.v2.bak", path.display()));
+		let _ = fs::remove_file(&path);
+		assert!(config.migrations.is_empty());
+		assert_eq!(contents, V2_FILE);
+		assert!(!backup.exists());
+	}
+
+	#[test]
+	fn test_backup_written() {
+		let path = write_file("v1", V1_FILE);
+		let config = load(&path).unwrap();
+		let backup = PathBuf::from(format!("{}.v1.bak", path.display()));
+		let backed_up = read_file(&backup);
+		let upgraded = read_file(&path);
+		let _ = fs::remove_file(&path);
+		let _ = fs::remove_file(&backup);
+		assert_eq!(backed_up, V1_FILE);
+		let root: Value = toml::from_str(&upgraded).unwrap();
+		assert_eq!(file_version(&root).unwrap(), CONFIG_VERSION);
+		assert!(root["mining"].get("enable_mining").is_none());
+		assert_eq!(
+			config.members.unwrap().mining.miner_plugin_dir,
+			Some("plugins".to_string())
+		);
+		assert!(config
+			.migrations
+			.iter()
+			.any(|m| m.contains("backed up to") && m.contains(".v1.bak")));
+	}
+}
@@ -16,7 +16,7 @@
 
 use std::path::PathBuf;
 use std::{io, fmt};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use util;
 
@@ -29,15 +29,310 @@ pub struct CuckooMinerPluginConfig {
 	/// Cuckoo size (edge bits) for the plugin
 	pub edge_bits: u8,
 
+	/// Devices to mine on: "all", or ids and ranges such as "0-3,5", or
+	/// PCI bus ids such as "pci:0000:03:00.0"
+	pub devices: Option<String>,
+
+	/// Devices not to mine on, e.g. one driving a display
+	pub exclude_devices: Option<Vec<u32>>,
+
+	/// OpenCL platform of OpenCL plugins: amd, nvidia, or the PLATFORM
+	/// parameter value
+	pub platform: Option<String>,
+
+	/// Solver instances to run on each device, so one's transfers and
+	/// kernels overlap with another's. Needs sandbox_plugins.
+	pub instances: Option<u32>,
+
+	/// Intensity preset, low, medium or high, filling in the parameters
+	/// device_parameters don't set
+	pub intensity: Option<String>,
+
+	/// SHA-256 checksum (hex) the plugin file must have to be loaded
+	pub sha256: Option<String>,
+
+	/// CPUs the plugin's host threads are pinned to, e.g. "0-7,16-23", in
+	/// place of those of its GPUs' NUMA node
+	pub cpu_affinity: Option<String>,
+
+	/// Whether the devices mine from startup (default true). When false,
+	/// they're loaded and checked but kept in standby until activated.
+	pub enabled: Option<bool>,
+
+	/// External solver to run in place of a plugin library: the executable
+	/// and its arguments, spoken with over the protocol in
+	/// doc/external_solver.md. type_filter then only names the algorithm,
+	/// e.g. cuckatoo_external.
+	pub command: Option<Vec<String>>,
+
 	/// device params
 	pub device_parameters: Option<HashMap<String, HashMap<String, u32>>>,
 }
 
+/// Prefix of devices addressed by PCI bus id, e.g. pci:0000:03:00.0
+pub const PCI_PREFIX: &str = "pci:";
+
+/// Domain, bus, device and function of a PCI bus id such as 0000:03:00.0
+/// (the domain is optional, and may be 8 digits as NVML gives it)
+pub fn pci_address(bus_id: &str) -> Option<(u32, u32, u32, u32)> {
+	let bus_id = bus_id.trim();
+	let (rest, function) = match bus_id.rfind('.') {
+		Some(i) => (&bus_id[..i], u32::from_str_radix(&bus_id[i + 1..], 16).ok()?),
+		None => return None,
+	};
+	let parts: Vec<&str> = rest.split(':').collect();
+	let (domain, bus, device) = match parts.len() {
+		2 => ("0", parts[0], parts[1]),
+		3 => (parts[0], parts[1], parts[2]),
+		_ => return None,
+	};
+	Some((
+		u32::from_str_radix(domain, 16).ok()?,
+		u32::from_str_radix(bus, 16).ok()?,
+		u32::from_str_radix(device, 16).ok()?,
+		function,
+	))
+}
+
+impl CuckooMinerPluginConfig {
+	/// Whether devices are selected, and the devices the plugin can see
+	/// are needed to work out which
+	pub fn needs_device_list(&self) -> bool {
+		if self.uses_pci_ids() {
+			return true;
+		}
+		match self.devices {
+			Some(ref d) => d.trim().eq_ignore_ascii_case("all"),
+			None => self.exclude_devices.is_some(),
+		}
+	}
+
+	/// Whether devices or device_parameters address devices by PCI bus id
+	pub fn uses_pci_ids(&self) -> bool {
+		self.devices.as_ref().map_or(false, |d| d.contains(PCI_PREFIX))
+			|| self
+				.device_parameters
+				.as_ref()
+				.map_or(false, |p| p.keys().any(|k| k.starts_with(PCI_PREFIX)))
+	}
+
+	/// Replace devices addressed by PCI bus id in devices and
+	/// device_parameters with their device ids, given the bus id of each
+	/// device id
+	pub fn resolve_pci_ids(&mut self, bus_ids: &[(u32, String)]) -> Result<(), String> {
+		let type_filter = self.type_filter.clone();
+		let resolve = |device: &str| -> Result<String, String> {
+			if !device.starts_with(PCI_PREFIX) {
+				return Ok(device.to_string());
+			}
+			let bus_id = &device[PCI_PREFIX.len()..];
+			let wanted = pci_address(bus_id).ok_or(format!("invalid PCI bus id {}", bus_id))?;
+			bus_ids
+				.iter()
+				.find(|&&(_, ref b)| pci_address(b) == Some(wanted))
+				.map(|&(id, _)| id.to_string())
+				.ok_or_else(|| {
+					let found: Vec<&str> = bus_ids.iter().map(|&(_, ref b)| b.as_str()).collect();
+					format!(
+						"no device at PCI bus id {} for plugin {}, its devices are at: {}",
+						bus_id,
+						type_filter,
+						if found.is_empty() {
+							"unknown".to_string()
+						} else {
+							found.join(", ")
+						}
+					)
+				})
+		};
+		if let Some(ref d) = self.devices {
+			let resolved = d
+				.split(',')
+				.map(|p| resolve(p.trim()))
+				.collect::<Result<Vec<String>, String>>()?;
+			self.devices = Some(resolved.join(","));
+		}
+		if let Some(params) = self.device_parameters.take() {
+			let mut resolved = HashMap::new();
+			for (device, p) in params {
+				resolved.insert(resolve(&device)?, p);
+			}
+			self.device_parameters = Some(resolved);
+		}
+		Ok(())
+	}
+
+	/// Devices selected with devices and exclude_devices, given the
+	/// devices the plugin can see (needed for "all"), or None if neither
+	/// is set
+	pub fn selected_devices(&self, available: Option<&[u32]>) -> Result<Option<Vec<u32>>, String> {
+		if self.devices.is_none() && self.exclude_devices.is_none() {
+			return Ok(None);
+		}
+		let all = || {
+			available
+				.map(|a| a.to_vec())
+				.ok_or("the devices the plugin can see are unknown".to_string())
+		};
+		let mut selected = match self.devices {
+			None => all()?,
+			Some(ref d) if d.trim().eq_ignore_ascii_case("all") => all()?,
+			Some(ref d) => {
+				let mut ids = vec![];
+				for part in d.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+					let invalid = || format!("invalid device selection {}", part);
+					match part.find('-') {
+						Some(i) => {
+							let from = part[..i].trim().parse::<u32>().map_err(|_| invalid())?;
+							let to = part[i + 1..].trim().parse::<u32>().map_err(|_| invalid())?;
+							if from > to {
+								return Err(invalid());
+							}
+							ids.extend(from..to + 1);
+						}
+						None => ids.push(part.parse::<u32>().map_err(|_| invalid())?),
+					}
+				}
+				ids
+			}
+		};
+		if let Some(ref excluded) = self.exclude_devices {
+			selected.retain(|d| !excluded.contains(d));
+		}
+		selected.sort();
+		selected.dedup();
+		Ok(Some(selected))
+	}
+
+	/// Make device_parameters mine on just the given devices: selected
+	/// devices get an entry, and with use_device (plugins that have a
+	/// USE_DEVICE parameter) the others are switched off rather than
+	/// dropped
+	pub fn apply_device_selection(&mut self, selected: &[u32], others: &[u32], use_device: bool) {
+		let mut params = self.device_parameters.take().unwrap_or_default();
+		let unselected: Vec<String> = params
+			.keys()
+			.filter(|k| !selected.iter().any(|s| s.to_string() == **k))
+			.cloned()
+			.chain(
+				others
+					.iter()
+					.filter(|o| !selected.contains(o))
+					.map(|o| o.to_string()),
+			)
+			.collect();
+		for device in unselected {
+			if use_device {
+				params
+					.entry(device)
+					.or_insert_with(HashMap::new)
+					.insert("USE_DEVICE".to_string(), 0);
+			} else {
+				params.remove(&device);
+			}
+		}
+		for device in selected {
+			let entry = params.entry(device.to_string()).or_insert_with(HashMap::new);
+			if use_device {
+				entry.insert("USE_DEVICE".to_string(), 1);
+			}
+		}
+		self.device_parameters = Some(params);
+		self.devices = None;
+		self.exclude_devices = None;
+	}
+
+	/// Parameters of each device, with the given defaults filled in where
+	/// the device doesn't set them (and the plugin has them, according to
+	/// has_param). Without any devices, the defaults go to device 0.
+	pub fn device_parameters_with_defaults<F>(
+		&self,
+		defaults: Option<&HashMap<String, u32>>,
+		has_param: F,
+	) -> HashMap<String, HashMap<String, u32>>
+	where
+		F: Fn(&str) -> bool,
+	{
+		let mut devices = self.device_parameters.clone().unwrap_or_default();
+		let mut defaults = defaults.cloned().unwrap_or_default();
+		if let Ok(Some(platform)) = self.platform_id() {
+			defaults.insert("PLATFORM".to_string(), platform);
+		}
+		if defaults.is_empty() {
+			return devices;
+		}
+		if devices.is_empty() {
+			devices.insert("0".to_string(), HashMap::new());
+		}
+		for params in devices.values_mut() {
+			for (name, value) in &defaults {
+				if has_param(name) && !params.contains_key(name) {
+					params.insert(name.clone(), *value);
+				}
+			}
+		}
+		devices
+	}
+
+	/// Algorithm the plugin mines, e.g. cuckaroo29 for cuckaroo_cuda at 29
+	/// edge bits
+	pub fn algorithm(&self) -> String {
+		format!(
+			"{}{}",
+			self.type_filter.split('_').next().unwrap_or(""),
+			self.edge_bits
+		)
+	}
+
+	/// Whether this is an OpenCL plugin
+	pub fn is_opencl(&self) -> bool {
+		self.type_filter.contains("ocl")
+	}
+
+	/// PLATFORM parameter value of the configured platform, if any
+	pub fn platform_id(&self) -> Result<Option<u32>, String> {
+		let platform = match self.platform {
+			Some(ref p) => p.trim().to_lowercase(),
+			None => return Ok(None),
+		};
+		match platform.as_str() {
+			"default" => Ok(Some(0)),
+			"amd" => Ok(Some(1)),
+			"nvidia" => Ok(Some(2)),
+			p => p.parse::<u32>().map(Some).map_err(|_| {
+				format!(
+					"Invalid platform {} for plugin {}, expected amd, nvidia or a number",
+					p, self.type_filter
+				)
+			}),
+		}
+	}
+
+	/// Whether this is an external solver rather than a plugin library
+	pub fn is_external(&self) -> bool {
+		self.command.is_some()
+	}
+
+	/// Whether the plugin mines on AMD GPUs
+	pub fn is_amd(&self) -> bool {
+		self.is_opencl() && self.platform_id() == Ok(Some(1))
+	}
+}
+
 impl Default for CuckooMinerPluginConfig {
 	fn default() -> CuckooMinerPluginConfig {
 		CuckooMinerPluginConfig {
 			type_filter: String::new(),
 			edge_bits: 30,
+			devices: None,
+			exclude_devices: None,
+			platform: None,
+			instances: None,
+			intensity: None,
+			sha256: None,
+			cpu_affinity: None,
+			enabled: None,
+			command: None,
 			device_parameters: None,
 		}
 	}
@@ -57,6 +352,15 @@ pub enum ConfigError {
 
 	/// Error serializing config values
 	SerializationError(String),
+
+	/// Error applying an override (key, message)
+	OverrideError(String, String),
+
+	/// Error upgrading an older config file (file, message)
+	MigrationError(String, String),
+
+	/// Keys in the config file that aren't understood (file, keys)
+	UnknownKeysError(String, Vec<String>),
 }
 
 impl fmt::Display for ConfigError {
@@ -76,6 +380,20 @@ impl fmt::Display for ConfigError {
 			ConfigError::SerializationError(ref message) => {
 				write!(f, "Error serializing configuration: {}", message)
 			}
+			ConfigError::OverrideError(ref key, ref message) => {
+				write!(f, "Error overriding configuration value {} - {}", key, message)
+			}
+			ConfigError::MigrationError(ref file_name, ref message) => write!(
+				f,
+				"Error upgrading configuration file at {} - {}",
+				file_name, message
+			),
+			ConfigError::UnknownKeysError(ref file_name, ref keys) => write!(
+				f,
+				"Unknown or obsolete keys in configuration file at {} - {}",
+				file_name,
+				keys.join(", ")
+			),
 		}
 	}
 }
@@ -95,6 +413,147 @@ pub struct MinerConfig {
 	/// Whether to run the tui
 	pub run_tui: bool,
 
+	/// TUI color theme: dark (default), light or monochrome
+	pub tui_theme: Option<String>,
+
+	/// Device temperature (celsius) at or above which the TUI highlights it
+	pub tui_temp_warning: Option<u32>,
+
+	/// Device fan speed (percent) at or above which the TUI highlights it
+	pub tui_fan_warning: Option<u32>,
+
+	/// Device power draw (watts) at or above which the TUI highlights it
+	pub tui_power_warning: Option<f64>,
+
+	/// Milliseconds between TUI refreshes
+	pub tui_refresh_interval: Option<u64>,
+
+	/// Group the TUI device table by plugin, with an aggregate row per plugin
+	pub tui_group_by_plugin: Option<bool>,
+
+	/// File to write the session summary to when quitting the TUI
+	pub session_summary_file: Option<String>,
+
+	/// Seconds between status blocks of the console dashboard, used
+	/// instead of the TUI when stdout is not a terminal
+	pub dashboard_interval: Option<u64>,
+
+	/// Language of the TUI, dashboard and console messages, e.g. de for
+	/// locales/de.toml, or auto for the environment's (default English)
+	pub language: Option<String>,
+
+	/// Directory to look for locale files in first
+	pub locale_dir: Option<String>,
+
+	/// Run the console dashboard when the terminal can't run the TUI
+	/// (default true), rather than trying it anyway
+	pub tui_fallback: Option<bool>,
+
+	/// Status output instead of the TUI: json prints a status line as a
+	/// JSON object to stdout periodically, for scripts wrapping the miner
+	pub output_format: Option<String>,
+
+	/// Seconds between JSON status lines
+	pub output_interval: Option<u64>,
+
+	/// Separator grouping the thousands of numbers shown, e.g. "," or " ",
+	/// none by default
+	pub thousands_separator: Option<String>,
+
+	/// Clock times are shown with, 24h (default) or 12h
+	pub clock_format: Option<String>,
+
+	/// Whether to show graph rates with SI prefixes, e.g. 12.3 kGPS
+	pub si_prefixes: Option<bool>,
+
+	/// Electricity price per kWh, to estimate the daily power cost
+	pub electricity_price: Option<f64>,
+
+	/// Whether to watch the config file, and reload it when it changes
+	/// (it's always reloaded on SIGHUP)
+	pub watch_config_file: Option<bool>,
+
+	/// Device profile to mine with, selecting one of profiles in place
+	/// of miner_plugin_config
+	pub profile: Option<String>,
+
+	/// File the session report is written to on SIGUSR1, as JSON if it
+	/// ends in .json, Markdown otherwise
+	pub report_file: Option<String>,
+
+	/// Chain mined on: mainnet (default), testnet or custom
+	pub chain_type: Option<String>,
+
+	/// Proof of work rules of the chain mined: grin (default) or mwc
+	pub pow_chain: Option<String>,
+
+	/// Choose plugins and their parameters for the detected hardware, in
+	/// place of miner_plugin_config
+	pub auto: Option<bool>,
+
+	/// Run each solver in a child process, so a crashing plugin only
+	/// loses its own devices
+	pub sandbox_plugins: Option<bool>,
+
+	/// File the tune subcommand caches the best parameters it finds in,
+	/// used from then on. An empty path switches the cache off.
+	pub tuning_cache: Option<String>,
+
+	/// Pin the solver threads to the CPUs of the NUMA node their GPUs are
+	/// on (default true)
+	pub numa_pinning: Option<bool>,
+
+	/// File the miner's PID is written to, always with --daemon, and the
+	/// stop and status subcommands read
+	pub pid_file: Option<String>,
+
+	/// Directory crash reports are written to
+	pub crash_dir: Option<String>,
+
+	/// Fully restart mining (unload the plugins and reconnect to the pool)
+	/// after this many hours, for drivers and plugins that degrade with
+	/// uptime
+	pub restart_after_hours: Option<u64>,
+
+	/// Seconds to wait after startup before mining, e.g. for the GPUs'
+	/// power to settle after boot
+	pub startup_delay_secs: Option<u64>,
+
+	/// Seconds between starting each device, so they don't all draw full
+	/// power at once (default 0, all together)
+	pub startup_stagger_secs: Option<u64>,
+
+	/// Threads working out the difficulty of the solutions found, off the
+	/// mining loop (default 1, 0 to do it on the mining loop)
+	pub difficulty_threads: Option<usize>,
+
+	/// Solutions found the miner holds for the stratum client, for when it
+	/// stalls (default 256)
+	pub solution_buffer_size: Option<usize>,
+
+	/// Which solution goes when the solution buffer is full: drop-oldest
+	/// (default) or drop-lowest-difficulty
+	pub solution_overflow_policy: Option<String>,
+
+	/// Minutes between reports of each device's part of the shares against
+	/// its part of the GPS in the log (default 60, 0 to disable)
+	pub fairness_report_minutes: Option<u64>,
+
+	/// Detect the system resuming from suspend, and restart mining in full
+	/// afterwards (default true)
+	pub suspend_detection: Option<bool>,
+
+	/// Milliseconds between polls of the solvers for solutions and
+	/// messages (default 100, 0 not to wait, for fast test plugins)
+	pub poll_interval_ms: Option<u64>,
+
+	/// Milliseconds between polls while paused or without a job (default
+	/// 500)
+	pub idle_poll_interval_ms: Option<u64>,
+
+	/// Seconds between updates of the device stats (default 2)
+	pub stats_interval_secs: Option<u64>,
+
 	/// mining loop by adding a sleep to the thread
 	pub stratum_server_addr: String,
 
@@ -104,22 +563,997 @@ pub struct MinerConfig {
 	/// password for the stratum server
 	pub stratum_server_password: Option<String>,
 
+	/// Worker token submits are signed with, for pools supporting it
+	pub stratum_submit_token: Option<String>,
+
+	/// Poll the pool for the next job from shortly before the next block is
+	/// due, for high latency pools (default false)
+	pub job_prefetch: Option<bool>,
+
+	/// Stop the solves in flight as soon as a new job arrives, rather than
+	/// letting them finish on the old job first (default true)
+	pub abort_on_new_job: Option<bool>,
+
+	/// Add the correlation id of each share to its submit, for pools
+	/// showing it on their dashboard (default false)
+	pub submit_correlation_id: Option<bool>,
+
+	/// Share difficulty to ask the pool for at login, as the least it
+	/// should set, for rigs too slow for its default
+	pub minimum_share_difficulty: Option<u64>,
+
+	/// File the blocks found solo mining are recorded in. An empty path
+	/// switches the record off.
+	pub found_blocks_file: Option<String>,
+
+	/// File every share submitted and the pool's answer is recorded in,
+	/// for reconciling against the pool. None (the default) switches the
+	/// ledger off.
+	pub share_ledger_file: Option<String>,
+
+	/// Key the share ledger is signed with (HMAC-SHA256), a plain SHA-256
+	/// chain if not set
+	pub share_ledger_key: Option<String>,
+
 	/// plugin dir
 	pub miner_plugin_dir: Option<String>,
 
+	/// Further directories to search for plugins, after miner_plugin_dir
+	pub miner_plugin_dirs: Option<Vec<String>>,
+
+	/// Keep the plugins their plugins.toml manifest says can't run on the
+	/// GPUs and drivers found in standby, rather than only warning (default
+	/// true)
+	pub skip_incompatible_plugins: Option<bool>,
+
+	/// Parameters inherited by every device of every plugin that has
+	/// them, unless set for the device
+	pub defaults: Option<HashMap<String, u32>>,
+
 	/// Cuckoo miner plugin configuration, one for each plugin
 	pub miner_plugin_config: Vec<CuckooMinerPluginConfig>,
+
+	/// Named plugin configurations, which can be switched between
+	pub profiles: Option<HashMap<String, Vec<CuckooMinerPluginConfig>>>,
 }
 
 impl Default for MinerConfig {
 	fn default() -> MinerConfig {
 		MinerConfig {
 			run_tui: false,
+			tui_theme: None,
+			tui_temp_warning: None,
+			tui_fan_warning: None,
+			tui_power_warning: None,
+			tui_refresh_interval: None,
+			tui_group_by_plugin: None,
+			session_summary_file: None,
+			dashboard_interval: None,
+			tui_fallback: None,
+			language: None,
+			locale_dir: None,
+			output_format: None,
+			output_interval: None,
+			thousands_separator: None,
+			clock_format: None,
+			si_prefixes: None,
+			electricity_price: None,
+			report_file: None,
+			watch_config_file: None,
+			profile: None,
+			profiles: None,
+			chain_type: None,
+			pow_chain: None,
+			auto: None,
+			sandbox_plugins: None,
+			tuning_cache: None,
+			numa_pinning: None,
+			pid_file: None,
+			crash_dir: None,
+			restart_after_hours: None,
+			startup_delay_secs: None,
+			startup_stagger_secs: None,
+			difficulty_threads: None,
+			solution_buffer_size: None,
+			solution_overflow_policy: None,
+			fairness_report_minutes: None,
+			suspend_detection: None,
+			poll_interval_ms: None,
+			idle_poll_interval_ms: None,
+			stats_interval_secs: None,
 			miner_plugin_dir: None,
+			miner_plugin_dirs: None,
+			skip_incompatible_plugins: None,
+			defaults: None,
 			miner_plugin_config: vec![],
 			stratum_server_addr: String::from("http://127.0.0.1:13416"),
 			stratum_server_login: None,
 			stratum_server_password: None,
+			stratum_submit_token: None,
+			job_prefetch: None,
+			abort_on_new_job: None,
+			submit_correlation_id: None,
+			minimum_share_difficulty: None,
+			found_blocks_file: None,
+			share_ledger_file: None,
+			share_ledger_key: None,
+		}
+	}
+}
+
+impl MinerConfig {
+	/// Whether solvers run in worker processes, as they do when asked to
+	/// and always for external solvers
+	pub fn sandboxed(&self) -> bool {
+		self.sandbox_plugins.unwrap_or(false)
+			|| self.miner_plugin_config.iter().any(|p| p.is_external())
+	}
+
+	/// Names of the configured profiles, sorted
+	pub fn profile_names(&self) -> Vec<String> {
+		let mut names: Vec<String> = match self.profiles {
+			Some(ref p) => p.keys().cloned().collect(),
+			None => vec![],
+		};
+		names.sort();
+		names
+	}
+
+	/// Switch to the named profile, replacing the plugin configuration
+	pub fn apply_profile(&mut self, name: &str) -> Result<(), String> {
+		let plugins = match self.profiles.as_ref().and_then(|p| p.get(name)) {
+			Some(p) => p.clone(),
+			None => {
+				return Err(format!(
+					"Unknown profile {}, configured profiles: {}",
+					name,
+					self.profile_names().join(", ")
+				))
+			}
+		};
+		self.miner_plugin_config = plugins;
+		self.profile = Some(name.to_string());
+		Ok(())
+	}
+
+	/// Apply the profile selected in the config, if any
+	pub fn apply_selected_profile(&mut self) -> Result<(), String> {
+		match self.profile.clone() {
+			Some(name) => self.apply_profile(&name),
+			None => Ok(()),
+		}
+	}
+
+	/// Algorithms the configured plugins mine, in plugin order
+	pub fn algorithms(&self) -> Vec<String> {
+		let mut algorithms: Vec<String> = vec![];
+		for a in self.miner_plugin_config.iter().map(|p| p.algorithm()) {
+			if !algorithms.contains(&a) {
+				algorithms.push(a);
+			}
+		}
+		algorithms
+	}
+}
+
+/// Thresholds for raising alerts on critical conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+	/// Number of consecutive stats updates a device must report an error
+	/// before an alert is raised
+	pub device_error_count: u32,
+
+	/// Percentage by which combined GPS must drop below its running
+	/// average before an alert is raised
+	pub gps_drop_percent: u32,
+
+	/// Seconds the pool connection must be down before an alert is raised
+	pub disconnect_secs: i64,
+
+	/// Percentage by which a device's GPS must drop below its learned
+	/// baseline, for anomaly_secs, before an alert is raised
+	pub device_gps_drop_percent: u32,
+
+	/// Seconds a device GPS drop must last, and window over which the
+	/// reject rate is measured
+	pub anomaly_secs: i64,
+
+	/// Reject rate (percent) over the anomaly window above which, if it's
+	/// also at least twice the session's reject rate, an alert is raised
+	pub reject_rate_percent: u32,
+
+	/// Minimum shares submitted in the anomaly window to check the reject
+	/// rate
+	pub reject_min_shares: u64,
+}
+
+impl Default for AlertConfig {
+	fn default() -> AlertConfig {
+		AlertConfig {
+			device_error_count: 3,
+			gps_drop_percent: 30,
+			disconnect_secs: 60,
+			device_gps_drop_percent: 20,
+			anomaly_secs: 300,
+			reject_rate_percent: 10,
+			reject_min_shares: 5,
+		}
+	}
+}
+
+/// Embedded HTTP stats API configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+	/// Whether to run the API server
+	pub enabled: bool,
+
+	/// Address and port the API server listens on
+	pub bind_address: String,
+
+	/// Token control requests must carry as bearer token. Control
+	/// endpoints are disabled if not set
+	pub control_token: Option<String>,
+
+	/// /healthz reports unhealthy if a device errored within this many
+	/// minutes
+	pub health_error_window_mins: u64,
+}
+
+impl Default for ApiConfig {
+	fn default() -> ApiConfig {
+		ApiConfig {
+			enabled: false,
+			bind_address: String::from("127.0.0.1:3417"),
+			control_token: None,
+			health_error_window_mins: 10,
+		}
+	}
+}
+
+/// Self-update (the update subcommand)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+	/// Url of the release manifest, listing the files of the latest
+	/// release. Its minisign signature is expected at the same url with
+	/// .minisig appended.
+	pub url: Option<String>,
+
+	/// minisign public key the manifest must be signed with
+	pub public_key: Option<String>,
+
+	/// Directory a release is downloaded to, until swapped in at the next
+	/// start
+	pub staging_dir: String,
+}
+
+impl Default for UpdateConfig {
+	fn default() -> UpdateConfig {
+		UpdateConfig {
+			url: None,
+			public_key: None,
+			staging_dir: String::from("grin-miner-update"),
+		}
+	}
+}
+
+/// Local control socket configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSocketConfig {
+	/// Whether to listen on the control socket
+	pub enabled: bool,
+
+	/// Path of the Unix socket, only accessible to the miner's user. On
+	/// Windows, the named pipe \\.\pipe\ followed by its file name
+	pub path: String,
+
+	/// Token commands must carry, on top of the socket's permissions
+	pub token: Option<String>,
+}
+
+impl Default for ControlSocketConfig {
+	fn default() -> ControlSocketConfig {
+		ControlSocketConfig {
+			enabled: false,
+			path: String::from("grin-miner.sock"),
+			token: None,
+		}
+	}
+}
+
+/// gRPC server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+	/// Whether to run the gRPC server
+	pub enabled: bool,
+
+	/// Address and port the gRPC server listens on
+	pub bind_address: String,
+
+	/// Token control calls must carry as bearer token, in the
+	/// authorization metadata. Control calls are refused if not set
+	pub control_token: Option<String>,
+}
+
+impl Default for GrpcConfig {
+	fn default() -> GrpcConfig {
+		GrpcConfig {
+			enabled: false,
+			bind_address: String::from("127.0.0.1:3418"),
+			control_token: None,
+		}
+	}
+}
+
+/// A webhook to send notifications to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+	/// Url to POST notifications to
+	pub url: String,
+
+	/// Body format: json (default), slack, discord or telegram
+	#[serde(default = "default_webhook_format")]
+	pub format: String,
+
+	/// Chat to send to, for telegram
+	pub chat_id: Option<String>,
+}
+
+fn default_webhook_format() -> String {
+	String::from("json")
+}
+
+/// Notifications on alerts and found blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+	/// Webhooks to notify
+	pub webhooks: Vec<WebhookConfig>,
+
+	/// Minimum number of seconds between two notifications of the same
+	/// kind of alert
+	pub min_interval_secs: i64,
+}
+
+impl Default for NotificationConfig {
+	fn default() -> NotificationConfig {
+		NotificationConfig {
+			webhooks: vec![],
+			min_interval_secs: 300,
+		}
+	}
+}
+
+/// Stats snapshot published in a memory-mapped file, for sidecars
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedStatsConfig {
+	/// Whether to publish the stats
+	pub enabled: bool,
+
+	/// File mapped, e.g. under /dev/shm for shared memory
+	pub path: String,
+
+	/// Seconds between updates
+	pub interval_secs: u64,
+
+	/// Size of the file in KB, which the snapshot must fit in
+	pub size_kb: u64,
+}
+
+impl Default for SharedStatsConfig {
+	fn default() -> SharedStatsConfig {
+		SharedStatsConfig {
+			enabled: false,
+			path: String::from("grin-miner-stats.shm"),
+			interval_secs: 5,
+			size_kb: 64,
+		}
+	}
+}
+
+/// Periodic logging of stats snapshots to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsLogConfig {
+	/// Whether to log stats snapshots
+	pub enabled: bool,
+
+	/// File to append the snapshots to
+	pub path: String,
+
+	/// File format: csv (default) or json (JSON lines)
+	pub format: String,
+
+	/// Seconds between snapshots
+	pub interval_secs: u64,
+
+	/// Size (in MB) at which the file is rotated
+	pub max_size_mb: u64,
+
+	/// Number of rotated files to keep
+	pub max_files: u32,
+}
+
+impl Default for StatsLogConfig {
+	fn default() -> StatsLogConfig {
+		StatsLogConfig {
+			enabled: false,
+			path: String::from("grin-miner-stats.csv"),
+			format: String::from("csv"),
+			interval_secs: 60,
+			max_size_mb: 10,
+			max_files: 5,
+		}
+	}
+}
+
+/// Telemetry push to a time series database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+	/// Whether to push telemetry
+	pub enabled: bool,
+
+	/// influxdb or graphite
+	pub backend: String,
+
+	/// InfluxDB write url (e.g. http://127.0.0.1:8086/write?db=grin), or
+	/// Graphite plaintext address (e.g. 127.0.0.1:2003)
+	pub url: String,
+
+	/// Prefix of graphite metric names
+	pub prefix: String,
+
+	/// Seconds between pushes
+	pub interval_secs: u64,
+
+	/// Tags added to every metric (e.g. rig name, location)
+	pub tags: HashMap<String, String>,
+}
+
+impl Default for TelemetryConfig {
+	fn default() -> TelemetryConfig {
+		TelemetryConfig {
+			enabled: false,
+			backend: String::from("influxdb"),
+			url: String::from("http://127.0.0.1:8086/write?db=grin_miner"),
+			prefix: String::from("grin_miner"),
+			interval_secs: 10,
+			tags: HashMap::new(),
+		}
+	}
+}
+
+/// Anonymous usage statistics, sent only if switched on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStatsConfig {
+	/// Whether to send usage statistics, off unless switched on
+	pub enabled: bool,
+
+	/// Where the reports are POSTed, as JSON
+	pub url: String,
+
+	/// Hours between reports
+	pub interval_hours: u64,
+}
+
+impl Default for UsageStatsConfig {
+	fn default() -> UsageStatsConfig {
+		UsageStatsConfig {
+			enabled: false,
+			url: String::new(),
+			interval_hours: 24,
+		}
+	}
+}
+
+/// MQTT stats publishing and command channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+	/// Whether to connect to the MQTT broker
+	pub enabled: bool,
+
+	/// Broker address and port
+	pub broker: String,
+
+	/// Client id to connect with
+	pub client_id: String,
+
+	/// Broker username, if required
+	pub username: Option<String>,
+
+	/// Broker password, if required
+	pub password: Option<String>,
+
+	/// Root of the topic tree
+	pub topic_prefix: String,
+
+	/// Rig name, stats are published under <topic_prefix>/<rig>/ and
+	/// commands read from <topic_prefix>/<rig>/command
+	pub rig: String,
+
+	/// Seconds between stats publications
+	pub interval_secs: u64,
+
+	/// Control commands accepted on the command topic, anyone able to
+	/// publish on the broker can send them
+	pub commands: Vec<String>,
+}
+
+impl Default for MqttConfig {
+	fn default() -> MqttConfig {
+		MqttConfig {
+			enabled: false,
+			broker: String::from("127.0.0.1:1883"),
+			client_id: String::from("grin-miner"),
+			username: None,
+			password: None,
+			topic_prefix: String::from("miners"),
+			rig: String::from("rig01"),
+			interval_secs: 10,
+			commands: vec![String::from("pause"), String::from("resume")],
+		}
+	}
+}
+
+/// Embedded SQLite store of stats snapshots and found shares
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryStoreConfig {
+	/// Whether to record history
+	pub enabled: bool,
+
+	/// Path of the SQLite database
+	pub path: String,
+
+	/// Seconds between snapshots
+	pub interval_secs: u64,
+
+	/// Days records are kept for
+	pub retention_days: u64,
+}
+
+impl Default for HistoryStoreConfig {
+	fn default() -> HistoryStoreConfig {
+		HistoryStoreConfig {
+			enabled: false,
+			path: String::from("grin-miner-history.db"),
+			interval_secs: 60,
+			retention_days: 30,
+		}
+	}
+}
+
+/// A window during which mining is allowed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleWindow {
+	/// Local time the window opens, as HH:MM
+	pub start: String,
+
+	/// Local time the window closes, as HH:MM. Windows ending before
+	/// they start run past midnight.
+	pub end: String,
+
+	/// Days the window opens on (sun, mon, ...), every day if not set
+	pub days: Option<Vec<String>>,
+
+	/// Device profile to mine with during the window
+	pub profile: Option<String>,
+}
+
+/// Time of day scheduling, pausing mining outside the given windows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+	/// Whether to schedule mining
+	pub enabled: bool,
+
+	/// Seconds between checks of the schedule
+	pub check_interval_secs: u64,
+
+	/// Windows during which mining is allowed
+	pub windows: Vec<ScheduleWindow>,
+}
+
+impl Default for SchedulerConfig {
+	fn default() -> SchedulerConfig {
+		SchedulerConfig {
+			enabled: false,
+			check_interval_secs: 30,
+			windows: vec![],
+		}
+	}
+}
+
+/// Solo mining against a node's stratum server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoloConfig {
+	/// Listener of the wallet the node builds coinbases with, as in the
+	/// node's wallet_listener_url, e.g. http://127.0.0.1:3415
+	pub wallet_listener_url: Option<String>,
+
+	/// Whether to check at startup that the wallet builds a coinbase
+	pub check_wallet: bool,
+}
+
+impl Default for SoloConfig {
+	fn default() -> SoloConfig {
+		SoloConfig {
+			wallet_listener_url: None,
+			check_wallet: true,
+		}
+	}
+}
+
+/// Desktop mode, pausing or throttling mining while the user is at the
+/// computer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopConfig {
+	/// Whether to watch for user activity
+	pub enabled: bool,
+
+	/// Seconds without keyboard or mouse input before mining resumes
+	pub idle_timeout_secs: u64,
+
+	/// Seconds between checks of user activity
+	pub check_interval_secs: u64,
+
+	/// Whether a fullscreen application (game, video, presentation) counts
+	/// as activity
+	pub pause_on_fullscreen: bool,
+
+	/// Profile to mine with while the user is active, instead of pausing
+	pub throttle_profile: Option<String>,
+
+	/// Command printing the milliseconds since the last input, where the
+	/// platform's own way isn't available (e.g. Wayland)
+	pub idle_command: Option<String>,
+}
+
+impl Default for DesktopConfig {
+	fn default() -> DesktopConfig {
+		DesktopConfig {
+			enabled: false,
+			idle_timeout_secs: 300,
+			check_interval_secs: 5,
+			pause_on_fullscreen: true,
+			throttle_profile: None,
+			idle_command: None,
+		}
+	}
+}
+
+/// GPU temperature, fan, power and clock monitoring through NVML
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HardwareMonitorConfig {
+	/// Whether to monitor GPUs
+	pub enabled: bool,
+
+	/// Seconds between readings
+	pub poll_interval_secs: u64,
+}
+
+impl Default for HardwareMonitorConfig {
+	fn default() -> HardwareMonitorConfig {
+		HardwareMonitorConfig {
+			enabled: false,
+			poll_interval_secs: 5,
+		}
+	}
+}
+
+/// Temperature limits of a single device, overriding the defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalLimits {
+	/// Temperature above which the device is throttled
+	pub soft_limit: Option<u32>,
+
+	/// Temperature above which the device is paused
+	pub hard_limit: Option<u32>,
+
+	/// Temperature a paused device resumes at
+	pub resume_temp: Option<u32>,
+}
+
+/// Thermal protection, throttling and pausing devices that run too hot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalConfig {
+	/// Whether to protect devices
+	pub enabled: bool,
+
+	/// Temperature (celsius) above which devices are throttled, reducing
+	/// their duty cycle
+	pub soft_limit: u32,
+
+	/// Temperature above which devices are paused
+	pub hard_limit: u32,
+
+	/// Temperature paused devices resume at
+	pub resume_temp: u32,
+
+	/// Seconds over which a throttled device's duty cycle is spread
+	pub duty_period_secs: u64,
+
+	/// Lowest duty cycle (percent) a device is throttled to
+	pub min_duty_cycle: u32,
+
+	/// Limits for individual devices, keyed by "plugin:device", e.g. "0:1"
+	pub devices: Option<HashMap<String, ThermalLimits>>,
+}
+
+impl Default for ThermalConfig {
+	fn default() -> ThermalConfig {
+		ThermalConfig {
+			enabled: false,
+			soft_limit: 80,
+			hard_limit: 90,
+			resume_temp: 70,
+			duty_period_secs: 60,
+			min_duty_cycle: 20,
+			devices: None,
+		}
+	}
+}
+
+/// Recovery of devices whose solver keeps failing, e.g. after a GPU fault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecoveryConfig {
+	/// Whether to try recovering errored devices
+	pub enabled: bool,
+
+	/// Consecutive errored stats updates (every 2 seconds) before a
+	/// device is recovered
+	pub error_threshold: u32,
+
+	/// Seconds to wait before the first recovery attempt, doubled for
+	/// each further attempt
+	pub initial_backoff_secs: u64,
+
+	/// Longest wait between recovery attempts
+	pub max_backoff_secs: u64,
+
+	/// Recovery attempts before the device is given up on and marked
+	/// failed
+	pub max_attempts: u32,
+
+	/// Seconds a device needs to mine without errors before its attempts
+	/// are forgotten
+	pub healthy_secs: u64,
+
+	/// Whether to reset NVIDIA GPUs (nvidia-smi --gpu-reset, which needs
+	/// root) while the solvers are torn down
+	pub gpu_reset: bool,
+
+	/// Whether to quarantine a device while it waits for its recovery
+	/// attempt, rather than leaving it erroring meanwhile
+	pub quarantine: bool,
+}
+
+impl Default for RecoveryConfig {
+	fn default() -> RecoveryConfig {
+		RecoveryConfig {
+			enabled: true,
+			error_threshold: 3,
+			initial_backoff_secs: 10,
+			max_backoff_secs: 600,
+			max_attempts: 5,
+			healthy_secs: 600,
+			gpu_reset: false,
+			quarantine: true,
+		}
+	}
+}
+
+/// Share watchdog, recovering the rig when no share has been accepted
+/// for a while despite being connected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+	/// Whether to watch for accepted shares
+	pub enabled: bool,
+
+	/// Minutes connected, mining and without an accepted share before the
+	/// watchdog triggers
+	pub no_share_minutes: u64,
+
+	/// Command to run when triggered (through the shell), e.g. a reboot
+	/// script. Mining is restarted internally if not set.
+	pub command: Option<String>,
+}
+
+impl Default for WatchdogConfig {
+	fn default() -> WatchdogConfig {
+		WatchdogConfig {
+			enabled: false,
+			no_share_minutes: 60,
+			command: None,
+		}
+	}
+}
+
+/// Identity of the rig, telling apart the rigs of a farm in the pool's
+/// worker list, metrics, notifications and logs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RigConfig {
+	/// Name of the rig, sent to the pool as the rig_id of the login
+	pub name: Option<String>,
+
+	/// Free form labels, e.g. location = "basement", added to the metrics
+	/// and notifications
+	pub labels: BTreeMap<String, String>,
+}
+
+/// Limits on the rate of submits, for pools banning clients that submit
+/// too fast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubmitLimitConfig {
+	/// Most submits a minute, unlimited if not set
+	pub max_per_minute: Option<u32>,
+
+	/// Caps of particular pools, by stratum server address, in place of
+	/// max_per_minute
+	pub pools: HashMap<String, u32>,
+
+	/// Seconds to hold submits back after the pool answers one with a ban
+	/// or too many requests error
+	pub cooldown_secs: u64,
+
+	/// Most submits held back, the oldest are dropped beyond
+	pub max_queued: usize,
+}
+
+impl Default for SubmitLimitConfig {
+	fn default() -> SubmitLimitConfig {
+		SubmitLimitConfig {
+			max_per_minute: None,
+			pools: HashMap::new(),
+			cooldown_secs: 60,
+			max_queued: 100,
+		}
+	}
+}
+
+/// A pool to fail over to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailoverPool {
+	/// Stratum server address, e.g. backup.pool.example:3416
+	pub url: String,
+
+	/// Login, the mining login if not set
+	pub login: Option<String>,
+
+	/// Password, the mining password if not set
+	pub password: Option<String>,
+}
+
+/// Circuit breaker failing over to the next pool, or pausing mining, when
+/// a pool rejects too many shares
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RejectBreakerConfig {
+	/// Whether to watch the reject rate
+	pub enabled: bool,
+
+	/// Percentage of the last window_shares shares answered that may be
+	/// rejected before the breaker trips
+	pub max_reject_percent: u32,
+
+	/// Maxima of particular pools, by stratum server address, in place of
+	/// max_reject_percent
+	pub pools: HashMap<String, u32>,
+
+	/// Number of the latest shares answered the reject rate is taken over
+	pub window_shares: usize,
+
+	/// Shares answered by a pool before its reject rate is checked
+	pub min_shares: usize,
+
+	/// Pools to fail over to in turn, mining is paused once the last one
+	/// trips the breaker
+	pub failover: Vec<FailoverPool>,
+
+	/// Minutes after tripping to go back to the first pool, resuming
+	/// mining if paused
+	pub retry_minutes: u64,
+}
+
+impl Default for RejectBreakerConfig {
+	fn default() -> RejectBreakerConfig {
+		RejectBreakerConfig {
+			enabled: false,
+			max_reject_percent: 25,
+			pools: HashMap::new(),
+			window_shares: 50,
+			min_shares: 20,
+			failover: vec![],
+			retry_minutes: 30,
+		}
+	}
+}
+
+/// What to do on a terminal condition, the condition's default for
+/// anything not given
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureRule {
+	/// exit, or retry
+	pub action: Option<String>,
+	/// Exit code of the process, when exiting
+	pub exit_code: Option<i32>,
+}
+
+/// Exit codes and exit or retry behaviour of terminal conditions, for
+/// supervisors restarting unattended rigs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailurePolicyConfig {
+	/// Every device mined on failed for good
+	pub all_devices_failed: FailureRule,
+	/// The pool rejected the login
+	pub pool_auth_rejected: FailureRule,
+	/// The configuration is invalid
+	pub config_invalid: FailureRule,
+	/// A configured plugin is missing or doesn't load
+	pub plugin_missing: FailureRule,
+	/// Seconds to wait before retrying
+	pub retry_secs: u64,
+}
+
+impl Default for FailurePolicyConfig {
+	fn default() -> FailurePolicyConfig {
+		FailurePolicyConfig {
+			all_devices_failed: FailureRule::default(),
+			pool_auth_rejected: FailureRule::default(),
+			config_invalid: FailureRule::default(),
+			plugin_missing: FailureRule::default(),
+			retry_secs: 60,
+		}
+	}
+}
+
+/// A remote grin-miner shown in the TUI's fleet view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetRigConfig {
+	/// Address of the rig's API, e.g. 192.168.1.20:3420
+	pub url: String,
+
+	/// Name shown in the fleet view, the rig's own name by default
+	pub name: Option<String>,
+
+	/// The rig's api control_token, if it has one
+	pub token: Option<String>,
+}
+
+/// Other rigs to summarize in the TUI, from their stats API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FleetConfig {
+	/// Rigs to poll
+	pub rigs: Vec<FleetRigConfig>,
+
+	/// Seconds between polls of each rig
+	pub interval_secs: u64,
+}
+
+impl Default for FleetConfig {
+	fn default() -> FleetConfig {
+		FleetConfig {
+			rigs: vec![],
+			interval_secs: 10,
 		}
 	}
 }
@@ -138,19 +1572,78 @@ pub struct GlobalConfig {
 	/// a config file or just the defaults
 	/// for each member
 	pub using_config_file: bool,
+	/// What was changed upgrading the config file from an older version
+	pub migrations: Vec<String>,
+	/// Config fragments merged over the config file, with the keys each set
+	pub merged: Vec<String>,
 	/// Global member config
 	pub members: Option<ConfigMembers>,
 }
 
+fn default_config_version() -> u32 {
+	::migrate::CONFIG_VERSION
+}
+
 /// Keeping an 'inner' structure here, as the top
 /// level GlobalConfigContainer options might want to keep
 /// internal state that we don't necessarily
 /// want serialised or deserialised
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigMembers {
+	/// Version of the config file layout
+	#[serde(default = "default_config_version")]
+	pub config_version: u32,
 	/// Server config
 	/// Mining config
 	pub mining: MinerConfig,
 	/// Logging config
 	pub logging: Option<util::types::LoggingConfig>,
+	/// Alert thresholds
+	pub alerts: Option<AlertConfig>,
+	/// HTTP stats API
+	pub api: Option<ApiConfig>,
+	/// Webhook notifications
+	pub notifications: Option<NotificationConfig>,
+	/// Stats snapshot logging
+	pub stats_log: Option<StatsLogConfig>,
+	/// Stats in a memory-mapped file
+	pub shared_stats: Option<SharedStatsConfig>,
+	/// Telemetry push
+	pub telemetry: Option<TelemetryConfig>,
+	/// MQTT
+	pub mqtt: Option<MqttConfig>,
+	/// Embedded SQLite stats history
+	pub history_store: Option<HistoryStoreConfig>,
+	/// Time of day scheduling
+	pub scheduler: Option<SchedulerConfig>,
+	/// GPU hardware monitoring
+	pub hardware_monitor: Option<HardwareMonitorConfig>,
+	/// Thermal protection
+	pub thermal: Option<ThermalConfig>,
+	/// Recovery of errored devices
+	pub recovery: Option<RecoveryConfig>,
+	/// Share watchdog
+	pub watchdog: Option<WatchdogConfig>,
+	/// Rig name and labels
+	pub rig: Option<RigConfig>,
+	/// Local control socket
+	pub control_socket: Option<ControlSocketConfig>,
+	/// gRPC server
+	pub grpc: Option<GrpcConfig>,
+	/// Remote rigs of the TUI's fleet view
+	pub fleet: Option<FleetConfig>,
+	/// Submit rate limits
+	pub submit_limit: Option<SubmitLimitConfig>,
+	/// Reject rate circuit breaker
+	pub reject_breaker: Option<RejectBreakerConfig>,
+	/// Exit codes and failure policy
+	pub failure_policy: Option<FailurePolicyConfig>,
+	/// Desktop mode
+	pub desktop: Option<DesktopConfig>,
+	/// Solo mining
+	pub solo: Option<SoloConfig>,
+	/// Anonymous usage statistics
+	pub usage_stats: Option<UsageStatsConfig>,
+	/// Self-update
+	pub update: Option<UpdateConfig>,
 }
@@ -0,0 +1,312 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection of critical conditions (repeatedly erroring devices, GPS
+//! drops, lost pool connection, the reject rate circuit breaker tripping)
+//! and anomalies (sustained device GPS drops against its learned baseline,
+//! reject rate spikes), published as alerts in the stats so the TUI and
+//! other consumers can report them
+
+use std::collections::{HashMap, VecDeque};
+
+use time;
+
+use clock;
+use config::AlertConfig;
+use stats::{self, Stats};
+use util::LOGGER;
+
+/// Kind of critical condition
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AlertKind {
+	/// A device has reported errors for several stats updates in a row
+	DeviceErrors,
+	/// Combined GPS dropped by more than the configured percentage
+	GpsDrop,
+	/// The pool connection has been down for longer than configured
+	Disconnected,
+	/// A device's GPS has been well below its learned baseline for a while
+	DeviceGpsAnomaly,
+	/// The recent reject rate is well above the session's
+	RejectRateSpike,
+	/// The reject rate circuit breaker tripped, failing over to another
+	/// pool or pausing mining
+	RejectBreaker,
+}
+
+/// Number of stats updates a device baseline is learned over before
+/// deviations from it are flagged
+const BASELINE_WARMUP: u32 = 30;
+
+/// Learned GPS baseline of a single device
+struct DeviceBaseline {
+	gps: f64,
+	samples: u32,
+	deviation_since: Option<i64>,
+}
+
+/// A critical condition that is currently active
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alert {
+	/// Kind of condition
+	pub kind: AlertKind,
+	/// Human readable description of the condition
+	pub message: String,
+	/// Time (seconds since epoch) the condition started
+	pub since: i64,
+}
+
+impl Alert {
+	/// How long the condition has been active, in seconds
+	pub fn duration(&self) -> i64 {
+		time::get_time().sec - self.since
+	}
+}
+
+/// Keeps the state needed to detect alert conditions across stats updates
+pub struct AlertMonitor {
+	config: AlertConfig,
+	/// consecutive errored updates, and when they started, per device
+	device_errors: HashMap<String, (u32, i64)>,
+	/// long running average of the combined GPS
+	baseline_gps: f64,
+	gps_drop_since: Option<i64>,
+	/// learned GPS baseline per device
+	device_baselines: HashMap<String, DeviceBaseline>,
+	/// (monotonic clock seconds, accepted, rejected) share counts, over
+	/// the anomaly window
+	share_samples: VecDeque<(i64, u64, u64)>,
+	reject_spike_since: Option<i64>,
+}
+
+impl AlertMonitor {
+	/// Create a new monitor
+	pub fn new(config: AlertConfig) -> AlertMonitor {
+		AlertMonitor {
+			config: config,
+			device_errors: HashMap::new(),
+			baseline_gps: 0.0,
+			gps_drop_since: None,
+			device_baselines: HashMap::new(),
+			share_samples: VecDeque::new(),
+			reject_spike_since: None,
+		}
+	}
+
+	/// Check the latest stats for alert conditions, and replace the
+	/// active alerts in the stats with the result
+	pub fn update(&mut self, stats: &mut Stats) {
+		let now = time::get_time().sec;
+		let mut alerts = vec![];
+
+		// timed on the monotonic clock, the wall clock time is for display
+		let disconnected = stats
+			.client_stats
+			.disconnected_since
+			.and_then(|since| stats.client_stats.disconnected_mono.map(|m| (since, m)));
+		if let Some((since, mono)) = disconnected {
+			if clock::mono_secs() - mono > self.config.disconnect_secs {
+				alerts.push(Alert {
+					kind: AlertKind::Disconnected,
+					message: format!(
+						"Pool connection to {} lost",
+						stats.client_stats.server_url
+					),
+					since: since,
+				});
+			}
+		}
+
+		if let Some(ref plugins) = stats.mining_stats.device_stats {
+			for (i, devices) in plugins.iter().enumerate() {
+				for d in devices {
+					let key = format!("{}:{}", i, d.device_id);
+					if d.in_use == 0 || d.has_errored == 0 {
+						self.device_errors.remove(&key);
+						continue;
+					}
+					let entry = self.device_errors.entry(key).or_insert((0, now));
+					entry.0 += 1;
+					if entry.0 >= self.config.device_error_count {
+						alerts.push(Alert {
+							kind: AlertKind::DeviceErrors,
+							message: format!(
+								"Plugin {} device {} ({}) is repeatedly erroring",
+								i, d.device_id, d.device_name
+							),
+							since: entry.1,
+						});
+					}
+				}
+			}
+		}
+
+		// Devices restart on every new job, so a GPS of 0 just means no
+		// graph has been completed yet
+		let gps = stats.mining_stats.combined_gps;
+		if gps > 0.0 {
+			let threshold =
+				self.baseline_gps * (1.0 - self.config.gps_drop_percent as f64 / 100.0);
+			if self.baseline_gps > 0.0 && gps < threshold {
+				if self.gps_drop_since.is_none() {
+					self.gps_drop_since = Some(now);
+				}
+			} else {
+				self.gps_drop_since = None;
+				self.baseline_gps = if self.baseline_gps == 0.0 {
+					gps
+				} else {
+					self.baseline_gps * 0.95 + gps * 0.05
+				};
+			}
+		}
+		if let Some(since) = self.gps_drop_since {
+			alerts.push(Alert {
+				kind: AlertKind::GpsDrop,
+				message: format!(
+					"GPS dropped to {:.*} from an average of {:.*}",
+					4, gps, 4, self.baseline_gps
+				),
+				since: since,
+			});
+		}
+
+		self.device_anomalies(stats, now, &mut alerts);
+		self.reject_rate_spike(stats, now, &mut alerts);
+
+		if let (true, Some(since)) = (
+			stats.client_stats.breaker.open,
+			stats.client_stats.breaker.since,
+		) {
+			alerts.push(Alert {
+				kind: AlertKind::RejectBreaker,
+				message: format!(
+					"Circuit breaker: {}",
+					stats.client_stats.breaker.reason.clone().unwrap_or_default()
+				),
+				since: since,
+			});
+		}
+
+		for a in &alerts {
+			if !stats.alerts.iter().any(|o| o.kind == a.kind && o.since == a.since) {
+				warn!(LOGGER, "Alert: {}", a.message);
+			}
+		}
+		stats.alerts = alerts;
+	}
+
+	/// Flag devices whose GPS has stayed below their learned baseline by
+	/// more than the configured percentage for the anomaly window
+	fn device_anomalies(&mut self, stats: &Stats, now: i64, alerts: &mut Vec<Alert>) {
+		let plugins = match stats.mining_stats.device_stats {
+			Some(ref p) => p,
+			None => return,
+		};
+		for (i, devices) in plugins.iter().enumerate() {
+			for d in devices.iter().filter(|d| d.in_use == 1) {
+				// no graph completed yet since the last job change
+				if d.last_solution_time == 0 || d.has_errored != 0 {
+					continue;
+				}
+				let gps = stats::device_gps(d);
+				let key = format!("{}:{}", i, d.device_id);
+				let baseline = self.device_baselines.entry(key).or_insert(DeviceBaseline {
+					gps: gps,
+					samples: 0,
+					deviation_since: None,
+				});
+				let threshold =
+					baseline.gps * (1.0 - self.config.device_gps_drop_percent as f64 / 100.0);
+				if baseline.samples >= BASELINE_WARMUP && gps < threshold {
+					if baseline.deviation_since.is_none() {
+						baseline.deviation_since = Some(now);
+					}
+				} else {
+					baseline.deviation_since = None;
+					baseline.gps = baseline.gps * 0.95 + gps * 0.05;
+					baseline.samples += 1;
+				}
+				if let Some(since) = baseline.deviation_since {
+					if now - since >= self.config.anomaly_secs {
+						alerts.push(Alert {
+							kind: AlertKind::DeviceGpsAnomaly,
+							message: format!(
+								"Plugin {} device {} ({}) at {:.*} GPS, {:.0}% below its baseline of {:.*}",
+								i,
+								d.device_id,
+								d.device_name,
+								4,
+								gps,
+								(1.0 - gps / baseline.gps) * 100.0,
+								4,
+								baseline.gps
+							),
+							since: since,
+						});
+					}
+				}
+			}
+		}
+	}
+
+	/// Flag a reject rate over the anomaly window above both the
+	/// configured percentage and twice the session's reject rate. The
+	/// window is taken on the monotonic clock, alerts start at wall clock
+	/// times.
+	fn reject_rate_spike(&mut self, stats: &Stats, now: i64, alerts: &mut Vec<Alert>) {
+		let accepted = stats.client_stats.shares_accepted;
+		let rejected = stats.client_stats.shares_rejected;
+		let mono = clock::mono_secs();
+		self.share_samples.push_back((mono, accepted, rejected));
+		let window_start = mono - self.config.anomaly_secs;
+		while self.share_samples.len() > 1 && self.share_samples[0].0 < window_start {
+			self.share_samples.pop_front();
+		}
+		let (_, start_accepted, start_rejected) = self.share_samples[0];
+		let window_accepted = accepted - start_accepted.min(accepted);
+		let window_rejected = rejected - start_rejected.min(rejected);
+		let window_total = window_accepted + window_rejected;
+		let session_rate = if accepted + rejected > 0 {
+			rejected as f64 / (accepted + rejected) as f64
+		} else {
+			0.0
+		};
+		let window_rate = if window_total > 0 {
+			window_rejected as f64 / window_total as f64
+		} else {
+			0.0
+		};
+		let threshold = (self.config.reject_rate_percent as f64 / 100.0).max(session_rate * 2.0);
+		if window_total >= self.config.reject_min_shares && window_rate > threshold {
+			if self.reject_spike_since.is_none() {
+				self.reject_spike_since = Some(now);
+			}
+		} else {
+			self.reject_spike_since = None;
+		}
+		if let Some(since) = self.reject_spike_since {
+			alerts.push(Alert {
+				kind: AlertKind::RejectRateSpike,
+				message: format!(
+					"{:.0}% of the last {} shares rejected, against {:.0}% over the session",
+					window_rate * 100.0,
+					window_total,
+					session_rate * 100.0
+				),
+				since: since,
+			});
+		}
+	}
+}
@@ -0,0 +1,784 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Embedded HTTP server, exposing the miner stats as JSON so rig status
+//! can be scraped by farm dashboards, and authenticated control endpoints
+//! so rigs can be managed remotely
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::{self, Value};
+use time;
+
+use alerts::Alert;
+use config::ApiConfig;
+use control::Control;
+use stats::{self, BlockProgress, DeviceHardwareStats, DeviceQuarantine, DifficultyHistogram,
+            Efficiency, GpsAverages, HistoryStats, PoolNotice, ShareRecord, ShareTiming,
+            StageLatencies, Stats};
+use store;
+use stream;
+use util::cuckoo_miner::CuckooMinerDeviceStats;
+use util::LOGGER;
+
+/// Largest request body read, control parameters are much smaller
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// An HTTP response, status code and (usually JSON) body
+pub struct Response {
+	/// HTTP status code
+	pub code: u16,
+	/// Content type of the body
+	pub content_type: &'static str,
+	/// Body
+	pub body: String,
+}
+
+impl Response {
+	/// Successful response with the given value as body
+	pub fn json<T: Serialize>(value: &T) -> Response {
+		match serde_json::to_string_pretty(value) {
+			Ok(body) => Response {
+				code: 200,
+				content_type: "application/json",
+				body: body,
+			},
+			Err(e) => Response::error(500, &format!("{}", e)),
+		}
+	}
+
+	/// Error response, with the error message as body
+	pub fn error(code: u16, message: &str) -> Response {
+		let mut body = HashMap::new();
+		body.insert("error", message);
+		Response {
+			code: code,
+			content_type: "application/json",
+			body: serde_json::to_string(&body).unwrap(),
+		}
+	}
+
+	/// Successful plain text response
+	pub fn text(body: String) -> Response {
+		Response {
+			code: 200,
+			content_type: "text/plain; version=0.0.4",
+			body: body,
+		}
+	}
+
+	fn reason(&self) -> &'static str {
+		match self.code {
+			200 => "OK",
+			400 => "Bad Request",
+			401 => "Unauthorized",
+			404 => "Not Found",
+			405 => "Method Not Allowed",
+			413 => "Payload Too Large",
+			503 => "Service Unavailable",
+			_ => "Internal Server Error",
+		}
+	}
+}
+
+/// `/v1/status`
+#[derive(Serialize)]
+struct StatusResponse<'a> {
+	connected: bool,
+	connection_status: &'a str,
+	mining_status: String,
+	block_height: u64,
+	target_difficulty: u64,
+	combined_gps: f64,
+	algorithm_gps: &'a Vec<(String, f64)>,
+	gps_averages: GpsAverages,
+	efficiency: Efficiency,
+	uptime: i64,
+	alerts: &'a Vec<Alert>,
+}
+
+/// `/v1/devices`, one for each device
+#[derive(Serialize)]
+struct DeviceResponse<'a> {
+	plugin: usize,
+	/// Stable identity of the device, across restarts
+	uid: Option<&'a str>,
+	gps: f64,
+	gps_averages: Option<GpsAverages>,
+	stats: &'a CuckooMinerDeviceStats,
+	hardware: Option<&'a DeviceHardwareStats>,
+	/// Until when the device is quarantined, if it is
+	quarantine: Option<&'a DeviceQuarantine>,
+}
+
+/// `/v1/pools`, one for each pool
+#[derive(Serialize)]
+struct PoolResponse<'a> {
+	url: &'a str,
+	connected: bool,
+	connection_status: &'a str,
+	disconnected_since: Option<i64>,
+	last_message_sent: &'a str,
+	last_message_received: &'a str,
+	traffic: TrafficResponse,
+	/// Smoothed round trip time of requests to the pool
+	pool_rtt_ms: Option<f64>,
+	/// Latest notices from the pool, oldest first
+	notices: &'a VecDeque<PoolNotice>,
+}
+
+/// Bytes and messages exchanged with a pool, rates in bytes per second
+#[derive(Serialize)]
+struct TrafficResponse {
+	bytes_sent: u64,
+	bytes_received: u64,
+	messages_sent: u64,
+	messages_received: u64,
+	connection_bytes_sent: u64,
+	connection_bytes_received: u64,
+	connection_messages_sent: u64,
+	connection_messages_received: u64,
+	send_rate: f64,
+	receive_rate: f64,
+}
+
+/// `/v1/shares`
+#[derive(Serialize)]
+struct SharesResponse<'a> {
+	accepted: u64,
+	rejected: u64,
+	stale: u64,
+	solutions_found: u64,
+	solutions_by_size: &'a HashMap<u32, u64>,
+	best_share_difficulty: u64,
+	below_target: u64,
+	difficulty_histogram: &'a DifficultyHistogram,
+	recent: &'a VecDeque<ShareRecord>,
+	/// Mean latency of each stage over the recent answered shares
+	latencies: StageLatencies,
+	/// Stage timings of the recent answered shares
+	timings: &'a VecDeque<ShareTiming>,
+	/// Best share against the network difficulty, if the pool sends it
+	block_progress: Option<BlockProgress>,
+	/// Jobs replaced by a new one while being mined
+	job_switches: u64,
+	/// Percentage of uptime spent on jobs the pool had already replaced
+	job_switch_waste: f64,
+	/// How long each device takes to switch to a new job
+	device_job_switches: Vec<DeviceSwitchResponse>,
+}
+
+#[derive(Serialize)]
+struct DeviceSwitchResponse<'a> {
+	plugin: usize,
+	device_id: &'a str,
+	switches: u64,
+	/// Mean ms from stopping the old job to the new one started
+	average_ms: f64,
+	last_ms: u64,
+}
+
+fn status(stats: &Stats) -> Response {
+	Response::json(&StatusResponse {
+		connected: stats.client_stats.connected,
+		connection_status: &stats.client_stats.connection_status,
+		mining_status: stats.mining_status().0,
+		block_height: stats.mining_stats.block_height,
+		target_difficulty: stats.mining_stats.target_difficulty,
+		combined_gps: stats.mining_stats.combined_gps,
+		algorithm_gps: &stats.mining_stats.algorithm_gps,
+		gps_averages: stats
+			.mining_stats
+			.gps_history
+			.averages(stats.clock_now()),
+		efficiency: stats.mining_stats.efficiency(),
+		uptime: stats.session_stats.uptime(stats.clock_now()),
+		alerts: &stats.alerts,
+	})
+}
+
+/// The devices as `/v1/devices` lists them, with their plugin index and
+/// device id as "plugin:device_id"
+pub fn device_values(stats: &Stats) -> Vec<(String, Value)> {
+	let mut devices = vec![];
+	let now = stats.clock_now();
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		for (i, plugin) in plugins.iter().enumerate() {
+			for d in plugin {
+				let device = DeviceResponse {
+					plugin: i,
+					uid: stats.mining_stats.device_uid(i, &d.device_id),
+					gps: stats::device_gps(d),
+					gps_averages: stats.mining_stats.device_gps_averages(i, &d.device_id, now),
+					stats: d,
+					hardware: stats.mining_stats.hardware_for(i, &d.device_id),
+					quarantine: stats.mining_stats.quarantine_for(i, &d.device_id),
+				};
+				if let Ok(v) = serde_json::to_value(&device) {
+					devices.push((format!("{}:{}", i, d.device_id), v));
+				}
+			}
+		}
+	}
+	devices
+}
+
+fn devices(stats: &Stats) -> Response {
+	let devices: Vec<Value> = device_values(stats).into_iter().map(|d| d.1).collect();
+	Response::json(&devices)
+}
+
+fn pools(stats: &Stats) -> Response {
+	let client_stats = &stats.client_stats;
+	let traffic = &client_stats.traffic;
+	let (send_rate, receive_rate) = traffic.rates(stats.clock_now());
+	Response::json(&vec![PoolResponse {
+		url: &client_stats.server_url,
+		connected: client_stats.connected,
+		connection_status: &client_stats.connection_status,
+		disconnected_since: client_stats.disconnected_since,
+		last_message_sent: &client_stats.last_message_sent,
+		last_message_received: &client_stats.last_message_received,
+		traffic: TrafficResponse {
+			bytes_sent: traffic.bytes_sent,
+			bytes_received: traffic.bytes_received,
+			messages_sent: traffic.messages_sent,
+			messages_received: traffic.messages_received,
+			connection_bytes_sent: traffic.connection_bytes_sent,
+			connection_bytes_received: traffic.connection_bytes_received,
+			connection_messages_sent: traffic.connection_messages_sent,
+			connection_messages_received: traffic.connection_messages_received,
+			send_rate: send_rate,
+			receive_rate: receive_rate,
+		},
+		pool_rtt_ms: client_stats.pool_rtt_ms,
+		notices: &client_stats.notices,
+	}])
+}
+
+fn shares(stats: &Stats) -> Response {
+	Response::json(&SharesResponse {
+		accepted: stats.client_stats.shares_accepted,
+		rejected: stats.client_stats.shares_rejected,
+		stale: stats.client_stats.shares_stale,
+		solutions_found: stats.session_stats.solutions_found,
+		solutions_by_size: &stats.session_stats.solutions_by_size,
+		best_share_difficulty: stats.session_stats.best_share_difficulty,
+		below_target: stats.session_stats.shares_below_target,
+		difficulty_histogram: &stats.session_stats.difficulty_histogram,
+		recent: &stats.session_stats.recent_shares,
+		latencies: stats.timing.averages(),
+		timings: &stats.timing.recent,
+		block_progress: stats.block_progress(),
+		job_switches: stats.timing.job_switches,
+		job_switch_waste: stats.job_switch_waste(),
+		device_job_switches: stats
+			.timing
+			.device_switches
+			.iter()
+			.map(|d| DeviceSwitchResponse {
+				plugin: d.plugin,
+				device_id: &d.device_id,
+				switches: d.switches,
+				average_ms: d.average_ms(),
+				last_ms: d.last_ms,
+			})
+			.collect(),
+	})
+}
+
+/// `/v1/gps?window=<secs>`, combined and per-device GPS, averaged over
+/// the window if given
+fn gps(stats: &Stats, query: &HashMap<String, String>) -> Response {
+	let window = match query.get("window") {
+		Some(w) => match w.parse::<i64>() {
+			Ok(w) if w > 0 => Some(w),
+			_ => return Response::error(400, "window must be a number of seconds"),
+		},
+		None => None,
+	};
+	Response::json(&stats.mining_stats.rig_gps(window, stats.clock_now()))
+}
+
+/// `/v1/history`
+#[derive(Serialize)]
+struct HistoryResponse<'a> {
+	start_time: i64,
+	uptime: i64,
+	history: &'a HistoryStats,
+}
+
+fn history(stats: &Stats) -> Response {
+	Response::json(&HistoryResponse {
+		start_time: stats.session_stats.start_time,
+		uptime: stats.session_stats.uptime(stats.clock_now()),
+		history: &stats.history,
+	})
+}
+
+/// A metric label, its name restricted to the characters Prometheus
+/// allows and its value escaped
+fn metric_label(name: &str, value: &str) -> String {
+	let name: String = name
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+		.collect();
+	let value = value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n");
+	format!("{}=\"{}\"", name, value)
+}
+
+/// `/metrics`, in the Prometheus text exposition format
+fn metrics(stats: &Stats) -> Response {
+	let mut out = String::new();
+	// the rig name and labels go on every metric
+	let mut rig_labels = vec![];
+	if let Some(ref name) = stats.rig.name {
+		rig_labels.push(metric_label("rig", name));
+	}
+	for (name, value) in &stats.rig.labels {
+		rig_labels.push(metric_label(name, value));
+	}
+	{
+		let mut gauge = |name: &str, help: &str, values: Vec<(String, f64)>| {
+			out.push_str(&format!("# HELP grin_miner_{} {}\n", name, help));
+			out.push_str(&format!("# TYPE grin_miner_{} gauge\n", name));
+			for (labels, value) in values {
+				let mut all = rig_labels.clone();
+				if !labels.is_empty() {
+					all.push(labels);
+				}
+				let labels = if all.is_empty() {
+					String::new()
+				} else {
+					format!("{{{}}}", all.join(","))
+				};
+				out.push_str(&format!("grin_miner_{}{} {}\n", name, labels, value));
+			}
+		};
+		let device_labels = |plugin: usize, device_id: &str| {
+			let mut labels = format!(
+				"{},{}",
+				metric_label("plugin", &plugin.to_string()),
+				metric_label("device", device_id)
+			);
+			if let Some(uid) = stats.mining_stats.device_uid(plugin, device_id) {
+				labels.push_str(&format!(",{}", metric_label("uid", uid)));
+			}
+			labels
+		};
+		let client_stats = &stats.client_stats;
+		gauge(
+			"connected",
+			"Whether connected to the stratum server",
+			vec![(String::new(), if client_stats.connected { 1.0 } else { 0.0 })],
+		);
+		gauge(
+			"gps",
+			"Combined graphs per second",
+			vec![(String::new(), stats.mining_stats.combined_gps)],
+		);
+		gauge(
+			"algorithm_gps",
+			"Combined graphs per second of each algorithm",
+			stats
+				.mining_stats
+				.algorithm_gps
+				.iter()
+				.map(|a| (metric_label("algorithm", &a.0), a.1))
+				.collect(),
+		);
+		gauge(
+			"shares",
+			"Shares submitted this session, by result",
+			vec![
+				(metric_label("result", "accepted"), client_stats.shares_accepted as f64),
+				(metric_label("result", "rejected"), client_stats.shares_rejected as f64),
+				(metric_label("result", "stale"), client_stats.shares_stale as f64),
+			],
+		);
+		let traffic = &client_stats.traffic;
+		gauge(
+			"pool_bytes",
+			"Bytes exchanged with the stratum server this session, by direction",
+			vec![
+				(metric_label("direction", "sent"), traffic.bytes_sent as f64),
+				(metric_label("direction", "received"), traffic.bytes_received as f64),
+			],
+		);
+		gauge(
+			"pool_messages",
+			"Messages exchanged with the stratum server this session, by direction",
+			vec![
+				(metric_label("direction", "sent"), traffic.messages_sent as f64),
+				(metric_label("direction", "received"), traffic.messages_received as f64),
+			],
+		);
+		let device_gps = stats
+			.mining_stats
+			.rig_gps(None, stats.clock_now())
+			.devices
+			.iter()
+			.filter(|d| d.gps > 0.0)
+			.map(|d| (device_labels(d.plugin, &d.device_id), d.gps))
+			.collect();
+		gauge("device_gps", "Graphs per second of each device", device_gps);
+		gauge(
+			"device_job_switch_ms",
+			"Average ms each device takes to switch to a new job",
+			stats
+				.timing
+				.device_switches
+				.iter()
+				.map(|d| (device_labels(d.plugin, &d.device_id), d.average_ms()))
+				.collect(),
+		);
+		let efficiency = stats.mining_stats.efficiency();
+		gauge(
+			"device_power_watts",
+			"Power draw of each device",
+			efficiency
+				.devices
+				.iter()
+				.map(|d| (device_labels(d.plugin, &d.device_id), d.power_usage))
+				.collect(),
+		);
+		gauge(
+			"device_graphs_per_joule",
+			"Graphs per joule of each device",
+			efficiency
+				.devices
+				.iter()
+				.map(|d| (device_labels(d.plugin, &d.device_id), d.graphs_per_joule))
+				.collect(),
+		);
+		gauge(
+			"power_watts",
+			"Total power draw of the devices reporting it",
+			vec![(String::new(), efficiency.power_usage)],
+		);
+		gauge(
+			"graphs_per_joule",
+			"Combined graphs per joule of the devices reporting their power draw",
+			vec![(String::new(), efficiency.graphs_per_joule)],
+		);
+		if let Some(cost) = efficiency.daily_cost {
+			gauge(
+				"daily_power_cost",
+				"Estimated electricity cost per day at the current power draw",
+				vec![(String::new(), cost)],
+			);
+		}
+	}
+	Response::text(out)
+}
+
+/// `/v1/history?from=&to=`, from the history store
+fn stored_history(store_path: &str, query: &HashMap<String, String>) -> Response {
+	let from = match query.get("from").map(|f| f.parse::<i64>()) {
+		Some(Ok(f)) => f,
+		Some(Err(_)) => return Response::error(400, "invalid from"),
+		None => 0,
+	};
+	let to = match query.get("to").map(|t| t.parse::<i64>()) {
+		Some(Ok(t)) => t,
+		Some(Err(_)) => return Response::error(400, "invalid to"),
+		None => time::get_time().sec,
+	};
+	match store::query(store_path, from, to) {
+		Ok(h) => Response::json(&h),
+		Err(e) => Response::error(500, &e),
+	}
+}
+
+/// Parameters of a query string
+fn parse_query(query: &str) -> HashMap<String, String> {
+	query
+		.split('&')
+		.filter_map(|p| {
+			let mut kv = p.splitn(2, '=');
+			match (kv.next(), kv.next()) {
+				(Some(k), Some(v)) if !k.is_empty() => Some((k.to_string(), v.to_string())),
+				_ => None,
+			}
+		})
+		.collect()
+}
+
+/// A single `/healthz` check
+#[derive(Serialize)]
+struct HealthCheck {
+	name: &'static str,
+	ok: bool,
+	message: String,
+}
+
+/// `/healthz`
+#[derive(Serialize)]
+struct HealthResponse {
+	healthy: bool,
+	checks: Vec<HealthCheck>,
+}
+
+/// 200 if connected, solving and free of recent device errors, 503 with
+/// the failing checks otherwise
+fn health(stats: &Stats, error_window_mins: u64) -> Response {
+	let mut checks = vec![];
+	checks.push(HealthCheck {
+		name: "pool_connected",
+		ok: stats.client_stats.connected,
+		message: stats.client_stats.connection_status.clone(),
+	});
+	let solving = match stats.mining_stats.device_stats {
+		Some(ref plugins) => plugins
+			.iter()
+			.flat_map(|p| p.iter())
+			.filter(|d| d.in_use == 1 && d.has_errored == 0 && d.last_solution_time > 0)
+			.count(),
+		None => 0,
+	};
+	checks.push(HealthCheck {
+		name: "devices_solving",
+		ok: solving > 0 && !stats.mining_stats.paused,
+		message: if stats.mining_stats.paused {
+			"mining is paused".to_string()
+		} else {
+			format!("{} devices solving", solving)
+		},
+	});
+	let since = time::get_time().sec - error_window_mins as i64 * 60;
+	let errored = stats
+		.history
+		.restarts
+		.iter()
+		.filter(|r| r.time >= since && r.reason == "device errored")
+		.count();
+	checks.push(HealthCheck {
+		name: "no_device_errors",
+		ok: errored == 0,
+		message: format!(
+			"{} device errors in the last {} minutes",
+			errored, error_window_mins
+		),
+	});
+	let healthy = checks.iter().all(|c| c.ok);
+	let mut response = Response::json(&HealthResponse {
+		healthy: healthy,
+		checks: checks,
+	});
+	if !healthy && response.code == 200 {
+		response.code = 503;
+	}
+	response
+}
+
+/// An HTTP request, only what the API needs of it
+struct Request {
+	method: String,
+	path: String,
+	authorization: Option<String>,
+	body: String,
+}
+
+/// The API server
+pub struct Server {
+	config: ApiConfig,
+	stats: Arc<RwLock<Stats>>,
+	control: Control,
+	history_store: Option<String>,
+}
+
+impl Server {
+	/// Create a new API server
+	pub fn new(config: ApiConfig, stats: Arc<RwLock<Stats>>, control: Control) -> Server {
+		Server {
+			config: config,
+			stats: stats,
+			control: control,
+			history_store: None,
+		}
+	}
+
+	/// Serve `/v1/history` queries from the history store at the given path
+	pub fn set_history_store(&mut self, path: String) {
+		self.history_store = Some(path);
+	}
+
+	/// Start serving in a new thread
+	pub fn start(self) {
+		let listener = match TcpListener::bind(&self.config.bind_address) {
+			Ok(l) => l,
+			Err(e) => {
+				error!(
+					LOGGER,
+					"Unable to start API server on {}: {}", self.config.bind_address, e
+				);
+				return;
+			}
+		};
+		info!(LOGGER, "API server listening on {}", self.config.bind_address);
+		let _ = thread::Builder::new()
+			.name("api_server".to_string())
+			.spawn(move || {
+				for stream in listener.incoming() {
+					match stream {
+						Ok(s) => {
+							if let Err(e) = self.handle_connection(s) {
+								debug!(LOGGER, "API connection error: {}", e);
+							}
+						}
+						Err(e) => debug!(LOGGER, "API connection error: {}", e),
+					}
+				}
+			});
+	}
+
+	fn handle_connection(&self, stream: TcpStream) -> Result<(), ::std::io::Error> {
+		stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+		let mut reader = BufReader::new(stream.try_clone()?);
+		let mut request_line = String::new();
+		reader.read_line(&mut request_line)?;
+		let mut parts = request_line.split_whitespace();
+		let mut request = Request {
+			method: parts.next().unwrap_or("").to_string(),
+			path: parts.next().unwrap_or("").to_string(),
+			authorization: None,
+			body: String::new(),
+		};
+		let mut content_length = 0;
+		loop {
+			let mut header = String::new();
+			if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+				break;
+			}
+			let mut header = header.splitn(2, ':');
+			let name = header.next().unwrap_or("").trim().to_lowercase();
+			let value = header.next().unwrap_or("").trim().to_string();
+			match name.as_str() {
+				"content-length" => content_length = value.parse::<u64>().unwrap_or(0),
+				"authorization" => request.authorization = Some(value),
+				_ => {}
+			}
+		}
+		// only authorized control requests have a body worth reading, and
+		// only up to a point
+		let mut too_large = false;
+		if request.method == "POST" && request.path.starts_with("/v1/control/")
+			&& self.authorized(&request)
+		{
+			if content_length > MAX_BODY_BYTES {
+				too_large = true;
+			} else {
+				reader
+					.take(content_length)
+					.read_to_string(&mut request.body)?;
+			}
+		}
+		if request.method == "GET" && request.path.split('?').next() == Some("/v1/stream") {
+			// long lived, served on a thread of its own
+			let interval = request
+				.path
+				.find('?')
+				.and_then(|i| parse_query(&request.path[i + 1..]).remove("interval_ms"))
+				.and_then(|i| i.parse::<u64>().ok())
+				.unwrap_or(stream::DEFAULT_INTERVAL_MS)
+				.max(stream::MIN_INTERVAL_MS);
+			stream::serve(stream, self.stats.clone(), interval);
+			return Ok(());
+		}
+		let response = if too_large {
+			Response::error(413, "request body too large")
+		} else {
+			self.handle_request(&request)
+		};
+		let mut stream = stream;
+		write!(
+			stream,
+			"HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			response.code,
+			response.reason(),
+			response.content_type,
+			response.body.len(),
+			response.body
+		)?;
+		stream.flush()
+	}
+
+	fn handle_request(&self, request: &Request) -> Response {
+		if request.path.starts_with("/v1/control/") {
+			if request.method != "POST" {
+				return Response::error(405, "method not allowed");
+			}
+			if !self.authorized(request) {
+				return Response::error(401, "unauthorized");
+			}
+			return self
+				.control
+				.handle(&request.path["/v1/control/".len()..], &request.body);
+		}
+		if request.method != "GET" {
+			return Response::error(405, "method not allowed");
+		}
+		let (path, query) = match request.path.find('?') {
+			Some(i) => (&request.path[..i], parse_query(&request.path[i + 1..])),
+			None => (request.path.as_str(), HashMap::new()),
+		};
+		if path == "/v1/history" && !query.is_empty() {
+			return match self.history_store {
+				Some(ref store_path) => stored_history(store_path, &query),
+				None => Response::error(404, "history store not enabled"),
+			};
+		}
+		if path == "/v1/stats" {
+			// everything, raw stratum messages included, for attaching
+			// a TUI remotely
+			if !self.authorized(request) {
+				return Response::error(401, "unauthorized");
+			}
+			return Response::json(&*self.stats.read().unwrap());
+		}
+		let stats = self.stats.read().unwrap();
+		match path {
+			"/v1/status" => status(&stats),
+			"/v1/devices" => devices(&stats),
+			"/v1/pools" => pools(&stats),
+			"/v1/shares" => shares(&stats),
+			"/v1/history" => history(&stats),
+			"/v1/blocks" => Response::json(&stats.blocks),
+			"/v1/environment" => Response::json(&stats.environment),
+			"/v1/gps" => gps(&stats, &query),
+			"/healthz" => health(&stats, self.config.health_error_window_mins),
+			"/metrics" => metrics(&stats),
+			_ => Response::error(404, "not found"),
+		}
+	}
+
+	/// Control requests (and the full stats) need the configured token as
+	/// bearer token, and are refused altogether if there's no token
+	/// configured
+	fn authorized(&self, request: &Request) -> bool {
+		match (&self.config.control_token, &request.authorization) {
+			(&Some(ref token), &Some(ref auth)) => *auth == format!("Bearer {}", token),
+			_ => false,
+		}
+	}
+}
@@ -0,0 +1,455 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hardware detection, and automatic plugin and parameter selection for
+//! the GPUs and memory found
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::process::Command;
+use std::thread;
+
+use config::types::CuckooMinerPluginConfig;
+use config::MinerConfig;
+use plugin;
+use util::cuckoo_miner::CuckooPluginCapabilities;
+use util::LOGGER;
+
+/// GPU plugins, fastest first, with the GPU memory (MB) each needs at
+/// the given edge bits
+const GPU_PLUGINS: &[(&str, u8, u64)] = &[
+	("cuckatoo_cuda", 31, 11000),
+	("cuckatoo_ocl", 31, 8000),
+	("cuckatoo_cuda", 29, 4000),
+	("cuckatoo_ocl", 29, 4000),
+	("cuckatoo_lean_cuda", 31, 1000),
+	("cuckatoo_lean_cuda", 29, 500),
+];
+
+/// CPU plugins, fastest first, with the system memory (MB) each needs,
+/// and whether it needs avx2
+const CPU_PLUGINS: &[(&str, u8, u64, bool)] = &[
+	("cuckatoo_mean_cpu", 31, 11000, true),
+	("cuckatoo_mean_compat_cpu", 31, 11000, false),
+	("cuckatoo_lean_cpu", 31, 1000, false),
+	("cuckatoo_mean_cpu", 29, 3000, true),
+	("cuckatoo_mean_compat_cpu", 29, 3000, false),
+	("cuckatoo_lean_cpu", 29, 500, false),
+];
+
+/// Whether the processor supports avx2
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn has_avx2() -> bool {
+	is_x86_feature_detected!("avx2")
+}
+
+/// Whether the processor supports avx2
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub fn has_avx2() -> bool {
+	false
+}
+
+/// Threads for CPU plugins, leaving a core free for the rest of the system
+pub fn cpu_threads() -> usize {
+	thread::available_parallelism()
+		.map(|n| n.get())
+		.unwrap_or(2)
+		.saturating_sub(1)
+		.max(1)
+}
+
+/// A GPU found on the system
+#[derive(Debug, Clone)]
+pub struct Gpu {
+	/// cuda or ocl
+	pub kind: &'static str,
+	/// OpenCL platform (amd or nvidia), none for CUDA devices
+	pub platform: Option<String>,
+	/// Device id, as the plugins number them
+	pub device: u32,
+	/// Device name
+	pub name: String,
+	/// Memory in MB
+	pub memory_mb: u64,
+	/// Memory not already in use in MB, where the driver reports it
+	pub free_mb: Option<u64>,
+}
+
+/// Output of a command, if it runs and succeeds
+pub fn command_output(program: &str, args: &[&str]) -> Option<String> {
+	let output = Command::new(program).args(args).output().ok()?;
+	if !output.status.success() {
+		return None;
+	}
+	String::from_utf8(output.stdout).ok()
+}
+
+/// CUDA devices, as listed by nvidia-smi
+pub fn cuda_gpus() -> Vec<Gpu> {
+	let output = match command_output(
+		"nvidia-smi",
+		&["--query-gpu=index,name,memory.total,memory.free", "--format=csv,noheader,nounits"],
+	) {
+		Some(o) => o,
+		None => return vec![],
+	};
+	output
+		.lines()
+		.filter_map(|l| {
+			let fields: Vec<&str> = l.split(',').map(|f| f.trim()).collect();
+			if fields.len() < 3 {
+				return None;
+			}
+			Some(Gpu {
+				kind: "cuda",
+				platform: None,
+				device: fields[0].parse().ok()?,
+				name: fields[1].to_string(),
+				memory_mb: fields[2].parse().ok()?,
+				free_mb: fields.get(3).and_then(|f| f.parse().ok()),
+			})
+		})
+		.collect()
+}
+
+/// OpenCL GPUs, as listed by clinfo
+pub fn opencl_gpus() -> Vec<Gpu> {
+	let output = match command_output("clinfo", &["--raw"]) {
+		Some(o) => o,
+		None => return vec![],
+	};
+	// lines look like "[NV/0]  CL_DEVICE_GLOBAL_MEM_SIZE  8513978368"
+	let mut platforms: Vec<String> = vec![];
+	let mut devices: Vec<(usize, u32, HashMap<String, String>)> = vec![];
+	for line in output.lines() {
+		let line = line.trim();
+		if !line.starts_with('[') {
+			continue;
+		}
+		let end = match line.find(']') {
+			Some(e) => e,
+			None => continue,
+		};
+		let mut id = line[1..end].split('/');
+		let (platform, device) = match (id.next(), id.next().and_then(|d| d.parse::<u32>().ok())) {
+			(Some(p), Some(d)) => (p.to_string(), d),
+			_ => continue,
+		};
+		let mut rest = line[end + 1..].trim().splitn(2, char::is_whitespace);
+		let (key, value) = match (rest.next(), rest.next()) {
+			(Some(k), Some(v)) => (k.to_string(), v.trim().to_string()),
+			_ => continue,
+		};
+		let platform = match platforms.iter().position(|p| *p == platform) {
+			Some(i) => i,
+			None => {
+				platforms.push(platform);
+				platforms.len() - 1
+			}
+		};
+		match devices
+			.iter_mut()
+			.find(|d| d.0 == platform && d.1 == device)
+		{
+			Some(d) => {
+				d.2.insert(key, value);
+			}
+			None => {
+				let mut props = HashMap::new();
+				props.insert(key, value);
+				devices.push((platform, device, props));
+			}
+		}
+	}
+	devices
+		.into_iter()
+		.filter(|d| {
+			d.2.get("CL_DEVICE_TYPE")
+				.map(|t| t.contains("GPU"))
+				.unwrap_or(false)
+		})
+		.filter_map(|(_, device, props)| {
+			let vendor = props.get("CL_DEVICE_VENDOR").cloned().unwrap_or_default();
+			let platform = if vendor.contains("Advanced Micro Devices") || vendor.contains("AMD") {
+				"amd"
+			} else if vendor.contains("NVIDIA") {
+				"nvidia"
+			} else {
+				return None;
+			};
+			Some(Gpu {
+				kind: "ocl",
+				platform: Some(platform.to_string()),
+				device: device,
+				name: props.get("CL_DEVICE_NAME").cloned().unwrap_or_default(),
+				memory_mb: props.get("CL_DEVICE_GLOBAL_MEM_SIZE")?.parse::<u64>().ok()? / 1024 / 1024,
+				// AMD only, the free total and largest free block in KB
+				free_mb: props
+					.get("CL_DEVICE_GLOBAL_FREE_MEMORY_AMD")
+					.and_then(|f| f.split_whitespace().next())
+					.and_then(|kb| kb.parse::<u64>().ok())
+					.map(|kb| kb / 1024),
+			})
+		})
+		.collect()
+}
+
+/// Total system memory in MB, where it can be found out
+pub fn system_memory_mb() -> Option<u64> {
+	let mut meminfo = String::new();
+	File::open("/proc/meminfo")
+		.and_then(|mut f| f.read_to_string(&mut meminfo))
+		.ok()?;
+	meminfo
+		.lines()
+		.find(|l| l.starts_with("MemTotal:"))
+		.and_then(|l| l.split_whitespace().nth(1))
+		.and_then(|kb| kb.parse::<u64>().ok())
+		.map(|kb| kb / 1024)
+}
+
+/// GPU memory (MB) the given plugin needs at the given edge bits, if known
+pub fn required_memory_mb(type_filter: &str, edge_bits: u8) -> Option<u64> {
+	GPU_PLUGINS
+		.iter()
+		.find(|&&(t, bits, _)| t == type_filter && bits == edge_bits)
+		.map(|&(_, _, mem)| mem)
+}
+
+/// Check the GPUs the GPU plugins are configured to mine on have the
+/// memory their solvers need, before any solver context is created,
+/// suggesting plugins or graph sizes that fit if not
+pub fn check_gpu_memory(config: &MinerConfig) -> Result<(), String> {
+	let mut problems = vec![];
+	let mut cuda = None;
+	let mut opencl = None;
+	for (i, p) in config.miner_plugin_config.iter().enumerate() {
+		let kind = if p.type_filter.contains("cuda") {
+			"cuda"
+		} else if p.is_opencl() {
+			"ocl"
+		} else {
+			continue;
+		};
+		let required = match required_memory_mb(&p.type_filter, p.edge_bits) {
+			Some(r) if config.sandboxed() => r * p.instances.unwrap_or(1).max(1) as u64,
+			Some(r) => r,
+			None => continue,
+		};
+		let gpus = if kind == "cuda" {
+			cuda.get_or_insert_with(cuda_gpus)
+		} else {
+			opencl.get_or_insert_with(opencl_gpus)
+		};
+		let gpus: Vec<&Gpu> = gpus
+			.iter()
+			.filter(|g| kind == "cuda" || p.platform.is_none() || g.platform == p.platform)
+			.collect();
+		if gpus.is_empty() {
+			// nothing to check against
+			continue;
+		}
+		let ids: Vec<u32> = gpus.iter().map(|g| g.device).collect();
+		let devices = match p.selected_devices(Some(&ids)) {
+			Ok(Some(selected)) => selected,
+			_ => match p.device_parameters {
+				Some(ref params) if !params.is_empty() => params
+					.iter()
+					.filter(|&(_, v)| v.get("USE_DEVICE").map_or(true, |u| *u != 0))
+					.filter_map(|(k, _)| k.parse::<u32>().ok())
+					.collect(),
+				_ => vec![0],
+			},
+		};
+		for gpu in gpus.iter().filter(|g| devices.contains(&g.device)) {
+			let available = gpu.free_mb.unwrap_or(gpu.memory_mb);
+			if available >= required {
+				continue;
+			}
+			let fits: Vec<String> = GPU_PLUGINS
+				.iter()
+				.filter(|&&(t, bits, mem)| {
+					t.contains(kind) && mem <= available && (t != p.type_filter || bits != p.edge_bits)
+				})
+				.map(|&(t, bits, _)| format!("{} with edge_bits = {}", t, bits))
+				.collect();
+			problems.push(format!(
+				"plugin {} ({}_{}) needs about {} MB of GPU memory, but {} device {} ({}) has {} MB {}; {}",
+				i,
+				p.type_filter,
+				p.edge_bits,
+				required,
+				kind,
+				gpu.device,
+				gpu.name,
+				available,
+				if gpu.free_mb.is_some() { "free" } else { "in all" },
+				if fits.is_empty() {
+					"no plugin fits on it".to_string()
+				} else {
+					format!("try lean mode or a smaller graph: {}", fits.join(", "))
+				}
+			));
+		}
+	}
+	if problems.is_empty() {
+		Ok(())
+	} else {
+		Err(format!("Not enough GPU memory:\n{}", problems.join("\n")))
+	}
+}
+
+fn find_plugin<'a>(
+	plugins: &'a [CuckooPluginCapabilities],
+	type_filter: &str,
+	edge_bits: u8,
+) -> Option<&'a CuckooPluginCapabilities> {
+	let name = format!("{}_{}", type_filter, edge_bits);
+	plugins.iter().find(|p| p.name.contains(&name))
+}
+
+fn has_param(plugin: &CuckooPluginCapabilities, name: &str) -> bool {
+	plugin.parameters.iter().any(|p| p.name == name)
+}
+
+/// Parameters for a GPU on the chosen plugin, sized to its memory
+fn gpu_params(gpu: &Gpu, plugin: &CuckooPluginCapabilities) -> HashMap<String, u32> {
+	let mut params = HashMap::new();
+	if has_param(plugin, "USE_DEVICE") {
+		params.insert("USE_DEVICE".to_string(), 1);
+	}
+	if has_param(plugin, "MEM_GB") {
+		let mem_gb = match gpu.memory_mb {
+			m if m >= 7500 => 7,
+			m if m >= 5500 => 5,
+			_ => 4,
+		};
+		params.insert("MEM_GB".to_string(), mem_gb);
+	}
+	if has_param(plugin, "EXPAND") {
+		// expanding trimmed edges saves memory on cards that only just fit
+		let expand = if gpu.memory_mb >= 11000 { 0 } else { 1 };
+		params.insert("EXPAND".to_string(), expand);
+	}
+	params
+}
+
+/// Choose a plugin and parameters for each GPU found (or the CPU if there
+/// are none), replacing the configured plugins
+pub fn auto_configure(config: &mut MinerConfig) -> Result<(), String> {
+	let chain = config.chain_type()?;
+	let plugins = plugin::available_plugins(config)?;
+	let mut gpus = cuda_gpus();
+	if gpus.is_empty() {
+		gpus = opencl_gpus();
+	}
+
+	let mut chosen: Vec<CuckooMinerPluginConfig> = vec![];
+	for gpu in &gpus {
+		let choice = GPU_PLUGINS.iter().find(|&&(t, bits, mem)| {
+			t.contains(gpu.kind) && gpu.memory_mb >= mem && chain.accepts(t, bits)
+				&& find_plugin(&plugins, t, bits).is_some()
+		});
+		let &(type_filter, edge_bits, _) = match choice {
+			Some(c) => c,
+			None => {
+				warn!(
+					LOGGER,
+					"Auto configuration: no installed plugin can mine on {} device {} ({}, {} MB)",
+					gpu.kind, gpu.device, gpu.name, gpu.memory_mb
+				);
+				continue;
+			}
+		};
+		let params = gpu_params(gpu, find_plugin(&plugins, type_filter, edge_bits).unwrap());
+		info!(
+			LOGGER,
+			"Auto configuration: {} device {} ({}, {} MB) mining with {}_{} {:?}",
+			gpu.kind, gpu.device, gpu.name, gpu.memory_mb, type_filter, edge_bits, params
+		);
+		let pos = match chosen
+			.iter()
+			.position(|p| {
+				p.type_filter == type_filter && p.edge_bits == edge_bits && p.platform == gpu.platform
+			}) {
+			Some(p) => p,
+			None => {
+				chosen.push(CuckooMinerPluginConfig {
+					type_filter: type_filter.to_string(),
+					edge_bits: edge_bits,
+					devices: None,
+					exclude_devices: None,
+					platform: gpu.platform.clone(),
+					instances: None,
+					intensity: None,
+					sha256: None,
+					cpu_affinity: None,
+					enabled: None,
+					command: None,
+					device_parameters: Some(HashMap::new()),
+				});
+				chosen.len() - 1
+			}
+		};
+		chosen[pos]
+			.device_parameters
+			.as_mut()
+			.unwrap()
+			.insert(gpu.device.to_string(), params);
+	}
+
+	if chosen.is_empty() {
+		let memory = system_memory_mb();
+		let avx2 = has_avx2();
+		let choice = CPU_PLUGINS.iter().find(|&&(t, bits, mem, needs_avx2)| {
+			memory.map(|m| m >= mem).unwrap_or(mem <= 3000) && (avx2 || !needs_avx2)
+				&& chain.accepts(t, bits) && find_plugin(&plugins, t, bits).is_some()
+		});
+		let &(type_filter, edge_bits, _, _) = match choice {
+			Some(c) => c,
+			None => return Err("Auto configuration: no installed plugin can mine on this machine".to_string()),
+		};
+		let threads = cpu_threads() as u32;
+		info!(
+			LOGGER,
+			"Auto configuration: no usable GPUs, mining on the CPU ({} MB memory{}) with {}_{}, {} threads",
+			memory.map(|m| m.to_string()).unwrap_or("unknown".to_string()),
+			if avx2 { ", avx2" } else { "" },
+			type_filter,
+			edge_bits,
+			threads
+		);
+		let mut device = HashMap::new();
+		device.insert("NUM_THREADS".to_string(), threads);
+		let mut device_parameters = HashMap::new();
+		device_parameters.insert("0".to_string(), device);
+		chosen.push(CuckooMinerPluginConfig {
+			type_filter: type_filter.to_string(),
+			edge_bits: edge_bits,
+			devices: None,
+			exclude_devices: None,
+			platform: None,
+			instances: None,
+			intensity: None,
+			sha256: None,
+			cpu_affinity: None,
+			enabled: None,
+			command: None,
+			device_parameters: Some(device_parameters),
+		});
+	}
+	config.miner_plugin_config = chosen;
+	Ok(())
+}
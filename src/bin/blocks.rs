@@ -0,0 +1,87 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Record of the blocks found solo mining, one JSON object per line, kept
+//! across sessions
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use serde_json::{self, Value};
+
+use config::MinerConfig;
+use stats::FoundBlock;
+use util::LOGGER;
+
+/// Found blocks file, unless configured with found_blocks_file
+pub const DEFAULT_BLOCKS_FILE: &str = "grin-miner-blocks.jsonl";
+
+/// Path of the found blocks file, empty if they're not recorded
+pub fn path(config: &MinerConfig) -> String {
+	config
+		.found_blocks_file
+		.clone()
+		.unwrap_or(DEFAULT_BLOCKS_FILE.to_string())
+}
+
+/// Hash of the block in a node's answer to a submit finding it, as in
+/// "blockfound - 0a1b..."
+pub fn block_hash(result: &Value) -> Option<String> {
+	result
+		.as_str()
+		.and_then(|r| r.splitn(2, " - ").nth(1))
+		.map(|h| h.trim().to_string())
+		.filter(|h| !h.is_empty())
+}
+
+/// Read the blocks recorded, none if there's no file or it's switched off
+/// (found_blocks_file = "")
+pub fn load(path: &str) -> Vec<FoundBlock> {
+	if path.is_empty() {
+		return vec![];
+	}
+	let file = match File::open(path) {
+		Ok(f) => f,
+		Err(_) => return vec![],
+	};
+	let mut blocks = vec![];
+	for (i, line) in BufReader::new(file).lines().enumerate() {
+		let line = match line {
+			Ok(l) => l,
+			Err(_) => break,
+		};
+		if line.trim().is_empty() {
+			continue;
+		}
+		match serde_json::from_str(&line) {
+			Ok(b) => blocks.push(b),
+			Err(e) => warn!(LOGGER, "Ignoring line {} of {}: {}", i + 1, path, e),
+		}
+	}
+	blocks
+}
+
+/// Add a block to the file, unless it's switched off
+pub fn record(path: &str, block: &FoundBlock) -> Result<(), String> {
+	if path.is_empty() {
+		return Ok(());
+	}
+	let line = serde_json::to_string(block).map_err(|e| e.to_string())?;
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.and_then(|mut f| writeln!(f, "{}", line))
+		.map_err(|e| format!("Unable to write {}: {}", path, e))
+}
@@ -0,0 +1,189 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reject rate circuit breaker. A pool rejecting most shares (a difficulty
+//! mix-up, a protocol change) has the rig burn power for nothing, so when
+//! the reject rate over the latest shares goes over the maximum the
+//! breaker trips: it fails over to the next pool configured, or pauses
+//! mining after the last one. After the retry time it goes back to the
+//! first pool, resuming mining.
+
+use std::collections::VecDeque;
+
+use clock;
+use config::{FailoverPool, RejectBreakerConfig};
+use stats::BreakerStats;
+
+/// What the client has to do for the breaker
+#[derive(Debug, PartialEq)]
+pub enum Action {
+	/// Switch to the pool
+	Failover(FailoverPool),
+	/// Stop mining
+	Pause,
+	/// Go back to the first pool, if it's not the current one, resuming
+	/// mining if the breaker paused it
+	Retry {
+		first: Option<FailoverPool>,
+		resume: bool,
+	},
+}
+
+/// Watches the shares answered by the pool mined on
+pub struct RejectBreaker {
+	config: RejectBreakerConfig,
+	/// The configured pool, then the failover pools
+	pools: Vec<FailoverPool>,
+	/// Index of the pool mined on
+	current: usize,
+	/// Most shares of the current pool that may be rejected, in percent
+	max_percent: u32,
+	/// Whether the latest shares answered were rejected, oldest first
+	answers: VecDeque<bool>,
+	/// When to go back to the first pool, in ms, if tripped
+	retry_at: Option<i64>,
+	stats: BreakerStats,
+}
+
+impl RejectBreaker {
+	/// Create a breaker for the configured pool. Failover pools without a
+	/// login or password use the configured pool's.
+	pub fn new(
+		config: RejectBreakerConfig,
+		url: &str,
+		login: Option<String>,
+		password: Option<String>,
+	) -> RejectBreaker {
+		let mut pools = vec![FailoverPool {
+			url: url.to_string(),
+			login: login.clone(),
+			password: password.clone(),
+		}];
+		pools.extend(config.failover.iter().map(|p| FailoverPool {
+			url: p.url.clone(),
+			login: p.login.clone().or(login.clone()),
+			password: p.password.clone().or(password.clone()),
+		}));
+		let mut breaker = RejectBreaker {
+			config: config,
+			pools: pools,
+			current: 0,
+			max_percent: 0,
+			answers: VecDeque::new(),
+			retry_at: None,
+			stats: BreakerStats::default(),
+		};
+		breaker.set_pool(url, login, password);
+		breaker
+	}
+
+	/// Start over on the pool switched to. A pool other than the failover
+	/// ones becomes the first pool.
+	pub fn set_pool(&mut self, url: &str, login: Option<String>, password: Option<String>) {
+		self.current = match self.pools.iter().position(|p| p.url == url) {
+			Some(i) => i,
+			None => {
+				self.pools[0] = FailoverPool {
+					url: url.to_string(),
+					login: login,
+					password: password,
+				};
+				0
+			}
+		};
+		self.max_percent = self
+			.config
+			.pools
+			.get(url)
+			.cloned()
+			.unwrap_or(self.config.max_reject_percent);
+		self.answers.clear();
+		self.stats.reject_percent = 0.0;
+	}
+
+	/// Count a share the pool answered, tripping the breaker if too many
+	/// of the latest ones were rejected
+	pub fn answered(&mut self, rejected: bool) -> Option<Action> {
+		if !self.config.enabled || self.stats.paused {
+			return None;
+		}
+		if self.answers.len() >= self.config.window_shares.max(1) {
+			self.answers.pop_front();
+		}
+		self.answers.push_back(rejected);
+		let rejects = self.answers.iter().filter(|r| **r).count();
+		self.stats.reject_percent = rejects as f64 * 100.0 / self.answers.len() as f64;
+		if self.answers.len() < self.config.min_shares.max(1)
+			|| self.stats.reject_percent <= self.max_percent as f64
+		{
+			return None;
+		}
+		let reason = format!(
+			"{:.0}% of the last {} shares rejected by {}, over the {}% allowed",
+			self.stats.reject_percent,
+			self.answers.len(),
+			self.pools[self.current].url,
+			self.max_percent
+		);
+		let now = clock::mono_ms();
+		let retry_at = now + self.config.retry_minutes.max(1) as i64 * 60_000;
+		self.retry_at = Some(retry_at);
+		self.stats.open = true;
+		self.stats.trips += 1;
+		self.stats.since = Some(clock::to_wall(now / 1000));
+		self.stats.retry_at = Some(clock::to_wall(retry_at / 1000));
+		let action = match self.pools.get(self.current + 1) {
+			Some(next) => {
+				self.stats.reason = Some(format!("{}, failed over to {}", reason, next.url));
+				Action::Failover(next.clone())
+			}
+			None => {
+				self.stats.reason = Some(format!("{}, mining paused", reason));
+				self.stats.paused = true;
+				Action::Pause
+			}
+		};
+		self.answers.clear();
+		Some(action)
+	}
+
+	/// Once the retry time is up, close the breaker, going back to the
+	/// first pool
+	pub fn retry(&mut self) -> Option<Action> {
+		match self.retry_at {
+			Some(t) if t <= clock::mono_ms() => {}
+			_ => return None,
+		}
+		let resume = self.stats.paused;
+		self.retry_at = None;
+		self.stats.open = false;
+		self.stats.paused = false;
+		self.stats.since = None;
+		self.stats.retry_at = None;
+		let first = if self.current == 0 {
+			None
+		} else {
+			Some(self.pools[0].clone())
+		};
+		Some(Action::Retry {
+			first: first,
+			resume: resume,
+		})
+	}
+
+	/// Current state, for the client stats
+	pub fn stats(&self) -> BreakerStats {
+		self.stats.clone()
+	}
+}
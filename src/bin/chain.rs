@@ -0,0 +1,115 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proof of work rules of the chain mined: how a header is hashed into
+//! the keys of its graph and how a proof's difficulty is worked out.
+//! Grin forks with their own rules add an implementation of `Chain` and
+//! its name to `from_name`, and are picked with `pow_chain` in the config.
+
+use std::cmp;
+use std::sync::Arc;
+
+use blake2_rfc::blake2b::blake2b;
+
+/// Names of the chains supported
+pub const NAMES: &[&str] = &["grin", "mwc"];
+
+/// Proof of work rules of a chain
+pub trait Chain: Send + Sync {
+	/// Name, as given in the config
+	fn name(&self) -> &'static str;
+
+	/// Hash of a header, pre-proof-of-work then nonce, the siphash keys
+	/// of its graph are taken from
+	fn header_hash(&self, pre_pow: &[u8], nonce: u64) -> Vec<u8>;
+
+	/// Hash of a proof, its difficulty is worked out from
+	fn proof_hash(&self, edge_bits: u32, nonces: &[u32]) -> Vec<u8>;
+
+	/// Unscaled difficulty of a proof, i.e. the maximum target divided by
+	/// the first 8 bytes of the proof hash, what pools compare with the
+	/// share difficulty
+	fn share_difficulty(&self, edge_bits: u32, nonces: &[u32]) -> u64 {
+		let hash = self.proof_hash(edge_bits, nonces);
+		let mut hash_u64 = 0u64;
+		for b in &hash[0..8] {
+			hash_u64 = (hash_u64 << 8) | *b as u64;
+		}
+		let diff = (1u128 << 64) / cmp::max(1, hash_u64) as u128;
+		cmp::min(diff, u64::max_value() as u128) as u64
+	}
+}
+
+/// Grin's rules, as the node applies them
+pub struct Grin;
+
+impl Chain for Grin {
+	fn name(&self) -> &'static str {
+		"grin"
+	}
+
+	/// blake2b of the pre-proof-of-work and the nonce (big endian)
+	fn header_hash(&self, pre_pow: &[u8], nonce: u64) -> Vec<u8> {
+		let mut header = pre_pow.to_vec();
+		header.extend_from_slice(&nonce.to_be_bytes());
+		blake2b(32, &[], &header).as_bytes().to_vec()
+	}
+
+	/// The solution nonces packed into a bit vector of `edge_bits` bits
+	/// per nonce, hashed with blake2b
+	fn proof_hash(&self, edge_bits: u32, nonces: &[u32]) -> Vec<u8> {
+		let nonce_bits = edge_bits as usize;
+		let mut bits = vec![0u8; (nonce_bits * nonces.len() + 7) / 8];
+		for (n, nonce) in nonces.iter().enumerate() {
+			for bit in 0..nonce_bits {
+				if nonce & (1 << bit) != 0 {
+					let pos = n * nonce_bits + bit;
+					bits[pos / 8] |= 1 << (pos % 8);
+				}
+			}
+		}
+		blake2b(32, &[], &bits).as_bytes().to_vec()
+	}
+}
+
+/// MimbleWimble Coin, which kept grin's header and proof hashing
+pub struct Mwc;
+
+impl Chain for Mwc {
+	fn name(&self) -> &'static str {
+		"mwc"
+	}
+
+	fn header_hash(&self, pre_pow: &[u8], nonce: u64) -> Vec<u8> {
+		Grin.header_hash(pre_pow, nonce)
+	}
+
+	fn proof_hash(&self, edge_bits: u32, nonces: &[u32]) -> Vec<u8> {
+		Grin.proof_hash(edge_bits, nonces)
+	}
+}
+
+/// The chain of the given name, grin if none is given
+pub fn from_name(name: Option<&str>) -> Result<Arc<dyn Chain>, String> {
+	match name.map(|n| n.to_lowercase()) {
+		None => Ok(Arc::new(Grin)),
+		Some(ref n) if n == "grin" => Ok(Arc::new(Grin)),
+		Some(ref n) if n == "mwc" => Ok(Arc::new(Mwc)),
+		Some(n) => Err(format!(
+			"Unknown pow_chain {}, expected one of {}",
+			n,
+			NAMES.join(", ")
+		)),
+	}
+}
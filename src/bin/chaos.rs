@@ -0,0 +1,146 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Chaos mode (`--chaos`), for developers: the stratum client randomly
+//! drops the connection, delays and garbles the messages it receives, at
+//! the rates given, and can stall its submits, to exercise the reconnect,
+//! failover and share retry logic without a flaky pool
+
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+/// Default longest delay of a message, in milliseconds
+const DEFAULT_MAX_DELAY_MS: u64 = 5000;
+
+/// What to inject, each rate being the chance (0 to 1) for a message
+/// received to be affected
+#[derive(Clone, Debug)]
+pub struct Chaos {
+	/// Drop the connection instead of reading the message
+	pub disconnect_rate: f64,
+	/// Delay the message, up to max_delay_ms
+	pub delay_rate: f64,
+	pub max_delay_ms: u64,
+	/// Garble the message
+	pub malformed_rate: f64,
+	/// Hold every share this long before submitting it
+	pub submit_stall_ms: u64,
+	/// xorshift state
+	state: u64,
+}
+
+impl Chaos {
+	/// Parse the `--chaos` value, e.g.
+	/// `disconnect=0.01,delay=0.05,delay_ms=2000,malformed=0.01,submit_stall_ms=3000`
+	pub fn parse(spec: &str) -> Result<Chaos, String> {
+		let mut chaos = Chaos {
+			disconnect_rate: 0.0,
+			delay_rate: 0.0,
+			max_delay_ms: DEFAULT_MAX_DELAY_MS,
+			malformed_rate: 0.0,
+			submit_stall_ms: 0,
+			state: time::precise_time_ns() | 1,
+		};
+		for item in spec.split(',').map(|i| i.trim()).filter(|i| !i.is_empty()) {
+			let mut kv = item.splitn(2, '=');
+			let (key, value) = match (kv.next(), kv.next()) {
+				(Some(k), Some(v)) => (k.trim(), v.trim()),
+				_ => return Err(format!("Invalid chaos setting {}, expected key=value", item)),
+			};
+			if key == "delay_ms" {
+				chaos.max_delay_ms = value
+					.parse()
+					.map_err(|_| format!("Invalid chaos delay_ms {}", value))?;
+				continue;
+			}
+			if key == "submit_stall_ms" {
+				chaos.submit_stall_ms = value
+					.parse()
+					.map_err(|_| format!("Invalid chaos submit_stall_ms {}", value))?;
+				continue;
+			}
+			let rate = match value.parse::<f64>() {
+				Ok(r) if r >= 0.0 && r <= 1.0 => r,
+				_ => return Err(format!("Invalid chaos rate {}, expected 0 to 1", item)),
+			};
+			match key {
+				"disconnect" => chaos.disconnect_rate = rate,
+				"delay" => chaos.delay_rate = rate,
+				"malformed" => chaos.malformed_rate = rate,
+				_ => {
+					return Err(format!(
+						"Unknown chaos setting {}, expected disconnect, delay, delay_ms, \
+						 malformed or submit_stall_ms",
+						key
+					))
+				}
+			}
+		}
+		Ok(chaos)
+	}
+
+	/// A random number in [0, 1)
+	fn random(&mut self) -> f64 {
+		self.state ^= self.state << 13;
+		self.state ^= self.state >> 7;
+		self.state ^= self.state << 17;
+		(self.state >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// Whether to drop the connection now
+	pub fn disconnect(&mut self) -> bool {
+		let rate = self.disconnect_rate;
+		self.random() < rate
+	}
+
+	/// Maybe hold the message a while, returning the delay in ms
+	pub fn delay(&mut self) -> u64 {
+		let rate = self.delay_rate;
+		if self.random() >= rate {
+			return 0;
+		}
+		let ms = (self.random() * self.max_delay_ms as f64) as u64;
+		thread::sleep(Duration::from_millis(ms));
+		ms
+	}
+
+	/// Hold a share about to be submitted, returning the delay in ms
+	pub fn submit_stall(&mut self) -> u64 {
+		if self.submit_stall_ms > 0 {
+			thread::sleep(Duration::from_millis(self.submit_stall_ms));
+		}
+		self.submit_stall_ms
+	}
+
+	/// Maybe garble the message: cut it short, or replace it with
+	/// something that isn't JSON
+	pub fn malform(&mut self, message: String) -> Option<String> {
+		let rate = self.malformed_rate;
+		if self.random() >= rate {
+			return None;
+		}
+		let message = message.trim();
+		if self.random() < 0.5 && message.len() > 1 {
+			let cut = 1 + (self.random() * (message.len() - 1) as f64) as usize;
+			let cut = (cut..message.len())
+				.find(|i| message.is_char_boundary(*i))
+				.unwrap_or(message.len());
+			Some(format!("{}\n", &message[..cut]))
+		} else {
+			Some("chaos: not a stratum message\n".to_string())
+		}
+	}
+}
@@ -16,7 +16,8 @@
 //! stratum server
 
 use std;
-use std::io::{BufRead, ErrorKind, Write};
+use std::collections::HashMap;
+use std::io::{BufRead, ErrorKind};
 use std::net::TcpStream;
 use std::sync::{mpsc, Arc, RwLock};
 use std::thread;
@@ -25,26 +26,89 @@ use bufstream::BufStream;
 use serde_json;
 use time;
 
+use blocks;
+use breaker::{self, RejectBreaker};
+use chaos::Chaos;
+use clock;
+use correlation;
+use errors::{self, Action, Condition};
+use config::{RejectBreakerConfig, SubmitLimitConfig};
+use ledger::{LedgerEntry, ShareLedger};
+use signing;
+use solutions::SolutionBuffer;
 use stats;
+use submitter::Submitter;
+use throttle::{self, Submit, SubmitLimiter};
 use types;
+use units;
 use util::LOGGER;
 
+/// Time between blocks, in ms
+const BLOCK_TIME_MS: i64 = 60_000;
+
+/// How long before the next block is due, on top of the round trip time,
+/// to start polling for its job, in ms
+const PREFETCH_LEAD_MS: i64 = 2_000;
+
+/// Shortest time between job polls, in ms
+const PREFETCH_INTERVAL_MS: i64 = 1_000;
+
 #[derive(Debug)]
 pub enum Error {
 	ConnectionError(String),
 }
 
+/// Text of a pool notice, sent either as a string or as an object with a
+/// message (or text) field
+fn notice_text(params: &serde_json::Value) -> Option<String> {
+	let text = match *params {
+		serde_json::Value::String(ref s) => Some(s.as_str()),
+		serde_json::Value::Array(ref a) => a.first().and_then(|m| m.as_str()),
+		ref p => p["message"].as_str().or(p["text"].as_str()),
+	};
+	text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
 pub struct Controller {
 	_id: u32,
 	server_url: String,
 	server_login: Option<String>,
 	server_password: Option<String>,
 	stream: Option<BufStream<TcpStream>>,
+	/// Writes to the server, on a thread of its own
+	submitter: Submitter,
 	rx: mpsc::Receiver<types::ClientMessage>,
 	pub tx: mpsc::Sender<types::ClientMessage>,
 	miner_tx: mpsc::Sender<types::MinerMessage>,
 	last_request_id: u32,
 	stats: Arc<RwLock<stats::Stats>>,
+	dry_run: bool,
+	algorithms: Vec<String>,
+	rig_id: Option<String>,
+	chaos: Option<Chaos>,
+	submit_token: Option<String>,
+	minimum_difficulty: Option<u64>,
+	/// Signing scheme the pool agreed to at login
+	signing: Option<String>,
+	limiter: SubmitLimiter,
+	breaker: RejectBreaker,
+	/// When the requests awaiting an answer were sent, by method, in ms
+	pending_requests: HashMap<String, i64>,
+	/// Height and id of the job being mined
+	current_job: Option<(u64, u64)>,
+	/// When the current job arrived, in ms
+	last_job_ms: i64,
+	job_prefetch: bool,
+	/// Whether submits carry the share's correlation id
+	submit_correlation_id: bool,
+	/// Solutions found by the miner, taken as the client gets to them
+	solutions: Option<SolutionBuffer>,
+	/// Seconds to wait before logging in again, after a rejected login
+	login_retry_secs: Option<u64>,
+	next_prefetch_ms: i64,
+	/// File blocks found are recorded in, none if empty
+	blocks_file: String,
+	ledger: Option<ShareLedger>,
 }
 
 impl Controller {
@@ -59,22 +123,131 @@ impl Controller {
 		Ok(Controller {
 			_id: 0,
 			server_url: server_url.to_string(),
-			server_login: server_login,
-			server_password: server_password,
+			server_login: server_login.clone(),
+			server_password: server_password.clone(),
 			stream: None,
+			submitter: Submitter::start(stats.clone()),
 			tx: tx,
 			rx: rx,
 			miner_tx: miner_tx,
 			last_request_id: 0,
 			stats: stats,
+			dry_run: false,
+			algorithms: vec![],
+			rig_id: None,
+			chaos: None,
+			submit_token: None,
+			minimum_difficulty: None,
+			signing: None,
+			limiter: SubmitLimiter::new(SubmitLimitConfig::default(), server_url),
+			breaker: RejectBreaker::new(
+				RejectBreakerConfig::default(),
+				server_url,
+				server_login.clone(),
+				server_password.clone(),
+			),
+			pending_requests: HashMap::new(),
+			current_job: None,
+			last_job_ms: 0,
+			job_prefetch: false,
+			submit_correlation_id: false,
+			solutions: None,
+			login_retry_secs: None,
+			next_prefetch_ms: 0,
+			blocks_file: String::new(),
+			ledger: None,
 		})
 	}
 
+	/// Don't submit any shares, only log and count them
+	pub fn set_dry_run(&mut self, dry_run: bool) {
+		self.dry_run = dry_run;
+	}
+
+	/// Algorithms mined, sent at login so pools supporting it send jobs
+	/// for each of them
+	pub fn set_algorithms(&mut self, algorithms: Vec<String>) {
+		self.algorithms = algorithms;
+	}
+
+	/// Rig name, sent at login as the rig_id
+	pub fn set_rig_id(&mut self, rig_id: Option<String>) {
+		self.rig_id = rig_id;
+	}
+
+	/// Worker token to sign submits with, if the pool supports it
+	pub fn set_submit_token(&mut self, token: Option<String>) {
+		self.submit_token = token;
+	}
+
+	/// Share difficulty to suggest to the pool at login
+	pub fn set_minimum_difficulty(&mut self, difficulty: Option<u64>) {
+		self.minimum_difficulty = difficulty;
+		self.stats.write().unwrap().client_stats.suggested_difficulty = difficulty;
+	}
+
+	/// Record the blocks found in the given file, none if empty
+	pub fn set_blocks_file(&mut self, path: String) {
+		self.blocks_file = path;
+	}
+
+	/// Record the shares submitted and their answers in the given ledger
+	pub fn set_share_ledger(&mut self, ledger: Option<ShareLedger>) {
+		self.ledger = ledger;
+	}
+
+	/// Limits on the rate of submits
+	pub fn set_submit_limit(&mut self, config: SubmitLimitConfig) {
+		self.limiter = SubmitLimiter::new(config, &self.server_url);
+	}
+
+	/// Reject rate circuit breaker
+	pub fn set_reject_breaker(&mut self, config: RejectBreakerConfig) {
+		self.breaker = RejectBreaker::new(
+			config,
+			&self.server_url,
+			self.server_login.clone(),
+			self.server_password.clone(),
+		);
+	}
+
+	/// Poll for the next job from shortly before the next block is due,
+	/// for pools whose job pushes arrive late
+	pub fn set_job_prefetch(&mut self, job_prefetch: bool) {
+		self.job_prefetch = job_prefetch;
+	}
+
+	/// Take the solutions to submit from the miner's buffer
+	pub fn set_solution_buffer(&mut self, solutions: SolutionBuffer) {
+		self.solutions = Some(solutions);
+	}
+
+	/// Add the correlation id of each share to its submit
+	pub fn set_submit_correlation_id(&mut self, submit_correlation_id: bool) {
+		self.submit_correlation_id = submit_correlation_id;
+	}
+
+	/// Inject failures into the messages received, for testing
+	pub fn set_chaos(&mut self, chaos: Chaos) {
+		self.submitter.set_chaos(chaos.clone());
+		self.chaos = Some(chaos);
+	}
+
 	pub fn try_connect(&mut self) -> Result<(), Error> {
 		match TcpStream::connect(self.server_url.clone()) {
 			Ok(conn) => {
 				let _ = conn.set_nonblocking(true);
+				let writer = conn
+					.try_clone()
+					.map_err(|e| Error::ConnectionError(format!("{}", e)))?;
+				self.submitter.connect(writer);
 				self.stream = Some(BufStream::new(conn));
+				self.stats
+					.write()
+					.unwrap()
+					.client_stats
+					.traffic
+					.new_connection();
 				Ok(())
 			}
 			Err(e) => Err(Error::ConnectionError(format!("{}", e))),
@@ -92,6 +265,25 @@ impl Controller {
 				if line == "" {
 					return Err(Error::ConnectionError("broken pipe".to_string()));
 				}
+				if let Some(ref mut chaos) = self.chaos {
+					if chaos.disconnect() {
+						warn!(LOGGER, "Chaos: dropping the connection");
+						return Err(Error::ConnectionError("chaos disconnect".to_string()));
+					}
+					let ms = chaos.delay();
+					if ms > 0 {
+						warn!(LOGGER, "Chaos: delayed a message by {}ms", ms);
+					}
+					if let Some(malformed) = chaos.malform(line.clone()) {
+						warn!(LOGGER, "Chaos: malformed a message");
+						line = malformed;
+					}
+				}
+				{
+					let mut stats = self.stats.write().unwrap();
+					stats.client_stats.trace_message(false, &line);
+					stats.client_stats.traffic.record_received(line.len());
+				}
 				return Ok(Some(line));
 			}
 			Err(ref e) if e.kind() == ErrorKind::BrokenPipe => {
@@ -112,17 +304,7 @@ impl Controller {
 			return Err(Error::ConnectionError(String::from("No server connection")));
 		}
 		debug!(LOGGER, "sending request: {}", message);
-		let _ = self.stream
-			.as_mut()
-			.unwrap()
-			.write(message.as_bytes())
-			.unwrap();
-		let _ = self.stream
-			.as_mut()
-			.unwrap()
-			.write("\n".as_bytes())
-			.unwrap();
-		let _ = self.stream.as_mut().unwrap().flush().unwrap();
+		self.submitter.send(message.to_string());
 		Ok(())
 	}
 
@@ -138,6 +320,7 @@ impl Controller {
 			let mut stats = self.stats.write().unwrap();
 			stats.client_stats.last_message_sent = format!("Last Message Sent: Get New Job");
 		}
+		self.request_sent("getjobtemplate");
 		self.send_message(&req_str)
 	}
 
@@ -158,6 +341,17 @@ impl Controller {
 			login: login_str,
 			pass: password_str,
 			agent: "grin-miner".to_string(),
+			algorithms: if self.algorithms.is_empty() {
+				None
+			} else {
+				Some(self.algorithms.clone())
+			},
+			rig_id: self.rig_id.clone(),
+			signing: self
+				.submit_token
+				.as_ref()
+				.map(|_| vec![signing::HMAC_SHA256.to_string()]),
+			difficulty: self.minimum_difficulty,
 		};
 		let req = types::RpcRequest {
 			id: self.last_request_id.to_string(),
@@ -173,6 +367,37 @@ impl Controller {
 		self.send_message(&req_str)
 	}
 
+	/// Note when a request was sent, to time its answer. Requests are
+	/// answered in order, so the oldest unanswered one is kept.
+	fn request_sent(&mut self, method: &str) {
+		self.pending_requests
+			.entry(method.to_string())
+			.or_insert(clock::mono_ms());
+	}
+
+	/// Ask for the job again from shortly before the next block is due, so
+	/// the new job reaches the solvers as soon as the pool has it
+	fn prefetch_job(&mut self) {
+		if self.current_job.is_none() || self.pending_requests.contains_key("getjobtemplate") {
+			return;
+		}
+		let now = clock::mono_ms();
+		let rtt = self
+			.stats
+			.read()
+			.unwrap()
+			.client_stats
+			.pool_rtt_ms
+			.unwrap_or(0.0) as i64;
+		if now < self.last_job_ms + BLOCK_TIME_MS - rtt - PREFETCH_LEAD_MS
+			|| now < self.next_prefetch_ms
+		{
+			return;
+		}
+		let _ = self.send_message_get_job_template();
+		self.next_prefetch_ms = now + PREFETCH_INTERVAL_MS.max(rtt);
+	}
+
 	fn send_message_get_status(&mut self) -> Result<(), Error> {
 		let req = types::RpcRequest {
 			id: self.last_request_id.to_string(),
@@ -181,16 +406,32 @@ impl Controller {
 			params: None,
 		};
 		let req_str = serde_json::to_string(&req).unwrap();
+		self.request_sent("status");
 		self.send_message(&req_str)
 	}
 
 	fn send_message_submit(&mut self, height: u64, job_id: u64, edge_bits: u32, nonce: u64, pow: Vec<u32>) -> Result<(), Error> {
+		let signature = match (self.signing.as_ref(), self.submit_token.as_ref()) {
+			(Some(_), Some(token)) => {
+				let login = self.server_login.clone().unwrap_or_default();
+				let message = signing::share_message(&login, height, job_id, edge_bits, nonce, &pow);
+				Some(signing::sign(token, &message))
+			}
+			_ => None,
+		};
+		let cid = correlation::solution(height, job_id, nonce);
 		let params_in = types::SubmitParams {
 			height: height,
 			job_id: job_id,
 			edge_bits: edge_bits,
 			nonce: nonce,
 			pow: pow,
+			signature: signature,
+			correlation_id: if self.submit_correlation_id {
+				Some(cid.clone())
+			} else {
+				None
+			},
 		};
 		let params = serde_json::to_string(&params_in).unwrap();
 		let req = types::RpcRequest {
@@ -207,17 +448,204 @@ impl Controller {
 				params_in.height, params_in.nonce
 			);
 		}
-		self.send_message(&req_str)
+		if self.stream.is_none() {
+			return Err(Error::ConnectionError(String::from("No server connection")));
+		}
+		debug!(LOGGER, "submitting: {}", req_str; "job_id" => job_id, "cid" => cid);
+		self.submitter.submit(req_str, job_id, nonce);
+		Ok(())
+	}
+
+	/// The block a submit found, from the share answered if it's known
+	fn found_block(
+		&self,
+		stats: &stats::Stats,
+		share: Option<stats::ShareTiming>,
+		result: &serde_json::Value,
+	) -> stats::FoundBlock {
+		let (height, edge_bits, nonce, device) = match share {
+			Some(s) => (s.height, s.edge_bits, s.nonce, s.device),
+			None => (self.current_job.map_or(0, |j| j.0), 0, 0, None),
+		};
+		let uid = device
+			.as_ref()
+			.and_then(|d| stats.mining_stats.device_uid(d.0, &d.1))
+			.map(|u| u.to_string());
+		stats::FoundBlock {
+			height: height,
+			hash: blocks::block_hash(result),
+			time: time::get_time().sec,
+			edge_bits: edge_bits,
+			nonce: nonce,
+			device: device,
+			uid: uid,
+		}
+	}
+
+	/// Log the block found and add it to the blocks file
+	fn record_block(&self, block: &stats::FoundBlock) {
+		let found_by = match block.device {
+			Some((plugin, ref device)) => format!(" by plugin {} device {}", plugin, device),
+			None => String::new(),
+		};
+		info!(
+			LOGGER,
+			"Block Found!! Height {}, hash {}{}",
+			block.height,
+			block.hash.as_ref().map_or("unknown", |h| h.as_str()),
+			found_by
+		);
+		if let Err(e) = blocks::record(&self.blocks_file, block) {
+			error!(LOGGER, "Unable to record the block found: {}", e);
+		}
+	}
+
+	/// Add a share and what became of it to the ledger, if it's kept
+	fn record_share(&mut self, share: Option<&stats::ShareTiming>, result: &str, response: String) {
+		let ledger = match self.ledger {
+			Some(ref mut l) => l,
+			None => return,
+		};
+		let entry = LedgerEntry {
+			seq: 0,
+			time: time::get_time().sec,
+			pool: self.server_url.clone(),
+			login: self.server_login.clone().unwrap_or_default(),
+			height: share.map_or(0, |s| s.height),
+			job_id: share.map_or(0, |s| s.job_id),
+			nonce: share.map_or(0, |s| s.nonce),
+			edge_bits: share.map_or(0, |s| s.edge_bits),
+			difficulty: share.map_or(0, |s| s.target_difficulty),
+			result: result.to_string(),
+			response: response,
+			signature: String::new(),
+		};
+		if let Err(e) = ledger.record(entry) {
+			error!(LOGGER, "Unable to record the share in the ledger: {}", e);
+		}
+	}
+
+	/// Count a share answered by the pool against the reject rate, acting
+	/// on the breaker tripping
+	fn breaker_answered(&mut self, rejected: bool) {
+		let action = self.breaker.answered(rejected);
+		self.stats.write().unwrap().client_stats.breaker = self.breaker.stats();
+		if let Some(action) = action {
+			self.breaker_action(action);
+		}
+	}
+
+	/// Fail over, pause or go back to the first pool for the breaker
+	fn breaker_action(&mut self, action: breaker::Action) {
+		let reason = self.breaker.stats().reason.unwrap_or_default();
+		match action {
+			breaker::Action::Failover(pool) => {
+				error!(LOGGER, "Reject rate circuit breaker tripped: {}", reason);
+				let switch = types::ClientMessage::SwitchPool(pool.url, pool.login, pool.password);
+				let _ = self.tx.send(switch);
+			}
+			breaker::Action::Pause => {
+				error!(LOGGER, "Reject rate circuit breaker tripped: {}", reason);
+				let _ = self.miner_tx.send(types::MinerMessage::Pause);
+			}
+			breaker::Action::Retry { first, resume } => {
+				warn!(LOGGER, "Reject rate circuit breaker closed, retrying");
+				if let Some(pool) = first {
+					let switch =
+						types::ClientMessage::SwitchPool(pool.url, pool.login, pool.password);
+					let _ = self.tx.send(switch);
+				}
+				if resume {
+					let _ = self.miner_tx.send(types::MinerMessage::Resume);
+				}
+			}
+		}
+		self.stats.write().unwrap().client_stats.breaker = self.breaker.stats();
+	}
+
+	/// Log and keep a notice from the pool
+	fn pool_notice(&self, notice: &str) {
+		let mut stats = self.stats.write().unwrap();
+		stats.client_stats.add_notice(notice);
+		info!(
+			LOGGER,
+			"Pool notice at {}: {}",
+			units::date_time(time::get_time().sec),
+			notice
+		);
+	}
+
+	/// Queue a solution found to submit, unless on a dry run
+	fn found_solution(
+		&mut self,
+		height: u64,
+		job_id: u64,
+		edge_bits: u32,
+		nonce: u64,
+		pow: Vec<u32>,
+	) {
+		if self.dry_run {
+			info!(
+				LOGGER,
+				"Dry run, not submitting share for height {} (edge bits {}, nonce {})",
+				height, edge_bits, nonce;
+				"job_id" => job_id
+			);
+			self.stats.write().unwrap().client_stats.withheld_shares += 1;
+			return;
+		}
+		self.limiter.push(Submit {
+			height: height,
+			job_id: job_id,
+			edge_bits: edge_bits,
+			nonce: nonce,
+			pow: pow,
+		});
+	}
+
+	/// Submit the shares the rate limiter allows now
+	fn send_submits(&mut self) {
+		if self.stream.is_none() {
+			return;
+		}
+		while let Some(s) = self.limiter.next() {
+			if let Err(e) = self.send_message_submit(s.height, s.job_id, s.edge_bits, s.nonce, s.pow) {
+				error!(LOGGER, "Mining Controller Error {:?}", e);
+			}
+		}
+		let throttle = self.limiter.stats();
+		let mut stats = self.stats.write().unwrap();
+		if throttle.throttled != stats.client_stats.throttle.throttled {
+			if throttle.throttled {
+				warn!(LOGGER, "Submits throttled, {} held back", throttle.queued);
+			} else {
+				info!(LOGGER, "Submits no longer throttled");
+			}
+		}
+		stats.client_stats.throttle = throttle;
 	}
 
 	fn send_miner_job(&mut self, job: types::JobTemplate) -> Result<(), Error> {
+		self.current_job = Some((job.height, job.job_id));
+		self.last_job_ms = clock::mono_ms();
 		let miner_message =
-			types::MinerMessage::ReceivedJob(job.height, job.job_id, job.difficulty, job.pre_pow);
+			types::MinerMessage::ReceivedJob(job.height, job.job_id, job.difficulty, job.pre_pow, job.edge_bits);
 		let mut stats = self.stats.write().unwrap();
 		stats.client_stats.last_message_received = format!(
-			"Last Message Received: Start Job for Height: {}, Difficulty: {}",
-			job.height, job.difficulty
+			"Last Message Received: Start Job for Height: {}, Difficulty: {}{}",
+			job.height,
+			job.difficulty,
+			job.edge_bits.map_or(String::new(), |e| format!(", Edge Bits: {}", e))
 		);
+		stats
+			.history
+			.record_job(job.height, job.difficulty, job.edge_bits);
+		if job.network_difficulty.is_some() {
+			stats.mining_stats.network_difficulty = job.network_difficulty;
+		}
+		self.limiter.new_height(job.height);
+		stats.client_stats.throttle = self.limiter.stats();
+		stats.timing.job_received(job.job_id);
 		let _ = self.miner_tx.send(miner_message);
 		Ok(())
 	}
@@ -233,9 +661,21 @@ impl Controller {
 		let _ = match req.method.as_str() {
 			"job" => {
 				let job: types::JobTemplate = serde_json::from_value(req.params.unwrap()).unwrap();
-				info!(LOGGER, "Got a new job: {:?}", job);
+				info!(
+					LOGGER,
+					"Got a new job: {:?}", job;
+					"job_id" => job.job_id,
+					"cid" => correlation::job(job.height, job.job_id)
+				);
 				self.send_miner_job(job)
 			}
+			"notice" | "motd" | "message" => {
+				match req.params.as_ref().and_then(notice_text) {
+					Some(notice) => self.pool_notice(&notice),
+					None => warn!(LOGGER, "Empty notice from the pool: {:?}", req.params),
+				}
+				Ok(())
+			}
 			_ => Ok(()),
 		};
 		Ok(())
@@ -243,6 +683,10 @@ impl Controller {
 
 	pub fn handle_response(&mut self, res: types::RpcResponse) -> Result<(), Error> {
 		debug!(LOGGER, "Received response with id: {}", res.id);
+		if let Some(sent) = self.pending_requests.remove(&res.method) {
+			let rtt = (clock::mono_ms() - sent) as f64;
+			self.stats.write().unwrap().client_stats.add_rtt(rtt);
+		}
 		match res.method.as_str() {
 			// "status" response can be used to further populate stats object
 			"status" => {
@@ -265,6 +709,9 @@ impl Controller {
 						"Last Message Received: Accepted: {}, Rejected: {}, Stale: {}",
 						st.accepted, st.rejected, st.stale
 					);
+					stats.client_stats.shares_accepted = st.accepted;
+					stats.client_stats.shares_rejected = st.rejected;
+					stats.client_stats.shares_stale = st.stale;
 				} else {
 					let err = res.error.unwrap();
 					let mut stats = self.stats.write().unwrap();
@@ -279,16 +726,23 @@ impl Controller {
 				if res.result.is_some() {
 					let job: types::JobTemplate =
 						serde_json::from_value(res.result.unwrap()).unwrap();
+					if self.current_job == Some((job.height, job.job_id)) {
+						// polled for the next job before the pool had it
+						debug!(LOGGER, "Job {} unchanged", job.job_id);
+						return Ok(());
+					}
 					{
 						let mut stats = self.stats.write().unwrap();
 						stats.client_stats.last_message_received = format!(
 							"Last Message Received: Got job for block {} at difficulty {}",
 							job.height, job.difficulty
 						);
+						stats.client_stats.jobs_received += 1;
 					}
 					info!(
 						LOGGER,
-						"Got a job at height {} and difficulty {}", job.height, job.difficulty
+						"Got a job at height {} and difficulty {}", job.height, job.difficulty;
+						"job_id" => job.job_id
 					);
 					let _ = self.send_miner_job(job);
 				} else {
@@ -304,25 +758,63 @@ impl Controller {
 			}
 			// "submit" response
 			"submit" => {
+				let share = {
+					let mut stats = self.stats.write().unwrap();
+					stats.client_stats.submits_pending =
+						stats.client_stats.submits_pending.saturating_sub(1);
+					let share = stats.timing.answered(res.result.is_some());
+					if let Some(d) = share.as_ref().and_then(|s| s.device.as_ref()) {
+						stats.session_stats.add_device_share(d, res.result.is_some());
+					}
+					share
+				};
+				let cid = share.as_ref().map_or(String::new(), |s| s.cid.clone());
 				if res.result.is_some() {
-					info!(LOGGER, "Share Accepted!!");
+					info!(LOGGER, "Share Accepted!!"; "cid" => cid);
+					let result = serde_json::to_string(&res.result).unwrap();
+					let outcome = if result.contains("blockfound") { "block" } else { "accepted" };
+					self.record_share(share.as_ref(), outcome, result.clone());
+					self.breaker_answered(false);
 					let mut stats = self.stats.write().unwrap();
+					stats.client_stats.last_share_accepted = Some(time::get_time().sec);
 					stats.client_stats.last_message_received =
 						format!("Last Message Received: Share Accepted!!");
-					let result = serde_json::to_string(&res.result).unwrap();
 					if result.contains("blockfound") {
-						info!(LOGGER, "Block Found!!");
+						stats.client_stats.blocks_found += 1;
 						stats.client_stats.last_message_received =
 						format!("Last Message Received: Block Found!!");
+						let block = self.found_block(&stats, share, res.result.as_ref().unwrap());
+						stats.blocks.push(block.clone());
+						drop(stats);
+						self.record_block(&block);
 					}
 				} else {
 					let err = res.error.unwrap();
+					let response = serde_json::to_string(&err).unwrap();
+					self.record_share(share.as_ref(), "rejected", response);
+					self.breaker_answered(true);
+					if throttle::is_ban_error(&err) {
+						self.limiter.cool_down();
+						warn!(
+							LOGGER,
+							"The pool is throttling submits, holding them back for the cool-down"
+						);
+					}
 					let mut stats = self.stats.write().unwrap();
 					stats.client_stats.last_message_received = format!(
 						"Last Message Received: Failed to submit a solution: {:?}",
 						err
 					);
-					error!(LOGGER, "Failed to submit a solution: {:?}", err);
+					stats.client_stats.throttle = self.limiter.stats();
+					error!(
+						LOGGER,
+						"Failed to submit a solution: {:?}", err;
+						"cid" => cid,
+						"device" => share.as_ref().and_then(|s| s.device.as_ref()).map_or(
+							String::from("unknown"),
+							|d| format!("plugin {} device {}", d.0, d.1),
+						)
+					);
 				}
 				()
 			}
@@ -344,13 +836,62 @@ impl Controller {
 			// "login" response
 			"login" => {
 				if res.result.is_some() {
-					// Nothing to do for login "ok"
 					// dont update last_message_received with good login response
+					info!(LOGGER, "Logged in to stratum server");
+					self.stats.write().unwrap().client_stats.logged_in = true;
+					if let Some(motd) = res.result.as_ref().and_then(|r| notice_text(&r["motd"])) {
+						self.pool_notice(&motd);
+					}
+					// pools supporting signed submits name the scheme
+					// to use in the login result
+					self.signing = res.result
+						.as_ref()
+						.and_then(|r| r["signing"].as_str())
+						.filter(|s| *s == signing::HMAC_SHA256)
+						.map(|s| s.to_string());
+					if let Some(suggested) = self.minimum_difficulty {
+						// pools taking the suggestion answer with the
+						// difficulty they settled on
+						let negotiated = res.result.as_ref().and_then(|r| r["difficulty"].as_u64());
+						match negotiated {
+							Some(d) if d > suggested => warn!(
+								LOGGER,
+								"The pool set share difficulty {}, above the {} suggested", d, suggested
+							),
+							Some(d) => info!(LOGGER, "The pool set share difficulty {}", d),
+							None => info!(
+								LOGGER,
+								"The pool didn't answer the suggested share difficulty {}", suggested
+							),
+						}
+						self.stats.write().unwrap().client_stats.negotiated_difficulty = negotiated;
+					}
+					if self.submit_token.is_some() {
+						match self.signing {
+							Some(ref s) => info!(LOGGER, "Signing submits with {}", s),
+							None => warn!(
+								LOGGER,
+								"The pool doesn't support signed submits, submitting unsigned"
+							),
+						}
+					}
 				} else {
-					// This is a fatal error
+					// the failure policy says whether to give up
 					let err = res.error.unwrap();
 					error!(LOGGER, "Failed to log in: {:?}", err);
-					panic!("Failed to log in to stratum server: {:?}", err);
+					match errors::action(Condition::PoolAuthRejected) {
+						Action::Exit(code) => errors::exit_on(Condition::PoolAuthRejected, code),
+						Action::Retry(secs) => {
+							warn!(LOGGER, "Logging in to {} again in {}s", self.server_url, secs);
+							self.stream = None;
+							self.login_retry_secs = Some(secs);
+							let mut stats = self.stats.write().unwrap();
+							stats.history.record_connection_drop("login rejected");
+							stats.client_stats.connection_status =
+								format!("Connection Status: Login rejected by {}", self.server_url);
+							stats.client_stats.logged_in = false;
+						}
+					}
 				}
 			}
 			// unknown method response
@@ -367,12 +908,10 @@ impl Controller {
 	}
 
 	pub fn run(mut self) {
-		let server_read_interval = 1;
 		let server_retry_interval = 5;
-		let mut next_server_read = time::get_time().sec + server_read_interval;
 		let status_interval = 30;
-		let mut next_status_request = time::get_time().sec + status_interval;
-		let mut next_server_retry = time::get_time().sec;
+		let mut next_status_request = clock::mono_secs() + status_interval;
+		let mut next_server_retry = clock::mono_secs();
 		// Request the first job template
 		thread::sleep(std::time::Duration::from_secs(1));
 		let mut was_disconnected = true;
@@ -382,9 +921,20 @@ impl Controller {
 			if let None = self.stream {
 				if !was_disconnected {
 					let _ = self.send_miner_stop();
+					self.pending_requests.clear();
+					self.current_job = None;
+					// answers to shares sent won't come now
+					let lost = {
+						let mut stats = self.stats.write().unwrap();
+						stats.client_stats.submits_pending = 0;
+						stats.timing.connection_lost()
+					};
+					for share in &lost {
+						self.record_share(Some(share), "unanswered", String::new());
+					}
 				}
 				was_disconnected = true;
-				if time::get_time().sec > next_server_retry {
+				if clock::mono_secs() > next_server_retry {
 					if let Err(_) = self.try_connect() {
 						let status = format!("Connection Status: Can't establish server connection to {}. Will retry every {} seconds",
 							self.server_url,
@@ -393,6 +943,11 @@ impl Controller {
 						let mut stats = self.stats.write().unwrap();
 						stats.client_stats.connection_status = status;
 						stats.client_stats.connected = false;
+						stats.client_stats.logged_in = false;
+						if stats.client_stats.disconnected_since.is_none() {
+							stats.client_stats.disconnected_since = Some(time::get_time().sec);
+							stats.client_stats.disconnected_mono = Some(clock::mono_secs());
+						}
 					} else {
 						let status = format!(
 							"Connection Status: Connected to Grin server at {}.",
@@ -401,8 +956,11 @@ impl Controller {
 						warn!(LOGGER, "{}", status);
 						let mut stats = self.stats.write().unwrap();
 						stats.client_stats.connection_status = status;
+						stats.client_stats.disconnected_since = None;
+						stats.client_stats.disconnected_mono = None;
+						stats.history.record_reconnect();
 					}
-					next_server_retry = time::get_time().sec + server_retry_interval;
+					next_server_retry = clock::mono_secs() + server_retry_interval;
 				}
 			} else {
 				// get new job template
@@ -411,52 +969,84 @@ impl Controller {
 					let _ = self.send_message_get_job_template();
 					was_disconnected = false;
 				}
-				// read messages from server
-				if time::get_time().sec > next_server_read {
-					match self.read_message() {
-						Ok(message) => {
-							match message {
-								Some(m) => {
-									{
-										let mut stats = self.stats.write().unwrap();
-										stats.client_stats.connected = true;
-									}
-									// figure out what kind of message,
-									// and dispatch appropriately
-									debug!(LOGGER, "Received message: {}", m);
-									// Deserialize to see what type of object it is
-									let v: serde_json::Value = serde_json::from_str(&m).unwrap();
-									// Is this a response or request?
-									if v["id"] == String::from("Stratum") {
-										// this is a request
-										let request: types::RpcRequest =
-											serde_json::from_str(&m).unwrap();
-										let _ = self.handle_request(request);
-										continue;
-									} else {
-										// this is a response
-										let response: types::RpcResponse =
-											serde_json::from_str(&m).unwrap();
-										let _ = self.handle_response(response);
+				// read messages from server, all those waiting
+				match self.read_message() {
+					Ok(message) => {
+						match message {
+							Some(m) => {
+								{
+									let mut stats = self.stats.write().unwrap();
+									stats.client_stats.connected = true;
+								}
+								// figure out what kind of message,
+								// and dispatch appropriately
+								debug!(LOGGER, "Received message: {}", m);
+								// Deserialize to see what type of object it is
+								let v: serde_json::Value = match serde_json::from_str(&m) {
+									Ok(v) => v,
+									Err(e) => {
+										error!(LOGGER, "Invalid message from server, ignored: {}", e);
 										continue;
 									}
+								};
+								// Is this a response or request?
+								if v["id"] == String::from("Stratum") {
+									// this is a request
+									match serde_json::from_value::<types::RpcRequest>(v) {
+										Ok(request) => {
+											let _ = self.handle_request(request);
+										}
+										Err(e) => error!(LOGGER, "Invalid request from server, ignored: {}", e),
+									}
+									continue;
+								} else {
+									// this is a response
+									match serde_json::from_value::<types::RpcResponse>(v) {
+										Ok(response) => {
+											let _ = self.handle_response(response);
+											if let Some(secs) = self.login_retry_secs.take() {
+												next_server_retry = clock::mono_secs() + secs;
+											}
+										}
+										Err(e) => error!(LOGGER, "Invalid response from server, ignored: {}", e),
+									}
+									continue;
 								}
-								None => {} // No messages from the server at this time
 							}
+							None => {} // No messages from the server at this time
 						}
-						Err(e) => {
-							error!(LOGGER, "Error reading message: {:?}", e);
-							self.stream = None;
-						}
 					}
-					next_server_read = time::get_time().sec + server_read_interval;
+					Err(e) => {
+						error!(LOGGER, "Error reading message: {:?}", e);
+						self.stream = None;
+						self.stats
+							.write()
+							.unwrap()
+							.history
+							.record_connection_drop(&format!("{:?}", e));
+					}
+				}
+
+				if self.job_prefetch {
+					self.prefetch_job();
 				}
 
 				// Request a status message from the server
-				if time::get_time().sec > next_status_request {
+				if clock::mono_secs() > next_status_request {
 					let _ = self.send_message_get_status();
-					next_status_request = time::get_time().sec + status_interval;
+					next_status_request = clock::mono_secs() + status_interval;
+				}
+			}
+
+			// Take the solutions found, before any shutdown message sent
+			// after them
+			let found = self.solutions.as_ref().map_or(vec![], |s| s.take());
+			if !found.is_empty() {
+				for s in found {
+					self.found_solution(s.height, s.job_id, s.edge_bits, s.nonce, s.pow);
 				}
+				self.stats.write().unwrap().mining_stats.solution_buffer.queued =
+					self.solutions.as_ref().map_or(0, |s| s.queued() as u64);
 			}
 
 			// Talk to the cuckoo miner plugin
@@ -464,7 +1054,40 @@ impl Controller {
 				debug!(LOGGER, "Client received message: {:?}", message);
 				let result = match message {
 					types::ClientMessage::FoundSolution(height, job_id, edge_bits, nonce, pow) => {
-						self.send_message_submit(height, job_id, edge_bits, nonce, pow)
+						self.found_solution(height, job_id, edge_bits, nonce, pow);
+						Ok(())
+					}
+					types::ClientMessage::SwitchPool(server_url, login, password) => {
+						warn!(LOGGER, "Switching pool to {}", server_url);
+						self.server_url = server_url;
+						self.server_login = login;
+						self.server_password = password;
+						self.limiter.set_pool(&self.server_url);
+						self.breaker.set_pool(
+							&self.server_url,
+							self.server_login.clone(),
+							self.server_password.clone(),
+						);
+						// reconnect (and log in) to the new server
+						self.stream = None;
+						next_server_retry = clock::mono_secs();
+						let mut stats = self.stats.write().unwrap();
+						stats.history.record_connection_drop("switched pool");
+						stats.client_stats.server_url = self.server_url.clone();
+						stats.client_stats.connected = false;
+						stats.client_stats.logged_in = false;
+						Ok(())
+					}
+					types::ClientMessage::Reconnect => {
+						warn!(LOGGER, "Reconnecting to {}", self.server_url);
+						self.stream = None;
+						next_server_retry = clock::mono_secs();
+						self.stats
+							.write()
+							.unwrap()
+							.history
+							.record_connection_drop("reconnect requested");
+						Ok(())
 					}
 					types::ClientMessage::Shutdown => {
 						//TODO: Inform server?
@@ -476,7 +1099,162 @@ impl Controller {
 					error!(LOGGER, "Mining Controller Error {:?}", e);
 				}
 			}
+			if let Some(action) = self.breaker.retry() {
+				self.breaker_action(action);
+			}
+			self.send_submits();
 			thread::sleep(std::time::Duration::from_millis(100));
 		} // loop
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::sync::{mpsc, Arc, RwLock};
+	use std::thread;
+
+	use super::*;
+	use mock_pool::{self, job, wait_for, MockPool, Reply, Script};
+
+	const TIMEOUT_SECS: u64 = 20;
+
+	/// Run the client against the pool with the mock solver, returning its
+	/// stats and channel
+	fn start_client(
+		pool: &MockPool,
+		hold: Vec<u64>,
+		dry_run: bool,
+		chaos: Option<&str>,
+	) -> (Arc<RwLock<stats::Stats>>, mpsc::Sender<types::ClientMessage>) {
+		let stats = Arc::new(RwLock::new(stats::Stats::default()));
+		let (miner_tx, miner_rx) = mpsc::channel();
+		let mut cc = Controller::new(
+			&pool.addr(),
+			Some("test".to_string()),
+			Some("x".to_string()),
+			miner_tx,
+			stats.clone(),
+		).unwrap();
+		cc.set_dry_run(dry_run);
+		if let Some(chaos) = chaos {
+			cc.set_chaos(Chaos::parse(chaos).unwrap());
+		}
+		let client_tx = cc.tx.clone();
+		mock_pool::mock_solver(miner_rx, client_tx.clone(), hold);
+		thread::spawn(move || cc.run());
+		(stats, client_tx)
+	}
+
+	fn last_message(stats: &Arc<RwLock<stats::Stats>>) -> String {
+		stats.read().unwrap().client_stats.last_message_received.clone()
+	}
+
+	#[test]
+	fn test_login_job_and_accepted_share() {
+		let pool = MockPool::start(Script::jobs(vec![job(100, 1)]));
+		let (stats, client_tx) = start_client(&pool, vec![], false, None);
+		assert!(wait_for(TIMEOUT_SECS, || pool.share_counts().0 == 1));
+		assert_eq!(pool.logins(), 1);
+		assert_eq!(pool.submits(), vec![(100, 1)]);
+		assert!(wait_for(TIMEOUT_SECS, || last_message(&stats).contains("Share Accepted")));
+		assert!(stats.read().unwrap().client_stats.logged_in);
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+
+	#[test]
+	fn test_pushed_job_replaces_current() {
+		let pool = MockPool::start(Script::jobs(vec![job(100, 1)]));
+		let (_stats, client_tx) = start_client(&pool, vec![], false, None);
+		assert!(wait_for(TIMEOUT_SECS, || pool.submits().len() == 1));
+		pool.push_job(job(101, 2));
+		assert!(wait_for(TIMEOUT_SECS, || pool.submits().len() == 2));
+		assert_eq!(pool.submits(), vec![(100, 1), (101, 2)]);
+		assert_eq!(pool.share_counts(), (2, 0, 0));
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+
+	#[test]
+	fn test_stale_share_rejected() {
+		let pool = MockPool::start(Script::jobs(vec![job(100, 1)]));
+		// the share for job 1 only comes once job 2 is out
+		let (stats, client_tx) = start_client(&pool, vec![1], false, None);
+		assert!(wait_for(TIMEOUT_SECS, || last_message(&stats).contains("Got job")));
+		pool.push_job(job(101, 2));
+		assert!(wait_for(TIMEOUT_SECS, || pool.submits().len() == 2));
+		assert_eq!(pool.submits(), vec![(100, 1), (101, 2)]);
+		assert_eq!(pool.share_counts(), (1, 0, 1));
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+
+	#[test]
+	fn test_rejected_share_and_block_found() {
+		let pool = MockPool::start(Script {
+			jobs: vec![job(100, 1)],
+			replies: vec![
+				Reply::Reject(-32502, "Failed to validate solution".to_string()),
+				Reply::BlockFound,
+			],
+		});
+		let (stats, client_tx) = start_client(&pool, vec![], false, None);
+		assert!(wait_for(TIMEOUT_SECS, || pool.share_counts().1 == 1));
+		assert!(wait_for(TIMEOUT_SECS, || last_message(&stats).contains("Failed to submit")));
+		pool.push_job(job(101, 2));
+		assert!(wait_for(TIMEOUT_SECS, || {
+			stats.read().unwrap().client_stats.blocks_found == 1
+		}));
+		assert_eq!(pool.share_counts(), (1, 1, 0));
+		{
+			let stats = stats.read().unwrap();
+			assert_eq!(stats.blocks.len(), 1);
+			assert_eq!(stats.blocks[0].height, 101);
+			assert_eq!(stats.blocks[0].hash, Some("0000".to_string()));
+		}
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+
+	#[test]
+	fn test_jobs_processed_while_submit_stalls() {
+		let pool = MockPool::start(Script::jobs(vec![job(100, 1)]));
+		let (stats, client_tx) = start_client(&pool, vec![], false, Some("submit_stall_ms=5000"));
+		assert!(wait_for(TIMEOUT_SECS, || last_message(&stats).contains("Height: 100")));
+		// the share for job 1 is stuck in the submit worker, the pushed
+		// job still gets through while it is
+		pool.push_job(job(101, 2));
+		assert!(wait_for(TIMEOUT_SECS, || last_message(&stats).contains("Height: 101")));
+		assert!(pool.submits().is_empty());
+		// both shares go out once the stalls are over, the first one late
+		assert!(wait_for(TIMEOUT_SECS, || pool.submits().len() == 2));
+		assert_eq!(pool.submits(), vec![(100, 1), (101, 2)]);
+		assert!(wait_for(TIMEOUT_SECS, || pool.share_counts() == (1, 0, 1)));
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+
+	#[test]
+	fn test_reconnect_after_disconnect() {
+		let pool = MockPool::start(Script::jobs(vec![job(100, 1), job(101, 2)]));
+		let (stats, client_tx) = start_client(&pool, vec![], false, None);
+		assert!(wait_for(TIMEOUT_SECS, || pool.submits().len() == 1));
+		pool.disconnect();
+		assert!(wait_for(TIMEOUT_SECS, || {
+			stats.read().unwrap().history.connection_drops.len() == 1
+		}));
+		// logs in again and mines the job it then gets
+		assert!(wait_for(TIMEOUT_SECS, || pool.submits().len() == 2));
+		assert_eq!(pool.connections(), 2);
+		assert_eq!(pool.logins(), 2);
+		assert_eq!(pool.submits(), vec![(100, 1), (101, 2)]);
+		assert!(stats.read().unwrap().history.connection_drops[0].duration.is_some());
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+
+	#[test]
+	fn test_dry_run_withholds_shares() {
+		let pool = MockPool::start(Script::jobs(vec![job(100, 1)]));
+		let (stats, client_tx) = start_client(&pool, vec![], true, None);
+		assert!(wait_for(TIMEOUT_SECS, || {
+			stats.read().unwrap().client_stats.withheld_shares == 1
+		}));
+		assert!(pool.submits().is_empty());
+		let _ = client_tx.send(types::ClientMessage::Shutdown);
+	}
+}
@@ -0,0 +1,55 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Monotonic clock for everything timed inside the miner. The wall clock
+//! jumps with NTP corrections and manual changes, so durations, deadlines
+//! and rolling windows are measured on this one, and the wall clock is
+//! only used for the times shown or stored.
+
+use std::time::Instant;
+
+use serde::Serializer;
+use time;
+
+lazy_static! {
+	static ref START: Instant = Instant::now();
+}
+
+/// Start the clock, so it reads from when the miner started
+pub fn init() {
+	let _ = *START;
+}
+
+/// Milliseconds on the monotonic clock, since the miner started
+pub fn mono_ms() -> i64 {
+	let elapsed = START.elapsed();
+	elapsed.as_secs() as i64 * 1000 + elapsed.subsec_nanos() as i64 / 1_000_000
+}
+
+/// Seconds on the monotonic clock, since the miner started
+pub fn mono_secs() -> i64 {
+	START.elapsed().as_secs() as i64
+}
+
+/// Wall clock time (seconds since epoch) of the given monotonic clock
+/// seconds, as the wall clock reads now
+pub fn to_wall(secs: i64) -> i64 {
+	time::get_time().sec - (mono_secs() - secs)
+}
+
+/// Serialize the monotonic clock seconds as of serializing, whatever the
+/// field holds
+pub fn serialize_now<S: Serializer>(_: &Option<i64>, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_some(&mono_secs())
+}
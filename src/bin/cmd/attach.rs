@@ -0,0 +1,92 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `attach`, showing the TUI of a miner already running headless (as a
+//! daemon, a service or with --output json), from the stats its API
+//! serves. The view is read only, nothing is sent back to the miner.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+
+use config::{ApiConfig, MinerConfig};
+use http;
+use stats::Stats;
+use tui::ui;
+
+/// Seconds between stats fetches
+const POLL_INTERVAL_SECS: u64 = 1;
+
+/// Fetch the stats of the miner
+pub fn fetch(url: &str, authorization: Option<&str>) -> Result<Stats, String> {
+	match http::get(url, authorization)? {
+		(200, body) => serde_json::from_str(&body).map_err(|e| format!("Invalid stats: {}", e)),
+		(401, _) => Err("Unauthorized, the miner's api control_token is needed (--token)".to_string()),
+		(404, _) => Err("Not found, is it a grin-miner api?".to_string()),
+		(code, _) => Err(format!("HTTP error {}", code)),
+	}
+}
+
+/// Show the TUI of the miner whose API is at the url (by default the one
+/// configured locally), until it's quit
+pub fn attach(
+	config: MinerConfig,
+	api_config: ApiConfig,
+	url: Option<&str>,
+	token: Option<&str>,
+) -> Result<(), String> {
+	let base = match url {
+		Some(u) if u.starts_with("http://") || u.starts_with("https://") => u.to_string(),
+		Some(u) => format!("http://{}", u),
+		None => format!(
+			"http://{}",
+			api_config.bind_address.replace("0.0.0.0", "127.0.0.1")
+		),
+	};
+	let url = format!("{}/v1/stats", base.trim_right_matches('/'));
+	let authorization = token
+		.map(|t| t.to_string())
+		.or(api_config.control_token.clone())
+		.map(|t| format!("Bearer {}", t));
+
+	let stats = fetch(&url, authorization.as_ref().map(|a| a.as_str()))
+		.map_err(|e| format!("Unable to attach to {}: {}", base, e))?;
+	let stats = Arc::new(RwLock::new(stats));
+	let poll_stats = stats.clone();
+	let _ = thread::Builder::new()
+		.name("attach".to_string())
+		.spawn(move || loop {
+			thread::sleep(Duration::from_secs(POLL_INTERVAL_SECS));
+			match fetch(&url, authorization.as_ref().map(|a| a.as_str())) {
+				Ok(s) => *poll_stats.write().unwrap() = s,
+				Err(e) => {
+					let mut s = poll_stats.write().unwrap();
+					s.client_stats.connected = false;
+					s.client_stats.connection_status =
+						format!("Connection Status: Lost the miner at {}: {}", base, e);
+				}
+			}
+		});
+
+	// profiles can't be switched from here
+	let mut view_config = config;
+	view_config.profiles = None;
+	let (miner_tx, _miner_rx) = mpsc::channel();
+	let mut controller = ui::Controller::new(&view_config, miner_tx)?;
+	controller.run(stats, Arc::new(AtomicBool::new(false)));
+	Ok(())
+}
@@ -0,0 +1,297 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `benchmark`, running a configured plugin on one device on synthetic
+//! headers and measuring its steady state graph times
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use blake2_rfc::blake2b::blake2b;
+use serde_json;
+use time;
+
+use auto;
+use clock;
+use config::MinerConfig;
+use plugin::{self, PluginMiner};
+use util;
+
+/// Default seconds a benchmark runs, warm-up included
+pub const DEFAULT_DURATION_SECS: u64 = 120;
+
+/// Default graphs discarded before measuring, while the solver warms up
+pub const DEFAULT_WARMUP_GRAPHS: usize = 3;
+
+/// Results of a benchmark, as written to its JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+	/// When the benchmark ran, in seconds since the epoch
+	pub timestamp: i64,
+	/// grin-miner version
+	pub version: String,
+	pub type_filter: String,
+	pub edge_bits: u8,
+	/// Full path of the plugin run
+	pub plugin_path: String,
+	pub device: u32,
+	pub device_name: String,
+	/// Parameters set on the device, as configured
+	pub parameters: HashMap<String, u32>,
+	pub duration_secs: u64,
+	/// Graphs discarded while warming up
+	pub warmup_graphs: usize,
+	/// Graphs measured
+	pub graphs: usize,
+	pub mean_ms: f64,
+	pub median_ms: f64,
+	pub p95_ms: f64,
+	/// Graphs per second, from the mean graph time
+	pub gps: f64,
+	/// Peak memory the solver used, GPU memory for GPU plugins, in MB
+	pub memory_mb: Option<u64>,
+	/// Graph times measured, in ms
+	#[serde(skip)]
+	pub graph_times_ms: Vec<f64>,
+}
+
+/// A random header to solve, nothing is submitted
+pub fn synthetic_pre_pow() -> String {
+	seeded_pre_pow(&time::precise_time_ns().to_le_bytes())
+}
+
+/// A header to solve derived from the seed, the same for every run
+pub fn seeded_pre_pow(seed: &[u8]) -> String {
+	let mut bytes = vec![];
+	let mut hash = blake2b(64, &[], seed).as_bytes().to_vec();
+	while bytes.len() < 186 {
+		bytes.extend_from_slice(&hash);
+		hash = blake2b(64, &[], &hash).as_bytes().to_vec();
+	}
+	bytes.truncate(186);
+	util::to_hex(bytes)
+}
+
+/// Memory in use (MB): on the GPU of a CUDA device, or the miner's peak
+/// resident memory for CPU plugins
+fn memory_used_mb(type_filter: &str, device: u32) -> Option<u64> {
+	if type_filter.contains("cuda") {
+		return auto::cuda_gpus()
+			.into_iter()
+			.find(|g| g.device == device)
+			.and_then(|g| g.free_mb.map(|f| g.memory_mb.saturating_sub(f)));
+	}
+	if type_filter.contains("ocl") {
+		return auto::opencl_gpus()
+			.into_iter()
+			.find(|g| g.device == device)
+			.and_then(|g| g.free_mb.map(|f| g.memory_mb.saturating_sub(f)));
+	}
+	let mut status = String::new();
+	File::open("/proc/self/status")
+		.and_then(|mut f| f.read_to_string(&mut status))
+		.ok()?;
+	status
+		.lines()
+		.find(|l| l.starts_with("VmHWM:"))
+		.and_then(|l| l.split_whitespace().nth(1))
+		.and_then(|kb| kb.parse::<u64>().ok())
+		.map(|kb| kb / 1024)
+}
+
+/// Value at the given percentile of sorted values
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+	if sorted.is_empty() {
+		return 0.0;
+	}
+	let rank = (p / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+	sorted[rank.min(sorted.len() - 1)]
+}
+
+/// Run the given plugin of the configuration on one device for the given
+/// seconds, discarding the first graphs, and measure its graph times
+pub fn run(
+	config: &MinerConfig,
+	plugin_index: usize,
+	device: u32,
+	duration_secs: u64,
+	warmup_graphs: usize,
+) -> Result<BenchmarkResult, String> {
+	run_on(
+		config,
+		plugin_index,
+		device,
+		duration_secs,
+		warmup_graphs,
+		&synthetic_pre_pow(),
+	)
+}
+
+/// Run a benchmark on the given header, for runs to compare on the same
+/// work
+pub fn run_on(
+	config: &MinerConfig,
+	plugin_index: usize,
+	device: u32,
+	duration_secs: u64,
+	warmup_graphs: usize,
+	pre_pow: &str,
+) -> Result<BenchmarkResult, String> {
+	let mut plugin_config = config
+		.miner_plugin_config
+		.get(plugin_index)
+		.cloned()
+		.ok_or(format!("No plugin {} in the configuration", plugin_index))?;
+	// just this plugin, on just this device
+	plugin_config.devices = Some(device.to_string());
+	plugin_config.exclude_devices = None;
+	plugin_config.instances = None;
+	let mut bench_config = config.clone();
+	bench_config.miner_plugin_config = vec![plugin_config.clone()];
+	let parameters = plugin_config
+		.device_parameters
+		.as_ref()
+		.and_then(|p| p.get(&device.to_string()))
+		.cloned()
+		.unwrap_or_default();
+
+	plugin::reset_loaded_config();
+	let memory_before = memory_used_mb(&plugin_config.type_filter, device);
+	let mut plugin_miner = PluginMiner::new();
+	plugin_miner.init(bench_config);
+	let plugin_path = plugin::loaded_plugins().pop().unwrap_or_default();
+	let miner = plugin_miner.get_consumable();
+	let mut job_handle = miner
+		.notify(1, pre_pow, "", 0, false)
+		.map_err(|e| format!("Unable to start the plugin: {:?}", e))?;
+
+	let start = clock::mono_secs();
+	let mut iterations = 0;
+	let mut seen = 0;
+	let mut device_name = String::new();
+	let mut graph_times: Vec<f64> = vec![];
+	let mut memory_peak = memory_before;
+	let mut next_memory_check = start;
+	let result = loop {
+		let now = clock::mono_secs();
+		if now - start >= duration_secs as i64 {
+			break Ok(());
+		}
+		let stats = match job_handle.get_stats(0) {
+			Ok(s) => s,
+			Err(e) => break Err(format!("Unable to get the device stats: {:?}", e)),
+		};
+		if let Some(d) = stats.iter().find(|d| d.device_id == device.to_string()) {
+			if d.has_errored != 0 {
+				break Err(format!("Device {} errored", device));
+			}
+			device_name = d.device_name.clone();
+			if d.iterations_completed > iterations && d.last_solution_time > 0 {
+				iterations = d.iterations_completed;
+				seen += 1;
+				if seen > warmup_graphs {
+					graph_times.push(d.last_solution_time as f64 / 1000000.0);
+				}
+			}
+		}
+		if now >= next_memory_check {
+			if let Some(m) = memory_used_mb(&plugin_config.type_filter, device) {
+				memory_peak = Some(memory_peak.map_or(m, |p| p.max(m)));
+			}
+			next_memory_check = now + 5;
+		}
+		thread::sleep(Duration::from_millis(100));
+	};
+	job_handle.stop_jobs();
+	plugin::reset_loaded_config();
+	result?;
+
+	if graph_times.is_empty() {
+		return Err(format!(
+			"No graphs measured on device {} in {}s, try a longer --duration",
+			device, duration_secs
+		));
+	}
+	let mut sorted = graph_times.clone();
+	sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	let mean = graph_times.iter().sum::<f64>() / graph_times.len() as f64;
+	// GPU memory in use before the solver started isn't its own
+	let memory_mb = match (memory_peak, memory_before) {
+		(Some(p), Some(b)) if !plugin_config.type_filter.contains("cpu") => Some(p.saturating_sub(b)),
+		(p, _) => p,
+	};
+	Ok(BenchmarkResult {
+		timestamp: time::get_time().sec,
+		version: env!("CARGO_PKG_VERSION").to_string(),
+		type_filter: plugin_config.type_filter.clone(),
+		edge_bits: plugin_config.edge_bits,
+		plugin_path: plugin_path,
+		device: device,
+		device_name: device_name,
+		parameters: parameters,
+		duration_secs: duration_secs,
+		warmup_graphs: warmup_graphs,
+		graphs: graph_times.len(),
+		mean_ms: mean,
+		median_ms: percentile(&sorted, 50.0),
+		p95_ms: percentile(&sorted, 95.0),
+		gps: 1000.0 / mean,
+		memory_mb: memory_mb,
+		graph_times_ms: graph_times,
+	})
+}
+
+/// Benchmark a device and print the results, writing them to the given
+/// JSON file (benchmark-<plugin>_<edge bits>-<device>.json by default)
+pub fn benchmark(
+	config: MinerConfig,
+	plugin_index: usize,
+	device: u32,
+	duration_secs: u64,
+	warmup_graphs: usize,
+	output: Option<&str>,
+) -> Result<(), String> {
+	println!(
+		"Benchmarking plugin {} on device {} for {}s, discarding {} warm-up graphs...",
+		plugin_index, device, duration_secs, warmup_graphs
+	);
+	let result = run(&config, plugin_index, device, duration_secs, warmup_graphs)?;
+	println!("{} on device {} ({})", result.plugin_path, result.device, result.device_name);
+	println!("  Graphs measured: {}", result.graphs);
+	println!(
+		"  Graph time: mean {:.1} ms, median {:.1} ms, p95 {:.1} ms",
+		result.mean_ms, result.median_ms, result.p95_ms
+	);
+	println!("  Graphs per second: {:.4}", result.gps);
+	match result.memory_mb {
+		Some(m) => println!("  Memory used: {} MB", m),
+		None => println!("  Memory used: unknown"),
+	}
+	let path = match output {
+		Some(p) => p.to_string(),
+		None => format!(
+			"benchmark-{}_{}-{}.json",
+			result.type_filter, result.edge_bits, result.device
+		),
+	};
+	let json = serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?;
+	File::create(&path)
+		.and_then(|mut f| f.write_all(json.as_bytes()))
+		.map_err(|e| format!("Unable to write {}: {}", path, e))?;
+	println!("Results written to {}", path);
+	Ok(())
+}
@@ -0,0 +1,195 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `compare`, benchmarking candidate plugins one after the other on the
+//! same device and header, and ranking them, e.g. to choose between the
+//! mean and lean solvers for a GPU
+
+use cmd::benchmark::{self, BenchmarkResult};
+use config::types::{CuckooMinerPluginConfig, MinerConfig};
+
+/// Default seconds each plugin runs for, warm-up included
+pub const DEFAULT_DURATION_SECS: u64 = 120;
+
+/// Two-sided 95% t values by degrees of freedom, from 1; 1.96 past them
+const T_95: [f64; 30] = [
+	12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+	2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+	2.052, 2.048, 2.045, 2.042,
+];
+
+/// A plugin to compare: an index in the config, or a plugin name as
+/// list-plugins shows it, e.g. cuckatoo_lean_cuda_31
+fn candidate(config: &MinerConfig, spec: &str) -> Result<CuckooMinerPluginConfig, String> {
+	if let Ok(i) = spec.parse::<usize>() {
+		return config
+			.miner_plugin_config
+			.get(i)
+			.cloned()
+			.ok_or(format!("No plugin {} in the configuration", i));
+	}
+	let stem = spec.split('.').next().unwrap_or(spec);
+	let (type_filter, edge_bits) = match stem.rfind('_') {
+		Some(i) => match stem[i + 1..].parse::<u8>() {
+			Ok(e) => (&stem[..i], e),
+			Err(_) => return Err(format!("{} has no edge bits, e.g. {}_31", spec, stem)),
+		},
+		None => return Err(format!("{} has no edge bits, e.g. {}_31", spec, stem)),
+	};
+	// the configured settings of the plugin, if it's configured
+	let configured = config
+		.miner_plugin_config
+		.iter()
+		.find(|p| p.type_filter == type_filter && p.edge_bits == edge_bits);
+	Ok(match configured {
+		Some(p) => p.clone(),
+		None => CuckooMinerPluginConfig {
+			type_filter: type_filter.to_string(),
+			edge_bits: edge_bits,
+			..Default::default()
+		},
+	})
+}
+
+/// Mean graph time and the half-width of its 95% confidence interval, in
+/// ms
+fn mean_interval(times: &[f64]) -> (f64, f64) {
+	let n = times.len();
+	let mean = times.iter().sum::<f64>() / n as f64;
+	if n < 2 {
+		return (mean, 0.0);
+	}
+	let variance = times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+	let t = T_95.get(n - 2).cloned().unwrap_or(1.96);
+	(mean, t * (variance / n as f64).sqrt())
+}
+
+/// A plugin's results, normalized for the ranking
+struct Ranked {
+	name: String,
+	result: BenchmarkResult,
+	mean_ms: f64,
+	half_width_ms: f64,
+	/// Graphs per second at the smallest graph size compared, and its
+	/// confidence interval
+	score: f64,
+	score_low: f64,
+	score_high: f64,
+}
+
+/// Benchmark each plugin on the device on the same header, one after the
+/// other, and print them ranked
+pub fn compare(
+	config: MinerConfig,
+	device: u32,
+	plugins: &str,
+	duration_secs: u64,
+	warmup_graphs: usize,
+) -> Result<(), String> {
+	let candidates = plugins
+		.split(',')
+		.map(|p| p.trim())
+		.filter(|p| !p.is_empty())
+		.map(|p| candidate(&config, p))
+		.collect::<Result<Vec<_>, String>>()?;
+	if candidates.len() < 2 {
+		return Err("Give at least two plugins to compare with --plugins".to_string());
+	}
+	let pre_pow = benchmark::synthetic_pre_pow();
+	println!(
+		"Comparing {} plugins on device {}, {}s each, about {} minutes...",
+		candidates.len(),
+		device,
+		duration_secs,
+		(candidates.len() as u64 * duration_secs + 59) / 60
+	);
+	let mut results = vec![];
+	for plugin_config in candidates {
+		let name = format!("{}_{}", plugin_config.type_filter, plugin_config.edge_bits);
+		let mut trial = config.clone();
+		trial.miner_plugin_config = vec![plugin_config];
+		match benchmark::run_on(&trial, 0, device, duration_secs, warmup_graphs, &pre_pow) {
+			Ok(r) => {
+				println!("  {}: {:.4} gps over {} graphs", name, r.gps, r.graphs);
+				results.push((name, r));
+			}
+			Err(e) => println!("  {}: failed, {}", name, e),
+		}
+	}
+	if results.is_empty() {
+		return Err("No plugin ran successfully".to_string());
+	}
+
+	// plugins of larger graphs search more edges per graph
+	let min_edge_bits = results.iter().map(|r| r.1.edge_bits).min().unwrap();
+	let mut ranked: Vec<Ranked> = results
+		.into_iter()
+		.map(|(name, r)| {
+			let (mean, half_width) = mean_interval(&r.graph_times_ms);
+			let weight = (1u64 << (r.edge_bits - min_edge_bits)) as f64;
+			Ranked {
+				name: name,
+				mean_ms: mean,
+				half_width_ms: half_width,
+				score: weight * 1000.0 / mean,
+				score_low: weight * 1000.0 / (mean + half_width),
+				score_high: if half_width < mean {
+					weight * 1000.0 / (mean - half_width)
+				} else {
+					f64::INFINITY
+				},
+				result: r,
+			}
+		})
+		.collect();
+	ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+	let mixed = ranked.iter().any(|r| r.result.edge_bits != min_edge_bits);
+	println!();
+	println!(
+		"Device {} ({}), ranked by {}:",
+		device,
+		ranked[0].result.device_name,
+		if mixed {
+			format!("graphs per second scaled to {} edge bits", min_edge_bits)
+		} else {
+			"graphs per second".to_string()
+		}
+	);
+	println!(
+		"{:<5} {:<32} {:>7} {:>20} {:>26} {:>9}",
+		"Rank", "Plugin", "Graphs", "Graph time (ms)", "GPS (95% CI)", "Relative"
+	);
+	let best = ranked[0].score;
+	for (i, r) in ranked.iter().enumerate() {
+		println!(
+			"{:<5} {:<32} {:>7} {:>20} {:>26} {:>8.1}%",
+			i + 1,
+			r.name,
+			r.result.graphs,
+			format!("{:.1} ± {:.1}", r.mean_ms, r.half_width_ms),
+			format!("{:.4} ({:.4}-{:.4})", r.score, r.score_low, r.score_high),
+			r.score * 100.0 / best
+		);
+	}
+	if ranked.len() > 1 && ranked[1].score_high >= ranked[0].score_low {
+		println!();
+		println!(
+			"{} and {} are within each other's confidence intervals, run with a \
+			 longer --duration to tell them apart",
+			ranked[0].name, ranked[1].name
+		);
+	}
+	Ok(())
+}
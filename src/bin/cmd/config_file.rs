@@ -0,0 +1,411 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `config generate` and `config validate`, writing a commented default
+//! config file, and checking an existing one against the installed plugins
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use auto::{cpu_threads, has_avx2};
+use chain;
+use errors;
+use intensity;
+use json_status;
+use placement;
+use units;
+use config::types::{pci_address, PCI_PREFIX};
+use config::{GlobalConfig, MinerConfig, DEFAULT_CONFIG};
+use plugin::{self, enumerate_devices, pci_bus_ids};
+use solutions::OverflowPolicy;
+use util::cuckoo_miner::CuckooPluginCapabilities;
+use wallet;
+
+/// The plugin section enabled in the default config
+pub const DEFAULT_PLUGIN_SECTION: &str = "[[mining.miner_plugin_config]]
+edge_bits = 29
+type_filter = \"cuckatoo_mean_compat_cpu\"
+[mining.miner_plugin_config.device_parameters.0]
+NUM_THREADS = 4
+";
+
+/// Type filter and edge bits of a plugin name, e.g. cuckatoo_cuda_29
+fn split_plugin_name(name: &str) -> (String, Option<u8>) {
+	match name.rfind('_') {
+		Some(i) => match name[i + 1..].parse::<u8>() {
+			Ok(e) => (name[..i].to_string(), Some(e)),
+			Err(_) => (name.to_string(), None),
+		},
+		None => (name.to_string(), None),
+	}
+}
+
+/// Plugin section for the best plugin installed: a GPU plugin if there
+/// is one, otherwise the fastest CPU plugin the processor supports
+pub fn detected_plugin_section(plugins: &[CuckooPluginCapabilities]) -> Option<String> {
+	let names: Vec<&str> = plugins.iter().map(|p| p.name.as_str()).collect();
+	let find = |pattern: &str| names.iter().find(|n| n.contains(pattern)).cloned();
+	let (name, gpu) = if let Some(n) = find("cuda").or(find("ocl")) {
+		(n, true)
+	} else if let Some(n) = find("mean_cpu").filter(|_| has_avx2()) {
+		(n, false)
+	} else if let Some(n) = find("mean_compat_cpu").or(find("lean_cpu")) {
+		(n, false)
+	} else {
+		return None;
+	};
+	let (type_filter, edge_bits) = split_plugin_name(name);
+	let device_params = if gpu {
+		"USE_DEVICE = 1".to_string()
+	} else {
+		format!("NUM_THREADS = {}", cpu_threads())
+	};
+	Some(format!(
+		"[[mining.miner_plugin_config]]\nedge_bits = {}\ntype_filter = \"{}\"\n\
+		 [mining.miner_plugin_config.device_parameters.0]\n{}\n",
+		edge_bits.unwrap_or(29),
+		type_filter,
+		device_params
+	))
+}
+
+/// Write the commented default config to path, optionally with the
+/// plugin section tailored to the installed plugins and hardware
+pub fn generate(path: &str, detect: bool, force: bool) -> Result<(), String> {
+	if Path::new(path).exists() && !force {
+		return Err(format!("{} already exists, use --force to overwrite it", path));
+	}
+	let mut contents = DEFAULT_CONFIG.to_string();
+	if detect {
+		let plugins = plugin::available_plugins(&MinerConfig::default())?;
+		match detected_plugin_section(&plugins) {
+			Some(section) => {
+				println!("Detected plugin configuration:\n{}", section);
+				contents = contents.replace(DEFAULT_PLUGIN_SECTION, &section);
+			}
+			None => println!("No usable plugins found, keeping the default plugin configuration"),
+		}
+	}
+	let mut file = File::create(path).map_err(|e| format!("Unable to create {}: {}", path, e))?;
+	file.write_all(contents.as_bytes())
+		.map_err(|e| format!("Unable to write {}: {}", path, e))?;
+	println!("Configuration written to {}", path);
+	Ok(())
+}
+
+/// Line numbers of the config file contents, to point problems at
+struct Lines<'a> {
+	lines: Vec<&'a str>,
+}
+
+impl<'a> Lines<'a> {
+	/// First line of the nth plugin section
+	fn plugin_section(&self, index: usize) -> Option<usize> {
+		self.lines
+			.iter()
+			.enumerate()
+			.filter(|&(_, l)| l.trim() == "[[mining.miner_plugin_config]]")
+			.nth(index)
+			.map(|(i, _)| i)
+	}
+
+	/// Line (1 based) of the first line starting with needle, from the
+	/// given line to the end of that plugin section
+	fn find(&self, from: Option<usize>, needle: &str) -> Option<usize> {
+		let from = from?;
+		for (i, l) in self.lines.iter().enumerate().skip(from) {
+			if i > from && l.trim() == "[[mining.miner_plugin_config]]" {
+				break;
+			}
+			if l.trim_left().starts_with(needle) {
+				return Some(i + 1);
+			}
+		}
+		None
+	}
+}
+
+/// Problems found in the config, with the line they're on if known
+type Problems = Vec<(Option<usize>, String)>;
+
+fn check_plugins(config: &MinerConfig, lines: &Lines, problems: &mut Problems) {
+	if config.miner_plugin_config.is_empty() {
+		problems.push((None, "no [[mining.miner_plugin_config]] sections".to_string()));
+		return;
+	}
+	let plugins = match plugin::available_plugins(config) {
+		Ok(p) => p,
+		Err(e) => {
+			problems.push((lines.find(Some(0), "miner_plugin_dir"), e));
+			return;
+		}
+	};
+	for (i, p) in config.miner_plugin_config.iter().enumerate() {
+		let section = lines.plugin_section(i);
+		if let Some(ref command) = p.command {
+			// external solvers have their own parameters, nothing to check
+			// them against
+			if command.first().map_or(true, |c| c.trim().is_empty()) {
+				problems.push((
+					lines.find(section, "command"),
+					"command needs at least the solver executable".to_string(),
+				));
+			}
+			continue;
+		}
+		let name = format!("{}_{}", p.type_filter, p.edge_bits);
+		let caps = match plugins.iter().find(|c| c.name.contains(&name)) {
+			Some(c) => c,
+			None => {
+				problems.push((
+					lines.find(section, "type_filter"),
+					format!(
+						"no installed plugin {} (type_filter {}, edge_bits {}), see list-plugins",
+						name, p.type_filter, p.edge_bits
+					),
+				));
+				continue;
+			}
+		};
+		if p.platform.is_some() {
+			let line = lines.find(section, "platform");
+			if let Err(e) = p.platform_id() {
+				problems.push((line, e));
+			} else if !caps.parameters.iter().any(|c| c.name == "PLATFORM") {
+				problems.push((
+					line,
+					format!("plugin {} has no PLATFORM parameter, platform only applies to OpenCL plugins", name),
+				));
+			}
+		}
+		if let Some(ref cpus) = p.cpu_affinity {
+			if let Err(e) = placement::parse_cpu_list(cpus) {
+				problems.push((lines.find(section, "cpu_affinity"), e));
+			}
+		}
+		if let Some(ref sha256) = p.sha256 {
+			let sha256 = sha256.trim();
+			if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+				problems.push((
+					lines.find(section, "sha256"),
+					"sha256 must be 64 hex digits, e.g. as printed by sha256sum".to_string(),
+				));
+			}
+		}
+		if let Some(ref level) = p.intensity {
+			if let Err(e) = intensity::validate(&level.to_lowercase()) {
+				problems.push((lines.find(section, "intensity"), e));
+			}
+		}
+		if p.instances.map_or(false, |n| n == 0) {
+			problems.push((lines.find(section, "instances"), "instances must be at least 1".to_string()));
+		} else if p.instances.map_or(false, |n| n > 1) && !config.sandboxed() {
+			problems.push((
+				lines.find(section, "instances"),
+				"more than one solver instance per device needs sandbox_plugins = true".to_string(),
+			));
+		}
+		let device_params = match p.device_parameters {
+			Some(ref d) => d,
+			None => continue,
+		};
+		for (device, params) in device_params {
+			let device_line = lines.find(
+				section,
+				&format!("[mining.miner_plugin_config.device_parameters.{}]", device),
+			);
+			if device.starts_with(PCI_PREFIX) {
+				if pci_address(&device[PCI_PREFIX.len()..]).is_none() {
+					problems.push((
+						device_line,
+						format!("invalid PCI bus id {} of plugin {}", device, name),
+					));
+				}
+			} else if device.parse::<u32>().is_err() {
+				problems.push((
+					device_line,
+					format!("device id {} of plugin {} is not a number", device, name),
+				));
+			}
+			for (param, value) in params {
+				let line = lines.find(device_line.map(|l| l - 1), param);
+				match caps.parameters.iter().find(|c| c.name == *param) {
+					None => problems.push((
+						line,
+						format!("plugin {} has no parameter {}, see plugin-params", name, param),
+					)),
+					Some(c) if *value < c.min_value || *value > c.max_value => problems.push((
+						line,
+						format!(
+							"{} = {} out of range for plugin {}, must be {} to {}",
+							param, value, name, c.min_value, c.max_value
+						),
+					)),
+					Some(_) => {}
+				}
+			}
+		}
+	}
+}
+
+/// Start the plugins and check the configured device ids exist
+fn check_devices(config: &MinerConfig, lines: &Lines, problems: &mut Problems) {
+	// the plugins can't be started with devices that aren't found
+	let mut resolved = config.clone();
+	for (i, p) in resolved.miner_plugin_config.iter_mut().enumerate() {
+		if p.uses_pci_ids() {
+			if let Err(e) = pci_bus_ids(p).and_then(|bus_ids| p.resolve_pci_ids(&bus_ids)) {
+				problems.push((lines.plugin_section(i).map(|l| l + 1), e));
+			}
+		}
+	}
+	if !problems.is_empty() {
+		return;
+	}
+	let plugins = match enumerate_devices(config) {
+		Ok(p) => p,
+		Err(e) => {
+			problems.push((None, e));
+			return;
+		}
+	};
+	for (i, (p, devices)) in resolved
+		.miner_plugin_config
+		.iter()
+		.zip(plugins.into_iter())
+		.enumerate()
+	{
+		let devices = match devices {
+			Ok(d) => d,
+			Err(e) => {
+				problems.push((lines.plugin_section(i).map(|l| l + 1), e));
+				continue;
+			}
+		};
+		if let Some(ref device_params) = p.device_parameters {
+			for device in device_params.keys() {
+				if !devices.iter().any(|d| d.device_id == *device) {
+					problems.push((
+						lines.find(
+							lines.plugin_section(i),
+							&format!("[mining.miner_plugin_config.device_parameters.{}]", device),
+						),
+						format!(
+							"plugin {} can't see device {}, see list-devices",
+							p.type_filter, device
+						),
+					));
+				}
+			}
+		}
+	}
+}
+
+/// Parse the config file at path (or the one the miner would use), check
+/// it against the installed plugins and report all problems found
+pub fn validate(path: Option<&str>, check_device_ids: bool) -> Result<(), String> {
+	let global_config = GlobalConfig::new(path).map_err(|e| format!("{}", e))?;
+	let config_path = match global_config.config_file_path {
+		Some(ref p) => p.clone(),
+		None => return Err("No config file found, see config generate".to_string()),
+	};
+	let mut contents = String::new();
+	File::open(&config_path)
+		.and_then(|mut f| f.read_to_string(&mut contents))
+		.map_err(|e| format!("Unable to read {}: {}", config_path.display(), e))?;
+	let lines = Lines {
+		lines: contents.lines().collect(),
+	};
+	let mut config = global_config.members.as_ref().unwrap().mining.clone();
+	let mut problems = vec![];
+	if let Err(e) = config.apply_selected_profile() {
+		problems.push((lines.find(Some(0), "profile"), e));
+	}
+	match config.apply_chain_defaults() {
+		Ok(warnings) => for w in warnings {
+			println!("{}: warning: {}", config_path.display(), w);
+		},
+		Err(e) => problems.push((lines.find(Some(0), "chain_type"), e)),
+	}
+	if let Err(e) = chain::from_name(config.pow_chain.as_ref().map(|c| c.as_str())) {
+		problems.push((lines.find(Some(0), "pow_chain"), e));
+	}
+	let overflow_policy = config.solution_overflow_policy.as_ref().map(|p| p.as_str());
+	if let Err(e) = OverflowPolicy::from_name(overflow_policy) {
+		problems.push((lines.find(Some(0), "solution_overflow_policy"), e));
+	}
+	if let Some(ref desktop) = global_config.members.as_ref().unwrap().desktop {
+		if let Some(ref p) = desktop.throttle_profile {
+			if let Err(e) = config.clone().apply_profile(p) {
+				problems.push((lines.find(Some(0), "throttle_profile"), e));
+			}
+		}
+	}
+	if let Some(ref solo) = global_config.members.as_ref().unwrap().solo {
+		if let Some(ref url) = solo.wallet_listener_url {
+			if let Err(e) = wallet::check(url) {
+				problems.push((lines.find(Some(0), "wallet_listener_url"), e));
+			}
+		}
+	}
+	if let Some(ref policy) = global_config.members.as_ref().unwrap().failure_policy {
+		if let Err(e) = errors::check_failure_policy(policy) {
+			problems.push((lines.find(Some(0), "[failure_policy"), e));
+		}
+	}
+	let config = &config;
+
+	if !config.stratum_server_addr.contains(':') {
+		problems.push((
+			lines.find(Some(0), "stratum_server_addr"),
+			format!(
+				"stratum_server_addr {} has no port",
+				config.stratum_server_addr
+			),
+		));
+	}
+	if let Some(ref f) = config.output_format {
+		if f != json_status::FORMAT {
+			problems.push((
+				lines.find(Some(0), "output_format"),
+				format!("output_format {} is not json", f),
+			));
+		}
+	}
+	if let Some(ref c) = config.clock_format {
+		if c != units::CLOCK_24H && c != units::CLOCK_12H {
+			problems.push((
+				lines.find(Some(0), "clock_format"),
+				format!("clock_format {} is not 24h or 12h", c),
+			));
+		}
+	}
+	check_plugins(config, &lines, &mut problems);
+	if check_device_ids && problems.is_empty() {
+		check_devices(config, &lines, &mut problems);
+	}
+
+	if problems.is_empty() {
+		println!("{}: OK", config_path.display());
+		return Ok(());
+	}
+	for (line, problem) in &problems {
+		match *line {
+			Some(l) => println!("{}:{}: {}", config_path.display(), l, problem),
+			None => println!("{}: {}", config_path.display(), problem),
+		}
+	}
+	Err(format!("{} problems found", problems.len()))
+}
@@ -0,0 +1,116 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `ctl`, sending a control command to the miner running on this machine
+//! through its control socket
+
+use serde_json::{self, Value};
+
+use config::ControlSocketConfig;
+use control_socket::{Reply, Request};
+
+/// Send the command, with its JSON parameters, and print the answer
+pub fn ctl(
+	config: ControlSocketConfig,
+	command: &str,
+	params: Option<&str>,
+	token: Option<&str>,
+) -> Result<(), String> {
+	let params = match params {
+		Some(p) => serde_json::from_str(p).map_err(|e| format!("Invalid parameters {}: {}", p, e))?,
+		None => Value::Null,
+	};
+	let request = Request {
+		command: command.to_string(),
+		params: params,
+		token: token.map(|t| t.to_string()).or(config.token.clone()),
+	};
+	let reply = send(&config.path, &serde_json::to_string(&request).unwrap())?;
+	let reply: Reply =
+		serde_json::from_str(&reply).map_err(|e| format!("Invalid answer from the miner: {}", e))?;
+	match reply.code {
+		200 => {
+			println!("{}", serde_json::to_string_pretty(&reply.body).unwrap());
+			Ok(())
+		}
+		401 => Err("Unauthorized, the miner's control_socket token is needed (--token)".to_string()),
+		code => Err(format!(
+			"{} failed ({}): {}",
+			command,
+			code,
+			reply.body.get("error").and_then(|e| e.as_str()).unwrap_or("")
+		)),
+	}
+}
+
+#[cfg(unix)]
+fn send(path: &str, request: &str) -> Result<String, String> {
+	use std::io::{BufRead, BufReader, Write};
+	use std::os::unix::net::UnixStream;
+
+	let mut stream = UnixStream::connect(path).map_err(|e| {
+		format!(
+			"Unable to connect to {}, is grin-miner running with [control_socket] enabled? {}",
+			path, e
+		)
+	})?;
+	writeln!(stream, "{}", request).map_err(|e| format!("Unable to send the command: {}", e))?;
+	let mut reply = String::new();
+	BufReader::new(stream)
+		.read_line(&mut reply)
+		.map_err(|e| format!("No answer from the miner: {}", e))?;
+	Ok(reply)
+}
+
+#[cfg(windows)]
+fn send(path: &str, request: &str) -> Result<String, String> {
+	use std::fs::OpenOptions;
+	use std::io::{BufRead, BufReader, Write};
+	use std::thread;
+	use std::time::Duration;
+
+	use control_socket::pipe_name;
+
+	// not found while the miner recreates the pipe between clients
+	const ERROR_FILE_NOT_FOUND: i32 = 2;
+	const ERROR_PIPE_BUSY: i32 = 231;
+
+	let name = pipe_name(path);
+	let mut attempts = 0;
+	let mut pipe = loop {
+		match OpenOptions::new().read(true).write(true).open(&name) {
+			Ok(p) => break p,
+			Err(ref e)
+				if attempts < 20
+					&& (e.raw_os_error() == Some(ERROR_PIPE_BUSY)
+						|| e.raw_os_error() == Some(ERROR_FILE_NOT_FOUND)) =>
+			{
+				attempts += 1;
+				thread::sleep(Duration::from_millis(100));
+			}
+			Err(e) => {
+				return Err(format!(
+					"Unable to connect to {}, is grin-miner running with [control_socket] enabled? {}",
+					name, e
+				))
+			}
+		}
+	};
+	writeln!(pipe, "{}", request).map_err(|e| format!("Unable to send the command: {}", e))?;
+	let mut reply = String::new();
+	BufReader::new(pipe)
+		.read_line(&mut reply)
+		.map_err(|e| format!("No answer from the miner: {}", e))?;
+	Ok(reply)
+}
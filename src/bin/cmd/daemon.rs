@@ -0,0 +1,89 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `stop` and `status`, for a miner started with --daemon, found by its
+//! PID file
+
+use std::thread;
+use std::time::Duration;
+
+use config::MinerConfig;
+use daemon;
+
+/// Seconds stop waits for the miner to exit
+const STOP_TIMEOUT_SECS: u64 = 60;
+
+/// Ask the miner to shut down, and wait for it to exit
+fn terminate(pid: u32) -> Result<(), String> {
+	#[cfg(unix)]
+	{
+		use libc;
+		if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+			return Err(format!(
+				"Unable to signal pid {}: {}",
+				pid,
+				::std::io::Error::last_os_error()
+			));
+		}
+	}
+	#[cfg(windows)]
+	{
+		use std::process::Command;
+		// a detached miner has no console to send a close event to
+		let status = Command::new("taskkill")
+			.args(&["/PID", &pid.to_string(), "/F"])
+			.status()
+			.map_err(|e| format!("Unable to run taskkill: {}", e))?;
+		if !status.success() {
+			return Err(format!("taskkill failed for pid {}", pid));
+		}
+	}
+	for _ in 0..STOP_TIMEOUT_SECS * 10 {
+		if !daemon::is_running(pid) {
+			return Ok(());
+		}
+		thread::sleep(Duration::from_millis(100));
+	}
+	Err(format!(
+		"grin-miner (pid {}) hasn't exited after {}s",
+		pid, STOP_TIMEOUT_SECS
+	))
+}
+
+/// Stop the miner running with the configured PID file
+pub fn stop(config: MinerConfig) -> Result<(), String> {
+	let path = daemon::pid_file(&config);
+	let pid = daemon::running_pid(&path)
+		.ok_or(format!("grin-miner isn't running (no live pid in {})", path))?;
+	println!("Stopping grin-miner (pid {})...", pid);
+	terminate(pid)?;
+	// a miner that was killed leaves its PID file behind
+	if daemon::read_pid(&path) == Some(pid) {
+		let _ = ::std::fs::remove_file(&path);
+	}
+	println!("Stopped");
+	Ok(())
+}
+
+/// Report whether the miner with the configured PID file is running
+pub fn status(config: MinerConfig) -> Result<(), String> {
+	let path = daemon::pid_file(&config);
+	match daemon::running_pid(&path) {
+		Some(pid) => {
+			println!("grin-miner is running (pid {})", pid);
+			Ok(())
+		}
+		None => Err(format!("grin-miner isn't running (no live pid in {})", path)),
+	}
+}
@@ -0,0 +1,70 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `list-devices`, loading the configured plugins and listing the devices
+//! they can see, to help fill in the device parameters
+
+use config::MinerConfig;
+use plugin::{enumerate_devices, pci_bus_ids};
+
+/// Print a table of the devices the configured plugins can see
+pub fn list_devices(config: MinerConfig) -> Result<(), String> {
+	let plugins = enumerate_devices(&config)?;
+	println!(
+		"{:<8} {:<8} {:<32} {:<14} {:<10} {:<8} {}",
+		"Plugin", "Device", "Name", "PCI Bus", "Edge Bits", "In Use", "Status"
+	);
+	for (i, (plugin_config, devices)) in config
+		.miner_plugin_config
+		.iter()
+		.zip(plugins.into_iter())
+		.enumerate()
+	{
+		let devices = match devices {
+			Ok(d) => d,
+			Err(e) => {
+				println!("{:<8} unable to query devices: {}", i, e);
+				continue;
+			}
+		};
+		if devices.is_empty() {
+			println!("{:<8} no devices found ({})", i, plugin_config.type_filter);
+		}
+		let bus_ids = pci_bus_ids(plugin_config).unwrap_or_default();
+		for d in devices {
+			let bus_id = bus_ids
+				.iter()
+				.find(|&&(id, _)| id.to_string() == d.device_id)
+				.map(|&(_, ref b)| b.as_str())
+				.unwrap_or("-");
+			println!(
+				"{:<8} {:<8} {:<32} {:<14} {:<10} {:<8} {}",
+				i,
+				d.device_id,
+				d.device_name,
+				bus_id,
+				d.cuckoo_size,
+				if d.in_use == 1 { "yes" } else { "no" },
+				if d.has_errored == 0 { "OK" } else { "Errored" }
+			);
+		}
+	}
+	println!();
+	println!(
+		"Use the device ids with the USE_DEVICE parameter (and others) in \
+		 [[mining.miner_plugin_config]] device_parameters, or the PCI bus ids \
+		 as pci:<bus id>, which don't change when GPUs are added or removed"
+	);
+	Ok(())
+}
@@ -0,0 +1,45 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Command line subcommands, which run instead of mining and exit
+
+pub mod attach;
+pub mod benchmark;
+pub mod compare;
+pub mod config_file;
+pub mod ctl;
+pub mod daemon;
+pub mod devices;
+pub mod plugins;
+pub mod qualify;
+pub mod shares;
+pub mod selftest;
+pub mod tune;
+pub mod update;
+pub mod usage;
+pub mod wizard;
+
+pub use self::attach::attach;
+pub use self::benchmark::benchmark;
+pub use self::compare::compare;
+pub use self::ctl::ctl;
+pub use self::daemon::{status, stop};
+pub use self::devices::list_devices;
+pub use self::plugins::{list_plugins, plugin_params};
+pub use self::qualify::qualify;
+pub use self::selftest::selftest;
+pub use self::shares::export_shares;
+pub use self::tune::tune;
+pub use self::update::update;
+pub use self::usage::telemetry_status;
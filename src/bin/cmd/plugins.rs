@@ -0,0 +1,102 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `list-plugins` and `plugin-params`, describing the plugins in the
+//! plugin directory and the parameters they accept
+
+use config::MinerConfig;
+use plugin;
+
+/// Algorithm and edge bits of a plugin, from its file name, e.g.
+/// cuckatoo_lean_cuda_29.cuckooplugin
+fn plugin_algorithm(name: &str) -> (String, String) {
+	let stem = name.split('.').next().unwrap_or(name);
+	let algorithm = stem.split('_').next().unwrap_or("").to_string();
+	let edge_bits = match stem.rsplit('_').next() {
+		Some(e) if e.parse::<u8>().is_ok() => e.to_string(),
+		_ => String::from("?"),
+	};
+	(algorithm, edge_bits)
+}
+
+/// Print the plugins in the plugin directory
+pub fn list_plugins(config: MinerConfig) -> Result<(), String> {
+	let (files, _) = plugin::scan_plugin_dirs(&config);
+	let failed: Vec<&plugin::PluginFile> = files.iter().filter(|f| f.loadable.is_err()).collect();
+	if !failed.is_empty() {
+		println!("Plugin files that don't load:");
+		for f in failed {
+			println!("  {}: {}", f.path, f.loadable.as_ref().unwrap_err());
+		}
+		println!();
+	}
+	let plugins = plugin::available_plugins(&config)?;
+	println!("Plugins in {}:", plugin::plugin_dirs(&config).join(", "));
+	println!(
+		"{:<36} {:<10} {:<10} {:<8} {}",
+		"Name", "Algorithm", "Edge Bits", "API", "Description"
+	);
+	for p in &plugins {
+		let (algorithm, edge_bits) = plugin_algorithm(&p.file_name);
+		// the plugin interface doesn't report a version, all plugins
+		// loadable by this build share the same one
+		println!(
+			"{:<36} {:<10} {:<10} {:<8} {}",
+			p.name, algorithm, edge_bits, "1", p.description
+		);
+	}
+	println!();
+	println!(
+		"Use the name without the edge bits suffix as type_filter in \
+		 [[mining.miner_plugin_config]], see plugin-params for its parameters"
+	);
+	Ok(())
+}
+
+/// Print the tunable parameters of the plugins matching the given name
+pub fn plugin_params(config: MinerConfig, name: &str) -> Result<(), String> {
+	let plugins = plugin::available_plugins(&config)?;
+	let matching: Vec<_> = plugins.iter().filter(|p| p.name.contains(name)).collect();
+	if matching.is_empty() {
+		return Err(format!(
+			"No plugin matching {} in {}, see list-plugins",
+			name,
+			plugin::plugin_dirs(&config).join(", ")
+		));
+	}
+	for p in matching {
+		println!("{} ({})", p.name, p.full_path);
+		println!(
+			"  {:<24} {:<6} {:<10} {:<10} {:<10} {}",
+			"Parameter", "Type", "Default", "Min", "Max", "Description"
+		);
+		for param in &p.parameters {
+			println!(
+				"  {:<24} {:<6} {:<10} {:<10} {:<10} {}",
+				param.name,
+				"u32",
+				param.default_value,
+				param.min_value,
+				param.max_value,
+				param.description
+			);
+		}
+		println!();
+	}
+	println!(
+		"Set parameters per device in device_parameters, e.g.\n  \
+		 [mining.miner_plugin_config.device_parameters.0]\n  NUM_BLOCKS = 64"
+	);
+	Ok(())
+}
@@ -0,0 +1,466 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `qualify`, running a fixed scenario and recording its results, to
+//! compare a miner upgrade against the results of the release in use
+//! before deploying it farm-wide. The default scenario runs the stratum
+//! client against a mock pool with a deterministic solver, so shares,
+//! rejections, stale shares and blocks found must match exactly; with
+//! `--devices` each configured device is benchmarked on the same header
+//! every run instead. Results compared against a golden file fail on
+//! any figure that got worse than its tolerance allows.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+
+use serde_json;
+use time;
+
+use client;
+use cmd::benchmark;
+use config::MinerConfig;
+use mock_pool::{self, MockPool, Reply, Script};
+use plugin;
+use stats::Stats;
+use types::ClientMessage;
+
+/// Default seconds each device is benchmarked for, with --devices
+pub const DEFAULT_DURATION_SECS: u64 = 60;
+
+/// Default file the results are written to
+pub const DEFAULT_OUTPUT: &str = "qualification.json";
+
+/// Jobs the mock pool sends, one after the other
+const MOCK_JOBS: u64 = 20;
+
+/// Job whose share the solver only finds once the next one is out, and
+/// the pool rejects as stale
+const MOCK_HELD_JOB: u64 = 10;
+
+/// Seconds to wait for each step of the mock scenario
+const MOCK_TIMEOUT_SECS: u64 = 20;
+
+/// Seed of the header the devices are benchmarked on
+const DEVICE_SEED: &[u8] = b"grin-miner qualification";
+
+/// Which way a figure improves
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Better {
+	Higher,
+	Lower,
+	/// It must not change
+	Equal,
+}
+
+/// A figure of the results, with how much worse it may get
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Metric {
+	pub value: f64,
+	pub better: Better,
+	/// Percentage of the golden value it may get worse by
+	pub tolerance_pct: f64,
+	/// And by this much more, for figures too small for a percentage
+	#[serde(default)]
+	pub slack: f64,
+}
+
+impl Metric {
+	fn exact(value: f64) -> Metric {
+		Metric {
+			value: value,
+			better: Better::Equal,
+			tolerance_pct: 0.0,
+			slack: 0.0,
+		}
+	}
+
+	fn higher(value: f64, tolerance_pct: f64) -> Metric {
+		Metric {
+			value: value,
+			better: Better::Higher,
+			tolerance_pct: tolerance_pct,
+			slack: 0.0,
+		}
+	}
+
+	fn lower(value: f64, tolerance_pct: f64, slack: f64) -> Metric {
+		Metric {
+			value: value,
+			better: Better::Lower,
+			tolerance_pct: tolerance_pct,
+			slack: slack,
+		}
+	}
+}
+
+/// Results of a qualification run, as written to its file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualificationResult {
+	/// grin-miner version
+	pub version: String,
+	/// When it ran, in seconds since the epoch
+	pub timestamp: i64,
+	/// mock or devices
+	pub scenario: String,
+	/// Name of each device benchmarked, by its key in the metrics
+	#[serde(default)]
+	pub devices: BTreeMap<String, String>,
+	pub metrics: BTreeMap<String, Metric>,
+}
+
+/// How a figure compares with its golden value
+#[derive(Debug, PartialEq)]
+enum Verdict {
+	Same,
+	Improved,
+	/// Worse, within the tolerance
+	Within,
+	Regressed,
+	Missing,
+}
+
+impl Verdict {
+	fn label(&self) -> &'static str {
+		match *self {
+			Verdict::Same => "ok",
+			Verdict::Improved => "improved",
+			Verdict::Within => "ok (within tolerance)",
+			Verdict::Regressed => "REGRESSED",
+			Verdict::Missing => "MISSING",
+		}
+	}
+}
+
+/// Compare a figure with its golden value, at the given tolerance if
+/// overridden
+fn verdict(golden: &Metric, current: Option<&Metric>, tolerance_pct: Option<f64>) -> Verdict {
+	let current = match current {
+		Some(c) => c.value,
+		None => return Verdict::Missing,
+	};
+	let allowed = golden.value.abs() * tolerance_pct.unwrap_or(golden.tolerance_pct) / 100.0
+		+ golden.slack;
+	// how much worse it got, negative if better
+	let worse_by = match golden.better {
+		Better::Higher => golden.value - current,
+		Better::Lower => current - golden.value,
+		Better::Equal => {
+			return if current == golden.value {
+				Verdict::Same
+			} else {
+				Verdict::Regressed
+			}
+		}
+	};
+	if worse_by == 0.0 {
+		Verdict::Same
+	} else if worse_by < 0.0 {
+		Verdict::Improved
+	} else if worse_by <= allowed {
+		Verdict::Within
+	} else {
+		Verdict::Regressed
+	}
+}
+
+/// Send the jobs of the mock scenario one after the other, each once the
+/// share of the previous one is in, and wait for every share to be
+/// answered
+fn push_mock_jobs(pool: &MockPool, stats: &Arc<RwLock<Stats>>) -> Result<(), String> {
+	if !mock_pool::wait_for(MOCK_TIMEOUT_SECS, || pool.submits().len() == 1) {
+		return Err("The client submitted no share to the mock pool".to_string());
+	}
+	for job_id in 2..MOCK_JOBS + 1 {
+		pool.push_job(mock_pool::job(99 + job_id, job_id));
+		// the held job's share only comes with the next one
+		let expected = if job_id == MOCK_HELD_JOB { job_id - 1 } else { job_id };
+		let submitted = mock_pool::wait_for(MOCK_TIMEOUT_SECS, || {
+			pool.submits().len() as u64 >= expected
+		});
+		if !submitted {
+			return Err(format!("No share submitted for job {} of the mock pool", job_id));
+		}
+	}
+	let answered = mock_pool::wait_for(MOCK_TIMEOUT_SECS, || {
+		let (accepted, rejected, stale) = pool.share_counts();
+		accepted + rejected + stale == MOCK_JOBS
+			&& stats.read().unwrap().client_stats.submits_pending == 0
+	});
+	if !answered {
+		return Err("The client didn't get every share answered".to_string());
+	}
+	Ok(())
+}
+
+/// Run the stratum client against the mock pool, the solver answering
+/// every job, and record the shares as the pool saw them and the
+/// client's submit latencies
+fn run_mock() -> Result<QualificationResult, String> {
+	let pool = MockPool::start(Script {
+		jobs: vec![mock_pool::job(100, 1)],
+		replies: vec![
+			Reply::Accept,
+			Reply::Reject(-32502, "Failed to validate solution".to_string()),
+			Reply::BlockFound,
+		],
+	});
+	let stats = Arc::new(RwLock::new(Stats::default()));
+	let (miner_tx, miner_rx) = mpsc::channel();
+	let mut cc = client::Controller::new(
+		&pool.addr(),
+		Some("qualify".to_string()),
+		Some("x".to_string()),
+		miner_tx,
+		stats.clone(),
+	).map_err(|e| format!("Unable to start the stratum client: {:?}", e))?;
+	let client_tx = cc.tx.clone();
+	mock_pool::mock_solver(miner_rx, client_tx.clone(), vec![MOCK_HELD_JOB]);
+	let _ = thread::Builder::new()
+		.name("qualify_client".to_string())
+		.spawn(move || cc.run());
+
+	let result = push_mock_jobs(&pool, &stats);
+	let _ = client_tx.send(ClientMessage::Shutdown);
+	result?;
+
+	let (accepted, rejected, stale) = pool.share_counts();
+	let stats = stats.read().unwrap();
+	let latencies = stats.timing.averages();
+	let mut metrics = BTreeMap::new();
+	metrics.insert("pool.submits".to_string(), Metric::exact(pool.submits().len() as f64));
+	metrics.insert("pool.accepted".to_string(), Metric::exact(accepted as f64));
+	metrics.insert("pool.rejected".to_string(), Metric::exact(rejected as f64));
+	metrics.insert("pool.stale".to_string(), Metric::exact(stale as f64));
+	metrics.insert(
+		"pool.blocks_found".to_string(),
+		Metric::exact(stats.client_stats.blocks_found as f64),
+	);
+	// localhost round trips, a few ms either way is noise
+	if let Some(ms) = latencies.submit {
+		metrics.insert("latency.submit_ms".to_string(), Metric::lower(ms, 50.0, 5.0));
+	}
+	if let Some(ms) = latencies.ack {
+		metrics.insert("latency.ack_ms".to_string(), Metric::lower(ms, 50.0, 5.0));
+	}
+	Ok(QualificationResult {
+		version: env!("CARGO_PKG_VERSION").to_string(),
+		timestamp: time::get_time().sec,
+		scenario: "mock".to_string(),
+		devices: BTreeMap::new(),
+		metrics: metrics,
+	})
+}
+
+/// Benchmark every device of every configured plugin on the same header,
+/// recording their GPS, p95 graph time and memory used
+fn run_devices(config: &MinerConfig, duration_secs: u64) -> Result<QualificationResult, String> {
+	let plugins = plugin::enumerate_devices(config)?;
+	plugin::reset_loaded_config();
+	let pre_pow = benchmark::seeded_pre_pow(DEVICE_SEED);
+	let mut devices = BTreeMap::new();
+	let mut metrics = BTreeMap::new();
+	for (i, plugin_devices) in plugins.into_iter().enumerate() {
+		let plugin_config = &config.miner_plugin_config[i];
+		let name = format!("{}_{}", plugin_config.type_filter, plugin_config.edge_bits);
+		let plugin_devices = match plugin_devices {
+			Ok(d) => d,
+			Err(e) => {
+				println!("Plugin {} ({}): unable to query devices: {}", i, name, e);
+				continue;
+			}
+		};
+		for d in plugin_devices.iter().filter(|d| d.in_use == 1) {
+			let id = match d.device_id.parse::<u32>() {
+				Ok(id) => id,
+				Err(_) => continue,
+			};
+			let key = format!("device.{}.{}", name, id);
+			println!(
+				"Benchmarking plugin {} ({}) device {} ({}) for {}s...",
+				i, name, id, d.device_name, duration_secs
+			);
+			devices.insert(key.clone(), d.device_name.trim().to_string());
+			let r = match benchmark::run_on(
+				config,
+				i,
+				id,
+				duration_secs,
+				benchmark::DEFAULT_WARMUP_GRAPHS,
+				&pre_pow,
+			) {
+				Ok(r) => r,
+				Err(e) => {
+					// left out of the results, so a comparison fails on it
+					println!("  failed, {}", e);
+					continue;
+				}
+			};
+			println!("  {:.4} gps, p95 graph time {:.1} ms", r.gps, r.p95_ms);
+			metrics.insert(format!("{}.gps", key), Metric::higher(r.gps, 3.0));
+			metrics.insert(format!("{}.p95_ms", key), Metric::lower(r.p95_ms, 10.0, 0.0));
+			if let Some(mb) = r.memory_mb {
+				metrics.insert(format!("{}.memory_mb", key), Metric::lower(mb as f64, 10.0, 0.0));
+			}
+		}
+	}
+	if devices.is_empty() {
+		return Err("No devices to benchmark".to_string());
+	}
+	Ok(QualificationResult {
+		version: env!("CARGO_PKG_VERSION").to_string(),
+		timestamp: time::get_time().sec,
+		scenario: "devices".to_string(),
+		devices: devices,
+		metrics: metrics,
+	})
+}
+
+/// Compare results with golden ones, printing each figure, and failing
+/// if any regressed or is missing
+fn compare(
+	golden: &QualificationResult,
+	current: &QualificationResult,
+	tolerance_pct: Option<f64>,
+) -> Result<(), String> {
+	if golden.scenario != current.scenario {
+		return Err(format!(
+			"The golden results are of the {} scenario, these of the {} one",
+			golden.scenario, current.scenario
+		));
+	}
+	for (key, name) in &golden.devices {
+		match current.devices.get(key) {
+			Some(n) if n != name => {
+				println!("Warning: {} was a {}, it's a {} now", key, name, n)
+			}
+			_ => {}
+		}
+	}
+	println!();
+	println!("Compared with grin-miner {} (golden):", golden.version);
+	println!(
+		"{:<44} {:>12} {:>12} {:>9}  {}",
+		"Figure", "Golden", "Current", "Change", "Result"
+	);
+	let mut regressions = 0;
+	for (key, g) in &golden.metrics {
+		let c = current.metrics.get(key);
+		let v = verdict(g, c, tolerance_pct);
+		if v == Verdict::Regressed || v == Verdict::Missing {
+			regressions += 1;
+		}
+		let (value, change) = match c {
+			Some(c) if g.value != 0.0 => (
+				format!("{:.3}", c.value),
+				format!("{:+.1}%", (c.value - g.value) * 100.0 / g.value.abs()),
+			),
+			Some(c) => (format!("{:.3}", c.value), "".to_string()),
+			None => ("-".to_string(), "".to_string()),
+		};
+		println!(
+			"{:<44} {:>12} {:>12} {:>9}  {}",
+			key,
+			format!("{:.3}", g.value),
+			value,
+			change,
+			v.label()
+		);
+	}
+	for key in current.metrics.keys().filter(|k| !golden.metrics.contains_key(*k)) {
+		println!("{:<44} {:>12} {:>12} {:>9}  new", key, "-", "", "");
+	}
+	if regressions > 0 {
+		Err(format!("{} figures regressed against the golden results", regressions))
+	} else {
+		println!("No regressions against the golden results");
+		Ok(())
+	}
+}
+
+fn read_results(path: &str) -> Result<QualificationResult, String> {
+	let mut json = String::new();
+	File::open(path)
+		.and_then(|mut f| f.read_to_string(&mut json))
+		.map_err(|e| format!("Unable to read {}: {}", path, e))?;
+	serde_json::from_str(&json).map_err(|e| format!("Invalid results in {}: {}", path, e))
+}
+
+/// Run the qualification scenario, write its results to the output file
+/// and compare them with the golden file, if given
+pub fn qualify(
+	config: MinerConfig,
+	devices: bool,
+	duration_secs: u64,
+	output: Option<&str>,
+	golden: Option<&str>,
+	tolerance_pct: Option<f64>,
+) -> Result<(), String> {
+	// read first, not to run the scenario for nothing
+	let golden = match golden {
+		Some(path) => Some(read_results(path)?),
+		None => None,
+	};
+	let result = if devices {
+		run_devices(&config, duration_secs)?
+	} else {
+		println!("Running the mock pool scenario, {} jobs...", MOCK_JOBS);
+		run_mock()?
+	};
+	let path = output.unwrap_or(DEFAULT_OUTPUT);
+	let json = serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?;
+	File::create(path)
+		.and_then(|mut f| f.write_all(json.as_bytes()))
+		.map_err(|e| format!("Unable to write {}: {}", path, e))?;
+	println!("Results written to {}", path);
+	match golden {
+		Some(g) => compare(&g, &result, tolerance_pct),
+		None => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_verdict() {
+		let gps = Metric::higher(10.0, 3.0);
+		assert_eq!(verdict(&gps, Some(&Metric::higher(10.0, 3.0)), None), Verdict::Same);
+		assert_eq!(verdict(&gps, Some(&Metric::higher(10.5, 3.0)), None), Verdict::Improved);
+		assert_eq!(verdict(&gps, Some(&Metric::higher(9.8, 3.0)), None), Verdict::Within);
+		assert_eq!(verdict(&gps, Some(&Metric::higher(9.5, 3.0)), None), Verdict::Regressed);
+		assert_eq!(verdict(&gps, Some(&Metric::higher(9.5, 3.0)), Some(10.0)), Verdict::Within);
+		assert_eq!(verdict(&gps, None, None), Verdict::Missing);
+		let ack = Metric::lower(1.0, 50.0, 5.0);
+		assert_eq!(verdict(&ack, Some(&Metric::lower(4.0, 50.0, 5.0)), None), Verdict::Within);
+		assert_eq!(verdict(&ack, Some(&Metric::lower(8.0, 50.0, 5.0)), None), Verdict::Regressed);
+		let stale = Metric::exact(1.0);
+		assert_eq!(verdict(&stale, Some(&Metric::exact(2.0)), None), Verdict::Regressed);
+	}
+
+	#[test]
+	fn test_mock_scenario() {
+		let result = run_mock().unwrap();
+		let value = |key: &str| result.metrics[key].value;
+		assert_eq!(value("pool.submits"), MOCK_JOBS as f64);
+		assert_eq!(value("pool.accepted"), (MOCK_JOBS - 2) as f64);
+		assert_eq!(value("pool.rejected"), 1.0);
+		assert_eq!(value("pool.stale"), 1.0);
+		assert_eq!(value("pool.blocks_found"), 1.0);
+	}
+}
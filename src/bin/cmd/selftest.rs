@@ -0,0 +1,178 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `selftest`, running each configured plugin on each of its devices on
+//! synthetic headers and checking every solution it returns is a 42-cycle
+//! of the header's graph, with a verifier of our own. Plugins pick their
+//! own nonces, so the cycles can't be compared with known ones; checking
+//! them against the graph catches the same broken solvers.
+
+use std::thread;
+use std::time::Duration;
+
+use chain;
+use clock;
+use cmd::benchmark;
+use config::MinerConfig;
+use plugin::{self, PluginMiner};
+use sandbox::Solution;
+use util;
+use verifier::{self, Algorithm};
+
+/// Default seconds each device is tested for
+pub const DEFAULT_DURATION_SECS: u64 = 300;
+
+/// Solutions verified after which a device passes without waiting out
+/// the duration
+const SOLUTIONS_WANTED: usize = 3;
+
+/// Outcome of testing a device
+enum Outcome {
+	/// Solutions found, all verified
+	Pass(usize),
+	/// No solutions found in time, graphs were searched
+	Inconclusive(u64),
+	/// An invalid solution, or the device failed
+	Fail(String),
+	/// No verifier for the plugin's algorithm
+	Skipped(String),
+}
+
+/// Test one device of a plugin
+fn test_device(config: &MinerConfig, plugin_index: usize, device: u32, duration_secs: u64) -> Outcome {
+	let mut plugin_config = config.miner_plugin_config[plugin_index].clone();
+	let algorithm = match Algorithm::from_type_filter(&plugin_config.type_filter) {
+		Some(a) => a,
+		None => return Outcome::Skipped(format!("no verifier for {}", plugin_config.type_filter)),
+	};
+	let pow_chain = match chain::from_name(config.pow_chain.as_ref().map(|c| c.as_str())) {
+		Ok(c) => c,
+		Err(e) => return Outcome::Fail(e),
+	};
+	plugin_config.devices = Some(device.to_string());
+	plugin_config.exclude_devices = None;
+	plugin_config.instances = None;
+	let mut test_config = config.clone();
+	test_config.miner_plugin_config = vec![plugin_config.clone()];
+
+	plugin::reset_loaded_config();
+	let mut plugin_miner = PluginMiner::new();
+	plugin_miner.init(test_config);
+	let miner = plugin_miner.get_consumable();
+	let pre_pow = benchmark::synthetic_pre_pow();
+	let pre_pow_bytes = util::from_hex(pre_pow.clone()).unwrap();
+	// difficulty 0, every cycle found is returned
+	let mut job_handle = match miner.notify(1, &pre_pow, "", 0, false) {
+		Ok(h) => h,
+		Err(e) => return Outcome::Fail(format!("unable to start the plugin: {:?}", e)),
+	};
+
+	let start = clock::mono_secs();
+	let mut verified = 0;
+	let mut graphs = 0;
+	let outcome = 'test: loop {
+		while let Some(s) = job_handle.get_solution() {
+			let s = Solution::from(&s);
+			let edges: Vec<u64> = s.solution_nonces.iter().map(|n| *n as u64).collect();
+			let verified_edges = verifier::verify(
+				&*pow_chain,
+				algorithm,
+				plugin_config.edge_bits,
+				&pre_pow_bytes,
+				s.nonce,
+				&edges,
+			);
+			match verified_edges {
+				Ok(()) => verified += 1,
+				Err(e) => {
+					break 'test Outcome::Fail(format!(
+						"invalid solution for nonce {}: {} (edges {:?})",
+						s.nonce, e, s.solution_nonces
+					))
+				}
+			}
+		}
+		if let Ok(stats) = job_handle.get_stats(0) {
+			if let Some(d) = stats.iter().find(|d| d.device_id == device.to_string()) {
+				if d.has_errored != 0 {
+					break Outcome::Fail("device errored".to_string());
+				}
+				graphs = d.iterations_completed as u64;
+			}
+		}
+		if verified >= SOLUTIONS_WANTED {
+			break Outcome::Pass(verified);
+		}
+		if clock::mono_secs() - start >= duration_secs as i64 {
+			break if verified > 0 {
+				Outcome::Pass(verified)
+			} else {
+				Outcome::Inconclusive(graphs)
+			};
+		}
+		thread::sleep(Duration::from_millis(100));
+	};
+	job_handle.stop_jobs();
+	plugin::reset_loaded_config();
+	outcome
+}
+
+/// Test every device of every configured plugin, reporting pass or fail
+/// for each, and failing if any device fails
+pub fn selftest(config: MinerConfig, duration_secs: u64) -> Result<(), String> {
+	let plugins = plugin::enumerate_devices(&config)?;
+	plugin::reset_loaded_config();
+	let mut failures = 0;
+	for (i, devices) in plugins.into_iter().enumerate() {
+		let name = format!(
+			"{}_{}",
+			config.miner_plugin_config[i].type_filter, config.miner_plugin_config[i].edge_bits
+		);
+		let devices = match devices {
+			Ok(d) => d,
+			Err(e) => {
+				println!("Plugin {} ({}): FAIL, unable to query devices: {}", i, name, e);
+				failures += 1;
+				continue;
+			}
+		};
+		for d in devices.iter().filter(|d| d.in_use == 1) {
+			let id = match d.device_id.parse::<u32>() {
+				Ok(id) => id,
+				Err(_) => continue,
+			};
+			println!(
+				"Plugin {} ({}) device {} ({}): testing for up to {}s...",
+				i, name, id, d.device_name, duration_secs
+			);
+			match test_device(&config, i, id, duration_secs) {
+				Outcome::Pass(n) => println!("  PASS, {} solutions verified", n),
+				Outcome::Inconclusive(graphs) => println!(
+					"  INCONCLUSIVE, no solutions in {} graphs, try a longer --duration",
+					graphs
+				),
+				Outcome::Fail(e) => {
+					println!("  FAIL, {}", e);
+					failures += 1;
+				}
+				Outcome::Skipped(why) => println!("  SKIPPED, {}", why),
+			}
+		}
+	}
+	if failures > 0 {
+		Err(format!("{} selftest failures", failures))
+	} else {
+		Ok(())
+	}
+}
@@ -0,0 +1,124 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `export-shares`, checking the share ledger's signatures and exporting
+//! it as CSV or JSON to reconcile against the shares a pool reports
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use serde_json;
+
+use config::MinerConfig;
+use ledger::{self, LedgerEntry};
+
+/// Counts of the shares exported, by what became of them
+#[derive(Debug, Default, Serialize)]
+struct Totals {
+	shares: u64,
+	accepted: u64,
+	rejected: u64,
+	unanswered: u64,
+	/// Share difficulty of the accepted shares, added up, as a pool
+	/// credits them
+	accepted_difficulty: u64,
+}
+
+impl Totals {
+	fn count(entries: &[&LedgerEntry]) -> Totals {
+		let mut totals = Totals::default();
+		for e in entries {
+			totals.shares += 1;
+			match e.result.as_str() {
+				"accepted" | "block" => {
+					totals.accepted += 1;
+					totals.accepted_difficulty += e.difficulty;
+				}
+				"rejected" => totals.rejected += 1,
+				_ => totals.unanswered += 1,
+			}
+		}
+		totals
+	}
+}
+
+fn csv(entries: &[&LedgerEntry]) -> String {
+	let mut out = String::from(
+		"seq,time,pool,login,height,job_id,nonce,edge_bits,difficulty,result,response,signature\n",
+	);
+	for e in entries {
+		out.push_str(&format!(
+			"{},{},\"{}\",\"{}\",{},{},{},{},{},{},\"{}\",{}\n",
+			e.seq,
+			e.time,
+			e.pool.replace("\"", "\"\""),
+			e.login.replace("\"", "\"\""),
+			e.height,
+			e.job_id,
+			e.nonce,
+			e.edge_bits,
+			e.difficulty,
+			e.result,
+			e.response.replace("\"", "\"\""),
+			e.signature
+		));
+	}
+	out
+}
+
+/// Check the ledger and export the shares answered since the given time,
+/// to the output file or stdout
+pub fn export_shares(
+	config: MinerConfig,
+	format: &str,
+	since: i64,
+	output: Option<&str>,
+) -> Result<(), String> {
+	let path = match config.share_ledger_file {
+		Some(ref p) if !p.is_empty() => p.clone(),
+		_ => return Err("No share_ledger_file configured, there's no ledger to export".to_string()),
+	};
+	let entries = ledger::load(&path)?;
+	let verified = ledger::verify(&entries, config.share_ledger_key.as_ref().map(|k| k.as_str()));
+	if let Err(ref e) = verified {
+		eprintln!("Warning: the ledger {} doesn't check out, {}", path, e);
+	}
+	let exported: Vec<&LedgerEntry> = entries.iter().filter(|e| e.time >= since).collect();
+	let totals = Totals::count(&exported);
+	let text = if format == "json" {
+		let export = json!({
+			"ledger": path,
+			"verified": verified.is_ok(),
+			"problem": verified.err(),
+			"totals": totals,
+			"shares": exported,
+		});
+		serde_json::to_string_pretty(&export).map_err(|e| e.to_string())? + "\n"
+	} else {
+		csv(&exported)
+	};
+	match output {
+		Some(file) => File::create(file)
+			.and_then(|mut f| f.write_all(text.as_bytes()))
+			.map_err(|e| format!("Unable to write {}: {}", file, e))?,
+		None => io::stdout()
+			.write_all(text.as_bytes())
+			.map_err(|e| e.to_string())?,
+	}
+	eprintln!(
+		"{} shares: {} accepted (difficulty {}), {} rejected, {} unanswered",
+		totals.shares, totals.accepted, totals.accepted_difficulty, totals.rejected, totals.unanswered
+	);
+	Ok(())
+}
@@ -0,0 +1,201 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `tune`, sweeping a plugin's key parameters on one device with the
+//! benchmark and caching the fastest settings for later runs
+
+use std::collections::HashMap;
+
+use time;
+
+use auto;
+use cmd::benchmark;
+use config::MinerConfig;
+use plugin;
+use tuning::{self, TunedDevice};
+
+/// Default seconds each configuration is benchmarked for
+pub const DEFAULT_DURATION_SECS: u64 = 60;
+
+/// Parameters swept, with the values tried, by plugin type filter
+const SEARCH_SPACE: &[(&str, &[(&str, &[u32])])] = &[
+	(
+		"cuckatoo_cuda",
+		&[
+			("EXPAND", &[0, 1, 2]),
+			("GEN_A_TPB", &[128, 256, 512]),
+			("GEN_B_TPB", &[64, 128, 256]),
+			("TRIM_TPB", &[256, 512, 1024]),
+			("TAIL_TPB", &[256, 512, 1024]),
+		],
+	),
+	(
+		"cuckaroo_cuda",
+		&[
+			("EXPAND", &[0, 1, 2]),
+			("GEN_A_TPB", &[128, 256, 512]),
+			("TRIM_TPB", &[256, 512, 1024]),
+		],
+	),
+	(
+		"cuckatoo_lean_cuda",
+		&[("N_BLOCKS", &[64, 128, 256]), ("TPB", &[128, 256, 512])],
+	),
+	(
+		"cuckatoo_ocl",
+		&[("EXPAND", &[0, 1]), ("TRIM_TPB", &[128, 256, 512])],
+	),
+];
+
+/// Values of NUM_THREADS tried for CPU plugins: powers of two up to the
+/// number of threads, and that number
+fn thread_counts() -> Vec<u32> {
+	let max = auto::cpu_threads() as u32;
+	let mut counts: Vec<u32> = (0..8).map(|i| 1 << i).filter(|n| *n < max).collect();
+	counts.push(max);
+	counts
+}
+
+/// Sweep the plugin's parameters one at a time on the device, keeping the
+/// fastest value of each before moving on to the next, and cache the best
+/// settings found
+pub fn tune(config: MinerConfig, plugin_index: usize, device: u32, duration_secs: u64) -> Result<(), String> {
+	let plugin_config = config
+		.miner_plugin_config
+		.get(plugin_index)
+		.cloned()
+		.ok_or(format!("No plugin {} in the configuration", plugin_index))?;
+	let name = format!("{}_{}", plugin_config.type_filter, plugin_config.edge_bits);
+	let caps = plugin::available_plugins(&config)?
+		.into_iter()
+		.find(|c| c.name.contains(&name))
+		.ok_or(format!("No installed plugin {}, see list-plugins", name))?;
+
+	let mut space: Vec<(String, Vec<u32>)> = SEARCH_SPACE
+		.iter()
+		.find(|&&(t, _)| t == plugin_config.type_filter)
+		.map(|&(_, params)| {
+			params
+				.iter()
+				.map(|&(n, values)| (n.to_string(), values.to_vec()))
+				.collect()
+		})
+		.unwrap_or_default();
+	if plugin_config.type_filter.contains("cpu") {
+		space.push(("NUM_THREADS".to_string(), thread_counts()));
+	}
+	// only parameters the plugin has, at values it accepts
+	let space: Vec<(String, Vec<u32>)> = space
+		.into_iter()
+		.filter_map(|(n, values)| {
+			let param = caps.parameters.iter().find(|p| p.name == n)?;
+			let values: Vec<u32> = values
+				.into_iter()
+				.filter(|v| *v >= param.min_value && *v <= param.max_value)
+				.collect();
+			if values.len() < 2 {
+				None
+			} else {
+				Some((n, values))
+			}
+		})
+		.collect();
+	if space.is_empty() {
+		return Err(format!("Plugin {} has no parameters to tune", name));
+	}
+	let runs: usize = space.iter().map(|s| s.1.len()).sum();
+	println!(
+		"Tuning {} on device {}: {} configurations of {}s each, about {} minutes",
+		name,
+		device,
+		runs,
+		duration_secs,
+		(runs as u64 * duration_secs + 59) / 60
+	);
+
+	let key = device.to_string();
+	let mut best: HashMap<String, u32> = plugin_config
+		.device_parameters
+		.as_ref()
+		.and_then(|p| p.get(&key))
+		.cloned()
+		.unwrap_or_default();
+	let mut best_gps = 0.0;
+	let mut device_name = String::new();
+	for &(ref param, ref values) in &space {
+		let mut best_value = None;
+		for value in values {
+			let mut params = best.clone();
+			params.insert(param.clone(), *value);
+			let mut trial = config.clone();
+			{
+				let p = &mut trial.miner_plugin_config[plugin_index];
+				p.device_parameters
+					.get_or_insert_with(HashMap::new)
+					.insert(key.clone(), params);
+				// measure the swept values, nothing else
+				p.intensity = None;
+			}
+			// without the cached settings of the parameters not swept yet
+			trial.tuning_cache = Some(String::new());
+			let result = benchmark::run(
+				&trial,
+				plugin_index,
+				device,
+				duration_secs,
+				benchmark::DEFAULT_WARMUP_GRAPHS,
+			);
+			match result {
+				Ok(r) => {
+					println!("  {} = {}: {:.4} gps", param, value, r.gps);
+					device_name = r.device_name;
+					if r.gps > best_gps {
+						best_gps = r.gps;
+						best_value = Some(*value);
+					}
+				}
+				Err(e) => println!("  {} = {}: failed, {}", param, value, e),
+			}
+		}
+		if let Some(v) = best_value {
+			best.insert(param.clone(), v);
+		}
+	}
+	if best_gps == 0.0 {
+		return Err("No configuration ran successfully".to_string());
+	}
+	// just the swept parameters, the rest are the config's business
+	best.retain(|k, _| space.iter().any(|s| s.0 == *k));
+	let mut settings: Vec<String> = best.iter().map(|(k, v)| format!("{} = {}", k, v)).collect();
+	settings.sort();
+	println!("Best: {:.4} gps with {}", best_gps, settings.join(", "));
+	tuning::save(
+		&config,
+		TunedDevice {
+			type_filter: plugin_config.type_filter.clone(),
+			edge_bits: plugin_config.edge_bits,
+			device: device,
+			device_name: device_name,
+			parameters: best,
+			gps: best_gps,
+			timestamp: time::get_time().sec,
+		},
+	)?;
+	println!(
+		"Saved to {}, used for device {} from the next run on",
+		tuning::cache_path(&config),
+		device
+	);
+	Ok(())
+}
@@ -0,0 +1,47 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `update`, checking for a newer signed release and downloading it, to be
+//! swapped in the next time the miner starts
+
+use built_info;
+use config::UpdateConfig;
+use update;
+
+/// Check for a newer release, and unless only checking, stage it
+pub fn update(config: UpdateConfig, check_only: bool) -> Result<(), String> {
+	let manifest = update::latest_release(&config)?;
+	if !update::is_newer(&manifest.version, built_info::PKG_VERSION) {
+		println!(
+			"grin-miner {} is up to date (latest release {})",
+			built_info::PKG_VERSION,
+			manifest.version
+		);
+		return Ok(());
+	}
+	println!(
+		"grin-miner {} is available (running {})",
+		manifest.version,
+		built_info::PKG_VERSION
+	);
+	if check_only {
+		return Ok(());
+	}
+	update::stage(&config, &manifest, built_info::TARGET)?;
+	println!(
+		"Release {} downloaded to {}, it will be installed the next time grin-miner starts",
+		manifest.version, config.staging_dir
+	);
+	Ok(())
+}
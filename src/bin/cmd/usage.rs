@@ -0,0 +1,71 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `--telemetry-status`, showing whether usage statistics are sent, where,
+//! and exactly what a report holds
+
+use serde_json;
+
+use cmd::attach;
+use config::{ApiConfig, MinerConfig, UsageStatsConfig};
+use plugin::enumerate_devices;
+use usage;
+
+/// Print the usage statistics settings and the report that would be sent,
+/// from the stats of the miner running locally if there is one, from the
+/// devices the plugins see otherwise
+pub fn telemetry_status(
+	config: MinerConfig,
+	usage_config: UsageStatsConfig,
+	api_config: ApiConfig,
+) -> Result<(), String> {
+	if usage_config.enabled && !usage_config.url.is_empty() {
+		println!(
+			"Anonymous usage statistics are on, sent to {} every {} hours.",
+			usage_config.url, usage_config.interval_hours
+		);
+	} else {
+		println!(
+			"Anonymous usage statistics are off, nothing is sent. Set enabled and url in \
+			 [usage_stats] to send them."
+		);
+	}
+	let url = format!(
+		"http://{}/v1/stats",
+		api_config.bind_address.replace("0.0.0.0", "127.0.0.1")
+	);
+	let authorization = api_config.control_token.map(|t| format!("Bearer {}", t));
+	let report = match attach::fetch(&url, authorization.as_ref().map(|a| a.as_str())) {
+		Ok(stats) => {
+			println!("Report, from the stats of the miner running now:");
+			usage::report_from_stats(&config, &stats)
+		}
+		Err(_) => {
+			println!(
+				"Report, from the devices the plugins see (no miner is running, the \
+				 average GPS is filled in once mining):"
+			);
+			let plugins = enumerate_devices(&config)?
+				.into_iter()
+				.map(|d| d.unwrap_or_default())
+				.collect::<Vec<_>>();
+			usage::report(&config, &plugins, &|_, _| None)
+		}
+	};
+	println!(
+		"{}",
+		serde_json::to_string_pretty(&report).map_err(|e| format!("{}", e))?
+	);
+	Ok(())
+}
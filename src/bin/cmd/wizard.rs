@@ -0,0 +1,144 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! First run setup, asking a few questions on the terminal and writing a
+//! config file for the detected hardware
+
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use config::{MinerConfig, DEFAULT_CONFIG};
+use plugin;
+
+use cmd::config_file::{detected_plugin_section, DEFAULT_PLUGIN_SECTION};
+
+/// Ask a question, returning the answer or the default if none is given
+fn ask(question: &str, default: &str) -> Result<String, String> {
+	if default.is_empty() {
+		print!("{}: ", question);
+	} else {
+		print!("{} [{}]: ", question, default);
+	}
+	io::stdout().flush().map_err(|e| format!("{}", e))?;
+	let mut answer = String::new();
+	let read = io::stdin()
+		.lock()
+		.read_line(&mut answer)
+		.map_err(|e| format!("{}", e))?;
+	if read == 0 {
+		return Err("Setup cancelled".to_string());
+	}
+	let answer = answer.trim();
+	Ok(if answer.is_empty() {
+		default.to_string()
+	} else {
+		answer.to_string()
+	})
+}
+
+fn ask_yes_no(question: &str, default: bool) -> Result<bool, String> {
+	let answer = ask(question, if default { "Y/n" } else { "y/N" })?;
+	Ok(match answer.to_lowercase().as_str() {
+		"y" | "yes" => true,
+		"n" | "no" => false,
+		_ => default,
+	})
+}
+
+fn toml_string(s: &str) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Run the setup wizard, returning the path of the config file written
+/// if mining should start with it
+pub fn run() -> Result<Option<String>, String> {
+	println!("No grin-miner.toml found, let's create one.");
+	println!();
+
+	let plugins = plugin::available_plugins(&MinerConfig::default()).unwrap_or_else(|e| {
+		println!("Unable to look for mining plugins: {}", e);
+		vec![]
+	});
+	let plugin_section = detected_plugin_section(&plugins);
+	match plugin_section {
+		Some(ref s) => println!("Detected the best plugin for this machine:\n{}", s),
+		None => println!(
+			"No usable plugins found, the default CPU plugin will be configured, \
+			 see list-plugins once they're installed"
+		),
+	}
+
+	let chain = loop {
+		let c = ask("Chain to mine on (mainnet or testnet)", "mainnet")?;
+		if c == "mainnet" || c == "testnet" {
+			break c;
+		}
+		println!("Please answer mainnet or testnet");
+	};
+	let default_port = if chain == "testnet" { 13416 } else { 3416 };
+	let pool = ask(
+		"Stratum server (pool or grin node) address",
+		&format!("127.0.0.1:{}", default_port),
+	)?;
+	let login = ask("Login, e.g. your pool account (leave empty if none)", "")?;
+	let password = if login.is_empty() {
+		String::new()
+	} else {
+		ask("Password (leave empty if none)", "")?
+	};
+	let path = ask("Config file to write", "grin-miner.toml")?;
+	if Path::new(&path).exists() && !ask_yes_no(&format!("{} exists, overwrite it?", path), false)? {
+		return Err("Setup cancelled".to_string());
+	}
+
+	let mut contents = DEFAULT_CONFIG.to_string();
+	if let Some(ref s) = plugin_section {
+		contents = contents.replace(DEFAULT_PLUGIN_SECTION, s);
+	}
+	contents = contents.replace(
+		"#chain_type = \"mainnet\"",
+		&format!("chain_type = {}", toml_string(&chain)),
+	);
+	contents = contents.replace(
+		"stratum_server_addr = \"127.0.0.1:13416\"",
+		&format!("stratum_server_addr = {}", toml_string(&pool)),
+	);
+	if !login.is_empty() {
+		contents = contents.replace(
+			"#stratum_server_login = \"http://192.168.1.100:13415\"",
+			&format!("stratum_server_login = {}", toml_string(&login)),
+		);
+	}
+	if !password.is_empty() {
+		contents = contents.replace(
+			"#stratum_server_password = \"x\"",
+			&format!("stratum_server_password = {}", toml_string(&password)),
+		);
+	}
+	let mut file = File::create(&path).map_err(|e| format!("Unable to create {}: {}", path, e))?;
+	file.write_all(contents.as_bytes())
+		.map_err(|e| format!("Unable to write {}: {}", path, e))?;
+	println!();
+	println!(
+		"Configuration written to {}, edit it to fine tune the plugin parameters",
+		path
+	);
+
+	if ask_yes_no("Start mining now?", true)? {
+		Ok(Some(path))
+	} else {
+		Ok(None)
+	}
+}
@@ -0,0 +1,377 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Plugin compatibility. A plugin bundle may ship a plugins.toml alongside
+//! the plugin files, describing each plugin's algorithm, edge bits, the
+//! minimum driver version it needs and the GPU architectures it's built
+//! for, e.g.
+//!
+//! ```toml
+//! [[plugin]]
+//! name = "cuckatoo_cuda_31"
+//! algorithm = "cuckatoo"
+//! edge_bits = 31
+//! version = "1.0.1"
+//! min_driver = "410.48"
+//! architectures = ["sm_61", "sm_70", "sm_75"]
+//! ```
+//!
+//! At startup the configured plugins are checked against the GPUs and
+//! drivers found, so a plugin that can't run fails with a message saying
+//! why rather than a driver error.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use toml;
+
+use config::types::{CuckooMinerPluginConfig, MinerConfig};
+use plugin;
+use update;
+use util::LOGGER;
+
+/// File name of the manifest in a plugin directory
+pub const MANIFEST_FILE: &str = "plugins.toml";
+
+/// A plugin, as described by the manifest
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginInfo {
+	/// Plugin name, the file name without its extension, e.g.
+	/// cuckatoo_cuda_31
+	pub name: String,
+	pub algorithm: String,
+	pub edge_bits: u8,
+	/// Plugin version, for the environment report
+	#[serde(default)]
+	pub version: Option<String>,
+	/// Lowest driver version the plugin runs with
+	#[serde(default)]
+	pub min_driver: Option<String>,
+	/// GPU architectures the plugin is built for, e.g. sm_61 for CUDA or
+	/// gfx900 for AMD, any if empty
+	#[serde(default)]
+	pub architectures: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+	#[serde(default)]
+	plugin: Vec<PluginInfo>,
+}
+
+/// Driver and GPU architectures of a GPU vendor, as detected
+#[derive(Clone, Debug, Default)]
+pub struct VendorHardware {
+	pub driver: Option<String>,
+	pub architectures: Vec<String>,
+}
+
+/// GPU drivers and architectures found on this machine
+#[derive(Clone, Debug, Default)]
+pub struct Hardware {
+	pub nvidia: VendorHardware,
+	pub amd: VendorHardware,
+}
+
+/// The plugins described by the manifests of the plugin directories.
+/// Where a plugin is in more than one, the first directory's is used.
+pub fn load_manifests(miner_config: &MinerConfig) -> Vec<PluginInfo> {
+	let mut plugins: Vec<PluginInfo> = vec![];
+	for dir in plugin::plugin_dirs(miner_config) {
+		let path = Path::new(&dir).join(MANIFEST_FILE);
+		if !path.exists() {
+			continue;
+		}
+		let mut contents = String::new();
+		let manifest = File::open(&path)
+			.and_then(|mut f| f.read_to_string(&mut contents))
+			.map_err(|e| e.to_string())
+			.and_then(|_| toml::from_str::<Manifest>(&contents).map_err(|e| e.to_string()));
+		match manifest {
+			Ok(m) => {
+				for p in m.plugin {
+					if !plugins.iter().any(|q| q.name == p.name) {
+						plugins.push(p);
+					}
+				}
+			}
+			Err(e) => warn!(LOGGER, "Ignoring invalid plugin manifest {}: {}", path.display(), e),
+		}
+	}
+	plugins
+}
+
+/// NVIDIA driver version and compute capabilities, from nvidia-smi
+fn detect_nvidia() -> VendorHardware {
+	let output = Command::new("nvidia-smi")
+		.args(&["--query-gpu=driver_version,compute_cap", "--format=csv,noheader"])
+		.output();
+	let mut hardware = VendorHardware::default();
+	let output = match output {
+		Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).into_owned(),
+		_ => return hardware,
+	};
+	for line in output.lines() {
+		let mut fields = line.split(',').map(|f| f.trim());
+		if let Some(driver) = fields.next().filter(|d| !d.is_empty()) {
+			hardware.driver = Some(driver.to_string());
+		}
+		// compute capability 7.5 is architecture sm_75
+		if let Some(cap) = fields.next().filter(|c| c.contains('.')) {
+			let arch = format!("sm_{}", cap.replace('.', ""));
+			if !hardware.architectures.contains(&arch) {
+				hardware.architectures.push(arch);
+			}
+		}
+	}
+	hardware
+}
+
+/// amdgpu driver version and GPU architectures, from sysfs
+fn detect_amd() -> VendorHardware {
+	let mut hardware = VendorHardware::default();
+	hardware.driver = fs::read_to_string("/sys/module/amdgpu/version")
+		.ok()
+		.map(|v| v.trim().to_string())
+		.filter(|v| !v.is_empty());
+	let nodes = match fs::read_dir("/sys/class/kfd/kfd/topology/nodes") {
+		Ok(n) => n,
+		Err(_) => return hardware,
+	};
+	for node in nodes.filter_map(|n| n.ok()) {
+		let properties = fs::read_to_string(node.path().join("properties")).unwrap_or_default();
+		// e.g. gfx_target_version 90010 is gfx90a, CPU nodes have 0
+		let version = properties
+			.lines()
+			.filter(|l| l.starts_with("gfx_target_version "))
+			.filter_map(|l| l.split_whitespace().nth(1))
+			.filter_map(|v| v.parse::<u32>().ok())
+			.next()
+			.unwrap_or(0);
+		if version == 0 {
+			continue;
+		}
+		let arch = format!(
+			"gfx{}{:x}{:x}",
+			version / 10000,
+			version / 100 % 100,
+			version % 100
+		);
+		if !hardware.architectures.contains(&arch) {
+			hardware.architectures.push(arch);
+		}
+	}
+	hardware
+}
+
+/// Detect the GPU drivers and architectures
+pub fn detect_hardware() -> Hardware {
+	Hardware {
+		nvidia: detect_nvidia(),
+		amd: detect_amd(),
+	}
+}
+
+/// The GPU vendors a configured plugin may mine on, none for CPU plugins
+fn vendors<'a>(
+	config: &CuckooMinerPluginConfig,
+	hardware: &'a Hardware,
+) -> Vec<(&'static str, &'a VendorHardware)> {
+	if config.type_filter.contains("cuda") {
+		vec![("NVIDIA", &hardware.nvidia)]
+	} else if config.is_opencl() {
+		match config.platform_id() {
+			Ok(Some(1)) => vec![("AMD", &hardware.amd)],
+			Ok(Some(2)) => vec![("NVIDIA", &hardware.nvidia)],
+			_ => vec![("AMD", &hardware.amd), ("NVIDIA", &hardware.nvidia)],
+		}
+	} else {
+		vec![]
+	}
+}
+
+/// Check a plugin against the hardware found. Returns why it can't run
+/// here, if it can't, and warnings about what couldn't be checked or only
+/// partly works.
+pub fn check_plugin(
+	config: &CuckooMinerPluginConfig,
+	info: &PluginInfo,
+	hardware: &Hardware,
+) -> (Option<String>, Vec<String>) {
+	let mut warnings = vec![];
+	if info.edge_bits != config.edge_bits {
+		warnings.push(format!(
+			"plugin {} is described as mining {} edge bits, but {} are configured",
+			info.name, info.edge_bits, config.edge_bits
+		));
+	}
+	let vendors: Vec<(&str, &VendorHardware)> = vendors(config, hardware)
+		.into_iter()
+		.filter(|&(_, h)| h.driver.is_some() || !h.architectures.is_empty())
+		.collect();
+	if vendors.is_empty() {
+		if info.min_driver.is_some() || !info.architectures.is_empty() {
+			warnings.push(format!(
+				"unable to detect the GPU driver, not checking plugin {} against it",
+				info.name
+			));
+		}
+		return (None, warnings);
+	}
+	let mut problems = vec![];
+	for &(vendor, h) in &vendors {
+		if let (Some(min), Some(driver)) = (info.min_driver.as_ref(), h.driver.as_ref()) {
+			if update::is_newer(min, driver) {
+				problems.push(format!(
+					"plugin {} needs {} driver {} or later, {} is installed: update the GPU \
+					 driver",
+					info.name, vendor, min, driver
+				));
+				continue;
+			}
+		}
+		if info.architectures.is_empty() || h.architectures.is_empty() {
+			return (None, warnings);
+		}
+		let unsupported: Vec<&String> = h
+			.architectures
+			.iter()
+			.filter(|a| !info.architectures.contains(a))
+			.collect();
+		if unsupported.len() == h.architectures.len() {
+			problems.push(format!(
+				"plugin {} is built for {}, but the {} GPUs are {}: use a plugin built for \
+				 them, or a lean or cpu plugin",
+				info.name,
+				info.architectures.join(", "),
+				vendor,
+				h.architectures.join(", ")
+			));
+			continue;
+		}
+		if !unsupported.is_empty() {
+			warnings.push(format!(
+				"plugin {} isn't built for the {} GPUs with architecture {}: exclude them \
+				 with exclude_devices",
+				info.name,
+				vendor,
+				unsupported
+					.iter()
+					.map(|a| a.as_str())
+					.collect::<Vec<&str>>()
+					.join(", ")
+			));
+		}
+		return (None, warnings);
+	}
+	// incompatible with every vendor it could run on
+	(Some(problems.join("; ")), warnings)
+}
+
+/// Check the configured plugins against the plugin manifests and the
+/// hardware found, warning about what may not work and, unless
+/// skip_incompatible_plugins is false, putting the plugins that can't run
+/// in standby. Fails if none of them can run.
+pub fn check_plugins(miner_config: &mut MinerConfig) -> Result<(), String> {
+	let manifest = load_manifests(miner_config);
+	if manifest.is_empty() {
+		return Ok(());
+	}
+	let hardware = detect_hardware();
+	debug!(LOGGER, "GPU hardware found: {:?}", hardware);
+	let skip = miner_config.skip_incompatible_plugins.unwrap_or(true);
+	let mut incompatible = vec![];
+	for (i, p) in miner_config.miner_plugin_config.iter().enumerate() {
+		let name = format!("{}_{}", p.type_filter, p.edge_bits);
+		let info = match manifest.iter().find(|m| m.name == name) {
+			Some(m) => m,
+			None => {
+				debug!(LOGGER, "Plugin {} isn't in a plugin manifest, not checking it", name);
+				continue;
+			}
+		};
+		let (problem, warnings) = check_plugin(p, info, &hardware);
+		for w in warnings {
+			warn!(LOGGER, "Cuckoo plugin {}: {}", i, w);
+		}
+		if let Some(problem) = problem {
+			if skip {
+				error!(LOGGER, "Cuckoo plugin {}: {}, keeping it in standby", i, problem);
+				incompatible.push(i);
+			} else {
+				warn!(LOGGER, "Cuckoo plugin {}: {}", i, problem);
+			}
+		}
+	}
+	if incompatible.is_empty() {
+		return Ok(());
+	}
+	if incompatible.len() == miner_config.miner_plugin_config.len() {
+		return Err(
+			"None of the configured plugins can run on this machine's GPUs and drivers".to_string(),
+		);
+	}
+	// in place, plugins are known by their index
+	for i in incompatible {
+		miner_config.miner_plugin_config[i].enabled = Some(false);
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn info() -> PluginInfo {
+		PluginInfo {
+			name: "cuckatoo_cuda_31".to_string(),
+			algorithm: "cuckatoo".to_string(),
+			edge_bits: 31,
+			version: None,
+			min_driver: Some("410.48".to_string()),
+			architectures: vec!["sm_61".to_string(), "sm_75".to_string()],
+		}
+	}
+
+	fn cuda() -> CuckooMinerPluginConfig {
+		let mut config = CuckooMinerPluginConfig::default();
+		config.type_filter = "cuckatoo_cuda".to_string();
+		config.edge_bits = 31;
+		config
+	}
+
+	fn nvidia(driver: &str, architectures: &[&str]) -> Hardware {
+		let mut hardware = Hardware::default();
+		hardware.nvidia.driver = Some(driver.to_string());
+		hardware.nvidia.architectures = architectures.iter().map(|a| a.to_string()).collect();
+		hardware
+	}
+
+	#[test]
+	fn compatibility() {
+		let (problem, warnings) = check_plugin(&cuda(), &info(), &nvidia("418.56", &["sm_75"]));
+		assert!(problem.is_none() && warnings.is_empty());
+		let (problem, _) = check_plugin(&cuda(), &info(), &nvidia("396.26", &["sm_75"]));
+		assert!(problem.unwrap().contains("update the GPU driver"));
+		let (problem, _) = check_plugin(&cuda(), &info(), &nvidia("418.56", &["sm_50"]));
+		assert!(problem.is_some());
+		let (problem, warnings) =
+			check_plugin(&cuda(), &info(), &nvidia("418.56", &["sm_50", "sm_61"]));
+		assert!(problem.is_none() && warnings.len() == 1);
+		let (problem, warnings) = check_plugin(&cuda(), &info(), &Hardware::default());
+		assert!(problem.is_none() && warnings.len() == 1);
+	}
+}
@@ -0,0 +1,133 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remote control of the miner, so headless rigs can be paused, resumed
+//! and reconfigured from farm software
+
+use std::sync::mpsc;
+
+use serde_json;
+
+use api::Response;
+use types::{ClientMessage, MinerMessage};
+use util::{self, LogLevel, LOGGER};
+
+/// Device to pause or resume, if not the whole miner
+#[derive(Deserialize)]
+struct DeviceParams {
+	plugin: usize,
+	device: u32,
+}
+
+/// Plugin whose standby devices to activate, if not all
+#[derive(Deserialize)]
+struct ActivateParams {
+	plugin: usize,
+}
+
+/// Pool to switch to
+#[derive(Deserialize)]
+struct PoolParams {
+	url: String,
+	login: Option<String>,
+	password: Option<String>,
+}
+
+/// Device profile to switch to
+#[derive(Deserialize)]
+struct ProfileParams {
+	name: String,
+}
+
+/// Log level to switch to
+#[derive(Deserialize)]
+struct LogLevelParams {
+	level: LogLevel,
+}
+
+/// Sends control commands on to the mining and stratum client controllers
+#[derive(Clone)]
+pub struct Control {
+	miner_tx: mpsc::Sender<MinerMessage>,
+	client_tx: mpsc::Sender<ClientMessage>,
+}
+
+impl Control {
+	/// Create a new control
+	pub fn new(
+		miner_tx: mpsc::Sender<MinerMessage>,
+		client_tx: mpsc::Sender<ClientMessage>,
+	) -> Control {
+		Control {
+			miner_tx: miner_tx,
+			client_tx: client_tx,
+		}
+	}
+
+	/// Run a control command, with its (JSON) parameters as body
+	pub fn handle(&self, command: &str, body: &str) -> Response {
+		let body = if body.trim().is_empty() { "null" } else { body };
+		info!(LOGGER, "Control command received: {} {}", command, body);
+		let result = match command {
+			"pause" => match serde_json::from_str::<Option<DeviceParams>>(body) {
+				Ok(Some(d)) => self.send_miner(MinerMessage::PauseDevice(d.plugin, d.device)),
+				Ok(None) => self.send_miner(MinerMessage::Pause),
+				Err(e) => Err(format!("{}", e)),
+			},
+			"resume" => match serde_json::from_str::<Option<DeviceParams>>(body) {
+				Ok(Some(d)) => self.send_miner(MinerMessage::ResumeDevice(d.plugin, d.device)),
+				Ok(None) => self.send_miner(MinerMessage::Resume),
+				Err(e) => Err(format!("{}", e)),
+			},
+			"activate" => match serde_json::from_str::<Option<ActivateParams>>(body) {
+				Ok(a) => self.send_miner(MinerMessage::Activate(a.map(|a| a.plugin))),
+				Err(e) => Err(format!("{}", e)),
+			},
+			"reload" => self.send_miner(MinerMessage::ReloadPlugins),
+			"reconnect" => self.send_client(ClientMessage::Reconnect),
+			"pool" => match serde_json::from_str::<PoolParams>(body) {
+				Ok(p) => self.send_client(ClientMessage::SwitchPool(p.url, p.login, p.password)),
+				Err(e) => Err(format!("{}", e)),
+			},
+			"profile" => match serde_json::from_str::<ProfileParams>(body) {
+				Ok(p) => self.send_miner(MinerMessage::SwitchProfile(p.name)),
+				Err(e) => Err(format!("{}", e)),
+			},
+			"loglevel" => match serde_json::from_str::<LogLevelParams>(body) {
+				Ok(l) => {
+					util::set_log_level(l.level);
+					Ok(())
+				}
+				Err(e) => Err(format!("{}", e)),
+			},
+			_ => return Response::error(404, "not found"),
+		};
+		match result {
+			Ok(()) => Response::json(&"ok"),
+			Err(e) => Response::error(400, &e),
+		}
+	}
+
+	fn send_miner(&self, message: MinerMessage) -> Result<(), String> {
+		self.miner_tx
+			.send(message)
+			.map_err(|_| "mining controller has stopped".to_string())
+	}
+
+	fn send_client(&self, message: ClientMessage) -> Result<(), String> {
+		self.client_tx
+			.send(message)
+			.map_err(|_| "stratum client has stopped".to_string())
+	}
+}
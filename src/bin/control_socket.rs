@@ -0,0 +1,276 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local control socket, taking the control commands of the HTTP API
+//! without opening a TCP port, for automation on the rig itself (and the
+//! `ctl` subcommand). Requests and replies are JSON, one per line. Only
+//! the miner's user can connect, and a token can be required on top. On
+//! Windows it's a named pipe, local clients only, which other users can
+//! open for reading only and so can't send commands.
+
+use std::io::{BufRead, BufReader, Write};
+
+use serde_json::Value;
+
+use config::ControlSocketConfig;
+use control::Control;
+use util::LOGGER;
+
+/// A control command
+#[derive(Serialize, Deserialize)]
+pub struct Request {
+	/// Command, as in /v1/control/<command>
+	pub command: String,
+	/// Parameters, as the HTTP request body
+	#[serde(default)]
+	pub params: Value,
+	#[serde(default)]
+	pub token: Option<String>,
+}
+
+/// The answer to a command, with the HTTP API's status code and body
+#[derive(Serialize, Deserialize)]
+pub struct Reply {
+	pub code: u16,
+	pub body: Value,
+}
+
+/// Serves control commands on the configured socket
+pub struct ControlSocket {
+	config: ControlSocketConfig,
+	control: Control,
+}
+
+impl ControlSocket {
+	/// Create a new control socket
+	pub fn new(config: ControlSocketConfig, control: Control) -> ControlSocket {
+		ControlSocket {
+			config: config,
+			control: control,
+		}
+	}
+
+	fn handle(&self, line: &str) -> Reply {
+		let request: Request = match ::serde_json::from_str(line) {
+			Ok(r) => r,
+			Err(e) => {
+				return Reply {
+					code: 400,
+					body: json!({ "error": format!("{}", e) }),
+				}
+			}
+		};
+		if let Some(ref token) = self.config.token {
+			if request.token.as_ref() != Some(token) {
+				return Reply {
+					code: 401,
+					body: json!({ "error": "unauthorized" }),
+				};
+			}
+		}
+		let params = match request.params {
+			Value::Null => String::new(),
+			ref p => p.to_string(),
+		};
+		let response = self.control.handle(&request.command, &params);
+		Reply {
+			code: response.code,
+			body: ::serde_json::from_str(&response.body).unwrap_or(Value::Null),
+		}
+	}
+
+	/// Answer the requests of a connection until it closes
+	fn serve<R: BufRead, W: Write>(&self, reader: R, mut writer: W) {
+		for line in reader.lines() {
+			let line = match line {
+				Ok(l) => l,
+				Err(_) => break,
+			};
+			if line.trim().is_empty() {
+				continue;
+			}
+			let reply = ::serde_json::to_string(&self.handle(&line)).unwrap();
+			if writeln!(writer, "{}", reply).is_err() {
+				break;
+			}
+		}
+	}
+
+	/// Start listening in a new thread
+	#[cfg(unix)]
+	pub fn start(self) {
+		use std::fs;
+		use std::os::unix::fs::PermissionsExt;
+		use std::os::unix::net::UnixListener;
+		use std::thread;
+
+		// left behind by a miner that didn't exit cleanly
+		let _ = fs::remove_file(&self.config.path);
+		let listener = match UnixListener::bind(&self.config.path) {
+			Ok(l) => l,
+			Err(e) => {
+				error!(
+					LOGGER,
+					"Unable to listen on control socket {}: {}", self.config.path, e
+				);
+				return;
+			}
+		};
+		if let Err(e) = fs::set_permissions(&self.config.path, fs::Permissions::from_mode(0o600)) {
+			error!(
+				LOGGER,
+				"Unable to restrict control socket {}, not listening: {}", self.config.path, e
+			);
+			return;
+		}
+		info!(LOGGER, "Control socket listening on {}", self.config.path);
+		let _ = thread::Builder::new()
+			.name("control_socket".to_string())
+			.spawn(move || {
+				for stream in listener.incoming() {
+					let stream = match stream {
+						Ok(s) => s,
+						Err(e) => {
+							debug!(LOGGER, "Control socket connection error: {}", e);
+							continue;
+						}
+					};
+					let writer = match stream.try_clone() {
+						Ok(w) => w,
+						Err(_) => continue,
+					};
+					self.serve(BufReader::new(stream), writer);
+				}
+			});
+	}
+
+	/// Start listening on the named pipe in a new thread
+	#[cfg(windows)]
+	pub fn start(self) {
+		use std::thread;
+
+		let name = pipe_name(&self.config.path);
+		// the first instance is created here, to fail now if another
+		// process has the pipe
+		let mut instance = match pipe::create(&name) {
+			Ok(h) => h,
+			Err(e) => {
+				error!(LOGGER, "Unable to listen on control pipe {}: {}", name, e);
+				return;
+			}
+		};
+		info!(LOGGER, "Control socket listening on {}", name);
+		let _ = thread::Builder::new()
+			.name("control_socket".to_string())
+			.spawn(move || loop {
+				match pipe::connect(&instance) {
+					Ok(()) => self.serve(BufReader::new(&instance), &instance),
+					Err(e) => debug!(LOGGER, "Control pipe connection error: {}", e),
+				}
+				// closing the instance disconnects the client
+				drop(instance);
+				instance = match pipe::create(&name) {
+					Ok(h) => h,
+					Err(e) => {
+						error!(LOGGER, "Unable to listen on control pipe {}: {}", name, e);
+						return;
+					}
+				};
+			});
+	}
+}
+
+/// Name of the pipe the control socket's path stands for on Windows:
+/// its file name under \\.\pipe\, unless it's a pipe name already
+#[cfg(windows)]
+pub fn pipe_name(path: &str) -> String {
+	use std::path::Path;
+
+	if path.starts_with(r"\\.\pipe\") {
+		return path.to_string();
+	}
+	let name = Path::new(path)
+		.file_name()
+		.and_then(|n| n.to_str())
+		.unwrap_or("grin-miner.sock");
+	format!(r"\\.\pipe\{}", name)
+}
+
+#[cfg(windows)]
+mod pipe {
+	use std::ffi::OsStr;
+	use std::fs::File;
+	use std::io;
+	use std::os::windows::ffi::OsStrExt;
+	use std::os::windows::io::{AsRawHandle, FromRawHandle, RawHandle};
+	use std::ptr;
+
+	const PIPE_ACCESS_DUPLEX: u32 = 0x0000_0003;
+	const FILE_FLAG_FIRST_PIPE_INSTANCE: u32 = 0x0008_0000;
+	// byte type and read mode, blocking
+	const PIPE_MODE: u32 = 0;
+	const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x0000_0008;
+	const BUFFER_SIZE: u32 = 4096;
+	const ERROR_PIPE_CONNECTED: i32 = 535;
+
+	extern "system" {
+		fn CreateNamedPipeW(
+			name: *const u16,
+			open_mode: u32,
+			pipe_mode: u32,
+			max_instances: u32,
+			out_buffer_size: u32,
+			in_buffer_size: u32,
+			default_timeout: u32,
+			security_attributes: *mut u8,
+		) -> RawHandle;
+		fn ConnectNamedPipe(pipe: RawHandle, overlapped: *mut u8) -> i32;
+	}
+
+	/// Create the single instance of the pipe, failing if another process
+	/// has one by that name. The default security descriptor gives other
+	/// users read access only.
+	pub fn create(name: &str) -> io::Result<File> {
+		let wide: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+		let handle = unsafe {
+			CreateNamedPipeW(
+				wide.as_ptr(),
+				PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+				PIPE_MODE | PIPE_REJECT_REMOTE_CLIENTS,
+				1,
+				BUFFER_SIZE,
+				BUFFER_SIZE,
+				0,
+				ptr::null_mut(),
+			)
+		};
+		// INVALID_HANDLE_VALUE
+		if handle as isize == -1 {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(unsafe { File::from_raw_handle(handle) })
+	}
+
+	/// Wait for a client on the instance
+	pub fn connect(pipe: &File) -> io::Result<()> {
+		if unsafe { ConnectNamedPipe(pipe.as_raw_handle(), ptr::null_mut()) } == 0 {
+			let e = io::Error::last_os_error();
+			// connected between the instance's creation and now
+			if e.raw_os_error() != Some(ERROR_PIPE_CONNECTED) {
+				return Err(e);
+			}
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,56 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Correlation ids, short ids of jobs and solutions carried by the log
+//! lines, the stats and (if enabled) the submits about them. They're
+//! derived from what the pool sees of a share, its height, job id and
+//! nonce, so a share on the pool's dashboard can be traced back to the
+//! log lines of the job and device that found it.
+
+use sha2::{Digest, Sha256};
+
+use util;
+
+/// Hex digits of a correlation id
+const ID_LEN: usize = 8;
+
+fn short_hash(message: &str) -> String {
+	let mut hex = util::to_hex(Sha256::digest(message.as_bytes()).to_vec());
+	hex.truncate(ID_LEN);
+	hex
+}
+
+/// Correlation id of a job, e.g. `j3f9a01c2`
+pub fn job(height: u64, job_id: u64) -> String {
+	format!("j{}", short_hash(&format!("{}:{}", height, job_id)))
+}
+
+/// Correlation id of a solution of a job, e.g. `s7b20e4d9`
+pub fn solution(height: u64, job_id: u64, nonce: u64) -> String {
+	format!("s{}", short_hash(&format!("{}:{}:{}", height, job_id, nonce)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_ids_are_stable_and_distinct() {
+		assert_eq!(job(1000, 7), job(1000, 7));
+		assert_eq!(job(1000, 7).len(), ID_LEN + 1);
+		assert_ne!(job(1000, 7), job(1000, 8));
+		assert_ne!(solution(1000, 7, 1), solution(1000, 7, 2));
+		assert!(solution(1000, 7, 1).starts_with('s'));
+	}
+}
@@ -0,0 +1,345 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Crash reports: on a panic, a timestamped report with the backtrace,
+//! loaded plugins, last job and last log lines is written to the crash
+//! directory, and the last crash is reported when the miner next starts.
+//! A fatal signal (a plugin segfaulting) only leaves its name behind, the
+//! report for it is written at the next start.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use backtrace::Backtrace;
+use time;
+
+use config::MinerConfig;
+use plugin;
+use stats::Stats;
+use util::{self, LOGGER};
+
+/// Crash directory, unless configured with crash_dir
+pub const DEFAULT_CRASH_DIR: &str = "crashes";
+
+/// Name of the newest crash report already reported at startup
+const REPORTED_FILE: &str = "last-reported";
+
+/// File the signal handlers of the miner and its workers append the
+/// signal's name to
+const PENDING_FILE: &str = "pending-signals";
+
+lazy_static! {
+	/// Where reports go, and the stats to take the last job from
+	static ref CONTEXT: Mutex<Option<(PathBuf, Option<Arc<RwLock<Stats>>>)>> = Mutex::new(None);
+}
+
+/// Path of the crash directory
+pub fn crash_dir(config: &MinerConfig) -> PathBuf {
+	PathBuf::from(
+		config
+			.crash_dir
+			.clone()
+			.unwrap_or(DEFAULT_CRASH_DIR.to_string()),
+	)
+}
+
+/// Write crash reports to the configured directory from now on, on top
+/// of the logging panic hook. Called once the logger is set up, with the
+/// stats if there are any yet.
+pub fn install(config: &MinerConfig, stats: Option<Arc<RwLock<Stats>>>) {
+	let dir = crash_dir(config);
+	write_pending_report(&dir);
+	*CONTEXT.lock().unwrap() = Some((dir.clone(), stats));
+	let log_hook = panic::take_hook();
+	panic::set_hook(Box::new(move |info| {
+		log_hook(info);
+		let thread = thread::current();
+		let reason = format!(
+			"thread '{}' panicked: {}",
+			thread.name().unwrap_or("unnamed"),
+			info
+		);
+		write_report(&reason);
+	}));
+	native::install(&dir);
+}
+
+/// Stats to take the last job from, once they exist
+pub fn set_stats(stats: Arc<RwLock<Stats>>) {
+	if let Some(ref mut c) = *CONTEXT.lock().unwrap() {
+		c.1 = Some(stats);
+	}
+}
+
+/// Write a crash report, returning its path. Runs while crashing, so
+/// locks are only tried.
+fn write_report(reason: &str) -> Option<PathBuf> {
+	let (dir, stats) = match CONTEXT.try_lock() {
+		Ok(c) => c.clone()?,
+		Err(_) => return None,
+	};
+	let now = time::now_utc();
+	let mut report = vec![
+		format!("grin-miner {} crash report", env!("CARGO_PKG_VERSION")),
+		format!("Time: {}", now.rfc3339()),
+		format!("Reason: {}", reason),
+		String::new(),
+		"Loaded plugins:".to_string(),
+	];
+	let plugins = plugin::loaded_plugins();
+	if plugins.is_empty() {
+		report.push("  none".to_string());
+	}
+	for p in plugins {
+		report.push(format!("  {}", p));
+	}
+	report.push(String::new());
+	match stats.as_ref().and_then(|s| s.try_read().ok()) {
+		Some(s) => report.push(format!(
+			"Last job: height {}, target difficulty {}, {:.4} gps, {}",
+			s.mining_stats.block_height,
+			s.mining_stats.target_difficulty,
+			s.mining_stats.combined_gps,
+			s.client_stats.connection_status
+		)),
+		None => report.push("Last job: unknown".to_string()),
+	}
+	report.push(String::new());
+	report.push("Backtrace:".to_string());
+	report.push(format!("{:?}", Backtrace::new()));
+	report.push(format!("Last {} log lines:", util::logger::RECENT_LINES_LEN));
+	report.extend(util::recent_log_lines());
+	let path = save_report(&dir, &now, &report)?;
+	eprintln!("Crash report written to {}", path.display());
+	Some(path)
+}
+
+/// Write the report lines to the crash directory, named by the time
+fn save_report(dir: &Path, time: &time::Tm, report: &[String]) -> Option<PathBuf> {
+	if fs::create_dir_all(dir).is_err() {
+		return None;
+	}
+	let path = dir.join(format!("crash-{}.txt", time.strftime("%Y%m%d-%H%M%S").ok()?));
+	File::create(&path)
+		.and_then(|mut f| f.write_all(report.join("\n").as_bytes()))
+		.ok()?;
+	Some(path)
+}
+
+/// Write the report of a fatal signal received on the last run, which
+/// the handler could only leave the name of
+fn write_pending_report(dir: &Path) {
+	let pending = dir.join(PENDING_FILE);
+	let mut signals = String::new();
+	let _ = File::open(&pending).and_then(|mut f| f.read_to_string(&mut signals));
+	let signals: Vec<&str> = signals.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+	if signals.is_empty() {
+		return;
+	}
+	let secs = fs::metadata(&pending)
+		.and_then(|m| m.modified())
+		.ok()
+		.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+		.map_or(time::get_time().sec, |d| d.as_secs() as i64);
+	let crashed = time::at_utc(time::Timespec::new(secs, 0));
+	let report = vec![
+		format!("grin-miner {} crash report", env!("CARGO_PKG_VERSION")),
+		format!("Time: {}", crashed.rfc3339()),
+		format!("Reason: {} received", signals.join(", ")),
+		String::new(),
+		"Written at the next start: the loaded plugins, last job, backtrace and log \
+		 lines of the crashed run aren't known, see its log file"
+			.to_string(),
+	];
+	// emptied in place, other processes may have it open
+	if save_report(dir, &crashed, &report).is_some() {
+		let _ = OpenOptions::new().write(true).open(&pending).and_then(|f| f.set_len(0));
+	}
+}
+
+/// Crash reports in the directory, oldest first
+fn reports(dir: &Path) -> Vec<PathBuf> {
+	let mut reports: Vec<PathBuf> = match fs::read_dir(dir) {
+		Ok(entries) => entries
+			.filter_map(|e| e.ok())
+			.map(|e| e.path())
+			.filter(|p| {
+				p.file_name()
+					.map_or(false, |n| n.to_string_lossy().starts_with("crash-"))
+			})
+			.collect(),
+		Err(_) => vec![],
+	};
+	// the names sort by time
+	reports.sort();
+	reports
+}
+
+/// Warn about the newest crash report, if it's new since the last start
+pub fn report_last_crash(config: &MinerConfig) {
+	let dir = crash_dir(config);
+	let newest = match reports(&dir).pop() {
+		Some(r) => r,
+		None => return,
+	};
+	let name = newest
+		.file_name()
+		.map(|n| n.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	let mut reported = String::new();
+	let _ = File::open(dir.join(REPORTED_FILE)).and_then(|mut f| f.read_to_string(&mut reported));
+	if reported.trim() == name {
+		return;
+	}
+	let mut contents = String::new();
+	let _ = File::open(&newest).and_then(|mut f| f.read_to_string(&mut contents));
+	let reason = contents
+		.lines()
+		.find(|l| l.starts_with("Reason: "))
+		.map(|l| l["Reason: ".len()..].to_string())
+		.unwrap_or("unknown".to_string());
+	warn!(
+		LOGGER,
+		"grin-miner crashed on its last run ({}), see {}",
+		reason,
+		newest.display()
+	);
+	let _ = File::create(dir.join(REPORTED_FILE)).and_then(|mut f| writeln!(f, "{}", name));
+}
+
+/// Fatal signals (or unhandled exceptions on Windows), mostly from
+/// plugins, which don't go through the panic hook. On unix the handler
+/// may only make async-signal-safe calls: it writes the signal's name to
+/// the pending file opened beforehand, and the process dies as it would
+/// have. The report is written at the next start.
+#[cfg(unix)]
+mod native {
+	use libc;
+	use std::fs::{self, OpenOptions};
+	use std::mem;
+	use std::os::unix::io::IntoRawFd;
+	use std::path::Path;
+	use std::ptr;
+	use std::sync::atomic::{AtomicIsize, Ordering};
+
+	/// Size of the stack the handler runs on, for stack overflows
+	const ALT_STACK_SIZE: usize = 64 * 1024;
+
+	const SIGNALS: &[(libc::c_int, &[u8])] = &[
+		(libc::SIGSEGV, b"SIGSEGV\n"),
+		(libc::SIGBUS, b"SIGBUS\n"),
+		(libc::SIGILL, b"SIGILL\n"),
+		(libc::SIGFPE, b"SIGFPE\n"),
+		(libc::SIGABRT, b"SIGABRT\n"),
+	];
+
+	/// Descriptor of the pending file, -1 until opened
+	static PENDING_FD: AtomicIsize = AtomicIsize::new(-1);
+
+	extern "C" fn handler(signal: libc::c_int) {
+		let mut name: &[u8] = b"fatal signal\n";
+		for &(s, n) in SIGNALS {
+			if s == signal {
+				name = n;
+			}
+		}
+		let fd = PENDING_FD.load(Ordering::SeqCst) as libc::c_int;
+		unsafe {
+			if fd >= 0 {
+				libc::write(fd, name.as_ptr() as *const libc::c_void, name.len());
+			}
+			libc::signal(signal, libc::SIG_DFL);
+			libc::raise(signal);
+		}
+	}
+
+	pub fn install(dir: &Path) {
+		let file = fs::create_dir_all(dir).and_then(|_| {
+			OpenOptions::new()
+				.append(true)
+				.create(true)
+				.open(dir.join(super::PENDING_FILE))
+		});
+		if let Ok(f) = file {
+			let old = PENDING_FD.swap(f.into_raw_fd() as isize, Ordering::SeqCst);
+			if old >= 0 {
+				unsafe {
+					libc::close(old as libc::c_int);
+				}
+			}
+		}
+		unsafe {
+			// for the installing thread, the others overflow their guard
+			// page without a report
+			let size = ALT_STACK_SIZE.max(libc::SIGSTKSZ);
+			let stack = Box::into_raw(vec![0u8; size].into_boxed_slice());
+			let alt = libc::stack_t {
+				ss_sp: stack as *mut libc::c_void,
+				ss_flags: 0,
+				ss_size: size,
+			};
+			libc::sigaltstack(&alt, ptr::null_mut());
+			for &(signal, _) in SIGNALS {
+				let mut action: libc::sigaction = mem::zeroed();
+				action.sa_sigaction = handler as usize;
+				action.sa_flags = libc::SA_RESETHAND | libc::SA_ONSTACK;
+				libc::sigaction(signal, &action, ptr::null_mut());
+			}
+		}
+	}
+}
+
+#[cfg(windows)]
+mod native {
+	type Filter = unsafe extern "system" fn(*mut ExceptionPointers) -> i32;
+
+	#[repr(C)]
+	struct ExceptionPointers {
+		record: *const u32,
+		context: *const u8,
+	}
+
+	const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+
+	extern "system" {
+		fn SetUnhandledExceptionFilter(filter: Option<Filter>) -> Option<Filter>;
+	}
+
+	unsafe extern "system" fn filter(pointers: *mut ExceptionPointers) -> i32 {
+		// the exception code is the record's first field
+		let code = if pointers.is_null() || (*pointers).record.is_null() {
+			0
+		} else {
+			*(*pointers).record
+		};
+		super::write_report(&format!("unhandled exception 0x{:08x}", code));
+		EXCEPTION_CONTINUE_SEARCH
+	}
+
+	pub fn install(_dir: &::std::path::Path) {
+		unsafe {
+			SetUnhandledExceptionFilter(Some(filter));
+		}
+	}
+}
+
+#[cfg(not(any(unix, windows)))]
+mod native {
+	pub fn install(_dir: &::std::path::Path) {}
+}
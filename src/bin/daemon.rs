@@ -0,0 +1,157 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Daemon mode, for init scripts: detaching from the terminal, and the
+//! PID file the stop and status subcommands find the miner by
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+use config::MinerConfig;
+
+/// PID file, unless configured with pid_file or --pid-file
+pub const DEFAULT_PID_FILE: &str = "grin-miner.pid";
+
+/// Set in the environment of the detached miner on Windows, which is
+/// started again rather than forked
+#[cfg(windows)]
+const DETACHED_ENV: &str = "GRIN_MINER_DETACHED";
+
+/// Path of the PID file
+pub fn pid_file(config: &MinerConfig) -> String {
+	config
+		.pid_file
+		.clone()
+		.unwrap_or(DEFAULT_PID_FILE.to_string())
+}
+
+/// PID in the PID file, if there's one
+pub fn read_pid(path: &str) -> Option<u32> {
+	let mut contents = String::new();
+	File::open(path)
+		.and_then(|mut f| f.read_to_string(&mut contents))
+		.ok()?;
+	contents.trim().parse::<u32>().ok()
+}
+
+/// Whether a process with the given PID exists
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+	use libc;
+	use std::io;
+	unsafe {
+		libc::kill(pid as libc::pid_t, 0) == 0
+			|| io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+	}
+}
+
+#[cfg(windows)]
+pub fn is_running(pid: u32) -> bool {
+	use std::process::Command;
+	Command::new("tasklist")
+		.args(&["/FI", &format!("PID eq {}", pid), "/NH"])
+		.output()
+		.map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+		.unwrap_or(false)
+}
+
+/// PID of the miner running with the given PID file, if any
+pub fn running_pid(path: &str) -> Option<u32> {
+	read_pid(path).and_then(|pid| if is_running(pid) { Some(pid) } else { None })
+}
+
+/// Write the PID file, refusing if the miner it names is still running.
+/// A PID file left behind by a miner that's gone is replaced.
+pub fn write_pid_file(path: &str) -> Result<(), String> {
+	if let Some(pid) = running_pid(path) {
+		if pid != ::std::process::id() {
+			return Err(format!(
+				"grin-miner is already running with PID file {} (pid {})",
+				path, pid
+			));
+		}
+	}
+	File::create(path)
+		.and_then(|mut f| writeln!(f, "{}", ::std::process::id()))
+		.map_err(|e| format!("Unable to write PID file {}: {}", path, e))
+}
+
+/// Remove the PID file, if it's still this process's
+pub fn remove_pid_file(path: &str) {
+	if read_pid(path) == Some(::std::process::id()) {
+		let _ = fs::remove_file(path);
+	}
+}
+
+/// Detach from the terminal: fork twice, so the miner is neither a
+/// session leader nor the child of the shell, with stdin, stdout and
+/// stderr on /dev/null. Only the detached process returns. Must be called
+/// before any thread is started.
+#[cfg(unix)]
+pub fn detach() -> Result<(), String> {
+	use libc;
+	use std::io;
+	let error = |what: &str| format!("Unable to {}: {}", what, io::Error::last_os_error());
+	unsafe {
+		match libc::fork() {
+			-1 => return Err(error("fork")),
+			0 => {}
+			_ => libc::_exit(0),
+		}
+		if libc::setsid() == -1 {
+			return Err(error("start a new session"));
+		}
+		match libc::fork() {
+			-1 => return Err(error("fork")),
+			0 => {}
+			_ => libc::_exit(0),
+		}
+		let dev_null = libc::open(b"/dev/null\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+		if dev_null == -1 {
+			return Err(error("open /dev/null"));
+		}
+		for fd in 0..3 {
+			libc::dup2(dev_null, fd);
+		}
+		if dev_null > 2 {
+			libc::close(dev_null);
+		}
+	}
+	Ok(())
+}
+
+/// Detach from the console: start the miner again as a detached process
+/// without one, and exit. Only the detached process returns.
+#[cfg(windows)]
+pub fn detach() -> Result<(), String> {
+	use std::env;
+	use std::os::windows::process::CommandExt;
+	use std::process::{self, Command, Stdio};
+	const DETACHED_PROCESS: u32 = 0x0000_0008;
+	const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+	if env::var_os(DETACHED_ENV).is_some() {
+		return Ok(());
+	}
+	let exe = env::current_exe().map_err(|e| format!("{}", e))?;
+	Command::new(exe)
+		.args(env::args().skip(1))
+		.env(DETACHED_ENV, "1")
+		.stdin(Stdio::null())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+		.spawn()
+		.map_err(|e| format!("Unable to start the detached miner: {}", e))?;
+	process::exit(0);
+}
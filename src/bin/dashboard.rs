@@ -0,0 +1,85 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Console dashboard, periodically printing a compact status block to
+//! stdout for when the TUI can't run (i.e. stdout is not a terminal)
+
+use std::sync::{Arc, RwLock};
+use std::{self, thread};
+
+use time;
+
+use i18n;
+use stats::{self, Stats};
+use units;
+
+/// Default number of seconds between status blocks
+pub const DEFAULT_INTERVAL: u64 = 10;
+
+/// Format the status block for the given stats
+pub fn status_block(stats: &Stats) -> String {
+	let (mining_status, network_info) = stats.mining_status();
+	let timestamp = units::date_time(time::get_time().sec);
+	let mut lines = vec![
+		i18n::tf("dashboard.title", &[&timestamp]),
+		stats.client_stats.connection_status.clone(),
+		mining_status,
+	];
+	if network_info.trim() != "" {
+		lines.push(network_info);
+	}
+	lines.push(stats.shares_status());
+	for a in &stats.alerts {
+		lines.push(i18n::tf("dashboard.alert", &[&a.message, &a.duration()]));
+	}
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		for (i, devices) in plugins.iter().enumerate() {
+			for d in devices {
+				if d.in_use == 0 {
+					continue;
+				}
+				let graph_time = d.last_solution_time as f64 / 1000000000.0;
+				let status = match d.has_errored {
+					0 => i18n::t("dashboard.ok"),
+					_ => i18n::t("dashboard.errored"),
+				};
+				lines.push(i18n::tf(
+					"dashboard.device",
+					&[
+						&i,
+						&d.device_id,
+						&d.device_name,
+						&d.cuckoo_size,
+						&status,
+						&format!("{:.3}", graph_time),
+						&units::gps_value(stats::device_gps(d)),
+					],
+				));
+			}
+		}
+	}
+	lines.join("\n")
+}
+
+/// Print the status block every `interval` seconds, forever
+pub fn run(stats: Arc<RwLock<Stats>>, interval: u64) {
+	loop {
+		thread::sleep(std::time::Duration::from_secs(interval.max(1)));
+		let block = {
+			let stats = stats.read().unwrap();
+			status_block(&stats)
+		};
+		println!("{}", block);
+	}
+}
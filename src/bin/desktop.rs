@@ -0,0 +1,310 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Desktop mode, for mining on the computer in use: mining pauses (or
+//! switches to a throttle profile) while there's keyboard or mouse input
+//! or a fullscreen application in front, and resumes once the user has
+//! been idle for the timeout.
+
+use std::process::Command;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use config::{DesktopConfig, MinerConfig};
+use stats::{DesktopStatus, Stats};
+use types::MinerMessage;
+use util::LOGGER;
+
+/// Why the user counts as active
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Activity {
+	Input,
+	Fullscreen,
+}
+
+impl Activity {
+	fn reason(&self) -> &'static str {
+		match *self {
+			Activity::Input => "user active",
+			Activity::Fullscreen => "fullscreen application",
+		}
+	}
+}
+
+/// Milliseconds since the last input, from the configured command
+fn command_idle_ms(command: &str) -> Option<u64> {
+	let output = if cfg!(windows) {
+		Command::new("cmd").arg("/C").arg(command).output()
+	} else {
+		Command::new("sh").arg("-c").arg(command).output()
+	}
+	.ok()?;
+	if !output.status.success() {
+		return None;
+	}
+	String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+}
+
+#[cfg(windows)]
+mod native {
+	#[repr(C)]
+	struct LastInputInfo {
+		size: u32,
+		time: u32,
+	}
+
+	const QUNS_BUSY: i32 = 2;
+	const QUNS_RUNNING_D3D_FULL_SCREEN: i32 = 3;
+	const QUNS_PRESENTATION_MODE: i32 = 4;
+
+	#[link(name = "user32")]
+	extern "system" {
+		fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+	}
+
+	extern "system" {
+		fn GetTickCount() -> u32;
+	}
+
+	#[link(name = "shell32")]
+	extern "system" {
+		fn SHQueryUserNotificationState(state: *mut i32) -> i32;
+	}
+
+	pub fn idle_ms() -> Option<u64> {
+		let mut info = LastInputInfo {
+			size: ::std::mem::size_of::<LastInputInfo>() as u32,
+			time: 0,
+		};
+		if unsafe { GetLastInputInfo(&mut info) } == 0 {
+			return None;
+		}
+		// both wrap after 49 days
+		Some(unsafe { GetTickCount() }.wrapping_sub(info.time) as u64)
+	}
+
+	pub fn fullscreen() -> Option<bool> {
+		let mut state = 0;
+		if unsafe { SHQueryUserNotificationState(&mut state) } != 0 {
+			return None;
+		}
+		Some(
+			state == QUNS_BUSY
+				|| state == QUNS_RUNNING_D3D_FULL_SCREEN
+				|| state == QUNS_PRESENTATION_MODE,
+		)
+	}
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+	use auto::command_output;
+
+	/// HIDIdleTime of the HID system, in nanoseconds
+	pub fn idle_ms() -> Option<u64> {
+		let output = command_output("ioreg", &["-c", "IOHIDSystem", "-d", "4"])?;
+		let line = output.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+		let ns = line.rsplit('=').next()?.trim().parse::<u64>().ok()?;
+		Some(ns / 1_000_000)
+	}
+
+	pub fn fullscreen() -> Option<bool> {
+		None
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod native {
+	use auto::command_output;
+
+	/// From xprintidle, under X11
+	pub fn idle_ms() -> Option<u64> {
+		command_output("xprintidle", &[])?.trim().parse().ok()
+	}
+
+	/// Whether the active window's state is fullscreen, asking the window
+	/// manager with xprop
+	pub fn fullscreen() -> Option<bool> {
+		let active = command_output("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+		// _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+		let id = active.split_whitespace().last()?;
+		if !id.starts_with("0x") || id == "0x0" {
+			return Some(false);
+		}
+		let state = command_output("xprop", &["-id", id, "_NET_WM_STATE"])?;
+		Some(state.contains("_NET_WM_STATE_FULLSCREEN"))
+	}
+}
+
+/// Pauses or throttles mining while the user is active
+pub struct DesktopWatcher {
+	config: DesktopConfig,
+	/// The mining configuration to go back to after throttling, when no
+	/// profile was active
+	mining_config: MinerConfig,
+	stats: Arc<RwLock<Stats>>,
+	miner_tx: mpsc::Sender<MinerMessage>,
+	/// Whether mining was paused by desktop mode, and not before
+	paused: bool,
+	/// Profile active when throttling started, to switch back to
+	throttled_from: Option<Option<String>>,
+}
+
+impl DesktopWatcher {
+	/// Create a new watcher
+	pub fn new(
+		config: DesktopConfig,
+		mining_config: MinerConfig,
+		stats: Arc<RwLock<Stats>>,
+		miner_tx: mpsc::Sender<MinerMessage>,
+	) -> DesktopWatcher {
+		DesktopWatcher {
+			config: config,
+			mining_config: mining_config,
+			stats: stats,
+			miner_tx: miner_tx,
+			paused: false,
+			throttled_from: None,
+		}
+	}
+
+	/// Milliseconds since the last input, if it can be told
+	fn idle_ms(&self) -> Option<u64> {
+		match self.config.idle_command {
+			Some(ref c) => command_idle_ms(c),
+			None => native::idle_ms(),
+		}
+	}
+
+	/// Whether the user is active now, and why
+	fn activity(&self, idle_ms: Option<u64>) -> Option<Activity> {
+		if idle_ms.map_or(false, |ms| ms < self.config.idle_timeout_secs * 1000) {
+			return Some(Activity::Input);
+		}
+		if self.config.pause_on_fullscreen && native::fullscreen().unwrap_or(false) {
+			return Some(Activity::Fullscreen);
+		}
+		None
+	}
+
+	/// Start watching in a new thread
+	pub fn start(mut self) {
+		if let Some(ref p) = self.config.throttle_profile {
+			if let Err(e) = self.mining_config.clone().apply_profile(p) {
+				error!(LOGGER, "Invalid desktop throttle_profile, not watching: {}", e);
+				return;
+			}
+		}
+		if self.idle_ms().is_none() {
+			warn!(
+				LOGGER,
+				"Unable to tell how long the user has been idle, desktop mode only \
+				 sees fullscreen applications (see idle_command)"
+			);
+		}
+		let _ = thread::Builder::new()
+			.name("desktop".to_string())
+			.spawn(move || {
+				let mut active: Option<Activity> = None;
+				loop {
+					let idle_ms = self.idle_ms();
+					let now = self.activity(idle_ms);
+					if now != active {
+						self.transition(now, active.is_some());
+						active = now;
+					}
+					self.update_stats(active, idle_ms);
+					thread::sleep(Duration::from_secs(self.config.check_interval_secs.max(1)));
+				}
+			});
+	}
+
+	/// Pause or throttle as the user becomes active, and resume once idle
+	fn transition(&mut self, activity: Option<Activity>, was_active: bool) {
+		match activity {
+			Some(a) => {
+				if was_active {
+					return;
+				}
+				let (paused, profile) = {
+					let stats = self.stats.read().unwrap();
+					(stats.mining_stats.paused, stats.mining_stats.profile.clone())
+				};
+				match self.config.throttle_profile.clone() {
+					Some(p) => {
+						warn!(LOGGER, "Desktop mode: {}, throttling to profile {}", a.reason(), p);
+						self.throttled_from = Some(profile);
+						let _ = self.miner_tx.send(MinerMessage::SwitchProfile(p));
+					}
+					None => {
+						// paused by hand or by the schedule, left to them
+						if paused {
+							return;
+						}
+						warn!(LOGGER, "Desktop mode: {}, pausing", a.reason());
+						self.paused = true;
+						let _ = self.miner_tx.send(MinerMessage::Pause);
+					}
+				}
+			}
+			None => {
+				if let Some(profile) = self.throttled_from.take() {
+					warn!(LOGGER, "Desktop mode: user idle, ending throttling");
+					let message = match profile {
+						Some(p) => MinerMessage::SwitchProfile(p),
+						None => MinerMessage::UpdateConfig(self.mining_config.clone()),
+					};
+					let _ = self.miner_tx.send(message);
+					return;
+				}
+				if !self.paused {
+					return;
+				}
+				self.paused = false;
+				warn!(LOGGER, "Desktop mode: user idle, resuming");
+				// outside the schedule's windows it stays paused
+				let scheduled_off = self
+					.stats
+					.read()
+					.unwrap()
+					.mining_stats
+					.schedule
+					.as_ref()
+					.map_or(false, |s| !s.active);
+				if !scheduled_off {
+					let _ = self.miner_tx.send(MinerMessage::Resume);
+				}
+			}
+		}
+	}
+
+	fn update_stats(&self, activity: Option<Activity>, idle_ms: Option<u64>) {
+		let idle_secs = idle_ms.map(|ms| ms / 1000);
+		let resumes_at = match (activity, idle_secs) {
+			(Some(Activity::Input), Some(idle)) => Some(
+				time::get_time().sec + self.config.idle_timeout_secs.saturating_sub(idle) as i64,
+			),
+			_ => None,
+		};
+		self.stats.write().unwrap().mining_stats.desktop = Some(DesktopStatus {
+			reason: activity.map(|a| a.reason().to_string()),
+			throttled: self.config.throttle_profile.is_some(),
+			idle_secs: idle_secs,
+			resumes_at: resumes_at,
+		});
+	}
+}
@@ -0,0 +1,106 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A pool of threads working out the difficulty of the shares found, with
+//! the rules of the chain mined, so the mining loop handing solvers their
+//! work never waits on the hashing
+
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+
+use chain::Chain;
+use stats::Stats;
+use util::LOGGER;
+
+/// Default number of difficulty threads
+pub const DEFAULT_THREADS: usize = 1;
+
+/// A solution to work out the difficulty of
+struct Work {
+	edge_bits: u32,
+	nonces: Vec<u32>,
+	target_difficulty: u64,
+}
+
+fn evaluate(chain: &dyn Chain, stats: &RwLock<Stats>, work: Work) {
+	let diff = chain.share_difficulty(work.edge_bits, &work.nonces);
+	stats
+		.write()
+		.unwrap()
+		.session_stats
+		.add_solution(work.edge_bits, diff, work.target_difficulty);
+}
+
+/// Threads working out the difficulty of the solutions found, adding
+/// them to the session stats. With no threads, it's done by the caller.
+pub struct DifficultyPool {
+	tx: Option<mpsc::Sender<Work>>,
+	chain: Arc<dyn Chain>,
+	stats: Arc<RwLock<Stats>>,
+}
+
+impl DifficultyPool {
+	/// Start a pool of the given number of threads
+	pub fn new(threads: usize, chain: Arc<dyn Chain>, stats: Arc<RwLock<Stats>>) -> DifficultyPool {
+		if threads == 0 {
+			return DifficultyPool {
+				tx: None,
+				chain: chain,
+				stats: stats,
+			};
+		}
+		let (tx, rx) = mpsc::channel::<Work>();
+		let rx = Arc::new(Mutex::new(rx));
+		for i in 0..threads {
+			let rx = rx.clone();
+			let chain = chain.clone();
+			let stats = stats.clone();
+			let spawned = thread::Builder::new()
+				.name(format!("difficulty_{}", i))
+				.spawn(move || loop {
+					let work = match rx.lock().unwrap().recv() {
+						Ok(w) => w,
+						Err(_) => return,
+					};
+					evaluate(&*chain, &stats, work);
+				});
+			if let Err(e) = spawned {
+				error!(LOGGER, "Unable to start difficulty thread: {}", e);
+			}
+		}
+		DifficultyPool {
+			tx: Some(tx),
+			chain: chain,
+			stats: stats,
+		}
+	}
+
+	/// Work out the difficulty of a solution found for a job with the
+	/// given target difficulty
+	pub fn evaluate(&self, edge_bits: u32, nonces: &[u32], target_difficulty: u64) {
+		let work = Work {
+			edge_bits: edge_bits,
+			nonces: nonces.to_vec(),
+			target_difficulty: target_difficulty,
+		};
+		let work = match self.tx {
+			Some(ref tx) => match tx.send(work) {
+				Ok(()) => return,
+				Err(mpsc::SendError(w)) => w,
+			},
+			None => work,
+		};
+		evaluate(&*self.chain, &self.stats, work);
+	}
+}
@@ -0,0 +1,82 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dry run report, what a run that submitted nothing would have done
+
+use plugin;
+use stats::Stats;
+
+/// Default number of seconds a dry run lasts
+pub const DEFAULT_DRY_RUN_SECS: u64 = 60;
+
+/// Report of a dry run that lasted the given seconds
+pub fn report(stats: &Stats, login: Option<&str>, secs: u64) -> String {
+	let client = &stats.client_stats;
+	let session = &stats.session_stats;
+	let mut lines = vec![
+		format!(
+			"Dry run finished after {} seconds, no shares were submitted",
+			secs
+		),
+		String::new(),
+		"Plugins:".to_string(),
+	];
+	let plugins = plugin::loaded_plugins();
+	if plugins.is_empty() {
+		lines.push("  none loaded".to_string());
+	}
+	for (i, p) in plugins.iter().enumerate() {
+		lines.push(format!("  {}: {}", i, p));
+	}
+	lines.push("Devices:".to_string());
+	if session.devices.is_empty() {
+		lines.push("  none reported stats".to_string());
+	}
+	for d in &session.devices {
+		lines.push(format!(
+			"  plugin {} device {} ({}): {} graphs at {:.4} GPS, {} errors",
+			d.plugin,
+			d.device_id,
+			d.device_name,
+			d.graphs,
+			d.average_gps(),
+			d.errors
+		));
+	}
+	lines.push(String::new());
+	let connection = if !client.connected {
+		format!("not connected to {} ({})", client.server_url, client.connection_status)
+	} else {
+		match login {
+			None => format!("connected to {}, no login configured", client.server_url),
+			Some(l) if client.logged_in => {
+				format!("connected to {}, logged in as {}", client.server_url, l)
+			}
+			Some(l) => format!(
+				"connected to {}, login as {} not confirmed",
+				client.server_url, l
+			),
+		}
+	};
+	lines.push(format!("Pool: {}", connection));
+	lines.push(format!(
+		"Jobs received: {} (last height {}, target difficulty {})",
+		client.jobs_received, stats.mining_stats.block_height, stats.mining_stats.target_difficulty
+	));
+	lines.push(format!(
+		"Solutions found: {}, shares that would have been submitted: {}",
+		session.solutions_found, client.withheld_shares
+	));
+	lines.join("\n")
+}
@@ -0,0 +1,219 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Environment report, the hardware and software the miner runs on as found
+//! at startup: what every support request asks for. Logged at startup and
+//! served at `/v1/environment`.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use auto;
+use built_info;
+use compat;
+use config::MinerConfig;
+use plugin;
+use stats::{Environment, GpuEnvironment, PluginEnvironment};
+use util::LOGGER;
+
+/// Value of a key of /etc/os-release
+fn os_release(key: &str) -> Option<String> {
+	let contents = fs::read_to_string("/etc/os-release").ok()?;
+	contents
+		.lines()
+		.find(|l| l.starts_with(&format!("{}=", key)))
+		.map(|l| l[key.len() + 1..].trim_matches('"').to_string())
+}
+
+/// Operating system name and version
+fn os() -> String {
+	let name = match env::consts::OS {
+		"linux" => os_release("PRETTY_NAME"),
+		"macos" => auto::command_output("sw_vers", &["-productVersion"])
+			.map(|v| format!("macOS {}", v.trim())),
+		"windows" => auto::command_output("cmd", &["/c", "ver"]).map(|v| v.trim().to_string()),
+		_ => None,
+	};
+	name.unwrap_or_else(|| env::consts::OS.to_string())
+}
+
+/// CPU model
+fn cpu() -> Option<String> {
+	if let Ok(cpuinfo) = fs::read_to_string("/proc/cpuinfo") {
+		return cpuinfo
+			.lines()
+			.find(|l| l.starts_with("model name"))
+			.and_then(|l| l.splitn(2, ':').nth(1))
+			.map(|m| m.trim().to_string());
+	}
+	auto::command_output("sysctl", &["-n", "machdep.cpu.brand_string"])
+		.map(|m| m.trim().to_string())
+		.filter(|m| !m.is_empty())
+}
+
+/// Highest CUDA version the NVIDIA driver supports, from the nvidia-smi
+/// header
+fn cuda_version() -> Option<String> {
+	let output = auto::command_output("nvidia-smi", &[])?;
+	let start = output.find("CUDA Version:")? + "CUDA Version:".len();
+	output[start..]
+		.split_whitespace()
+		.next()
+		.map(|v| v.to_string())
+}
+
+/// Names and versions of the OpenCL platforms, from clinfo
+fn opencl_platforms() -> Vec<String> {
+	let output = match auto::command_output("clinfo", &["--raw"]) {
+		Some(o) => o,
+		None => return vec![],
+	};
+	// lines look like "[NV/*]  CL_PLATFORM_VERSION  OpenCL 3.0 CUDA 12.2.148"
+	let value = |line: &str, key: &str| {
+		line.find(key)
+			.map(|i| line[i + key.len()..].trim().to_string())
+	};
+	let names: Vec<String> = output
+		.lines()
+		.filter_map(|l| value(l, "CL_PLATFORM_NAME"))
+		.collect();
+	output
+		.lines()
+		.filter_map(|l| value(l, "CL_PLATFORM_VERSION"))
+		.enumerate()
+		.map(|(i, v)| match names.get(i) {
+			Some(n) => format!("{} ({})", n, v),
+			None => v,
+		})
+		.collect()
+}
+
+/// The files of the configured plugins, with their checksums
+fn plugins(config: &MinerConfig) -> Vec<PluginEnvironment> {
+	let manifest = compat::load_manifests(config);
+	let (files, _) = plugin::scan_plugin_dirs(config);
+	let mut plugins = vec![];
+	for p in &config.miner_plugin_config {
+		let name = format!("{}_{}", p.type_filter, p.edge_bits);
+		for f in &files {
+			let file_name = Path::new(&f.path)
+				.file_name()
+				.map(|n| n.to_string_lossy().into_owned())
+				.unwrap_or_default();
+			if !file_name.starts_with(&format!("{}.", name)) {
+				continue;
+			}
+			plugins.push(PluginEnvironment {
+				path: f.path.clone(),
+				version: manifest
+					.iter()
+					.find(|m| m.name == name)
+					.and_then(|m| m.version.clone()),
+				sha256: plugin::file_sha256(Path::new(&f.path)).ok(),
+				error: f.loadable.clone().err(),
+			});
+		}
+	}
+	plugins
+}
+
+/// Find out the environment
+pub fn collect(config: &MinerConfig) -> Environment {
+	let hardware = compat::detect_hardware();
+	let cuda = auto::cuda_gpus();
+	// NVIDIA GPUs are listed by both nvidia-smi and clinfo
+	let opencl = auto::opencl_gpus()
+		.into_iter()
+		.filter(|g| cuda.is_empty() || g.platform.as_ref().map_or(true, |p| p != "nvidia"));
+	let gpus = cuda
+		.iter()
+		.cloned()
+		.chain(opencl)
+		.map(|g| GpuEnvironment {
+			kind: g.kind.to_string(),
+			name: g.name,
+			memory_mb: g.memory_mb,
+		})
+		.collect();
+	Environment {
+		version: format!(
+			"{}{}",
+			built_info::PKG_VERSION,
+			built_info::GIT_VERSION.map_or_else(|| "".to_owned(), |v| format!(" (git {})", v))
+		),
+		target: built_info::TARGET.to_string(),
+		os: os(),
+		kernel: auto::command_output("uname", &["-r"]).map(|k| k.trim().to_string()),
+		cpu: cpu(),
+		cpu_threads: thread::available_parallelism()
+			.map(|n| n.get())
+			.unwrap_or(1),
+		memory_mb: auto::system_memory_mb(),
+		gpus: gpus,
+		nvidia_driver: hardware.nvidia.driver,
+		amd_driver: hardware.amd.driver,
+		cuda_version: cuda_version(),
+		opencl_platforms: opencl_platforms(),
+		plugins: plugins(config),
+	}
+}
+
+/// Log the environment, one line per item
+pub fn log(environment: &Environment) {
+	let unknown = |v: &Option<String>| v.clone().unwrap_or("unknown".to_string());
+	info!(
+		LOGGER,
+		"Environment: grin-miner {} ({}) on {}, kernel {}",
+		environment.version,
+		environment.target,
+		environment.os,
+		unknown(&environment.kernel)
+	);
+	info!(
+		LOGGER,
+		"Environment: CPU {} ({} threads), {} MB RAM",
+		unknown(&environment.cpu),
+		environment.cpu_threads,
+		environment
+			.memory_mb
+			.map_or("unknown".to_string(), |m| m.to_string())
+	);
+	for g in &environment.gpus {
+		info!(LOGGER, "Environment: GPU {} ({}, {} MB)", g.name, g.kind, g.memory_mb);
+	}
+	info!(
+		LOGGER,
+		"Environment: NVIDIA driver {}, CUDA {}, AMD driver {}",
+		unknown(&environment.nvidia_driver),
+		unknown(&environment.cuda_version),
+		unknown(&environment.amd_driver)
+	);
+	for p in &environment.opencl_platforms {
+		info!(LOGGER, "Environment: OpenCL platform {}", p);
+	}
+	for p in &environment.plugins {
+		info!(
+			LOGGER,
+			"Environment: plugin {} version {}, SHA-256 {}{}",
+			p.path,
+			unknown(&p.version),
+			unknown(&p.sha256),
+			p.error
+				.as_ref()
+				.map_or("".to_string(), |e| format!(", doesn't load: {}", e))
+		);
+	}
+}
@@ -0,0 +1,406 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Errors of the miner, by category, with the plugin and device they're
+//! about. cuckoo-miner reports every failure as a `CuckooMinerError`, so
+//! the miner sorts them by what it was doing when they happened, keeping
+//! the plugin's error as the source. A fatal error exits with the code of
+//! its category, for scripts and service managers to act on.
+//!
+//! The terminal conditions a rig can't mine through (every device failed,
+//! login rejected, invalid config, plugin missing) follow the failure
+//! policy of the config instead: each exits with its configured code, or
+//! retries forever.
+
+use std::error::Error;
+use std::fmt;
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use config::FailurePolicyConfig;
+use cuckoo::CuckooMinerError;
+use update;
+use util::LOGGER;
+
+/// Kind of failure
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+	/// A plugin couldn't be found or loaded
+	PluginLoad,
+	/// A solver couldn't set up on a device
+	ContextCreation,
+	/// A solver failed while mining
+	SolverRuntime,
+	/// The configuration is invalid
+	Config,
+	/// A control request (API, control socket, signal) couldn't be
+	/// carried out
+	Control,
+}
+
+impl Category {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Category::PluginLoad => "plugin_load",
+			Category::ContextCreation => "context_creation",
+			Category::SolverRuntime => "solver_runtime",
+			Category::Config => "config",
+			Category::Control => "control",
+		}
+	}
+
+	/// Exit code of the process on a fatal error of the category
+	pub fn exit_code(&self) -> i32 {
+		match *self {
+			Category::Config => 2,
+			Category::PluginLoad => 3,
+			Category::ContextCreation => 4,
+			Category::SolverRuntime => 5,
+			Category::Control => 6,
+		}
+	}
+}
+
+/// An error reported by cuckoo-miner, kept as the source of a
+/// `MinerError`
+#[derive(Debug)]
+pub struct PluginFailure(String);
+
+impl fmt::Display for PluginFailure {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+impl Error for PluginFailure {}
+
+impl From<CuckooMinerError> for PluginFailure {
+	fn from(e: CuckooMinerError) -> PluginFailure {
+		PluginFailure(format!("{:?}", e))
+	}
+}
+
+/// An error of the miner
+#[derive(Debug)]
+pub enum MinerError {
+	PluginLoad {
+		/// Index of the plugin in the config, if it's about one
+		plugin: Option<usize>,
+		message: String,
+		source: Option<PluginFailure>,
+	},
+	ContextCreation {
+		plugin: Option<usize>,
+		device: Option<u32>,
+		source: PluginFailure,
+	},
+	SolverRuntime {
+		plugin: Option<usize>,
+		device: Option<u32>,
+		source: PluginFailure,
+	},
+	Config {
+		message: String,
+	},
+	Control {
+		message: String,
+	},
+}
+
+impl MinerError {
+	pub fn category(&self) -> Category {
+		match *self {
+			MinerError::PluginLoad { .. } => Category::PluginLoad,
+			MinerError::ContextCreation { .. } => Category::ContextCreation,
+			MinerError::SolverRuntime { .. } => Category::SolverRuntime,
+			MinerError::Config { .. } => Category::Config,
+			MinerError::Control { .. } => Category::Control,
+		}
+	}
+
+	/// Plugin and device the error is about, as far as known
+	pub fn context(&self) -> (Option<usize>, Option<u32>) {
+		match *self {
+			MinerError::PluginLoad { plugin, .. } => (plugin, None),
+			MinerError::ContextCreation { plugin, device, .. }
+			| MinerError::SolverRuntime { plugin, device, .. } => (plugin, device),
+			MinerError::Config { .. } | MinerError::Control { .. } => (None, None),
+		}
+	}
+
+	pub fn plugin_load(plugin: Option<usize>, message: &str) -> MinerError {
+		MinerError::PluginLoad {
+			plugin: plugin,
+			message: message.to_string(),
+			source: None,
+		}
+	}
+
+	pub fn config(message: &str) -> MinerError {
+		MinerError::Config {
+			message: message.to_string(),
+		}
+	}
+
+	pub fn control(message: &str) -> MinerError {
+		MinerError::Control {
+			message: message.to_string(),
+		}
+	}
+
+	/// Solvers failing to start on the job
+	pub fn context_creation(
+		plugin: Option<usize>,
+		device: Option<u32>,
+		e: CuckooMinerError,
+	) -> MinerError {
+		MinerError::ContextCreation {
+			plugin: plugin,
+			device: device,
+			source: e.into(),
+		}
+	}
+
+	/// A device's solver failing while mining
+	pub fn solver_runtime(plugin: Option<usize>, device: Option<u32>, message: &str) -> MinerError {
+		MinerError::SolverRuntime {
+			plugin: plugin,
+			device: device,
+			source: PluginFailure(message.to_string()),
+		}
+	}
+}
+
+/// "plugin 0 device 1: ", or as much of it as is known
+fn context_prefix(plugin: Option<usize>, device: Option<u32>) -> String {
+	match (plugin, device) {
+		(Some(p), Some(d)) => format!("plugin {} device {}: ", p, d),
+		(Some(p), None) => format!("plugin {}: ", p),
+		(None, Some(d)) => format!("device {}: ", d),
+		(None, None) => String::new(),
+	}
+}
+
+impl fmt::Display for MinerError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let (plugin, device) = self.context();
+		let prefix = context_prefix(plugin, device);
+		match *self {
+			MinerError::PluginLoad {
+				ref message,
+				ref source,
+				..
+			} => match *source {
+				Some(ref s) => write!(f, "{}{}: {}", prefix, message, s),
+				None => write!(f, "{}{}", prefix, message),
+			},
+			MinerError::ContextCreation { ref source, .. } => {
+				write!(f, "Unable to start the solvers, {}{}", prefix, source)
+			}
+			MinerError::SolverRuntime { ref source, .. } => {
+				write!(f, "Solver failed, {}{}", prefix, source)
+			}
+			MinerError::Config { ref message } => write!(f, "Invalid configuration: {}", message),
+			MinerError::Control { ref message } => write!(f, "Control request failed: {}", message),
+		}
+	}
+}
+
+impl Error for MinerError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match *self {
+			MinerError::PluginLoad { ref source, .. } => source.as_ref().map(|s| s as &dyn Error),
+			MinerError::ContextCreation { ref source, .. }
+			| MinerError::SolverRuntime { ref source, .. } => Some(source),
+			MinerError::Config { .. } | MinerError::Control { .. } => None,
+		}
+	}
+}
+
+/// A solver error not otherwise placed
+impl From<CuckooMinerError> for MinerError {
+	fn from(e: CuckooMinerError) -> MinerError {
+		MinerError::SolverRuntime {
+			plugin: None,
+			device: None,
+			source: e.into(),
+		}
+	}
+}
+
+/// A condition the rig can't mine through, handled by the failure policy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+	AllDevicesFailed,
+	PoolAuthRejected,
+	ConfigInvalid,
+	PluginMissing,
+}
+
+impl Condition {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Condition::AllDevicesFailed => "all_devices_failed",
+			Condition::PoolAuthRejected => "pool_auth_rejected",
+			Condition::ConfigInvalid => "config_invalid",
+			Condition::PluginMissing => "plugin_missing",
+		}
+	}
+
+	/// Whether the condition is retried, and its exit code, if not
+	/// configured
+	fn default_rule(&self) -> (bool, i32) {
+		match *self {
+			Condition::AllDevicesFailed => (true, 7),
+			Condition::PoolAuthRejected => (false, 8),
+			Condition::ConfigInvalid => (false, Category::Config.exit_code()),
+			Condition::PluginMissing => (false, Category::PluginLoad.exit_code()),
+		}
+	}
+}
+
+/// What to do on a condition
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+	/// Exit with the code
+	Exit(i32),
+	/// Retry after the seconds
+	Retry(u64),
+}
+
+lazy_static! {
+	static ref POLICY: Mutex<FailurePolicyConfig> = Mutex::new(FailurePolicyConfig::default());
+}
+
+/// Check a failure policy, for the config validation
+pub fn check_failure_policy(policy: &FailurePolicyConfig) -> Result<(), String> {
+	let rules = [
+		(&policy.all_devices_failed, Condition::AllDevicesFailed),
+		(&policy.pool_auth_rejected, Condition::PoolAuthRejected),
+		(&policy.config_invalid, Condition::ConfigInvalid),
+		(&policy.plugin_missing, Condition::PluginMissing),
+	];
+	for &(rule, condition) in rules.iter() {
+		match rule.action.as_ref().map(|a| a.as_str()) {
+			None | Some("exit") | Some("retry") => {}
+			Some(a) => {
+				return Err(format!(
+					"Unknown failure_policy.{}.action {}, expected exit or retry",
+					condition.name(),
+					a
+				))
+			}
+		}
+		match rule.exit_code {
+			Some(c) if c < 1 || c > 255 => {
+				return Err(format!(
+					"failure_policy.{}.exit_code {} is not within 1-255",
+					condition.name(),
+					c
+				))
+			}
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+/// Use the failure policy of the config from now on
+pub fn set_failure_policy(policy: FailurePolicyConfig) {
+	*POLICY.lock().unwrap() = policy;
+}
+
+/// What the failure policy says to do on a condition
+pub fn action(condition: Condition) -> Action {
+	let policy = POLICY.lock().unwrap();
+	let rule = match condition {
+		Condition::AllDevicesFailed => &policy.all_devices_failed,
+		Condition::PoolAuthRejected => &policy.pool_auth_rejected,
+		Condition::ConfigInvalid => &policy.config_invalid,
+		Condition::PluginMissing => &policy.plugin_missing,
+	};
+	let (retry, code) = condition.default_rule();
+	let retry = match rule.action.as_ref().map(|a| a.as_str()) {
+		Some("retry") => true,
+		Some("exit") => false,
+		_ => retry,
+	};
+	if retry {
+		Action::Retry(policy.retry_secs.max(1))
+	} else {
+		Action::Exit(rule.exit_code.unwrap_or(code))
+	}
+}
+
+/// Exit on a condition the failure policy says to exit on
+pub fn exit_on(condition: Condition, code: i32) -> ! {
+	error!(LOGGER, "Exiting"; "condition" => condition.name(), "exit_code" => code);
+	process::exit(code);
+}
+
+/// Log a fatal error and exit with the code of its category, or, for an
+/// invalid config or missing plugin, do what the failure policy says:
+/// exit with its code, or wait and run the miner again
+pub fn fatal(e: MinerError) -> ! {
+	let category = e.category();
+	error!(LOGGER, "{}", e; "category" => category.name());
+	eprintln!("{}", e);
+	let condition = match category {
+		Category::Config => Condition::ConfigInvalid,
+		Category::PluginLoad => Condition::PluginMissing,
+		_ => process::exit(category.exit_code()),
+	};
+	match action(condition) {
+		Action::Exit(code) => exit_on(condition, code),
+		Action::Retry(secs) => {
+			warn!(LOGGER, "Running the miner again in {}s", secs; "condition" => condition.name());
+			thread::sleep(Duration::from_secs(secs));
+			if let Err(e) = update::restart() {
+				error!(LOGGER, "{}", e);
+			}
+			process::exit(category.exit_code());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_device_context() {
+		let e = MinerError::solver_runtime(Some(0), Some(1), "keeps failing");
+		assert_eq!(e.context(), (Some(0), Some(1)));
+		assert_eq!(e.category(), Category::SolverRuntime);
+		assert_eq!(e.to_string(), "Solver failed, plugin 0 device 1: keeps failing");
+	}
+
+	#[test]
+	fn test_failure_policy() {
+		let mut policy = FailurePolicyConfig::default();
+		policy.pool_auth_rejected.action = Some("retry".to_string());
+		policy.config_invalid.exit_code = Some(64);
+		policy.retry_secs = 30;
+		assert!(check_failure_policy(&policy).is_ok());
+		set_failure_policy(policy.clone());
+		assert_eq!(action(Condition::PoolAuthRejected), Action::Retry(30));
+		assert_eq!(action(Condition::ConfigInvalid), Action::Exit(64));
+		assert_eq!(action(Condition::PluginMissing), Action::Exit(3));
+		assert_eq!(action(Condition::AllDevicesFailed), Action::Retry(30));
+		policy.plugin_missing.action = Some("abort".to_string());
+		assert!(check_failure_policy(&policy).is_err());
+	}
+}
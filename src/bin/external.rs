@@ -0,0 +1,126 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Messages of the external solver protocol, spoken with solver
+//! executables configured with command in place of a plugin library. One
+//! JSON object per line over the solver's stdin and stdout, told apart by
+//! their type, as documented in doc/external_solver.md.
+
+use std::collections::HashMap;
+
+use config::types::CuckooMinerPluginConfig;
+use cuckoo::CuckooMinerDeviceStats;
+
+/// Version of the protocol the miner speaks
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Messages to an external solver
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToSolver {
+	/// First message, with the solver's configuration
+	Init {
+		version: u32,
+		algorithm: String,
+		edge_bits: u8,
+		/// Parameters of each device, by device id
+		device_parameters: HashMap<String, HashMap<String, u32>>,
+	},
+	/// Start mining a job, replacing any job being mined
+	Job {
+		/// Header before the nonce, hex encoded
+		pre_pow: String,
+		target_difficulty: u64,
+		/// Devices not to mine on
+		disabled: Vec<u32>,
+	},
+	/// Stop mining, keeping the devices ready for the next job
+	Stop,
+	/// Exit
+	Shutdown,
+}
+
+impl ToSolver {
+	/// The init message for a plugin configuration
+	pub fn init(plugin: &CuckooMinerPluginConfig) -> ToSolver {
+		ToSolver::Init {
+			version: PROTOCOL_VERSION,
+			algorithm: plugin.algorithm(),
+			edge_bits: plugin.edge_bits,
+			device_parameters: plugin.device_parameters.clone().unwrap_or_default(),
+		}
+	}
+}
+
+/// Stats of one of an external solver's devices
+#[derive(Debug, Deserialize)]
+pub struct SolverDevice {
+	pub device_id: String,
+	#[serde(default)]
+	pub device_name: String,
+	#[serde(default = "default_true")]
+	pub in_use: bool,
+	#[serde(default)]
+	pub errored: bool,
+	/// Time the last graph took, in milliseconds
+	#[serde(default)]
+	pub graph_time_ms: u64,
+	/// Graphs searched since the solver started
+	#[serde(default)]
+	pub graphs: u32,
+}
+
+fn default_true() -> bool {
+	true
+}
+
+/// Messages from an external solver
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FromSolver {
+	/// A solution for the job being mined
+	Solution {
+		nonce: u64,
+		edge_bits: u32,
+		/// The cycle's edges
+		cycle: Vec<u32>,
+		/// Device that found it, if the solver knows
+		#[serde(default)]
+		device_id: Option<String>,
+	},
+	/// Stats of the solver's devices
+	Stats { devices: Vec<SolverDevice> },
+	/// A line for the miner's log
+	Log {
+		#[serde(default)]
+		level: String,
+		message: String,
+	},
+}
+
+impl SolverDevice {
+	/// The device stats as plugins report them
+	pub fn device_stats(&self, edge_bits: u8) -> CuckooMinerDeviceStats {
+		CuckooMinerDeviceStats {
+			device_id: self.device_id.clone(),
+			device_name: self.device_name.clone(),
+			in_use: self.in_use as u32,
+			cuckoo_size: edge_bits.to_string(),
+			has_errored: self.errored as u32,
+			last_solution_time: self.graph_time_ms * 1_000_000,
+			iterations_completed: self.graphs,
+			..Default::default()
+		}
+	}
+}
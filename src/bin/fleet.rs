@@ -0,0 +1,103 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fleet polling, fetching the stats of the other rigs listed in [fleet]
+//! from their API, for the TUI's fleet view
+
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+
+use config::{FleetConfig, FleetRigConfig};
+use http;
+use stats::{FleetRig, Stats};
+
+/// Polls the rigs of the fleet
+pub struct FleetMonitor {
+	config: FleetConfig,
+	stats: Arc<RwLock<Stats>>,
+}
+
+/// Base url of a rig's API, as configured
+fn base_url(url: &str) -> String {
+	let url = url.trim_right_matches('/');
+	if url.starts_with("http://") || url.starts_with("https://") {
+		url.to_string()
+	} else {
+		format!("http://{}", url)
+	}
+}
+
+/// Fetch the stats of a rig, and summarize them
+fn poll(rig: &FleetRigConfig) -> Result<FleetRig, String> {
+	let url = format!("{}/v1/stats", base_url(&rig.url));
+	let authorization = rig.token.as_ref().map(|t| format!("Bearer {}", t));
+	let stats: Stats = match http::get(&url, authorization.as_ref().map(|a| a.as_str()))? {
+		(200, body) => serde_json::from_str(&body).map_err(|e| format!("Invalid stats: {}", e))?,
+		(401, _) => return Err("Unauthorized, set the rig's token".to_string()),
+		(code, _) => return Err(format!("HTTP error {}", code)),
+	};
+	let name = rig
+		.name
+		.clone()
+		.or(stats.rig.name.clone())
+		.unwrap_or(rig.url.clone());
+	Ok(FleetRig::summarize(&name, &rig.url, &stats))
+}
+
+impl FleetMonitor {
+	/// Create a new fleet monitor
+	pub fn new(config: FleetConfig, stats: Arc<RwLock<Stats>>) -> FleetMonitor {
+		FleetMonitor {
+			config: config,
+			stats: stats,
+		}
+	}
+
+	/// Start polling in a new thread
+	pub fn start(self) {
+		let _ = thread::Builder::new()
+			.name("fleet".to_string())
+			.spawn(move || loop {
+				let previous = self.stats.read().unwrap().fleet.clone();
+				let fleet: Vec<FleetRig> = self
+					.config
+					.rigs
+					.iter()
+					.map(|rig| match poll(rig) {
+						Ok(r) => r,
+						Err(e) => {
+							// keep what was last known of the rig, it may be
+							// a passing network error
+							let mut r = previous
+								.iter()
+								.find(|p| p.url == rig.url)
+								.cloned()
+								.unwrap_or(FleetRig {
+									name: rig.name.clone().unwrap_or(rig.url.clone()),
+									url: rig.url.clone(),
+									..FleetRig::default()
+								});
+							r.error = Some(e);
+							r
+						}
+					})
+					.collect();
+				self.stats.write().unwrap().fleet = fleet;
+				thread::sleep(Duration::from_secs(self.config.interval_secs.max(1)));
+			});
+	}
+}
@@ -17,28 +17,119 @@
 extern crate grin_miner_util as util;
 extern crate grin_miner_config as config;
 
+extern crate backtrace;
+extern crate blake2_rfc;
 extern crate bufstream;
+#[macro_use]
+extern crate clap;
 extern crate time;
+extern crate toml;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
+extern crate sha2;
 #[macro_use]
 extern crate slog;
 #[macro_use]
 extern crate lazy_static;
+extern crate libloading;
 extern crate cursive;
+extern crate native_tls;
+extern crate rusqlite;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(unix)]
+extern crate signal_hook;
+#[cfg(feature = "nvml")]
+extern crate nvml_wrapper;
+#[cfg(feature = "grpc")]
+extern crate futures;
+#[cfg(feature = "grpc")]
+extern crate grpcio;
+#[cfg(feature = "grpc")]
+extern crate protobuf;
+#[cfg(windows)]
+#[macro_use]
+extern crate windows_service;
 
+pub mod alerts;
+pub mod api;
+pub mod auto;
+pub mod blocks;
+pub mod breaker;
+pub mod chain;
+pub mod chaos;
+pub mod clock;
+pub mod cmd;
+pub mod compat;
+pub mod control;
+pub mod control_socket;
+pub mod correlation;
+pub mod crash;
+pub mod daemon;
+pub mod dashboard;
+pub mod desktop;
+pub mod difficulty;
+pub mod dry_run;
+pub mod environment;
+pub mod errors;
+pub mod external;
+pub mod fleet;
+pub mod grpc;
+pub mod hardware;
+pub mod http;
+pub mod i18n;
+pub mod json_status;
+pub mod intensity;
+pub mod ledger;
+pub mod notify;
+pub mod pacing;
 pub mod plugin;
+pub mod recovery;
+pub mod reload;
+pub mod sandbox;
+pub mod service;
+pub mod signals;
 pub mod mining;
+pub mod mock_pool;
+pub mod mqtt;
+pub mod placement;
+pub mod rampup;
+pub mod report;
+pub mod scheduler;
+pub mod shared_stats;
+pub mod signing;
+pub mod solutions;
 pub mod client;
 pub mod types;
 pub mod stats;
+pub mod statslog;
+pub mod store;
+pub mod stream;
+pub mod submitter;
+pub mod suspend;
+pub mod systemd;
+pub mod telemetry;
+pub mod thermal;
+pub mod throttle;
+pub mod tuning;
+pub mod units;
+pub mod update;
+pub mod usage;
+pub mod verifier;
+pub mod wallet;
+pub mod watchdog;
 pub mod tui;
 
+use std::io::{IsTerminal, Write};
 use std::thread;
 use std::sync::{mpsc, Arc, RwLock};
 use std::sync::atomic::{AtomicBool, Ordering};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use config::GlobalConfig;
+use errors::MinerError;
 use util::cuckoo_miner as cuckoo;
 
 use tui::ui;
@@ -77,36 +168,691 @@ fn log_build_info() {
 }
 
 
+fn write_session_summary(path: &str, summary: &str) -> std::io::Result<()> {
+	let mut file = std::fs::File::create(path)?;
+	file.write_all(summary.as_bytes())?;
+	file.write_all(b"\n")
+}
+
+/// Default session report file, see report_file in the config
+const DEFAULT_REPORT_FILE: &str = "grin-miner-report.md";
+
+fn write_session_report(path: &str, config: &config::ConfigMembers, stats: &stats::Stats) {
+	let (basic_info, detailed_info, _) = info_strings();
+	match report::write_report(path, config, (basic_info, detailed_info), stats) {
+		Ok(()) => info!(LOGGER, "Session report written to {}", path),
+		Err(e) => error!(LOGGER, "Unable to write session report to {}: {}", path, e),
+	}
+}
+
 fn start_tui(
 	s: Arc<RwLock<stats::Stats>>, 
+	config: config::MinerConfig,
 	client_tx: mpsc::Sender<types::ClientMessage>,
 	miner_tx: mpsc::Sender<types::MinerMessage>,
-	stop: Arc<AtomicBool>) {
+	stop: Arc<AtomicBool>,
+	quit: Arc<AtomicBool>) {
 	// Run the UI controller.. here for now for simplicity to access
 	// everything it might need
-	println!("Starting Grin Miner in UI mode...");
+	println!("{}", i18n::t("console.starting_tui"));
 	let _ = thread::Builder::new()
 		.name("ui".to_string())
 		.spawn(move || {
-			let mut controller = ui::Controller::new().unwrap_or_else(|e| {
+			let mut controller = ui::Controller::new(&config, miner_tx.clone()).unwrap_or_else(|e| {
 				panic!("Error loading UI controller: {}", e);
 			});
-			controller.run(s.clone());
-			// Shut down everything else on tui exit
-			let _ = client_tx.send(types::ClientMessage::Shutdown);
-			let _ = miner_tx.send(types::MinerMessage::Shutdown);
-			println!("Stopping mining plugins and exiting...");
+			controller.run(s.clone(), quit.clone());
+			// Shut down everything else on tui exit, unless a signal made
+			// it quit and the miner is already draining
+			if !quit.load(Ordering::Relaxed) {
+				let _ = client_tx.send(types::ClientMessage::Shutdown);
+				let _ = miner_tx.send(types::MinerMessage::Shutdown);
+			}
+			let summary = s.read().unwrap().session_summary();
+			println!("{}", summary);
+			if let Some(f) = config.session_summary_file.clone() {
+				if let Err(e) = write_session_summary(&f, &summary) {
+					println!("{}", i18n::tf("console.summary_write_failed", &[&f, &e]));
+				}
+			}
+			println!("{}", i18n::t("console.stopping"));
 			stop.store(true, Ordering::Relaxed);
 		});
 }
 
-fn main() {
-	// Init configuration
-	let mut global_config = GlobalConfig::new(None).unwrap_or_else(|e| {
-		panic!("Error parsing config file: {}", e);
+/// Split `--section.key=value` config overrides out of the command line
+/// arguments, as clap can't declare them up front
+fn split_config_overrides(args: Vec<String>) -> (Vec<String>, Vec<(String, String)>) {
+	let mut rest = vec![];
+	let mut overrides = vec![];
+	for arg in args {
+		if arg.starts_with("--") {
+			if let Some(i) = arg.find('=') {
+				if arg[2..i].contains('.') {
+					overrides.push((arg[2..i].to_string(), arg[i + 1..].to_string()));
+					continue;
+				}
+			}
+		}
+		rest.push(arg);
+	}
+	(rest, overrides)
+}
+
+/// Command line arguments
+fn app() -> App<'static, 'static> {
+	App::new("grin-miner")
+		.version(crate_version!())
+		.about("Standalone Cuck(at)oo miner for grin")
+		.after_help(
+			"Any config value can be overridden with --section.key=value, e.g.\n    \
+			 --mining.stratum_server_addr=pool.example.com:3416\n    \
+			 -o miner_plugin_config.0.device_parameters.0.USE_DEVICE=1",
+		)
+		.arg(
+			Arg::with_name("config")
+				.short("c")
+				.long("config")
+				.value_name("FILE")
+				.help("Config file to use instead of the default grin-miner.toml")
+				.takes_value(true)
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("config-extra")
+				.long("config-extra")
+				.value_name("FILE")
+				.help("Config fragment to merge over the config file, can be given more than once")
+				.takes_value(true)
+				.multiple(true)
+				.number_of_values(1)
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("profile")
+				.long("profile")
+				.value_name("NAME")
+				.help("Device profile to mine with, one of the profiles in the config")
+				.takes_value(true)
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("chain")
+				.long("chain")
+				.value_name("CHAIN")
+				.help("Chain to mine on, setting its defaults")
+				.possible_values(&["mainnet", "testnet", "floonet", "custom"])
+				.takes_value(true)
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("daemon")
+				.long("daemon")
+				.help("Detach from the terminal and run in the background, logging to file only"),
+		)
+		.arg(
+			Arg::with_name("pid-file")
+				.long("pid-file")
+				.value_name("FILE")
+				.help("PID file written when mining, and read by stop and status")
+				.takes_value(true)
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("output")
+				.long("output")
+				.takes_value(true)
+				.possible_values(&["json"])
+				.help("Print status as one JSON object per line to stdout instead of running the TUI"),
+		)
+		.arg(
+			Arg::with_name("dry-run")
+				.long("dry-run")
+				.help("Start the plugins and log in to the pool, but submit no shares, then report"),
+		)
+		.arg(
+			Arg::with_name("dry-run-secs")
+				.long("dry-run-secs")
+				.value_name("SECS")
+				.help("Seconds a dry run lasts, 60 by default")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("chaos")
+				.long("chaos")
+				.value_name("RATES")
+				.help(
+					"Developer option, randomly drop the pool connection, delay and garble \
+					 pool messages, and stall submits, e.g. \
+					 disconnect=0.01,delay=0.05,delay_ms=2000,malformed=0.01,submit_stall_ms=3000",
+				)
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("telemetry-status")
+				.long("telemetry-status")
+				.help("Shows what anonymous usage statistics would be sent, if any, and exits"),
+		)
+		.arg(
+			Arg::with_name("override")
+				.short("o")
+				.long("override")
+				.value_name("KEY=VALUE")
+				.help("Overrides a config value, keys without a section are mining keys")
+				.takes_value(true)
+				.multiple(true)
+				.number_of_values(1)
+				.global(true),
+		)
+		.subcommand(
+			SubCommand::with_name("list-devices")
+				.about("Lists the devices each configured plugin can see, and exits"),
+		)
+		.subcommand(
+			SubCommand::with_name("solver-worker")
+				.about("Runs a sandboxed solver for the miner, over stdin and stdout")
+				.setting(AppSettings::Hidden),
+		)
+		.subcommand(
+			SubCommand::with_name("benchmark")
+				.about("Benchmarks a configured plugin on one device, writing the results to a JSON file")
+				.arg(
+					Arg::with_name("plugin")
+						.long("plugin")
+						.value_name("INDEX")
+						.help("Index of the plugin in the config, 0 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("device")
+						.long("device")
+						.value_name("N")
+						.help("Device to benchmark, 0 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("duration")
+						.long("duration")
+						.value_name("SECS")
+						.help("Seconds to run for, warm-up included, 120 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("warmup")
+						.long("warmup")
+						.value_name("GRAPHS")
+						.help("Graphs to discard while the solver warms up, 3 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("output")
+						.long("output")
+						.value_name("FILE")
+						.help("JSON file to write the results to")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("compare")
+				.about("Benchmarks plugins one after the other on one device, ranking them")
+				.arg(
+					Arg::with_name("device")
+						.long("device")
+						.value_name("N")
+						.help("Device to compare the plugins on, 0 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("plugins")
+						.long("plugins")
+						.value_name("LIST")
+						.help(
+							"Plugins to compare, comma separated: names as list-plugins shows \
+							 them (e.g. cuckatoo_lean_cuda_31) or indexes in the config",
+						)
+						.takes_value(true)
+						.required(true),
+				)
+				.arg(
+					Arg::with_name("duration")
+						.long("duration")
+						.value_name("SECS")
+						.help("Seconds to run each plugin for, warm-up included, 120 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("warmup")
+						.long("warmup")
+						.value_name("GRAPHS")
+						.help("Graphs to discard while each solver warms up, 3 by default")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("export-shares")
+				.about("Checks the share ledger and exports it, with totals to reconcile against the pool")
+				.arg(
+					Arg::with_name("format")
+						.long("format")
+						.takes_value(true)
+						.possible_values(&["csv", "json"])
+						.help("Export format, csv by default"),
+				)
+				.arg(
+					Arg::with_name("since")
+						.long("since")
+						.value_name("TIME")
+						.help("Only export the shares answered from this time on (seconds since epoch)")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("output")
+						.long("output")
+						.value_name("FILE")
+						.help("File to write the export to, stdout by default")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("tune")
+				.about("Sweeps a plugin's parameters on one device, caching the fastest for later runs")
+				.arg(
+					Arg::with_name("plugin")
+						.long("plugin")
+						.value_name("INDEX")
+						.help("Index of the plugin in the config, 0 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("device")
+						.long("device")
+						.value_name("N")
+						.help("Device to tune, 0 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("duration")
+						.long("duration")
+						.value_name("SECS")
+						.help("Seconds to benchmark each configuration for, 60 by default")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("stop")
+				.about("Stops the miner running in the background, found by its PID file"),
+		)
+		.subcommand(
+			SubCommand::with_name("status")
+				.about("Reports whether the miner is running in the background"),
+		)
+		.subcommand(
+			SubCommand::with_name("service")
+				.about("Runs the miner as a Windows service, starting at boot")
+				.subcommand(
+					SubCommand::with_name("install")
+						.about("Registers the service, mining with the config file in use"),
+				)
+				.subcommand(SubCommand::with_name("uninstall").about("Removes the service"))
+				.subcommand(
+					SubCommand::with_name("run")
+						.about("Runs as the service, as the service manager does")
+						.setting(AppSettings::Hidden),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("selftest")
+				.about("Checks the solutions each configured plugin finds on each device are valid cycles")
+				.arg(
+					Arg::with_name("duration")
+						.long("duration")
+						.value_name("SECS")
+						.help("Seconds to test each device for at most, 300 by default")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("qualify")
+				.about("Runs a fixed scenario and records its results, comparing them to golden ones")
+				.arg(
+					Arg::with_name("devices")
+						.long("devices")
+						.help("Benchmarks the configured devices instead of the mock pool scenario"),
+				)
+				.arg(
+					Arg::with_name("duration")
+						.long("duration")
+						.value_name("SECS")
+						.help("Seconds to benchmark each device for with --devices, 60 by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("output")
+						.long("output")
+						.value_name("FILE")
+						.help("File to write the results to, qualification.json by default")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("compare")
+						.long("compare")
+						.value_name("FILE")
+						.help("Golden results to compare with, failing on regressions")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::with_name("tolerance")
+						.long("tolerance")
+						.value_name("PCT")
+						.help("Percentage figures may get worse by, overriding the golden file's")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("attach")
+				.about("Shows the TUI of a miner running headless, from its API")
+				.arg(
+					Arg::with_name("url")
+						.help("Address of the miner's API, the local api bind_address by default"),
+				)
+				.arg(
+					Arg::with_name("token")
+						.long("token")
+						.value_name("TOKEN")
+						.help("The miner's api control_token, the local one by default")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("ctl")
+				.about("Sends a control command to the miner running here, through its control socket")
+				.arg(
+					Arg::with_name("command")
+						.help(
+							"pause, resume, activate, reload, reconnect, pool, profile or \
+							 loglevel",
+						)
+						.required(true),
+				)
+				.arg(Arg::with_name("params").help(
+					"The command's parameters as JSON, e.g. '{\"name\":\"night\"}' for profile",
+				))
+				.arg(
+					Arg::with_name("token")
+						.long("token")
+						.value_name("TOKEN")
+						.help("The miner's control_socket token, the local one by default")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("update")
+				.about("Downloads a newer signed release, installed the next time the miner starts")
+				.arg(
+					Arg::with_name("check-only")
+						.long("check-only")
+						.help("Only report whether a newer release is available"),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("list-plugins")
+				.about("Lists the plugins in the plugin directory, and exits"),
+		)
+		.subcommand(
+			SubCommand::with_name("plugin-params")
+				.about("Lists the tunable parameters of a plugin, and exits")
+				.arg(
+					Arg::with_name("plugin")
+						.help("Plugin name, or part of it, e.g. cuckatoo_lean_cpu")
+						.required(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("config")
+				.about("Generates or validates the config file")
+				.subcommand(
+					SubCommand::with_name("generate")
+						.about("Writes a commented default config file")
+						.arg(
+							Arg::with_name("output")
+								.help("File to write")
+								.default_value("grin-miner.toml"),
+						)
+						.arg(
+							Arg::with_name("detect")
+								.long("detect")
+								.help("Configure the best plugin installed for this machine"),
+						)
+						.arg(
+							Arg::with_name("force")
+								.long("force")
+								.help("Overwrite an existing file"),
+						),
+				)
+				.subcommand(
+					SubCommand::with_name("validate")
+						.about("Checks a config file against the installed plugins")
+						.arg(Arg::with_name("file").help(
+							"Config file to check, the one grin-miner would use by default",
+						))
+						.arg(
+							Arg::with_name("devices")
+								.long("devices")
+								.help("Also start the plugins to check the configured device ids"),
+						),
+				),
+		)
+}
+
+/// The arguments without the `service run` subcommand, found where clap
+/// parses it rather than by value, so that option values which happen to
+/// be service or run are kept
+fn without_service_run(cli_args: &[String]) -> Vec<String> {
+	let position = (1..cli_args.len().saturating_sub(1)).find(|&i| {
+		if cli_args[i] != "service" || cli_args[i + 1] != "run" {
+			return false;
+		}
+		// an option's value followed by run wouldn't parse as the subcommand
+		app()
+			.get_matches_from_safe(&cli_args[..i + 2])
+			.ok()
+			.and_then(|m| {
+				m.subcommand_matches("service")
+					.map(|s| s.subcommand_name() == Some("run"))
+			})
+			.unwrap_or(false)
 	});
+	let mut miner_args = cli_args.to_vec();
+	if let Some(i) = position {
+		miner_args.drain(i..i + 2);
+	}
+	miner_args
+}
+
+fn main() {
+	clock::init();
+	let (cli_args, config_overrides) = split_config_overrides(std::env::args().collect());
+	let args = app().get_matches_from(cli_args.clone());
+
+	// The Windows service manager runs the miner with service run, the
+	// rest of the service commands only (un)register it
+	if let Some(service_args) = args.subcommand_matches("service") {
+		let result = match service_args.subcommand_name() {
+			Some("install") => config_file_path(args.value_of("config"))
+				.and_then(|path| service::install(&path))
+				.map(|_| println!("Service {} installed, starting at boot", service::SERVICE_NAME)),
+			Some("uninstall") => service::uninstall()
+				.map(|_| println!("Service {} removed", service::SERVICE_NAME)),
+			Some("run") => {
+				// services start in the system directory, the config's
+				// relative paths are relative to its own
+				if let Ok(path) = config_file_path(args.value_of("config")) {
+					if let Some(dir) = std::path::Path::new(&path).parent() {
+						let _ = std::env::set_current_dir(dir);
+					}
+				}
+				let miner_args = without_service_run(&cli_args);
+				service::run(Box::new(move || {
+					run(app().get_matches_from(miner_args.clone()), config_overrides.clone())
+				}))
+			}
+			_ => Err(service_args.usage().to_string()),
+		};
+		if let Err(e) = result {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+		return;
+	}
+	run(args, config_overrides);
+}
+
+/// Absolute path of the config file that would be used
+fn config_file_path(config: Option<&str>) -> Result<String, String> {
+	let path = match config {
+		Some(c) => std::path::PathBuf::from(c),
+		None => GlobalConfig::new(None)
+			.map_err(|e| format!("{}", e))?
+			.config_file_path
+			.ok_or("No config file found, give one with --config".to_string())?,
+	};
+	std::fs::canonicalize(&path)
+		.map(|p| p.to_string_lossy().into_owned())
+		.map_err(|e| format!("Unable to find {}: {}", path.display(), e))
+}
+
+/// Run the command given, mining unless it's one of the subcommands
+fn run(args: ArgMatches<'static>, mut config_overrides: Vec<(String, String)>) {
+	if let Some(values) = args.values_of("override") {
+		for v in values {
+			match v.find('=') {
+				Some(i) => config_overrides.push((v[..i].to_string(), v[i + 1..].to_string())),
+				None => {
+					eprintln!("Invalid override {}, expected KEY=VALUE", v);
+					std::process::exit(1);
+				}
+			}
+		}
+	}
+
+	// flags that are shorthand for config values
+	for &(flag, key) in &[
+		("profile", "mining.profile"),
+		("chain", "mining.chain_type"),
+		("pid-file", "mining.pid_file"),
+		("output", "mining.output_format"),
+	] {
+		if let Some(v) = args.value_of(flag) {
+			config_overrides.push((
+				key.to_string(),
+				format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")),
+			));
+		}
+	}
+
+	// Config file commands run before the config is loaded, as it may
+	// not exist or be invalid
+	if let Some(config_args) = args.subcommand_matches("config") {
+		let result = match config_args.subcommand() {
+			("generate", Some(a)) => cmd::config_file::generate(
+				a.value_of("output").unwrap(),
+				a.is_present("detect"),
+				a.is_present("force"),
+			),
+			("validate", Some(a)) => {
+				cmd::config_file::validate(
+					a.value_of("file").or(args.value_of("config")),
+					a.is_present("devices"),
+				)
+			}
+			_ => Err(config_args.usage().to_string()),
+		};
+		if let Err(e) = result {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	// Sandboxed solvers get everything they need from the miner, over
+	// stdin, and stdout is theirs to answer on
+	if args.subcommand_name() == Some("solver-worker") {
+		if let Err(e) = sandbox::run_worker() {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	// First run on a terminal without a config file, set one up
+	let mut config_path = args.value_of("config").map(|s| s.to_string());
+	let no_config_file = config_path.is_none()
+		&& GlobalConfig::new(None)
+			.map(|c| c.config_file_path.is_none())
+			.unwrap_or(false);
+	if no_config_file && args.subcommand_name().is_none() && std::io::stdin().is_terminal() {
+		match cmd::wizard::run() {
+			Ok(Some(path)) => config_path = Some(path),
+			Ok(None) => return,
+			Err(e) => {
+				eprintln!("{}", e);
+				std::process::exit(1);
+			}
+		}
+	}
+
+	// Init configuration, failures until its failure policy is loaded
+	// follow the default one
+	let mut global_config = GlobalConfig::new(config_path.as_ref().map(|s| s.as_str()))
+		.unwrap_or_else(|e| {
+			errors::fatal(MinerError::config(&format!("Error parsing config file: {}", e)))
+		});
+	let config_extra: Vec<String> = args
+		.values_of("config-extra")
+		.map(|v| v.map(|s| s.to_string()).collect())
+		.unwrap_or_default();
+	global_config
+		.merge_files(&config_extra)
+		.unwrap_or_else(|e| errors::fatal(MinerError::config(&e.to_string())));
+	// environment overrides the file, the command line overrides both
+	let mut overrides = config::env_overrides();
+	overrides.extend(config_overrides.iter().cloned());
+	global_config
+		.apply_overrides(&overrides)
+		.unwrap_or_else(|e| errors::fatal(MinerError::config(&e.to_string())));
+	global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.mining
+		.apply_selected_profile()
+		.unwrap_or_else(|e| errors::fatal(MinerError::config(&e.to_string())));
+	let chain_warnings = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.mining
+		.apply_chain_defaults()
+		.unwrap_or_else(|e| errors::fatal(MinerError::config(&e.to_string())));
+	// terminal conditions from here on follow the config's failure policy
+	let failure_policy = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.failure_policy
+		.clone()
+		.unwrap_or_default();
+	errors::check_failure_policy(&failure_policy)
+		.unwrap_or_else(|e| errors::fatal(MinerError::config(&e)));
+	errors::set_failure_policy(failure_policy);
 	println!("Starting Grin-Miner from config file at: {}", 
 		global_config.config_file_path.unwrap().to_str().unwrap());
+	for m in &global_config.migrations {
+		println!("Config file upgraded: {}", m);
+	}
+	for m in &global_config.merged {
+		println!("Config merged: {}", m);
+	}
 	// Init logging
 	let mut log_conf = global_config
 		.members
@@ -116,45 +862,641 @@ fn main() {
 		.clone()
 		.unwrap();
 
-	let mining_config = global_config.members.as_mut().unwrap().mining.clone();
+	let mut mining_config = global_config.members.as_mut().unwrap().mining.clone();
+	units::configure(&mining_config);
+	// before anything loads the plugins
+	hardware::prepare(&mining_config);
+	let alert_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.alerts
+		.clone()
+		.unwrap_or_default();
+	let api_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.api
+		.clone()
+		.unwrap_or_default();
+	let notification_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.notifications
+		.clone()
+		.unwrap_or_default();
+	let stats_log_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.stats_log
+		.clone()
+		.unwrap_or_default();
+	let shared_stats_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.shared_stats
+		.clone()
+		.unwrap_or_default();
+	let telemetry_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.telemetry
+		.clone()
+		.unwrap_or_default();
+	let mqtt_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.mqtt
+		.clone()
+		.unwrap_or_default();
+	let history_store_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.history_store
+		.clone()
+		.unwrap_or_default();
+	let update_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.update
+		.clone()
+		.unwrap_or_default();
+	let control_socket_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.control_socket
+		.clone()
+		.unwrap_or_default();
+	let grpc_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.grpc
+		.clone()
+		.unwrap_or_default();
+
+	let usage_stats_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.usage_stats
+		.clone()
+		.unwrap_or_default();
+
+	if args.is_present("telemetry-status") {
+		log_conf.log_to_stdout = false;
+		init_logger(Some(log_conf));
+		let result = cmd::telemetry_status(mining_config, usage_stats_config, api_config);
+		if let Err(e) = result {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	// Subcommands run instead of mining, logging to file only
+	if let (name, Some(sub_args)) = args.subcommand() {
+		log_conf.log_to_stdout = false;
+		init_logger(Some(log_conf));
+		crash::install(&mining_config, None);
+		let number = |name: &str, default: u64| match sub_args.value_of(name) {
+			Some(v) => v.parse::<u64>().unwrap_or_else(|_| {
+				eprintln!("Invalid --{} {}", name, v);
+				std::process::exit(1);
+			}),
+			None => default,
+		};
+		let result = match name {
+			"list-devices" => cmd::list_devices(mining_config),
+			"list-plugins" => cmd::list_plugins(mining_config),
+			"stop" => cmd::stop(mining_config),
+			"attach" => cmd::attach(
+				mining_config,
+				api_config,
+				sub_args.value_of("url"),
+				sub_args.value_of("token"),
+			),
+			"status" => cmd::status(mining_config),
+			"update" => cmd::update(update_config, sub_args.is_present("check-only")),
+			"ctl" => cmd::ctl(
+				control_socket_config,
+				sub_args.value_of("command").unwrap(),
+				sub_args.value_of("params"),
+				sub_args.value_of("token"),
+			),
+			"plugin-params" => cmd::plugin_params(mining_config, sub_args.value_of("plugin").unwrap()),
+			"benchmark" => cmd::benchmark(
+				mining_config,
+				number("plugin", 0) as usize,
+				number("device", 0) as u32,
+				number("duration", cmd::benchmark::DEFAULT_DURATION_SECS),
+				number("warmup", cmd::benchmark::DEFAULT_WARMUP_GRAPHS as u64) as usize,
+				sub_args.value_of("output"),
+			),
+			"compare" => cmd::compare(
+				mining_config,
+				number("device", 0) as u32,
+				sub_args.value_of("plugins").unwrap(),
+				number("duration", cmd::compare::DEFAULT_DURATION_SECS),
+				number("warmup", cmd::benchmark::DEFAULT_WARMUP_GRAPHS as u64) as usize,
+			),
+			"selftest" => cmd::selftest(
+				mining_config,
+				number("duration", cmd::selftest::DEFAULT_DURATION_SECS),
+			),
+			"qualify" => cmd::qualify(
+				mining_config,
+				sub_args.is_present("devices"),
+				number("duration", cmd::qualify::DEFAULT_DURATION_SECS),
+				sub_args.value_of("output"),
+				sub_args.value_of("compare"),
+				sub_args.value_of("tolerance").map(|t| {
+					t.parse::<f64>().unwrap_or_else(|_| {
+						eprintln!("Invalid --tolerance {}", t);
+						std::process::exit(1);
+					})
+				}),
+			),
+			"export-shares" => cmd::export_shares(
+				mining_config,
+				sub_args.value_of("format").unwrap_or("csv"),
+				number("since", 0) as i64,
+				sub_args.value_of("output"),
+			),
+			"tune" => cmd::tune(
+				mining_config,
+				number("plugin", 0) as usize,
+				number("device", 0) as u32,
+				number("duration", cmd::tune::DEFAULT_DURATION_SECS),
+			),
+			_ => Ok(()),
+		};
+		if let Err(e) = result {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+		return;
+	}
+	let scheduler_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.scheduler
+		.clone()
+		.unwrap_or_default();
+	let submit_limit_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.submit_limit
+		.clone()
+		.unwrap_or_default();
+	let reject_breaker_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.reject_breaker
+		.clone()
+		.unwrap_or_default();
+	let fleet_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.fleet
+		.clone()
+		.unwrap_or_default();
+
+	// translations are loaded before anything's printed, and the outcome
+	// logged once there's a logger
+	let translation = i18n::init(&mining_config);
 
-	if mining_config.run_tui {
+	let dry_run = args.is_present("dry-run");
+	let dry_run_secs = match args.value_of("dry-run-secs") {
+		Some(s) => s.parse::<u64>().unwrap_or_else(|_| {
+			eprintln!("Invalid --dry-run-secs {}", s);
+			std::process::exit(1);
+		}),
+		None => dry_run::DEFAULT_DRY_RUN_SECS,
+	};
+	let chaos = args.value_of("chaos").map(|c| {
+		chaos::Chaos::parse(c).unwrap_or_else(|e| {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		})
+	});
+	let hardware_monitor_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.hardware_monitor
+		.clone()
+		.unwrap_or_default();
+	let solo_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.solo
+		.clone()
+		.unwrap_or_default();
+	let desktop_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.desktop
+		.clone()
+		.unwrap_or_default();
+	let thermal_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.thermal
+		.clone()
+		.unwrap_or_default();
+	let recovery_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.recovery
+		.clone()
+		.unwrap_or_default();
+	let watchdog_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.watchdog
+		.clone()
+		.unwrap_or_default();
+	let rig_config = global_config
+		.members
+		.as_mut()
+		.unwrap()
+		.rig
+		.clone()
+		.unwrap_or_default();
+
+	// The TUI needs a terminal, fall back to the console dashboard if
+	// there isn't one (e.g. running under systemd or docker). Dry runs
+	// just log, and report at the end.
+	// A daemon has neither, and logs to its file only. JSON status output
+	// replaces both, and keeps stdout for itself.
+	let run_daemon = args.is_present("daemon");
+	let run_json_status = mining_config
+		.output_format
+		.as_ref()
+		.map_or(false, |f| f == json_status::FORMAT) && !dry_run && !run_daemon;
+	let is_terminal = std::io::stdout().is_terminal();
+	let mut run_tui =
+		mining_config.run_tui && is_terminal && !dry_run && !run_daemon && !run_json_status;
+	let mut run_dashboard =
+		mining_config.run_tui && !is_terminal && !dry_run && !run_daemon && !run_json_status;
+	// ncurses exits the process if it can't start, so check first and
+	// keep the rig mining on the dashboard. Only unix terminals have
+	// terminfo entries.
+	#[cfg(unix)]
+	let tui_problem = if run_tui && mining_config.tui_fallback.unwrap_or(true) {
+		tui::terminal::check().err()
+	} else {
+		None
+	};
+	#[cfg(not(unix))]
+	let tui_problem: Option<String> = None;
+	if tui_problem.is_some() {
+		run_tui = false;
+		run_dashboard = true;
+	}
+
+	if run_tui {
 		log_conf.log_to_stdout = false;
 		log_conf.tui_running = Some(true);
 	}
+	if run_json_status {
+		log_conf.log_to_stdout = false;
+	}
+
+	let pid_file = if run_daemon || mining_config.pid_file.is_some() {
+		Some(daemon::pid_file(&mining_config))
+	} else {
+		None
+	};
+	if run_daemon {
+		log_conf.log_to_stdout = false;
+		log_conf.log_to_file = true;
+		// refuse here, while there's still a terminal to say so on
+		if let Some(pid) = daemon::running_pid(pid_file.as_ref().unwrap()) {
+			eprintln!("{}", i18n::tf("console.already_running", &[&pid]));
+			std::process::exit(1);
+		}
+		println!(
+			"{}",
+			i18n::tf(
+				"console.daemon_started",
+				&[&log_conf.log_file_path, pid_file.as_ref().unwrap()]
+			)
+		);
+		// before any thread is started
+		if let Err(e) = daemon::detach() {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+	}
 
+	log_conf.log_rig = rig_config.name.clone();
 	init_logger(Some(log_conf));
+	crash::install(&mining_config, None);
+	crash::report_last_crash(&mining_config);
+	// once the logger is up, logging before it would disable it for good
+	match update::apply_staged(&update_config) {
+		Ok(Some(version)) => {
+			warn!(LOGGER, "Restarting to run grin-miner {}", version);
+			if let Err(e) = update::restart() {
+				error!(LOGGER, "{}", e);
+			}
+		}
+		Ok(None) => {}
+		Err(e) => error!(LOGGER, "Unable to install the staged release: {}", e),
+	}
+	match translation {
+		Ok(Some((language, translated))) => info!(
+			LOGGER,
+			"Using the {} translation, {} of {} messages translated",
+			language,
+			translated,
+			i18n::message_count()
+		),
+		Ok(None) => {}
+		Err(e) => warn!(LOGGER, "{}, using English", e),
+	}
+	// before the solver workers are started, so they don't inherit it
+	let mut systemd_notifier = systemd::Notifier::new();
+	if let Some(ref path) = pid_file {
+		if let Err(e) = daemon::write_pid_file(path) {
+			error!(LOGGER, "{}", e);
+			std::process::exit(1);
+		}
+	}
 
 	log_build_info();
+	for w in &chain_warnings {
+		warn!(LOGGER, "{}", w);
+	}
+	if mining_config.auto.unwrap_or(false) {
+		auto::auto_configure(&mut mining_config).unwrap_or_else(|e| {
+			errors::fatal(MinerError::config(&e));
+		});
+		global_config.members.as_mut().unwrap().mining = mining_config.clone();
+	}
+	if let Err(e) = plugin::verify_plugins(&mining_config) {
+		errors::fatal(MinerError::plugin_load(None, &e));
+	}
+	plugin::log_plugin_dirs(&mining_config);
+	if let Err(e) = compat::check_plugins(&mut mining_config) {
+		errors::fatal(MinerError::plugin_load(None, &e));
+	}
+	global_config.members.as_mut().unwrap().mining = mining_config.clone();
+	if let Err(e) = auto::check_gpu_memory(&mining_config) {
+		errors::fatal(MinerError::config(&e));
+	}
+	if let Some(ref url) = solo_config.wallet_listener_url {
+		if solo_config.check_wallet {
+			match wallet::check(url) {
+				Ok(()) => info!(LOGGER, "Solo mining wallet at {} answers", url),
+				Err(e) => errors::fatal(MinerError::config(&e)),
+			}
+		}
+	}
+
+	if let Some(ref problem) = tui_problem {
+		warn!(
+			LOGGER,
+			"The TUI can't start on this terminal ({}), running console dashboard instead",
+			problem
+		);
+	} else if run_dashboard {
+		warn!(
+			LOGGER,
+			"stdout is not a terminal, running console dashboard instead of the TUI"
+		);
+	}
 
 	let stats = Arc::new(RwLock::new(stats::Stats::default()));
+	stats.write().unwrap().rig = rig_config.clone();
+	let blocks_file = blocks::path(&mining_config);
+	stats.write().unwrap().blocks = blocks::load(&blocks_file);
+	let mining_environment = environment::collect(&mining_config);
+	environment::log(&mining_environment);
+	stats.write().unwrap().environment = Some(mining_environment);
+	crash::set_stats(stats.clone());
 
-	let mut mc = mining::Controller::new(mining_config.clone(), stats.clone()).unwrap_or_else(|e| {
-		panic!("Error loading mining controller: {}", e);
-	});
+	let mut mc = mining::Controller::new(mining_config.clone(), alert_config, stats.clone())
+		.unwrap_or_else(|e| {
+			errors::fatal(MinerError::config(&e));
+		});
 
-	let cc = client::Controller::new(
+	let mut cc = client::Controller::new(
 		&mining_config.stratum_server_addr,
 		mining_config.stratum_server_login.clone(),
 		mining_config.stratum_server_password.clone(),
 		mc.tx.clone(),
 		stats.clone(),
 	).unwrap_or_else(|e| {
-
-		panic!("Error loading stratum client controller: {:?}", e);
+		errors::fatal(MinerError::config(&format!(
+			"unable to start the stratum client: {:?}",
+			e
+		)));
 	});
 
 	let tui_stopped = Arc::new(AtomicBool::new(false));
+	let tui_quit = Arc::new(AtomicBool::new(false));
 	let miner_stopped = Arc::new(AtomicBool::new(false));
 	let client_stopped = Arc::new(AtomicBool::new(false));
 
-	if mining_config.run_tui {
-		start_tui(stats.clone(), cc.tx.clone(), mc.tx.clone(), tui_stopped.clone());
+	if run_tui {
+		start_tui(
+			stats.clone(),
+			mining_config.clone(),
+			cc.tx.clone(),
+			mc.tx.clone(),
+			tui_stopped.clone(),
+			tui_quit.clone(),
+		);
 	} else {
 		tui_stopped.store(true, Ordering::Relaxed);
 	}
 
+	if run_dashboard {
+		let dashboard_stats = stats.clone();
+		let interval = mining_config
+			.dashboard_interval
+			.unwrap_or(dashboard::DEFAULT_INTERVAL);
+		let _ = thread::Builder::new()
+			.name("dashboard".to_string())
+			.spawn(move || {
+				dashboard::run(dashboard_stats, interval);
+			});
+	}
+
+	if run_json_status {
+		let json_status_stats = stats.clone();
+		let interval = mining_config
+			.output_interval
+			.unwrap_or(json_status::DEFAULT_INTERVAL);
+		let _ = thread::Builder::new()
+			.name("json_status".to_string())
+			.spawn(move || {
+				json_status::run(json_status_stats, interval);
+			});
+	}
+
+	if api_config.enabled {
+		let control = control::Control::new(mc.tx.clone(), cc.tx.clone());
+		let mut server = api::Server::new(api_config, stats.clone(), control);
+		if history_store_config.enabled {
+			server.set_history_store(history_store_config.path.clone());
+		}
+		server.start();
+	}
+
+	if control_socket_config.enabled {
+		let control = control::Control::new(mc.tx.clone(), cc.tx.clone());
+		control_socket::ControlSocket::new(control_socket_config, control).start();
+	}
+
+	if grpc_config.enabled {
+		let control = control::Control::new(mc.tx.clone(), cc.tx.clone());
+		grpc::Server::new(grpc_config, stats.clone(), control).start();
+	}
+
+	if !notification_config.webhooks.is_empty() {
+		notify::Notifier::new(notification_config, stats.clone()).start();
+	}
+
+	if stats_log_config.enabled {
+		statslog::StatsLogger::new(stats_log_config, stats.clone()).start();
+	}
+
+	if shared_stats_config.enabled {
+		shared_stats::SharedStats::new(shared_stats_config, stats.clone()).start();
+	}
+
+	if history_store_config.enabled {
+		store::HistoryStore::new(history_store_config, stats.clone()).start();
+	}
+
+	if telemetry_config.enabled {
+		telemetry::Exporter::new(telemetry_config, stats.clone()).start();
+	}
+
+	if usage_stats_config.enabled {
+		usage::UsageReporter::new(usage_stats_config, mining_config.clone(), stats.clone())
+			.start();
+	}
+
+	if mqtt_config.enabled {
+		let control = control::Control::new(mc.tx.clone(), cc.tx.clone());
+		mqtt::MqttClient::new(mqtt_config, stats.clone(), control).start();
+	}
+
+	if !fleet_config.rigs.is_empty() {
+		fleet::FleetMonitor::new(fleet_config, stats.clone()).start();
+	}
+
+	if scheduler_config.enabled {
+		scheduler::Scheduler::new(scheduler_config, stats.clone(), mc.tx.clone()).start();
+	}
+
+	if desktop_config.enabled {
+		desktop::DesktopWatcher::new(
+			desktop_config,
+			mining_config.clone(),
+			stats.clone(),
+			mc.tx.clone(),
+		).start();
+	}
+
+	if thermal_config.enabled {
+		if !hardware_monitor_config.enabled {
+			warn!(
+				LOGGER,
+				"Thermal protection needs hardware monitoring, enable [hardware_monitor]"
+			);
+		}
+		thermal::ThermalGuard::new(thermal_config, stats.clone(), mc.tx.clone()).start();
+	}
+
+	if hardware_monitor_config.enabled {
+		hardware::HardwareMonitor::new(hardware_monitor_config, stats.clone()).start();
+	}
+
+	if watchdog_config.enabled {
+		watchdog::ShareWatchdog::new(watchdog_config, stats.clone(), mc.tx.clone()).start();
+	}
+
+	if mining_config.suspend_detection.unwrap_or(true) {
+		suspend::SuspendDetector::new(stats.clone(), mc.tx.clone(), cc.tx.clone()).start();
+	}
+
 	mc.set_client_tx(cc.tx.clone());
+	cc.set_solution_buffer(mc.solutions.clone());
+	mc.set_recovery(recovery_config);
+	cc.set_rig_id(rig_config.name.clone());
+	cc.set_submit_token(mining_config.stratum_submit_token.clone());
+	cc.set_minimum_difficulty(mining_config.minimum_share_difficulty);
+	cc.set_submit_limit(submit_limit_config);
+	cc.set_reject_breaker(reject_breaker_config);
+	cc.set_job_prefetch(mining_config.job_prefetch.unwrap_or(false));
+	cc.set_submit_correlation_id(mining_config.submit_correlation_id.unwrap_or(false));
+	cc.set_blocks_file(blocks_file);
+	if let Some(path) = mining_config.share_ledger_file.clone().filter(|p| !p.is_empty()) {
+		let key = mining_config.share_ledger_key.clone();
+		cc.set_share_ledger(Some(ledger::ShareLedger::open(&path, key)));
+	}
+	let algorithms = mining_config.algorithms();
+	if algorithms.len() > 1 {
+		info!(LOGGER, "Mining {} concurrently", algorithms.join(" and "));
+		cc.set_algorithms(algorithms);
+	}
+	if dry_run {
+		warn!(
+			LOGGER,
+			"Dry run for {} seconds, shares found won't be submitted", dry_run_secs
+		);
+		cc.set_dry_run(true);
+	}
+	if let Some(chaos) = chaos {
+		warn!(
+			LOGGER,
+			"Chaos mode, per message received: disconnect {}, delay {} (up to {}ms), \
+			 malformed {}; submits stalled {}ms",
+			chaos.disconnect_rate,
+			chaos.delay_rate,
+			chaos.max_delay_ms,
+			chaos.malformed_rate,
+			chaos.submit_stall_ms
+		);
+		cc.set_chaos(chaos);
+	}
+	let miner_tx = mc.tx.clone();
+	let client_tx = cc.tx.clone();
+	let dry_run_end = clock::mono_secs() + dry_run_secs as i64;
+	let mut dry_run_done = false;
+
+	let mut reloader = reload::ConfigReloader::new(
+		config_path,
+		config_extra,
+		config_overrides,
+		global_config.members.clone().unwrap(),
+		mc.tx.clone(),
+		cc.tx.clone(),
+	);
 
 	let miner_stopped_internal = miner_stopped.clone();
 	let _ = thread::Builder::new()
@@ -172,7 +1514,80 @@ fn main() {
 			client_stopped_internal.store(true, Ordering::Relaxed);
 		});
 
+	systemd_notifier.ready();
+
+	// SIGTERM and SIGINT drain and exit, SIGUSR1 dumps the stats and
+	// writes a session report, SIGHUP reloads the config
+	let signals = signals::Signals::register();
+	let mut drain_deadline: Option<i64> = None;
+	let mut client_shut_down = false;
+	let watch_config_file = mining_config.watch_config_file.unwrap_or(false);
+	let mut ticks: u64 = 0;
+	let report_file = mining_config
+		.report_file
+		.clone()
+		.unwrap_or(DEFAULT_REPORT_FILE.to_string());
+
 	loop{
+		if signals::Signals::take(&signals.shutdown) {
+			if drain_deadline.is_some() {
+				warn!(LOGGER, "Shutdown signal received again, exiting now");
+				if let Some(ref path) = pid_file {
+					daemon::remove_pid_file(path);
+				}
+				std::process::exit(1);
+			}
+			warn!(
+				LOGGER,
+				"Shutdown signal received, stopping the plugins and sending the solutions found before exiting"
+			);
+			tui_quit.store(true, Ordering::Relaxed);
+			systemd_notifier.stopping();
+			// the client goes once the miner has handed it its last solutions
+			let _ = miner_tx.send(types::MinerMessage::Shutdown);
+			drain_deadline = Some(clock::mono_secs() + signals::DRAIN_TIMEOUT_SECS);
+		}
+		if let Some(deadline) = drain_deadline {
+			if !client_shut_down && miner_stopped.load(Ordering::Relaxed) {
+				let _ = client_tx.send(types::ClientMessage::Shutdown);
+				client_shut_down = true;
+			}
+			if clock::mono_secs() >= deadline {
+				warn!(LOGGER, "Still draining after {}s, exiting anyway", signals::DRAIN_TIMEOUT_SECS);
+				break;
+			}
+		}
+		if signals::Signals::take(&signals.stats_dump) {
+			let stats = stats.read().unwrap();
+			signals::log_stats_snapshot(&stats);
+			write_session_report(&report_file, global_config.members.as_ref().unwrap(), &stats);
+		}
+		if signals::Signals::take(&signals.reload) {
+			info!(LOGGER, "SIGHUP received, reloading config");
+			systemd_notifier.reloading();
+			reloader.reload();
+			systemd_notifier.reloaded();
+		}
+		systemd_notifier.tick(&stats.read().unwrap(), !miner_stopped.load(Ordering::Relaxed));
+		// check the config file about once a second
+		ticks += 1;
+		if watch_config_file && ticks % 10 == 0 {
+			reloader.check_modified();
+		}
+		if dry_run && !dry_run_done && clock::mono_secs() >= dry_run_end {
+			dry_run_done = true;
+			let _ = client_tx.send(types::ClientMessage::Shutdown);
+			let _ = miner_tx.send(types::MinerMessage::Shutdown);
+			let stats = stats.read().unwrap();
+			println!(
+				"{}",
+				dry_run::report(
+					&stats,
+					mining_config.stratum_server_login.as_ref().map(|s| s.as_str()),
+					dry_run_secs
+				)
+			);
+		}
 		if miner_stopped.load(Ordering::Relaxed)
 				&& client_stopped.load(Ordering::Relaxed)
 				&& tui_stopped.load(Ordering::Relaxed) {
@@ -181,4 +1596,7 @@ fn main() {
 		}
 		thread::sleep(std::time::Duration::from_millis(100));
 	}
+	if let Some(ref path) = pid_file {
+		daemon::remove_pid_file(path);
+	}
 }
@@ -0,0 +1,376 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! gRPC server, the Miner service of proto/grin_miner.proto: the stats
+//! of /v1/status and /v1/devices, once or streamed, and the pause, resume
+//! and pool control commands, run by the same handler as the API's
+//! control endpoints. Only with the grpc feature.
+
+use std::sync::{Arc, RwLock};
+
+use config::GrpcConfig;
+use control::Control;
+use stats::Stats;
+
+/// The gRPC server
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+pub struct Server {
+	config: GrpcConfig,
+	stats: Arc<RwLock<Stats>>,
+	control: Control,
+}
+
+impl Server {
+	/// Create a new gRPC server
+	pub fn new(config: GrpcConfig, stats: Arc<RwLock<Stats>>, control: Control) -> Server {
+		Server {
+			config: config,
+			stats: stats,
+			control: control,
+		}
+	}
+
+	/// Start serving, on gRPC's own threads
+	#[cfg(feature = "grpc")]
+	pub fn start(self) {
+		service::start(self.config, self.stats, self.control);
+	}
+
+	/// Nothing to start without the grpc feature
+	#[cfg(not(feature = "grpc"))]
+	pub fn start(self) {
+		use util::LOGGER;
+
+		warn!(
+			LOGGER,
+			"grin-miner was built without gRPC support (the grpc feature), [grpc] ignored"
+		);
+	}
+}
+
+#[cfg(feature = "grpc")]
+mod proto {
+	pub mod grin_miner {
+		include!(concat!(env!("OUT_DIR"), "/grin_miner.rs"));
+	}
+	pub mod grin_miner_grpc {
+		include!(concat!(env!("OUT_DIR"), "/grin_miner_grpc.rs"));
+	}
+}
+
+#[cfg(feature = "grpc")]
+mod service {
+	use std::mem;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::{Arc, RwLock};
+	use std::thread;
+	use std::time::Duration;
+
+	use futures::sync::mpsc;
+	use futures::{Future, Sink, Stream};
+	use grpcio::{self, Environment, RpcContext, RpcStatus, RpcStatusCode, ServerBuilder,
+	             ServerStreamingSink, UnarySink, WriteFlags};
+	use serde_json::{self, Value};
+	use time;
+
+	use super::proto::grin_miner as pb;
+	use super::proto::grin_miner_grpc::{create_miner, Miner};
+	use config::GrpcConfig;
+	use control::Control;
+	use stats::{self, Stats};
+	use util::LOGGER;
+
+	/// Seconds between StreamStats updates, unless asked otherwise
+	const DEFAULT_STREAM_INTERVAL_SECS: u64 = 10;
+
+	/// StreamStats calls served at once, each has a thread
+	const MAX_STREAMS: usize = 16;
+
+	static STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+	/// Listen on the configured address, for the rest of the run
+	pub fn start(config: GrpcConfig, stats: Arc<RwLock<Stats>>, control: Control) {
+		let bind_address = config.bind_address.clone();
+		let (host, port) = match bind_address.rfind(':') {
+			Some(i) => (&bind_address[..i], bind_address[i + 1..].parse::<u16>().ok()),
+			None => (bind_address.as_str(), None),
+		};
+		let port = match port {
+			Some(p) => p,
+			None => {
+				error!(LOGGER, "Invalid gRPC bind_address {}", bind_address);
+				return;
+			}
+		};
+		let service = create_miner(MinerService {
+			config: config,
+			stats: stats,
+			control: control,
+		});
+		let server = ServerBuilder::new(Arc::new(Environment::new(1)))
+			.register_service(service)
+			.bind(host, port)
+			.build();
+		let mut server = match server {
+			Ok(s) => s,
+			Err(e) => {
+				error!(LOGGER, "Unable to start gRPC server on {}: {}", bind_address, e);
+				return;
+			}
+		};
+		server.start();
+		info!(LOGGER, "gRPC server listening on {}", bind_address);
+		// dropping the server would shut it down
+		mem::forget(server);
+	}
+
+	/// The stats as the Status message
+	fn status(stats: &Stats) -> pb::Status {
+		let mut status = pb::Status::new();
+		status.set_time(time::get_time().sec);
+		status.set_rig(stats.rig.name.clone().unwrap_or_default());
+		status.set_connected(stats.client_stats.connected);
+		status.set_connection_status(stats.client_stats.connection_status.clone());
+		status.set_mining_status(stats.mining_status().0);
+		status.set_paused(stats.mining_stats.paused);
+		status.set_block_height(stats.mining_stats.block_height);
+		status.set_target_difficulty(stats.mining_stats.target_difficulty);
+		status.set_combined_gps(stats.mining_stats.combined_gps);
+		status.set_uptime(stats.session_stats.uptime(stats.clock_now()));
+
+		let mut shares = pb::Shares::new();
+		shares.set_accepted(stats.client_stats.shares_accepted);
+		shares.set_rejected(stats.client_stats.shares_rejected);
+		shares.set_stale(stats.client_stats.shares_stale);
+		shares.set_blocks_found(stats.client_stats.blocks_found);
+		shares.set_solutions_found(stats.session_stats.solutions_found);
+		status.set_shares(shares);
+
+		status.set_alerts(
+			stats
+				.alerts
+				.iter()
+				.map(|a| {
+					let mut alert = pb::Alert::new();
+					alert.set_message(a.message.clone());
+					alert.set_duration(a.duration());
+					alert
+				})
+				.collect(),
+		);
+
+		let mut devices = vec![];
+		if let Some(ref plugins) = stats.mining_stats.device_stats {
+			for (i, plugin) in plugins.iter().enumerate() {
+				for d in plugin {
+					let mut device = pb::Device::new();
+					device.set_plugin(i as u32);
+					device.set_id(d.device_id.clone());
+					device.set_name(d.device_name.clone());
+					device.set_edge_bits(d.cuckoo_size as u32);
+					device.set_in_use(d.in_use != 0);
+					device.set_errored(d.has_errored != 0);
+					device.set_paused(d.device_id.parse::<u32>().ok().map_or(false, |id| {
+						stats.mining_stats.paused_devices.contains(&(i, id))
+					}));
+					device.set_gps(stats::device_gps(d));
+					if let Some(h) = stats.mining_stats.hardware_for(i, &d.device_id) {
+						device.set_temperature(h.temperature.unwrap_or(0));
+						device.set_fan_speed(h.fan_speed.unwrap_or(0));
+						device.set_power_usage(h.power_usage.unwrap_or(0.0));
+					}
+					devices.push(device);
+				}
+			}
+		}
+		status.set_devices(devices.into_iter().collect());
+		status
+	}
+
+	/// Control command parameters of a device request, none for the whole
+	/// miner
+	fn device_params(req: &pb::DeviceRequest) -> String {
+		if !req.has_device() {
+			return String::new();
+		}
+		let device = req.get_device();
+		json!({"plugin": device.get_plugin(), "device": device.get_device()}).to_string()
+	}
+
+	/// Empty strings are unset fields in proto3
+	fn non_empty(s: &str) -> Option<&str> {
+		if s.is_empty() {
+			None
+		} else {
+			Some(s)
+		}
+	}
+
+	#[derive(Clone)]
+	struct MinerService {
+		config: GrpcConfig,
+		stats: Arc<RwLock<Stats>>,
+		control: Control,
+	}
+
+	impl MinerService {
+		/// Control calls need the configured token as bearer token in the
+		/// authorization metadata, and are refused altogether if there's
+		/// no token configured
+		fn authorized(&self, ctx: &RpcContext) -> bool {
+			let token = match self.config.control_token {
+				Some(ref t) => t,
+				None => return false,
+			};
+			let expected = format!("Bearer {}", token);
+			ctx.request_headers()
+				.iter()
+				.any(|(name, value)| name == "authorization" && value == expected.as_bytes())
+		}
+
+		/// Run a control command and answer the call with its outcome
+		fn control(
+			&self,
+			ctx: RpcContext,
+			sink: UnarySink<pb::ControlReply>,
+			command: &str,
+			params: &str,
+		) {
+			if !self.authorized(&ctx) {
+				let status = RpcStatus::new(
+					RpcStatusCode::Unauthenticated,
+					Some("unauthorized".to_string()),
+				);
+				ctx.spawn(
+					sink.fail(status)
+						.map_err(|e| debug!(LOGGER, "gRPC reply error: {}", e)),
+				);
+				return;
+			}
+			let response = self.control.handle(command, params);
+			let mut reply = pb::ControlReply::new();
+			if response.code == 200 {
+				reply.set_ok(true);
+			} else {
+				let error = serde_json::from_str::<Value>(&response.body)
+					.ok()
+					.and_then(|b| b.get("error").and_then(|e| e.as_str()).map(|e| e.to_string()))
+					.unwrap_or(response.body);
+				reply.set_error(error);
+			}
+			ctx.spawn(
+				sink.success(reply)
+					.map_err(|e| debug!(LOGGER, "gRPC reply error: {}", e)),
+			);
+		}
+	}
+
+	impl Miner for MinerService {
+		fn get_status(
+			&mut self,
+			ctx: RpcContext,
+			_req: pb::GetStatusRequest,
+			sink: UnarySink<pb::Status>,
+		) {
+			let status = status(&self.stats.read().unwrap());
+			ctx.spawn(
+				sink.success(status)
+					.map_err(|e| debug!(LOGGER, "gRPC reply error: {}", e)),
+			);
+		}
+
+		fn stream_stats(
+			&mut self,
+			ctx: RpcContext,
+			req: pb::StreamStatsRequest,
+			sink: ServerStreamingSink<pb::Status>,
+		) {
+			if STREAMS.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMS {
+				STREAMS.fetch_sub(1, Ordering::SeqCst);
+				let status = RpcStatus::new(
+					RpcStatusCode::ResourceExhausted,
+					Some("too many streams".to_string()),
+				);
+				ctx.spawn(
+					sink.fail(status)
+						.map_err(|e| debug!(LOGGER, "gRPC reply error: {}", e)),
+				);
+				return;
+			}
+			let interval = match req.get_interval_secs() {
+				0 => DEFAULT_STREAM_INTERVAL_SECS,
+				i => i as u64,
+			};
+			let (tx, rx) = mpsc::channel(1);
+			let stats = self.stats.clone();
+			let spawned = thread::Builder::new()
+				.name("grpc_stream".to_string())
+				.spawn(move || {
+					let mut tx = tx;
+					loop {
+						let status = status(&stats.read().unwrap());
+						tx = match tx.send((status, WriteFlags::default())).wait() {
+							Ok(tx) => tx,
+							// the call is over
+							Err(_) => break,
+						};
+						thread::sleep(Duration::from_secs(interval));
+					}
+					STREAMS.fetch_sub(1, Ordering::SeqCst);
+				});
+			// the stream just ends if there's no thread to feed it
+			if spawned.is_err() {
+				STREAMS.fetch_sub(1, Ordering::SeqCst);
+			}
+			let updates = rx.map_err(|_| grpcio::Error::RemoteStopped);
+			ctx.spawn(
+				sink.send_all(updates)
+					.map(|_| ())
+					.map_err(|e| debug!(LOGGER, "gRPC stream closed: {}", e)),
+			);
+		}
+
+		fn pause_device(
+			&mut self,
+			ctx: RpcContext,
+			req: pb::DeviceRequest,
+			sink: UnarySink<pb::ControlReply>,
+		) {
+			self.control(ctx, sink, "pause", &device_params(&req));
+		}
+
+		fn resume_device(
+			&mut self,
+			ctx: RpcContext,
+			req: pb::DeviceRequest,
+			sink: UnarySink<pb::ControlReply>,
+		) {
+			self.control(ctx, sink, "resume", &device_params(&req));
+		}
+
+		fn switch_pool(
+			&mut self,
+			ctx: RpcContext,
+			req: pb::SwitchPoolRequest,
+			sink: UnarySink<pb::ControlReply>,
+		) {
+			let params = json!({
+				"url": req.get_url(),
+				"login": non_empty(req.get_login()),
+				"password": non_empty(req.get_password()),
+			});
+			self.control(ctx, sink, "pool", &params.to_string());
+		}
+	}
+}
@@ -0,0 +1,270 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GPU hardware monitoring, reading temperature, fan speed, power draw and
+//! clocks through NVML and matching them to the plugin devices
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use config::{HardwareMonitorConfig, MinerConfig};
+use plugin;
+use stats::{DeviceHardwareStats, Stats};
+use util::LOGGER;
+
+/// Readings of a single GPU
+#[derive(Clone, Debug)]
+pub struct GpuReadings {
+	/// PCI bus id, e.g. 00000000:01:00.0
+	pub pci_bus_id: String,
+	/// Device name
+	pub name: String,
+	/// Temperature, in degrees celsius
+	pub temperature: Option<u32>,
+	/// Fan speed, in percent
+	pub fan_speed: Option<u32>,
+	/// Power draw, in watts
+	pub power_usage: Option<f64>,
+	/// Core clock, in MHz
+	pub core_clock: Option<u32>,
+	/// Memory clock, in MHz
+	pub memory_clock: Option<u32>,
+}
+
+#[cfg(feature = "nvml")]
+mod source {
+	use nvml_wrapper::enum_wrappers::device::{Clock, TemperatureSensor};
+	use nvml_wrapper::NVML;
+
+	use super::GpuReadings;
+
+	/// Readings source, NVML
+	pub struct Source {
+		nvml: NVML,
+	}
+
+	impl Source {
+		/// Initialise NVML
+		pub fn new() -> Result<Source, String> {
+			NVML::init()
+				.map(|nvml| Source { nvml: nvml })
+				.map_err(|e| format!("Unable to initialise NVML: {}", e))
+		}
+
+		/// Readings of every GPU NVML can see
+		pub fn read(&self) -> Vec<GpuReadings> {
+			let count = self.nvml.device_count().unwrap_or(0);
+			(0..count)
+				.filter_map(|i| {
+					let device = self.nvml.device_by_index(i).ok()?;
+					Some(GpuReadings {
+						pci_bus_id: device.pci_info().ok()?.bus_id,
+						name: device.name().unwrap_or_default(),
+						temperature: device.temperature(TemperatureSensor::Gpu).ok(),
+						fan_speed: device.fan_speed().ok(),
+						power_usage: device.power_usage().ok().map(|mw| mw as f64 / 1000.0),
+						core_clock: device.clock_info(Clock::Graphics).ok(),
+						memory_clock: device.clock_info(Clock::Memory).ok(),
+					})
+				})
+				.collect()
+		}
+	}
+}
+
+#[cfg(not(feature = "nvml"))]
+mod source {
+	use super::GpuReadings;
+
+	/// Readings source, unavailable without NVML support
+	pub struct Source;
+
+	impl Source {
+		/// Always fails, there's nothing to read from
+		pub fn new() -> Result<Source, String> {
+			Err("grin-miner was built without NVML support (the nvml feature)".to_string())
+		}
+
+		/// No readings
+		pub fn read(&self) -> Vec<GpuReadings> {
+			vec![]
+		}
+	}
+}
+
+/// PCI vendor id of NVIDIA
+pub const PCI_VENDOR_NVIDIA: u16 = 0x10de;
+
+/// PCI vendor id of AMD
+pub const PCI_VENDOR_AMD: u16 = 0x1002;
+
+/// Number CUDA devices in PCI bus order, as NVML, nvidia-smi and OpenCL
+/// drivers do, so devices addressed by PCI bus id can be matched to plugin
+/// device ids. Only if the config addresses some that way, it renumbers
+/// the devices the device ids of device_parameters refer to otherwise.
+/// Must be called before the plugins are loaded, and leaves an order set
+/// by the user alone.
+pub fn prepare(config: &MinerConfig) {
+	let uses_pci_ids = config.miner_plugin_config.iter().any(|p| p.uses_pci_ids());
+	if uses_pci_ids && env::var_os("CUDA_DEVICE_ORDER").is_none() {
+		env::set_var("CUDA_DEVICE_ORDER", "PCI_BUS_ID");
+	}
+}
+
+/// Whether CUDA devices are numbered in PCI bus order
+pub fn cuda_pci_order() -> bool {
+	env::var("CUDA_DEVICE_ORDER")
+		.map(|o| o == "PCI_BUS_ID")
+		.unwrap_or(false)
+}
+
+/// PCI bus ids of the display controllers of the given vendors, in bus
+/// order. Empty where sysfs isn't available.
+pub fn gpu_pci_bus_ids(vendors: &[u16]) -> Vec<String> {
+	let read = |path: PathBuf| {
+		fs::read_to_string(path)
+			.ok()
+			.and_then(|s| u32::from_str_radix(s.trim().trim_start_matches("0x"), 16).ok())
+	};
+	let entries = match fs::read_dir("/sys/bus/pci/devices") {
+		Ok(e) => e,
+		Err(_) => return vec![],
+	};
+	let mut ids: Vec<String> = entries
+		.filter_map(|e| e.ok())
+		.filter(|e| {
+			let vendor = read(e.path().join("vendor"));
+			let class = read(e.path().join("class"));
+			vendor.map_or(false, |v| vendors.iter().any(|w| *w as u32 == v))
+				&& class.map_or(false, |c| c >> 16 == 0x03)
+		})
+		.map(|e| e.file_name().to_string_lossy().into_owned())
+		.collect();
+	ids.sort();
+	ids
+}
+
+/// Reset an NVIDIA GPU, which needs root and nothing using the GPU
+pub fn reset_gpu(bus_id: &str) -> Result<(), String> {
+	let output = Command::new("nvidia-smi")
+		.args(&["--gpu-reset", "-i", bus_id])
+		.output()
+		.map_err(|e| format!("unable to run nvidia-smi: {}", e))?;
+	if output.status.success() {
+		Ok(())
+	} else {
+		Err(String::from_utf8_lossy(&output.stdout).trim().to_string())
+	}
+}
+
+/// Match GPU readings, sorted by PCI bus id, to the devices of the cuda
+/// and ocl plugins: the nth device of a plugin is the GPU nth in PCI bus
+/// order, provided the names agree
+fn match_devices(stats: &Stats, plugins: &[String], gpus: &[GpuReadings]) -> Vec<DeviceHardwareStats> {
+	let mut matched = vec![];
+	let device_stats = match stats.mining_stats.device_stats {
+		Some(ref d) => d,
+		None => return matched,
+	};
+	for (i, devices) in device_stats.iter().enumerate() {
+		let is_gpu_plugin = plugins
+			.get(i)
+			.map(|p| p.contains("cuda") || p.contains("ocl"))
+			.unwrap_or(false);
+		if !is_gpu_plugin {
+			continue;
+		}
+		for d in devices {
+			let gpu = match d.device_id.parse::<usize>().ok().and_then(|n| gpus.get(n)) {
+				Some(g) => g,
+				None => continue,
+			};
+			if !d.device_name.is_empty()
+				&& !gpu.name.is_empty()
+				&& !d.device_name.contains(&gpu.name)
+				&& !gpu.name.contains(&d.device_name)
+			{
+				continue;
+			}
+			matched.push(DeviceHardwareStats {
+				plugin: i,
+				device_id: d.device_id.clone(),
+				temperature: gpu.temperature,
+				fan_speed: gpu.fan_speed,
+				power_usage: gpu.power_usage,
+				core_clock: gpu.core_clock,
+				memory_clock: gpu.memory_clock,
+				pci_bus_id: Some(gpu.pci_bus_id.clone()),
+			});
+		}
+	}
+	matched
+}
+
+/// Polls GPU readings into the mining stats
+pub struct HardwareMonitor {
+	config: HardwareMonitorConfig,
+	stats: Arc<RwLock<Stats>>,
+}
+
+impl HardwareMonitor {
+	/// Create a new monitor
+	pub fn new(config: HardwareMonitorConfig, stats: Arc<RwLock<Stats>>) -> HardwareMonitor {
+		HardwareMonitor {
+			config: config,
+			stats: stats,
+		}
+	}
+
+	/// Start polling in a new thread
+	pub fn start(self) {
+		let _ = thread::Builder::new()
+			.name("hardware_monitor".to_string())
+			.spawn(move || {
+				let source = match source::Source::new() {
+					Ok(s) => s,
+					Err(e) => {
+						warn!(LOGGER, "Hardware monitoring unavailable: {}", e);
+						return;
+					}
+				};
+				let mut gpus = source.read();
+				info!(
+					LOGGER,
+					"Monitoring {} GPUs: {}",
+					gpus.len(),
+					gpus.iter()
+						.map(|g| format!("{} ({})", g.name, g.pci_bus_id))
+						.collect::<Vec<String>>()
+						.join(", ")
+				);
+				loop {
+					gpus.sort_by(|a, b| a.pci_bus_id.cmp(&b.pci_bus_id));
+					let plugins = plugin::loaded_plugins();
+					{
+						let mut stats = self.stats.write().unwrap();
+						let matched = match_devices(&stats, &plugins, &gpus);
+						stats.mining_stats.hardware_stats = matched;
+					}
+					thread::sleep(Duration::from_secs(self.config.poll_interval_secs.max(1)));
+					gpus = source.read();
+				}
+			});
+	}
+}
@@ -0,0 +1,172 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal blocking HTTP(S) client, for pushing data (notifications,
+//! telemetry) to external services, and reading a miner's API
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use native_tls::TlsConnector;
+
+/// Timeout for connecting, reading and writing
+const TIMEOUT_SECS: u64 = 10;
+
+/// Parts of an http(s) url
+struct Url {
+	tls: bool,
+	host: String,
+	port: u16,
+	path: String,
+}
+
+fn parse_url(url: &str) -> Result<Url, String> {
+	let (tls, rest) = if url.starts_with("https://") {
+		(true, &url["https://".len()..])
+	} else if url.starts_with("http://") {
+		(false, &url["http://".len()..])
+	} else {
+		return Err(format!("Unsupported url: {}", url));
+	};
+	let (authority, path) = match rest.find('/') {
+		Some(i) => (&rest[..i], &rest[i..]),
+		None => (rest, "/"),
+	};
+	let (host, port) = match authority.rfind(':') {
+		Some(i) => (
+			&authority[..i],
+			authority[i + 1..]
+				.parse::<u16>()
+				.map_err(|_| format!("Invalid port in url: {}", url))?,
+		),
+		None => (authority, if tls { 443 } else { 80 }),
+	};
+	Ok(Url {
+		tls: tls,
+		host: host.to_string(),
+		port: port,
+		path: path.to_string(),
+	})
+}
+
+/// Send the request, returning the response status code, and the body if
+/// wanted
+fn send<S: Read + Write>(
+	mut stream: S,
+	req: &str,
+	read_body: bool,
+) -> Result<(u16, Vec<u8>), String> {
+	stream
+		.write_all(req.as_bytes())
+		.map_err(|e| format!("{}", e))?;
+	let mut reader = BufReader::new(stream);
+	let mut status_line = String::new();
+	reader
+		.read_line(&mut status_line)
+		.map_err(|e| format!("{}", e))?;
+	// e.g. HTTP/1.1 200 OK
+	let code = status_line
+		.split_whitespace()
+		.nth(1)
+		.and_then(|c| c.parse::<u16>().ok())
+		.ok_or(format!("Invalid response: {}", status_line.trim()))?;
+	let mut body = vec![];
+	if read_body {
+		// the connection is closed after the response, so the body is
+		// whatever follows the headers
+		loop {
+			let mut header = String::new();
+			if reader.read_line(&mut header).map_err(|e| format!("{}", e))? == 0
+				|| header.trim().is_empty()
+			{
+				break;
+			}
+		}
+		reader
+			.read_to_end(&mut body)
+			.map_err(|e| format!("{}", e))?;
+	}
+	Ok((code, body))
+}
+
+/// Connect to the url and send the request for it
+fn request(url: &Url, req: &str, read_body: bool) -> Result<(u16, Vec<u8>), String> {
+	let addr = (url.host.as_str(), url.port)
+		.to_socket_addrs()
+		.map_err(|e| format!("{}", e))?
+		.next()
+		.ok_or(format!("Unable to resolve {}", url.host))?;
+	let timeout = Duration::from_secs(TIMEOUT_SECS);
+	let stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| format!("{}", e))?;
+	let _ = stream.set_read_timeout(Some(timeout));
+	let _ = stream.set_write_timeout(Some(timeout));
+	if url.tls {
+		let connector = TlsConnector::new().map_err(|e| format!("{}", e))?;
+		let stream = connector
+			.connect(&url.host, stream)
+			.map_err(|e| format!("{}", e))?;
+		send(stream, req, read_body)
+	} else {
+		send(stream, req, read_body)
+	}
+}
+
+/// POST the body to the url, returning the response status code
+pub fn post(url: &str, content_type: &str, body: &str) -> Result<u16, String> {
+	let url = parse_url(url)?;
+	let req = format!(
+		"POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		url.path,
+		url.host,
+		content_type,
+		body.len(),
+		body
+	);
+	request(&url, &req, false).map(|(code, _)| code)
+}
+
+/// POST the JSON body to the url, returning the response status code and
+/// body
+pub fn post_json(url: &str, body: &str) -> Result<(u16, String), String> {
+	let url = parse_url(url)?;
+	let req = format!(
+		"POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		url.path,
+		url.host,
+		body.len(),
+		body
+	);
+	let (code, body) = request(&url, &req, true)?;
+	Ok((code, String::from_utf8_lossy(&body).into_owned()))
+}
+
+/// GET the url, with the Authorization header given if any, returning
+/// the response status code and body
+pub fn get(url: &str, authorization: Option<&str>) -> Result<(u16, String), String> {
+	let (code, body) = get_bytes(url, authorization)?;
+	Ok((code, String::from_utf8_lossy(&body).into_owned()))
+}
+
+/// GET the url as get does, with the body as is, e.g. for downloads
+pub fn get_bytes(url: &str, authorization: Option<&str>) -> Result<(u16, Vec<u8>), String> {
+	let url = parse_url(url)?;
+	let req = format!(
+		"GET {} HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+		url.path,
+		url.host,
+		authorization.map_or(String::new(), |a| format!("Authorization: {}\r\n", a))
+	);
+	request(&url, &req, true)
+}
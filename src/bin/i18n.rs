@@ -0,0 +1,177 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translations of the TUI, dashboard and console messages. Messages are
+//! looked up by key, e.g. menu.mining, in the catalog of the configured
+//! language: a locale file (locales/<language>.toml) with a table per
+//! section, over the English catalog built in for any message it leaves
+//! out. Placeholders ({}) are filled in order.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use toml;
+
+use config::MinerConfig;
+
+/// The English catalog, which locale files translate
+const ENGLISH: &str = include_str!("../../locales/en.toml");
+
+lazy_static! {
+	static ref CATALOG: RwLock<HashMap<String, String>> = RwLock::new(english());
+}
+
+fn english() -> HashMap<String, String> {
+	parse(ENGLISH).expect("Invalid built in English catalog")
+}
+
+/// Messages of a locale file, by section.key
+fn parse(text: &str) -> Result<HashMap<String, String>, String> {
+	let value: toml::Value = toml::from_str(text).map_err(|e| e.to_string())?;
+	let sections = value
+		.as_table()
+		.ok_or("expected a table per section".to_string())?;
+	let mut messages = HashMap::new();
+	for (section, entries) in sections {
+		let entries = entries
+			.as_table()
+			.ok_or(format!("{} is not a table of messages", section))?;
+		for (key, text) in entries {
+			let text = text
+				.as_str()
+				.ok_or(format!("{}.{} is not a string", section, key))?;
+			messages.insert(format!("{}.{}", section, key), text.to_string());
+		}
+	}
+	Ok(messages)
+}
+
+/// Language to use: the configured one, or with auto the one of the
+/// environment's locale (LC_ALL, LC_MESSAGES or LANG), e.g. de for
+/// de_DE.UTF-8
+fn language(config: &MinerConfig) -> Option<String> {
+	let language = config.language.as_ref()?.trim().to_lowercase();
+	if language != "auto" {
+		return Some(language);
+	}
+	["LC_ALL", "LC_MESSAGES", "LANG"]
+		.iter()
+		.filter_map(|v| env::var(v).ok())
+		.find(|l| !l.is_empty())
+		.map(|l| {
+			l.split(|c| c == '_' || c == '.' || c == '@')
+				.next()
+				.unwrap_or("")
+				.to_lowercase()
+		})
+}
+
+/// Directories the locale files are looked for in: locale_dir, then the
+/// locales directory next to the executable and in the working directory
+fn locale_dirs(config: &MinerConfig) -> Vec<PathBuf> {
+	let mut dirs: Vec<PathBuf> = config.locale_dir.iter().map(PathBuf::from).collect();
+	if let Ok(mut exe_path) = env::current_exe() {
+		exe_path.pop();
+		dirs.push(exe_path.join("locales"));
+	}
+	dirs.push(PathBuf::from("locales"));
+	dirs
+}
+
+/// Load the configured language's catalog over the English one. Returns
+/// the language and how many of the English messages it translates, none
+/// for English.
+pub fn init(config: &MinerConfig) -> Result<Option<(String, usize)>, String> {
+	let language = match language(config) {
+		Some(ref l) if l == "en" || l == "c" || l == "posix" || l.is_empty() => return Ok(None),
+		Some(l) => l,
+		None => return Ok(None),
+	};
+	let file_name = format!("{}.toml", language);
+	let path = locale_dirs(config)
+		.into_iter()
+		.map(|d| d.join(&file_name))
+		.find(|p| p.is_file())
+		.ok_or(format!("No locale file {} found", file_name))?;
+	let mut text = String::new();
+	File::open(&path)
+		.and_then(|mut f| f.read_to_string(&mut text))
+		.map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+	let messages =
+		parse(&text).map_err(|e| format!("Invalid locale file {}: {}", path.display(), e))?;
+	let mut catalog = CATALOG.write().unwrap();
+	let translated = messages.keys().filter(|k| catalog.contains_key(*k)).count();
+	for (key, text) in messages {
+		// keys the miner doesn't know are left out, they'd never show
+		if catalog.contains_key(&key) {
+			catalog.insert(key, text);
+		}
+	}
+	Ok(Some((language, translated)))
+}
+
+/// Number of messages there are to translate
+pub fn message_count() -> usize {
+	english().len()
+}
+
+/// Text of a message, the key itself if there's no such message
+pub fn t(key: &str) -> String {
+	CATALOG
+		.read()
+		.unwrap()
+		.get(key)
+		.cloned()
+		.unwrap_or(key.to_string())
+}
+
+/// Text of a message with its placeholders filled in with the arguments,
+/// in order
+pub fn tf(key: &str, args: &[&dyn fmt::Display]) -> String {
+	let text = t(key);
+	let mut out = String::with_capacity(text.len());
+	let mut args = args.iter();
+	let mut rest = text.as_str();
+	while let Some(i) = rest.find("{}") {
+		out.push_str(&rest[..i]);
+		match args.next() {
+			Some(a) => out.push_str(&a.to_string()),
+			None => out.push_str("{}"),
+		}
+		rest = &rest[i + 2..];
+	}
+	out.push_str(rest);
+	out
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn english_catalog() {
+		let english = english();
+		assert_eq!(english.get("menu.mining").map(|s| s.as_str()), Some("Mining"));
+		assert_eq!(t("no.such_message"), "no.such_message");
+		assert_eq!(
+			tf("dashboard.alert", &[&"No shares", &30]),
+			"ALERT: No shares (for 30s)"
+		);
+	}
+}
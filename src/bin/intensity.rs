@@ -0,0 +1,289 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Intensity presets, the low, medium and high parameter sets of each
+//! plugin and GPU generation, filled in under a device's own parameters
+
+use std::collections::HashMap;
+
+use auto::{self, Gpu};
+use config::types::CuckooMinerPluginConfig;
+use util::cuckoo_miner::CuckooPluginCapabilities;
+use util::LOGGER;
+
+/// Intensity levels
+pub const LEVELS: &[&str] = &["low", "medium", "high"];
+
+/// Parameter sets by plugin type filter, GPU generation ("any" matching
+/// every GPU, listed after the specific ones) and level
+const PRESETS: &[(&str, &str, &str, &[(&str, u32)])] = &[
+	// mean CUDA solver; medium is the plugin's own defaults
+	(
+		"cuckatoo_cuda",
+		"pascal",
+		"high",
+		&[
+			("EXPAND", 0),
+			("N_TRIMS", 176),
+			("GEN_A_BLOCKS", 4096),
+			("GEN_A_TPB", 256),
+			("GEN_B_TPB", 128),
+			("TRIM_TPB", 512),
+			("TAIL_TPB", 1024),
+			("RECOVER_BLOCKS", 2048),
+			("RECOVER_TPB", 256),
+		],
+	),
+	(
+		"cuckatoo_cuda",
+		"turing",
+		"high",
+		&[
+			("EXPAND", 0),
+			("N_TRIMS", 176),
+			("GEN_A_BLOCKS", 8192),
+			("GEN_A_TPB", 256),
+			("GEN_B_TPB", 256),
+			("TRIM_TPB", 512),
+			("TAIL_TPB", 1024),
+			("RECOVER_BLOCKS", 2048),
+			("RECOVER_TPB", 512),
+		],
+	),
+	(
+		"cuckatoo_cuda",
+		"ampere",
+		"high",
+		&[
+			("EXPAND", 0),
+			("N_TRIMS", 176),
+			("GEN_A_BLOCKS", 8192),
+			("GEN_A_TPB", 512),
+			("GEN_B_TPB", 256),
+			("TRIM_TPB", 1024),
+			("TAIL_TPB", 1024),
+			("RECOVER_BLOCKS", 2048),
+			("RECOVER_TPB", 512),
+		],
+	),
+	(
+		"cuckatoo_cuda",
+		"any",
+		"high",
+		&[
+			("EXPAND", 0),
+			("N_TRIMS", 176),
+			("GEN_A_BLOCKS", 4096),
+			("GEN_A_TPB", 256),
+			("GEN_B_TPB", 256),
+			("TRIM_TPB", 512),
+			("TAIL_TPB", 1024),
+			("RECOVER_BLOCKS", 2048),
+			("RECOVER_TPB", 512),
+		],
+	),
+	(
+		"cuckatoo_cuda",
+		"any",
+		"medium",
+		&[
+			("EXPAND", 0),
+			("N_TRIMS", 176),
+			("GEN_A_BLOCKS", 4096),
+			("GEN_A_TPB", 256),
+			("GEN_B_TPB", 128),
+			("TRIM_TPB", 512),
+			("TAIL_TPB", 1024),
+			("RECOVER_BLOCKS", 1024),
+			("RECOVER_TPB", 1024),
+		],
+	),
+	// fewer, smaller blocks and expanded edges, to leave the GPU (and its
+	// memory) usable for a display or other work
+	(
+		"cuckatoo_cuda",
+		"any",
+		"low",
+		&[
+			("EXPAND", 1),
+			("N_TRIMS", 176),
+			("GEN_A_BLOCKS", 1024),
+			("GEN_A_TPB", 128),
+			("GEN_B_TPB", 64),
+			("TRIM_TPB", 256),
+			("TAIL_TPB", 256),
+			("RECOVER_BLOCKS", 512),
+			("RECOVER_TPB", 256),
+		],
+	),
+	("cuckatoo_lean_cuda", "any", "high", &[("N_BLOCKS", 256), ("TPB", 512)]),
+	("cuckatoo_lean_cuda", "any", "medium", &[("N_BLOCKS", 128), ("TPB", 256)]),
+	("cuckatoo_lean_cuda", "any", "low", &[("N_BLOCKS", 32), ("TPB", 128)]),
+	// OpenCL solver, mostly run on AMD GPUs
+	(
+		"cuckatoo_ocl",
+		"any",
+		"high",
+		&[("EXPAND", 0), ("N_TRIMS", 176), ("GEN_A_BLOCKS", 4096), ("TRIM_TPB", 512)],
+	),
+	(
+		"cuckatoo_ocl",
+		"any",
+		"medium",
+		&[("EXPAND", 0), ("N_TRIMS", 176), ("GEN_A_BLOCKS", 2048), ("TRIM_TPB", 256)],
+	),
+	(
+		"cuckatoo_ocl",
+		"any",
+		"low",
+		&[("EXPAND", 1), ("N_TRIMS", 176), ("GEN_A_BLOCKS", 1024), ("TRIM_TPB", 128)],
+	),
+];
+
+/// Check an intensity setting is one of the levels
+pub fn validate(intensity: &str) -> Result<(), String> {
+	if LEVELS.contains(&intensity.trim()) {
+		Ok(())
+	} else {
+		Err(format!(
+			"Invalid intensity {}, expected one of {}",
+			intensity,
+			LEVELS.join(", ")
+		))
+	}
+}
+
+/// Generation of an NVIDIA GPU, from its name
+fn generation(name: &str) -> &'static str {
+	let name = name.to_uppercase();
+	let has = |patterns: &[&str]| patterns.iter().any(|p| name.contains(p));
+	if has(&["RTX 30", "RTX 40", "RTX A", " A10", " A40", "A100", " L4", " L40"]) {
+		"ampere"
+	} else if has(&["RTX 20", "GTX 16", "TITAN RTX", "QUADRO RTX", " T4"]) {
+		"turing"
+	} else if has(&["GTX 10", "TITAN X", "TITAN XP", " P40", " P100", "P10"]) {
+		"pascal"
+	} else {
+		"any"
+	}
+}
+
+/// Parameter set of a plugin at the given level on a GPU of the given
+/// generation
+fn preset(type_filter: &str, generation: &str, level: &str) -> Option<&'static [(&'static str, u32)]> {
+	PRESETS
+		.iter()
+		.find(|&&(t, g, l, _)| t == type_filter && (g == generation || g == "any") && l == level)
+		.map(|&(_, _, _, params)| params)
+}
+
+/// Parameters for CPU plugins: threads by level
+fn cpu_preset(level: &str) -> Vec<(&'static str, u32)> {
+	let threads = auto::cpu_threads() as u32;
+	let threads = match level {
+		"low" => (threads / 4).max(1),
+		"medium" => (threads / 2).max(1),
+		_ => threads,
+	};
+	vec![("NUM_THREADS", threads)]
+}
+
+/// Fill in the parameters of the plugin's intensity preset on each device
+/// it mines on, configured or found, where its device_parameters don't
+/// set them. Parameters the
+/// plugin doesn't have are left out, and values clamped to its range.
+pub fn apply(
+	index: usize,
+	config: &mut CuckooMinerPluginConfig,
+	caps: &CuckooPluginCapabilities,
+) -> Result<(), String> {
+	let level = match config.intensity {
+		Some(ref i) => i.trim().to_lowercase(),
+		None => return Ok(()),
+	};
+	validate(&level)?;
+	let is_gpu = config.type_filter.contains("cuda") || config.is_opencl();
+	let gpus: Vec<Gpu> = if config.type_filter.contains("cuda") {
+		auto::cuda_gpus()
+	} else if config.is_opencl() {
+		let platform = config.platform.as_ref().map(|p| p.trim().to_lowercase());
+		auto::opencl_gpus()
+			.into_iter()
+			.filter(|g| platform.is_none() || g.platform == platform)
+			.collect()
+	} else {
+		vec![]
+	};
+	let mut devices = config.device_parameters.take().unwrap_or_default();
+	// every GPU found mines, but those deselected
+	for g in &gpus {
+		devices.entry(g.device.to_string()).or_insert_with(HashMap::new);
+	}
+	if devices.is_empty() {
+		devices.insert("0".to_string(), HashMap::new());
+	}
+	for (device, params) in devices.iter_mut() {
+		if params.get("USE_DEVICE") == Some(&0) {
+			continue;
+		}
+		let gpu = device
+			.parse::<u32>()
+			.ok()
+			.and_then(|id| gpus.iter().find(|g| g.device == id));
+		let generation = gpu.map_or("any", |g| generation(&g.name));
+		let preset = if !is_gpu {
+			cpu_preset(&level)
+		} else {
+			match preset(&config.type_filter, generation, &level) {
+				Some(p) => p.to_vec(),
+				None => {
+					warn!(
+						LOGGER,
+						"Cuckoo plugin {} device {}: no {} intensity preset for {}, using its own parameters",
+						index, device, level, config.type_filter
+					);
+					continue;
+				}
+			}
+		};
+		let mut applied = vec![];
+		for (name, value) in preset {
+			let param = match caps.parameters.iter().find(|p| p.name == name) {
+				Some(p) => p,
+				None => continue,
+			};
+			if params.contains_key(name) {
+				continue;
+			}
+			let value = value.max(param.min_value).min(param.max_value);
+			params.insert(name.to_string(), value);
+			applied.push(format!("{}={}", name, value));
+		}
+		info!(
+			LOGGER,
+			"Cuckoo plugin {} device {}: {} intensity{}: {}",
+			index,
+			device,
+			level,
+			gpu.map_or(String::new(), |g| format!(" for {} ({})", g.name, generation)),
+			if applied.is_empty() {
+				"no parameters left to set".to_string()
+			} else {
+				applied.join(", ")
+			}
+		);
+	}
+	config.device_parameters = Some(devices);
+	Ok(())
+}
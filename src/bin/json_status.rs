@@ -0,0 +1,166 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Machine readable status output (`--output json`): what the TUI shows,
+//! printed periodically to stdout as one JSON object per line, for
+//! scripts wrapping the miner. Fields are only ever added to the schema;
+//! removing or changing one bumps `schema`.
+
+use std::sync::{Arc, RwLock};
+use std::{self, thread};
+
+use serde_json;
+use time;
+
+use stats::{self, Stats};
+
+/// Version of the status line schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Default number of seconds between status lines
+pub const DEFAULT_INTERVAL: u64 = 10;
+
+/// Output format selecting this mode
+pub const FORMAT: &str = "json";
+
+#[derive(Serialize)]
+struct StatusLine<'a> {
+	schema: u32,
+	/// Seconds since epoch
+	time: i64,
+	connection: Connection<'a>,
+	mining: Mining<'a>,
+	shares: Shares,
+	alerts: Vec<AlertLine<'a>>,
+	devices: Vec<Device<'a>>,
+}
+
+#[derive(Serialize)]
+struct Connection<'a> {
+	server_url: &'a str,
+	connected: bool,
+	logged_in: bool,
+	status: &'a str,
+	last_message_received: &'a str,
+}
+
+#[derive(Serialize)]
+struct Mining<'a> {
+	status: String,
+	paused: bool,
+	block_height: u64,
+	target_difficulty: u64,
+	combined_gps: f64,
+	algorithm_gps: &'a Vec<(String, f64)>,
+	uptime: i64,
+}
+
+#[derive(Serialize)]
+struct Shares {
+	accepted: u64,
+	rejected: u64,
+	stale: u64,
+	blocks_found: u64,
+	solutions_found: u64,
+}
+
+#[derive(Serialize)]
+struct AlertLine<'a> {
+	message: &'a str,
+	duration: i64,
+}
+
+#[derive(Serialize)]
+struct Device<'a> {
+	plugin: usize,
+	id: &'a str,
+	name: &'a str,
+	edge_bits: u32,
+	in_use: bool,
+	errored: bool,
+	/// Seconds, 0 until the first graph
+	graph_time: f64,
+	gps: f64,
+}
+
+/// The status line for the given stats
+pub fn status_line(stats: &Stats) -> String {
+	let mut devices = vec![];
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		for (i, plugin) in plugins.iter().enumerate() {
+			for d in plugin {
+				let graph_time = d.last_solution_time as f64 / 1000000000.0;
+				devices.push(Device {
+					plugin: i,
+					id: &d.device_id,
+					name: &d.device_name,
+					edge_bits: d.cuckoo_size as u32,
+					in_use: d.in_use != 0,
+					errored: d.has_errored != 0,
+					graph_time: graph_time,
+					gps: stats::device_gps(d),
+				});
+			}
+		}
+	}
+	let line = StatusLine {
+		schema: SCHEMA_VERSION,
+		time: time::get_time().sec,
+		connection: Connection {
+			server_url: &stats.client_stats.server_url,
+			connected: stats.client_stats.connected,
+			logged_in: stats.client_stats.logged_in,
+			status: &stats.client_stats.connection_status,
+			last_message_received: &stats.client_stats.last_message_received,
+		},
+		mining: Mining {
+			status: stats.mining_status().0,
+			paused: stats.mining_stats.paused,
+			block_height: stats.mining_stats.block_height,
+			target_difficulty: stats.mining_stats.target_difficulty,
+			combined_gps: stats.mining_stats.combined_gps,
+			algorithm_gps: &stats.mining_stats.algorithm_gps,
+			uptime: stats.session_stats.uptime(stats.clock_now()),
+		},
+		shares: Shares {
+			accepted: stats.client_stats.shares_accepted,
+			rejected: stats.client_stats.shares_rejected,
+			stale: stats.client_stats.shares_stale,
+			blocks_found: stats.client_stats.blocks_found,
+			solutions_found: stats.session_stats.solutions_found,
+		},
+		alerts: stats
+			.alerts
+			.iter()
+			.map(|a| AlertLine {
+				message: &a.message,
+				duration: a.duration(),
+			})
+			.collect(),
+		devices: devices,
+	};
+	serde_json::to_string(&line).unwrap()
+}
+
+/// Print a status line every `interval` seconds, forever
+pub fn run(stats: Arc<RwLock<Stats>>, interval: u64) {
+	loop {
+		thread::sleep(std::time::Duration::from_secs(interval.max(1)));
+		let line = {
+			let stats = stats.read().unwrap();
+			status_line(&stats)
+		};
+		println!("{}", line);
+	}
+}
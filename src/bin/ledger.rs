@@ -0,0 +1,265 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ledger of every share submitted and the pool's answer, one JSON object
+//! per line, to reconcile against the shares a pool reports. Each entry is
+//! signed together with the signature of the one before, so entries
+//! edited, dropped or reordered afterwards break the chain: with
+//! HMAC-SHA256 and share_ledger_key if set, SHA-256 otherwise.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use serde_json;
+use sha2::{Digest, Sha256};
+use time;
+
+use signing;
+use util::{self, LOGGER};
+
+/// Signature the first entry is chained to
+const GENESIS: &str = "";
+
+/// A share submitted and what became of it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+	/// Position in the ledger, from 1
+	pub seq: u64,
+	/// When the pool answered, or the connection was lost (seconds since
+	/// epoch)
+	pub time: i64,
+	pub pool: String,
+	pub login: String,
+	pub height: u64,
+	pub job_id: u64,
+	pub nonce: u64,
+	pub edge_bits: u32,
+	/// Share difficulty of the job, the difficulty the pool credits
+	pub difficulty: u64,
+	/// accepted, block, rejected, or unanswered if the connection was
+	/// lost first
+	pub result: String,
+	/// The pool's answer as it sent it, its result or error
+	pub response: String,
+	/// Signature of the entry, chained to the one before
+	pub signature: String,
+}
+
+impl LedgerEntry {
+	/// What's signed of the entry: the previous entry's signature and
+	/// every other field
+	fn message(&self, previous: &str) -> String {
+		format!(
+			"{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+			previous,
+			self.seq,
+			self.time,
+			self.pool,
+			self.login,
+			self.height,
+			self.job_id,
+			self.nonce,
+			self.edge_bits,
+			self.difficulty,
+			self.result,
+			self.response
+		)
+	}
+}
+
+fn sign(key: Option<&str>, message: &str) -> String {
+	match key {
+		Some(k) => signing::sign(k, message),
+		None => util::to_hex(Sha256::digest(message.as_bytes()).to_vec()),
+	}
+}
+
+/// Read the entries of a ledger, none if there's no file
+pub fn load(path: &str) -> Result<Vec<LedgerEntry>, String> {
+	let file = match File::open(path) {
+		Ok(f) => f,
+		Err(_) => return Ok(vec![]),
+	};
+	let mut entries = vec![];
+	for (i, line) in BufReader::new(file).lines().enumerate() {
+		let line = line.map_err(|e| format!("Unable to read {}: {}", path, e))?;
+		if line.trim().is_empty() {
+			continue;
+		}
+		let entry = serde_json::from_str(&line)
+			.map_err(|e| format!("Line {} of {} is not a ledger entry: {}", i + 1, path, e))?;
+		entries.push(entry);
+	}
+	Ok(entries)
+}
+
+/// Check the chain of signatures, failing at the first entry that doesn't
+/// match
+pub fn verify(entries: &[LedgerEntry], key: Option<&str>) -> Result<(), String> {
+	let mut previous = GENESIS.to_string();
+	for (i, e) in entries.iter().enumerate() {
+		if e.seq != i as u64 + 1 {
+			return Err(format!("entry {} is numbered {}, entries are missing", i + 1, e.seq));
+		}
+		if sign(key, &e.message(&previous)) != e.signature {
+			return Err(format!("the signature of entry {} doesn't match", e.seq));
+		}
+		previous = e.signature.clone();
+	}
+	Ok(())
+}
+
+/// Appends the shares answered to the ledger file
+pub struct ShareLedger {
+	path: String,
+	key: Option<String>,
+	/// Position and signature of the last entry
+	seq: u64,
+	last_signature: String,
+	/// Why nothing can be recorded, if the file is unreadable and couldn't
+	/// be moved aside
+	unusable: Option<String>,
+}
+
+impl ShareLedger {
+	/// Carry on the ledger at the given path, chaining to its last entry.
+	/// An unreadable ledger is moved aside and started over, appending to
+	/// it would never verify.
+	pub fn open(path: &str, key: Option<String>) -> ShareLedger {
+		let mut unusable = None;
+		let (seq, last_signature) = match load(path) {
+			Ok(entries) => entries
+				.last()
+				.map_or((0, GENESIS.to_string()), |e| (e.seq, e.signature.clone())),
+			Err(e) => {
+				let aside = format!("{}.{}.bad", path, time::get_time().sec);
+				match fs::rename(path, &aside) {
+					Ok(()) => error!(
+						LOGGER,
+						"{}, moved to {}, starting the share ledger over", e, aside
+					),
+					Err(re) => {
+						error!(
+							LOGGER,
+							"{}, unable to move it aside ({}), not recording shares", e, re
+						);
+						unusable = Some(e);
+					}
+				}
+				(0, GENESIS.to_string())
+			}
+		};
+		ShareLedger {
+			path: path.to_string(),
+			key: key,
+			seq: seq,
+			last_signature: last_signature,
+			unusable: unusable,
+		}
+	}
+
+	/// Sign the entry, numbering it next, and add it to the file
+	pub fn record(&mut self, mut entry: LedgerEntry) -> Result<(), String> {
+		if let Some(ref e) = self.unusable {
+			return Err(e.clone());
+		}
+		entry.seq = self.seq + 1;
+		entry.signature = sign(
+			self.key.as_ref().map(|k| k.as_str()),
+			&entry.message(&self.last_signature),
+		);
+		let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+		OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(&self.path)
+			.and_then(|mut f| writeln!(f, "{}", line))
+			.map_err(|e| format!("Unable to write {}: {}", self.path, e))?;
+		self.seq = entry.seq;
+		self.last_signature = entry.signature;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn chain(key: Option<&str>, count: u64) -> Vec<LedgerEntry> {
+		let mut previous = GENESIS.to_string();
+		(1..count + 1)
+			.map(|seq| {
+				let mut e = LedgerEntry {
+					seq: seq,
+					time: 1_500_000_000 + seq as i64,
+					pool: "127.0.0.1:3416".to_string(),
+					login: "miner".to_string(),
+					height: 100,
+					job_id: seq,
+					nonce: seq * 7,
+					edge_bits: 29,
+					difficulty: 4,
+					result: "accepted".to_string(),
+					response: "\"ok\"".to_string(),
+					signature: String::new(),
+				};
+				e.signature = sign(key, &e.message(&previous));
+				previous = e.signature.clone();
+				e
+			})
+			.collect()
+	}
+
+	#[test]
+	fn tampering_breaks_the_chain() {
+		let entries = chain(Some("secret"), 3);
+		assert!(verify(&entries, Some("secret")).is_ok());
+		assert!(verify(&entries, Some("other")).is_err());
+		assert!(verify(&entries, None).is_err());
+
+		let mut edited = entries.clone();
+		edited[1].difficulty = 8;
+		assert!(verify(&edited, Some("secret")).is_err());
+
+		let mut dropped = entries.clone();
+		dropped.remove(1);
+		assert!(verify(&dropped, Some("secret")).is_err());
+
+		assert!(verify(&chain(None, 3), None).is_ok());
+	}
+
+	#[test]
+	fn unreadable_ledger_moved_aside() {
+		let dir = ::std::env::temp_dir()
+			.join(format!("grin-miner-ledger-{}", ::std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("ledger.jsonl").to_str().unwrap().to_string();
+		fs::write(&path, "{\"seq\": 1, trailing garbage\n").unwrap();
+
+		let mut ledger = ShareLedger::open(&path, Some("secret".to_string()));
+		let entry = chain(Some("secret"), 1).remove(0);
+		ledger.record(entry.clone()).unwrap();
+		ledger.record(entry).unwrap();
+		let entries = load(&path).unwrap();
+		assert_eq!(entries.len(), 2);
+		assert!(verify(&entries, Some("secret")).is_ok());
+		let aside = fs::read_dir(&dir)
+			.unwrap()
+			.filter_map(|e| e.ok())
+			.filter(|e| e.file_name().to_string_lossy().ends_with(".bad"))
+			.count();
+		assert_eq!(aside, 1);
+		let _ = fs::remove_dir_all(&dir);
+	}
+}
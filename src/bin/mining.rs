@@ -17,50 +17,175 @@
 /// 
 
 use std::sync::{mpsc, Arc, RwLock};
-use time;
-use std::{self, thread};
-use util::LOGGER;
+use util::{self, LOGGER};
+use chain;
+use clock;
 use config;
+use correlation;
 use stats;
 
 use cuckoo::{
-	CuckooMinerSolution,
-	CuckooMinerJobHandle,
-	CuckooMinerError};
+	CuckooMinerDeviceStats,
+	CuckooMinerJobHandle};
 
-use {plugin, types};
+use alerts::AlertMonitor;
+use difficulty;
+use errors::{self, Action, Condition, MinerError};
+use hardware;
+use pacing::Pacing;
+use placement;
+use rampup::{self, RampUp};
+use recovery::DeviceRecovery;
+use sandbox::{Sandbox, Solution};
+use solutions::{self, OverflowPolicy, SolutionBuffer};
+use {plugin, types, units};
+
+/// Longest a scheduled restart waits for the pool to answer the shares
+/// submitted, in seconds
+const MAX_RESTART_DEFER_SECS: i64 = 60;
+
+/// A job from a pool sending a job per proof size
+#[derive(Clone, Debug)]
+struct AlgorithmJob {
+	edge_bits: u32,
+	height: u64,
+	job_id: u64,
+	target_diff: u64,
+	pre_pow: String,
+}
+
+/// A job received from the pool
+#[derive(Clone, Debug)]
+struct NewJob {
+	height: u64,
+	job_id: u64,
+	target_diff: u64,
+	pre_pow: String,
+	edge_bits: Option<u32>,
+}
 
 pub struct Controller {
 	config: config::MinerConfig,
 	plugin_miner: Option<plugin::PluginMiner>,
 	job_handle: Option<CuckooMinerJobHandle>,
+	sandbox: Option<Sandbox>,
 	rx: mpsc::Receiver<types::MinerMessage>,
 	pub tx: mpsc::Sender<types::MinerMessage>,
 	client_tx: Option<mpsc::Sender<types::ClientMessage>>,
+	/// Solutions found, for the stratum client to take
+	pub solutions: SolutionBuffer,
 	current_height: u64,
 	current_job_id: u64,
 	current_target_diff: u64,
+	current_pre_pow: Option<String>,
+	current_edge_bits: Option<u32>,
+	/// Latest job for each proof size, from pools sending a job per
+	/// proof size
+	algorithm_jobs: Vec<AlgorithmJob>,
+	mixed_jobs_warned: bool,
+	paused: bool,
+	paused_devices: Vec<(usize, u32)>,
+	/// Devices paused by the thermal limits
+	thermal_paused: Vec<(usize, u32)>,
+	/// Devices of plugins configured with enabled = false, until activated
+	standby_devices: Vec<(usize, u32)>,
+	failed_devices: Vec<(usize, u32)>,
+	/// When to try the failed devices again, once all have failed and
+	/// the failure policy says to retry
+	failed_retry_at: Option<i64>,
+	recovery: DeviceRecovery,
+	gpu_reset: bool,
 	stats: Arc<RwLock<stats::Stats>>,
+	alert_monitor: AlertMonitor,
+	chain: config::ChainType,
+	header_version_warned: bool,
+	/// When mining last (re)started in full, for restart_after_hours
+	last_full_restart: i64,
+	/// Why and since when a full restart is due, while it waits for
+	/// shares to be answered
+	restart_requested: Option<(String, i64)>,
+	difficulty_pool: difficulty::DifficultyPool,
+	pacing: Pacing,
+	/// Startup delay and devices yet to start, until all have
+	ramp_up: Option<RampUp>,
+	/// Job waiting for the solves in flight on the one it replaces, and
+	/// when to start it (monotonic ms), when abort_on_new_job is off
+	pending_job: Option<(NewJob, i64)>,
 }
 
 impl Controller {
-	pub fn new(config: config::MinerConfig, stats: Arc<RwLock<stats::Stats>>) -> Result<Controller, String> {
+	pub fn new(
+		config: config::MinerConfig,
+		alert_config: config::AlertConfig,
+		stats: Arc<RwLock<stats::Stats>>,
+	) -> Result<Controller, String> {
 		{
 			let mut stats_w = stats.write().unwrap();
 			stats_w.client_stats.server_url = config.stratum_server_addr.clone();
+			stats_w.mining_stats.electricity_price = config.electricity_price;
+			stats_w.mining_stats.profile = config.profile.clone();
+		}
+		let chain = config.chain_type()?;
+		if !config.sandboxed()
+			&& config.miner_plugin_config.iter().any(|p| p.instances.unwrap_or(1) > 1)
+		{
+			warn!(
+				LOGGER,
+				"Solver instances need sandbox_plugins, running one instance per device"
+			);
 		}
+		let pow_chain = chain::from_name(config.pow_chain.as_ref().map(|c| c.as_str()))?;
+		let overflow_policy = OverflowPolicy::from_name(
+			config.solution_overflow_policy.as_ref().map(|p| p.as_str()),
+		)?;
+		let solutions = SolutionBuffer::new(
+			config.solution_buffer_size.unwrap_or(solutions::DEFAULT_SIZE),
+			overflow_policy,
+			pow_chain.clone(),
+		);
+		stats.write().unwrap().mining_stats.solution_buffer.size = solutions.size() as u64;
 		let (tx, rx) = mpsc::channel::<types::MinerMessage>();
+		let difficulty_pool = difficulty::DifficultyPool::new(
+			config
+				.difficulty_threads
+				.unwrap_or(difficulty::DEFAULT_THREADS),
+			pow_chain,
+			stats.clone(),
+		);
 		Ok(Controller {
+			pacing: Pacing::new(&config),
 			config: config,
 			plugin_miner: None,
 			job_handle: None,
+			sandbox: None,
 			rx: rx,
 			tx: tx,
 			client_tx: None,
+			solutions: solutions,
 			current_height: 0,
 			current_job_id: 0,
 			current_target_diff: 0,
+			current_pre_pow: None,
+			current_edge_bits: None,
+			algorithm_jobs: vec![],
+			mixed_jobs_warned: false,
+			paused: false,
+			paused_devices: vec![],
+			thermal_paused: vec![],
+			standby_devices: vec![],
+			failed_devices: vec![],
+			failed_retry_at: None,
+			recovery: DeviceRecovery::new(config::RecoveryConfig::default()),
+			gpu_reset: false,
 			stats: stats,
+			alert_monitor: AlertMonitor::new(alert_config),
+			chain: chain,
+			header_version_warned: false,
+			last_full_restart: clock::mono_secs(),
+			restart_requested: None,
+			difficulty_pool: difficulty_pool,
+			ramp_up: None,
+			pending_job: None,
 		})
 	}
 
@@ -68,155 +193,1260 @@ impl Controller {
 		self.client_tx = Some(client_tx);
 	}
 
+	pub fn set_recovery(&mut self, recovery_config: config::RecoveryConfig) {
+		self.gpu_reset = recovery_config.gpu_reset;
+		self.recovery = DeviceRecovery::new(recovery_config);
+	}
+
 	/// Run the mining controller
 	pub fn run(&mut self){
-		// how often to output stats
-		let stat_output_interval = 2;
-		let mut next_stat_output = time::get_time().sec + stat_output_interval;
+		let mut next_stat_output = clock::mono_secs() + self.pacing.stats_interval_secs();
+		let fairness_interval = self.config.fairness_report_minutes.unwrap_or(60) as i64 * 60;
+		let mut next_fairness_report = clock::mono_secs() + fairness_interval;
+		self.load_standby_devices();
+		self.start_ramp_up(true);
 
 		loop {
 			while let Some(message) = self.rx.try_iter().next() {
 				debug!(LOGGER, "Miner received message: {:?}", message);
+				// anything but a job acts on the newest job
+				match message {
+					types::MinerMessage::ReceivedJob(..) => {}
+					types::MinerMessage::StopJob => self.pending_job = None,
+					_ => self.start_pending_job(true),
+				}
 				let result = match message {
-					types::MinerMessage::ReceivedJob(height, job_id, diff, pre_pow) => {
-						self.stop_job();
-						self.current_height = height;
-						self.current_job_id = job_id;
-						self.current_target_diff = diff;
-						self.start_job(&pre_pow)
-					},
+					types::MinerMessage::ReceivedJob(height, job_id, diff, pre_pow, edge_bits) => {
+						let job = NewJob {
+							height: height,
+							job_id: job_id,
+							target_diff: diff,
+							pre_pow: pre_pow,
+							edge_bits: edge_bits,
+						};
+						match self.drain_deadline() {
+							Some(start_at) => {
+								debug!(
+									LOGGER,
+									"Letting the solves in flight finish before starting the job";
+									"job_id" => job_id
+								);
+								self.pending_job = Some((job, start_at));
+								Ok(())
+							}
+							None => self.receive_job(job),
+						}
+					}
 					types::MinerMessage::StopJob => {
 						debug!(LOGGER, "Stopping jobs");
 						self.stop_job();
+						self.current_pre_pow = None;
 						Ok(())
-					}types::MinerMessage::Shutdown => {
+					}
+					types::MinerMessage::Pause => {
+						warn!(LOGGER, "Pausing mining");
+						self.paused = true;
+						self.stop_job();
+						self.job_handle = None;
+						self.update_pause_stats();
+						self.record_restart(None, "paused");
+						Ok(())
+					}
+					types::MinerMessage::Resume => {
+						warn!(LOGGER, "Resuming mining");
+						self.paused = false;
+						self.update_pause_stats();
+						self.record_restart(None, "resumed");
+						self.restart_job()
+					}
+					types::MinerMessage::PauseDevice(plugin, device) => {
+						warn!(
+							util::device_logger(plugin, &device.to_string()),
+							"Pausing plugin {} device {}", plugin, device
+						);
+						if !self.paused_devices.contains(&(plugin, device)) {
+							self.paused_devices.push((plugin, device));
+						}
+						self.update_pause_stats();
+						self.record_restart(Some((plugin, device.to_string())), "device paused");
+						self.apply_disabled()
+					}
+					types::MinerMessage::ResumeDevice(plugin, device) => {
+						warn!(
+							util::device_logger(plugin, &device.to_string()),
+							"Resuming plugin {} device {}", plugin, device
+						);
+						self.paused_devices.retain(|d| *d != (plugin, device));
+						self.update_pause_stats();
+						self.record_restart(Some((plugin, device.to_string())), "device resumed");
+						self.apply_disabled()
+					}
+					types::MinerMessage::ThermalPause(plugin, device) => {
+						if self.thermal_paused.contains(&(plugin, device)) {
+							Ok(())
+						} else {
+							self.thermal_paused.push((plugin, device));
+							self.apply_disabled()
+						}
+					}
+					types::MinerMessage::ThermalResume(plugin, device) => {
+						if self.thermal_paused.contains(&(plugin, device)) {
+							self.thermal_paused.retain(|d| *d != (plugin, device));
+							self.apply_disabled()
+						} else {
+							Ok(())
+						}
+					}
+					types::MinerMessage::Activate(plugin) => {
+						let activated: Vec<(usize, u32)> = self
+							.standby_devices
+							.iter()
+							.filter(|d| plugin.map_or(true, |p| d.0 == p))
+							.cloned()
+							.collect();
+						if activated.is_empty() {
+							warn!(LOGGER, "No devices in standby to activate");
+							Ok(())
+						} else {
+							for &(plugin, device) in &activated {
+								warn!(
+									util::device_logger(plugin, &device.to_string()),
+									"Activating plugin {} device {}", plugin, device
+								);
+							}
+							self.standby_devices.retain(|d| !activated.contains(d));
+							self.update_pause_stats();
+							self.record_restart(None, "standby devices activated");
+							self.restart_job()
+						}
+					}
+					types::MinerMessage::ReloadPlugins => {
+						warn!(LOGGER, "Reloading mining plugins");
+						plugin::reset_loaded_config();
+						self.sandbox = None;
+						self.record_restart(None, "plugins reloaded");
+						self.restart_job()
+					}
+					types::MinerMessage::UpdateConfig(config) => {
+						warn!(LOGGER, "Applying new plugin configuration");
+						self.config = config;
+						self.pacing = Pacing::new(&self.config);
+						self.stats.write().unwrap().mining_stats.profile = self.config.profile.clone();
+						plugin::reset_loaded_config();
+						self.sandbox = None;
+						self.record_restart(None, "plugin configuration changed");
+						self.restart_job()
+					}
+					types::MinerMessage::SwitchProfile(name) => {
+						let mut config = self.config.clone();
+						match config.apply_profile(&name) {
+							Ok(()) => {
+								warn!(LOGGER, "Switching to profile {}", name);
+								self.config = config;
+								self.stats.write().unwrap().mining_stats.profile = Some(name.clone());
+								plugin::reset_loaded_config();
+								self.sandbox = None;
+								self.record_restart(None, &format!("switched to profile {}", name));
+								self.restart_job()
+							}
+							Err(e) => {
+								error!(LOGGER, "{}", e);
+								Ok(())
+							}
+						}
+					}
+					types::MinerMessage::Restart(reason) => {
+						self.request_full_restart(&reason);
+						Ok(())
+					}
+					types::MinerMessage::Shutdown => {
 						debug!(LOGGER, "Stopping jobs and Shutting down mining controller");
+						// solutions already found still go to the pool
+						self.submit_solutions();
 						self.stop_job();
+						self.sandbox = None;
 						return;
 					}
 				};
 				if let Err(e) = result {
-					error!(LOGGER, "Mining Controller Error {:?}", e);
+					self.log_error(&e);
 				}
 			}
 
-			if time::get_time().sec > next_stat_output {
+			self.start_pending_job(false);
+
+			if let Err(e) = self.check_ramp_up() {
+				self.log_error(&e);
+			}
+
+			if clock::mono_secs() > next_stat_output {
 				self.output_job_stats();
-				next_stat_output = time::get_time().sec + stat_output_interval;
+				if let Err(e) = self.check_recovery() {
+					self.log_error(&e);
+				}
+				self.check_all_failed();
+				self.update_alerts();
+				next_stat_output = clock::mono_secs() + self.pacing.stats_interval_secs();
 			}
 
-			let sol = self.check_solutions();
-			if sol.is_some(){
-				let sol = sol.unwrap();
-				let _ = self.client_tx.as_mut().unwrap().send(types::ClientMessage::FoundSolution (
-					self.current_height,
-					self.current_job_id,
-					sol.cuckoo_size,
-					sol.get_nonce_as_u64(),
-					sol.solution_nonces.to_vec(),
-				));
+			if fairness_interval > 0 && clock::mono_secs() >= next_fairness_report {
+				self.fairness_report();
+				next_fairness_report = clock::mono_secs() + fairness_interval;
+			}
+
+			if let Some(hours) = self.config.restart_after_hours.map(|h| h.max(1)) {
+				if self.restart_requested.is_none()
+					&& clock::mono_secs() >= self.last_full_restart + hours as i64 * 3600
+				{
+					self.stats.write().unwrap().history.scheduled_restarts += 1;
+					self.request_full_restart(&format!("scheduled after {} hours", hours));
+				}
+			}
+			if self.restart_requested.is_some() {
+				self.full_restart();
+			}
+
+			self.submit_solutions();
+			self.pacing.wait(self.paused || self.current_pre_pow.is_none());
+		}
+	}
+
+	/// Log an error of the mining loop, with its category
+	fn log_error(&self, e: &MinerError) {
+		error!(
+			LOGGER,
+			"Mining Controller Error: {}", e;
+			"category" => e.category().name(),
+			"job_id" => self.current_job_id
+		);
+	}
+
+	/// (Re)start mining the current job, unless paused, waiting for the
+	/// startup delay or there's no job
+	fn restart_job(&mut self) -> Result<(), MinerError> {
+		self.stop_job();
+		if self.paused || self.ramp_up.as_ref().map_or(false, |r| r.waiting()) {
+			return Ok(());
+		}
+		match self.current_pre_pow.clone() {
+			Some(pre_pow) => self.start_job(&pre_pow),
+			None => Ok(()),
+		}
+	}
+
+	/// Apply a change of the disabled devices: sandboxed workers mining a
+	/// job only stop or start the devices that changed, the in-process
+	/// solvers have to restart the job
+	fn apply_disabled(&mut self) -> Result<(), MinerError> {
+		if !self.sandbox.as_ref().map_or(false, |s| s.has_job()) {
+			return self.restart_job();
+		}
+		let disabled = self.disabled_devices();
+		if let Some(ref mut sandbox) = self.sandbox {
+			sandbox.set_disabled(&disabled);
+		}
+		Ok(())
+	}
+
+	/// Mine a job received from the pool, in place of the one being mined
+	fn receive_job(&mut self, job: NewJob) -> Result<(), MinerError> {
+		let NewJob {
+			height,
+			job_id,
+			target_diff: diff,
+			pre_pow,
+			edge_bits,
+		} = job;
+		self.current_height = height;
+		self.current_job_id = job_id;
+		self.current_target_diff = diff;
+		self.current_edge_bits = edge_bits;
+		self.count_incompatible_job(edge_bits);
+		// mining a job that this one replaces
+		let replaced = !self.paused && self.current_pre_pow.is_some();
+		self.check_header_version(&pre_pow);
+		match edge_bits {
+			None => self.algorithm_jobs.clear(),
+			Some(bits) => {
+				self.algorithm_jobs.retain(|j| j.edge_bits != bits);
+				self.algorithm_jobs.push(AlgorithmJob {
+					edge_bits: bits,
+					height: height,
+					job_id: job_id,
+					target_diff: diff,
+					pre_pow: pre_pow.clone(),
+				});
+			}
+		}
+		self.current_pre_pow = Some(pre_pow);
+		let switch_started = clock::mono_ms();
+		let result = match edge_bits {
+			// only the plugins of that proof size change job
+			Some(bits)
+				if self.algorithm_jobs.len() > 1
+					&& !self.paused && self.sandbox.as_ref().map_or(false, |s| s.has_job()) =>
+			{
+				self.start_algorithm_jobs(Some(bits));
+				Ok(())
+			}
+			_ => self.restart_job(),
+		};
+		if result.is_ok() && !self.paused {
+			let switch_ms = (clock::mono_ms() - switch_started) as u64;
+			let mut stats = self.stats.write().unwrap();
+			let one_way_ms = stats.client_stats.pool_rtt_ms.map_or(0, |r| (r / 2.0) as u64);
+			stats.timing.job_started(job_id, replaced, one_way_ms);
+			// sandboxed workers time their own switch
+			if replaced && self.sandbox.is_none() && self.job_handle.is_some() {
+				let devices: Vec<(usize, String)> = stats
+					.mining_stats
+					.device_stats
+					.iter()
+					.flat_map(|plugins| plugins.iter().enumerate())
+					.flat_map(|(i, devices)| {
+						devices
+							.iter()
+							.filter(|d| d.in_use == 1)
+							.map(move |d| (i, d.device_id.clone()))
+					})
+					.collect();
+				for (plugin, device_id) in devices {
+					stats.timing.device_switched(plugin, &device_id, switch_ms);
+				}
+			}
+		}
+		result
+	}
+
+	/// When to start a new job for the solves in flight to finish first,
+	/// at most a graph time of the slowest device from now, none to start
+	/// it now
+	fn drain_deadline(&self) -> Option<i64> {
+		if self.config.abort_on_new_job.unwrap_or(true)
+			|| self.paused
+			|| self.current_pre_pow.is_none()
+		{
+			return None;
+		}
+		// a job arriving meanwhile takes the place of the one waiting
+		if let Some((_, start_at)) = self.pending_job {
+			return Some(start_at);
+		}
+		let slowest_ns = self
+			.stats
+			.read()
+			.unwrap()
+			.mining_stats
+			.device_stats
+			.iter()
+			.flat_map(|plugins| plugins.iter())
+			.flat_map(|devices| devices.iter())
+			.filter(|d| d.in_use == 1 && d.has_errored == 0)
+			.map(|d| d.last_solution_time)
+			.max()
+			.unwrap_or(0);
+		if slowest_ns == 0 {
+			return None;
+		}
+		Some(clock::mono_ms() + (slowest_ns / 1_000_000) as i64)
+	}
+
+	/// Start the job waiting for the solves in flight once they're done,
+	/// or right away if now is set
+	fn start_pending_job(&mut self, now: bool) {
+		let due = self
+			.pending_job
+			.as_ref()
+			.map_or(false, |&(_, start_at)| now || clock::mono_ms() >= start_at);
+		if !due {
+			return;
+		}
+		let (job, _) = self.pending_job.take().unwrap();
+		if let Err(e) = self.receive_job(job) {
+			self.log_error(&e);
+		}
+	}
+
+	/// Height, job id and target difficulty of the job a solution of the
+	/// given size was found for, none if no job being mined is for it
+	fn job_for(&self, edge_bits: u32) -> Option<(u64, u64, u64)> {
+		let current = (self.current_height, self.current_job_id, self.current_target_diff);
+		if self.algorithm_jobs.is_empty() {
+			return Some(current);
+		}
+		if self.sandbox.is_none() {
+			// every plugin mines the latest job
+			return if self.current_edge_bits == Some(edge_bits) {
+				Some(current)
+			} else {
+				None
+			};
+		}
+		self.algorithm_jobs
+			.iter()
+			.find(|j| j.edge_bits == edge_bits)
+			.map(|j| (j.height, j.job_id, j.target_diff))
+	}
+
+	/// Load the plugins configured with enabled = false and list their
+	/// devices, which are then kept in standby until activated
+	fn load_standby_devices(&mut self) {
+		let standby: Vec<usize> = self
+			.config
+			.miner_plugin_config
+			.iter()
+			.enumerate()
+			.filter(|&(_, p)| !p.enabled.unwrap_or(true))
+			.filter(|&(i, p)| {
+				if p.is_external() {
+					warn!(
+						LOGGER,
+						"Plugin {} is an external solver, which can't be kept in standby, \
+						 mining on it from startup",
+						i
+					);
+				}
+				!p.is_external()
+			})
+			.map(|(i, _)| i)
+			.collect();
+		if standby.is_empty() {
+			return;
+		}
+		let mut config = self.config.clone();
+		config.miner_plugin_config = standby
+			.iter()
+			.map(|&i| self.config.miner_plugin_config[i].clone())
+			.collect();
+		let result = plugin::enumerate_devices(&config);
+		// the standby plugins alone were loaded
+		plugin::reset_loaded_config();
+		let lists = match result {
+			Ok(l) => l,
+			Err(e) => {
+				error!(LOGGER, "Unable to load the standby plugins: {}", e);
+				return;
+			}
+		};
+		for (&plugin, devices) in standby.iter().zip(lists) {
+			match devices {
+				Ok(devices) => {
+					for d in devices {
+						if let Ok(id) = d.device_id.parse::<u32>() {
+							info!(
+								util::device_logger(plugin, &d.device_id),
+								"Plugin {} device {} ({}) in standby", plugin, id, d.device_name
+							);
+							self.standby_devices.push((plugin, id));
+						}
+					}
+				}
+				Err(e) => error!(LOGGER, "Standby plugin {} failed to start: {}", plugin, e),
+			}
+		}
+		self.update_pause_stats();
+	}
+
+	/// Devices not to mine on, paused, in standby, not started yet,
+	/// quarantined or failed
+	fn disabled_devices(&self) -> Vec<(usize, u32)> {
+		let quarantined = self.recovery.quarantined_ids();
+		let held_back = self.ramp_up.as_ref().map_or(vec![], |r| r.held_back());
+		self.paused_devices
+			.iter()
+			.chain(self.thermal_paused.iter())
+			.chain(self.standby_devices.iter())
+			.chain(held_back.iter())
+			.chain(quarantined.iter())
+			.chain(self.failed_devices.iter())
+			.cloned()
+			.collect()
+	}
+
+	/// Hold the devices back to start them one at a time, after the
+	/// startup delay if `delayed`
+	fn start_ramp_up(&mut self, delayed: bool) {
+		self.ramp_up = RampUp::new(&self.config, &self.standby_devices, delayed);
+		if let Some(ref r) = self.ramp_up {
+			let stats = r.stats();
+			if delayed && self.config.startup_delay_secs.unwrap_or(0) > 0 {
+				info!(
+					LOGGER,
+					"Waiting {} seconds before mining",
+					self.config.startup_delay_secs.unwrap_or(0)
+				);
+			}
+			if stats.total_devices > 1 {
+				info!(
+					LOGGER,
+					"Starting {} devices {} seconds apart",
+					stats.total_devices,
+					self.config.startup_stagger_secs.unwrap_or(0)
+				);
+			}
+		}
+		self.stats.write().unwrap().mining_stats.ramp_up = self.ramp_up.as_ref().map(|r| r.stats());
+	}
+
+	/// Start mining once the startup delay is over, then the devices held
+	/// back one at a time
+	fn check_ramp_up(&mut self) -> Result<(), MinerError> {
+		let mining = !self.paused && self.current_pre_pow.is_some();
+		let step = match self.ramp_up.as_mut().and_then(|r| r.advance(mining)) {
+			Some(s) => s,
+			None => return Ok(()),
+		};
+		match step {
+			rampup::Step::Start => info!(LOGGER, "Startup delay over, mining"),
+			rampup::Step::Device(plugin, device) => info!(
+				util::device_logger(plugin, &device.to_string()),
+				"Starting plugin {} device {}", plugin, device
+			),
+		}
+		if self.ramp_up.as_ref().map_or(false, |r| r.done()) {
+			info!(LOGGER, "All devices started");
+			self.ramp_up = None;
+		}
+		self.stats.write().unwrap().mining_stats.ramp_up = self.ramp_up.as_ref().map(|r| r.stats());
+		match step {
+			rampup::Step::Start => self.restart_job(),
+			// only the device released, the others keep mining
+			rampup::Step::Device(..) => self.apply_disabled(),
+		}
+	}
+
+	/// Start the latest job for each proof size on the plugins of that
+	/// size (or of just the given size), stopping those with no job
+	fn start_algorithm_jobs(&mut self, only: Option<u32>) {
+		let disabled = self.disabled_devices();
+		if self.sandbox.is_none() {
+			self.sandbox = Some(Sandbox::new(&self.config));
+		}
+		let sandbox = self.sandbox.as_mut().unwrap();
+		for (i, p) in self.config.miner_plugin_config.iter().enumerate() {
+			let edge_bits = plugin::supported_edge_bits(i, p);
+			if only.map_or(false, |o| !edge_bits.contains(&o)) {
+				continue;
+			}
+			match self.algorithm_jobs.iter().find(|j| edge_bits.contains(&j.edge_bits)) {
+				Some(j) => sandbox.start_plugin_job(i, &j.pre_pow, j.target_diff, &disabled),
+				None => sandbox.stop_plugin_job(i),
+			}
+		}
+	}
+
+	/// Whether the devices of the plugin solve the proof size
+	fn plugin_solves(&self, plugin: usize, edge_bits: u32) -> bool {
+		self.config
+			.miner_plugin_config
+			.get(plugin)
+			.map_or(false, |p| plugin::supported_edge_bits(plugin, p).contains(&edge_bits))
+	}
+
+	/// Devices of the plugins that don't solve the proof size of a job, as
+	/// they report themselves or as configured
+	fn incompatible_devices(&self, edge_bits: Option<u32>) -> Vec<(usize, u32)> {
+		let bits = match edge_bits {
+			Some(b) => b,
+			None => return vec![],
+		};
+		let device_stats = self.stats.read().unwrap().mining_stats.device_stats.clone();
+		let mut devices = vec![];
+		for (i, p) in self.config.miner_plugin_config.iter().enumerate() {
+			if self.plugin_solves(i, bits) {
+				continue;
+			}
+			let reported: Vec<u32> = device_stats
+				.as_ref()
+				.and_then(|s| s.get(i))
+				.map_or(vec![], |d| d.iter().filter_map(|d| d.device_id.parse().ok()).collect());
+			let ids = if reported.is_empty() {
+				placement::mining_devices(p)
+			} else {
+				reported
+			};
+			devices.extend(ids.into_iter().map(|d| (i, d)));
+		}
+		devices
+	}
+
+	/// Count a job for a proof size some or all of the devices don't
+	/// solve, which they skip rather than fail on
+	fn count_incompatible_job(&self, edge_bits: Option<u32>) {
+		let bits = match edge_bits {
+			Some(b) => b,
+			None => return,
+		};
+		let plugins = self.config.miner_plugin_config.len();
+		let solving = (0..plugins).filter(|&i| self.plugin_solves(i, bits)).count();
+		if solving == plugins {
+			return;
+		}
+		let mut stats = self.stats.write().unwrap();
+		if solving == 0 {
+			stats.mining_stats.unsupported_jobs += 1;
+			if stats.mining_stats.unsupported_jobs == 1 {
+				warn!(
+					LOGGER,
+					"The pool sent a job for edge bits {}, which none of the devices solve, \
+					 skipping jobs of that size",
+					bits;
+					"job_id" => self.current_job_id
+				);
+			}
+		} else {
+			stats.mining_stats.incompatible_jobs += 1;
+			debug!(
+				LOGGER,
+				"Job for edge bits {} skipped by the devices of {} plugins",
+				bits,
+				plugins - solving;
+				"job_id" => self.current_job_id
+			);
+		}
+	}
+
+	/// Warn (once) if a job's header version isn't the one expected at
+	/// its height on the configured chain, as the pool is likely on
+	/// another chain
+	fn check_header_version(&mut self, pre_pow: &str) {
+		if self.header_version_warned || pre_pow.len() < 4 {
+			return;
+		}
+		let expected = match self.chain.header_version(self.current_height) {
+			Some(v) => v,
+			None => return,
+		};
+		if let Ok(version) = u16::from_str_radix(&pre_pow[..4], 16) {
+			if version != expected {
+				warn!(
+					LOGGER,
+					"Job at height {} has header version {}, expected {} on {:?}, \
+					 is the pool on another chain? (see chain_type)",
+					self.current_height, version, expected, self.chain
+				);
+				self.header_version_warned = true;
 			}
-			thread::sleep(std::time::Duration::from_millis(100));
 		}
 	}
 
+	/// Tear down and re-create the solvers when devices keep erroring,
+	/// and stop mining on devices that recovery can't help
+	fn check_recovery(&mut self) -> Result<(), MinerError> {
+		let device_stats = match self.stats.read().unwrap().mining_stats.device_stats {
+			Some(ref d) => d.clone(),
+			None => return Ok(()),
+		};
+		let actions = self.recovery.update(&device_stats, clock::mono_secs());
+		if actions.recover.is_empty() && actions.failed.is_empty() && actions.quarantine.is_empty() {
+			return Ok(());
+		}
+		for &(plugin, ref device) in &actions.failed {
+			let e = MinerError::solver_runtime(
+				Some(plugin),
+				device.parse::<u32>().ok(),
+				"keeps failing after recovery, mining without it",
+			);
+			error!(
+				util::device_logger(plugin, device),
+				"{}", e;
+				"category" => e.category().name()
+			);
+			if let Ok(id) = device.parse::<u32>() {
+				if !self.failed_devices.contains(&(plugin, id)) {
+					self.failed_devices.push((plugin, id));
+				}
+			}
+			self.record_restart(Some((plugin, device.clone())), "device failed");
+		}
+		for &(plugin, ref device, until) in &actions.quarantine {
+			warn!(
+				util::device_logger(plugin, device),
+				"Plugin {} device {} keeps erroring, quarantined until {}",
+				plugin, device, units::clock(clock::to_wall(until))
+			);
+			self.record_restart(Some((plugin, device.clone())), "device quarantined");
+		}
+		for &(plugin, ref device, attempt) in &actions.recover {
+			warn!(
+				util::device_logger(plugin, device),
+				"Plugin {} device {} keeps erroring, re-creating the solvers (attempt {})",
+				plugin, device, attempt
+			);
+			self.record_restart(Some((plugin, device.clone())), "device recovery");
+		}
+
+		// tear the solvers down completely, so they're re-created
+		self.stop_job();
+		self.job_handle = None;
+		self.plugin_miner = None;
+		self.sandbox = None;
+		plugin::reset_loaded_config();
+		if self.gpu_reset {
+			for &(plugin, ref device, _) in &actions.recover {
+				self.reset_gpu(plugin, device);
+			}
+		}
+		{
+			let mut stats = self.stats.write().unwrap();
+			stats.mining_stats.recovery_attempts += actions.recover.len() as u64;
+			stats.mining_stats.failed_devices = self.failed_devices.clone();
+			stats.mining_stats.quarantined = self.recovery.quarantined();
+		}
+		self.restart_job()
+	}
+
+	/// Once every device has failed, exit or retry them all, as the
+	/// failure policy says
+	fn check_all_failed(&mut self) {
+		if let Some(at) = self.failed_retry_at {
+			if clock::mono_secs() >= at {
+				warn!(LOGGER, "Retrying the {} failed devices", self.failed_devices.len());
+				self.failed_retry_at = None;
+				self.failed_devices.clear();
+				self.recovery.reset();
+				self.stats.write().unwrap().mining_stats.failed_devices = vec![];
+				self.request_full_restart("retrying failed devices");
+			}
+			return;
+		}
+		if self.failed_devices.is_empty() {
+			return;
+		}
+		let all_failed = match self.stats.read().unwrap().mining_stats.device_stats {
+			Some(ref plugins) => plugins.iter().enumerate().all(|(i, devices)| {
+				devices.iter().all(|d| {
+					d.device_id
+						.parse::<u32>()
+						.map_or(false, |id| self.failed_devices.contains(&(i, id)))
+				})
+			}),
+			None => false,
+		};
+		if !all_failed {
+			return;
+		}
+		error!(LOGGER, "Every device has failed, nothing is mining");
+		match errors::action(Condition::AllDevicesFailed) {
+			Action::Exit(code) => errors::exit_on(Condition::AllDevicesFailed, code),
+			Action::Retry(secs) => {
+				warn!(LOGGER, "Retrying the failed devices in {}s", secs);
+				self.failed_retry_at = Some(clock::mono_secs() + secs as i64);
+			}
+		}
+	}
+
+	/// PCI bus id of the GPU of the given device, if it can be found
+	fn device_bus_id(&self, plugin: usize, device: &str) -> Option<String> {
+		self.stats
+			.read()
+			.unwrap()
+			.mining_stats
+			.hardware_for(plugin, device)
+			.and_then(|h| h.pci_bus_id.clone())
+			.or_else(|| {
+				let id = device.parse::<u32>().ok()?;
+				let bus_ids = plugin::pci_bus_ids(self.config.miner_plugin_config.get(plugin)?).ok()?;
+				bus_ids.into_iter().find(|b| b.0 == id).map(|b| b.1)
+			})
+	}
+
+	/// Work out the stable identity of the devices not seen before (or
+	/// since the plugin configuration changed), logging which plugin and
+	/// device each one is in this run
+	fn identify_devices(&self, device_vec: &[Vec<CuckooMinerDeviceStats>]) {
+		let known = self.stats.read().unwrap().mining_stats.device_identities.clone();
+		let mut identities = vec![];
+		let mut changed = false;
+		for (i, plugin) in device_vec.iter().enumerate() {
+			let (plugin_name, edge_bits) = match self.config.miner_plugin_config.get(i) {
+				Some(p) => (
+					format!("{}_{}", p.type_filter, p.edge_bits),
+					plugin::supported_edge_bits(i, p),
+				),
+				None => continue,
+			};
+			for d in plugin {
+				let identity = known.iter().find(|k| {
+					k.plugin == i && k.device_id == d.device_id && k.plugin_name == plugin_name
+				});
+				if let Some(identity) = identity {
+					identities.push(identity.clone());
+					continue;
+				}
+				let bus_id = self.device_bus_id(i, &d.device_id);
+				let uid = stats::device_uid(
+					&plugin_name,
+					bus_id.as_ref().map(|b| b.as_str()),
+					&d.device_id,
+					&d.device_name,
+				);
+				info!(
+					util::device_logger(i, &d.device_id),
+					"Device {} is plugin {} device {}", uid, i, d.device_id
+				);
+				identities.push(stats::DeviceIdentity {
+					plugin: i,
+					device_id: d.device_id.clone(),
+					plugin_name: plugin_name.clone(),
+					uid: uid,
+					edge_bits: edge_bits.clone(),
+				});
+				changed = true;
+			}
+		}
+		if changed || identities.len() != known.len() {
+			self.stats.write().unwrap().mining_stats.device_identities = identities;
+		}
+	}
+
+	/// Reset the GPU of the given device, if it can be found
+	fn reset_gpu(&self, plugin: usize, device: &str) {
+		let log = util::device_logger(plugin, device);
+		match self.device_bus_id(plugin, device) {
+			Some(b) => match hardware::reset_gpu(&b) {
+				Ok(()) => warn!(log, "Reset GPU {} of plugin {} device {}", b, plugin, device),
+				Err(e) => error!(log, "Unable to reset GPU {}: {}", b, e),
+			},
+			None => warn!(
+				log,
+				"Unable to reset plugin {} device {}, its PCI bus id is unknown", plugin, device
+			),
+		}
+	}
+
+	fn record_restart(&self, device: Option<(usize, String)>, reason: &str) {
+		self.stats
+			.write()
+			.unwrap()
+			.history
+			.record_restart(device, reason);
+	}
+
+	fn update_pause_stats(&self) {
+		let mut stats = self.stats.write().unwrap();
+		stats.mining_stats.paused = self.paused;
+		if self.paused {
+			stats.mining_stats.combined_gps = 0.0;
+		}
+		stats.mining_stats.paused_devices = self.paused_devices.clone();
+		stats.mining_stats.standby_devices = self.standby_devices.clone();
+	}
+
 	/// Inner part of the mining loop for cuckoo-miner async mode
 	fn start_job(
 		&mut self,
 		pre_pow: &str,
-	) -> Result<(), CuckooMinerError> {
+	) -> Result<(), MinerError> {
 		debug!(
 			LOGGER,
 			"Mining Cuck(at)oo for height: {}",
-			self.current_height,
+			self.current_height;
+			"job_id" => self.current_job_id,
+			"height" => self.current_height,
+			"cid" => correlation::job(self.current_height, self.current_job_id)
 		);
 
+		if self.config.sandboxed() {
+			if !self.algorithm_jobs.is_empty() {
+				self.start_algorithm_jobs(None);
+				return Ok(());
+			}
+			if self.sandbox.is_none() {
+				self.sandbox = Some(Sandbox::new(&self.config));
+			}
+			let disabled = self.disabled_devices();
+			self.sandbox
+				.as_mut()
+				.unwrap()
+				.start_job(pre_pow, self.current_target_diff, &disabled);
+			return Ok(());
+		}
+
+		if self.algorithm_jobs.len() > 1 && !self.mixed_jobs_warned {
+			warn!(
+				LOGGER,
+				"The pool sends a job per proof size, mining them concurrently needs \
+				 sandbox_plugins, mining the latest job only"
+			);
+			self.mixed_jobs_warned = true;
+		}
+
+		// every plugin mines the latest job, those of another proof size sit
+		// it out
+		if let Some(bits) = self.current_edge_bits {
+			let plugins = self.config.miner_plugin_config.len();
+			if !(0..plugins).any(|i| self.plugin_solves(i, bits)) {
+				return Ok(());
+			}
+		}
+
 		// Init the miner
 		let mut plugin_miner = plugin::PluginMiner::new();
 		plugin_miner.init(self.config.clone());
+		let incompatible = self.incompatible_devices(self.current_edge_bits);
+		for (plugin, device) in self.disabled_devices().into_iter().chain(incompatible) {
+			plugin_miner.disable_device(plugin, device);
+		}
 		self.plugin_miner = Some(plugin_miner);
 
 		// Start the miner working
 		let miner = self.plugin_miner.as_mut().unwrap().get_consumable();
-		self.job_handle = Some(miner.notify(1, &pre_pow, "", self.current_target_diff, false)?);
+		let job_handle = miner
+			.notify(1, &pre_pow, "", self.current_target_diff, false)
+			.map_err(|e| {
+				let (plugin, device) = self.failing_device();
+				MinerError::context_creation(plugin, device, e)
+			})?;
+		self.job_handle = Some(job_handle);
 		Ok(())
 	}
 
-	fn check_solutions(&mut self) -> Option<CuckooMinerSolution> {
-		if self.job_handle.is_none() {
-			return None;
+	/// Plugin and device the solvers failed to start on, as far as can be
+	/// told: the one device last reported errored, or the plugin if only
+	/// one was started
+	fn failing_device(&self) -> (Option<usize>, Option<u32>) {
+		let stats = self.stats.read().unwrap();
+		let reported = stats.mining_stats.device_stats.as_ref();
+		let started: Vec<usize> = (0..self.config.miner_plugin_config.len())
+			.filter(|&i| self.current_edge_bits.map_or(true, |bits| self.plugin_solves(i, bits)))
+			.collect();
+		let errored: Vec<(usize, u32)> = started
+			.iter()
+			.flat_map(|&i| {
+				reported
+					.and_then(|r| r.get(i))
+					.into_iter()
+					.flat_map(|devices| devices.iter())
+					.filter(|d| d.has_errored != 0)
+					.filter_map(move |d| d.device_id.parse::<u32>().ok().map(|id| (i, id)))
+			})
+			.collect();
+		match (started.len(), errored.len()) {
+			(_, 1) => (Some(errored[0].0), Some(errored[0].1)),
+			(1, _) => (Some(started[0]), None),
+			_ => (None, None),
 		}
-		let job_handle = self.job_handle.as_mut().unwrap();
-		if let Some(s) = job_handle.get_solution() {
+	}
+
+	fn check_solutions(&mut self) -> Vec<Solution> {
+		let solutions = match self.sandbox {
+			Some(ref mut sandbox) => {
+				let solutions = sandbox.poll();
+				let switches = sandbox.take_job_switches();
+				if !switches.is_empty() {
+					let mut stats = self.stats.write().unwrap();
+					for ((plugin, device_id), ms) in switches {
+						stats.timing.device_switched(plugin, &device_id, ms);
+					}
+				}
+				solutions
+			}
+			None => {
+				let mut solutions = vec![];
+				if let Some(ref mut job_handle) = self.job_handle {
+					while let Some(s) = job_handle.get_solution() {
+						solutions.push(Solution::from(&s));
+					}
+				}
+				solutions
+			}
+		};
+		for s in &solutions {
 			debug!(
 				LOGGER,
 				"Found cuckoo solution! nonce {}",
-				s.get_nonce_as_u64(),
+				s.nonce;
+				"job_id" => self.current_job_id
 			);
-			return Some(s);
 		}
-		None
+		solutions
 	}
 
-	fn output_job_stats(&mut self) {
-		if self.job_handle.is_none() {
+	fn request_full_restart(&mut self, reason: &str) {
+		if self.restart_requested.is_none() {
+			self.restart_requested = Some((reason.to_string(), clock::mono_secs()));
+		}
+	}
+
+	/// Restart mining in full, as requested: once the solutions found are
+	/// submitted and answered, stop and unload the plugins and reconnect
+	/// to the pool, mining again with fresh plugins on the job it sends
+	fn full_restart(&mut self) {
+		let now = clock::mono_secs();
+		let (reason, due_since) = match self.restart_requested.clone() {
+			Some(r) => r,
+			None => return,
+		};
+		let submitted = self.submit_solutions();
+		let pending = self.stats.read().unwrap().client_stats.submits_pending
+			+ self.solutions.queued() as u64;
+		if (submitted > 0 || pending > 0) && now - due_since < MAX_RESTART_DEFER_SECS {
+			debug!(
+				LOGGER,
+				"Full restart waiting for {} shares to be answered",
+				pending + submitted as u64
+			);
 			return;
 		}
-		let mut sps_total = 0.0;
-		let plugin_miner = self.plugin_miner.as_mut().unwrap();
-		let job_handle = self.job_handle.as_mut().unwrap();
-		for i in 0..plugin_miner.loaded_plugin_count() {
-			let stats = job_handle.get_stats(i);
-			if let Ok(stat_vec) = stats {
-				for s in stat_vec {
-					if s.in_use == 0 {
-						continue;
-					}
-					let last_solution_time_secs =
-						s.last_solution_time as f64 / 1000000000.0;
-					let last_hashes_per_sec = 1.0 / last_solution_time_secs;
-					let status = match s.has_errored {
-						0 => "OK",
-						_ => "ERRORED",
-					};
-					debug!(
-						LOGGER,
-								"Mining: Plugin {} - Device {} ({}) at Cuck(at)oo{} - Status: {} : Last Graph time: {}s; \
-						 Graphs per second: {:.*} - Total Attempts: {}",
-								i,
-						s.device_id,
-						s.device_name,
-						s.cuckoo_size,
-						status,
-						last_solution_time_secs,
-						3,
-						last_hashes_per_sec,
-						s.iterations_completed
-					);
-					if last_hashes_per_sec.is_finite() {
-						sps_total += last_hashes_per_sec;
-					}
+		warn!(LOGGER, "Restarting mining in full: {}", reason);
+		self.stop_job();
+		self.job_handle = None;
+		self.plugin_miner = None;
+		self.sandbox = None;
+		plugin::reset_loaded_config();
+		// the job comes again from the pool once reconnected
+		self.current_pre_pow = None;
+		self.pending_job = None;
+		self.algorithm_jobs.clear();
+		{
+			let mut stats = self.stats.write().unwrap();
+			stats
+				.history
+				.record_restart(None, &format!("full restart, {}", reason));
+		}
+		if let Some(ref client_tx) = self.client_tx {
+			let _ = client_tx.send(types::ClientMessage::Reconnect);
+		}
+		self.last_full_restart = now;
+		self.restart_requested = None;
+		self.start_ramp_up(false);
+	}
+
+	/// Send the solutions found to the stratum client, for the jobs they
+	/// were found for, returning how many were sent
+	fn submit_solutions(&mut self) -> usize {
+		let mut sent = 0;
+		for sol in self.check_solutions() {
+			let (height, job_id, target_diff) = match self.job_for(sol.cuckoo_size) {
+				Some(j) => j,
+				None => {
+					debug!(LOGGER, "Dropping Cuckoo{} solution, no current job is for it", sol.cuckoo_size);
+					continue;
+				}
+			};
+			let device = sol.device.clone().or_else(|| self.single_device(sol.cuckoo_size));
+			info!(
+				LOGGER,
+				"Submitting Cuckoo{} solution for height {}, nonce {}, found by {}",
+				sol.cuckoo_size,
+				height,
+				sol.nonce,
+				device.as_ref().map_or(String::from("an unknown device"), |d| {
+					format!("plugin {} device {}", d.0, d.1)
+				});
+				"job_id" => job_id,
+				"job_cid" => correlation::job(height, job_id),
+				"cid" => correlation::solution(height, job_id, sol.nonce)
+			);
+			{
+				let mut stats = self.stats.write().unwrap();
+				if let Some(ref d) = device {
+					stats.session_stats.add_device_solution(d);
 				}
+				stats.timing.solution_found(
+					height,
+					job_id,
+					target_diff,
+					sol.cuckoo_size,
+					sol.nonce,
+					device,
+				);
+			}
+			self.difficulty_pool
+				.evaluate(sol.cuckoo_size, &sol.solution_nonces, target_diff);
+			let dropped = self.solutions.push(
+				height,
+				job_id,
+				sol.cuckoo_size,
+				sol.nonce,
+				sol.solution_nonces,
+			);
+			if let Some(d) = dropped {
+				self.solution_dropped(&d);
+			}
+			sent += 1;
+		}
+		if sent > 0 {
+			let mut stats = self.stats.write().unwrap();
+			stats.mining_stats.solution_buffer.queued = self.solutions.queued() as u64;
+			stats.mining_stats.solution_buffer.overflowed = self.solutions.overflowed();
+		}
+		sent
+	}
+
+	/// Log a solution dropped from the full solution buffer, loudly the
+	/// first time as the stratum client isn't keeping up
+	fn solution_dropped(&self, solution: &solutions::FoundSolution) {
+		let cid = correlation::solution(solution.height, solution.job_id, solution.nonce);
+		if self.solutions.overflowed() == 1 {
+			warn!(
+				LOGGER,
+				"Solution buffer full ({} solutions), the stratum client isn't taking them, \
+				 dropping solutions",
+				self.solutions.size();
+				"cid" => cid
+			);
+		} else {
+			debug!(
+				LOGGER,
+				"Solution buffer full, dropped the solution for height {} nonce {}",
+				solution.height,
+				solution.nonce;
+				"job_id" => solution.job_id,
+				"cid" => cid
+			);
+		}
+	}
+
+	/// The only device mining the solution's proof size, if there's only
+	/// one, for solutions from solvers that don't tell which device found
+	/// them: the in-process plugins, each usually mining its own device
+	fn single_device(&self, edge_bits: u32) -> Option<(usize, String)> {
+		let stats = self.stats.read().unwrap();
+		let plugins = stats.mining_stats.device_stats.as_ref()?;
+		let mut used = plugins.iter().enumerate().flat_map(|(i, p)| {
+			p.iter()
+				.filter(move |d| d.in_use == 1 && d.cuckoo_size == edge_bits.to_string())
+				.map(move |d| (i, d))
+		});
+		match (used.next(), used.next()) {
+			(Some((i, d)), None) => Some((i, d.device_id.clone())),
+			_ => None,
+		}
+	}
+
+	/// Device stats of every plugin, from the sandboxed workers or the
+	/// running job
+	fn device_stats(&mut self) -> Option<Vec<Vec<CuckooMinerDeviceStats>>> {
+		if let Some(ref sandbox) = self.sandbox {
+			if !sandbox.has_job() {
+				return None;
+			}
+			return Some(sandbox.device_stats());
+		}
+		let plugin_miner = self.plugin_miner.as_ref()?;
+		let job_handle = self.job_handle.as_mut()?;
+		(0..plugin_miner.loaded_plugin_count())
+			.map(|i| job_handle.get_stats(i).ok())
+			.collect()
+	}
+
+	fn output_job_stats(&mut self) {
+		let device_vec = match self.device_stats() {
+			Some(d) => d,
+			None => return,
+		};
+		self.identify_devices(&device_vec);
+		let rig_gps = stats::RigGps::current(&device_vec);
+		let switches = self.stats.read().unwrap().timing.device_switches.clone();
+		let mut algorithm_gps: Vec<(String, f64)> = vec![];
+		for (i, stat_vec) in device_vec.iter().enumerate() {
+			let algorithm = self
+				.config
+				.miner_plugin_config
+				.get(i)
+				.map(|p| p.algorithm())
+				.unwrap_or_default();
+			let plugin_gps = rig_gps.plugin_gps(i);
+			if plugin_gps > 0.0 {
+				match algorithm_gps.iter_mut().find(|a| a.0 == algorithm) {
+					Some(a) => a.1 += plugin_gps,
+					None => algorithm_gps.push((algorithm.clone(), plugin_gps)),
+				}
+			}
+			for s in stat_vec {
+				if s.in_use == 0 {
+					continue;
+				}
+				let last_solution_time_secs =
+					s.last_solution_time as f64 / 1000000000.0;
+				let last_hashes_per_sec = stats::device_gps(s);
+				let status = match s.has_errored {
+					0 => "OK",
+					_ => "ERRORED",
+				};
+				let switch_ms = switches
+					.iter()
+					.find(|d| d.plugin == i && d.device_id == s.device_id)
+					.map_or(0.0, |d| d.average_ms());
+				debug!(
+					util::device_logger(i, &s.device_id),
+							"Mining: Plugin {} - Device {} ({}) at Cuck(at)oo{} - Status: {} : Last Graph time: {}s; \
+					 Graphs per second: {:.*} - Total Attempts: {}",
+							i,
+					s.device_id,
+					s.device_name,
+					s.cuckoo_size,
+					status,
+					last_solution_time_secs,
+					3,
+					last_hashes_per_sec,
+					s.iterations_completed;
+					"job_id" => self.current_job_id,
+					"cid" => correlation::job(self.current_height, self.current_job_id),
+					"duration_ms" => s.last_solution_time / 1000000,
+					"job_switch_ms" => switch_ms
+				);
 			}
 		}
 		info!(
 			LOGGER,
-			"Mining: Cuck(at)oo at {} gps (graphs per second)", sps_total
+			"Mining: Cuck(at)oo at {} gps (graphs per second)", rig_gps.combined
 		);
-		if sps_total.is_finite() {
+		if algorithm_gps.len() > 1 {
+			info!(
+				LOGGER,
+				"Mining: {}",
+				algorithm_gps
+					.iter()
+					.map(|a| format!("{} at {:.4} gps", a.0, a.1))
+					.collect::<Vec<String>>()
+					.join(", ")
+			);
+		}
+		{
 			let mut stats = self.stats.write().unwrap();
-			stats.mining_stats.combined_gps = sps_total;
+			stats.mining_stats.combined_gps = rig_gps.combined;
+			stats.mining_stats.algorithm_gps = algorithm_gps;
 			stats.mining_stats.target_difficulty = self.current_target_diff;
 			stats.mining_stats.block_height = self.current_height;
-			let mut device_vec = vec![];
-			for i in 0..plugin_miner.loaded_plugin_count() {
-				device_vec.push(job_handle.get_stats(i).unwrap());
+			for d in stats.session_stats.update_devices(&device_vec) {
+				error!(util::device_logger(d.0, &d.1), "Plugin {} - Device {} errored", d.0, d.1);
+				stats.history.record_restart(Some(d), "device errored");
 			}
+			stats.history.update_errors();
 			stats.mining_stats.device_stats = Some(device_vec);
+			stats.mining_stats.add_gps_samples(clock::mono_secs());
+		}
+	}
+
+	/// Log each device's part of the shares against its part of the GPS
+	fn fairness_report(&self) {
+		let stats = self.stats.read().unwrap();
+		let fairness = stats.session_stats.fairness();
+		if fairness.iter().all(|f| f.shares_accepted + f.shares_rejected == 0) {
+			return;
+		}
+		info!(LOGGER, "Share fairness report:");
+		for (f, line) in fairness.iter().zip(stats.session_stats.fairness_report()) {
+			if f.suspect {
+				warn!(LOGGER, "  {}", line);
+			} else {
+				info!(LOGGER, "  {}", line);
+			}
 		}
 	}
 
+	fn update_alerts(&mut self) {
+		let mut stats = self.stats.write().unwrap();
+		self.alert_monitor.update(&mut stats);
+	}
+
 	fn stop_job(&mut self){
+		if let Some(ref mut sandbox) = self.sandbox {
+			sandbox.stop_job();
+		}
 		if self.job_handle.is_none() {
 			return;
 		}
@@ -0,0 +1,343 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test and qualification support: an in-process stratum pool speaking the protocol the
+//! client does, serving a scripted sequence of jobs, accepting or
+//! rejecting submits as scripted (stale ones rejected as the grin
+//! stratum server does), and able to push jobs and drop the connection
+//! mid-session. Plus a mock solver, answering every job the client hands
+//! to the miner with a share for it.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+
+use types::{ClientMessage, JobTemplate, MinerMessage, RpcRequest, RpcResponse, SubmitParams,
+            WorkerStatus};
+
+/// Error code the grin stratum server rejects shares for old jobs with
+pub const STALE_CODE: i32 = -32503;
+
+/// How the pool answers a submit
+#[derive(Clone, Debug)]
+pub enum Reply {
+	Accept,
+	/// Accept, and report the share found a block
+	BlockFound,
+	/// Reject with a code and message
+	Reject(i32, String),
+}
+
+/// What the pool does: the jobs served to getjobtemplate in order (the
+/// last one repeating), and how submits are answered
+pub struct Script {
+	pub jobs: Vec<JobTemplate>,
+	/// Replies to submits in order, then accept. Shares for a job other
+	/// than the current one are rejected as stale regardless.
+	pub replies: Vec<Reply>,
+}
+
+impl Script {
+	/// Serve the given jobs, accepting every share for the current one
+	#[cfg(test)]
+	pub fn jobs(jobs: Vec<JobTemplate>) -> Script {
+		Script {
+			jobs: jobs,
+			replies: vec![],
+		}
+	}
+}
+
+/// A job for the tests
+pub fn job(height: u64, job_id: u64) -> JobTemplate {
+	JobTemplate {
+		height: height,
+		job_id: job_id,
+		difficulty: 1,
+		pre_pow: format!("{:0>16x}", height),
+		edge_bits: None,
+		network_difficulty: None,
+	}
+}
+
+/// What the pool has seen, and its script
+struct State {
+	script: Script,
+	next_job: usize,
+	next_reply: usize,
+	current_job_id: Option<u64>,
+	current_height: u64,
+	logins: usize,
+	connections: usize,
+	submits: Vec<SubmitParams>,
+	accepted: u64,
+	rejected: u64,
+	stale: u64,
+	/// Write half of the current connection, to push jobs or drop it
+	conn: Option<TcpStream>,
+}
+
+/// A running mock pool, listening on localhost until the process exits
+pub struct MockPool {
+	addr: String,
+	state: Arc<Mutex<State>>,
+}
+
+impl MockPool {
+	/// Start the pool on a free port
+	pub fn start(script: Script) -> MockPool {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap().to_string();
+		let state = Arc::new(Mutex::new(State {
+			script: script,
+			next_job: 0,
+			next_reply: 0,
+			current_job_id: None,
+			current_height: 0,
+			logins: 0,
+			connections: 0,
+			submits: vec![],
+			accepted: 0,
+			rejected: 0,
+			stale: 0,
+			conn: None,
+		}));
+		let accept_state = state.clone();
+		thread::spawn(move || {
+			for conn in listener.incoming() {
+				let conn = match conn {
+					Ok(c) => c,
+					Err(_) => continue,
+				};
+				{
+					let mut s = accept_state.lock().unwrap();
+					s.connections += 1;
+					s.conn = conn.try_clone().ok();
+				}
+				let state = accept_state.clone();
+				thread::spawn(move || serve(conn, state));
+			}
+		});
+		MockPool {
+			addr: addr,
+			state: state,
+		}
+	}
+
+	/// Address to point the client at
+	pub fn addr(&self) -> String {
+		self.addr.clone()
+	}
+
+	/// Push a new job to the connected client, making it the current one
+	pub fn push_job(&self, job: JobTemplate) {
+		let mut s = self.state.lock().unwrap();
+		s.current_job_id = Some(job.job_id);
+		s.current_height = job.height;
+		let req = RpcRequest {
+			id: "Stratum".to_string(),
+			jsonrpc: "2.0".to_string(),
+			method: "job".to_string(),
+			params: Some(serde_json::to_value(job).unwrap()),
+		};
+		if let Some(ref mut conn) = s.conn {
+			let _ = writeln!(conn, "{}", serde_json::to_string(&req).unwrap());
+		}
+	}
+
+	/// Drop the current connection, as a pool restarting would
+	#[cfg(test)]
+	pub fn disconnect(&self) {
+		if let Some(conn) = self.state.lock().unwrap().conn.take() {
+			let _ = conn.shutdown(::std::net::Shutdown::Both);
+		}
+	}
+
+	/// Shares submitted so far
+	pub fn submits(&self) -> Vec<(u64, u64)> {
+		self.state
+			.lock()
+			.unwrap()
+			.submits
+			.iter()
+			.map(|s| (s.height, s.job_id))
+			.collect()
+	}
+
+	/// Accepted, rejected and stale share counts
+	pub fn share_counts(&self) -> (u64, u64, u64) {
+		let s = self.state.lock().unwrap();
+		(s.accepted, s.rejected, s.stale)
+	}
+
+	#[cfg(test)]
+	pub fn logins(&self) -> usize {
+		self.state.lock().unwrap().logins
+	}
+
+	#[cfg(test)]
+	pub fn connections(&self) -> usize {
+		self.state.lock().unwrap().connections
+	}
+}
+
+/// Answer the requests of one connection until it closes
+fn serve(conn: TcpStream, state: Arc<Mutex<State>>) {
+	let mut writer = match conn.try_clone() {
+		Ok(w) => w,
+		Err(_) => return,
+	};
+	for line in BufReader::new(conn).lines() {
+		let line = match line {
+			Ok(l) => l,
+			Err(_) => return,
+		};
+		let req: RpcRequest = match serde_json::from_str(&line) {
+			Ok(r) => r,
+			Err(_) => continue,
+		};
+		let (result, error) = handle(&req, &mut state.lock().unwrap());
+		let res = RpcResponse {
+			id: req.id,
+			method: req.method,
+			jsonrpc: "2.0".to_string(),
+			result: result,
+			error: error,
+		};
+		if writeln!(writer, "{}", serde_json::to_string(&res).unwrap()).is_err() {
+			return;
+		}
+	}
+}
+
+fn error(code: i32, message: &str) -> Option<Value> {
+	Some(json!({ "code": code, "message": message }))
+}
+
+/// Result or error for a request
+fn handle(req: &RpcRequest, s: &mut State) -> (Option<Value>, Option<Value>) {
+	match req.method.as_str() {
+		"login" => {
+			s.logins += 1;
+			(Some(json!("ok")), None)
+		}
+		"keepalive" => (Some(json!("ok")), None),
+		"getjobtemplate" => {
+			if s.script.jobs.is_empty() {
+				return (None, error(-32000, "Node is syncing - Please wait"));
+			}
+			let i = s.next_job.min(s.script.jobs.len() - 1);
+			s.next_job += 1;
+			let job = &s.script.jobs[i];
+			s.current_job_id = Some(job.job_id);
+			s.current_height = job.height;
+			(Some(serde_json::to_value(job).unwrap()), None)
+		}
+		"submit" => {
+			let submit: SubmitParams = match req.params.clone().map(serde_json::from_value) {
+				Some(Ok(p)) => p,
+				_ => return (None, error(-32600, "Invalid Request")),
+			};
+			let stale = Some(submit.job_id) != s.current_job_id;
+			s.submits.push(submit);
+			if stale {
+				s.stale += 1;
+				return (None, error(STALE_CODE, "Solution submitted too late"));
+			}
+			let reply = s.script
+				.replies
+				.get(s.next_reply)
+				.cloned()
+				.unwrap_or(Reply::Accept);
+			s.next_reply += 1;
+			match reply {
+				Reply::Accept => {
+					s.accepted += 1;
+					(Some(json!("ok")), None)
+				}
+				Reply::BlockFound => {
+					s.accepted += 1;
+					(Some(json!("blockfound - 0000")), None)
+				}
+				Reply::Reject(code, message) => {
+					s.rejected += 1;
+					(None, error(code, &message))
+				}
+			}
+		}
+		"status" => {
+			let status = WorkerStatus {
+				id: "0".to_string(),
+				height: s.current_height,
+				difficulty: 1,
+				accepted: s.accepted,
+				rejected: s.rejected,
+				stale: s.stale,
+			};
+			(Some(serde_json::to_value(status).unwrap()), None)
+		}
+		_ => (None, error(-32601, "Method not found")),
+	}
+}
+
+/// Stand in for the miner: answer every job the client hands over with a
+/// share for it, until the channel closes. Jobs listed in `hold` aren't
+/// answered until the next job arrives, and the share then still names
+/// the held job, as a slow solver finishing an old graph would.
+pub fn mock_solver(
+	miner_rx: mpsc::Receiver<MinerMessage>,
+	client_tx: mpsc::Sender<ClientMessage>,
+	hold: Vec<u64>,
+) {
+	thread::spawn(move || {
+		let mut held: Option<(u64, u64)> = None;
+		for message in miner_rx {
+			if let MinerMessage::ReceivedJob(height, job_id, _, _, _) = message {
+				if let Some((h, j)) = held.take() {
+					let _ = client_tx.send(ClientMessage::FoundSolution(h, j, 29, 0, vec![0; 42]));
+				}
+				if hold.contains(&job_id) {
+					held = Some((height, job_id));
+					continue;
+				}
+				let _ = client_tx.send(ClientMessage::FoundSolution(
+					height,
+					job_id,
+					29,
+					job_id,
+					(0..42).collect(),
+				));
+			}
+		}
+	});
+}
+
+/// Wait up to the timeout for a condition, returning whether it held
+pub fn wait_for<F>(timeout_secs: u64, mut condition: F) -> bool
+where
+	F: FnMut() -> bool,
+{
+	let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+	while Instant::now() < deadline {
+		if condition() {
+			return true;
+		}
+		thread::sleep(Duration::from_millis(50));
+	}
+	condition()
+}
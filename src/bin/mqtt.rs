@@ -0,0 +1,322 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal MQTT (3.1.1, QoS 0) client, publishing stats to a topic tree
+//! and accepting commands on a command topic
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use clock;
+use config::MqttConfig;
+use control::Control;
+use stats::{self, Stats};
+use util::LOGGER;
+
+/// Keep alive interval announced to the broker, in seconds
+const KEEP_ALIVE: u16 = 60;
+
+/// Seconds to wait before reconnecting to the broker
+const RETRY_INTERVAL: u64 = 10;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const PINGREQ: u8 = 0xc0;
+
+fn encode_string(buf: &mut Vec<u8>, s: &str) {
+	buf.push((s.len() >> 8) as u8);
+	buf.push(s.len() as u8);
+	buf.extend_from_slice(s.as_bytes());
+}
+
+/// Packet with the given fixed header type/flags byte, and contents
+fn packet(header: u8, contents: &[u8]) -> Vec<u8> {
+	let mut packet = vec![header];
+	// remaining length, 7 bits per byte
+	let mut len = contents.len();
+	loop {
+		let mut byte = (len % 128) as u8;
+		len /= 128;
+		if len > 0 {
+			byte |= 0x80;
+		}
+		packet.push(byte);
+		if len == 0 {
+			break;
+		}
+	}
+	packet.extend_from_slice(contents);
+	packet
+}
+
+fn connect_packet(config: &MqttConfig) -> Vec<u8> {
+	let mut contents = vec![];
+	encode_string(&mut contents, "MQTT");
+	contents.push(4); // protocol level 3.1.1
+	let mut flags = 0x02; // clean session
+	if config.username.is_some() {
+		flags |= 0x80;
+	}
+	if config.password.is_some() {
+		flags |= 0x40;
+	}
+	contents.push(flags);
+	contents.push((KEEP_ALIVE >> 8) as u8);
+	contents.push(KEEP_ALIVE as u8);
+	encode_string(&mut contents, &config.client_id);
+	if let Some(ref u) = config.username {
+		encode_string(&mut contents, u);
+	}
+	if let Some(ref p) = config.password {
+		encode_string(&mut contents, p);
+	}
+	packet(CONNECT, &contents)
+}
+
+fn publish_packet(topic: &str, payload: &str) -> Vec<u8> {
+	let mut contents = vec![];
+	encode_string(&mut contents, topic);
+	contents.extend_from_slice(payload.as_bytes());
+	packet(PUBLISH, &contents)
+}
+
+fn subscribe_packet(topic: &str) -> Vec<u8> {
+	let mut contents = vec![0, 1]; // packet id
+	encode_string(&mut contents, topic);
+	contents.push(0); // QoS 0
+	packet(SUBSCRIBE, &contents)
+}
+
+/// Read a packet, returning its fixed header byte and contents, or None
+/// if there's nothing to read yet
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<(u8, Vec<u8>)>> {
+	let mut header = [0u8; 1];
+	match stream.read(&mut header) {
+		Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+		Ok(_) => {}
+		Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+			return Ok(None)
+		}
+		Err(e) => return Err(e),
+	}
+	let mut len = 0usize;
+	let mut multiplier = 1;
+	loop {
+		let mut byte = [0u8; 1];
+		stream.read_exact(&mut byte)?;
+		len += (byte[0] & 0x7f) as usize * multiplier;
+		multiplier *= 128;
+		if byte[0] & 0x80 == 0 {
+			break;
+		}
+	}
+	let mut contents = vec![0u8; len];
+	stream.read_exact(&mut contents)?;
+	Ok(Some((header[0], contents)))
+}
+
+/// Topic and payload of a received PUBLISH packet
+fn parse_publish(header: u8, contents: &[u8]) -> Option<(String, String)> {
+	if contents.len() < 2 {
+		return None;
+	}
+	let topic_len = ((contents[0] as usize) << 8) | contents[1] as usize;
+	let mut pos = 2 + topic_len;
+	// packet id, for QoS > 0
+	if header & 0x06 != 0 {
+		pos += 2;
+	}
+	if contents.len() < pos {
+		return None;
+	}
+	let topic = String::from_utf8_lossy(&contents[2..2 + topic_len]).to_string();
+	let payload = String::from_utf8_lossy(&contents[pos..]).to_string();
+	Some((topic, payload))
+}
+
+/// Topics and values to publish for the current stats
+fn stats_messages(stats: &Stats, base: &str) -> Vec<(String, String)> {
+	let mut messages = vec![
+		(format!("{}/status", base), stats.mining_status().0),
+		(format!("{}/gps", base), format!("{:.4}", stats.mining_stats.combined_gps)),
+		(format!("{}/height", base), stats.mining_stats.block_height.to_string()),
+		(format!("{}/connected", base), stats.client_stats.connected.to_string()),
+		(
+			format!("{}/shares/accepted", base),
+			stats.client_stats.shares_accepted.to_string(),
+		),
+		(
+			format!("{}/shares/rejected", base),
+			stats.client_stats.shares_rejected.to_string(),
+		),
+		(format!("{}/shares/stale", base), stats.client_stats.shares_stale.to_string()),
+	];
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		let mut n = 0;
+		for (i, plugin) in plugins.iter().enumerate() {
+			for d in plugin.iter().filter(|d| d.in_use == 1) {
+				let device = format!("{}/devices/{}", base, n);
+				messages.push((format!("{}/name", device), d.device_name.clone()));
+				messages.push((
+					format!("{}/status", device),
+					match d.has_errored {
+						0 => "OK".to_string(),
+						_ => "Errored".to_string(),
+					},
+				));
+				if d.last_solution_time > 0 {
+					let gps = stats::device_gps(d);
+					messages.push((format!("{}/gps", device), format!("{:.4}", gps)));
+				}
+				if let Some(h) = stats.mining_stats.hardware_for(i, &d.device_id) {
+					if let Some(t) = h.temperature {
+						messages.push((format!("{}/temperature", device), t.to_string()));
+					}
+					if let Some(f) = h.fan_speed {
+						messages.push((format!("{}/fan_speed", device), f.to_string()));
+					}
+					if let Some(p) = h.power_usage {
+						messages.push((format!("{}/power_usage", device), p.to_string()));
+					}
+				}
+				n += 1;
+			}
+		}
+	}
+	messages
+}
+
+/// MQTT publisher and command subscriber
+pub struct MqttClient {
+	config: MqttConfig,
+	stats: Arc<RwLock<Stats>>,
+	control: Control,
+}
+
+impl MqttClient {
+	/// Create a new MQTT client
+	pub fn new(config: MqttConfig, stats: Arc<RwLock<Stats>>, control: Control) -> MqttClient {
+		MqttClient {
+			config: config,
+			stats: stats,
+			control: control,
+		}
+	}
+
+	fn base_topic(&self) -> String {
+		format!("{}/{}", self.config.topic_prefix, self.config.rig)
+	}
+
+	/// Start the client in a new thread, reconnecting to the broker
+	/// whenever the connection is lost
+	pub fn start(self) {
+		let _ = thread::Builder::new()
+			.name("mqtt".to_string())
+			.spawn(move || loop {
+				if let Err(e) = self.run() {
+					warn!(
+						LOGGER,
+						"MQTT connection to {} lost: {}, retrying in {} seconds",
+						self.config.broker,
+						e,
+						RETRY_INTERVAL
+					);
+				}
+				thread::sleep(Duration::from_secs(RETRY_INTERVAL));
+			});
+	}
+
+	fn run(&self) -> io::Result<()> {
+		let mut stream = TcpStream::connect(self.config.broker.as_str())?;
+		stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+		stream.write_all(&connect_packet(&self.config))?;
+		match read_packet(&mut stream)? {
+			Some((h, ref c)) if h == CONNACK && c.len() == 2 && c[1] == 0 => {}
+			Some((h, c)) => {
+				return Err(io::Error::new(
+					ErrorKind::Other,
+					format!("connection refused ({:x} {:?})", h, c),
+				))
+			}
+			None => return Err(io::Error::new(ErrorKind::TimedOut, "no CONNACK")),
+		}
+		let command_topic = format!("{}/command", self.base_topic());
+		stream.write_all(&subscribe_packet(&command_topic))?;
+		info!(
+			LOGGER,
+			"Connected to MQTT broker {}, listening for commands on {}",
+			self.config.broker,
+			command_topic
+		);
+
+		stream.set_read_timeout(Some(Duration::from_millis(500)))?;
+		let mut next_publish = clock::mono_secs();
+		let mut next_ping = clock::mono_secs() + KEEP_ALIVE as i64 / 2;
+		loop {
+			let now = clock::mono_secs();
+			if now >= next_publish {
+				let messages = {
+					let stats = self.stats.read().unwrap();
+					stats_messages(&stats, &self.base_topic())
+				};
+				for (topic, payload) in messages {
+					stream.write_all(&publish_packet(&topic, &payload))?;
+				}
+				next_publish = now + self.config.interval_secs.max(1) as i64;
+			}
+			if now >= next_ping {
+				stream.write_all(&packet(PINGREQ, &[]))?;
+				next_ping = now + KEEP_ALIVE as i64 / 2;
+			}
+			if let Some((header, contents)) = read_packet(&mut stream)? {
+				if header & 0xf0 == PUBLISH {
+					if let Some((topic, payload)) = parse_publish(header, &contents) {
+						if topic == command_topic {
+							self.command(&payload);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	/// Run a command, e.g. "pause" or
+	/// "resume {"plugin": 0, "device": 1}"
+	fn command(&self, payload: &str) {
+		let payload = payload.trim();
+		let (command, params) = match payload.find(' ') {
+			Some(i) => (&payload[..i], &payload[i + 1..]),
+			None => (payload, ""),
+		};
+		if !self.config.commands.iter().any(|c| c == command) {
+			warn!(
+				LOGGER,
+				"MQTT command {} refused, not in [mqtt] commands", command
+			);
+			return;
+		}
+		let response = self.control.handle(command, params);
+		if response.code != 200 {
+			warn!(
+				LOGGER,
+				"MQTT command {} failed: {}", payload, response.body
+			);
+		}
+	}
+}
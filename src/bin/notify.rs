@@ -0,0 +1,187 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Webhook notifications (generic JSON, Slack, Discord, Telegram) on
+//! alerts and found blocks
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+use time;
+
+use alerts::AlertKind;
+use clock;
+use config::{NotificationConfig, RigConfig, WebhookConfig};
+use http;
+use stats::Stats;
+use util::LOGGER;
+
+/// Seconds between checks for new events
+const CHECK_INTERVAL: u64 = 5;
+
+/// Something worth notifying about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+enum Event {
+	Alert(AlertKind),
+	BlockFound,
+}
+
+/// Generic JSON notification body
+#[derive(Serialize)]
+struct JsonNotification<'a> {
+	event: Event,
+	message: &'a str,
+	time: i64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	rig: Option<&'a str>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	labels: &'a BTreeMap<String, String>,
+}
+
+fn notification_body(
+	webhook: &WebhookConfig,
+	rig: &RigConfig,
+	event: Event,
+	message: &str,
+) -> String {
+	let text = match rig.name {
+		Some(ref name) => format!("Grin Miner ({}): {}", name, message),
+		None => format!("Grin Miner: {}", message),
+	};
+	let mut body = HashMap::new();
+	match webhook.format.as_str() {
+		"slack" => {
+			body.insert("text", text);
+		}
+		"discord" => {
+			body.insert("content", text);
+		}
+		"telegram" => {
+			body.insert("chat_id", webhook.chat_id.clone().unwrap_or_default());
+			body.insert("text", text);
+		}
+		_ => {
+			return serde_json::to_string(&JsonNotification {
+				event: event,
+				message: message,
+				time: time::get_time().sec,
+				rig: rig.name.as_ref().map(|n| n.as_str()),
+				labels: &rig.labels,
+			}).unwrap()
+		}
+	}
+	serde_json::to_string(&body).unwrap()
+}
+
+/// Watches the stats for alerts and found blocks, and notifies the
+/// configured webhooks
+pub struct Notifier {
+	config: NotificationConfig,
+	stats: Arc<RwLock<Stats>>,
+	/// alerts already notified (or skipped), by kind and start time
+	notified: Vec<(AlertKind, i64)>,
+	/// when each kind of event was last notified, for rate limiting
+	last_sent: HashMap<Event, i64>,
+	blocks_found: u64,
+}
+
+impl Notifier {
+	/// Create a new notifier
+	pub fn new(config: NotificationConfig, stats: Arc<RwLock<Stats>>) -> Notifier {
+		Notifier {
+			config: config,
+			stats: stats,
+			notified: vec![],
+			last_sent: HashMap::new(),
+			blocks_found: 0,
+		}
+	}
+
+	/// Start checking for events in a new thread
+	pub fn start(mut self) {
+		let _ = thread::Builder::new()
+			.name("notifier".to_string())
+			.spawn(move || loop {
+				thread::sleep(Duration::from_secs(CHECK_INTERVAL));
+				for (event, message) in self.new_events() {
+					self.send(event, &message);
+				}
+			});
+	}
+
+	fn new_events(&mut self) -> Vec<(Event, String)> {
+		let stats = self.stats.read().unwrap();
+		let mut events = vec![];
+		// forget alerts once their condition is over
+		self.notified
+			.retain(|n| stats.alerts.iter().any(|a| a.kind == n.0 && a.since == n.1));
+		for a in &stats.alerts {
+			if !self.notified.contains(&(a.kind, a.since)) {
+				self.notified.push((a.kind, a.since));
+				events.push((Event::Alert(a.kind), a.message.clone()));
+			}
+		}
+		if stats.client_stats.blocks_found > self.blocks_found {
+			let new = (stats.client_stats.blocks_found - self.blocks_found) as usize;
+			self.blocks_found = stats.client_stats.blocks_found;
+			let skip = stats.blocks.len() - new.min(stats.blocks.len());
+			for b in stats.blocks.iter().skip(skip) {
+				let mut message = format!("Block found at height {}", b.height);
+				if let Some((plugin, ref device)) = b.device {
+					message.push_str(&format!(" by plugin {} device {}", plugin, device));
+				}
+				message.push('!');
+				if let Some(ref hash) = b.hash {
+					message.push_str(&format!(" Hash: {}", hash));
+				}
+				events.push((Event::BlockFound, message));
+			}
+		}
+		events
+	}
+
+	fn send(&mut self, event: Event, message: &str) {
+		let now = clock::mono_secs();
+		if event != Event::BlockFound {
+			if let Some(last) = self.last_sent.get(&event) {
+				if now - last < self.config.min_interval_secs {
+					debug!(LOGGER, "Not notifying {:?}, rate limited: {}", event, message);
+					return;
+				}
+			}
+		}
+		self.last_sent.insert(event, now);
+		let rig = self.stats.read().unwrap().rig.clone();
+		// webhook urls often embed a secret, so they're not logged
+		for (i, webhook) in self.config.webhooks.iter().enumerate() {
+			let body = notification_body(webhook, &rig, event, message);
+			match http::post(&webhook.url, "application/json", &body) {
+				Ok(code) if code < 300 => {
+					debug!(LOGGER, "Notified {:?} to webhook {}", event, i);
+				}
+				Ok(code) => warn!(
+					LOGGER,
+					"Notification to webhook {} ({}) failed with status {}", i, webhook.format, code
+				),
+				Err(e) => warn!(
+					LOGGER,
+					"Notification to webhook {} ({}) failed: {}", i, webhook.format, e
+				),
+			}
+		}
+	}
+}
@@ -0,0 +1,78 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pacing of the loops polling the solvers, in the mining controller and
+//! the solver workers, as configured in [mining]
+
+use std::thread;
+use std::time::Duration;
+
+use config::MinerConfig;
+
+/// Default milliseconds between polls
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 100;
+
+/// Default milliseconds between polls while idle
+pub const DEFAULT_IDLE_POLL_INTERVAL_MS: u64 = 500;
+
+/// Default seconds between device stats updates
+pub const DEFAULT_STATS_INTERVAL_SECS: u64 = 2;
+
+/// How often to poll the solvers and update the stats
+#[derive(Clone, Copy, Debug)]
+pub struct Pacing {
+	poll_interval: Duration,
+	idle_poll_interval: Duration,
+	stats_interval_secs: i64,
+}
+
+impl Pacing {
+	/// Pacing as configured
+	pub fn new(config: &MinerConfig) -> Pacing {
+		Pacing {
+			poll_interval: Duration::from_millis(
+				config.poll_interval_ms.unwrap_or(DEFAULT_POLL_INTERVAL_MS),
+			),
+			idle_poll_interval: Duration::from_millis(
+				config
+					.idle_poll_interval_ms
+					.unwrap_or(DEFAULT_IDLE_POLL_INTERVAL_MS),
+			),
+			stats_interval_secs: config
+				.stats_interval_secs
+				.unwrap_or(DEFAULT_STATS_INTERVAL_SECS)
+				.max(1) as i64,
+		}
+	}
+
+	/// Wait until the next poll, longer when idle (paused or without a
+	/// job)
+	pub fn wait(&self, idle: bool) {
+		let interval = if idle {
+			self.idle_poll_interval
+		} else {
+			self.poll_interval
+		};
+		if interval == Duration::from_millis(0) {
+			thread::yield_now();
+		} else {
+			thread::sleep(interval);
+		}
+	}
+
+	/// Seconds between device stats updates
+	pub fn stats_interval_secs(&self) -> i64 {
+		self.stats_interval_secs
+	}
+}
@@ -0,0 +1,209 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host thread placement, pinning the threads driving GPUs to the CPUs of
+//! the NUMA node their PCIe slots hang off, as read from sysfs
+
+use std::fs;
+use std::path::Path;
+
+use config::types::CuckooMinerPluginConfig;
+use config::MinerConfig;
+use plugin;
+use util::LOGGER;
+
+/// NUMA node of a PCI device, none if the system has a single node
+fn numa_node(bus_id: &str) -> Option<u32> {
+	let path = Path::new("/sys/bus/pci/devices").join(bus_id).join("numa_node");
+	fs::read_to_string(path)
+		.ok()
+		.and_then(|s| s.trim().parse::<i32>().ok())
+		.and_then(|n| if n < 0 { None } else { Some(n as u32) })
+}
+
+/// Whether the system has more than one NUMA node
+fn is_numa() -> bool {
+	Path::new("/sys/devices/system/node/node1").exists()
+}
+
+/// Parse a CPU list as written in sysfs and taskset, e.g. "0-7,16-23"
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, String> {
+	let invalid = || format!("Invalid CPU list {}, expected e.g. 0-7,16-23", list);
+	let mut cpus = vec![];
+	for part in list.trim().split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+		let mut bounds = part.splitn(2, '-');
+		let first = bounds
+			.next()
+			.and_then(|b| b.trim().parse::<usize>().ok())
+			.ok_or_else(invalid)?;
+		let last = match bounds.next() {
+			Some(b) => b.trim().parse::<usize>().map_err(|_| invalid())?,
+			None => first,
+		};
+		if last < first {
+			return Err(invalid());
+		}
+		cpus.extend(first..last + 1);
+	}
+	if cpus.is_empty() {
+		return Err(invalid());
+	}
+	cpus.sort();
+	cpus.dedup();
+	Ok(cpus)
+}
+
+/// CPUs of a NUMA node
+fn node_cpus(node: u32) -> Vec<usize> {
+	fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node))
+		.ok()
+		.and_then(|l| parse_cpu_list(&l).ok())
+		.unwrap_or_default()
+}
+
+/// A CPU list in its short form, e.g. "0-7,16-23"
+fn format_cpu_list(cpus: &[usize]) -> String {
+	let mut ranges: Vec<(usize, usize)> = vec![];
+	for &cpu in cpus {
+		match ranges.last_mut() {
+			Some(r) if r.1 + 1 == cpu => r.1 = cpu,
+			_ => ranges.push((cpu, cpu)),
+		}
+	}
+	ranges
+		.iter()
+		.map(|&(a, b)| if a == b { format!("{}", a) } else { format!("{}-{}", a, b) })
+		.collect::<Vec<_>>()
+		.join(",")
+}
+
+/// Pin the calling thread, and the threads it starts from then on, to
+/// the given CPUs
+#[cfg(target_os = "linux")]
+fn pin_current_thread(cpus: &[usize]) -> Result<(), String> {
+	use libc;
+	use std::mem;
+	unsafe {
+		let mut set: libc::cpu_set_t = mem::zeroed();
+		for &cpu in cpus {
+			libc::CPU_SET(cpu, &mut set);
+		}
+		if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+			return Err(format!("{}", ::std::io::Error::last_os_error()));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_cpus: &[usize]) -> Result<(), String> {
+	Err("thread pinning is only supported on Linux".to_string())
+}
+
+/// Devices a plugin mines on: those with USE_DEVICE set, or all those it
+/// has parameters for, device 0 if it has none
+pub fn mining_devices(config: &CuckooMinerPluginConfig) -> Vec<u32> {
+	let params = config.device_parameters.clone().unwrap_or_default();
+	let mut devices: Vec<u32> = params
+		.iter()
+		.filter(|&(_, p)| p.get("USE_DEVICE") != Some(&0))
+		.filter_map(|(d, _)| d.parse::<u32>().ok())
+		.collect();
+	if devices.is_empty() {
+		devices.push(0);
+	}
+	devices.sort();
+	devices
+}
+
+/// Where a plugin's host threads belong: its configured cpu_affinity, or
+/// the CPUs of the NUMA node all its GPUs are on. None if it has no
+/// single placement (a CPU plugin, or GPUs on different nodes).
+fn plugin_placement(index: usize, config: &CuckooMinerPluginConfig) -> Option<(Vec<usize>, String)> {
+	if let Some(ref list) = config.cpu_affinity {
+		return match parse_cpu_list(list) {
+			Ok(cpus) => Some((cpus, "configured cpu_affinity".to_string())),
+			Err(e) => {
+				warn!(LOGGER, "Cuckoo plugin {}: {}", index, e);
+				None
+			}
+		};
+	}
+	let bus_ids = plugin::pci_bus_ids(config).ok()?;
+	let mut nodes = vec![];
+	let mut near = vec![];
+	for device in mining_devices(config) {
+		let bus_id = bus_ids.iter().find(|b| b.0 == device)?.1.clone();
+		nodes.push(numa_node(&bus_id)?);
+		near.push(bus_id);
+	}
+	nodes.dedup();
+	if nodes.len() != 1 {
+		return None;
+	}
+	let cpus = node_cpus(nodes[0]);
+	if cpus.is_empty() {
+		return None;
+	}
+	Some((cpus, format!("NUMA node {}, near {}", nodes[0], near.join(", "))))
+}
+
+/// Pin the calling thread, and so the solver threads it starts, near the
+/// GPUs of the configured plugins, when they all share a placement. With
+/// sandbox_plugins this runs in each worker, so each device's solver is
+/// placed on its own.
+pub fn apply(config: &MinerConfig) {
+	if config.numa_pinning == Some(false) {
+		return;
+	}
+	let configured = config.miner_plugin_config.iter().any(|p| p.cpu_affinity.is_some());
+	if !configured && !is_numa() {
+		return;
+	}
+	let placements: Vec<Option<(Vec<usize>, String)>> = config
+		.miner_plugin_config
+		.iter()
+		.enumerate()
+		.map(|(i, p)| plugin_placement(i, p))
+		.collect();
+	let first = match placements.first() {
+		Some(&Some(ref p)) => p.clone(),
+		_ => {
+			debug!(LOGGER, "No NUMA placement for the solver threads, leaving them unpinned");
+			return;
+		}
+	};
+	if placements
+		.iter()
+		.any(|p| p.as_ref().map_or(true, |p| p.0 != first.0))
+	{
+		warn!(
+			LOGGER,
+			"Solver devices are on different NUMA nodes and can't be pinned together, \
+			 set sandbox_plugins and switch devices on with USE_DEVICE so each gets a \
+			 worker placed near its GPU"
+		);
+		return;
+	}
+	let (cpus, reason) = first;
+	match pin_current_thread(&cpus) {
+		Ok(()) => info!(
+			LOGGER,
+			"Solver threads pinned to CPUs {} ({})",
+			format_cpu_list(&cpus),
+			reason
+		),
+		Err(e) => warn!(LOGGER, "Unable to pin the solver threads to CPUs {}: {}", format_cpu_list(&cpus), e),
+	}
+}
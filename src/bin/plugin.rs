@@ -18,14 +18,36 @@
 //! with cuckoo-miner at present
 
 use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
-use config::types::MinerConfig;
-use util::LOGGER;
+use libloading::Library;
+use sha2::{Digest, Sha256};
+
+use config::types::{CuckooMinerPluginConfig, MinerConfig, PCI_PREFIX};
+use errors::{self, MinerError};
+use hardware;
+use intensity;
+use placement;
+use tuning;
+use util::{self, LOGGER};
 
 use std::sync::Mutex;
 
 use cuckoo::{CuckooMiner, CuckooMinerConfig, CuckooMinerDeviceStats, CuckooMinerError,
-                   CuckooMinerSolution, CuckooPluginManager};
+                   CuckooMinerSolution, CuckooPluginCapabilities, CuckooPluginManager};
+
+/// The plugins failing to initialize
+fn plugin_load_failed(e: CuckooMinerError) -> MinerError {
+	MinerError::PluginLoad {
+		plugin: None,
+		message: String::from("Unable to initialize the mining plugins"),
+		source: Some(e.into()),
+	}
+}
 
 // For now, we're just going to keep a static reference around to the loaded
 // config
@@ -38,6 +60,407 @@ lazy_static!{
 	static ref LOADED_CONFIG: Mutex<Option<Vec<CuckooMinerConfig>>> = Mutex::new(None);
 }
 
+/// Forget the loaded plugin configuration, so the plugin directory is
+/// queried again the next time a plugin miner is initialised
+pub fn reset_loaded_config() {
+	*LOADED_CONFIG.lock().unwrap() = None;
+}
+
+/// Environment variable naming further plugin directories, separated as
+/// in PATH
+pub const PLUGIN_DIR_ENV: &str = "GRIN_MINER_PLUGIN_DIR";
+
+/// File extensions of plugins
+const PLUGIN_EXTENSIONS: &[&str] = &["cuckooplugin", "so", "dll", "dylib"];
+
+/// Directories to search for plugins, in order: those in
+/// GRIN_MINER_PLUGIN_DIR, miner_plugin_dir, miner_plugin_dirs, then
+/// [exe_path]/plugins
+pub fn plugin_dirs(miner_config: &MinerConfig) -> Vec<String> {
+	let mut dirs: Vec<String> = vec![];
+	if let Some(paths) = env::var_os(PLUGIN_DIR_ENV) {
+		dirs.extend(env::split_paths(&paths).map(|p| p.to_string_lossy().into_owned()));
+	}
+	dirs.extend(miner_config.miner_plugin_dir.iter().cloned());
+	dirs.extend(miner_config.miner_plugin_dirs.iter().flat_map(|d| d.iter().cloned()));
+	// Get directory of executable
+	if let Ok(mut exe_path) = env::current_exe() {
+		exe_path.pop();
+		dirs.push(format!("{}/plugins", exe_path.to_string_lossy()));
+	}
+	let mut unique: Vec<String> = vec![];
+	for d in dirs.into_iter().filter(|d| !d.is_empty()) {
+		if !unique.contains(&d) {
+			unique.push(d);
+		}
+	}
+	unique
+}
+
+/// SHA-256 checksum of a file, as hex
+pub fn file_sha256(path: &Path) -> Result<String, String> {
+	let mut contents = vec![];
+	File::open(path)
+		.and_then(|mut f| f.read_to_end(&mut contents))
+		.map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+	Ok(util::to_hex(Sha256::digest(&contents).to_vec()))
+}
+
+/// Checksum configured for the plugin file of the given name, if any,
+/// from the plugins of exactly that type filter and edge bits. Plugins
+/// configured twice must pin the same checksum.
+fn expected_sha256<'a>(
+	miner_config: &'a MinerConfig,
+	file_name: &str,
+) -> Result<Option<&'a str>, String> {
+	let mut expected: Option<&str> = None;
+	for p in &miner_config.miner_plugin_config {
+		let name = format!("{}_{}", p.type_filter, p.edge_bits);
+		let matches = PLUGIN_EXTENSIONS
+			.iter()
+			.any(|x| file_name == format!("{}.{}", name, x));
+		let sha256 = match p.sha256 {
+			Some(ref s) if matches => s.trim(),
+			_ => continue,
+		};
+		match expected {
+			Some(e) if !e.eq_ignore_ascii_case(sha256) => {
+				return Err(format!(
+					"Plugin {} is configured with SHA-256 {} and {}",
+					file_name, e, sha256
+				))
+			}
+			_ => expected = Some(sha256),
+		}
+	}
+	Ok(expected)
+}
+
+/// Check a plugin file against its configured checksum, if it has one
+fn verify_plugin_file(miner_config: &MinerConfig, path: &Path) -> Result<(), String> {
+	let file_name = path
+		.file_name()
+		.map(|n| n.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	let expected = match expected_sha256(miner_config, &file_name)? {
+		Some(e) => e,
+		None => return Ok(()),
+	};
+	let actual = file_sha256(path)?;
+	if actual.eq_ignore_ascii_case(expected) {
+		Ok(())
+	} else {
+		Err(format!(
+			"Plugin {} has SHA-256 {}, but {} is configured: refusing to load it",
+			path.display(),
+			actual,
+			expected
+		))
+	}
+}
+
+/// Check every plugin file in the plugin directories that has a
+/// configured checksum, before any of them is loaded
+pub fn verify_plugins(miner_config: &MinerConfig) -> Result<(), String> {
+	if miner_config.miner_plugin_config.iter().all(|p| p.sha256.is_none()) {
+		return Ok(());
+	}
+	for dir in plugin_dirs(miner_config) {
+		let entries = match fs::read_dir(&dir) {
+			Ok(e) => e,
+			Err(_) => continue,
+		};
+		for entry in entries.filter_map(|e| e.ok()) {
+			verify_plugin_file(miner_config, &entry.path())?;
+		}
+	}
+	Ok(())
+}
+
+/// Capabilities of all plugins in the plugin directories. Where plugins
+/// of the same name are in more than one, the first directory's is used.
+pub fn available_plugins(miner_config: &MinerConfig) -> Result<Vec<CuckooPluginCapabilities>, String> {
+	verify_plugins(miner_config)?;
+	let dirs = plugin_dirs(miner_config);
+	let mut plugins: Vec<CuckooPluginCapabilities> = vec![];
+	let mut errors = vec![];
+	for dir in dirs.iter().filter(|d| Path::new(d).is_dir()) {
+		let mut plugin_manager =
+			CuckooPluginManager::new().map_err(|e| format!("Unable to load plugins: {:?}", e))?;
+		let found = plugin_manager
+			.load_plugin_dir(dir.clone())
+			.map_err(|e| format!("Unable to load plugin directory {}: {:?}", dir, e))
+			.and_then(|_| {
+				plugin_manager
+					.get_available_plugins("")
+					.map_err(|e| format!("Unable to query plugins in {}: {:?}", dir, e))
+			});
+		match found {
+			Ok(found) => {
+				for p in found {
+					if !plugins.iter().any(|q| q.name == p.name) {
+						plugins.push(p);
+					}
+				}
+			}
+			Err(e) => errors.push(e),
+		}
+	}
+	if plugins.is_empty() {
+		return Err(if errors.is_empty() {
+			format!("No plugins found in {}", dirs.join(", "))
+		} else {
+			errors.join(", ")
+		});
+	}
+	Ok(plugins)
+}
+
+/// A plugin file found in a plugin directory
+pub struct PluginFile {
+	/// Full path of the file
+	pub path: String,
+	/// Whether the library loads, or why it doesn't
+	pub loadable: Result<(), String>,
+}
+
+/// Every plugin file in the plugin directories, and whether it loads
+/// (e.g. it may need a CUDA runtime that isn't installed), along with the
+/// directories that don't exist
+pub fn scan_plugin_dirs(miner_config: &MinerConfig) -> (Vec<PluginFile>, Vec<String>) {
+	let mut files = vec![];
+	let mut missing = vec![];
+	for dir in plugin_dirs(miner_config) {
+		let entries = match fs::read_dir(&dir) {
+			Ok(e) => e,
+			Err(_) => {
+				missing.push(dir);
+				continue;
+			}
+		};
+		let mut paths: Vec<String> = entries
+			.filter_map(|e| e.ok())
+			.map(|e| e.path())
+			.filter(|p| {
+				p.extension()
+					.map_or(false, |e| PLUGIN_EXTENSIONS.iter().any(|x| e == *x))
+			})
+			.map(|p| p.to_string_lossy().into_owned())
+			.collect();
+		paths.sort();
+		for path in paths {
+			let loadable = verify_plugin_file(miner_config, Path::new(&path))
+				.and_then(|_| Library::new(&path).map(|_| ()).map_err(|e| e.to_string()));
+			files.push(PluginFile {
+				path: path,
+				loadable: loadable,
+			});
+		}
+	}
+	(files, missing)
+}
+
+/// Log the plugin files found in the plugin directories, and why any
+/// don't load
+pub fn log_plugin_dirs(miner_config: &MinerConfig) {
+	let (files, missing) = scan_plugin_dirs(miner_config);
+	for dir in missing {
+		debug!(LOGGER, "Plugin directory {} not found", dir);
+	}
+	for f in &files {
+		match f.loadable {
+			Ok(()) => info!(LOGGER, "Found plugin {}", f.path),
+			Err(ref e) => warn!(LOGGER, "Found plugin {}, but it doesn't load: {}", f.path, e),
+		}
+	}
+	if files.is_empty() {
+		warn!(
+			LOGGER,
+			"No plugins found in {}",
+			plugin_dirs(miner_config).join(", ")
+		);
+	}
+}
+
+/// Full paths of the loaded plugins, in plugin index order
+pub fn loaded_plugins() -> Vec<String> {
+	match *LOADED_CONFIG.lock().unwrap() {
+		Some(ref c) => c.iter().map(|p| p.plugin_full_path.clone()).collect(),
+		None => vec![],
+	}
+}
+
+/// Proof sizes the devices of a plugin solve: the size the loaded plugin
+/// file is built for, as in cuckatoo_cuda_31.cuckooplugin, or the
+/// configured edge_bits if it isn't loaded in this process (a sandboxed or
+/// external solver)
+pub fn supported_edge_bits(index: usize, config: &CuckooMinerPluginConfig) -> Vec<u32> {
+	let built_for = loaded_plugins().get(index).and_then(|path| {
+		Path::new(path)
+			.file_stem()
+			.and_then(|s| s.to_str())
+			.and_then(|s| s.rsplit('_').next())
+			.and_then(|bits| bits.parse::<u32>().ok())
+	});
+	vec![built_for.unwrap_or(config.edge_bits as u32)]
+}
+
+/// Environment AMD's OpenCL driver needs to let a single buffer use all
+/// of a GPU's memory, as the mean solvers' do
+const AMD_ENVIRONMENT: &[(&str, &str)] = &[
+	("GPU_FORCE_64BIT_PTR", "1"),
+	("GPU_MAX_HEAP_SIZE", "100"),
+	("GPU_USE_SYNC_OBJECTS", "1"),
+	("GPU_MAX_ALLOC_PERCENT", "100"),
+	("GPU_SINGLE_ALLOC_PERCENT", "100"),
+];
+
+/// Set up the driver environment for AMD GPUs if an OpenCL plugin mines on
+/// them, before the plugins are loaded. Variables already set are left
+/// alone.
+fn prepare_amd_environment(config: &MinerConfig) {
+	if !config.miner_plugin_config.iter().any(|p| p.is_amd()) {
+		return;
+	}
+	for &(name, value) in AMD_ENVIRONMENT {
+		if env::var_os(name).is_none() {
+			debug!(LOGGER, "Setting {}={} for AMD GPUs", name, value);
+			env::set_var(name, value);
+		}
+	}
+}
+
+/// Seconds to give the plugins to bring up their devices
+const DEVICE_STARTUP_SECS: u64 = 3;
+
+/// Load the configured plugins and start a dummy job so they enumerate
+/// their devices, returning the devices of each plugin (or the error
+/// querying them)
+pub fn enumerate_devices(
+	config: &MinerConfig,
+) -> Result<Vec<Result<Vec<CuckooMinerDeviceStats>, String>>, String> {
+	let mut plugin_miner = PluginMiner::new();
+	plugin_miner.init(config.clone());
+	let miner = plugin_miner.get_consumable();
+	// any header will do, nothing is submitted
+	let pre_pow = "00".repeat(186);
+	let mut job_handle = miner
+		.notify(1, &pre_pow, "", 0, false)
+		.map_err(|e| format!("Unable to start plugins: {:?}", e))?;
+	thread::sleep(Duration::from_secs(DEVICE_STARTUP_SECS));
+	let devices = (0..config.miner_plugin_config.len())
+		.map(|i| job_handle.get_stats(i).map_err(|e| format!("{:?}", e)))
+		.collect();
+	job_handle.stop_jobs();
+	Ok(devices)
+}
+
+/// PCI bus ids of the devices of a GPU plugin, by device id: the plugin
+/// vendor's GPUs in PCI bus order, as OpenCL drivers and CUDA (once
+/// hardware::prepare has been called) number them
+pub fn pci_bus_ids(config: &CuckooMinerPluginConfig) -> Result<Vec<(u32, String)>, String> {
+	let vendors = if config.type_filter.contains("cuda") {
+		if !hardware::cuda_pci_order() {
+			return Err(
+				"CUDA devices can only be addressed by PCI bus id with \
+				 CUDA_DEVICE_ORDER=PCI_BUS_ID"
+					.to_string(),
+			);
+		}
+		vec![hardware::PCI_VENDOR_NVIDIA]
+	} else if config.is_opencl() {
+		match config.platform_id() {
+			Ok(Some(1)) => vec![hardware::PCI_VENDOR_AMD],
+			Ok(Some(2)) => vec![hardware::PCI_VENDOR_NVIDIA],
+			_ => vec![hardware::PCI_VENDOR_AMD, hardware::PCI_VENDOR_NVIDIA],
+		}
+	} else {
+		return Err(format!(
+			"plugin {} doesn't mine on GPUs, its devices have no PCI bus id",
+			config.type_filter
+		));
+	};
+	Ok(hardware::gpu_pci_bus_ids(&vendors)
+		.into_iter()
+		.enumerate()
+		.map(|(i, b)| (i as u32, b))
+		.collect())
+}
+
+/// Turn the devices and exclude_devices selections of the plugin
+/// configuration, and devices addressed by PCI bus id, into device
+/// parameters, starting the plugins to list their devices if any
+/// selection needs them
+fn select_devices(mut miner_config: MinerConfig) -> MinerConfig {
+	if miner_config
+		.miner_plugin_config
+		.iter()
+		.all(|p| p.devices.is_none() && p.exclude_devices.is_none() && !p.uses_pci_ids())
+	{
+		return miner_config;
+	}
+	let mut device_lists: Vec<Option<Vec<u32>>> = vec![];
+	if miner_config.miner_plugin_config.iter().any(|p| p.needs_device_list()) {
+		let mut unselected = miner_config.clone();
+		for p in unselected.miner_plugin_config.iter_mut() {
+			p.devices = None;
+			p.exclude_devices = None;
+			if let Some(ref mut params) = p.device_parameters {
+				params.retain(|k, _| !k.starts_with(PCI_PREFIX));
+			}
+		}
+		match enumerate_devices(&unselected) {
+			Ok(lists) => {
+				device_lists = lists
+					.into_iter()
+					.map(|l| {
+						l.ok().map(|devices| {
+							devices
+								.iter()
+								.filter_map(|d| d.device_id.parse::<u32>().ok())
+								.collect()
+						})
+					})
+					.collect()
+			}
+			Err(e) => error!(LOGGER, "Unable to list devices: {}", e),
+		}
+		// load again with the selection applied
+		reset_loaded_config();
+	}
+	let caps = available_plugins(&miner_config).unwrap_or_default();
+	for (i, p) in miner_config.miner_plugin_config.iter_mut().enumerate() {
+		let available = device_lists.get(i).cloned().unwrap_or(None);
+		if p.uses_pci_ids() {
+			let resolved = pci_bus_ids(p).and_then(|bus_ids| {
+				// only the devices the plugin can see
+				let bus_ids: Vec<(u32, String)> = bus_ids
+					.into_iter()
+					.filter(|&(id, _)| available.as_ref().map_or(true, |a| a.contains(&id)))
+					.collect();
+				p.resolve_pci_ids(&bus_ids)
+			});
+			if let Err(e) = resolved {
+				errors::fatal(MinerError::config(&format!("plugin {}: {}", i, e)));
+			}
+		}
+		match p.selected_devices(available.as_ref().map(|a| a.as_slice())) {
+			Ok(Some(selected)) => {
+				let name = format!("{}_{}", p.type_filter, p.edge_bits);
+				let use_device = caps
+					.iter()
+					.filter(|c| c.name.contains(&name))
+					.any(|c| c.parameters.iter().any(|param| param.name == "USE_DEVICE"));
+				info!(LOGGER, "Cuckoo plugin {} mining on devices {:?}", i, selected);
+				p.apply_device_selection(&selected, &available.unwrap_or_default(), use_device);
+			}
+			Ok(None) => {}
+			Err(e) => errors::fatal(MinerError::config(&format!("plugin {}: {}", i, e))),
+		}
+	}
+	miner_config
+}
+
 /// plugin miner
 pub struct PluginMiner {
 	/// the miner
@@ -64,15 +487,12 @@ impl PluginMiner {
 
 	/// Init the plugin miner
 	pub fn init(&mut self, miner_config: MinerConfig) {
-		// Get directory of executable
-		let mut exe_path = env::current_exe().unwrap();
-		exe_path.pop();
-		let exe_path = exe_path.to_str().unwrap();
-		let plugin_install_path = match miner_config.miner_plugin_dir.clone() {
-			Some(s) => s,
-			None => String::from(format!("{}/plugins", exe_path)),
+		let loaded = LOADED_CONFIG.lock().unwrap().is_some();
+		let miner_config = if loaded {
+			miner_config
+		} else {
+			select_devices(miner_config)
 		};
-
 		let mut plugin_vec_filters = Vec::new();
 		for p in miner_config.clone().miner_plugin_config {
 			plugin_vec_filters.push(p.type_filter);
@@ -90,22 +510,19 @@ impl PluginMiner {
 			debug!(LOGGER, "Not re-loading plugin or directory.");
 			// this will load the associated plugin
 			let result = CuckooMiner::new(c.clone());
-			self.miner = Some(result.unwrap());
+			self.miner = Some(result.unwrap_or_else(|e| errors::fatal(plugin_load_failed(e))));
 			self.config = c.clone();
 			return;
 		}
 
-		let mut plugin_manager = CuckooPluginManager::new().unwrap();
-		let result = plugin_manager.load_plugin_dir(plugin_install_path);
-
-		if let Err(_) = result {
-			error!(
-				LOGGER,
-				"Unable to load cuckoo-miner plugin directory, either from configuration or [exe_path]/plugins."
-			);
-			panic!("Unable to load plugin directory... Please check configuration values");
-		}
+		prepare_amd_environment(&miner_config);
+		placement::apply(&miner_config);
+		let available = match available_plugins(&miner_config) {
+			Ok(a) => a,
+			Err(e) => errors::fatal(MinerError::plugin_load(None, &e)),
+		};
 
+		let tuned = tuning::load(&miner_config);
 		let mut cuckoo_configs = Vec::new();
 		let mut index = 0;
 		for f in plugin_vec_filters {
@@ -115,7 +532,14 @@ impl PluginMiner {
 			// type and the consensus sizeshift
 			let filter = format!("{}_{}", f, sz);
 
-			let caps = plugin_manager.get_available_plugins(&filter).unwrap();
+			let caps: Vec<&CuckooPluginCapabilities> =
+				available.iter().filter(|c| c.name.contains(&filter)).collect();
+			if caps.is_empty() {
+				errors::fatal(MinerError::plugin_load(
+					Some(index),
+					&format!("No plugin {} in {}", filter, plugin_dirs(&miner_config).join(", ")),
+				));
+			}
 			// insert it into the miner configuration being created below
 
 			let mut config = CuckooMinerConfig::new();
@@ -127,28 +551,35 @@ impl PluginMiner {
 				caps[0].full_path.clone()
 			);
 			config.plugin_full_path = caps[0].full_path.clone();
-			if let Some(dp) = l[index].device_parameters.clone() {
-				for (device, param_map) in dp.into_iter() {
-					for (param_name, param_value) in param_map.into_iter() {
-						let device_id = match device.parse::<u32>() {
-							Ok(n) => n,
-							Err(e) => {
-								error!(LOGGER, "Error initializing mining plugin: {:?}", e);
-								panic!("Unable to init mining plugin.");
-							}
-						};
-						debug!(
-							LOGGER,
-							"Cuckoo Plugin {}: Setting mining parameter {} to {} on Device {}",
-							index,
-							param_name,
-							param_value,
-							device_id
-						);
-						config
-							.parameter_list
-							.push((param_name, device_id, param_value));
-					}
+			let mut plugin_config = l[index].clone();
+			tuning::apply(index, &mut plugin_config, &tuned);
+			if let Err(e) = intensity::apply(index, &mut plugin_config, caps[0]) {
+				errors::fatal(MinerError::config(&format!("plugin {}: {}", index, e)));
+			}
+			let dp = plugin_config.device_parameters_with_defaults(
+				miner_config.defaults.as_ref(),
+				|name| caps[0].parameters.iter().any(|p| p.name == name),
+			);
+			for (device, param_map) in dp.into_iter() {
+				for (param_name, param_value) in param_map.into_iter() {
+					let device_id = match device.parse::<u32>() {
+						Ok(n) => n,
+						Err(_) => errors::fatal(MinerError::config(&format!(
+							"plugin {}: device_parameters for device {}, not a device number",
+							index, device
+						))),
+					};
+					debug!(
+						LOGGER,
+						"Cuckoo Plugin {}: Setting mining parameter {} to {} on Device {}",
+						index,
+						param_name,
+						param_value,
+						device_id
+					);
+					config
+						.parameter_list
+						.push((param_name, device_id, param_value));
 				}
 			}
 			cuckoo_configs.push(config);
@@ -159,26 +590,29 @@ impl PluginMiner {
 		*loaded_config_ref = Some(cuckoo_configs.clone());
 
 		// this will load the associated plugin
-		let result = CuckooMiner::new(cuckoo_configs.clone());
-		if let Err(e) = result {
-			error!(LOGGER, "Error initializing mining plugin: {:?}", e);
-			// error!(LOGGER, "Accepted values are: {:?}", caps[0].parameters);
-			panic!("Unable to init mining plugin.");
-		}
+		let miner = CuckooMiner::new(cuckoo_configs.clone())
+			.unwrap_or_else(|e| errors::fatal(plugin_load_failed(e)));
 
 		self.config = cuckoo_configs.clone();
-		self.miner = Some(result.unwrap());
+		self.miner = Some(miner);
 	}
 
 	/// Get the miner
 	pub fn get_consumable(&mut self) -> CuckooMiner {
 		// this will load the associated plugin
-		let result = CuckooMiner::new(self.config.clone());
-		if let Err(e) = result {
-			error!(LOGGER, "Error initializing mining plugin: {:?}", e);
-			panic!("Unable to init mining plugin.");
+		CuckooMiner::new(self.config.clone())
+			.unwrap_or_else(|e| errors::fatal(plugin_load_failed(e)))
+	}
+
+	/// Disable a device of a loaded plugin, by overriding its USE_DEVICE
+	/// parameter. Takes effect for miners created afterwards.
+	pub fn disable_device(&mut self, plugin: usize, device_id: u32) {
+		if let Some(c) = self.config.get_mut(plugin) {
+			c.parameter_list
+				.retain(|p| !(p.0 == "USE_DEVICE" && p.1 == device_id));
+			c.parameter_list
+				.push((String::from("USE_DEVICE"), device_id, 0));
 		}
-		result.unwrap()
 	}
 
 	/// Returns the number of mining plugins that have been loaded
@@ -0,0 +1,142 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Startup ramp-up. Creating the solver contexts of every GPU at once can
+//! draw more than the PSU of a larger rig gives, so mining can wait a
+//! while after startup and then start the devices one at a time. Devices
+//! not started yet are kept out of mining like paused ones.
+
+use std::collections::VecDeque;
+
+use clock;
+use config::MinerConfig;
+use placement;
+use stats::RampUpStats;
+
+/// A step of the ramp-up, after which the job starts, or the device
+/// released starts on it
+#[derive(Debug, PartialEq)]
+pub enum Step {
+	/// The startup delay is over, mining starts with the first device
+	Start,
+	/// The next device starts
+	Device(usize, u32),
+}
+
+/// Devices waiting to start
+pub struct RampUp {
+	/// When mining may start, in monotonic seconds
+	start_at: i64,
+	started: bool,
+	stagger_secs: i64,
+	/// Devices not started yet, in the order they start
+	pending: VecDeque<(usize, u32)>,
+	total: usize,
+	/// When the next device starts, in monotonic seconds
+	next_at: i64,
+}
+
+impl RampUp {
+	/// Ramp-up of the devices the config mines on, after the startup delay
+	/// if `delayed`, none if there's no delay or stagger
+	pub fn new(config: &MinerConfig, standby: &[(usize, u32)], delayed: bool) -> Option<RampUp> {
+		let delay = if delayed {
+			config.startup_delay_secs.unwrap_or(0) as i64
+		} else {
+			0
+		};
+		let stagger = config.startup_stagger_secs.unwrap_or(0) as i64;
+		if delay == 0 && stagger == 0 {
+			return None;
+		}
+		let mut pending = VecDeque::new();
+		if stagger > 0 {
+			for (i, plugin) in config.miner_plugin_config.iter().enumerate() {
+				for device in placement::mining_devices(plugin) {
+					if !standby.contains(&(i, device)) {
+						pending.push_back((i, device));
+					}
+				}
+			}
+		}
+		let now = clock::mono_secs();
+		Some(RampUp {
+			start_at: now + delay,
+			started: false,
+			stagger_secs: stagger,
+			total: pending.len(),
+			pending: pending,
+			next_at: now + delay,
+		})
+	}
+
+	/// Whether mining waits for the startup delay
+	pub fn waiting(&self) -> bool {
+		!self.started
+	}
+
+	/// Devices kept out of mining until they start
+	pub fn held_back(&self) -> Vec<(usize, u32)> {
+		self.pending.iter().cloned().collect()
+	}
+
+	/// Whether every device has started
+	pub fn done(&self) -> bool {
+		self.started && self.pending.is_empty()
+	}
+
+	/// The step due, if any. The stagger only counts down while mining,
+	/// so the devices don't all start together once a job arrives.
+	pub fn advance(&mut self, mining: bool) -> Option<Step> {
+		let now = clock::mono_secs();
+		if !self.started {
+			if now < self.start_at {
+				return None;
+			}
+			self.started = true;
+			self.next_at = now + self.stagger_secs;
+			self.pending.pop_front();
+			return Some(Step::Start);
+		}
+		if !mining {
+			self.next_at = now + self.stagger_secs;
+			return None;
+		}
+		if self.pending.is_empty() || now < self.next_at {
+			return None;
+		}
+		self.next_at = now + self.stagger_secs;
+		self.pending
+			.pop_front()
+			.map(|(plugin, device)| Step::Device(plugin, device))
+	}
+
+	/// Progress, for the stats
+	pub fn stats(&self) -> RampUpStats {
+		RampUpStats {
+			starts_at: if self.started {
+				None
+			} else {
+				Some(clock::to_wall(self.start_at))
+			},
+			started_devices: self.total - self.pending.len(),
+			total_devices: self.total,
+			next_device_at: if self.started && !self.pending.is_empty() {
+				Some(clock::to_wall(self.next_at))
+			} else {
+				None
+			},
+		}
+	}
+}
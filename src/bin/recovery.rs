@@ -0,0 +1,194 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recovery of devices whose solver keeps failing: deciding when to tear
+//! the solvers down and re-create them, backing off between attempts (the
+//! device quarantined meanwhile), and when to give a device up
+
+use std::cmp;
+
+use clock;
+use config::RecoveryConfig;
+use stats::DeviceQuarantine;
+use util::cuckoo_miner::CuckooMinerDeviceStats;
+
+/// Recovery state of a device
+struct DeviceState {
+	plugin: usize,
+	device_id: String,
+	/// Consecutive stats updates the device was errored in
+	errored_updates: u32,
+	/// Recovery attempts since the device was last healthy
+	attempts: u32,
+	/// When the next attempt may be made
+	next_attempt: i64,
+	/// When the device last errored
+	last_error: i64,
+	/// Paused until the next attempt
+	quarantined: bool,
+	failed: bool,
+}
+
+/// What to do about errored devices
+#[derive(Debug, Default)]
+pub struct RecoveryActions {
+	/// Devices to recover now, with the attempt number
+	pub recover: Vec<(usize, String, u32)>,
+	/// Devices given up on
+	pub failed: Vec<(usize, String)>,
+	/// Devices to pause until their next attempt, with its time (on the
+	/// monotonic clock)
+	pub quarantine: Vec<(usize, String, i64)>,
+}
+
+/// Tracks errored devices across stats updates
+pub struct DeviceRecovery {
+	config: RecoveryConfig,
+	devices: Vec<DeviceState>,
+}
+
+impl DeviceRecovery {
+	/// Create a new tracker
+	pub fn new(config: RecoveryConfig) -> DeviceRecovery {
+		DeviceRecovery {
+			config: config,
+			devices: vec![],
+		}
+	}
+
+	/// Seconds to wait before the given attempt
+	fn backoff(&self, attempt: u32) -> u64 {
+		let factor = 1u64 << cmp::min(attempt.saturating_sub(1), 20);
+		cmp::min(
+			self.config.initial_backoff_secs.saturating_mul(factor),
+			self.config.max_backoff_secs,
+		)
+	}
+
+	/// Update with the latest device stats of every plugin, as of the
+	/// given monotonic clock seconds, returning the devices to recover or
+	/// give up on
+	pub fn update(&mut self, plugins: &[Vec<CuckooMinerDeviceStats>], now: i64) -> RecoveryActions {
+		let mut actions = RecoveryActions::default();
+		if !self.config.enabled {
+			return actions;
+		}
+		// quarantined devices aren't mining, so they're tried again on time
+		// alone
+		for state in self.devices.iter_mut().filter(|s| s.quarantined) {
+			if now >= state.next_attempt {
+				state.attempts += 1;
+				state.errored_updates = 0;
+				state.next_attempt = 0;
+				state.quarantined = false;
+				actions
+					.recover
+					.push((state.plugin, state.device_id.clone(), state.attempts));
+			}
+		}
+		for (i, devices) in plugins.iter().enumerate() {
+			for d in devices.iter().filter(|d| d.in_use == 1) {
+				let pos = match self
+					.devices
+					.iter()
+					.position(|s| s.plugin == i && s.device_id == d.device_id)
+				{
+					Some(p) => p,
+					None => {
+						self.devices.push(DeviceState {
+							plugin: i,
+							device_id: d.device_id.clone(),
+							errored_updates: 0,
+							attempts: 0,
+							next_attempt: 0,
+							last_error: 0,
+							quarantined: false,
+							failed: false,
+						});
+						self.devices.len() - 1
+					}
+				};
+				if self.devices[pos].failed || self.devices[pos].quarantined {
+					continue;
+				}
+				if d.has_errored == 0 {
+					let state = &mut self.devices[pos];
+					state.errored_updates = 0;
+					if state.attempts > 0 && now - state.last_error >= self.config.healthy_secs as i64 {
+						state.attempts = 0;
+					}
+					continue;
+				}
+				let backoff = self.backoff(self.devices[pos].attempts + 1);
+				let state = &mut self.devices[pos];
+				state.errored_updates += 1;
+				state.last_error = now;
+				if state.errored_updates < self.config.error_threshold {
+					continue;
+				}
+				if state.attempts >= self.config.max_attempts {
+					state.failed = true;
+					actions.failed.push((i, d.device_id.clone()));
+					continue;
+				}
+				if state.next_attempt == 0 {
+					// first noticed, wait out the backoff before the attempt
+					state.next_attempt = now + backoff as i64;
+					if self.config.quarantine {
+						state.quarantined = true;
+						actions
+							.quarantine
+							.push((i, d.device_id.clone(), state.next_attempt));
+						continue;
+					}
+				}
+				if now >= state.next_attempt {
+					state.attempts += 1;
+					state.errored_updates = 0;
+					state.next_attempt = 0;
+					actions.recover.push((i, d.device_id.clone(), state.attempts));
+				}
+			}
+		}
+		actions
+	}
+
+	/// The devices quarantined until their next attempt
+	pub fn quarantined(&self) -> Vec<DeviceQuarantine> {
+		self.devices
+			.iter()
+			.filter(|s| s.quarantined)
+			.map(|s| DeviceQuarantine {
+				plugin: s.plugin,
+				device_id: s.device_id.clone(),
+				until: clock::to_wall(s.next_attempt),
+				attempt: s.attempts + 1,
+			})
+			.collect()
+	}
+
+	/// Forget every device's errors and attempts, to start over
+	pub fn reset(&mut self) {
+		self.devices.clear();
+	}
+
+	/// Ids of the devices quarantined, as (plugin index, device id)
+	pub fn quarantined_ids(&self) -> Vec<(usize, u32)> {
+		self.devices
+			.iter()
+			.filter(|s| s.quarantined)
+			.filter_map(|s| s.device_id.parse::<u32>().ok().map(|id| (s.plugin, id)))
+			.collect()
+	}
+}
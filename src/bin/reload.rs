@@ -0,0 +1,238 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Config reloading, applying the changes that can be applied while
+//! running and reporting those that need a restart
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+use serde_json::{self, Value};
+
+use auto;
+use config::{self, ConfigMembers, GlobalConfig};
+use types::{ClientMessage, MinerMessage};
+use util::{self, LOGGER};
+
+/// Keys (or key prefixes) that are applied live
+const LIVE_KEYS: &[&str] = &[
+	"logging.stdout_log_level",
+	"logging.file_log_level",
+	"mining.miner_plugin_dir",
+	"mining.miner_plugin_dirs",
+	"mining.miner_plugin_config",
+	"mining.defaults",
+	"mining.profile",
+	"mining.profiles",
+	"mining.stratum_server_addr",
+	"mining.stratum_server_login",
+	"mining.stratum_server_password",
+];
+
+/// Flatten a JSON value into dotted keys and their values
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, Value)>) {
+	match *value {
+		Value::Object(ref map) => for (k, v) in map {
+			let key = if prefix.is_empty() {
+				k.clone()
+			} else {
+				format!("{}.{}", prefix, k)
+			};
+			flatten(&key, v, out);
+		},
+		Value::Array(ref list) => for (i, v) in list.iter().enumerate() {
+			flatten(&format!("{}.{}", prefix, i), v, out);
+		},
+		_ => out.push((prefix.to_string(), value.clone())),
+	}
+}
+
+/// Keys whose values differ between the two configs
+pub fn changed_keys(old: &ConfigMembers, new: &ConfigMembers) -> Vec<String> {
+	let mut old_values = vec![];
+	let mut new_values = vec![];
+	flatten("", &serde_json::to_value(old).unwrap_or(Value::Null), &mut old_values);
+	flatten("", &serde_json::to_value(new).unwrap_or(Value::Null), &mut new_values);
+	let mut changed: Vec<String> = vec![];
+	for &(ref k, ref v) in &new_values {
+		if !old_values.iter().any(|o| o.0 == *k && o.1 == *v) {
+			changed.push(k.clone());
+		}
+	}
+	for &(ref k, _) in &old_values {
+		if !new_values.iter().any(|n| n.0 == *k) {
+			changed.push(k.clone());
+		}
+	}
+	changed.sort();
+	changed.dedup();
+	changed
+}
+
+fn is_live(key: &str) -> bool {
+	LIVE_KEYS
+		.iter()
+		.any(|l| key == *l || key.starts_with(&format!("{}.", l)))
+}
+
+/// Re-reads the config file on request or when it changes
+pub struct ConfigReloader {
+	path: Option<String>,
+	extra: Vec<String>,
+	overrides: Vec<(String, String)>,
+	current: ConfigMembers,
+	modified: Option<SystemTime>,
+	miner_tx: mpsc::Sender<MinerMessage>,
+	client_tx: mpsc::Sender<ClientMessage>,
+}
+
+impl ConfigReloader {
+	/// Create a new reloader, for the config file at path (or the default
+	/// location) with the given extra files merged and overrides applied
+	/// on top, as currently running with the given config
+	pub fn new(
+		path: Option<String>,
+		extra: Vec<String>,
+		overrides: Vec<(String, String)>,
+		current: ConfigMembers,
+		miner_tx: mpsc::Sender<MinerMessage>,
+		client_tx: mpsc::Sender<ClientMessage>,
+	) -> ConfigReloader {
+		let mut reloader = ConfigReloader {
+			path: path,
+			extra: extra,
+			overrides: overrides,
+			current: current,
+			modified: None,
+			miner_tx: miner_tx,
+			client_tx: client_tx,
+		};
+		reloader.modified = reloader.file_modified();
+		reloader
+	}
+
+	fn file_modified(&self) -> Option<SystemTime> {
+		let path = match GlobalConfig::new(self.path.as_ref().map(|p| p.as_str())) {
+			Ok(c) => c.config_file_path,
+			Err(_) => self.path.as_ref().map(PathBuf::from),
+		};
+		path.and_then(|p| fs::metadata(p).ok())
+			.and_then(|m| m.modified().ok())
+	}
+
+	/// Reload if the config file changed since it was last read
+	pub fn check_modified(&mut self) {
+		let modified = self.file_modified();
+		if modified.is_some() && modified != self.modified {
+			info!(LOGGER, "Config file changed, reloading");
+			self.reload();
+		}
+	}
+
+	/// Re-read the config file, apply what can be applied live and report
+	/// what needs a restart
+	pub fn reload(&mut self) {
+		self.modified = self.file_modified();
+		let mut global_config = match GlobalConfig::new(self.path.as_ref().map(|p| p.as_str())) {
+			Ok(c) => c,
+			Err(e) => {
+				error!(LOGGER, "Unable to reload config, keeping the current one: {}", e);
+				return;
+			}
+		};
+		if let Err(e) = global_config.merge_files(&self.extra) {
+			error!(LOGGER, "Unable to reload config, keeping the current one: {}", e);
+			return;
+		}
+		let mut overrides = config::env_overrides();
+		overrides.extend(self.overrides.iter().cloned());
+		if let Err(e) = global_config.apply_overrides(&overrides) {
+			error!(LOGGER, "Unable to reload config, keeping the current one: {}", e);
+			return;
+		}
+		let mut new = global_config.members.unwrap_or_default();
+		if let Err(e) = new
+			.mining
+			.apply_selected_profile()
+			.and_then(|_| new.mining.apply_chain_defaults())
+			.and_then(|_| {
+				if new.mining.auto.unwrap_or(false) {
+					auto::auto_configure(&mut new.mining)
+				} else {
+					Ok(())
+				}
+			})
+		{
+			error!(LOGGER, "Unable to reload config, keeping the current one: {}", e);
+			return;
+		}
+		let changed = changed_keys(&self.current, &new);
+		if changed.is_empty() {
+			info!(LOGGER, "Config reloaded, nothing changed");
+			return;
+		}
+
+		let (live, restart): (Vec<String>, Vec<String>) =
+			changed.into_iter().partition(|k| is_live(k));
+		if live.iter().any(|k| k.starts_with("logging.")) {
+			if let Some(ref l) = new.logging {
+				util::set_log_levels(l.stdout_log_level.clone(), l.file_log_level.clone());
+			}
+		}
+		// everything else live is plugin configuration
+		if live
+			.iter()
+			.any(|k| !k.starts_with("logging.") && !k.starts_with("mining.stratum_server"))
+		{
+			let _ = self.miner_tx.send(MinerMessage::UpdateConfig(new.mining.clone()));
+		}
+		if live.iter().any(|k| k.starts_with("mining.stratum_server")) {
+			let _ = self.client_tx.send(ClientMessage::SwitchPool(
+				new.mining.stratum_server_addr.clone(),
+				new.mining.stratum_server_login.clone(),
+				new.mining.stratum_server_password.clone(),
+			));
+		}
+		if !live.is_empty() {
+			warn!(LOGGER, "Config reloaded, applied: {}", live.join(", "));
+		}
+		if !restart.is_empty() {
+			warn!(
+				LOGGER,
+				"Config reloaded, these changes need a restart to take effect: {}",
+				restart.join(", ")
+			);
+		}
+
+		// only what was applied becomes current, so changes needing a
+		// restart are reported again on the next reload
+		let mut current = self.current.clone();
+		if let (Some(c), Some(n)) = (current.logging.as_mut(), new.logging.as_ref()) {
+			c.stdout_log_level = n.stdout_log_level.clone();
+			c.file_log_level = n.file_log_level.clone();
+		}
+		current.mining.miner_plugin_dir = new.mining.miner_plugin_dir.clone();
+		current.mining.miner_plugin_dirs = new.mining.miner_plugin_dirs.clone();
+		current.mining.miner_plugin_config = new.mining.miner_plugin_config.clone();
+		current.mining.profile = new.mining.profile.clone();
+		current.mining.defaults = new.mining.defaults.clone();
+		current.mining.profiles = new.mining.profiles.clone();
+		current.mining.stratum_server_addr = new.mining.stratum_server_addr.clone();
+		current.mining.stratum_server_login = new.mining.stratum_server_login.clone();
+		current.mining.stratum_server_password = new.mining.stratum_server_password.clone();
+		self.current = current;
+	}
+}
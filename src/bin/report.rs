@@ -0,0 +1,312 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Session report, everything needed for a support request or a
+//! benchmarking record in a single JSON or Markdown file
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, Write};
+
+use serde_json::{self, Value};
+use time;
+
+use config::ConfigMembers;
+use plugin;
+use stats::{DeviceSessionStats, Efficiency, GpsAverages, HistoryStats, ShareRecord, Stats};
+use units;
+
+/// Config values replaced in the report: any key containing one of these
+const REDACTED_PATTERNS: &[&str] = &["token", "password", "key"];
+
+/// Config keys whose values are replaced in the report too, urls that may
+/// carry secrets
+const REDACTED_KEYS: &[&str] = &["webhooks"];
+
+/// Shares part of the report
+#[derive(Serialize)]
+struct SharesReport<'a> {
+	accepted: u64,
+	rejected: u64,
+	stale: u64,
+	solutions_found: u64,
+	solutions_by_size: &'a HashMap<u32, u64>,
+	best_share_difficulty: u64,
+	below_target: u64,
+	difficulty_distribution: String,
+	recent: &'a VecDeque<ShareRecord>,
+}
+
+/// Pool part of the report
+#[derive(Serialize)]
+struct PoolReport<'a> {
+	url: &'a str,
+	connected: bool,
+	connection_status: &'a str,
+	blocks_found: u64,
+}
+
+/// The whole report
+#[derive(Serialize)]
+struct Report<'a> {
+	generated: i64,
+	version: String,
+	build: String,
+	plugins: Vec<String>,
+	config: Value,
+	start_time: i64,
+	uptime: i64,
+	gps_averages: GpsAverages,
+	efficiency: Efficiency,
+	devices: &'a Vec<DeviceSessionStats>,
+	shares: SharesReport<'a>,
+	pool: PoolReport<'a>,
+	history: &'a HistoryStats,
+}
+
+/// Whether the config key's value is a secret
+fn is_secret(key: &str) -> bool {
+	let key = key.to_lowercase();
+	REDACTED_PATTERNS.iter().any(|p| key.contains(p)) || REDACTED_KEYS.contains(&key.as_str())
+}
+
+/// Replace secrets in the config with a placeholder
+fn redact(value: &mut Value) {
+	match *value {
+		Value::Object(ref mut map) => for (k, v) in map.iter_mut() {
+			if is_secret(k) && !v.is_null() {
+				*v = Value::String("<redacted>".to_string());
+			} else {
+				redact(v);
+			}
+		},
+		Value::Array(ref mut list) => for v in list.iter_mut() {
+			redact(v);
+		},
+		_ => {}
+	}
+}
+
+fn format_time(secs: i64) -> String {
+	units::date_time(secs)
+}
+
+fn markdown(report: &Report) -> String {
+	let mut out = vec![
+		"# Grin Miner Session Report".to_string(),
+		String::new(),
+		format!("Generated: {}", format_time(report.generated)),
+		String::new(),
+		report.version.clone(),
+		report.build.clone(),
+		String::new(),
+		"## Plugins".to_string(),
+		String::new(),
+	];
+	for (i, p) in report.plugins.iter().enumerate() {
+		out.push(format!("- {}: {}", i, p));
+	}
+	out.push(String::new());
+	out.push("## Configuration".to_string());
+	out.push(String::new());
+	out.push("```json".to_string());
+	out.push(serde_json::to_string_pretty(&report.config).unwrap_or_default());
+	out.push("```".to_string());
+	out.push(String::new());
+	out.push("## Performance".to_string());
+	out.push(String::new());
+	out.push(format!("Started: {}", format_time(report.start_time)));
+	out.push(format!(
+		"Uptime: {}h {}m {}s",
+		report.uptime / 3600,
+		(report.uptime % 3600) / 60,
+		report.uptime % 60
+	));
+	let a = &report.gps_averages;
+	out.push(format!(
+		"GPS: 1m {}, 5m {}, 15m {}, session {} (min {}, max {})",
+		units::gps_value(a.avg_1m),
+		units::gps_value(a.avg_5m),
+		units::gps_value(a.avg_15m),
+		units::gps_value(a.avg_session),
+		units::gps_value(a.min),
+		units::gps_value(a.max)
+	));
+	if !report.efficiency.devices.is_empty() {
+		out.push(format!(
+			"Power: {:.1} W, {:.4} graphs/J",
+			report.efficiency.power_usage, report.efficiency.graphs_per_joule
+		));
+	}
+	out.push(String::new());
+	out.push("| Plugin | Device | Name | Average GPS | Graphs | Errors |".to_string());
+	out.push("|---|---|---|---|---|---|".to_string());
+	for d in report.devices {
+		out.push(format!(
+			"| {} | {} | {} | {} | {} | {} |",
+			d.plugin,
+			d.device_id,
+			d.device_name,
+			units::gps_value(d.average_gps()),
+			units::number(d.graphs),
+			units::number(d.errors)
+		));
+	}
+	out.push(String::new());
+	out.push("## Shares".to_string());
+	out.push(String::new());
+	let s = &report.shares;
+	out.push(format!(
+		"Accepted: {}, Rejected: {}, Stale: {}",
+		units::number(s.accepted),
+		units::number(s.rejected),
+		units::number(s.stale)
+	));
+	out.push(format!(
+		"Solutions found: {}, below target: {}, best difficulty: {}",
+		units::number(s.solutions_found),
+		units::number(s.below_target),
+		units::number(s.best_share_difficulty)
+	));
+	out.push(format!("Difficulty distribution: {}", s.difficulty_distribution));
+	out.push(String::new());
+	out.push("## Pool".to_string());
+	out.push(String::new());
+	out.push(format!(
+		"{} ({}), blocks found: {}",
+		report.pool.url, report.pool.connection_status, report.pool.blocks_found
+	));
+	for d in &report.history.connection_drops {
+		let duration = match d.duration {
+			Some(s) => format!("down for {}s", s),
+			None => "still down".to_string(),
+		};
+		out.push(format!("- {} {} ({})", format_time(d.time), d.reason, duration));
+	}
+	out.push(String::new());
+	out.push("## Solver Restarts".to_string());
+	out.push(String::new());
+	for r in &report.history.restarts {
+		let device = match r.device {
+			Some((ref p, ref d)) => format!("plugin {} device {}", p, d),
+			None => "all devices".to_string(),
+		};
+		out.push(format!("- {} {}: {}", format_time(r.time), device, r.reason));
+	}
+	out.push(String::new());
+	out.push("## Errors".to_string());
+	out.push(String::new());
+	for e in &report.history.errors {
+		out.push(format!("- {} {}", format_time(e.time), e.message));
+	}
+	out.push(String::new());
+	out.join("\n")
+}
+
+/// Write the session report to path, as JSON if the path ends in .json,
+/// Markdown otherwise
+pub fn write_report(
+	path: &str,
+	config: &ConfigMembers,
+	build_info: (String, String),
+	stats: &Stats,
+) -> io::Result<()> {
+	let mut config = serde_json::to_value(config)
+		.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))?;
+	redact(&mut config);
+	let now = time::get_time().sec;
+	let session = &stats.session_stats;
+	let report = Report {
+		generated: now,
+		version: build_info.0,
+		build: build_info.1,
+		plugins: plugin::loaded_plugins(),
+		config: config,
+		start_time: session.start_time,
+		uptime: session.uptime(stats.clock_now()),
+		gps_averages: stats.mining_stats.gps_history.averages(stats.clock_now()),
+		efficiency: stats.mining_stats.efficiency(),
+		devices: &session.devices,
+		shares: SharesReport {
+			accepted: stats.client_stats.shares_accepted,
+			rejected: stats.client_stats.shares_rejected,
+			stale: stats.client_stats.shares_stale,
+			solutions_found: session.solutions_found,
+			solutions_by_size: &session.solutions_by_size,
+			best_share_difficulty: session.best_share_difficulty,
+			below_target: session.shares_below_target,
+			difficulty_distribution: session.difficulty_histogram.summary(),
+			recent: &session.recent_shares,
+		},
+		pool: PoolReport {
+			url: &stats.client_stats.server_url,
+			connected: stats.client_stats.connected,
+			connection_status: &stats.client_stats.connection_status,
+			blocks_found: stats.client_stats.blocks_found,
+		},
+		history: &stats.history,
+	};
+	let contents = if path.ends_with(".json") {
+		serde_json::to_string_pretty(&report)
+			.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))?
+	} else {
+		markdown(&report)
+	};
+	let mut file = File::create(path)?;
+	file.write_all(contents.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn redacted() {
+		let mut config = json!({
+			"mining": {
+				"stratum_server_login": "grin1login",
+				"stratum_server_password": "pass",
+				"share_ledger_file": "shares.jsonl",
+				"share_ledger_key": "hmac-secret",
+			},
+			"api": {"control_token": "api-secret", "bind_address": "127.0.0.1:3417"},
+			"control_socket": {"token": "socket-secret", "path": "grin-miner.sock"},
+			"mqtt": {"password": null},
+		});
+		redact(&mut config);
+		assert_eq!(config["mining"]["stratum_server_login"], "grin1login");
+		assert_eq!(config["mining"]["stratum_server_password"], "<redacted>");
+		assert_eq!(config["api"]["control_token"], "<redacted>");
+		assert_eq!(config["api"]["bind_address"], "127.0.0.1:3417");
+		assert_eq!(config["mining"]["share_ledger_key"], "<redacted>");
+		assert_eq!(config["mining"]["share_ledger_file"], "shares.jsonl");
+		assert_eq!(config["control_socket"]["token"], "<redacted>");
+		assert_eq!(config["control_socket"]["path"], "grin-miner.sock");
+		assert!(config["mqtt"]["password"].is_null());
+	}
+
+	#[test]
+	fn fleet_tokens_redacted() {
+		let mut config = json!({
+			"fleet": {"rigs": [
+				{"url": "192.168.1.20:3420", "name": "rig02", "token": "rig02-secret"},
+				{"url": "192.168.1.21:3420", "name": "rig03"},
+			]},
+		});
+		redact(&mut config);
+		assert_eq!(config["fleet"]["rigs"][0]["token"], "<redacted>");
+		assert_eq!(config["fleet"]["rigs"][0]["url"], "192.168.1.20:3420");
+		assert_eq!(config["fleet"]["rigs"][1]["name"], "rig03");
+	}
+}
@@ -0,0 +1,737 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Plugin sandboxing, running each solver in a child process (the hidden
+//! `solver-worker` subcommand) so a plugin crashing only loses the
+//! devices it mines on. Parent and workers exchange one JSON message per
+//! line over the worker's stdin and stdout. External solvers run as
+//! workers too, their messages translated to and from the external
+//! solver protocol.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+
+use clock;
+use config::types::CuckooMinerPluginConfig;
+use config::MinerConfig;
+use crash;
+use external::{FromSolver, ToSolver};
+use pacing::Pacing;
+use cuckoo::{CuckooMinerDeviceStats, CuckooMinerJobHandle, CuckooMinerSolution};
+use plugin::PluginMiner;
+use util::{self, LoggingConfig, LOGGER};
+
+/// Longest wait before respawning a worker that keeps crashing
+const MAX_RESPAWN_BACKOFF_SECS: i64 = 60;
+
+/// A solution, as found by a plugin
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Solution {
+	/// Edge bits of the graph
+	pub cuckoo_size: u32,
+	/// Nonce the graph was built from
+	pub nonce: u64,
+	/// The cycle's edges
+	pub solution_nonces: Vec<u32>,
+	/// Plugin index and id of the device that found it, when known
+	#[serde(skip)]
+	pub device: Option<(usize, String)>,
+}
+
+impl<'a> From<&'a CuckooMinerSolution> for Solution {
+	fn from(s: &'a CuckooMinerSolution) -> Solution {
+		Solution {
+			cuckoo_size: s.cuckoo_size,
+			nonce: s.get_nonce_as_u64(),
+			solution_nonces: s.solution_nonces.to_vec(),
+			device: None,
+		}
+	}
+}
+
+/// Messages to a worker
+#[derive(Debug, Serialize, Deserialize)]
+enum ToWorker {
+	/// Configuration to load, with a single plugin, and the miner's
+	/// logging configuration
+	Config(MinerConfig, LoggingConfig),
+	/// Start mining a job, with devices of the plugin not to mine on
+	Job {
+		pre_pow: String,
+		target_difficulty: u64,
+		disabled: Vec<u32>,
+	},
+	/// Stop mining
+	Stop,
+	Shutdown,
+}
+
+/// Messages from a worker
+#[derive(Debug, Serialize, Deserialize)]
+enum FromWorker {
+	Solution(Solution),
+	Stats(Vec<CuckooMinerDeviceStats>),
+	/// A job replaced the one being mined, taking the ms to stop the old
+	/// one and start the new one
+	JobSwitched(u64),
+}
+
+/// An external solver's message as a worker's, none for those only logged
+fn from_solver(
+	line: &str,
+	plugin: usize,
+	plugin_edge_bits: u8,
+	description: &str,
+) -> Result<Option<FromWorker>, String> {
+	let message = serde_json::from_str::<FromSolver>(line).map_err(|e| e.to_string())?;
+	Ok(match message {
+		FromSolver::Solution {
+			nonce,
+			edge_bits,
+			cycle,
+			device_id,
+		} => Some(FromWorker::Solution(Solution {
+			cuckoo_size: edge_bits,
+			nonce: nonce,
+			solution_nonces: cycle,
+			device: device_id.map(|d| (plugin, d)),
+		})),
+		FromSolver::Stats { devices } => Some(FromWorker::Stats(
+			devices
+				.iter()
+				.map(|d| d.device_stats(plugin_edge_bits))
+				.collect(),
+		)),
+		FromSolver::Log { level, message } => {
+			match level.as_str() {
+				"error" => error!(LOGGER, "Solver for {}: {}", description, message),
+				"warn" | "warning" => warn!(LOGGER, "Solver for {}: {}", description, message),
+				"debug" => debug!(LOGGER, "Solver for {}: {}", description, message),
+				_ => info!(LOGGER, "Solver for {}: {}", description, message),
+			}
+			None
+		}
+	})
+}
+
+/// A worker's message as the external solver protocol has it
+fn to_solver(message: &ToWorker) -> ToSolver {
+	match *message {
+		ToWorker::Config(ref c, _) => ToSolver::init(&c.miner_plugin_config[0]),
+		ToWorker::Job {
+			ref pre_pow,
+			target_difficulty,
+			ref disabled,
+		} => ToSolver::Job {
+			pre_pow: pre_pow.clone(),
+			target_difficulty: target_difficulty,
+			disabled: disabled.clone(),
+		},
+		ToWorker::Stop => ToSolver::Stop,
+		ToWorker::Shutdown => ToSolver::Shutdown,
+	}
+}
+
+/// Run as a solver worker, mining the jobs the parent sends with the
+/// plugin it configures
+pub fn run_worker() -> Result<(), String> {
+	let (tx, rx) = mpsc::channel();
+	let _ = thread::Builder::new()
+		.name("worker_stdin".to_string())
+		.spawn(move || {
+			let stdin = io::stdin();
+			for line in stdin.lock().lines() {
+				let line = match line {
+					Ok(l) => l,
+					Err(_) => break,
+				};
+				match serde_json::from_str::<ToWorker>(&line) {
+					Ok(m) => {
+						if tx.send(m).is_err() {
+							return;
+						}
+					}
+					Err(e) => error!(LOGGER, "Invalid message from the miner: {}", e),
+				}
+			}
+			// the parent is gone
+			let _ = tx.send(ToWorker::Shutdown);
+		});
+	let config = match rx.recv() {
+		Ok(ToWorker::Config(c, mut log_conf)) => {
+			// stdout is for the miner, and the log file is shared with it
+			log_conf.log_to_stdout = false;
+			log_conf.log_file_append = true;
+			log_conf.tui_running = None;
+			util::init_logger(Some(log_conf));
+			crash::install(&c, None);
+			c
+		}
+		_ => return Err("Expected a configuration from the miner".to_string()),
+	};
+	let send = |m: FromWorker| {
+		let stdout = io::stdout();
+		let mut out = stdout.lock();
+		let line = serde_json::to_string(&m).unwrap();
+		if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+			// the parent is gone
+			std::process::exit(0);
+		}
+	};
+
+	let pacing = Pacing::new(&config);
+	let mut job_handle: Option<CuckooMinerJobHandle> = None;
+	// kept alive for as long as its job runs
+	let mut _plugin_miner = None;
+	let mut next_stats = 0;
+	loop {
+		while let Ok(m) = rx.try_recv() {
+			match m {
+				ToWorker::Job {
+					pre_pow,
+					target_difficulty,
+					disabled,
+				} => {
+					let started = clock::mono_ms();
+					let replaced = job_handle.is_some();
+					if let Some(mut h) = job_handle.take() {
+						h.stop_jobs();
+					}
+					let mut plugin_miner = PluginMiner::new();
+					plugin_miner.init(config.clone());
+					for d in disabled {
+						plugin_miner.disable_device(0, d);
+					}
+					let miner = plugin_miner.get_consumable();
+					match miner.notify(1, &pre_pow, "", target_difficulty, false) {
+						Ok(h) => {
+							job_handle = Some(h);
+							if replaced {
+								send(FromWorker::JobSwitched((clock::mono_ms() - started) as u64));
+							}
+						}
+						Err(e) => error!(LOGGER, "Unable to start the job: {:?}", e),
+					}
+					_plugin_miner = Some(plugin_miner);
+				}
+				ToWorker::Stop => {
+					if let Some(mut h) = job_handle.take() {
+						h.stop_jobs();
+					}
+				}
+				ToWorker::Shutdown => {
+					if let Some(mut h) = job_handle.take() {
+						h.stop_jobs();
+					}
+					return Ok(());
+				}
+				ToWorker::Config(..) => {}
+			}
+		}
+		if let Some(ref mut h) = job_handle {
+			while let Some(s) = h.get_solution() {
+				send(FromWorker::Solution(Solution::from(&s)));
+			}
+			let now = clock::mono_secs();
+			if now >= next_stats {
+				if let Ok(stats) = h.get_stats(0) {
+					send(FromWorker::Stats(stats));
+				}
+				next_stats = now + pacing.stats_interval_secs();
+			}
+		}
+		pacing.wait(job_handle.is_none());
+	}
+}
+
+/// A solver worker process
+struct Worker {
+	/// Index of the plugin in the miner configuration
+	plugin: usize,
+	/// Device the worker mines on, none if it runs all of the plugin's
+	device: Option<u32>,
+	/// Which of the solver instances of its devices the worker is
+	instance: u32,
+	config: MinerConfig,
+	child: Option<Child>,
+	stdin: Option<ChildStdin>,
+	rx: Option<mpsc::Receiver<FromWorker>>,
+	stats: Vec<CuckooMinerDeviceStats>,
+	crashes: u32,
+	respawn_at: Option<i64>,
+}
+
+impl Worker {
+	fn description(&self) -> String {
+		let mut description = match self.device {
+			Some(d) => format!("plugin {} device {}", self.plugin, d),
+			None => format!("plugin {}", self.plugin),
+		};
+		if self.instance > 0 {
+			description.push_str(&format!(" instance {}", self.instance));
+		}
+		description
+	}
+
+	/// Device the solutions of the worker come from, if it mines on a
+	/// single one
+	fn solver_device(&self) -> Option<(usize, String)> {
+		match self.device {
+			Some(d) => Some((self.plugin, d.to_string())),
+			None => {
+				let mut used = self.stats.iter().filter(|s| s.in_use == 1);
+				match (used.next(), used.next()) {
+					(Some(s), None) => Some((self.plugin, s.device_id.clone())),
+					_ => None,
+				}
+			}
+		}
+	}
+
+	/// Whether the worker is an external solver
+	fn is_external(&self) -> bool {
+		self.config.miner_plugin_config[0].is_external()
+	}
+
+	fn spawn(&mut self) -> Result<(), String> {
+		let plugin = self.config.miner_plugin_config[0].clone();
+		let (mut command, stderr) = match plugin.command {
+			Some(ref c) => {
+				let mut command = Command::new(c.first().map_or("", |e| e.as_str()));
+				command.args(c.iter().skip(1));
+				(command, Stdio::piped())
+			}
+			None => {
+				let mut command = Command::new(env::current_exe().map_err(|e| format!("{}", e))?);
+				command.arg("solver-worker");
+				(command, Stdio::inherit())
+			}
+		};
+		let mut child = command
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(stderr)
+			.spawn()
+			.map_err(|e| match plugin.command {
+				Some(ref c) => format!("Unable to start external solver {}: {}", c.join(" "), e),
+				None => format!("Unable to start solver worker: {}", e),
+			})?;
+		let stdout = child.stdout.take().unwrap();
+		let thread_name = format!("worker_{}", self.description().replace(' ', "_"));
+		let (tx, rx) = mpsc::channel();
+		let external = plugin.is_external();
+		let edge_bits = plugin.edge_bits;
+		let index = self.plugin;
+		let description = self.description();
+		let _ = thread::Builder::new()
+			.name(thread_name.clone())
+			.spawn(move || {
+				for line in BufReader::new(stdout).lines() {
+					let line = match line {
+						Ok(l) => l,
+						Err(_) => break,
+					};
+					let message = if external {
+						from_solver(&line, index, edge_bits, &description)
+					} else {
+						serde_json::from_str::<FromWorker>(&line)
+							.map(Some)
+							.map_err(|e| e.to_string())
+					};
+					match message {
+						Ok(Some(m)) => {
+							if tx.send(m).is_err() {
+								break;
+							}
+						}
+						Ok(None) => {}
+						Err(e) => error!(LOGGER, "Invalid message from a solver worker: {}", e),
+					}
+				}
+			});
+		// whatever else an external solver prints goes to the debug log
+		if let Some(stderr) = child.stderr.take() {
+			let description = self.description();
+			let _ = thread::Builder::new()
+				.name(format!("{}_stderr", thread_name))
+				.spawn(move || {
+					for line in BufReader::new(stderr).lines() {
+						match line {
+							Ok(l) => debug!(LOGGER, "Solver for {}: {}", description, l),
+							Err(_) => break,
+						}
+					}
+				});
+		}
+		self.stdin = child.stdin.take();
+		self.child = Some(child);
+		self.rx = Some(rx);
+		self.stats = vec![];
+		self.send(&ToWorker::Config(self.config.clone(), util::logging_config()));
+		Ok(())
+	}
+
+	fn send(&mut self, message: &ToWorker) {
+		let line = if self.is_external() {
+			serde_json::to_string(&to_solver(message)).unwrap()
+		} else {
+			serde_json::to_string(message).unwrap()
+		};
+		if let Some(ref mut stdin) = self.stdin {
+			// a worker that's gone is noticed when polled
+			let _ = writeln!(stdin, "{}", line).and_then(|_| stdin.flush());
+		}
+	}
+
+	/// Whether the worker process has exited, reaping it if so
+	fn exited(&mut self) -> Option<String> {
+		let status = match self.child {
+			Some(ref mut c) => match c.try_wait() {
+				Ok(Some(status)) => format!("{}", status),
+				Ok(None) => return None,
+				Err(e) => format!("{}", e),
+			},
+			None => return None,
+		};
+		self.child = None;
+		self.stdin = None;
+		self.rx = None;
+		Some(status)
+	}
+
+	fn kill(&mut self) {
+		self.send(&ToWorker::Shutdown);
+		if let Some(mut c) = self.child.take() {
+			thread::sleep(Duration::from_millis(100));
+			if let Ok(None) = c.try_wait() {
+				let _ = c.kill();
+			}
+			let _ = c.wait();
+		}
+		self.stdin = None;
+		self.rx = None;
+	}
+}
+
+/// Devices of a plugin each get a worker of their own if they're switched
+/// on and off with USE_DEVICE, otherwise the plugin gets a single worker
+fn split_plugin(
+	config: &MinerConfig,
+	index: usize,
+	plugin: &CuckooMinerPluginConfig,
+) -> Vec<(Option<u32>, MinerConfig)> {
+	let single = |plugin: CuckooMinerPluginConfig| {
+		let mut c = config.clone();
+		c.miner_plugin_config = vec![plugin];
+		c.profiles = None;
+		c.profile = None;
+		c
+	};
+	if plugin.devices.is_some() || plugin.exclude_devices.is_some() || plugin.uses_pci_ids() {
+		// the devices are only known once the plugin is loaded
+		return vec![(None, single(plugin.clone()))];
+	}
+	let params = plugin.device_parameters.clone().unwrap_or_default();
+	let used: Vec<u32> = params
+		.iter()
+		.filter(|&(_, p)| p.get("USE_DEVICE") == Some(&1))
+		.filter_map(|(d, _)| d.parse::<u32>().ok())
+		.collect();
+	if used.len() < 2 {
+		return vec![(None, single(plugin.clone()))];
+	}
+	debug!(
+		LOGGER,
+		"Sandboxing plugin {} with a worker for each of devices {:?}", index, used
+	);
+	used.iter()
+		.map(|&device| {
+			let mut p = plugin.clone();
+			let mut params: HashMap<String, HashMap<String, u32>> = params.clone();
+			for (d, device_params) in params.iter_mut() {
+				if *d != device.to_string() {
+					device_params.insert("USE_DEVICE".to_string(), 0);
+				}
+			}
+			p.device_parameters = Some(params);
+			(Some(device), single(p))
+		})
+		.collect()
+}
+
+/// Add the stats of another solver instance on the same device: graphs
+/// per second add up, so the combined solution time is the reciprocal of
+/// the summed rates
+fn add_instance(device: &mut CuckooMinerDeviceStats, instance: &CuckooMinerDeviceStats) {
+	let gps = |t: u64| if t > 0 { 1000000000.0 / t as f64 } else { 0.0 };
+	let total = gps(device.last_solution_time) + gps(instance.last_solution_time);
+	device.last_solution_time = if total > 0.0 {
+		(1000000000.0 / total) as u64
+	} else {
+		0
+	};
+	device.iterations_completed += instance.iterations_completed;
+	device.has_errored = cmp::max(device.has_errored, instance.has_errored);
+	device.in_use = cmp::max(device.in_use, instance.in_use);
+}
+
+/// Solver workers for all the configured plugins
+pub struct Sandbox {
+	workers: Vec<Worker>,
+	plugins: usize,
+	/// Job of each plugin, if it's mining one
+	jobs: Vec<Option<(String, u64, Vec<(usize, u32)>)>>,
+	/// Job switch latencies reported by the workers, by device, since
+	/// last taken
+	job_switches: Vec<((usize, String), u64)>,
+}
+
+impl Sandbox {
+	/// Start the workers for the given configuration
+	pub fn new(config: &MinerConfig) -> Sandbox {
+		let mut workers = vec![];
+		for (i, p) in config.miner_plugin_config.iter().enumerate() {
+			let instances = p.instances.unwrap_or(1).max(1);
+			let split = split_plugin(config, i, p);
+			for instance in 0..instances {
+				for &(device, ref worker_config) in &split {
+					let mut worker = Worker {
+						plugin: i,
+						device: device,
+						instance: instance,
+						config: worker_config.clone(),
+						child: None,
+						stdin: None,
+						rx: None,
+						stats: vec![],
+						crashes: 0,
+						respawn_at: None,
+					};
+					if let Err(e) = worker.spawn() {
+						error!(LOGGER, "Solver worker for {}: {}", worker.description(), e);
+						worker.respawn_at = Some(clock::mono_secs() + 1);
+					}
+					workers.push(worker);
+				}
+			}
+		}
+		info!(LOGGER, "Started {} sandboxed solver workers", workers.len());
+		Sandbox {
+			workers: workers,
+			plugins: config.miner_plugin_config.len(),
+			jobs: vec![None; config.miner_plugin_config.len()],
+			job_switches: vec![],
+		}
+	}
+
+	fn send_job(worker: &mut Worker, job: &(String, u64, Vec<(usize, u32)>)) {
+		let disabled: Vec<u32> = job
+			.2
+			.iter()
+			.filter(|d| d.0 == worker.plugin)
+			.map(|d| d.1)
+			.collect();
+		if worker.device.map_or(false, |d| disabled.contains(&d)) {
+			worker.send(&ToWorker::Stop);
+			return;
+		}
+		worker.send(&ToWorker::Job {
+			pre_pow: job.0.clone(),
+			target_difficulty: job.1,
+			disabled: disabled,
+		});
+	}
+
+	/// Start mining a job on every worker, but on the disabled devices
+	pub fn start_job(&mut self, pre_pow: &str, target_difficulty: u64, disabled: &[(usize, u32)]) {
+		for plugin in 0..self.plugins {
+			self.start_plugin_job(plugin, pre_pow, target_difficulty, disabled);
+		}
+	}
+
+	/// Start mining a job on the workers of one plugin, leaving the others
+	/// on the jobs they have
+	pub fn start_plugin_job(
+		&mut self,
+		plugin: usize,
+		pre_pow: &str,
+		target_difficulty: u64,
+		disabled: &[(usize, u32)],
+	) {
+		let job = (pre_pow.to_string(), target_difficulty, disabled.to_vec());
+		for w in self.workers.iter_mut().filter(|w| w.plugin == plugin) {
+			Sandbox::send_job(w, &job);
+		}
+		self.jobs[plugin] = Some(job);
+	}
+
+	/// Change the disabled devices of the jobs being mined, stopping or
+	/// starting only the workers of the devices that changed
+	pub fn set_disabled(&mut self, disabled: &[(usize, u32)]) {
+		for plugin in 0..self.plugins {
+			let mut job = match self.jobs[plugin] {
+				Some(ref j) => j.clone(),
+				None => continue,
+			};
+			let changed: Vec<u32> = job
+				.2
+				.iter()
+				.filter(|d| !disabled.contains(d))
+				.chain(disabled.iter().filter(|d| !job.2.contains(d)))
+				.filter(|d| d.0 == plugin)
+				.map(|d| d.1)
+				.collect();
+			if changed.is_empty() {
+				continue;
+			}
+			job.2 = disabled.to_vec();
+			for w in self.workers.iter_mut().filter(|w| w.plugin == plugin) {
+				if w.device.map_or(true, |d| changed.contains(&d)) {
+					Sandbox::send_job(w, &job);
+				}
+			}
+			self.jobs[plugin] = Some(job);
+		}
+	}
+
+	/// Stop mining on every worker
+	pub fn stop_job(&mut self) {
+		for plugin in 0..self.plugins {
+			self.stop_plugin_job(plugin);
+		}
+	}
+
+	/// Stop mining on the workers of one plugin
+	pub fn stop_plugin_job(&mut self, plugin: usize) {
+		for w in self.workers.iter_mut().filter(|w| w.plugin == plugin) {
+			w.send(&ToWorker::Stop);
+		}
+		self.jobs[plugin] = None;
+	}
+
+	/// Take the job switch latencies the workers reported, by device
+	pub fn take_job_switches(&mut self) -> Vec<((usize, String), u64)> {
+		::std::mem::replace(&mut self.job_switches, vec![])
+	}
+
+	/// Whether a job is being mined
+	pub fn has_job(&self) -> bool {
+		self.jobs.iter().any(|j| j.is_some())
+	}
+
+	/// Collect the workers' messages, returning the solutions found, and
+	/// respawn workers that crashed
+	pub fn poll(&mut self) -> Vec<Solution> {
+		let now = clock::mono_secs();
+		let mut solutions = vec![];
+		for w in self.workers.iter_mut() {
+			let device = w.solver_device();
+			if let Some(ref rx) = w.rx {
+				while let Ok(m) = rx.try_recv() {
+					match m {
+						FromWorker::Solution(mut s) => {
+							if s.device.is_none() {
+								s.device = device.clone();
+							}
+							solutions.push(s);
+						}
+						FromWorker::Stats(s) => w.stats = s,
+						FromWorker::JobSwitched(ms) => {
+							// a worker of the whole plugin switches all its devices
+							let devices = match device {
+								Some(ref d) => vec![d.clone()],
+								None => w
+									.stats
+									.iter()
+									.filter(|s| s.in_use == 1)
+									.map(|s| (w.plugin, s.device_id.clone()))
+									.collect(),
+							};
+							for d in devices {
+								self.job_switches.push((d, ms));
+							}
+						}
+					}
+				}
+			}
+			if let Some(status) = w.exited() {
+				w.crashes += 1;
+				let backoff = cmp::min(1 << cmp::min(w.crashes, 6), MAX_RESPAWN_BACKOFF_SECS);
+				error!(
+					util::device_logger(w.plugin, &w.device.map(|d| d.to_string()).unwrap_or_default()),
+					"Solver worker for {} exited ({}), respawning it in {}s",
+					w.description(),
+					status,
+					backoff
+				);
+				for s in w.stats.iter_mut() {
+					s.has_errored = 1;
+				}
+				w.respawn_at = Some(now + backoff);
+			}
+			if w.respawn_at.map_or(false, |t| now >= t) {
+				w.respawn_at = None;
+				match w.spawn() {
+					Ok(()) => {
+						info!(LOGGER, "Respawned solver worker for {}", w.description());
+						if let Some(ref job) = self.jobs[w.plugin] {
+							Sandbox::send_job(w, job);
+						}
+					}
+					Err(e) => {
+						error!(LOGGER, "Solver worker for {}: {}", w.description(), e);
+						w.respawn_at = Some(now + MAX_RESPAWN_BACKOFF_SECS);
+					}
+				}
+			}
+		}
+		solutions
+	}
+
+	/// Latest device stats, per plugin as the plugins themselves report
+	/// them, with the solver instances of a device added up into one
+	pub fn device_stats(&self) -> Vec<Vec<CuckooMinerDeviceStats>> {
+		let mut plugins: Vec<Vec<CuckooMinerDeviceStats>> = vec![vec![]; self.plugins];
+		for w in &self.workers {
+			let stats = w.stats.iter().filter(|s| match w.device {
+				Some(d) => s.device_id == d.to_string(),
+				None => true,
+			});
+			for s in stats {
+				let devices = &mut plugins[w.plugin];
+				match devices.iter_mut().find(|d| d.device_id == s.device_id) {
+					Some(d) => add_instance(d, s),
+					None => devices.push(s.clone()),
+				}
+			}
+		}
+		plugins
+	}
+}
+
+impl Drop for Sandbox {
+	fn drop(&mut self) {
+		for w in self.workers.iter_mut() {
+			w.kill();
+		}
+	}
+}
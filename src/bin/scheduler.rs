@@ -0,0 +1,219 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Time of day scheduler, pausing mining outside the configured windows
+//! (e.g. to only mine during cheap electricity hours)
+
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use config::{ScheduleWindow, SchedulerConfig};
+use stats::{ScheduleStatus, Stats};
+use types::MinerMessage;
+use util::LOGGER;
+
+const DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+const MINUTES_PER_WEEK: u32 = 7 * 24 * 60;
+
+/// A window, parsed
+struct Window {
+	/// Minutes since midnight the window starts and ends at
+	start: u32,
+	end: u32,
+	/// Days (0 is Sunday) the window starts on
+	days: Vec<u32>,
+	profile: Option<String>,
+	description: String,
+}
+
+/// Parse HH:MM into minutes since midnight
+fn parse_time(s: &str) -> Result<u32, String> {
+	let parts: Vec<&str> = s.trim().split(':').collect();
+	if parts.len() != 2 {
+		return Err(format!("invalid time {}, expected HH:MM", s));
+	}
+	let hours = parts[0].parse::<u32>().map_err(|_| format!("invalid time {}", s))?;
+	let minutes = parts[1].parse::<u32>().map_err(|_| format!("invalid time {}", s))?;
+	if hours > 24 || minutes > 59 || (hours == 24 && minutes > 0) {
+		return Err(format!("invalid time {}", s));
+	}
+	Ok(hours * 60 + minutes)
+}
+
+fn parse_window(w: &ScheduleWindow) -> Result<Window, String> {
+	let days = match w.days {
+		None => (0..7).collect(),
+		Some(ref days) => {
+			let mut parsed = vec![];
+			for d in days {
+				let prefix: String = d.trim().to_lowercase().chars().take(3).collect();
+				match DAYS.iter().position(|n| *n == prefix) {
+					Some(i) => parsed.push(i as u32),
+					None => return Err(format!("invalid day {}", d)),
+				}
+			}
+			parsed
+		}
+	};
+	let mut description = format!("{}-{}", w.start, w.end);
+	if let Some(ref p) = w.profile {
+		description.push_str(&format!(" ({})", p));
+	}
+	Ok(Window {
+		start: parse_time(&w.start)?,
+		end: parse_time(&w.end)?,
+		days: days,
+		profile: w.profile.clone(),
+		description: description,
+	})
+}
+
+impl Window {
+	/// Whether the window is open at the given minute of the week (0 is
+	/// Sunday midnight). Windows ending before they start run past
+	/// midnight, into the next day.
+	fn contains(&self, minute_of_week: u32) -> bool {
+		let day = minute_of_week / (24 * 60);
+		let minute = minute_of_week % (24 * 60);
+		if self.start == self.end {
+			self.days.contains(&day)
+		} else if self.start < self.end {
+			self.days.contains(&day) && minute >= self.start && minute < self.end
+		} else {
+			(self.days.contains(&day) && minute >= self.start)
+				|| (self.days.contains(&((day + 6) % 7)) && minute < self.end)
+		}
+	}
+}
+
+/// The first window open at the given minute of the week
+fn open_window(windows: &[Window], minute_of_week: u32) -> Option<&Window> {
+	windows.iter().find(|w| w.contains(minute_of_week))
+}
+
+/// Minutes from the given minute of the week until the open window
+/// changes, if it ever does
+fn minutes_to_change(windows: &[Window], minute_of_week: u32) -> Option<u32> {
+	let current = open_window(windows, minute_of_week).map(|w| &w.description);
+	(1..MINUTES_PER_WEEK).find(|m| {
+		open_window(windows, (minute_of_week + m) % MINUTES_PER_WEEK).map(|w| &w.description)
+			!= current
+	})
+}
+
+/// Pauses and resumes mining (switching profiles if a window has one)
+/// as the configured windows open and close
+pub struct Scheduler {
+	config: SchedulerConfig,
+	stats: Arc<RwLock<Stats>>,
+	miner_tx: mpsc::Sender<MinerMessage>,
+}
+
+impl Scheduler {
+	/// Create a new scheduler
+	pub fn new(
+		config: SchedulerConfig,
+		stats: Arc<RwLock<Stats>>,
+		miner_tx: mpsc::Sender<MinerMessage>,
+	) -> Scheduler {
+		Scheduler {
+			config: config,
+			stats: stats,
+			miner_tx: miner_tx,
+		}
+	}
+
+	/// Start scheduling in a new thread
+	pub fn start(self) {
+		let windows = match self
+			.config
+			.windows
+			.iter()
+			.map(parse_window)
+			.collect::<Result<Vec<Window>, String>>()
+		{
+			Ok(w) => w,
+			Err(e) => {
+				error!(LOGGER, "Invalid scheduler configuration, not scheduling: {}", e);
+				return;
+			}
+		};
+		if windows.is_empty() {
+			warn!(LOGGER, "Scheduler enabled without any windows, not scheduling");
+			return;
+		}
+		info!(
+			LOGGER,
+			"Mining only during: {}",
+			windows
+				.iter()
+				.map(|w| w.description.clone())
+				.collect::<Vec<String>>()
+				.join(", ")
+		);
+		let _ = thread::Builder::new()
+			.name("scheduler".to_string())
+			.spawn(move || {
+				let mut was_open: Option<Option<String>> = None;
+				loop {
+					let now = time::now();
+					let minute_of_week =
+						(now.tm_wday * 24 * 60 + now.tm_hour * 60 + now.tm_min) as u32;
+					let open = open_window(&windows, minute_of_week);
+					let current = open.map(|w| w.description.clone());
+					if was_open.as_ref() != Some(&current) {
+						self.transition(open, was_open.is_none());
+						was_open = Some(current);
+					}
+					self.update_stats(&windows, open, minute_of_week);
+					thread::sleep(Duration::from_secs(self.config.check_interval_secs.max(1)));
+				}
+			});
+	}
+
+	/// Pause or resume as a window opens or closes, or switch profile when
+	/// moving between windows
+	fn transition(&self, open: Option<&Window>, first: bool) {
+		match open {
+			Some(w) => {
+				warn!(LOGGER, "Scheduled mining window {} open", w.description);
+				if let Some(ref p) = w.profile {
+					let _ = self.miner_tx.send(MinerMessage::SwitchProfile(p.clone()));
+				}
+				if !first {
+					let _ = self.miner_tx.send(MinerMessage::Resume);
+				}
+			}
+			None => {
+				warn!(LOGGER, "Outside scheduled mining windows, pausing");
+				let _ = self.miner_tx.send(MinerMessage::Pause);
+			}
+		}
+	}
+
+	fn update_stats(&self, windows: &[Window], open: Option<&Window>, minute_of_week: u32) {
+		let next_change = minutes_to_change(windows, minute_of_week)
+			.map(|m| time::get_time().sec - time::now().tm_sec as i64 + m as i64 * 60);
+		let mut stats = self.stats.write().unwrap();
+		stats.mining_stats.schedule = Some(ScheduleStatus {
+			active: open.is_some(),
+			window: open.map(|w| w.description.clone()),
+			next_change: next_change,
+		});
+	}
+}
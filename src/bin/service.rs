@@ -0,0 +1,157 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Windows service wrapper: `grin-miner service install` registers the
+//! miner to start at boot without anyone logged in, and the service
+//! control manager runs it with `service run`, stopping it with the same
+//! drain as Ctrl+C. Under systemd, see the Type=notify support instead.
+
+/// Name the service is registered under
+pub const SERVICE_NAME: &str = "grin-miner";
+
+#[cfg(not(windows))]
+const NOT_WINDOWS: &str = "Services are only supported on Windows, under systemd run the miner \
+                           from a unit with Type=notify instead";
+
+/// Register the service, to start at boot with the given config file
+#[cfg(not(windows))]
+pub fn install(_config_path: &str) -> Result<(), String> {
+	Err(NOT_WINDOWS.to_string())
+}
+
+/// Remove the service
+#[cfg(not(windows))]
+pub fn uninstall() -> Result<(), String> {
+	Err(NOT_WINDOWS.to_string())
+}
+
+/// Run as the service, mining with the given function until the service
+/// is stopped
+#[cfg(not(windows))]
+pub fn run(_miner: Box<dyn FnMut() + Send>) -> Result<(), String> {
+	Err(NOT_WINDOWS.to_string())
+}
+
+#[cfg(windows)]
+pub use self::windows::{install, run, uninstall};
+
+#[cfg(windows)]
+mod windows {
+	use std::env;
+	use std::ffi::OsString;
+	use std::sync::Mutex;
+	use std::time::Duration;
+
+	use windows_service::service::{
+		ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+		ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+	};
+	use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+	use windows_service::service_dispatcher;
+	use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+	use super::SERVICE_NAME;
+	use signals;
+	use util::LOGGER;
+
+	lazy_static! {
+		/// The miner the service runs, handed over to the dispatcher's thread
+		static ref MINER: Mutex<Option<Box<dyn FnMut() + Send>>> = Mutex::new(None);
+	}
+
+	define_windows_service!(ffi_service_main, service_main);
+
+	pub fn install(config_path: &str) -> Result<(), String> {
+		let manager = ServiceManager::local_computer(
+			None::<&str>,
+			ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+		).map_err(|e| format!("Unable to open the service manager: {}", e))?;
+		let exe = env::current_exe().map_err(|e| format!("{}", e))?;
+		let info = ServiceInfo {
+			name: OsString::from(SERVICE_NAME),
+			display_name: OsString::from("Grin Miner"),
+			service_type: ServiceType::OWN_PROCESS,
+			start_type: ServiceStartType::AutoStart,
+			error_control: ServiceErrorControl::Normal,
+			executable_path: exe,
+			launch_arguments: vec![
+				OsString::from("--config"),
+				OsString::from(config_path),
+				OsString::from("service"),
+				OsString::from("run"),
+			],
+			account_name: None,
+			account_password: None,
+		};
+		manager
+			.create_service(info, ServiceAccess::empty())
+			.map_err(|e| format!("Unable to install the service: {}", e))?;
+		Ok(())
+	}
+
+	pub fn uninstall() -> Result<(), String> {
+		let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+			.map_err(|e| format!("Unable to open the service manager: {}", e))?;
+		let service = manager
+			.open_service(SERVICE_NAME, ServiceAccess::DELETE)
+			.map_err(|e| format!("Unable to open the service: {}", e))?;
+		service
+			.delete()
+			.map_err(|e| format!("Unable to remove the service: {}", e))
+	}
+
+	pub fn run(miner: Box<dyn FnMut() + Send>) -> Result<(), String> {
+		*MINER.lock().unwrap() = Some(miner);
+		service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+			.map_err(|e| format!("Unable to start as a service, it must be started by the service manager: {}", e))
+	}
+
+	fn status(state: ServiceState, wait_hint_secs: u64) -> ServiceStatus {
+		ServiceStatus {
+			service_type: ServiceType::OWN_PROCESS,
+			current_state: state,
+			controls_accepted: if state == ServiceState::Running {
+				ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+			} else {
+				ServiceControlAccept::empty()
+			},
+			exit_code: ServiceExitCode::Win32(0),
+			checkpoint: 0,
+			wait_hint: Duration::from_secs(wait_hint_secs),
+		}
+	}
+
+	fn service_main(_arguments: Vec<OsString>) {
+		let handler = move |event| match event {
+			ServiceControl::Stop | ServiceControl::Shutdown => {
+				signals::request_shutdown();
+				ServiceControlHandlerResult::NoError
+			}
+			ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+			_ => ServiceControlHandlerResult::NotImplemented,
+		};
+		let status_handle = match service_control_handler::register(SERVICE_NAME, handler) {
+			Ok(h) => h,
+			Err(e) => {
+				error!(LOGGER, "Unable to register the service control handler: {}", e);
+				return;
+			}
+		};
+		let _ = status_handle.set_service_status(status(ServiceState::Running, 0));
+		if let Some(mut miner) = MINER.lock().unwrap().take() {
+			miner();
+		}
+		let _ = status_handle.set_service_status(status(ServiceState::Stopped, 0));
+	}
+}
@@ -0,0 +1,310 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stats published in a memory-mapped file, for monitoring sidecars to
+//! read locally without the HTTP API. The file has a fixed size: a header
+//! with the main figures, then the status line of `--output json` as the
+//! payload. Updates are bracketed by a sequence number, odd while one is
+//! being written, so readers can tell a consistent copy (see
+//! doc/shared_stats.md).
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use config::SharedStatsConfig;
+use json_status;
+use stats::Stats;
+use util::LOGGER;
+
+/// Identifies the file
+pub const MAGIC: &[u8; 8] = b"GRNSTATS";
+
+/// Version of the layout, bumped when a field moves or changes meaning.
+/// Fields are only added in the reserved part of the header.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// Size of the header, the payload follows
+pub const HEADER_SIZE: usize = 128;
+
+/// Offset of the sequence number, odd while an update is written
+const SEQUENCE_OFFSET: usize = 16;
+
+/// Flags of the header
+const FLAG_CONNECTED: u32 = 1;
+const FLAG_PAUSED: u32 = 2;
+const FLAG_ALERTS: u32 = 4;
+
+/// The file, mapped in memory
+#[cfg(unix)]
+struct Segment {
+	_file: File,
+	ptr: *mut u8,
+	len: usize,
+}
+
+#[cfg(unix)]
+impl Segment {
+	fn map(file: File, len: usize) -> io::Result<Segment> {
+		use libc;
+		use std::os::unix::io::AsRawFd;
+		use std::ptr;
+		let ptr = unsafe {
+			libc::mmap(
+				ptr::null_mut(),
+				len,
+				libc::PROT_READ | libc::PROT_WRITE,
+				libc::MAP_SHARED,
+				file.as_raw_fd(),
+				0,
+			)
+		};
+		if ptr == libc::MAP_FAILED {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(Segment {
+			_file: file,
+			ptr: ptr as *mut u8,
+			len: len,
+		})
+	}
+
+	fn read_u64(&mut self, offset: usize) -> io::Result<u64> {
+		use std::ptr;
+		Ok(u64::from_le(unsafe {
+			ptr::read_volatile(self.ptr.add(offset) as *const u64)
+		}))
+	}
+
+	/// Write the sequence number, ordered after the writes before it
+	fn write_sequence(&mut self, sequence: u64) -> io::Result<()> {
+		use std::ptr;
+		use std::sync::atomic::{fence, Ordering};
+		fence(Ordering::Release);
+		unsafe {
+			ptr::write_volatile(self.ptr.add(SEQUENCE_OFFSET) as *mut u64, sequence.to_le());
+		}
+		fence(Ordering::Release);
+		Ok(())
+	}
+
+	fn write(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()> {
+		use std::ptr;
+		assert!(offset + bytes.len() <= self.len);
+		unsafe {
+			ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(offset), bytes.len());
+		}
+		Ok(())
+	}
+}
+
+#[cfg(unix)]
+impl Drop for Segment {
+	fn drop(&mut self) {
+		use libc;
+		unsafe {
+			libc::munmap(self.ptr as *mut libc::c_void, self.len);
+		}
+	}
+}
+
+/// The file, written in place where it can't be mapped
+#[cfg(not(unix))]
+struct Segment {
+	file: File,
+}
+
+#[cfg(not(unix))]
+impl Segment {
+	fn map(file: File, _len: usize) -> io::Result<Segment> {
+		Ok(Segment { file: file })
+	}
+
+	fn read_u64(&mut self, offset: usize) -> io::Result<u64> {
+		use std::io::{Read, Seek, SeekFrom};
+		let mut bytes = [0u8; 8];
+		self.file.seek(SeekFrom::Start(offset as u64))?;
+		self.file.read_exact(&mut bytes)?;
+		Ok(u64_from_le(&bytes))
+	}
+
+	fn write_sequence(&mut self, sequence: u64) -> io::Result<()> {
+		self.write(SEQUENCE_OFFSET, &u64_to_le(sequence))
+	}
+
+	fn write(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()> {
+		use std::io::{Seek, SeekFrom, Write};
+		self.file.seek(SeekFrom::Start(offset as u64))?;
+		self.file.write_all(bytes)
+	}
+}
+
+#[cfg(not(unix))]
+fn u64_from_le(bytes: &[u8; 8]) -> u64 {
+	bytes
+		.iter()
+		.rev()
+		.fold(0, |value, b| value << 8 | *b as u64)
+}
+
+fn u64_to_le(value: u64) -> [u8; 8] {
+	let mut bytes = [0u8; 8];
+	for (i, b) in bytes.iter_mut().enumerate() {
+		*b = (value >> (i * 8)) as u8;
+	}
+	bytes
+}
+
+fn u32_to_le(value: u32) -> [u8; 4] {
+	let mut bytes = [0u8; 4];
+	for (i, b) in bytes.iter_mut().enumerate() {
+		*b = (value >> (i * 8)) as u8;
+	}
+	bytes
+}
+
+/// The header after the sequence number, and the payload, for the stats
+fn snapshot(stats: &Stats, size: usize) -> (Vec<u8>, Vec<u8>) {
+	let mut payload = json_status::status_line(stats).into_bytes();
+	if HEADER_SIZE + payload.len() > size {
+		payload.clear();
+	}
+	let mut devices = 0u32;
+	let mut errored = 0u32;
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		for d in plugins.iter().flat_map(|p| p.iter()).filter(|d| d.in_use != 0) {
+			devices += 1;
+			if d.has_errored != 0 {
+				errored += 1;
+			}
+		}
+	}
+	let mut flags = 0;
+	if stats.client_stats.connected {
+		flags |= FLAG_CONNECTED;
+	}
+	if stats.mining_stats.paused {
+		flags |= FLAG_PAUSED;
+	}
+	if !stats.alerts.is_empty() {
+		flags |= FLAG_ALERTS;
+	}
+	let mut header = Vec::with_capacity(HEADER_SIZE - SEQUENCE_OFFSET - 8);
+	header.extend_from_slice(&u64_to_le(size as u64));
+	header.extend_from_slice(&u64_to_le(time::get_time().sec as u64));
+	header.extend_from_slice(&u32_to_le(payload.len() as u32));
+	header.extend_from_slice(&u32_to_le(json_status::SCHEMA_VERSION));
+	header.extend_from_slice(&u64_to_le(stats.mining_stats.combined_gps.to_bits()));
+	header.extend_from_slice(&u64_to_le(stats.client_stats.shares_accepted));
+	header.extend_from_slice(&u64_to_le(stats.client_stats.shares_rejected));
+	header.extend_from_slice(&u64_to_le(stats.mining_stats.block_height));
+	header.extend_from_slice(&u32_to_le(flags));
+	header.extend_from_slice(&u32_to_le(devices));
+	header.extend_from_slice(&u32_to_le(errored));
+	header.resize(HEADER_SIZE - SEQUENCE_OFFSET - 8, 0);
+	(header, payload)
+}
+
+/// Publishes the stats in the file
+pub struct SharedStats {
+	config: SharedStatsConfig,
+	stats: Arc<RwLock<Stats>>,
+}
+
+impl SharedStats {
+	/// Create a new publisher
+	pub fn new(config: SharedStatsConfig, stats: Arc<RwLock<Stats>>) -> SharedStats {
+		SharedStats {
+			config: config,
+			stats: stats,
+		}
+	}
+
+	/// Size of the file
+	fn size(&self) -> usize {
+		(self.config.size_kb.max(1) * 1024) as usize
+	}
+
+	/// Create (or resize) and map the file, writing its fixed fields
+	fn open(&self) -> io::Result<(Segment, u64)> {
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.open(&self.config.path)?;
+		file.set_len(self.size() as u64)?;
+		let mut segment = Segment::map(file, self.size())?;
+		// carry on the sequence of a previous run, so readers don't see it
+		// go back
+		let sequence = segment.read_u64(SEQUENCE_OFFSET)?;
+		let sequence = sequence + sequence % 2;
+		segment.write_sequence(sequence + 1)?;
+		segment.write(0, MAGIC)?;
+		segment.write(8, &u32_to_le(LAYOUT_VERSION))?;
+		segment.write(12, &u32_to_le(HEADER_SIZE as u32))?;
+		segment.write_sequence(sequence + 2)?;
+		Ok((segment, sequence + 2))
+	}
+
+	/// Start publishing in a new thread
+	pub fn start(self) {
+		info!(
+			LOGGER,
+			"Publishing stats in {} every {} seconds", self.config.path, self.config.interval_secs
+		);
+		let _ = thread::Builder::new()
+			.name("shared_stats".to_string())
+			.spawn(move || {
+				let (mut segment, mut sequence) = match self.open() {
+					Ok(s) => s,
+					Err(e) => {
+						error!(LOGGER, "Unable to map {}: {}", self.config.path, e);
+						return;
+					}
+				};
+				let mut warned = false;
+				loop {
+					let (header, payload) = {
+						let stats = self.stats.read().unwrap();
+						snapshot(&stats, self.size())
+					};
+					if payload.is_empty() && !warned {
+						warn!(
+							LOGGER,
+							"The stats don't fit in {}, publishing the header only, raise size_kb",
+							self.config.path
+						);
+						warned = true;
+					}
+					let result = segment
+						.write_sequence(sequence + 1)
+						.and_then(|_| segment.write(SEQUENCE_OFFSET + 8, &header))
+						.and_then(|_| segment.write(HEADER_SIZE, &payload))
+						.and_then(|_| segment.write_sequence(sequence + 2));
+					if let Err(e) = result {
+						error!(
+							LOGGER,
+							"Unable to publish the stats in {}: {}", self.config.path, e
+						);
+					}
+					sequence += 2;
+					thread::sleep(Duration::from_secs(self.config.interval_secs.max(1)));
+				}
+			});
+	}
+}
@@ -0,0 +1,162 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signal handling: SIGTERM and SIGINT drain the miner and exit, SIGUSR1
+//! dumps a stats snapshot to the log (and writes the session report),
+//! SIGHUP reloads the config. On Windows, Ctrl+C and closing the console,
+//! logging off or shutting down drain, and Ctrl+Break dumps the stats.
+//! The handlers only raise flags, which the main loop acts on.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use stats::Stats;
+use util::LOGGER;
+
+/// Seconds a drain may take before the miner exits anyway
+pub const DRAIN_TIMEOUT_SECS: i64 = 30;
+
+lazy_static! {
+	/// The registered flags, for handlers that aren't closures over them
+	static ref REGISTERED: Mutex<Option<Signals>> = Mutex::new(None);
+}
+
+/// Ask the miner to drain and exit as if it got SIGTERM, e.g. when the
+/// service it runs as is stopped. Exits straight away if it isn't mining
+/// yet.
+pub fn request_shutdown() {
+	match *REGISTERED.lock().unwrap() {
+		Some(ref s) => s.shutdown.store(true, Ordering::Relaxed),
+		None => ::std::process::exit(0),
+	}
+}
+
+/// Flags raised by signals
+#[derive(Clone)]
+pub struct Signals {
+	/// Drain and exit
+	pub shutdown: Arc<AtomicBool>,
+	/// Dump a stats snapshot to the log
+	pub stats_dump: Arc<AtomicBool>,
+	/// Reload the config
+	pub reload: Arc<AtomicBool>,
+}
+
+impl Signals {
+	/// Register the handlers
+	pub fn register() -> Signals {
+		let signals = Signals {
+			shutdown: Arc::new(AtomicBool::new(false)),
+			stats_dump: Arc::new(AtomicBool::new(false)),
+			reload: Arc::new(AtomicBool::new(false)),
+		};
+		signals.register_platform();
+		*REGISTERED.lock().unwrap() = Some(signals.clone());
+		signals
+	}
+
+	#[cfg(unix)]
+	fn register_platform(&self) {
+		use signal_hook;
+		let handlers = [
+			(signal_hook::SIGTERM, &self.shutdown, "SIGTERM"),
+			(signal_hook::SIGINT, &self.shutdown, "SIGINT"),
+			(signal_hook::SIGUSR1, &self.stats_dump, "SIGUSR1"),
+			(signal_hook::SIGHUP, &self.reload, "SIGHUP"),
+		];
+		for &(signal, flag, name) in &handlers {
+			if let Err(e) = signal_hook::flag::register(signal, flag.clone()) {
+				warn!(LOGGER, "Unable to register {} handler: {}", name, e);
+			}
+		}
+	}
+
+	#[cfg(windows)]
+	fn register_platform(&self) {
+		windows::register();
+	}
+
+	/// Whether a flag was raised since it was last checked
+	pub fn take(flag: &Arc<AtomicBool>) -> bool {
+		flag.swap(false, Ordering::Relaxed)
+	}
+}
+
+/// Log a snapshot of the stats
+pub fn log_stats_snapshot(stats: &Stats) {
+	info!(LOGGER, "Stats snapshot requested");
+	info!(LOGGER, "{}", stats.mining_status().0);
+	info!(LOGGER, "{}", stats.client_stats.connection_status);
+	info!(
+		LOGGER,
+		"Block height {}, target difficulty {}, {:.4} gps",
+		stats.mining_stats.block_height,
+		stats.mining_stats.target_difficulty,
+		stats.mining_stats.combined_gps
+	);
+	for line in stats.session_summary().lines().skip(1) {
+		info!(LOGGER, "{}", line);
+	}
+}
+
+#[cfg(windows)]
+mod windows {
+	use std::sync::atomic::Ordering;
+	use std::thread;
+	use std::time::Duration;
+
+	use super::REGISTERED;
+	use util::LOGGER;
+
+	const CTRL_C_EVENT: u32 = 0;
+	const CTRL_BREAK_EVENT: u32 = 1;
+	const CTRL_CLOSE_EVENT: u32 = 2;
+	const CTRL_LOGOFF_EVENT: u32 = 5;
+	const CTRL_SHUTDOWN_EVENT: u32 = 6;
+
+	/// Seconds to hold a closing console open while the miner drains,
+	/// Windows ends the process soon after regardless
+	const CLOSE_GRACE_SECS: u64 = 5;
+
+	extern "system" {
+		fn SetConsoleCtrlHandler(
+			handler: Option<unsafe extern "system" fn(u32) -> i32>,
+			add: i32,
+		) -> i32;
+	}
+
+	unsafe extern "system" fn handler(event: u32) -> i32 {
+		let signals = match REGISTERED.lock().ok().and_then(|s| s.clone()) {
+			Some(s) => s,
+			None => return 0,
+		};
+		match event {
+			CTRL_C_EVENT => signals.shutdown.store(true, Ordering::Relaxed),
+			CTRL_BREAK_EVENT => signals.stats_dump.store(true, Ordering::Relaxed),
+			CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+				signals.shutdown.store(true, Ordering::Relaxed);
+				// the process ends when the handler returns
+				thread::sleep(Duration::from_secs(CLOSE_GRACE_SECS));
+			}
+			_ => return 0,
+		}
+		1
+	}
+
+	pub fn register() {
+		if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+			warn!(LOGGER, "Unable to register the console event handler");
+		}
+	}
+}
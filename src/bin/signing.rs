@@ -0,0 +1,92 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Submit signing, for pools binding shares to a worker token so they
+//! can't be taken over by a man in the middle. The miner offers the
+//! schemes it supports at login, and signs its submits once the pool
+//! answers the login naming one of them.
+
+use sha2::{Digest, Sha256};
+
+use util;
+
+/// HMAC-SHA256 of the share with the worker token, hex encoded
+pub const HMAC_SHA256: &str = "hmac-sha256";
+
+/// HMAC-SHA256 (RFC 2104)
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+	const BLOCK_LEN: usize = 64;
+	let mut key = if key.len() > BLOCK_LEN {
+		Sha256::digest(key).to_vec()
+	} else {
+		key.to_vec()
+	};
+	key.resize(BLOCK_LEN, 0);
+	let mut inner = Sha256::new();
+	inner.input(&key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+	inner.input(message);
+	let mut outer = Sha256::new();
+	outer.input(&key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+	outer.input(&inner.result());
+	outer.result().to_vec()
+}
+
+/// What's signed of a share: the login it's submitted under and every
+/// submit field, e.g. `login:height:job_id:edge_bits:nonce:pow,...`
+pub fn share_message(
+	login: &str,
+	height: u64,
+	job_id: u64,
+	edge_bits: u32,
+	nonce: u64,
+	pow: &[u32],
+) -> String {
+	let pow: Vec<String> = pow.iter().map(|p| p.to_string()).collect();
+	format!(
+		"{}:{}:{}:{}:{}:{}",
+		login,
+		height,
+		job_id,
+		edge_bits,
+		nonce,
+		pow.join(",")
+	)
+}
+
+/// Signature of a share message with the worker token
+pub fn sign(token: &str, message: &str) -> String {
+	util::to_hex(hmac_sha256(token.as_bytes(), message.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn hmac_sha256_rfc4231() {
+		// test case 2 of RFC 4231
+		assert_eq!(
+			util::to_hex(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+			"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+		);
+		// test case 6, a key longer than the block
+		assert_eq!(
+			util::to_hex(hmac_sha256(
+				&[0xaa; 131],
+				b"Test Using Larger Than Block-Size Key - Hash Key First"
+			)),
+			"60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+		);
+	}
+}
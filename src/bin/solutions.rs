@@ -0,0 +1,192 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bounded buffer of the solutions found, handed from the miner to the
+//! stratum client. A client stalled on a long pool outage takes nothing
+//! from it, so once full the overflow policy decides which solution goes:
+//! the oldest, or the one of lowest difficulty, the least likely to be
+//! worth anything once it's submitted.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chain::Chain;
+
+/// Default number of solutions the buffer holds
+pub const DEFAULT_SIZE: usize = 256;
+
+/// Which solution goes when the buffer is full
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+	DropOldest,
+	DropLowestDifficulty,
+}
+
+impl OverflowPolicy {
+	/// Parse a policy name, drop-oldest if none is given
+	pub fn from_name(name: Option<&str>) -> Result<OverflowPolicy, String> {
+		match name {
+			None | Some("drop-oldest") => Ok(OverflowPolicy::DropOldest),
+			Some("drop-lowest-difficulty") => Ok(OverflowPolicy::DropLowestDifficulty),
+			Some(n) => Err(format!(
+				"Unknown solution_overflow_policy {}, expected drop-oldest or \
+				 drop-lowest-difficulty",
+				n
+			)),
+		}
+	}
+}
+
+/// A solution waiting for the client
+#[derive(Clone, Debug)]
+pub struct FoundSolution {
+	pub height: u64,
+	pub job_id: u64,
+	pub edge_bits: u32,
+	pub nonce: u64,
+	pub pow: Vec<u32>,
+	/// Unscaled difficulty, worked out for drop-lowest-difficulty only
+	difficulty: u64,
+}
+
+struct Buffer {
+	solutions: VecDeque<FoundSolution>,
+	overflowed: u64,
+}
+
+/// The buffer, shared by the miner and the client
+#[derive(Clone)]
+pub struct SolutionBuffer {
+	size: usize,
+	policy: OverflowPolicy,
+	chain: Arc<dyn Chain>,
+	buffer: Arc<Mutex<Buffer>>,
+}
+
+impl SolutionBuffer {
+	pub fn new(size: usize, policy: OverflowPolicy, chain: Arc<dyn Chain>) -> SolutionBuffer {
+		SolutionBuffer {
+			size: size.max(1),
+			policy: policy,
+			chain: chain,
+			buffer: Arc::new(Mutex::new(Buffer {
+				solutions: VecDeque::new(),
+				overflowed: 0,
+			})),
+		}
+	}
+
+	/// Add a solution, returning the one dropped to make room, if any
+	pub fn push(
+		&self,
+		height: u64,
+		job_id: u64,
+		edge_bits: u32,
+		nonce: u64,
+		pow: Vec<u32>,
+	) -> Option<FoundSolution> {
+		let difficulty = match self.policy {
+			OverflowPolicy::DropOldest => 0,
+			OverflowPolicy::DropLowestDifficulty => self.chain.share_difficulty(edge_bits, &pow),
+		};
+		let solution = FoundSolution {
+			height: height,
+			job_id: job_id,
+			edge_bits: edge_bits,
+			nonce: nonce,
+			pow: pow,
+			difficulty: difficulty,
+		};
+		let mut buffer = self.buffer.lock().unwrap();
+		if buffer.solutions.len() < self.size {
+			buffer.solutions.push_back(solution);
+			return None;
+		}
+		buffer.overflowed += 1;
+		let dropped = match self.policy {
+			OverflowPolicy::DropOldest => buffer.solutions.pop_front(),
+			OverflowPolicy::DropLowestDifficulty => {
+				let lowest = buffer
+					.solutions
+					.iter()
+					.enumerate()
+					.min_by_key(|&(_, s)| s.difficulty)
+					.map(|(i, s)| (i, s.difficulty));
+				match lowest {
+					Some((i, d)) if d < solution.difficulty => buffer.solutions.remove(i),
+					// the new one is the lowest
+					_ => return Some(solution),
+				}
+			}
+		};
+		buffer.solutions.push_back(solution);
+		dropped
+	}
+
+	/// Take every solution buffered, oldest first
+	pub fn take(&self) -> Vec<FoundSolution> {
+		self.buffer.lock().unwrap().solutions.drain(..).collect()
+	}
+
+	/// Solutions buffered
+	pub fn queued(&self) -> usize {
+		self.buffer.lock().unwrap().solutions.len()
+	}
+
+	/// Solutions dropped for lack of room
+	pub fn overflowed(&self) -> u64 {
+		self.buffer.lock().unwrap().overflowed
+	}
+
+	pub fn size(&self) -> usize {
+		self.size
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chain::Grin;
+
+	#[test]
+	fn test_drop_oldest() {
+		let buffer = SolutionBuffer::new(2, OverflowPolicy::DropOldest, Arc::new(Grin));
+		assert!(buffer.push(1, 1, 29, 1, vec![1; 42]).is_none());
+		assert!(buffer.push(1, 1, 29, 2, vec![2; 42]).is_none());
+		assert_eq!(buffer.push(1, 1, 29, 3, vec![3; 42]).unwrap().nonce, 1);
+		assert_eq!(buffer.overflowed(), 1);
+		let nonces: Vec<u64> = buffer.take().iter().map(|s| s.nonce).collect();
+		assert_eq!(nonces, vec![2, 3]);
+		assert_eq!(buffer.queued(), 0);
+	}
+
+	#[test]
+	fn test_drop_lowest_difficulty() {
+		let chain = Arc::new(Grin);
+		let buffer = SolutionBuffer::new(2, OverflowPolicy::DropLowestDifficulty, chain.clone());
+		let pows: Vec<Vec<u32>> = (0..3).map(|n| vec![n; 42]).collect();
+		for (n, pow) in pows.iter().enumerate() {
+			buffer.push(1, 1, 29, n as u64, pow.clone());
+		}
+		let lowest = pows
+			.iter()
+			.map(|p| chain.share_difficulty(29, p))
+			.min()
+			.unwrap();
+		let kept = buffer.take();
+		assert_eq!(kept.len(), 2);
+		assert!(kept.iter().all(|s| chain.share_difficulty(29, &s.pow) > lowest));
+		assert_eq!(buffer.overflowed(), 1);
+	}
+}
@@ -18,32 +18,712 @@
 /// Struct to return relevant information about the mining process
 /// back to interested callers (such as the TUI)
  
+use std::collections::{HashMap, VecDeque};
+
+use sha2::{Digest, Sha256};
+use time;
+
+use clock;
+use correlation;
+use config::RigConfig;
 use util;
+use util::cuckoo_miner::CuckooMinerDeviceStats;
+use alerts::Alert;
+use units;
+
+/// Hardware readings for a single device, as reported by a hardware
+/// monitoring source. Readings the source can't provide are left empty.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DeviceHardwareStats {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id, as reported by the plugin
+	pub device_id: String,
+	/// Temperature, in degrees celsius
+	pub temperature: Option<u32>,
+	/// Fan speed, in percent
+	pub fan_speed: Option<u32>,
+	/// Power draw, in watts
+	pub power_usage: Option<f64>,
+	/// Core clock, in MHz
+	pub core_clock: Option<u32>,
+	/// Memory clock, in MHz
+	pub memory_clock: Option<u32>,
+	/// PCI bus id of the device the readings were matched to
+	pub pci_bus_id: Option<String>,
+}
+
+/// A device paused until its next recovery attempt, for erroring
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceQuarantine {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id, as reported by the plugin
+	pub device_id: String,
+	/// When the device is tried again (seconds since epoch)
+	pub until: i64,
+	/// The recovery attempt it's tried again with
+	pub attempt: u32,
+}
+
+/// Identity of a device that stays the same across restarts, unlike its
+/// plugin index and device id, which change when plugins or GPUs are
+/// reordered
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceIdentity {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id, as reported by the plugin
+	pub device_id: String,
+	/// Plugin name, e.g. cuckatoo_cuda_31
+	pub plugin_name: String,
+	/// The stable identity
+	pub uid: String,
+	/// Proof sizes (edge bits) the device solves, jobs for other sizes
+	/// aren't given to it
+	#[serde(default)]
+	pub edge_bits: Vec<u32>,
+}
+
+/// Stable identity of a device: its plugin, the PCI bus id of its GPU
+/// (its device id for devices without one) and a hash of its name
+pub fn device_uid(
+	plugin_name: &str,
+	bus_id: Option<&str>,
+	device_id: &str,
+	device_name: &str,
+) -> String {
+	let hash = util::to_hex(Sha256::digest(device_name.as_bytes()).to_vec());
+	format!("{}-{}-{}", plugin_name, bus_id.unwrap_or(device_id), &hash[..8])
+}
+
+/// Power efficiency of a single device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceEfficiency {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id, as reported by the plugin
+	pub device_id: String,
+	/// Current graphs per second
+	pub gps: f64,
+	/// Power draw, in watts
+	pub power_usage: f64,
+	/// Graphs per joule (gps per watt)
+	pub graphs_per_joule: f64,
+}
+
+/// Power efficiency of the devices reporting their power draw, and of the
+/// whole rig
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Efficiency {
+	/// Devices reporting their power draw
+	pub devices: Vec<DeviceEfficiency>,
+	/// Total power draw of those devices, in watts
+	pub power_usage: f64,
+	/// Combined graphs per joule of those devices
+	pub graphs_per_joule: f64,
+	/// Estimated electricity cost per day at the current power draw, if
+	/// an electricity price is configured
+	pub daily_cost: Option<f64>,
+}
+
+/// Graphs per second of a device, from the time its last graph took. 0
+/// until it completes a graph.
+pub fn device_gps(d: &CuckooMinerDeviceStats) -> f64 {
+	if d.last_solution_time == 0 {
+		0.0
+	} else {
+		1.0 / (d.last_solution_time as f64 / 1000000000.0)
+	}
+}
+
+/// Whether a device counts towards the rig's GPS: in use and not errored
+pub fn counts_towards_gps(d: &CuckooMinerDeviceStats) -> bool {
+	d.in_use == 1 && d.has_errored == 0
+}
+
+/// GPS of a single device
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DeviceGps {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	pub device_id: String,
+	pub gps: f64,
+}
+
+/// Combined and per-device GPS of the rig. Every consumer (log, TUI, api,
+/// exporters) works GPS out through this, so they agree.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RigGps {
+	pub combined: f64,
+	/// Devices counting towards the GPS
+	pub devices: Vec<DeviceGps>,
+}
+
+impl RigGps {
+	/// GPS from the devices' last graph times
+	pub fn current(device_stats: &[Vec<CuckooMinerDeviceStats>]) -> RigGps {
+		let mut rig = RigGps::default();
+		for (i, plugin) in device_stats.iter().enumerate() {
+			for d in plugin.iter().filter(|d| counts_towards_gps(d)) {
+				let gps = device_gps(d);
+				rig.combined += gps;
+				rig.devices.push(DeviceGps {
+					plugin: i,
+					device_id: d.device_id.clone(),
+					gps: gps,
+				});
+			}
+		}
+		rig
+	}
+
+	/// Combined GPS of the devices of a plugin
+	pub fn plugin_gps(&self, plugin: usize) -> f64 {
+		self.devices
+			.iter()
+			.filter(|d| d.plugin == plugin)
+			.map(|d| d.gps)
+			.sum()
+	}
+
+	/// GPS of a device, 0 if it doesn't count
+	pub fn device(&self, plugin: usize, device_id: &str) -> f64 {
+		self.devices
+			.iter()
+			.find(|d| d.plugin == plugin && d.device_id == device_id)
+			.map_or(0.0, |d| d.gps)
+	}
+}
+
+/// Length of the longest rolling window, in seconds
+const GPS_HISTORY_SECS: i64 = 15 * 60;
+
+/// GPS averaged over several windows, and its extremes
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GpsAverages {
+	/// Average over the last minute
+	pub avg_1m: f64,
+	/// Average over the last 5 minutes
+	pub avg_5m: f64,
+	/// Average over the last 15 minutes
+	pub avg_15m: f64,
+	/// Average over the whole session
+	pub avg_session: f64,
+	/// Lowest sample of the session
+	pub min: f64,
+	/// Highest sample of the session
+	pub max: f64,
+}
+
+/// Timestamped GPS samples, for rolling averages
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct GpsHistory {
+	samples: VecDeque<(i64, f64)>,
+	session_sum: f64,
+	session_samples: u64,
+	min: Option<f64>,
+	max: Option<f64>,
+}
 
-#[derive(Clone)]
+impl GpsHistory {
+	/// Add a sample taken at the given time (seconds on the monotonic
+	/// clock)
+	pub fn add(&mut self, time: i64, gps: f64) {
+		if !gps.is_finite() {
+			return;
+		}
+		self.samples.push_back((time, gps));
+		while self
+			.samples
+			.front()
+			.map_or(false, |s| s.0 <= time - GPS_HISTORY_SECS)
+		{
+			self.samples.pop_front();
+		}
+		self.session_sum += gps;
+		self.session_samples += 1;
+		self.min = Some(self.min.map_or(gps, |m| m.min(gps)));
+		self.max = Some(self.max.map_or(gps, |m| m.max(gps)));
+	}
+
+	/// Average over the last secs seconds (up to 15 minutes), 0 without
+	/// samples
+	pub fn window_average(&self, now: i64, secs: i64) -> f64 {
+		let (sum, count) = self
+			.samples
+			.iter()
+			.filter(|s| s.0 > now - secs)
+			.fold((0.0, 0), |acc, s| (acc.0 + s.1, acc.1 + 1));
+		if count == 0 {
+			0.0
+		} else {
+			sum / count as f64
+		}
+	}
+
+	/// Averages as of the given time
+	pub fn averages(&self, now: i64) -> GpsAverages {
+		GpsAverages {
+			avg_1m: self.window_average(now, 60),
+			avg_5m: self.window_average(now, 5 * 60),
+			avg_15m: self.window_average(now, 15 * 60),
+			avg_session: if self.session_samples == 0 {
+				0.0
+			} else {
+				self.session_sum / self.session_samples as f64
+			},
+			min: self.min.unwrap_or(0.0),
+			max: self.max.unwrap_or(0.0),
+		}
+	}
+}
+
+/// GPS history of a single device
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceGpsHistory {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id
+	pub device_id: String,
+	/// GPS history
+	pub history: GpsHistory,
+}
+
+/// State of the mining schedule
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduleStatus {
+	/// Whether a mining window is open
+	pub active: bool,
+	/// The open window
+	pub window: Option<String>,
+	/// When the schedule next pauses or resumes mining (seconds since
+	/// epoch), if it ever does
+	pub next_change: Option<i64>,
+}
+
+/// State of desktop mode
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DesktopStatus {
+	/// Why mining is paused or throttled for the user, none while idle
+	pub reason: Option<String>,
+	/// Whether the user being active throttles mining rather than pausing
+	/// it
+	pub throttled: bool,
+	/// Seconds since the last input, if it can be told
+	pub idle_secs: Option<u64>,
+	/// When mining resumes (seconds since epoch), if the user stays idle
+	pub resumes_at: Option<i64>,
+}
+
+/// Progress of the startup ramp-up
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RampUpStats {
+	/// When mining starts (seconds since epoch), while waiting for the
+	/// startup delay
+	pub starts_at: Option<i64>,
+	/// Devices started, of those staggered
+	pub started_devices: usize,
+	pub total_devices: usize,
+	/// When the next device starts (seconds since epoch), if any is left
+	pub next_device_at: Option<i64>,
+}
+
+/// Thermal protection state of a single device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThermalStatus {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id, as reported by the plugin
+	pub device_id: String,
+	/// Percentage of the time the device mines, 100 unless throttled
+	pub duty_cycle: u32,
+	/// Whether the device is paused for being above its hard limit
+	pub overheated: bool,
+	/// Times the device went above its soft or hard limit
+	pub events: u64,
+}
+
+/// The buffer of solutions found waiting for the stratum client
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SolutionBufferStats {
+	/// Solutions in the buffer
+	pub queued: u64,
+	/// Solutions it holds
+	pub size: u64,
+	/// Solutions dropped as it was full
+	pub overflowed: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MiningStats {
 	/// combined graphs per second
 	pub combined_gps: f64,
+	/// Combined graphs per second of each algorithm mined, e.g.
+	/// cuckaroo29
+	pub algorithm_gps: Vec<(String, f64)>,
 	/// what block height we're mining at
 	pub block_height: u64,
 	/// current target for share difficulty we're working on
 	pub target_difficulty: u64,
 	/// Individual device status from Cuckoo-Miner
 	pub device_stats: Option<Vec<Vec<util::cuckoo_miner::CuckooMinerDeviceStats>>>,
+	/// Hardware readings (temperature, fan, power) per device
+	pub hardware_stats: Vec<DeviceHardwareStats>,
+	/// Whether mining is paused
+	pub paused: bool,
+	/// Devices paused individually, as (plugin index, device id)
+	pub paused_devices: Vec<(usize, u32)>,
+	/// Devices in standby until activated, as (plugin index, device id)
+	#[serde(default)]
+	pub standby_devices: Vec<(usize, u32)>,
+	/// Stable identity of each device
+	#[serde(default)]
+	pub device_identities: Vec<DeviceIdentity>,
+	/// Combined GPS history
+	pub gps_history: GpsHistory,
+	/// Per-device GPS history
+	pub device_gps_history: Vec<DeviceGpsHistory>,
+	/// Electricity price per kWh, for cost estimates
+	pub electricity_price: Option<f64>,
+	/// Device profile being mined with, if any
+	pub profile: Option<String>,
+	/// Mining schedule state, if scheduling
+	pub schedule: Option<ScheduleStatus>,
+	/// Desktop mode state, if enabled
+	pub desktop: Option<DesktopStatus>,
+	/// Startup ramp-up progress, until every device has started
+	#[serde(default)]
+	pub ramp_up: Option<RampUpStats>,
+	/// Thermal protection state of the devices it has readings for
+	pub thermal: Vec<ThermalStatus>,
+	/// Thermal events (going above a soft or hard limit) of all devices
+	pub thermal_events: u64,
+	/// Times errored devices were recovered, re-creating the solvers
+	pub recovery_attempts: u64,
+	/// Devices given up on after failing recovery, as (plugin index,
+	/// device id)
+	pub failed_devices: Vec<(usize, u32)>,
+	/// Devices quarantined until their next recovery attempt
+	#[serde(default)]
+	pub quarantined: Vec<DeviceQuarantine>,
+	/// Network difficulty of the current job, if the pool sends it
+	#[serde(default)]
+	pub network_difficulty: Option<u64>,
+	/// Jobs received for a proof size some devices don't solve, which
+	/// those devices skipped
+	#[serde(default)]
+	pub incompatible_jobs: u64,
+	/// Jobs received for a proof size none of the devices solve, not
+	/// mined at all
+	#[serde(default)]
+	pub unsupported_jobs: u64,
+	/// Solutions waiting for the stratum client
+	#[serde(default)]
+	pub solution_buffer: SolutionBufferStats,
+}
+
+impl MiningStats {
+	/// Add the current combined and per-device GPS to their histories
+	pub fn add_gps_samples(&mut self, now: i64) {
+		self.gps_history.add(now, self.combined_gps);
+		let rig = self.rig_gps(None, now);
+		// devices that haven't completed a graph yet have no sample
+		for d in rig.devices.into_iter().filter(|d| d.gps > 0.0) {
+			let pos = match self
+				.device_gps_history
+				.iter()
+				.position(|h| h.plugin == d.plugin && h.device_id == d.device_id)
+			{
+				Some(p) => p,
+				None => {
+					self.device_gps_history.push(DeviceGpsHistory {
+						plugin: d.plugin,
+						device_id: d.device_id.clone(),
+						history: GpsHistory::default(),
+					});
+					self.device_gps_history.len() - 1
+				}
+			};
+			self.device_gps_history[pos].history.add(now, d.gps);
+		}
+	}
+
+	/// Combined and per-device GPS: current with no window, otherwise
+	/// averaged over the last window seconds of samples (up to 15
+	/// minutes)
+	pub fn rig_gps(&self, window: Option<i64>, now: i64) -> RigGps {
+		let current = match self.device_stats {
+			Some(ref d) => RigGps::current(d),
+			None => RigGps::default(),
+		};
+		let secs = match window {
+			Some(s) => s,
+			None => return current,
+		};
+		let devices: Vec<DeviceGps> = current
+			.devices
+			.into_iter()
+			.map(|d| DeviceGps {
+				gps: self
+					.device_gps_history
+					.iter()
+					.find(|h| h.plugin == d.plugin && h.device_id == d.device_id)
+					.map_or(0.0, |h| h.history.window_average(now, secs)),
+				..d
+			})
+			.collect();
+		RigGps {
+			combined: self.gps_history.window_average(now, secs),
+			devices: devices,
+		}
+	}
+
+	/// GPS averages of the given device, if it has any history
+	pub fn device_gps_averages(&self, plugin: usize, device_id: &str, now: i64) -> Option<GpsAverages> {
+		self.device_gps_history
+			.iter()
+			.find(|h| h.plugin == plugin && h.device_id == device_id)
+			.map(|h| h.history.averages(now))
+	}
+
+	/// Power efficiency of the devices reporting their power draw
+	pub fn efficiency(&self) -> Efficiency {
+		let mut efficiency = Efficiency::default();
+		let device_stats = match self.device_stats {
+			Some(ref d) => d,
+			None => return efficiency,
+		};
+		let mut gps_total = 0.0;
+		for (i, plugin) in device_stats.iter().enumerate() {
+			for d in plugin.iter().filter(|d| d.in_use == 1) {
+				let power_usage = match self.hardware_for(i, &d.device_id).and_then(|h| h.power_usage) {
+					Some(p) if p > 0.0 => p,
+					_ => continue,
+				};
+				let gps = device_gps(d);
+				gps_total += gps;
+				efficiency.power_usage += power_usage;
+				efficiency.devices.push(DeviceEfficiency {
+					plugin: i,
+					device_id: d.device_id.clone(),
+					gps: gps,
+					power_usage: power_usage,
+					graphs_per_joule: gps / power_usage,
+				});
+			}
+		}
+		if efficiency.power_usage > 0.0 {
+			efficiency.graphs_per_joule = gps_total / efficiency.power_usage;
+			efficiency.daily_cost = self
+				.electricity_price
+				.map(|price| efficiency.power_usage * 24.0 / 1000.0 * price);
+		}
+		efficiency
+	}
+
+	/// Hardware readings for the given device, if any
+	pub fn hardware_for(&self, plugin: usize, device_id: &str) -> Option<&DeviceHardwareStats> {
+		self.hardware_stats
+			.iter()
+			.find(|h| h.plugin == plugin && h.device_id == device_id)
+	}
+
+	/// Quarantine of a device, if it's quarantined
+	pub fn quarantine_for(&self, plugin: usize, device_id: &str) -> Option<&DeviceQuarantine> {
+		self.quarantined
+			.iter()
+			.find(|q| q.plugin == plugin && q.device_id == device_id)
+	}
+
+	/// Stable identity of a device, once worked out
+	pub fn device_uid(&self, plugin: usize, device_id: &str) -> Option<&str> {
+		self.device_identities
+			.iter()
+			.find(|d| d.plugin == plugin && d.device_id == device_id)
+			.map(|d| d.uid.as_str())
+	}
 }
 
 impl Default for MiningStats {
 	fn default() -> MiningStats{
 		MiningStats{
 			combined_gps: 0.0,
+			algorithm_gps: vec![],
 			block_height: 0,
 			target_difficulty: 0,
 			device_stats: None,
+			hardware_stats: vec![],
+			paused: false,
+			paused_devices: vec![],
+			standby_devices: vec![],
+			device_identities: vec![],
+			gps_history: GpsHistory::default(),
+			device_gps_history: vec![],
+			electricity_price: None,
+			profile: None,
+			schedule: None,
+			desktop: None,
+			ramp_up: None,
+			thermal: vec![],
+			thermal_events: 0,
+			recovery_attempts: 0,
+			failed_devices: vec![],
+			quarantined: vec![],
+			network_difficulty: None,
+			incompatible_jobs: 0,
+			unsupported_jobs: 0,
+			solution_buffer: SolutionBufferStats::default(),
+		}
+	}
+}
+
+/// Number of raw stratum messages kept in the client message trace
+pub const MESSAGE_TRACE_LEN: usize = 100;
+
+/// A raw message exchanged with the stratum server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StratumMessage {
+	/// Whether the message was sent (true) or received (false)
+	pub sent: bool,
+	/// When the message was sent or received (seconds since epoch)
+	pub time: i64,
+	/// The message, as sent over the wire
+	pub message: String,
+}
+
+/// Number of pool notices kept
+pub const NOTICES_LEN: usize = 20;
+
+/// An informational message from the pool, e.g. a maintenance notice
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolNotice {
+	/// When the notice was received (seconds since epoch)
+	pub time: i64,
+	pub message: String,
+}
+
+/// Seconds over which traffic rates are averaged
+pub const TRAFFIC_RATE_SECS: i64 = 60;
+
+/// Bytes and messages exchanged with the stratum server, in total and
+/// over the current connection
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TrafficStats {
+	/// Bytes sent this session, line endings included
+	pub bytes_sent: u64,
+	/// Bytes received this session
+	pub bytes_received: u64,
+	/// Messages sent this session
+	pub messages_sent: u64,
+	/// Messages received this session
+	pub messages_received: u64,
+	/// Bytes sent over the current connection
+	pub connection_bytes_sent: u64,
+	/// Bytes received over the current connection
+	pub connection_bytes_received: u64,
+	/// Messages sent over the current connection
+	pub connection_messages_sent: u64,
+	/// Messages received over the current connection
+	pub connection_messages_received: u64,
+	/// Session totals of bytes sent and received before the first message
+	/// of each second, over the last TRAFFIC_RATE_SECS, for the rates
+	samples: VecDeque<(i64, u64, u64)>,
+}
+
+impl TrafficStats {
+	/// Start counting a new connection
+	pub fn new_connection(&mut self) {
+		self.connection_bytes_sent = 0;
+		self.connection_bytes_received = 0;
+		self.connection_messages_sent = 0;
+		self.connection_messages_received = 0;
+	}
+
+	/// Count a message sent
+	pub fn record_sent(&mut self, bytes: usize) {
+		self.sample();
+		self.bytes_sent += bytes as u64;
+		self.connection_bytes_sent += bytes as u64;
+		self.messages_sent += 1;
+		self.connection_messages_sent += 1;
+	}
+
+	/// Count a message received
+	pub fn record_received(&mut self, bytes: usize) {
+		self.sample();
+		self.bytes_received += bytes as u64;
+		self.connection_bytes_received += bytes as u64;
+		self.messages_received += 1;
+		self.connection_messages_received += 1;
+	}
+
+	fn sample(&mut self) {
+		let now = clock::mono_secs();
+		if self.samples.back().map_or(true, |s| s.0 < now) {
+			self.samples
+				.push_back((now, self.bytes_sent, self.bytes_received));
+		}
+		while self.samples.front().map_or(false, |s| s.0 < now - TRAFFIC_RATE_SECS) {
+			self.samples.pop_front();
 		}
 	}
+
+	/// Bytes sent and received per second over the last TRAFFIC_RATE_SECS,
+	/// as of the given monotonic clock seconds
+	pub fn rates(&self, now: i64) -> (f64, f64) {
+		match self.samples.iter().find(|s| s.0 >= now - TRAFFIC_RATE_SECS) {
+			Some(&(time, sent, received)) => {
+				let secs = (now - time).max(1) as f64;
+				(
+					(self.bytes_sent - sent) as f64 / secs,
+					(self.bytes_received - received) as f64 / secs,
+				)
+			}
+			None => (0.0, 0.0),
+		}
+	}
+}
+
+/// State of the submit rate limiter
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ThrottleStats {
+	/// Whether submits are being held back
+	pub throttled: bool,
+	/// End of the cool-down after a ban error (seconds since epoch), if
+	/// in one
+	pub cooldown_until: Option<i64>,
+	/// Submits held back now
+	pub queued: u64,
+	/// Submits that had to wait
+	pub delayed: u64,
+	/// Submits dropped, stale or over the queue limit
+	pub dropped: u64,
+	/// Cool-downs after ban errors
+	pub cooldowns: u64,
 }
 
-#[derive(Clone)]
+/// State of the reject rate circuit breaker
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BreakerStats {
+	/// Percentage of the latest shares answered by the pool rejected
+	pub reject_percent: f64,
+	/// Whether the breaker has tripped and not yet gone back to the first
+	/// pool
+	pub open: bool,
+	/// Whether mining is paused by the breaker
+	pub paused: bool,
+	/// Why the breaker last tripped
+	pub reason: Option<String>,
+	/// When it tripped (seconds since epoch), if open
+	pub since: Option<i64>,
+	/// When it goes back to the first pool (seconds since epoch), if open
+	pub retry_at: Option<i64>,
+	/// Times the breaker tripped
+	pub trips: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ClientStats {
 	/// Server we're connected to
 	pub server_url: String,
@@ -55,6 +735,88 @@ pub struct ClientStats {
 	pub last_message_sent: String,
 	/// Last response/command received from server
 	pub last_message_received: String,
+	/// When the connection to the server was lost, if not connected
+	pub disconnected_since: Option<i64>,
+	/// The same on the monotonic clock, to time the disconnection with
+	#[serde(default)]
+	pub disconnected_mono: Option<i64>,
+	/// Shares accepted by the server, as last reported by it
+	pub shares_accepted: u64,
+	/// Shares rejected by the server, as last reported by it
+	pub shares_rejected: u64,
+	/// Stale shares, as last reported by the server
+	pub shares_stale: u64,
+	/// Blocks found (solo mining), as reported in submit responses
+	pub blocks_found: u64,
+	/// Whether the server accepted our login
+	pub logged_in: bool,
+	/// Jobs received from the server
+	pub jobs_received: u64,
+	/// Shares found but not submitted, in a dry run
+	pub withheld_shares: u64,
+	/// Shares submitted the server hasn't answered yet
+	pub submits_pending: u64,
+	/// When the server last accepted a share (seconds since epoch)
+	pub last_share_accepted: Option<i64>,
+	/// Ring buffer of the last raw messages exchanged with the server,
+	/// oldest first
+	pub message_trace: VecDeque<StratumMessage>,
+	/// Bytes and messages exchanged with the server
+	pub traffic: TrafficStats,
+	/// Submits held back by the rate limiter
+	#[serde(default)]
+	pub throttle: ThrottleStats,
+	/// Reject rate circuit breaker
+	#[serde(default)]
+	pub breaker: BreakerStats,
+	/// Latest notices from the pool, oldest first
+	#[serde(default)]
+	pub notices: VecDeque<PoolNotice>,
+	/// Round trip time to the pool in ms, smoothed over the requests
+	/// answered
+	#[serde(default)]
+	pub pool_rtt_ms: Option<f64>,
+	/// Share difficulty suggested to the pool at login
+	#[serde(default)]
+	pub suggested_difficulty: Option<u64>,
+	/// Share difficulty the pool answered the suggestion with
+	#[serde(default)]
+	pub negotiated_difficulty: Option<u64>,
+}
+
+impl ClientStats {
+	/// Add a raw message to the message trace, dropping the oldest one if
+	/// the trace is full
+	pub fn trace_message(&mut self, sent: bool, message: &str) {
+		if self.message_trace.len() >= MESSAGE_TRACE_LEN {
+			self.message_trace.pop_front();
+		}
+		self.message_trace.push_back(StratumMessage {
+			sent: sent,
+			time: time::get_time().sec,
+			message: message.trim().to_string(),
+		});
+	}
+
+	/// Add a notice from the pool, dropping the oldest one if there are
+	/// too many
+	pub fn add_notice(&mut self, message: &str) {
+		if self.notices.len() >= NOTICES_LEN {
+			self.notices.pop_front();
+		}
+		self.notices.push_back(PoolNotice {
+			time: time::get_time().sec,
+			message: message.to_string(),
+		});
+	}
+
+	/// Add a round trip time measured to the pool, in ms
+	pub fn add_rtt(&mut self, ms: f64) {
+		self.pool_rtt_ms = Some(match self.pool_rtt_ms {
+			Some(rtt) => rtt * 0.8 + ms * 0.2,
+			None => ms,
+		});
+	}
 }
 
 impl Default for ClientStats {
@@ -65,16 +827,1003 @@ impl Default for ClientStats {
 			connection_status: "Connection Status: Starting".to_string(),
 			last_message_sent: "Last Message Sent: None".to_string(),
 			last_message_received: "Last Message Received: None".to_string(),
+			disconnected_since: None,
+			disconnected_mono: None,
+			shares_accepted: 0,
+			shares_rejected: 0,
+			shares_stale: 0,
+			blocks_found: 0,
+			logged_in: false,
+			jobs_received: 0,
+			withheld_shares: 0,
+			submits_pending: 0,
+			last_share_accepted: None,
+			message_trace: VecDeque::with_capacity(MESSAGE_TRACE_LEN),
+			traffic: TrafficStats::default(),
+			throttle: ThrottleStats::default(),
+			breaker: BreakerStats::default(),
+			notices: VecDeque::new(),
+			pool_rtt_ms: None,
+			suggested_difficulty: None,
+			negotiated_difficulty: None,
+		}
+	}
+}
+
+/// Per-device totals accumulated over the whole session
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceSessionStats {
+	/// Index of the plugin running the device
+	pub plugin: usize,
+	/// Device id
+	pub device_id: String,
+	/// Device name
+	pub device_name: String,
+	/// Sum of all GPS samples taken
+	pub gps_sum: f64,
+	/// Number of GPS samples taken
+	pub gps_samples: u64,
+	/// Total graphs searched
+	pub graphs: u64,
+	/// Number of times the device went into an errored state
+	pub errors: u64,
+	/// Solutions found by the device, when the solver tells them apart
+	#[serde(default)]
+	pub solutions: u64,
+	/// Shares of the device the pool accepted and rejected
+	#[serde(default)]
+	pub shares_accepted: u64,
+	#[serde(default)]
+	pub shares_rejected: u64,
+	#[serde(skip)]
+	last_iterations: u64,
+	#[serde(skip)]
+	errored: bool,
+}
+
+impl DeviceSessionStats {
+	/// Average GPS over the session
+	pub fn average_gps(&self) -> f64 {
+		if self.gps_samples == 0 {
+			return 0.0;
 		}
+		self.gps_sum / self.gps_samples as f64
 	}
 }
 
-#[derive(Clone)]
+/// Accepted shares of the session before devices are judged on theirs
+const FAIRNESS_MIN_SHARES: u64 = 20;
+
+/// A device's part of the accepted shares against its part of the GPS.
+/// A healthy device gets about as many shares as its GPS is worth; one
+/// searching graphs but getting far fewer shares is likely producing
+/// corrupt solutions.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceFairness {
+	pub plugin: usize,
+	pub device_id: String,
+	pub device_name: String,
+	/// Percentage of the combined session GPS
+	pub gps_percent: f64,
+	/// Percentage of the session's attributed accepted shares, none while
+	/// no shares could be attributed to devices
+	pub share_percent: Option<f64>,
+	pub solutions: u64,
+	pub shares_accepted: u64,
+	pub shares_rejected: u64,
+	/// Whether the device gets less than half the shares its GPS is
+	/// worth, or has more shares rejected than accepted
+	pub suspect: bool,
+}
+
+/// Number of most recent shares kept in the session stats
+pub const RECENT_SHARES_LEN: usize = 100;
+
+/// A solution found, with its difficulty
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRecord {
+	/// When the solution was found (seconds since epoch)
+	pub time: i64,
+	/// Graph size
+	pub edge_bits: u32,
+	/// Difficulty of the solution
+	pub difficulty: u64,
+	/// Share difficulty targeted when the solution was found
+	pub target_difficulty: u64,
+}
+
+/// A block found solo mining, as the node answered its submit
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FoundBlock {
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block, if the node gave it
+	pub hash: Option<String>,
+	/// When the node accepted it (seconds since epoch)
+	pub time: i64,
+	/// Graph size of the solution
+	pub edge_bits: u32,
+	pub nonce: u64,
+	/// Plugin index and id of the device that found it, when known
+	pub device: Option<(usize, String)>,
+	/// Stable identity of that device, see device_uid
+	pub uid: Option<String>,
+}
+
+/// Histogram of share difficulties, in power of 2 buckets: bucket `i`
+/// counts the shares with a difficulty in `[2^i, 2^(i+1))`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DifficultyHistogram {
+	/// Share count per bucket
+	pub buckets: Vec<u64>,
+}
+
+impl DifficultyHistogram {
+	/// Count a share of the given difficulty
+	pub fn add(&mut self, difficulty: u64) {
+		let bucket = 63 - difficulty.max(1).leading_zeros() as usize;
+		if self.buckets.len() <= bucket {
+			self.buckets.resize(bucket + 1, 0);
+		}
+		self.buckets[bucket] += 1;
+	}
+
+	/// Printable one line summary of the non empty buckets
+	pub fn summary(&self) -> String {
+		let buckets: Vec<String> = self.buckets
+			.iter()
+			.enumerate()
+			.filter(|b| *b.1 > 0)
+			.map(|(i, n)| format!("{}+: {}", 1u64 << i, n))
+			.collect();
+		if buckets.is_empty() {
+			"none".to_string()
+		} else {
+			buckets.join(", ")
+		}
+	}
+}
+
+/// Totals accumulated over the whole mining session
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionStats {
+	/// When the session started (seconds since epoch)
+	pub start_time: i64,
+	/// When the session started (seconds on the monotonic clock)
+	#[serde(default)]
+	pub start_mono: i64,
+	/// Per-device totals
+	pub devices: Vec<DeviceSessionStats>,
+	/// Number of solutions found
+	pub solutions_found: u64,
+	/// Number of solutions found, per graph size (edge bits)
+	pub solutions_by_size: HashMap<u32, u64>,
+	/// Highest difficulty of any solution found
+	pub best_share_difficulty: u64,
+	/// Solutions found below the target difficulty
+	pub shares_below_target: u64,
+	/// Distribution of the difficulties of all solutions found
+	pub difficulty_histogram: DifficultyHistogram,
+	/// Most recent solutions found, oldest first
+	pub recent_shares: VecDeque<ShareRecord>,
+}
+
+impl Default for SessionStats {
+	fn default() -> SessionStats {
+		SessionStats {
+			start_time: time::get_time().sec,
+			start_mono: clock::mono_secs(),
+			devices: vec![],
+			solutions_found: 0,
+			solutions_by_size: HashMap::new(),
+			best_share_difficulty: 0,
+			shares_below_target: 0,
+			difficulty_histogram: DifficultyHistogram::default(),
+			recent_shares: VecDeque::with_capacity(RECENT_SHARES_LEN),
+		}
+	}
+}
+
+impl SessionStats {
+	/// Accumulate the latest device stats into the session totals,
+	/// returning the devices (plugin index and device id) that went into
+	/// an errored state since the last update
+	pub fn update_devices(
+		&mut self,
+		device_stats: &Vec<Vec<CuckooMinerDeviceStats>>,
+	) -> Vec<(usize, String)> {
+		let mut errored_devices = vec![];
+		for (i, plugin) in device_stats.iter().enumerate() {
+			for d in plugin {
+				if d.in_use == 0 {
+					continue;
+				}
+				let pos = match self
+					.devices
+					.iter()
+					.position(|s| s.plugin == i && s.device_id == d.device_id)
+				{
+					Some(p) => p,
+					None => {
+						self.devices.push(DeviceSessionStats {
+							plugin: i,
+							device_id: d.device_id.clone(),
+							device_name: d.device_name.clone(),
+							gps_sum: 0.0,
+							gps_samples: 0,
+							graphs: 0,
+							errors: 0,
+							solutions: 0,
+							shares_accepted: 0,
+							shares_rejected: 0,
+							last_iterations: 0,
+							errored: false,
+						});
+						self.devices.len() - 1
+					}
+				};
+				let s = &mut self.devices[pos];
+				// device iterations start again from 0 on every new job
+				let iterations = d.iterations_completed as u64;
+				if iterations >= s.last_iterations {
+					s.graphs += iterations - s.last_iterations;
+				} else {
+					s.graphs += iterations;
+				}
+				s.last_iterations = iterations;
+				let gps = device_gps(d);
+				if gps > 0.0 {
+					s.gps_sum += gps;
+					s.gps_samples += 1;
+				}
+				let errored = d.has_errored != 0;
+				if errored && !s.errored {
+					s.errors += 1;
+					errored_devices.push((i, d.device_id.clone()));
+				}
+				s.errored = errored;
+			}
+		}
+		errored_devices
+	}
+
+	/// Record a solution found, along with its graph size, difficulty and
+	/// the share difficulty targeted
+	pub fn add_solution(&mut self, edge_bits: u32, difficulty: u64, target_difficulty: u64) {
+		self.solutions_found += 1;
+		*self.solutions_by_size.entry(edge_bits).or_insert(0) += 1;
+		if difficulty > self.best_share_difficulty {
+			self.best_share_difficulty = difficulty;
+		}
+		if difficulty < target_difficulty {
+			self.shares_below_target += 1;
+		}
+		self.difficulty_histogram.add(difficulty);
+		if self.recent_shares.len() >= RECENT_SHARES_LEN {
+			self.recent_shares.pop_front();
+		}
+		self.recent_shares.push_back(ShareRecord {
+			time: time::get_time().sec,
+			edge_bits: edge_bits,
+			difficulty: difficulty,
+			target_difficulty: target_difficulty,
+		});
+	}
+
+	/// Record a solution found by a device
+	pub fn add_device_solution(&mut self, device: &(usize, String)) {
+		if let Some(d) = self
+			.devices
+			.iter_mut()
+			.find(|d| d.plugin == device.0 && d.device_id == device.1)
+		{
+			d.solutions += 1;
+		}
+	}
+
+	/// Record the pool's answer to a share found by a device
+	pub fn add_device_share(&mut self, device: &(usize, String), accepted: bool) {
+		if let Some(d) = self
+			.devices
+			.iter_mut()
+			.find(|d| d.plugin == device.0 && d.device_id == device.1)
+		{
+			if accepted {
+				d.shares_accepted += 1;
+			} else {
+				d.shares_rejected += 1;
+			}
+		}
+	}
+
+	/// Share contribution of each device against its GPS
+	pub fn fairness(&self) -> Vec<DeviceFairness> {
+		let total_gps: f64 = self.devices.iter().map(|d| d.average_gps()).sum();
+		let total_shares: u64 = self.devices.iter().map(|d| d.shares_accepted).sum();
+		let percent = |part: f64, total: f64| if total > 0.0 { part * 100.0 / total } else { 0.0 };
+		self.devices
+			.iter()
+			.map(|d| {
+				let gps_percent = percent(d.average_gps(), total_gps);
+				let share_percent = if total_shares > 0 {
+					Some(percent(d.shares_accepted as f64, total_shares as f64))
+				} else {
+					None
+				};
+				let answered = d.shares_accepted + d.shares_rejected;
+				DeviceFairness {
+					plugin: d.plugin,
+					device_id: d.device_id.clone(),
+					device_name: d.device_name.clone(),
+					gps_percent: gps_percent,
+					share_percent: share_percent,
+					solutions: d.solutions,
+					shares_accepted: d.shares_accepted,
+					shares_rejected: d.shares_rejected,
+					suspect: (total_shares >= FAIRNESS_MIN_SHARES
+						&& share_percent.map_or(false, |s| s < gps_percent / 2.0))
+						|| (answered >= 5 && d.shares_rejected > d.shares_accepted),
+				}
+			})
+			.collect()
+	}
+
+	/// Fairness report lines, one per device
+	pub fn fairness_report(&self) -> Vec<String> {
+		self.fairness()
+			.iter()
+			.map(|f| {
+				format!(
+					"Plugin {} - Device {} ({}): {}% of shares for {}% of GPS, {} solutions, {} accepted, {} rejected{}",
+					f.plugin,
+					f.device_id,
+					f.device_name,
+					f.share_percent.map_or(String::from("-"), |s| units::decimal(s, 1)),
+					units::decimal(f.gps_percent, 1),
+					f.solutions,
+					f.shares_accepted,
+					f.shares_rejected,
+					if f.suspect {
+						" - SUSPECT, may be producing corrupt solutions"
+					} else {
+						""
+					}
+				)
+			})
+			.collect()
+	}
+
+	/// Seconds since the session started, as of the given monotonic clock
+	/// seconds
+	pub fn uptime(&self, now: i64) -> i64 {
+		now - self.start_mono
+	}
+}
+
+/// Number of entries kept in each of the history lists
+pub const HISTORY_LEN: usize = 50;
+
+/// A solver restart
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestartRecord {
+	/// When the restart happened (seconds since epoch)
+	pub time: i64,
+	/// Plugin index and device id of the device restarted, all devices
+	/// if not set
+	pub device: Option<(usize, String)>,
+	/// Why the solver was restarted
+	pub reason: String,
+}
+
+/// A lost connection to the stratum server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionDrop {
+	/// When the connection was lost (seconds since epoch)
+	pub time: i64,
+	/// How long it took to reconnect, if reconnected
+	pub duration: Option<i64>,
+	/// Why the connection was lost
+	pub reason: String,
+	/// When the connection was lost (seconds on the monotonic clock)
+	#[serde(skip)]
+	lost: i64,
+}
+
+/// Number of network events kept
+pub const NETWORK_EVENTS_LEN: usize = 200;
+
+/// What changed in the jobs the pool sends
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkEventKind {
+	/// Block height
+	Height,
+	/// Target share difficulty
+	Difficulty,
+}
+
+/// A change of block height or difficulty in the jobs from the pool
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetworkEvent {
+	/// When the job was received (seconds since epoch)
+	pub time: i64,
+	pub kind: NetworkEventKind,
+	/// Graph size of the job, for pools sending jobs per algorithm
+	pub edge_bits: Option<u32>,
+	/// Value before, none for the first job
+	pub from: Option<u64>,
+	pub to: u64,
+}
+
+/// A system suspend, detected once resumed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SuspendRecord {
+	/// When the system was suspended (seconds since epoch)
+	pub time: i64,
+	/// How long it was suspended, in seconds
+	pub duration: i64,
+}
+
+/// An error message that was logged
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorRecord {
+	/// When the error was logged (seconds since epoch)
+	pub time: i64,
+	/// The error message
+	pub message: String,
+}
+
+/// History of notable events, most recent last
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct HistoryStats {
+	/// Solver restarts
+	pub restarts: VecDeque<RestartRecord>,
+	/// Lost connections to the stratum server
+	pub connection_drops: VecDeque<ConnectionDrop>,
+	/// Most recent errors logged
+	pub errors: Vec<ErrorRecord>,
+	/// Full restarts done as scheduled by restart_after_hours
+	pub scheduled_restarts: u64,
+	/// Times the share watchdog triggered
+	pub watchdog_triggers: u64,
+	/// System suspends
+	pub suspends: VecDeque<SuspendRecord>,
+	/// Most recent height and difficulty changes
+	pub network_events: VecDeque<NetworkEvent>,
+	/// Network events recorded this session, including those dropped
+	pub network_events_total: u64,
+	/// Height of the last job, and difficulty of the last job of each
+	/// graph size
+	#[serde(skip)]
+	last_height: Option<u64>,
+	#[serde(skip)]
+	last_difficulties: Vec<(Option<u32>, u64)>,
+}
+
+fn push_capped<T>(list: &mut VecDeque<T>, item: T) {
+	if list.len() >= HISTORY_LEN {
+		list.pop_front();
+	}
+	list.push_back(item);
+}
+
+impl HistoryStats {
+	/// Record a solver restart, of a single device or all of them
+	pub fn record_restart(&mut self, device: Option<(usize, String)>, reason: &str) {
+		push_capped(
+			&mut self.restarts,
+			RestartRecord {
+				time: time::get_time().sec,
+				device: device,
+				reason: reason.to_string(),
+			},
+		);
+	}
+
+	/// Record a lost connection to the stratum server
+	pub fn record_connection_drop(&mut self, reason: &str) {
+		push_capped(
+			&mut self.connection_drops,
+			ConnectionDrop {
+				time: time::get_time().sec,
+				duration: None,
+				reason: reason.to_string(),
+				lost: clock::mono_secs(),
+			},
+		);
+	}
+
+	/// Record the height and difficulty changes a new job brings
+	pub fn record_job(&mut self, height: u64, difficulty: u64, edge_bits: Option<u32>) {
+		let now = time::get_time().sec;
+		let mut events = vec![];
+		if self.last_height != Some(height) {
+			events.push(NetworkEvent {
+				time: now,
+				kind: NetworkEventKind::Height,
+				edge_bits: None,
+				from: self.last_height,
+				to: height,
+			});
+			self.last_height = Some(height);
+		}
+		let last_difficulty = self
+			.last_difficulties
+			.iter()
+			.position(|d| d.0 == edge_bits);
+		let from = last_difficulty.map(|i| self.last_difficulties[i].1);
+		if from != Some(difficulty) {
+			events.push(NetworkEvent {
+				time: now,
+				kind: NetworkEventKind::Difficulty,
+				edge_bits: edge_bits,
+				from: from,
+				to: difficulty,
+			});
+			match last_difficulty {
+				Some(i) => self.last_difficulties[i].1 = difficulty,
+				None => self.last_difficulties.push((edge_bits, difficulty)),
+			}
+		}
+		for e in events {
+			if self.network_events.len() >= NETWORK_EVENTS_LEN {
+				self.network_events.pop_front();
+			}
+			self.network_events.push_back(e);
+			self.network_events_total += 1;
+		}
+	}
+
+	/// Record a system suspend, detected on resume
+	pub fn record_suspend(&mut self, time: i64, duration: i64) {
+		push_capped(
+			&mut self.suspends,
+			SuspendRecord {
+				time: time,
+				duration: duration,
+			},
+		);
+	}
+
+	/// Record the connection to the stratum server is back
+	pub fn record_reconnect(&mut self) {
+		if let Some(d) = self.connection_drops.back_mut() {
+			if d.duration.is_none() {
+				d.duration = Some(clock::mono_secs() - d.lost);
+			}
+		}
+	}
+
+	/// Refresh the most recent errors from the logger
+	pub fn update_errors(&mut self) {
+		self.errors = util::recent_errors()
+			.into_iter()
+			.map(|(time, message)| ErrorRecord {
+				time: time,
+				message: message,
+			})
+			.collect();
+	}
+}
+
+/// Number of jobs and shares whose timings are kept
+pub const TIMING_LEN: usize = 50;
+
+/// When a job got through the miner
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct JobTiming {
+	job_id: u64,
+	received: i64,
+	started: Option<i64>,
+}
+
+/// When a share got through each stage, from its job being received
+/// from the pool to the pool answering its submit (milliseconds on the
+/// monotonic clock, missing if unknown or not there yet)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareTiming {
+	pub job_id: u64,
+	pub nonce: u64,
+	/// Height of the job
+	#[serde(default)]
+	pub height: u64,
+	/// Graph size of the solution
+	#[serde(default)]
+	pub edge_bits: u32,
+	/// Share difficulty of the job
+	#[serde(default)]
+	pub target_difficulty: u64,
+	/// Job received from the pool
+	pub job_received: Option<i64>,
+	/// Job started on the solvers
+	pub job_started: Option<i64>,
+	/// Solution picked up from the solvers
+	pub found: i64,
+	/// Submit sent to the pool
+	pub submitted: Option<i64>,
+	/// Answer received from the pool
+	pub answered: Option<i64>,
+	/// Whether the pool accepted the share
+	pub accepted: bool,
+	/// Plugin index and id of the device that found it, when known
+	#[serde(default)]
+	pub device: Option<(usize, String)>,
+	/// Correlation id of the solution
+	#[serde(default)]
+	pub cid: String,
+}
+
+/// Latencies of each stage of a share, in milliseconds: queue (job
+/// received to started on the solvers), solve (started to solution
+/// found), submit (found to sent) and ack (sent to answered)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StageLatencies {
+	pub queue: Option<f64>,
+	pub solve: Option<f64>,
+	pub submit: Option<f64>,
+	pub ack: Option<f64>,
+}
+
+impl ShareTiming {
+	/// Latencies of the stages this share went through
+	pub fn stages(&self) -> StageLatencies {
+		let between = |from: Option<i64>, to: Option<i64>| match (from, to) {
+			(Some(f), Some(t)) => Some((t - f) as f64),
+			_ => None,
+		};
+		StageLatencies {
+			queue: between(self.job_received, self.job_started),
+			solve: between(self.job_started, Some(self.found)),
+			submit: between(Some(self.found), self.submitted),
+			ack: between(self.submitted, self.answered),
+		}
+	}
+}
+
+/// Timings of the most recent shares
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TimingStats {
+	#[serde(skip)]
+	jobs: VecDeque<JobTiming>,
+	/// Shares found and not answered yet, oldest first
+	pub in_flight: VecDeque<ShareTiming>,
+	/// Shares answered, oldest first
+	pub recent: VecDeque<ShareTiming>,
+	/// Jobs started in place of a job being mined
+	#[serde(default)]
+	pub job_switches: u64,
+	/// Estimated solver time spent on jobs the pool had already replaced,
+	/// in ms
+	#[serde(default)]
+	pub switch_waste_ms: u64,
+	/// How long each device's solvers took to drop the old job and start
+	/// the new one, per device
+	#[serde(default)]
+	pub device_switches: Vec<DeviceSwitchLatency>,
+}
+
+/// Job switch latency of a device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceSwitchLatency {
+	pub plugin: usize,
+	pub device_id: String,
+	pub switches: u64,
+	pub total_ms: u64,
+	pub last_ms: u64,
+}
+
+impl DeviceSwitchLatency {
+	pub fn average_ms(&self) -> f64 {
+		if self.switches == 0 {
+			return 0.0;
+		}
+		self.total_ms as f64 / self.switches as f64
+	}
+}
+
+fn push_timing<T>(list: &mut VecDeque<T>, item: T) {
+	if list.len() >= TIMING_LEN {
+		list.pop_front();
+	}
+	list.push_back(item);
+}
+
+impl TimingStats {
+	/// A job was received from the pool
+	pub fn job_received(&mut self, job_id: u64) {
+		push_timing(
+			&mut self.jobs,
+			JobTiming {
+				job_id: job_id,
+				received: clock::mono_ms(),
+				started: None,
+			},
+		);
+	}
+
+	/// A job was started on the solvers. Where it replaced a job being
+	/// mined, the solvers kept working on the old one from when the pool
+	/// sent the new one (taking one_way_ms to arrive) until it started, and
+	/// that work is counted as wasted.
+	pub fn job_started(&mut self, job_id: u64, replaced: bool, one_way_ms: u64) {
+		let now = clock::mono_ms();
+		if let Some(j) = self.jobs.iter_mut().rev().find(|j| j.job_id == job_id) {
+			j.started = Some(now);
+			if replaced {
+				self.job_switches += 1;
+				self.switch_waste_ms += one_way_ms + (now - j.received).max(0) as u64;
+			}
+		}
+	}
+
+	/// A device's solvers switched to a new job, taking ms to stop the old
+	/// one and start the new one
+	pub fn device_switched(&mut self, plugin: usize, device_id: &str, ms: u64) {
+		let pos = self
+			.device_switches
+			.iter()
+			.position(|d| d.plugin == plugin && d.device_id == device_id);
+		let device = match pos {
+			Some(i) => &mut self.device_switches[i],
+			None => {
+				self.device_switches.push(DeviceSwitchLatency {
+					plugin: plugin,
+					device_id: device_id.to_string(),
+					switches: 0,
+					total_ms: 0,
+					last_ms: 0,
+				});
+				self.device_switches.last_mut().unwrap()
+			}
+		};
+		device.switches += 1;
+		device.total_ms += ms;
+		device.last_ms = ms;
+	}
+
+	/// A solution was picked up from the solvers, for a job of the given
+	/// share difficulty, from the device given if known
+	pub fn solution_found(
+		&mut self,
+		height: u64,
+		job_id: u64,
+		target_difficulty: u64,
+		edge_bits: u32,
+		nonce: u64,
+		device: Option<(usize, String)>,
+	) {
+		let job = self.jobs.iter().rev().find(|j| j.job_id == job_id);
+		let timing = ShareTiming {
+			job_id: job_id,
+			nonce: nonce,
+			height: height,
+			edge_bits: edge_bits,
+			target_difficulty: target_difficulty,
+			job_received: job.map(|j| j.received),
+			job_started: job.and_then(|j| j.started),
+			found: clock::mono_ms(),
+			submitted: None,
+			answered: None,
+			accepted: false,
+			device: device,
+			cid: correlation::solution(height, job_id, nonce),
+		};
+		push_timing(&mut self.in_flight, timing);
+	}
+
+	/// A solution was submitted to the pool
+	pub fn submitted(&mut self, job_id: u64, nonce: u64) {
+		if let Some(s) = self
+			.in_flight
+			.iter_mut()
+			.find(|s| s.job_id == job_id && s.nonce == nonce && s.submitted.is_none())
+		{
+			s.submitted = Some(clock::mono_ms());
+		}
+	}
+
+	/// A submit recorded as sent couldn't be written after all
+	pub fn unsubmitted(&mut self, job_id: u64, nonce: u64) {
+		if let Some(s) = self
+			.in_flight
+			.iter_mut()
+			.rev()
+			.find(|s| s.job_id == job_id && s.nonce == nonce && s.submitted.is_some())
+		{
+			s.submitted = None;
+		}
+	}
+
+	/// The pool answered the oldest submit it hadn't answered yet, as
+	/// pools answer in order. Returns the share answered, if it was
+	/// known.
+	pub fn answered(&mut self, accepted: bool) -> Option<ShareTiming> {
+		let i = self.in_flight.iter().position(|s| s.submitted.is_some())?;
+		let mut s = self.in_flight.remove(i).unwrap();
+		s.answered = Some(clock::mono_ms());
+		s.accepted = accepted;
+		push_timing(&mut self.recent, s.clone());
+		Some(s)
+	}
+
+	/// The connection was lost, submits sent won't be answered. Returns
+	/// the shares that were waiting for an answer.
+	pub fn connection_lost(&mut self) -> Vec<ShareTiming> {
+		let lost = self
+			.in_flight
+			.iter()
+			.filter(|s| s.submitted.is_some())
+			.cloned()
+			.collect();
+		self.in_flight.retain(|s| s.submitted.is_none());
+		lost
+	}
+
+	/// Mean latencies of each stage over the recent shares
+	pub fn averages(&self) -> StageLatencies {
+		let stages: Vec<StageLatencies> = self.recent.iter().map(|s| s.stages()).collect();
+		let mean = |f: &dyn Fn(&StageLatencies) -> Option<f64>| {
+			let values: Vec<f64> = stages.iter().filter_map(|s| f(s)).collect();
+			if values.is_empty() {
+				None
+			} else {
+				Some(values.iter().sum::<f64>() / values.len() as f64)
+			}
+		};
+		StageLatencies {
+			queue: mean(&|s| s.queue),
+			solve: mean(&|s| s.solve),
+			submit: mean(&|s| s.submit),
+			ack: mean(&|s| s.ack),
+		}
+	}
+}
+
+/// How close the session's best share came to being a block
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockProgress {
+	pub network_difficulty: u64,
+	pub best_share_difficulty: u64,
+	/// Best share difficulty as a percentage of the network difficulty
+	pub progress: f64,
+	/// One share at the target difficulty in this many is a block, on
+	/// average
+	pub share_odds: Option<f64>,
+}
+
+/// Summary of a rig of the fleet, as shown in the TUI's fleet view
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FleetRig {
+	/// Configured or reported name of the rig
+	pub name: String,
+	/// Address of the rig's API
+	pub url: String,
+	/// Why the rig's stats couldn't be fetched, if they couldn't
+	pub error: Option<String>,
+	/// When the stats were last fetched
+	pub updated: Option<i64>,
+	pub connected: bool,
+	pub paused: bool,
+	pub combined_gps: f64,
+	/// Devices in use and solving, and errored
+	pub devices_up: usize,
+	pub devices_down: usize,
+	/// Share of the shares rejected, in percent
+	pub reject_rate: f64,
+	/// Time of the last share accepted
+	pub last_share: Option<i64>,
+}
+
+impl FleetRig {
+	/// Summary of a rig from its stats
+	pub fn summarize(name: &str, url: &str, stats: &Stats) -> FleetRig {
+		let (mut up, mut down) = (0, 0);
+		if let Some(ref plugins) = stats.mining_stats.device_stats {
+			for d in plugins.iter().flat_map(|p| p.iter()).filter(|d| d.in_use == 1) {
+				if d.has_errored == 0 {
+					up += 1;
+				} else {
+					down += 1;
+				}
+			}
+		}
+		let client = &stats.client_stats;
+		let answered = client.shares_accepted + client.shares_rejected;
+		FleetRig {
+			name: name.to_string(),
+			url: url.to_string(),
+			error: None,
+			updated: Some(time::get_time().sec),
+			connected: client.connected,
+			paused: stats.mining_stats.paused,
+			combined_gps: stats.mining_stats.combined_gps,
+			devices_up: up,
+			devices_down: down,
+			reject_rate: if answered == 0 {
+				0.0
+			} else {
+				client.shares_rejected as f64 * 100.0 / answered as f64
+			},
+			last_share: client.last_share_accepted,
+		}
+	}
+}
+
+/// A GPU found at startup
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GpuEnvironment {
+	/// cuda or ocl
+	pub kind: String,
+	pub name: String,
+	pub memory_mb: u64,
+}
+
+/// A plugin file of the configured plugins
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PluginEnvironment {
+	pub path: String,
+	/// Version, from the plugin manifest
+	pub version: Option<String>,
+	/// SHA-256 of the file, as hex
+	pub sha256: Option<String>,
+	/// Why the library doesn't load, if it doesn't
+	pub error: Option<String>,
+}
+
+/// Hardware and software the miner runs on, as found at startup
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Environment {
+	/// grin-miner version, with the git commit if known
+	pub version: String,
+	/// Target triple the miner was built for
+	pub target: String,
+	/// Operating system name and version
+	pub os: String,
+	pub kernel: Option<String>,
+	pub cpu: Option<String>,
+	pub cpu_threads: usize,
+	pub memory_mb: Option<u64>,
+	pub gpus: Vec<GpuEnvironment>,
+	pub nvidia_driver: Option<String>,
+	pub amd_driver: Option<String>,
+	/// Highest CUDA version the NVIDIA driver supports
+	pub cuda_version: Option<String>,
+	/// Versions of the OpenCL platforms installed
+	pub opencl_platforms: Vec<String>,
+	pub plugins: Vec<PluginEnvironment>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Stats {
 	/// Client/networking stats
 	pub client_stats: ClientStats,
 	/// Mining stats
 	pub mining_stats: MiningStats,
+	/// Currently active alerts
+	pub alerts: Vec<Alert>,
+	/// Session totals
+	pub session_stats: SessionStats,
+	/// Restart, connection and error history
+	pub history: HistoryStats,
+	/// Rig name and labels, as configured
+	#[serde(default)]
+	pub rig: RigConfig,
+	/// Timings of the most recent shares
+	#[serde(default)]
+	pub timing: TimingStats,
+	/// Remote rigs of the fleet view, as last polled
+	#[serde(default)]
+	pub fleet: Vec<FleetRig>,
+	/// Hardware and software found at startup
+	#[serde(default)]
+	pub environment: Option<Environment>,
+	/// Blocks found solo mining, in this session and recorded before,
+	/// oldest first
+	#[serde(default)]
+	pub blocks: Vec<FoundBlock>,
+	/// Monotonic clock seconds, written as of serializing, so the rolling
+	/// windows of stats read from another miner can be taken on its clock
+	#[serde(default, serialize_with = "clock::serialize_now")]
+	clock: Option<i64>,
 }
 
 impl Default for Stats {
@@ -82,6 +1831,204 @@ impl Default for Stats {
 		Stats {
 			client_stats: ClientStats::default(),
 			mining_stats: MiningStats::default(),
+			alerts: vec![],
+			session_stats: SessionStats::default(),
+			history: HistoryStats::default(),
+			rig: RigConfig::default(),
+			timing: TimingStats::default(),
+			fleet: vec![],
+			environment: None,
+			blocks: vec![],
+			clock: None,
+		}
+	}
+}
+
+impl Stats {
+	/// Monotonic clock seconds to take rolling windows and uptime as of:
+	/// now, or when the stats were read for those of another miner
+	pub fn clock_now(&self) -> i64 {
+		self.clock.unwrap_or_else(clock::mono_secs)
+	}
+
+	/// Part of the session's solver time wasted on jobs the pool had
+	/// already replaced, in percent
+	pub fn job_switch_waste(&self) -> f64 {
+		let uptime_ms = self.session_stats.uptime(self.clock_now()) * 1000;
+		if uptime_ms <= 0 {
+			return 0.0;
+		}
+		(self.timing.switch_waste_ms as f64 * 100.0 / uptime_ms as f64).min(100.0)
+	}
+
+	/// How close the best share came to the network difficulty, when the
+	/// pool sends it
+	pub fn block_progress(&self) -> Option<BlockProgress> {
+		let network = match self.mining_stats.network_difficulty {
+			Some(d) if d > 0 => d,
+			_ => return None,
+		};
+		let best = self.session_stats.best_share_difficulty;
+		let target = self.mining_stats.target_difficulty;
+		Some(BlockProgress {
+			network_difficulty: network,
+			best_share_difficulty: best,
+			progress: best as f64 * 100.0 / network as f64,
+			share_odds: if target > 0 {
+				Some(network as f64 / target as f64)
+			} else {
+				None
+			},
+		})
+	}
+
+	/// Mining status and network info lines, as shown in the TUI and
+	/// the console dashboard
+	/// The share difficulty suggested at login and the pool's answer, if
+	/// one was suggested
+	fn share_difficulty_negotiation(&self) -> String {
+		let suggested = match self.client_stats.suggested_difficulty {
+			Some(s) => s,
+			None => return String::new(),
+		};
+		match self.client_stats.negotiated_difficulty {
+			Some(n) => format!(
+				" (suggested {}, pool set {})",
+				units::number(suggested),
+				units::number(n)
+			),
+			None => format!(" (suggested {})", units::number(suggested)),
+		}
+	}
+
+	pub fn mining_status(&self) -> (String, String) {
+		let ramp_up = self.mining_stats.ramp_up.as_ref();
+		if let Some(at) = ramp_up.and_then(|r| r.starts_at) {
+			(
+				format!(
+					"Mining Status: Starting in {}s (startup delay)",
+					(at - time::get_time().sec).max(0)
+				),
+				" ".to_string(),
+			)
+		} else if self.mining_stats.paused {
+			let reason = self
+				.mining_stats
+				.desktop
+				.as_ref()
+				.and_then(|d| d.reason.as_ref())
+				.map_or(String::new(), |r| format!(" ({})", r));
+			(format!("Mining Status: Paused{}", reason), " ".to_string())
+		} else if let Some(r) = ramp_up
+			.filter(|r| r.next_device_at.is_some() && self.client_stats.connected)
+		{
+			(
+				format!(
+					"Mining Status: Ramping up, {} of {} devices started, next in {}s",
+					r.started_devices,
+					r.total_devices,
+					(r.next_device_at.unwrap_or(0) - time::get_time().sec).max(0)
+				),
+				" ".to_string(),
+			)
+		} else if self.client_stats.connected {
+			if self.mining_stats.combined_gps == 0.0 {
+				(
+					"Mining Status: Starting miner and awaiting first graph time...".to_string(),
+					" ".to_string(),
+				)
+			} else {
+				let profile = match self.mining_stats.profile {
+					Some(ref p) => format!(" (profile {})", p),
+					None => String::new(),
+				};
+				let algorithms = if self.mining_stats.algorithm_gps.len() > 1 {
+					format!(
+						" ({})",
+						self.mining_stats
+							.algorithm_gps
+							.iter()
+							.map(|a| format!("{} {}", a.0, units::gps_value(a.1)))
+							.collect::<Vec<String>>()
+							.join(", ")
+					)
+				} else {
+					String::new()
+				};
+				(
+					format!(
+						"Mining Status: Mining at height {} at {}{}{}",
+						self.mining_stats.block_height,
+						units::gps(self.mining_stats.combined_gps),
+						algorithms,
+						profile
+					),
+					format!(
+						"Cuck(at)oo - Target Share Difficulty {}{}",
+						units::number(self.mining_stats.target_difficulty),
+						self.share_difficulty_negotiation()
+					),
+				)
+			}
+		} else {
+			("Mining Status: Waiting for server".to_string(), "  ".to_string())
+		}
+	}
+
+	/// Shares line, as shown in the TUI and the console dashboard
+	pub fn shares_status(&self) -> String {
+		format!(
+			"Shares: Accepted: {}, Rejected: {}, Stale: {}",
+			units::number(self.client_stats.shares_accepted),
+			units::number(self.client_stats.shares_rejected),
+			units::number(self.client_stats.shares_stale)
+		)
+	}
+
+	/// Summary of the mining session so far, as printable text
+	pub fn session_summary(&self) -> String {
+		let session = &self.session_stats;
+		let uptime = session.uptime(self.clock_now());
+		let mut lines = vec![
+			"Grin Miner Session Summary".to_string(),
+			format!(
+				"Uptime: {}h {}m {}s",
+				uptime / 3600,
+				(uptime % 3600) / 60,
+				uptime % 60
+			),
+			self.shares_status(),
+			format!("Solutions found: {}", units::number(session.solutions_found)),
+			format!(
+				"Best share difficulty: {}",
+				units::number(session.best_share_difficulty)
+			),
+			format!(
+				"Solutions below target difficulty: {}",
+				units::number(session.shares_below_target)
+			),
+			format!(
+				"Share difficulty distribution: {}",
+				session.difficulty_histogram.summary()
+			),
+		];
+		let mut total_graphs = 0;
+		let mut total_errors = 0;
+		for d in &session.devices {
+			lines.push(format!(
+				"Plugin {} - Device {} ({}): Average GPS: {}, Graphs: {}, Errors: {}",
+				d.plugin,
+				d.device_id,
+				d.device_name,
+				units::gps_value(d.average_gps()),
+				units::number(d.graphs),
+				units::number(d.errors)
+			));
+			total_graphs += d.graphs;
+			total_errors += d.errors;
 		}
+		lines.push(format!("Total graphs: {}", units::number(total_graphs)));
+		lines.push(format!("Total errors: {}", units::number(total_errors)));
+		lines.join("\n")
 	}
 }
@@ -0,0 +1,192 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Periodic logging of stats snapshots to a CSV or JSON lines file, with
+//! size based rotation, for long term performance analysis
+
+use std::io::{self, Write};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+use time;
+
+use clock;
+use config::StatsLogConfig;
+use stats::{self, Stats};
+use util::{RotatingFile, LOGGER};
+
+const CSV_HEADER: &str = "timestamp,plugin,device_id,device_name,edge_bits,errored,gps,temperature,fan_speed,power_usage,shares_accepted,shares_rejected,shares_stale,uid,uptime_ms";
+
+/// A device in a JSON lines snapshot
+#[derive(Serialize)]
+pub struct DeviceRecord<'a> {
+	plugin: usize,
+	device_id: &'a str,
+	device_name: &'a str,
+	edge_bits: &'a str,
+	errored: bool,
+	gps: f64,
+	temperature: Option<u32>,
+	fan_speed: Option<u32>,
+	power_usage: Option<f64>,
+	/// Stable identity of the device, see stats::device_uid
+	uid: Option<&'a str>,
+}
+
+/// A JSON lines snapshot
+#[derive(Serialize)]
+pub struct Record<'a> {
+	timestamp: i64,
+	/// Milliseconds on the monotonic clock since the miner started, so
+	/// the time between records of a run holds across wall clock changes
+	uptime_ms: i64,
+	combined_gps: f64,
+	shares_accepted: u64,
+	shares_rejected: u64,
+	shares_stale: u64,
+	devices: Vec<DeviceRecord<'a>>,
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+	value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Build the snapshot record of the current stats
+pub fn record(stats: &Stats, timestamp: i64) -> Record {
+	let mut devices = vec![];
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		for (i, plugin) in plugins.iter().enumerate() {
+			for d in plugin.iter().filter(|d| d.in_use == 1) {
+				let hardware = stats.mining_stats.hardware_for(i, &d.device_id);
+				devices.push(DeviceRecord {
+					plugin: i,
+					device_id: &d.device_id,
+					device_name: &d.device_name,
+					edge_bits: &d.cuckoo_size,
+					errored: d.has_errored != 0,
+					gps: stats::device_gps(d),
+					temperature: hardware.and_then(|h| h.temperature),
+					fan_speed: hardware.and_then(|h| h.fan_speed),
+					power_usage: hardware.and_then(|h| h.power_usage),
+					uid: stats.mining_stats.device_uid(i, &d.device_id),
+				});
+			}
+		}
+	}
+	Record {
+		timestamp: timestamp,
+		uptime_ms: clock::mono_ms(),
+		combined_gps: stats.mining_stats.combined_gps,
+		shares_accepted: stats.client_stats.shares_accepted,
+		shares_rejected: stats.client_stats.shares_rejected,
+		shares_stale: stats.client_stats.shares_stale,
+		devices: devices,
+	}
+}
+
+/// Format the record as CSV rows, one per device
+fn csv_rows(record: &Record) -> String {
+	let mut rows = String::new();
+	for d in &record.devices {
+		rows.push_str(&format!(
+			"{},{},{},\"{}\",{},{},{:.4},{},{},{},{},{},{},{},{}\n",
+			record.timestamp,
+			d.plugin,
+			d.device_id,
+			d.device_name.replace("\"", "\"\""),
+			d.edge_bits,
+			d.errored,
+			d.gps,
+			optional(d.temperature),
+			optional(d.fan_speed),
+			optional(d.power_usage),
+			record.shares_accepted,
+			record.shares_rejected,
+			record.shares_stale,
+			optional(d.uid),
+			record.uptime_ms
+		));
+	}
+	rows
+}
+
+/// Appends stats snapshots to the configured file
+pub struct StatsLogger {
+	config: StatsLogConfig,
+	stats: Arc<RwLock<Stats>>,
+	file: Option<RotatingFile>,
+}
+
+impl StatsLogger {
+	/// Create a new stats logger
+	pub fn new(config: StatsLogConfig, stats: Arc<RwLock<Stats>>) -> StatsLogger {
+		StatsLogger {
+			config: config,
+			stats: stats,
+			file: None,
+		}
+	}
+
+	/// Start logging snapshots in a new thread
+	pub fn start(mut self) {
+		let interval = self.config.interval_secs.max(1);
+		info!(LOGGER, "Logging stats to {} every {} seconds", self.config.path, interval);
+		let _ = thread::Builder::new()
+			.name("stats_log".to_string())
+			.spawn(move || loop {
+				thread::sleep(Duration::from_secs(interval));
+				if let Err(e) = self.log_snapshot() {
+					error!(LOGGER, "Unable to write stats to {}: {}", self.config.path, e);
+					// opened again on the next snapshot
+					self.file = None;
+				}
+			});
+	}
+
+	fn log_snapshot(&mut self) -> io::Result<()> {
+		let lines = {
+			let stats = self.stats.read().unwrap();
+			let record = record(&stats, time::get_time().sec);
+			if self.config.format == "json" {
+				format!("{}\n", serde_json::to_string(&record).unwrap())
+			} else {
+				csv_rows(&record)
+			}
+		};
+		if self.file.is_none() {
+			// rotated once it reaches the maximum size, to path.1, shifting
+			// older files up to path.max_files and dropping the oldest
+			self.file = Some(RotatingFile::open(
+				&self.config.path,
+				true,
+				Some(self.config.max_size_mb * 1024 * 1024),
+				None,
+				self.config.max_files,
+				false,
+			)?);
+		}
+		let file = self.file.as_mut().unwrap();
+		// every CSV file starts with the header, rotated ones too
+		let new_file = file.size() == 0 || file.needs_rotation(lines.len());
+		let contents = if new_file && self.config.format != "json" {
+			format!("{}\n{}", CSV_HEADER, lines)
+		} else {
+			lines
+		};
+		file.write_all(contents.as_bytes())?;
+		file.flush()
+	}
+}
@@ -0,0 +1,299 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Embedded SQLite store of stats snapshots and found shares, so history
+//! can be kept and queried without an external database
+
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::{self, Connection};
+use serde_json::{self, Value};
+use time;
+
+use clock;
+use config::HistoryStoreConfig;
+use stats::{NetworkEventKind, Stats};
+use statslog;
+use util::LOGGER;
+
+const SCHEMA: &str = "
+	CREATE TABLE IF NOT EXISTS snapshots (
+		time INTEGER NOT NULL,
+		combined_gps REAL NOT NULL,
+		data TEXT NOT NULL,
+		uptime_ms INTEGER
+	);
+	CREATE INDEX IF NOT EXISTS snapshots_time ON snapshots (time);
+	CREATE TABLE IF NOT EXISTS shares (
+		time INTEGER NOT NULL,
+		edge_bits INTEGER NOT NULL,
+		difficulty INTEGER NOT NULL,
+		target_difficulty INTEGER NOT NULL
+	);
+	CREATE INDEX IF NOT EXISTS shares_time ON shares (time);
+	CREATE TABLE IF NOT EXISTS network_events (
+		time INTEGER NOT NULL,
+		kind TEXT NOT NULL,
+		edge_bits INTEGER,
+		from_value INTEGER,
+		to_value INTEGER NOT NULL
+	);
+	CREATE INDEX IF NOT EXISTS network_events_time ON network_events (time);
+";
+
+/// A stored stats snapshot
+#[derive(Serialize)]
+pub struct Snapshot {
+	/// When the snapshot was taken (seconds since epoch)
+	pub time: i64,
+	/// When the snapshot was taken, in milliseconds on the monotonic clock
+	/// since the miner started: the time between snapshots of a run, even
+	/// across wall clock changes. Missing for stores older than it.
+	pub uptime_ms: Option<i64>,
+	/// The snapshot, as logged by the stats logger
+	pub data: Value,
+}
+
+/// A stored share
+#[derive(Serialize)]
+pub struct Share {
+	/// When the share was found (seconds since epoch)
+	pub time: i64,
+	/// Graph size
+	pub edge_bits: i64,
+	/// Difficulty of the share
+	pub difficulty: i64,
+	/// Share difficulty targeted when the share was found
+	pub target_difficulty: i64,
+}
+
+/// A stored block height or share difficulty change
+#[derive(Serialize)]
+pub struct Event {
+	/// When the job bringing the change was received (seconds since epoch)
+	pub time: i64,
+	/// "height" or "difficulty"
+	pub kind: String,
+	/// Graph size, for difficulty changes of pools sending jobs per
+	/// algorithm
+	pub edge_bits: Option<i64>,
+	/// Value before, none for the first job of a session
+	pub from: Option<i64>,
+	pub to: i64,
+}
+
+/// Snapshots, shares and network events in a time range
+#[derive(Serialize)]
+pub struct History {
+	/// Snapshots, oldest first
+	pub snapshots: Vec<Snapshot>,
+	/// Shares, oldest first
+	pub shares: Vec<Share>,
+	/// Height and difficulty changes, oldest first
+	pub events: Vec<Event>,
+}
+
+fn open(path: &str) -> rusqlite::Result<Connection> {
+	let conn = Connection::open(path)?;
+	conn.execute_batch(SCHEMA)?;
+	// stores created before snapshots had their uptime
+	if conn.prepare("SELECT uptime_ms FROM snapshots LIMIT 0").is_err() {
+		conn.execute_batch("ALTER TABLE snapshots ADD COLUMN uptime_ms INTEGER")?;
+	}
+	Ok(conn)
+}
+
+/// Query the store at path for the snapshots, shares and network events
+/// between from and to (inclusive, seconds since epoch)
+pub fn query(path: &str, from: i64, to: i64) -> Result<History, String> {
+	let conn = open(path).map_err(|e| format!("{}", e))?;
+	let snapshots = {
+		let mut stmt = conn
+			.prepare(
+				"SELECT time, uptime_ms, data FROM snapshots \
+				 WHERE time >= ?1 AND time <= ?2 ORDER BY time",
+			)
+			.map_err(|e| format!("{}", e))?;
+		let rows = stmt
+			.query_map(&[&from, &to], |row| {
+				let data: String = row.get(2);
+				Snapshot {
+					time: row.get(0),
+					uptime_ms: row.get(1),
+					data: serde_json::from_str(&data).unwrap_or(Value::Null),
+				}
+			})
+			.map_err(|e| format!("{}", e))?;
+		rows.filter_map(|r| r.ok()).collect()
+	};
+	let shares = {
+		let mut stmt = conn
+			.prepare(
+				"SELECT time, edge_bits, difficulty, target_difficulty FROM shares \
+				 WHERE time >= ?1 AND time <= ?2 ORDER BY time",
+			)
+			.map_err(|e| format!("{}", e))?;
+		let rows = stmt
+			.query_map(&[&from, &to], |row| Share {
+				time: row.get(0),
+				edge_bits: row.get(1),
+				difficulty: row.get(2),
+				target_difficulty: row.get(3),
+			})
+			.map_err(|e| format!("{}", e))?;
+		rows.filter_map(|r| r.ok()).collect()
+	};
+	let events = {
+		let mut stmt = conn
+			.prepare(
+				"SELECT time, kind, edge_bits, from_value, to_value FROM network_events \
+				 WHERE time >= ?1 AND time <= ?2 ORDER BY time",
+			)
+			.map_err(|e| format!("{}", e))?;
+		let rows = stmt
+			.query_map(&[&from, &to], |row| Event {
+				time: row.get(0),
+				kind: row.get(1),
+				edge_bits: row.get(2),
+				from: row.get(3),
+				to: row.get(4),
+			})
+			.map_err(|e| format!("{}", e))?;
+		rows.filter_map(|r| r.ok()).collect()
+	};
+	Ok(History {
+		snapshots: snapshots,
+		shares: shares,
+		events: events,
+	})
+}
+
+/// Periodically records stats snapshots, new shares and network events,
+/// pruning records older than the retention period
+pub struct HistoryStore {
+	config: HistoryStoreConfig,
+	stats: Arc<RwLock<Stats>>,
+}
+
+impl HistoryStore {
+	/// Create a new history store
+	pub fn new(config: HistoryStoreConfig, stats: Arc<RwLock<Stats>>) -> HistoryStore {
+		HistoryStore {
+			config: config,
+			stats: stats,
+		}
+	}
+
+	/// Start recording in a new thread
+	pub fn start(self) {
+		let conn = match open(&self.config.path) {
+			Ok(c) => c,
+			Err(e) => {
+				error!(
+					LOGGER,
+					"Unable to open history store {}: {}", self.config.path, e
+				);
+				return;
+			}
+		};
+		info!(
+			LOGGER,
+			"Recording stats history to {} every {} seconds",
+			self.config.path,
+			self.config.interval_secs
+		);
+		let _ = thread::Builder::new()
+			.name("history_store".to_string())
+			.spawn(move || {
+				let mut stored = (0, 0);
+				loop {
+					thread::sleep(Duration::from_secs(self.config.interval_secs.max(1)));
+					match self.record(&conn, stored) {
+						Ok(s) => stored = s,
+						Err(e) => error!(LOGGER, "Unable to record stats history: {}", e),
+					}
+				}
+			});
+	}
+
+	/// Record a snapshot, and the shares found and network events since
+	/// the last one, given and returning the number of shares found and
+	/// network events so far
+	fn record(&self, conn: &Connection, stored: (u64, u64)) -> rusqlite::Result<(u64, u64)> {
+		let (shares_stored, events_stored) = stored;
+		let now = time::get_time().sec;
+		let uptime_ms = clock::mono_ms();
+		let (combined_gps, data, new_shares, solutions_found, new_events, events_total) = {
+			let stats = self.stats.read().unwrap();
+			let record = statslog::record(&stats, now);
+			let session = &stats.session_stats;
+			let new = (session.solutions_found - shares_stored.min(session.solutions_found)) as usize;
+			let skip = session.recent_shares.len() - new.min(session.recent_shares.len());
+			let new_shares: Vec<_> = session.recent_shares.iter().skip(skip).cloned().collect();
+			let history = &stats.history;
+			let new = (history.network_events_total - events_stored.min(history.network_events_total))
+				as usize;
+			let skip = history.network_events.len() - new.min(history.network_events.len());
+			let new_events: Vec<_> = history.network_events.iter().skip(skip).cloned().collect();
+			(
+				stats.mining_stats.combined_gps,
+				serde_json::to_string(&record).unwrap(),
+				new_shares,
+				session.solutions_found,
+				new_events,
+				history.network_events_total,
+			)
+		};
+		conn.execute(
+			"INSERT INTO snapshots (time, combined_gps, data, uptime_ms) VALUES (?1, ?2, ?3, ?4)",
+			&[&now, &combined_gps, &data, &uptime_ms],
+		)?;
+		for s in new_shares {
+			conn.execute(
+				"INSERT INTO shares (time, edge_bits, difficulty, target_difficulty) \
+				 VALUES (?1, ?2, ?3, ?4)",
+				&[
+					&s.time,
+					&(s.edge_bits as i64),
+					&(s.difficulty as i64),
+					&(s.target_difficulty as i64),
+				],
+			)?;
+		}
+		for e in new_events {
+			let kind = match e.kind {
+				NetworkEventKind::Height => "height".to_string(),
+				NetworkEventKind::Difficulty => "difficulty".to_string(),
+			};
+			conn.execute(
+				"INSERT INTO network_events (time, kind, edge_bits, from_value, to_value) \
+				 VALUES (?1, ?2, ?3, ?4, ?5)",
+				&[
+					&e.time,
+					&kind,
+					&e.edge_bits.map(|b| b as i64),
+					&e.from.map(|f| f as i64),
+					&(e.to as i64),
+				],
+			)?;
+		}
+		let cutoff = now - self.config.retention_days as i64 * 24 * 3600;
+		conn.execute("DELETE FROM snapshots WHERE time < ?1", &[&cutoff])?;
+		conn.execute("DELETE FROM shares WHERE time < ?1", &[&cutoff])?;
+		conn.execute("DELETE FROM network_events WHERE time < ?1", &[&cutoff])?;
+		Ok((solutions_found, events_total))
+	}
+}
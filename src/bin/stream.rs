@@ -0,0 +1,332 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `/v1/stream`, a server-sent events stream of the stats for dashboards
+//! updating every second: a snapshot when connecting, then only the
+//! devices that changed and the shares found since, numbered so a client
+//! can tell it missed nothing
+
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use api;
+use clock;
+use stats::{ShareRecord, Stats};
+use util::LOGGER;
+
+/// Time between updates, unless asked otherwise
+pub const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+/// Shortest time between updates a client may ask for
+pub const MIN_INTERVAL_MS: u64 = 250;
+
+/// Streams served at once, each having a thread
+const MAX_STREAMS: usize = 16;
+
+/// Seconds without an update before a keepalive comment is sent
+const KEEPALIVE_SECS: i64 = 15;
+
+/// Streams being served
+static STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+/// What the whole miner is doing
+#[derive(Clone, PartialEq, Serialize)]
+struct Summary {
+	connected: bool,
+	mining_status: String,
+	block_height: u64,
+	target_difficulty: u64,
+	combined_gps: f64,
+	shares_accepted: u64,
+	shares_rejected: u64,
+	shares_stale: u64,
+	alerts: usize,
+}
+
+/// The `snapshot` event, everything a client starts from
+#[derive(Serialize)]
+struct SnapshotEvent<'a> {
+	seq: u64,
+	summary: &'a Summary,
+	devices: Vec<&'a Value>,
+	shares: Vec<&'a ShareRecord>,
+}
+
+/// The `update` event, what changed since the previous event
+#[derive(Serialize)]
+struct UpdateEvent<'a> {
+	seq: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	summary: Option<&'a Summary>,
+	/// Devices added or changed, whole
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	devices: Vec<&'a Value>,
+	/// Devices gone, as "plugin:device_id"
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	removed: Vec<&'a str>,
+	/// Shares found since the previous event
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	shares: Vec<ShareRecord>,
+}
+
+/// The state last sent to the client
+struct Sent {
+	summary: Summary,
+	devices: Vec<(String, Value)>,
+	solutions_found: u64,
+}
+
+fn summary(stats: &Stats) -> Summary {
+	Summary {
+		connected: stats.client_stats.connected,
+		mining_status: stats.mining_status().0,
+		block_height: stats.mining_stats.block_height,
+		target_difficulty: stats.mining_stats.target_difficulty,
+		combined_gps: stats.mining_stats.combined_gps,
+		shares_accepted: stats.client_stats.shares_accepted,
+		shares_rejected: stats.client_stats.shares_rejected,
+		shares_stale: stats.client_stats.shares_stale,
+		alerts: stats.alerts.len(),
+	}
+}
+
+/// Shares found since the given number of solutions, as far as the recent
+/// shares go back
+fn shares_since(stats: &Stats, solutions_found: u64) -> Vec<ShareRecord> {
+	let session = &stats.session_stats;
+	let new = (session.solutions_found - solutions_found.min(session.solutions_found)) as usize;
+	let skip = session.recent_shares.len() - new.min(session.recent_shares.len());
+	session.recent_shares.iter().skip(skip).cloned().collect()
+}
+
+/// The update from what was last sent, none if nothing changed
+fn update<'a>(
+	previous: &Sent,
+	seq: u64,
+	summary: &'a Summary,
+	devices: &'a [(String, Value)],
+	shares: Vec<ShareRecord>,
+) -> Option<UpdateEvent<'a>> {
+	let changed: Vec<&Value> = devices
+		.iter()
+		.filter(|d| !previous.devices.contains(d))
+		.map(|d| &d.1)
+		.collect();
+	let removed: Vec<&str> = previous
+		.devices
+		.iter()
+		.filter(|p| !devices.iter().any(|d| d.0 == p.0))
+		.map(|p| p.0.as_str())
+		.collect();
+	let update = UpdateEvent {
+		seq: seq,
+		summary: if *summary != previous.summary {
+			Some(summary)
+		} else {
+			None
+		},
+		devices: changed,
+		removed: removed,
+		shares: shares,
+	};
+	if update.summary.is_none()
+		&& update.devices.is_empty()
+		&& update.removed.is_empty()
+		&& update.shares.is_empty()
+	{
+		None
+	} else {
+		Some(update)
+	}
+}
+
+/// Whether a keepalive comment is due, nothing having been written since
+/// last_write
+fn keepalive_due(now: i64, last_write: i64) -> bool {
+	now - last_write >= KEEPALIVE_SECS
+}
+
+fn write_event<T: Serialize>(
+	stream: &mut TcpStream,
+	name: &str,
+	seq: u64,
+	event: &T,
+) -> io::Result<()> {
+	let data = serde_json::to_string(event).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+	write!(stream, "id: {}\nevent: {}\ndata: {}\n\n", seq, name, data)?;
+	stream.flush()
+}
+
+/// Serve the stream on a thread of its own until the client goes away,
+/// unless too many are already served
+pub fn serve(mut stream: TcpStream, stats: Arc<RwLock<Stats>>, interval_ms: u64) {
+	if STREAMS.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMS {
+		STREAMS.fetch_sub(1, Ordering::SeqCst);
+		let body = "{\"error\": \"too many streams\"}";
+		let _ = write!(
+			stream,
+			"HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\n\
+			 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+			body.len(),
+			body
+		);
+		return;
+	}
+	let spawned = thread::Builder::new()
+		.name("api_stream".to_string())
+		.spawn(move || {
+			if let Err(e) = run(&mut stream, &stats, interval_ms) {
+				debug!(LOGGER, "API stream closed: {}", e);
+			}
+			STREAMS.fetch_sub(1, Ordering::SeqCst);
+		});
+	if spawned.is_err() {
+		STREAMS.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+fn run(stream: &mut TcpStream, stats: &Arc<RwLock<Stats>>, interval_ms: u64) -> io::Result<()> {
+	stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+	write!(
+		stream,
+		"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+		 Connection: keep-alive\r\n\r\n"
+	)?;
+	let mut seq = 0;
+	let mut sent: Option<Sent> = None;
+	let mut last_write = clock::mono_secs();
+	loop {
+		let (summary, devices, solutions_found, shares) = {
+			let stats = stats.read().unwrap();
+			let shares = match sent {
+				Some(ref s) => shares_since(&stats, s.solutions_found),
+				None => stats.session_stats.recent_shares.iter().cloned().collect(),
+			};
+			(
+				summary(&stats),
+				api::device_values(&stats),
+				stats.session_stats.solutions_found,
+				shares,
+			)
+		};
+		let wrote = match sent {
+			None => {
+				seq += 1;
+				write_event(
+					stream,
+					"snapshot",
+					seq,
+					&SnapshotEvent {
+						seq: seq,
+						summary: &summary,
+						devices: devices.iter().map(|d| &d.1).collect(),
+						shares: shares.iter().collect(),
+					},
+				)?;
+				true
+			}
+			Some(ref previous) => match update(previous, seq + 1, &summary, &devices, shares) {
+				Some(update) => {
+					seq += 1;
+					write_event(stream, "update", seq, &update)?;
+					true
+				}
+				None => false,
+			},
+		};
+		let now = clock::mono_secs();
+		if wrote {
+			last_write = now;
+		} else if keepalive_due(now, last_write) {
+			// also finds out when the client has gone
+			write!(stream, ": keepalive\n\n")?;
+			stream.flush()?;
+			last_write = now;
+		}
+		sent = Some(Sent {
+			summary: summary,
+			devices: devices,
+			solutions_found: solutions_found,
+		});
+		thread::sleep(Duration::from_millis(interval_ms));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use util::cuckoo_miner::CuckooMinerDeviceStats;
+
+	fn device(id: &str, iterations: u32) -> CuckooMinerDeviceStats {
+		CuckooMinerDeviceStats {
+			device_id: id.to_string(),
+			device_name: format!("GPU {}", id),
+			in_use: 1,
+			cuckoo_size: "31".to_string(),
+			iterations_completed: iterations,
+			..Default::default()
+		}
+	}
+
+	fn sent(stats: &Stats) -> Sent {
+		Sent {
+			summary: summary(stats),
+			devices: api::device_values(stats),
+			solutions_found: stats.session_stats.solutions_found,
+		}
+	}
+
+	#[test]
+	fn test_update() {
+		let mut stats = Stats::default();
+		stats.mining_stats.device_stats = Some(vec![vec![device("0", 1), device("1", 1)]]);
+		stats.session_stats.add_solution(31, 10, 4);
+		let previous = sent(&stats);
+
+		// nothing changed
+		let current = summary(&stats);
+		let devices = api::device_values(&stats);
+		let shares = shares_since(&stats, previous.solutions_found);
+		assert!(shares.is_empty());
+		assert!(update(&previous, 2, &current, &devices, shares).is_none());
+
+		// device 0 mined on, device 1 is gone and a share was found
+		stats.mining_stats.device_stats = Some(vec![vec![device("0", 2)]]);
+		stats.session_stats.add_solution(31, 20, 4);
+		let current = summary(&stats);
+		let devices = api::device_values(&stats);
+		let shares = shares_since(&stats, previous.solutions_found);
+		let update = update(&previous, 2, &current, &devices, shares).unwrap();
+		assert_eq!(update.seq, 2);
+		assert_eq!(update.devices.len(), 1);
+		assert_eq!(update.devices[0]["stats"]["device_id"], "0");
+		assert_eq!(update.removed, vec!["0:1"]);
+		assert_eq!(update.shares.len(), 1);
+		assert_eq!(update.shares[0].difficulty, 20);
+	}
+
+	#[test]
+	fn test_keepalive() {
+		assert!(!keepalive_due(100, 100));
+		assert!(!keepalive_due(100 + KEEPALIVE_SECS - 1, 100));
+		assert!(keepalive_due(100 + KEEPALIVE_SECS, 100));
+	}
+}
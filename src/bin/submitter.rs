@@ -0,0 +1,160 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Submit worker, writing to the stratum server on a thread of its own so
+//! a slow submit never holds up the client loop reading new jobs. Every
+//! message goes through it, submits and requests alike, so they're sent
+//! in order and lines are never interleaved.
+
+use std::io::{ErrorKind, Write};
+use std::net::TcpStream;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use chaos::Chaos;
+use stats::Stats;
+use util::LOGGER;
+
+/// Wait between attempts to write to a full socket
+const WRITE_RETRY_MS: u64 = 10;
+
+enum Message {
+	/// Write to this connection from now on
+	Connect(TcpStream),
+	/// Send a request
+	Send(String),
+	/// Send a share, for the given job and nonce
+	Submit(String, u64, u64),
+	/// Inject failures into submits, for testing
+	Chaos(Chaos),
+}
+
+/// Handle of the submit worker, which runs until the handle is dropped
+pub struct Submitter {
+	tx: mpsc::Sender<Message>,
+}
+
+impl Submitter {
+	/// Start the worker
+	pub fn start(stats: Arc<RwLock<Stats>>) -> Submitter {
+		let (tx, rx) = mpsc::channel();
+		thread::Builder::new()
+			.name("submitter".to_string())
+			.spawn(move || run(rx, stats))
+			.expect("unable to start the submit worker");
+		Submitter { tx: tx }
+	}
+
+	/// Write to the given connection from now on, once the messages
+	/// queued for the previous one are written
+	pub fn connect(&self, conn: TcpStream) {
+		let _ = self.tx.send(Message::Connect(conn));
+	}
+
+	/// Queue a request
+	pub fn send(&self, message: String) {
+		let _ = self.tx.send(Message::Send(message));
+	}
+
+	/// Queue a share
+	pub fn submit(&self, message: String, job_id: u64, nonce: u64) {
+		let _ = self.tx.send(Message::Submit(message, job_id, nonce));
+	}
+
+	/// Inject failures into submits, for testing
+	pub fn set_chaos(&self, chaos: Chaos) {
+		let _ = self.tx.send(Message::Chaos(chaos));
+	}
+}
+
+/// Write the whole line, waiting while the socket is full
+fn write_line(conn: &mut TcpStream, message: &str) -> Result<(), String> {
+	let line = format!("{}\n", message);
+	let mut written = 0;
+	while written < line.len() {
+		match conn.write(&line.as_bytes()[written..]) {
+			Ok(0) => return Err("connection closed".to_string()),
+			Ok(n) => written += n,
+			Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+				thread::sleep(Duration::from_millis(WRITE_RETRY_MS));
+			}
+			Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+			Err(e) => return Err(format!("{}", e)),
+		}
+	}
+	conn.flush().map_err(|e| format!("{}", e))
+}
+
+fn run(rx: mpsc::Receiver<Message>, stats: Arc<RwLock<Stats>>) {
+	let mut conn: Option<TcpStream> = None;
+	let mut chaos: Option<Chaos> = None;
+	for message in rx {
+		let (line, share) = match message {
+			Message::Connect(c) => {
+				conn = Some(c);
+				continue;
+			}
+			Message::Chaos(c) => {
+				chaos = Some(c);
+				continue;
+			}
+			Message::Send(line) => (line, None),
+			Message::Submit(line, job_id, nonce) => {
+				if let Some(ref mut chaos) = chaos {
+					let ms = chaos.submit_stall();
+					if ms > 0 {
+						warn!(LOGGER, "Chaos: stalled a submit for {}ms", ms);
+					}
+				}
+				(line, Some((job_id, nonce)))
+			}
+		};
+		// counted before writing, the answer can be read before the write
+		// returns
+		if let Some((job_id, nonce)) = share {
+			let mut stats = stats.write().unwrap();
+			stats.client_stats.submits_pending += 1;
+			stats.timing.submitted(job_id, nonce);
+		}
+		let result = match conn {
+			Some(ref mut c) => write_line(c, &line),
+			None => Err("no server connection".to_string()),
+		};
+		if let Err(e) = result {
+			// the client loop notices the connection is gone when reading
+			match share {
+				Some((job_id, nonce)) => {
+					error!(
+						LOGGER,
+						"Unable to submit share (nonce {}): {}", nonce, e;
+						"job_id" => job_id
+					);
+					let mut stats = stats.write().unwrap();
+					stats.client_stats.submits_pending =
+						stats.client_stats.submits_pending.saturating_sub(1);
+					stats.timing.unsubmitted(job_id, nonce);
+				}
+				None => error!(LOGGER, "Unable to send a request to the server: {}", e),
+			}
+			conn = None;
+			continue;
+		}
+		debug!(LOGGER, "sent request: {}", line);
+		let mut stats = stats.write().unwrap();
+		stats.client_stats.trace_message(true, &line);
+		// with the line ending
+		stats.client_stats.traffic.record_sent(line.len() + 1);
+	}
+}
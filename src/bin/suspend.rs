@@ -0,0 +1,99 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! System suspend detection. A thread waking up every few seconds finds
+//! the clock far ahead when the system was suspended in between; the pool
+//! connection is then dropped, its socket likely dead, and mining fully
+//! restarted, rebuilding the solver contexts the GPU driver may have lost.
+//! A large forward step of the system clock looks the same, and is
+//! handled alike.
+
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use time;
+
+use stats::Stats;
+use types::{ClientMessage, MinerMessage};
+use util::LOGGER;
+
+/// Seconds between checks
+const CHECK_INTERVAL_SECS: i64 = 5;
+
+/// Seconds a check may come late before a suspend is assumed
+const SUSPEND_THRESHOLD_SECS: i64 = 30;
+
+/// Watches for the system resuming from suspend
+pub struct SuspendDetector {
+	stats: Arc<RwLock<Stats>>,
+	miner_tx: mpsc::Sender<MinerMessage>,
+	client_tx: mpsc::Sender<ClientMessage>,
+}
+
+impl SuspendDetector {
+	/// Create a new suspend detector
+	pub fn new(
+		stats: Arc<RwLock<Stats>>,
+		miner_tx: mpsc::Sender<MinerMessage>,
+		client_tx: mpsc::Sender<ClientMessage>,
+	) -> SuspendDetector {
+		SuspendDetector {
+			stats: stats,
+			miner_tx: miner_tx,
+			client_tx: client_tx,
+		}
+	}
+
+	/// Start watching in a new thread
+	pub fn start(self) {
+		let _ = thread::Builder::new()
+			.name("suspend".to_string())
+			.spawn(move || {
+				let mut last_wall = time::get_time().sec;
+				let mut last_instant = Instant::now();
+				loop {
+					thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS as u64));
+					let wall = time::get_time().sec;
+					// the monotonic clock stops during suspend on some
+					// platforms and not others, the wall clock always runs
+					let elapsed = (wall - last_wall).max(last_instant.elapsed().as_secs() as i64);
+					last_wall = wall;
+					last_instant = Instant::now();
+					if elapsed - CHECK_INTERVAL_SECS > SUSPEND_THRESHOLD_SECS {
+						self.resumed(wall - elapsed, elapsed - CHECK_INTERVAL_SECS);
+					}
+				}
+			});
+	}
+
+	fn resumed(&self, since: i64, duration: i64) {
+		warn!(
+			LOGGER,
+			"System resumed after being suspended for about {}s, reconnecting and restarting mining",
+			duration
+		);
+		self.stats
+			.write()
+			.unwrap()
+			.history
+			.record_suspend(since, duration);
+		// shares sent before won't be answered on the old socket
+		let _ = self.client_tx.send(ClientMessage::Reconnect);
+		let _ = self.miner_tx.send(MinerMessage::Restart(format!(
+			"resumed from a {}s suspend",
+			duration
+		)));
+	}
+}
@@ -0,0 +1,172 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! systemd integration (sd_notify): readiness once mining starts, status
+//! lines, and watchdog pings for units with Type=notify and WatchdogSec.
+//! Does nothing unless started by systemd with NOTIFY_SOCKET set, or on
+//! other systems than Linux.
+
+use std::env;
+
+use clock;
+use stats::Stats;
+use util::LOGGER;
+
+/// Seconds between status updates
+const STATUS_INTERVAL_SECS: i64 = 10;
+
+/// Sends notifications to systemd
+pub struct Notifier {
+	/// Notification socket path, abstract if it starts with @
+	socket: Option<String>,
+	/// Milliseconds between watchdog pings, half the unit's WatchdogSec
+	watchdog_interval_ms: Option<i64>,
+	next_watchdog_ms: i64,
+	next_status: i64,
+}
+
+impl Notifier {
+	/// Notifier for the socket systemd gave, if any
+	pub fn new() -> Notifier {
+		let socket = env::var("NOTIFY_SOCKET").ok().filter(|s| !s.is_empty());
+		// the watchdog is for this process only, not its solver workers
+		let for_us = env::var("WATCHDOG_PID")
+			.ok()
+			.and_then(|p| p.parse::<u32>().ok())
+			.map_or(true, |p| p == ::std::process::id());
+		let watchdog_interval_ms = env::var("WATCHDOG_USEC")
+			.ok()
+			.and_then(|u| u.parse::<i64>().ok())
+			.filter(|u| *u > 0 && for_us)
+			.map(|u| u / 2000);
+		if socket.is_some() {
+			debug!(
+				LOGGER,
+				"systemd notifications enabled{}",
+				watchdog_interval_ms.map_or(String::new(), |i| format!(", watchdog ping every {}ms", i))
+			);
+		}
+		// not passed on to the solver workers
+		env::remove_var("NOTIFY_SOCKET");
+		env::remove_var("WATCHDOG_USEC");
+		env::remove_var("WATCHDOG_PID");
+		Notifier {
+			socket: socket,
+			watchdog_interval_ms: watchdog_interval_ms,
+			next_watchdog_ms: 0,
+			next_status: 0,
+		}
+	}
+
+	/// Tell systemd the miner has started
+	pub fn ready(&self) {
+		self.notify("READY=1\nSTATUS=Mining");
+	}
+
+	/// Tell systemd the config is being reloaded, then that it's done
+	pub fn reloading(&self) {
+		self.notify("RELOADING=1");
+	}
+
+	pub fn reloaded(&self) {
+		self.notify("READY=1");
+	}
+
+	/// Tell systemd the miner is shutting down
+	pub fn stopping(&self) {
+		self.notify("STOPPING=1\nSTATUS=Draining");
+	}
+
+	/// Ping the watchdog and update the status line when they're due.
+	/// Called from the main loop, with whether the mining controller is
+	/// still running: a stopped miner isn't kept alive.
+	pub fn tick(&mut self, stats: &Stats, mining: bool) {
+		if self.socket.is_none() {
+			return;
+		}
+		if let Some(interval) = self.watchdog_interval_ms {
+			let now = clock::mono_ms();
+			if mining && now >= self.next_watchdog_ms {
+				self.notify("WATCHDOG=1");
+				self.next_watchdog_ms = now + interval;
+			}
+		}
+		let now = clock::mono_secs();
+		if now >= self.next_status {
+			self.notify(&format!(
+				"STATUS={}, {:.4} gps, {}",
+				stats.mining_status().0,
+				stats.mining_stats.combined_gps,
+				stats.shares_status()
+			));
+			self.next_status = now + STATUS_INTERVAL_SECS;
+		}
+	}
+
+	fn notify(&self, message: &str) {
+		if let Some(ref socket) = self.socket {
+			if let Err(e) = send(socket, message) {
+				debug!(LOGGER, "Unable to notify systemd: {}", e);
+			}
+		}
+	}
+}
+
+/// Send a datagram to the notification socket, which can be in the
+/// abstract namespace (@name), so it's addressed by hand
+#[cfg(target_os = "linux")]
+fn send(socket: &str, message: &str) -> Result<(), String> {
+	use libc;
+	use std::io;
+	use std::mem;
+	unsafe {
+		let mut addr: libc::sockaddr_un = mem::zeroed();
+		addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+		let path = socket.as_bytes();
+		if path.len() >= addr.sun_path.len() {
+			return Err(format!("socket path too long: {}", socket));
+		}
+		for (i, b) in path.iter().enumerate() {
+			addr.sun_path[i] = *b as libc::c_char;
+		}
+		if path[0] == b'@' {
+			addr.sun_path[0] = 0;
+		}
+		let len = mem::size_of::<libc::sa_family_t>() + path.len();
+		let fd = libc::socket(libc::AF_UNIX, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+		if fd == -1 {
+			return Err(format!("{}", io::Error::last_os_error()));
+		}
+		let sent = libc::sendto(
+			fd,
+			message.as_ptr() as *const libc::c_void,
+			message.len(),
+			libc::MSG_NOSIGNAL,
+			&addr as *const libc::sockaddr_un as *const libc::sockaddr,
+			len as libc::socklen_t,
+		);
+		let result = if sent == -1 {
+			Err(format!("{}", io::Error::last_os_error()))
+		} else {
+			Ok(())
+		};
+		libc::close(fd);
+		result
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send(_socket: &str, _message: &str) -> Result<(), String> {
+	Ok(())
+}
@@ -0,0 +1,209 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Telemetry push exporter, periodically writing device and share metrics
+//! to InfluxDB (line protocol over HTTP) or Graphite (plaintext over TCP)
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use config::TelemetryConfig;
+use http;
+use stats::{self, Stats};
+use util::LOGGER;
+
+/// A single metric value, with its tags
+struct Metric {
+	measurement: &'static str,
+	field: &'static str,
+	tags: Vec<(String, String)>,
+	value: f64,
+}
+
+/// Collect the metrics to push from the current stats
+fn metrics(stats: &Stats) -> Vec<Metric> {
+	let metric = |measurement, field, tags: &Vec<(String, String)>, value| Metric {
+		measurement: measurement,
+		field: field,
+		tags: tags.clone(),
+		value: value,
+	};
+	let mut metrics = vec![];
+	let none = vec![];
+	metrics.push(metric("miner", "combined_gps", &none, stats.mining_stats.combined_gps));
+	for &(ref algorithm, gps) in &stats.mining_stats.algorithm_gps {
+		let tags = vec![("algorithm".to_string(), algorithm.clone())];
+		metrics.push(metric("miner", "algorithm_gps", &tags, gps));
+	}
+	metrics.push(metric("miner", "block_height", &none, stats.mining_stats.block_height as f64));
+	let client_stats = &stats.client_stats;
+	metrics.push(metric("shares", "accepted", &none, client_stats.shares_accepted as f64));
+	metrics.push(metric("shares", "rejected", &none, client_stats.shares_rejected as f64));
+	metrics.push(metric("shares", "stale", &none, client_stats.shares_stale as f64));
+	if let Some(ref plugins) = stats.mining_stats.device_stats {
+		for (i, plugin) in plugins.iter().enumerate() {
+			for d in plugin.iter().filter(|d| d.in_use == 1) {
+				let mut tags = vec![
+					("plugin".to_string(), i.to_string()),
+					("device".to_string(), d.device_id.clone()),
+					("edge_bits".to_string(), d.cuckoo_size.clone()),
+				];
+				if let Some(uid) = stats.mining_stats.device_uid(i, &d.device_id) {
+					tags.push(("uid".to_string(), uid.to_string()));
+				}
+				if d.last_solution_time > 0 {
+					metrics.push(metric("device", "gps", &tags, stats::device_gps(d)));
+				}
+				metrics.push(metric("device", "errored", &tags, d.has_errored as f64));
+				if let Some(h) = stats.mining_stats.hardware_for(i, &d.device_id) {
+					if let Some(t) = h.temperature {
+						metrics.push(metric("device", "temperature", &tags, t as f64));
+					}
+					if let Some(f) = h.fan_speed {
+						metrics.push(metric("device", "fan_speed", &tags, f as f64));
+					}
+					if let Some(p) = h.power_usage {
+						metrics.push(metric("device", "power_usage", &tags, p));
+					}
+					if let Some(c) = h.core_clock {
+						metrics.push(metric("device", "core_clock", &tags, c as f64));
+					}
+					if let Some(c) = h.memory_clock {
+						metrics.push(metric("device", "memory_clock", &tags, c as f64));
+					}
+				}
+			}
+		}
+	}
+	metrics
+}
+
+/// Escape commas, spaces and equal signs in influx tag keys and values
+fn influx_escape(s: &str) -> String {
+	s.replace(",", "\\,").replace(" ", "\\ ").replace("=", "\\=")
+}
+
+/// Graphite tag values can't contain spaces or semicolons
+fn graphite_escape(s: &str) -> String {
+	s.replace(" ", "_").replace(";", "_")
+}
+
+/// Influx line protocol, one line per metric, timestamp in seconds
+fn influx_lines(metrics: &[Metric], global_tags: &[(String, String)], timestamp: i64) -> String {
+	let mut lines = String::new();
+	for m in metrics {
+		let mut line = m.measurement.to_string();
+		for &(ref k, ref v) in global_tags.iter().chain(m.tags.iter()) {
+			line.push_str(&format!(",{}={}", influx_escape(k), influx_escape(v)));
+		}
+		line.push_str(&format!(" {}={} {}\n", m.field, m.value, timestamp));
+		lines.push_str(&line);
+	}
+	lines
+}
+
+/// Graphite plaintext protocol, with graphite 1.1 tags
+fn graphite_lines(
+	metrics: &[Metric],
+	prefix: &str,
+	global_tags: &[(String, String)],
+	timestamp: i64,
+) -> String {
+	let mut lines = String::new();
+	for m in metrics {
+		let mut line = format!("{}.{}.{}", prefix, m.measurement, m.field);
+		for &(ref k, ref v) in global_tags.iter().chain(m.tags.iter()) {
+			line.push_str(&format!(";{}={}", graphite_escape(k), graphite_escape(v)));
+		}
+		line.push_str(&format!(" {} {}\n", m.value, timestamp));
+		lines.push_str(&line);
+	}
+	lines
+}
+
+/// Pushes metrics to the configured time series database
+pub struct Exporter {
+	config: TelemetryConfig,
+	stats: Arc<RwLock<Stats>>,
+}
+
+impl Exporter {
+	/// Create a new exporter
+	pub fn new(config: TelemetryConfig, stats: Arc<RwLock<Stats>>) -> Exporter {
+		Exporter {
+			config: config,
+			stats: stats,
+		}
+	}
+
+	/// Start pushing metrics in a new thread
+	pub fn start(self) {
+		info!(
+			LOGGER,
+			"Pushing {} telemetry every {} seconds", self.config.backend, self.config.interval_secs
+		);
+		let _ = thread::Builder::new()
+			.name("telemetry".to_string())
+			.spawn(move || loop {
+				thread::sleep(Duration::from_secs(self.config.interval_secs.max(1)));
+				if let Err(e) = self.push() {
+					warn!(LOGGER, "Unable to push {} telemetry: {}", self.config.backend, e);
+				}
+			});
+	}
+
+	fn push(&self) -> Result<(), String> {
+		let metrics = {
+			let stats = self.stats.read().unwrap();
+			metrics(&stats)
+		};
+		let mut tags: Vec<(String, String)> = self.config
+			.tags
+			.iter()
+			.map(|(k, v)| (k.clone(), v.clone()))
+			.collect();
+		tags.sort();
+		let timestamp = time::get_time().sec;
+		match self.config.backend.as_str() {
+			"influxdb" => {
+				let url = if self.config.url.contains("precision=") {
+					self.config.url.clone()
+				} else if self.config.url.contains('?') {
+					format!("{}&precision=s", self.config.url)
+				} else {
+					format!("{}?precision=s", self.config.url)
+				};
+				let body = influx_lines(&metrics, &tags, timestamp);
+				match http::post(&url, "text/plain", &body)? {
+					c if c < 300 => Ok(()),
+					c => Err(format!("status {}", c)),
+				}
+			}
+			"graphite" => {
+				let body = graphite_lines(&metrics, &self.config.prefix, &tags, timestamp);
+				let mut stream = TcpStream::connect(self.config.url.as_str())
+					.map_err(|e| format!("{}", e))?;
+				stream
+					.write_all(body.as_bytes())
+					.map_err(|e| format!("{}", e))
+			}
+			b => Err(format!("unknown backend {}", b)),
+		}
+	}
+}
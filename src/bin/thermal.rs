@@ -0,0 +1,249 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Thermal protection, throttling devices above their soft temperature
+//! limit by mining only part of the time, and pausing them above their
+//! hard limit until they cool down
+
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use clock;
+use config::ThermalConfig;
+use stats::{Stats, ThermalStatus};
+use types::MinerMessage;
+use util::{self, LOGGER};
+
+/// Seconds between temperature checks, adjusting duty cycles
+const CHECK_INTERVAL_SECS: i64 = 5;
+
+/// Duty cycle change (percent) at each check
+const DUTY_STEP: u32 = 10;
+
+/// Degrees below the soft limit a device needs to be at before its duty
+/// cycle goes back up
+const HYSTERESIS: u32 = 3;
+
+/// Limits applying to a device
+struct Limits {
+	soft: u32,
+	hard: u32,
+	resume: u32,
+}
+
+/// Protection state of a device
+struct Device {
+	plugin: usize,
+	device_id: String,
+	duty_cycle: u32,
+	overheated: bool,
+	/// Whether the device is above its soft limit
+	hot: bool,
+	/// Whether the device is paused for its duty cycle
+	duty_paused: bool,
+	events: u64,
+}
+
+/// Throttles and pauses devices from their temperature readings
+pub struct ThermalGuard {
+	config: ThermalConfig,
+	stats: Arc<RwLock<Stats>>,
+	miner_tx: mpsc::Sender<MinerMessage>,
+}
+
+impl ThermalGuard {
+	/// Create a new thermal guard
+	pub fn new(
+		config: ThermalConfig,
+		stats: Arc<RwLock<Stats>>,
+		miner_tx: mpsc::Sender<MinerMessage>,
+	) -> ThermalGuard {
+		ThermalGuard {
+			config: config,
+			stats: stats,
+			miner_tx: miner_tx,
+		}
+	}
+
+	/// Start watching temperatures in a new thread
+	pub fn start(self) {
+		info!(
+			LOGGER,
+			"Thermal protection: throttling above {}C, pausing above {}C until {}C",
+			self.config.soft_limit,
+			self.config.hard_limit,
+			self.config.resume_temp
+		);
+		let _ = thread::Builder::new()
+			.name("thermal".to_string())
+			.spawn(move || {
+				let mut devices: Vec<Device> = vec![];
+				let mut next_check = 0;
+				loop {
+					let now = clock::mono_secs();
+					if now >= next_check {
+						self.check(&mut devices);
+						next_check = now + CHECK_INTERVAL_SECS;
+					}
+					self.apply_duty_cycles(&mut devices, now);
+					thread::sleep(Duration::from_secs(1));
+				}
+			});
+	}
+
+	/// Limits of the given device, its own or the defaults
+	fn limits(&self, plugin: usize, device_id: &str) -> Limits {
+		let own = self
+			.config
+			.devices
+			.as_ref()
+			.and_then(|d| d.get(&format!("{}:{}", plugin, device_id)));
+		Limits {
+			soft: own.and_then(|l| l.soft_limit).unwrap_or(self.config.soft_limit),
+			hard: own.and_then(|l| l.hard_limit).unwrap_or(self.config.hard_limit),
+			resume: own.and_then(|l| l.resume_temp).unwrap_or(self.config.resume_temp),
+		}
+	}
+
+	fn send(&self, device: &Device, pause: bool) {
+		let id = match device.device_id.parse::<u32>() {
+			Ok(id) => id,
+			Err(_) => return,
+		};
+		let message = if pause {
+			MinerMessage::ThermalPause(device.plugin, id)
+		} else {
+			MinerMessage::ThermalResume(device.plugin, id)
+		};
+		let _ = self.miner_tx.send(message);
+	}
+
+	/// Compare the latest readings to the limits, pausing, resuming and
+	/// adjusting duty cycles
+	fn check(&self, devices: &mut Vec<Device>) {
+		let readings: Vec<(usize, String, u32)> = {
+			let stats = self.stats.read().unwrap();
+			stats
+				.mining_stats
+				.hardware_stats
+				.iter()
+				.filter_map(|h| h.temperature.map(|t| (h.plugin, h.device_id.clone(), t)))
+				.collect()
+		};
+		let mut new_events = 0;
+		for (plugin, device_id, temp) in readings {
+			let limits = self.limits(plugin, &device_id);
+			let pos = match devices
+				.iter()
+				.position(|d| d.plugin == plugin && d.device_id == device_id)
+			{
+				Some(p) => p,
+				None => {
+					devices.push(Device {
+						plugin: plugin,
+						device_id: device_id.clone(),
+						duty_cycle: 100,
+						overheated: false,
+						hot: false,
+						duty_paused: false,
+						events: 0,
+					});
+					devices.len() - 1
+				}
+			};
+			let log = util::device_logger(plugin, &device_id);
+			if temp >= limits.hard {
+				if !devices[pos].overheated {
+					error!(
+						log,
+						"Plugin {} device {} at {}C, above its hard limit of {}C, pausing it",
+						plugin, device_id, temp, limits.hard
+					);
+					devices[pos].overheated = true;
+					devices[pos].events += 1;
+					new_events += 1;
+					if !devices[pos].duty_paused {
+						self.send(&devices[pos], true);
+					}
+				}
+				continue;
+			}
+			if devices[pos].overheated {
+				if temp > limits.resume {
+					continue;
+				}
+				warn!(
+					log,
+					"Plugin {} device {} cooled down to {}C, resuming it", plugin, device_id, temp
+				);
+				devices[pos].overheated = false;
+				devices[pos].duty_paused = false;
+				self.send(&devices[pos], false);
+			}
+			let device = &mut devices[pos];
+			if temp >= limits.soft {
+				if !device.hot {
+					warn!(
+						log,
+						"Plugin {} device {} at {}C, above its soft limit of {}C, throttling it",
+						plugin, device_id, temp, limits.soft
+					);
+					device.hot = true;
+					device.events += 1;
+					new_events += 1;
+				}
+				device.duty_cycle = device
+					.duty_cycle
+					.saturating_sub(DUTY_STEP)
+					.max(self.config.min_duty_cycle.min(100));
+			} else if temp + HYSTERESIS < limits.soft {
+				if device.hot {
+					info!(
+						log,
+						"Plugin {} device {} back down to {}C", plugin, device_id, temp
+					);
+					device.hot = false;
+				}
+				device.duty_cycle = (device.duty_cycle + DUTY_STEP).min(100);
+			}
+		}
+		let mut stats = self.stats.write().unwrap();
+		stats.mining_stats.thermal_events += new_events;
+		stats.mining_stats.thermal = devices
+			.iter()
+			.map(|d| ThermalStatus {
+				plugin: d.plugin,
+				device_id: d.device_id.clone(),
+				duty_cycle: d.duty_cycle,
+				overheated: d.overheated,
+				events: d.events,
+			})
+			.collect();
+	}
+
+	/// Pause and resume throttled devices so they mine only the first
+	/// duty cycle percent of each period
+	fn apply_duty_cycles(&self, devices: &mut Vec<Device>, now: i64) {
+		let period = self.config.duty_period_secs.max(1) as i64;
+		for device in devices.iter_mut().filter(|d| !d.overheated) {
+			let run = device.duty_cycle >= 100
+				|| (now % period) * 100 < period * device.duty_cycle as i64;
+			if run == device.duty_paused {
+				device.duty_paused = !run;
+				self.send(device, !run);
+			}
+		}
+	}
+}
@@ -0,0 +1,150 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Submit rate limiting. Some pools ban clients for a while when they
+//! submit too fast, typically right after errors. Submits over the
+//! configured rate are held back and sent as the rate allows, and all of
+//! them are held for a cool-down when the pool answers with a ban or too
+//! many requests error.
+
+use std::collections::VecDeque;
+
+use serde_json::Value;
+
+use clock;
+use config::SubmitLimitConfig;
+use stats::ThrottleStats;
+
+/// A share waiting to be submitted
+pub struct Submit {
+	pub height: u64,
+	pub job_id: u64,
+	pub edge_bits: u32,
+	pub nonce: u64,
+	pub pow: Vec<u32>,
+}
+
+/// Holds submits back to the pool's rate
+pub struct SubmitLimiter {
+	config: SubmitLimitConfig,
+	/// Submits a minute allowed by the current pool
+	cap: Option<u32>,
+	/// When the submits of the last minute were sent, in ms
+	sent: VecDeque<i64>,
+	queue: VecDeque<Submit>,
+	cooldown_until: Option<i64>,
+	stats: ThrottleStats,
+}
+
+/// Whether a submit error is the pool banning or throttling us
+pub fn is_ban_error(error: &Value) -> bool {
+	if error["code"].as_i64() == Some(429) {
+		return true;
+	}
+	let message = match error["message"].as_str() {
+		Some(m) => m.to_lowercase(),
+		None => error.to_string().to_lowercase(),
+	};
+	["ban", "too many", "rate limit", "too fast"]
+		.iter()
+		.any(|m| message.contains(m))
+}
+
+impl SubmitLimiter {
+	/// Create a limiter for the pool at the address
+	pub fn new(config: SubmitLimitConfig, pool: &str) -> SubmitLimiter {
+		let mut limiter = SubmitLimiter {
+			config: config,
+			cap: None,
+			sent: VecDeque::new(),
+			queue: VecDeque::new(),
+			cooldown_until: None,
+			stats: ThrottleStats::default(),
+		};
+		limiter.set_pool(pool);
+		limiter
+	}
+
+	/// Switch to the caps of another pool, forgetting the cool-down of the
+	/// previous one
+	pub fn set_pool(&mut self, pool: &str) {
+		self.cap = self
+			.config
+			.pools
+			.get(pool)
+			.cloned()
+			.or(self.config.max_per_minute);
+		self.cooldown_until = None;
+		self.sent.clear();
+	}
+
+	/// Whether a submit can be sent now
+	fn allowed(&mut self, now: i64) -> bool {
+		match self.cooldown_until {
+			Some(t) if t > now => return false,
+			Some(_) => self.cooldown_until = None,
+			None => {}
+		}
+		while self.sent.front().map_or(false, |t| *t <= now - 60_000) {
+			self.sent.pop_front();
+		}
+		self.cap.map_or(true, |cap| self.sent.len() < cap as usize)
+	}
+
+	/// Queue a share to submit
+	pub fn push(&mut self, submit: Submit) {
+		if !self.queue.is_empty() || !self.allowed(clock::mono_ms()) {
+			self.stats.delayed += 1;
+		}
+		if self.queue.len() >= self.config.max_queued.max(1) {
+			self.queue.pop_front();
+			self.stats.dropped += 1;
+		}
+		self.queue.push_back(submit);
+	}
+
+	/// Drop the shares queued for heights before the new one, the pool
+	/// would reject them as stale
+	pub fn new_height(&mut self, height: u64) {
+		let queued = self.queue.len();
+		self.queue.retain(|s| s.height >= height);
+		self.stats.dropped += (queued - self.queue.len()) as u64;
+	}
+
+	/// Hold all submits for the cool-down, the pool having banned us
+	pub fn cool_down(&mut self) {
+		self.cooldown_until = Some(clock::mono_ms() + self.config.cooldown_secs as i64 * 1000);
+		self.stats.cooldowns += 1;
+	}
+
+	/// The next share to submit now, if any is allowed
+	pub fn next(&mut self) -> Option<Submit> {
+		let now = clock::mono_ms();
+		if self.queue.is_empty() || !self.allowed(now) {
+			return None;
+		}
+		self.sent.push_back(now);
+		self.queue.pop_front()
+	}
+
+	/// Current state, for the client stats
+	pub fn stats(&self) -> ThrottleStats {
+		let cooling_down = self.cooldown_until.map_or(false, |t| t > clock::mono_ms());
+		let mut stats = self.stats.clone();
+		stats.throttled = !self.queue.is_empty() || cooling_down;
+		stats.queued = self.queue.len() as u64;
+		stats.cooldown_until = self.cooldown_until.map(|t| clock::to_wall(t / 1000));
+		stats
+	}
+}
@@ -24,7 +24,39 @@ pub const TABLE_MINING_STATUS: &str = "mining_status_table";
 /// Version view
 pub const VIEW_VERSION: &str = "version_view";
 
+// Logging View
+/// Logging view
+pub const VIEW_LOGGING: &str = "logging_view";
+/// Current log level text
+pub const TEXT_LOG_LEVEL: &str = "log_level_text";
+
+// Stratum Messages View
+/// Stratum messages view
+pub const VIEW_MESSAGES: &str = "messages_view";
+/// Stratum message history list
+pub const SELECT_MESSAGES: &str = "messages_select";
+/// Selected stratum message detail
+pub const TEXT_MESSAGE_DETAIL: &str = "message_detail_text";
+
+// History View
+/// History view
+pub const VIEW_HISTORY: &str = "history_view";
+/// History text
+pub const TEXT_HISTORY: &str = "history_text";
+
+/// Fleet view
+pub const VIEW_FLEET: &str = "fleet_view";
+/// Fleet rig table
+pub const TABLE_FLEET: &str = "fleet_table";
+/// Fleet totals text
+pub const TEXT_FLEET_TOTAL: &str = "fleet_total_text";
+
+/// Default number of milliseconds between TUI refreshes
+pub const DEFAULT_REFRESH_INTERVAL: u64 = 1000;
+
 // Menu and root elements
+/// Title bar text
+pub const TEXT_TITLE: &str = "title_text";
 /// Main menu
 pub const MAIN_MENU: &str = "main_menu";
 /// root stack
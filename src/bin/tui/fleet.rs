@@ -0,0 +1,134 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fleet view, summarizing this rig and the remote rigs configured in
+//! [fleet], one per row
+
+use std::cmp::Ordering;
+
+use cursive::Cursive;
+use cursive::direction::Orientation;
+use cursive::view::View;
+use cursive::views::{BoxView, LinearLayout, TextView};
+use cursive::traits::*;
+
+use tui::constants::*;
+use tui::table::{TableView, TableViewItem};
+use tui::types::*;
+
+use i18n;
+use stats::{FleetRig, Stats};
+use units;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum FleetColumn {
+	Name,
+	Status,
+	Gps,
+	Devices,
+	RejectRate,
+	LastShare,
+}
+
+impl TableViewItem<FleetColumn> for FleetRig {
+	fn to_column(&self, column: FleetColumn) -> String {
+		match column {
+			FleetColumn::Name => self.name.clone(),
+			FleetColumn::Status => match self.error {
+				Some(ref e) => i18n::tf("fleet.unreachable", &[e]),
+				None if self.paused => i18n::t("fleet.paused"),
+				None if self.connected => i18n::t("fleet.mining"),
+				None => i18n::t("fleet.disconnected"),
+			},
+			FleetColumn::Gps => units::gps_value(self.combined_gps),
+			FleetColumn::Devices => {
+				i18n::tf("fleet.devices_up_down", &[&self.devices_up, &self.devices_down])
+			}
+			FleetColumn::RejectRate => format!("{}%", units::decimal(self.reject_rate, 1)),
+			FleetColumn::LastShare => match self.last_share {
+				Some(t) => units::clock(t),
+				None => String::from("-"),
+			},
+		}
+	}
+
+	fn cmp(&self, other: &Self, column: FleetColumn) -> Ordering
+	where
+		Self: Sized,
+	{
+		match column {
+			FleetColumn::Name => self.name.cmp(&other.name),
+			FleetColumn::Status => self.error.is_some().cmp(&other.error.is_some()),
+			FleetColumn::Gps => self
+				.combined_gps
+				.partial_cmp(&other.combined_gps)
+				.unwrap_or(Ordering::Equal),
+			FleetColumn::Devices => self.devices_down.cmp(&other.devices_down),
+			FleetColumn::RejectRate => self
+				.reject_rate
+				.partial_cmp(&other.reject_rate)
+				.unwrap_or(Ordering::Equal),
+			FleetColumn::LastShare => self.last_share.cmp(&other.last_share),
+		}
+	}
+}
+
+/// Fleet view
+pub struct TUIFleetView;
+
+impl TUIStatusListener for TUIFleetView {
+	/// Create the fleet view
+	fn create() -> Box<View> {
+		let table_view = TableView::<FleetRig, FleetColumn>::new()
+			.column(FleetColumn::Name, i18n::t("fleet.column_rig"), |c| c.width_percent(20))
+			.column(FleetColumn::Status, i18n::t("fleet.column_status"), |c| c.width_percent(25))
+			.column(FleetColumn::Gps, i18n::t("fleet.column_gps"), |c| c.width_percent(10))
+			.column(FleetColumn::Devices, i18n::t("fleet.column_devices"), |c| c.width_percent(15))
+			.column(FleetColumn::RejectRate, i18n::t("fleet.column_rejected"), |c| {
+				c.width_percent(10)
+			})
+			.column(FleetColumn::LastShare, i18n::t("fleet.column_last_share"), |c| {
+				c.width_percent(20)
+			});
+
+		let fleet_view = LinearLayout::new(Orientation::Vertical)
+			.child(TextView::new("").with_id(TEXT_FLEET_TOTAL))
+			.child(BoxView::with_full_screen(table_view.with_id(TABLE_FLEET)));
+		Box::new(fleet_view.with_id(VIEW_FLEET))
+	}
+
+	/// update
+	fn update(c: &mut Cursive, stats: &Stats) {
+		let name = stats.rig.name.clone().unwrap_or(i18n::t("fleet.this_rig"));
+		let mut rigs = vec![FleetRig::summarize(&name, "local", stats)];
+		rigs.extend(stats.fleet.iter().cloned());
+		let reachable: Vec<&FleetRig> = rigs.iter().filter(|r| r.error.is_none()).collect();
+		let total = i18n::tf(
+			"fleet.total",
+			&[
+				&rigs.len(),
+				&(rigs.len() - reachable.len()),
+				&units::gps(reachable.iter().map(|r| r.combined_gps).sum()),
+				&reachable.iter().map(|r| r.devices_up).sum::<usize>(),
+				&reachable.iter().map(|r| r.devices_down).sum::<usize>(),
+			],
+		);
+		c.call_on_id(TEXT_FLEET_TOTAL, |t: &mut TextView| {
+			t.set_content(total);
+		});
+		let _ = c.call_on_id(TABLE_FLEET, |t: &mut TableView<FleetRig, FleetColumn>| {
+			t.set_items(rigs);
+		});
+	}
+}
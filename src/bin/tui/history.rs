@@ -0,0 +1,111 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! History view, showing uptime, solver restarts, connection drops and
+//! the most recent errors
+
+use cursive::Cursive;
+use cursive::view::View;
+use cursive::views::{BoxView, TextView};
+use cursive::traits::*;
+
+use tui::constants::*;
+use tui::types::*;
+
+use i18n;
+use stats::{NetworkEventKind, Stats};
+use units;
+
+fn format_time(secs: i64) -> String {
+	units::date_time(secs)
+}
+
+fn history_text(stats: &Stats) -> String {
+	let uptime = stats.session_stats.uptime(stats.clock_now());
+	let history = &stats.history;
+	let mut lines = vec![
+		i18n::tf("history.started", &[&format_time(stats.session_stats.start_time)]),
+		i18n::tf(
+			"history.uptime",
+			&[&(uptime / 3600), &((uptime % 3600) / 60), &(uptime % 60)],
+		),
+		String::new(),
+		i18n::tf("history.scheduled_restarts", &[&history.scheduled_restarts]),
+		i18n::tf("history.watchdog_triggers", &[&history.watchdog_triggers]),
+		String::new(),
+		i18n::tf("history.restarts", &[&history.restarts.len()]),
+	];
+	for r in history.restarts.iter().rev() {
+		let device = match r.device {
+			Some((ref p, ref d)) => i18n::tf("history.device", &[p, d]),
+			None => i18n::t("history.all_devices"),
+		};
+		lines.push(format!("  {} {}: {}", format_time(r.time), device, r.reason));
+	}
+	lines.push(String::new());
+	lines.push(i18n::tf("history.connection_drops", &[&history.connection_drops.len()]));
+	for d in history.connection_drops.iter().rev() {
+		let duration = match d.duration {
+			Some(s) => i18n::tf("history.down_for", &[&s]),
+			None => i18n::t("history.still_down"),
+		};
+		lines.push(format!("  {} {} ({})", format_time(d.time), d.reason, duration));
+	}
+	lines.push(String::new());
+	lines.push(i18n::tf("history.network_events", &[&history.network_events.len()]));
+	for e in history.network_events.iter().rev() {
+		let what = match e.kind {
+			NetworkEventKind::Height => i18n::t("history.height"),
+			NetworkEventKind::Difficulty => match e.edge_bits {
+				Some(b) => i18n::tf("history.difficulty_size", &[&b]),
+				None => i18n::t("history.difficulty"),
+			},
+		};
+		let change = match e.from {
+			Some(f) => format!("{} -> {}", units::number(f), units::number(e.to)),
+			None => units::number(e.to),
+		};
+		lines.push(format!("  {} {}: {}", format_time(e.time), what, change));
+	}
+	lines.push(String::new());
+	lines.push(i18n::tf("history.suspends", &[&history.suspends.len()]));
+	for s in history.suspends.iter().rev() {
+		lines.push(i18n::tf("history.suspended_for", &[&format_time(s.time), &s.duration]));
+	}
+	lines.push(String::new());
+	lines.push(i18n::tf("history.errors", &[&history.errors.len()]));
+	for e in history.errors.iter().rev() {
+		lines.push(format!("  {} {}", format_time(e.time), e.message));
+	}
+	lines.join("\n")
+}
+
+/// History view
+pub struct TUIHistoryView;
+
+impl TUIStatusListener for TUIHistoryView {
+	/// Create the history view
+	fn create() -> Box<View> {
+		let history_view = BoxView::with_full_screen(TextView::new("").with_id(TEXT_HISTORY));
+		Box::new(history_view.with_id(VIEW_HISTORY))
+	}
+
+	/// update
+	fn update(c: &mut Cursive, stats: &Stats) {
+		let text = history_text(stats);
+		c.call_on_id(TEXT_HISTORY, |t: &mut TextView| {
+			t.set_content(text);
+		});
+	}
+}
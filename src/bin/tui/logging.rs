@@ -0,0 +1,103 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log level view, to change the active log level at runtime
+
+use cursive::Cursive;
+use cursive::view::View;
+use cursive::views::{BoxView, LinearLayout, SelectView, TextView};
+use cursive::direction::Orientation;
+use cursive::traits::*;
+
+use tui::constants::*;
+use tui::types::*;
+
+use i18n;
+use stats::Stats;
+use util::{self, LogLevel, LOGGER};
+
+const LOG_LEVELS: [LogLevel; 5] = [
+	LogLevel::Error,
+	LogLevel::Warning,
+	LogLevel::Info,
+	LogLevel::Debug,
+	LogLevel::Trace,
+];
+
+fn level_name(level: LogLevel) -> &'static str {
+	match level {
+		LogLevel::Critical => "Critical",
+		LogLevel::Error => "Error",
+		LogLevel::Warning => "Warning",
+		LogLevel::Info => "Info",
+		LogLevel::Debug => "Debug",
+		LogLevel::Trace => "Trace",
+	}
+}
+
+/// Name of the level as shown, in the configured language
+fn level_label(level: LogLevel) -> String {
+	i18n::t(&format!("logging.level_{}", level_name(level).to_lowercase()))
+}
+
+fn set_level(c: &mut Cursive, level: LogLevel) {
+	util::set_log_level(level);
+	warn!(LOGGER, "Log level changed to {}", level_name(level));
+	c.call_on_id(TEXT_LOG_LEVEL, |t: &mut TextView| {
+		t.set_content(i18n::tf("logging.current", &[&level_label(level)]));
+	});
+}
+
+/// Log level view
+pub struct TUILoggingView;
+
+impl TUILoggingView {
+	/// Switch to the next (more verbose) log level, wrapping around
+	/// to the least verbose one
+	pub fn cycle_level(c: &mut Cursive) {
+		let current = util::log_level();
+		let pos = LOG_LEVELS
+			.iter()
+			.position(|l| *l == current)
+			.unwrap_or(0);
+		let next = LOG_LEVELS[(pos + 1) % LOG_LEVELS.len()];
+		set_level(c, next);
+	}
+}
+
+impl TUIStatusListener for TUILoggingView {
+	/// Create the log level view
+	fn create() -> Box<View> {
+		let mut level_select = SelectView::new();
+		for &level in LOG_LEVELS.iter() {
+			level_select.add_item(level_label(level), level);
+		}
+		level_select.set_on_submit(|c, level: &LogLevel| set_level(c, *level));
+
+		let logging_view = BoxView::with_full_screen(
+			LinearLayout::new(Orientation::Vertical)
+				.child(
+					TextView::new(i18n::tf("logging.current", &[&level_label(util::log_level())]))
+						.with_id(TEXT_LOG_LEVEL),
+				)
+				.child(TextView::new(" "))
+				.child(TextView::new(i18n::t("logging.help")))
+				.child(level_select),
+		);
+		Box::new(logging_view.with_id(VIEW_LOGGING))
+	}
+
+	/// update
+	fn update(_c: &mut Cursive, _stats: &Stats) {}
+}
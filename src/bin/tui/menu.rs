@@ -22,13 +22,18 @@ use cursive::view::Identifiable;
 use cursive::views::{BoxView, LinearLayout, OnEventView, SelectView, StackView, TextView, ViewRef};
 use cursive::direction::Orientation;
 
+use i18n;
 use tui::constants::*;
 
 /// Create menu
 pub fn create() -> Box<View> {
 	let mut main_menu = SelectView::new().h_align(HAlign::Left).with_id(MAIN_MENU);
-	main_menu.get_mut().add_item("Mining", VIEW_MINING);
-	main_menu.get_mut().add_item("Version Info", VIEW_VERSION);
+	main_menu.get_mut().add_item(i18n::t("menu.mining"), VIEW_MINING);
+	main_menu.get_mut().add_item(i18n::t("menu.fleet"), VIEW_FLEET);
+	main_menu.get_mut().add_item(i18n::t("menu.history"), VIEW_HISTORY);
+	main_menu.get_mut().add_item(i18n::t("menu.messages"), VIEW_MESSAGES);
+	main_menu.get_mut().add_item(i18n::t("menu.logging"), VIEW_LOGGING);
+	main_menu.get_mut().add_item(i18n::t("menu.version"), VIEW_VERSION);
 	let change_view = |s: &mut Cursive, v: &&str| {
 		if *v == "" {
 			return;
@@ -64,8 +69,12 @@ pub fn create() -> Box<View> {
 	let main_menu = LinearLayout::new(Orientation::Vertical)
 		.child(BoxView::with_full_height(main_menu))
 		.child(TextView::new("------------------"))
-		.child(TextView::new("Tab/Arrow : Cycle "))
-		.child(TextView::new("Enter     : Select"))
-		.child(TextView::new("Q         : Quit  "));
+		.child(TextView::new(i18n::t("menu.keys_cycle")))
+		.child(TextView::new(i18n::t("menu.keys_select")))
+		.child(TextView::new(i18n::t("menu.keys_sort")))
+		.child(TextView::new(i18n::t("menu.keys_clear_alerts")))
+		.child(TextView::new(i18n::t("menu.keys_log_level")))
+		.child(TextView::new(i18n::t("menu.keys_profile")))
+		.child(TextView::new(i18n::t("menu.keys_quit")));
 	Box::new(main_menu)
 }
@@ -0,0 +1,113 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stratum message inspector, showing the messages exchanged with the
+//! stratum server in full
+
+use cursive::Cursive;
+use cursive::view::View;
+use cursive::views::{BoxView, Dialog, LinearLayout, SelectView, TextView};
+use cursive::direction::Orientation;
+use cursive::traits::*;
+
+use serde_json;
+
+use tui::constants::*;
+use tui::types::*;
+
+use i18n;
+use stats::{self, StratumMessage};
+use units;
+
+/// Pretty-print a message if it's valid JSON, otherwise show it as is
+fn pretty_message(message: &str) -> String {
+	serde_json::from_str::<serde_json::Value>(message)
+		.and_then(|v| serde_json::to_string_pretty(&v))
+		.unwrap_or(message.to_string())
+}
+
+fn message_label(m: &StratumMessage) -> String {
+	let method = serde_json::from_str::<serde_json::Value>(&m.message)
+		.ok()
+		.and_then(|v| v.get("method").and_then(|m| m.as_str()).map(|m| m.to_string()))
+		.unwrap_or("response".to_string());
+	let timestamp = units::clock(m.time);
+	format!(
+		"{} {} {}",
+		timestamp,
+		if m.sent { "->" } else { "<-" },
+		method
+	)
+}
+
+fn message_detail(m: &StratumMessage) -> String {
+	let key = if m.sent { "messages.sent" } else { "messages.received" };
+	i18n::tf(key, &[&units::date_time(m.time), &pretty_message(&m.message)])
+}
+
+/// Stratum messages view
+pub struct TUIMessagesView;
+
+impl TUIStatusListener for TUIMessagesView {
+	/// Create the stratum messages view
+	fn create() -> Box<View> {
+		let mut message_select: SelectView<String> = SelectView::new();
+		message_select.set_on_select(|c, detail: &String| {
+			let detail = detail.clone();
+			c.call_on_id(TEXT_MESSAGE_DETAIL, |t: &mut TextView| {
+				t.set_content(detail);
+			});
+		});
+
+		let messages_view = BoxView::with_full_screen(
+			LinearLayout::new(Orientation::Horizontal)
+				.child(
+					Dialog::around(message_select.with_id(SELECT_MESSAGES).min_width(30))
+						.title(i18n::t("messages.history_title")),
+				)
+				.child(BoxView::with_full_screen(
+					Dialog::around(
+						TextView::new(i18n::t("messages.none")).with_id(TEXT_MESSAGE_DETAIL),
+					).title(i18n::t("messages.message_title")),
+				)),
+		);
+		Box::new(messages_view.with_id(VIEW_MESSAGES))
+	}
+
+	/// update
+	fn update(c: &mut Cursive, stats: &stats::Stats) {
+		let trace: Vec<&StratumMessage> = stats.client_stats.message_trace.iter().rev().collect();
+		if trace.is_empty() {
+			return;
+		}
+		// Keep the selected position, so the newest message stays selected
+		// unless the user is looking through the history
+		let selected = c
+			.call_on_id(SELECT_MESSAGES, |s: &mut SelectView<String>| {
+				let selected = s.selected_id().unwrap_or(0);
+				s.clear();
+				for m in &trace {
+					s.add_item(message_label(m), message_detail(m));
+				}
+				let selected = if selected < trace.len() { selected } else { 0 };
+				let _ = s.set_selection(selected);
+				selected
+			})
+			.unwrap_or(0);
+		let detail = message_detail(trace[selected]);
+		c.call_on_id(TEXT_MESSAGE_DETAIL, |t: &mut TextView| {
+			t.set_content(detail);
+		});
+	}
+}
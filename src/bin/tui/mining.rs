@@ -15,9 +15,12 @@
 //! Mining status view definition
 
 use std::cmp::Ordering;
-use std::sync::{Arc, RwLock};
+use std::collections::HashSet;
+use std::sync::{Mutex, RwLock};
 
 use cursive::Cursive;
+use cursive::theme::Color;
+use cursive::utils::markup::StyledString;
 use cursive::view::View;
 use cursive::views::{BoxView, Dialog, LinearLayout, StackView,
                      TextView};
@@ -25,9 +28,15 @@ use cursive::direction::Orientation;
 use cursive::traits::*;
 
 use tui::constants::*;
+use tui::theme::{self, Status};
 use tui::types::*;
 
+use alerts::{Alert, AlertKind};
+use config::MinerConfig;
+use i18n;
 use stats;
+use stats::{DeviceFairness, DeviceHardwareStats, DeviceQuarantine, GpsAverages};
+use units;
 use util::cuckoo_miner::CuckooMinerDeviceStats;
 use tui::table::{TableView, TableViewItem};
 
@@ -41,6 +50,11 @@ enum MiningDeviceColumn {
 	ErrorStatus,
 	LastGraphTime,
 	GraphsPerSecond,
+	AverageGraphsPerSecond,
+	Temperature,
+	FanSpeed,
+	PowerUsage,
+	ShareFairness,
 }
 
 impl MiningDeviceColumn {
@@ -54,32 +68,168 @@ impl MiningDeviceColumn {
 			MiningDeviceColumn::ErrorStatus => "Status",
 			MiningDeviceColumn::LastGraphTime => "Last Graph Time",
 			MiningDeviceColumn::GraphsPerSecond => "GPS",
+			MiningDeviceColumn::AverageGraphsPerSecond => "GPS (5m)",
+			MiningDeviceColumn::Temperature => "Temp",
+			MiningDeviceColumn::FanSpeed => "Fan %",
+			MiningDeviceColumn::PowerUsage => "Power (W)",
+			MiningDeviceColumn::ShareFairness => "Shares/GPS %",
 		}
 	}
 }
 
-impl TableViewItem<MiningDeviceColumn> for CuckooMinerDeviceStats {
+/// Thresholds above which hardware readings are highlighted
+#[derive(Clone, Copy)]
+struct HardwareWarnings {
+	temperature: u32,
+	fan_speed: u32,
+	power_usage: Option<f64>,
+}
+
+lazy_static! {
+	static ref HARDWARE_WARNINGS: RwLock<HardwareWarnings> = RwLock::new(HardwareWarnings {
+		temperature: 80,
+		fan_speed: 90,
+		power_usage: None,
+	});
+}
+
+/// Whether the device table is grouped by plugin, and which groups are
+/// currently collapsed
+#[derive(Default)]
+struct DeviceGrouping {
+	enabled: bool,
+	collapsed: HashSet<usize>,
+}
+
+lazy_static! {
+	static ref DEVICE_GROUPING: RwLock<DeviceGrouping> = RwLock::new(DeviceGrouping::default());
+}
+
+/// Aggregate stats of all devices run by a plugin
+#[derive(Clone)]
+struct GroupSummary {
+	devices: usize,
+	errored: usize,
+	combined_gps: f64,
+	graphs: u64,
+	solutions: u64,
+	collapsed: bool,
+}
+
+/// A row of the mining device table, either a device or, when grouping by
+/// plugin, the aggregate row of a plugin
+#[derive(Clone)]
+struct DeviceRow {
+	plugin: usize,
+	grouped: bool,
+	stats: CuckooMinerDeviceStats,
+	hardware: Option<DeviceHardwareStats>,
+	gps_averages: Option<GpsAverages>,
+	fairness: Option<DeviceFairness>,
+	quarantine: Option<DeviceQuarantine>,
+	summary: Option<GroupSummary>,
+}
+
+fn cmp_option<T: PartialOrd>(a: &Option<T>, b: &Option<T>) -> Ordering {
+	match (a, b) {
+		(&Some(ref a), &Some(ref b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+		(&Some(_), &None) => Ordering::Greater,
+		(&None, &Some(_)) => Ordering::Less,
+		(&None, &None) => Ordering::Equal,
+	}
+}
+
+impl DeviceRow {
+	fn temperature(&self) -> Option<u32> {
+		self.hardware.as_ref().and_then(|h| h.temperature)
+	}
+
+	fn fan_speed(&self) -> Option<u32> {
+		self.hardware.as_ref().and_then(|h| h.fan_speed)
+	}
+
+	fn power_usage(&self) -> Option<f64> {
+		self.hardware.as_ref().and_then(|h| h.power_usage)
+	}
+
+	fn average_gps(&self) -> Option<f64> {
+		self.gps_averages.as_ref().map(|a| a.avg_5m)
+	}
+
+	fn summary_column(&self, summary: &GroupSummary, column: MiningDeviceColumn) -> String {
+		match column {
+			MiningDeviceColumn::Plugin => format!(
+				"{} {}",
+				if summary.collapsed { "▶" } else { "▼" },
+				self.stats.plugin_name.clone().unwrap()
+			),
+			MiningDeviceColumn::DeviceName => i18n::tf("mining.group_devices", &[&summary.devices]),
+			MiningDeviceColumn::EdgeBits => self.stats.cuckoo_size.clone(),
+			MiningDeviceColumn::ErrorStatus => match summary.errored {
+				0 => i18n::t("mining.ok"),
+				n => i18n::tf("mining.group_errored", &[&n]),
+			},
+			MiningDeviceColumn::LastGraphTime => i18n::tf(
+				"mining.group_graphs",
+				&[&summary.graphs, &summary.solutions],
+			),
+			MiningDeviceColumn::GraphsPerSecond => units::gps_value(summary.combined_gps),
+			_ => String::new(),
+		}
+	}
+}
+
+impl TableViewItem<MiningDeviceColumn> for DeviceRow {
 	fn to_column(&self, column: MiningDeviceColumn) -> String {
-		let last_solution_time_secs = self.last_solution_time as f64 / 1000000000.0;
+		if let Some(ref summary) = self.summary {
+			return self.summary_column(summary, column);
+		}
+		let last_solution_time_secs = self.stats.last_solution_time as f64 / 1000000000.0;
 		match column {
-			MiningDeviceColumn::Plugin => self.plugin_name.clone().unwrap(),
-			MiningDeviceColumn::DeviceId => self.device_id.clone(),
-			MiningDeviceColumn::DeviceName => self.device_name.clone(),
-			MiningDeviceColumn::EdgeBits => self.cuckoo_size.clone(),
-			MiningDeviceColumn::InUse => match self.in_use {
-				1 => String::from("Yes"),
-				_ => String::from("No"),
+			MiningDeviceColumn::Plugin => self.stats.plugin_name.clone().unwrap(),
+			MiningDeviceColumn::DeviceId => self.stats.device_id.clone(),
+			MiningDeviceColumn::DeviceName => self.stats.device_name.clone(),
+			MiningDeviceColumn::EdgeBits => self.stats.cuckoo_size.clone(),
+			MiningDeviceColumn::InUse => match self.stats.in_use {
+				1 => i18n::t("mining.yes"),
+				_ => i18n::t("mining.no"),
 			},
-			MiningDeviceColumn::ErrorStatus => match self.has_errored {
-				0 => String::from("OK"),
-				_ => String::from("Errored"),
+			MiningDeviceColumn::ErrorStatus => match (&self.quarantine, self.stats.has_errored) {
+				(&Some(ref q), _) => i18n::tf("mining.quarantined", &[&units::clock(q.until)]),
+				(&None, 0) => i18n::t("mining.ok"),
+				(&None, _) => i18n::t("mining.errored"),
 			},
 			MiningDeviceColumn::LastGraphTime => {
 				String::from(format!("{}s", last_solution_time_secs))
 			}
-			MiningDeviceColumn::GraphsPerSecond => {
-				String::from(format!("{:.*}", 4, 1.0 / last_solution_time_secs))
-			}
+			MiningDeviceColumn::GraphsPerSecond => units::gps_value(stats::device_gps(&self.stats)),
+			MiningDeviceColumn::AverageGraphsPerSecond => match self.average_gps() {
+				Some(g) => units::gps_value(g),
+				None => String::from("-"),
+			},
+			MiningDeviceColumn::Temperature => match self.temperature() {
+				Some(t) => format!("{}C", t),
+				None => String::from("-"),
+			},
+			MiningDeviceColumn::FanSpeed => match self.fan_speed() {
+				Some(f) => format!("{}%", f),
+				None => String::from("-"),
+			},
+			MiningDeviceColumn::PowerUsage => match self.power_usage() {
+				Some(p) => format!("{:.*}", 1, p),
+				None => String::from("-"),
+			},
+			// devices getting far fewer shares than their GPS is worth are
+			// flagged, likely producing corrupt solutions
+			MiningDeviceColumn::ShareFairness => match self.fairness {
+				Some(ref f) => format!(
+					"{}{}/{}%",
+					if f.suspect { "! " } else { "" },
+					f.share_percent.map_or(String::from("-"), |s| units::decimal(s, 0)),
+					units::decimal(f.gps_percent, 0)
+				),
+				None => String::from("-"),
+			},
 		}
 	}
 
@@ -87,71 +237,358 @@ impl TableViewItem<MiningDeviceColumn> for CuckooMinerDeviceStats {
 	where
 		Self: Sized,
 	{
-		let last_solution_time_secs_self = self.last_solution_time as f64 / 1000000000.0;
-		let gps_self = 1.0 / last_solution_time_secs_self;
-		let last_solution_time_secs_other = other.last_solution_time as f64 / 1000000000.0;
-		let gps_other = 1.0 / last_solution_time_secs_other;
+		let gps_self = stats::device_gps(&self.stats);
+		let gps_other = stats::device_gps(&other.stats);
 		match column {
-			MiningDeviceColumn::Plugin => self.plugin_name.cmp(&other.plugin_name),
-			MiningDeviceColumn::DeviceId => self.device_id.cmp(&other.device_id),
-			MiningDeviceColumn::DeviceName => self.device_name.cmp(&other.device_name),
-			MiningDeviceColumn::EdgeBits => self.cuckoo_size.cmp(&other.cuckoo_size),
-			MiningDeviceColumn::InUse => self.in_use.cmp(&other.in_use),
-			MiningDeviceColumn::ErrorStatus => self.has_errored.cmp(&other.has_errored),
+			MiningDeviceColumn::Plugin => self.stats.plugin_name.cmp(&other.stats.plugin_name),
+			MiningDeviceColumn::DeviceId => self.stats.device_id.cmp(&other.stats.device_id),
+			MiningDeviceColumn::DeviceName => self.stats.device_name.cmp(&other.stats.device_name),
+			MiningDeviceColumn::EdgeBits => self.stats.cuckoo_size.cmp(&other.stats.cuckoo_size),
+			MiningDeviceColumn::InUse => self.stats.in_use.cmp(&other.stats.in_use),
+			MiningDeviceColumn::ErrorStatus => self.stats.has_errored.cmp(&other.stats.has_errored),
 			MiningDeviceColumn::LastGraphTime => {
-				self.last_solution_time.cmp(&other.last_solution_time)
+				self.stats.last_solution_time.cmp(&other.stats.last_solution_time)
 			}
 			MiningDeviceColumn::GraphsPerSecond => gps_self.partial_cmp(&gps_other).unwrap(),
+			MiningDeviceColumn::AverageGraphsPerSecond => {
+				cmp_option(&self.average_gps(), &other.average_gps())
+			}
+			MiningDeviceColumn::Temperature => cmp_option(&self.temperature(), &other.temperature()),
+			MiningDeviceColumn::FanSpeed => cmp_option(&self.fan_speed(), &other.fan_speed()),
+			MiningDeviceColumn::PowerUsage => cmp_option(&self.power_usage(), &other.power_usage()),
+			MiningDeviceColumn::ShareFairness => cmp_option(
+				&self.fairness.as_ref().and_then(|f| f.share_percent.map(|s| s - f.gps_percent)),
+				&other.fairness.as_ref().and_then(|f| f.share_percent.map(|s| s - f.gps_percent)),
+			),
+		}
+	}
+
+	fn to_column_color(&self, column: MiningDeviceColumn) -> Option<Color> {
+		let warnings = *HARDWARE_WARNINGS.read().unwrap();
+		let warn_if = |over: bool| {
+			if over {
+				Some(theme::color(Status::Error))
+			} else {
+				None
+			}
+		};
+		if let Some(ref summary) = self.summary {
+			return match column {
+				MiningDeviceColumn::ErrorStatus => match summary.errored {
+					0 => Some(theme::color(Status::Ok)),
+					_ => Some(theme::color(Status::Error)),
+				},
+				_ => None,
+			};
+		}
+		match column {
+			MiningDeviceColumn::InUse => match self.stats.in_use {
+				1 => None,
+				_ => Some(theme::color(Status::Warning)),
+			},
+			MiningDeviceColumn::ErrorStatus => match (&self.quarantine, self.stats.has_errored) {
+				(&Some(_), _) => Some(theme::color(Status::Warning)),
+				(&None, 0) => Some(theme::color(Status::Ok)),
+				(&None, _) => Some(theme::color(Status::Error)),
+			},
+			MiningDeviceColumn::Temperature => {
+				warn_if(self.temperature().map_or(false, |t| t >= warnings.temperature))
+			}
+			MiningDeviceColumn::FanSpeed => {
+				warn_if(self.fan_speed().map_or(false, |f| f >= warnings.fan_speed))
+			}
+			MiningDeviceColumn::PowerUsage => match (self.power_usage(), warnings.power_usage) {
+				(Some(p), Some(w)) => warn_if(p >= w),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+
+	fn group(&self) -> Option<usize> {
+		if self.grouped {
+			Some(self.plugin)
+		} else {
+			None
+		}
+	}
+
+	fn is_group_header(&self) -> bool {
+		self.summary.is_some()
+	}
+}
+
+/// Build the aggregate row of a plugin from its devices
+fn group_row(
+	plugin: usize,
+	devices: &[CuckooMinerDeviceStats],
+	session_stats: &stats::SessionStats,
+	collapsed: bool,
+) -> Option<DeviceRow> {
+	let first = match devices.first() {
+		Some(d) => d.clone(),
+		None => return None,
+	};
+	let in_use: Vec<&CuckooMinerDeviceStats> = devices.iter().filter(|d| d.in_use == 1).collect();
+	let combined_gps: f64 = in_use
+		.iter()
+		.filter(|d| stats::counts_towards_gps(d))
+		.map(|d| stats::device_gps(d))
+		.sum();
+	let graphs: u64 = session_stats
+		.devices
+		.iter()
+		.filter(|d| d.plugin == plugin)
+		.map(|d| d.graphs)
+		.sum();
+	// plugins may share a proof size, so only the group's own devices'
+	let solutions: u64 = session_stats
+		.devices
+		.iter()
+		.filter(|d| d.plugin == plugin)
+		.map(|d| d.solutions)
+		.sum();
+	Some(DeviceRow {
+		plugin: plugin,
+		grouped: true,
+		stats: first,
+		hardware: None,
+		gps_averages: None,
+		fairness: None,
+		quarantine: None,
+		summary: Some(GroupSummary {
+			devices: in_use.len(),
+			errored: in_use.iter().filter(|d| d.has_errored != 0).count(),
+			combined_gps: combined_gps,
+			graphs: graphs,
+			solutions: solutions,
+			collapsed: collapsed,
+		}),
+	})
+}
+
+/// Collapse or expand the group of the plugin whose aggregate row is
+/// submitted in the device table
+fn toggle_group(c: &mut Cursive, index: usize) {
+	let plugin = c.call_on_id(
+		TABLE_MINING_STATUS,
+		|t: &mut TableView<DeviceRow, MiningDeviceColumn>| match t.borrow_item(index) {
+			Some(r) if r.summary.is_some() => Some(r.plugin),
+			_ => None,
+		},
+	);
+	if let Some(Some(p)) = plugin {
+		let mut grouping = DEVICE_GROUPING.write().unwrap();
+		if !grouping.collapsed.remove(&p) {
+			grouping.collapsed.insert(p);
+		}
+	}
+}
+
+/// Alerts currently shown in the banner, and those cleared by the user
+#[derive(Default)]
+struct AlertBannerState {
+	shown: Vec<(AlertKind, i64)>,
+	cleared: Vec<(AlertKind, i64)>,
+}
+
+lazy_static! {
+	static ref ALERT_BANNER: Mutex<AlertBannerState> = Mutex::new(AlertBannerState::default());
+}
+
+fn format_duration(secs: i64) -> String {
+	if secs >= 3600 {
+		format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+	} else if secs >= 60 {
+		format!("{}m {}s", secs / 60, secs % 60)
+	} else {
+		format!("{}s", secs)
+	}
+}
+
+fn update_alert_banner(c: &mut Cursive, alerts: &[Alert]) {
+	let mut state = ALERT_BANNER.lock().unwrap();
+	// forget cleared alerts once their condition is over
+	state
+		.cleared
+		.retain(|k| alerts.iter().any(|a| a.kind == k.0 && a.since == k.1));
+	let visible: Vec<&Alert> = alerts
+		.iter()
+		.filter(|a| !state.cleared.contains(&(a.kind, a.since)))
+		.collect();
+	state.shown = visible.iter().map(|a| (a.kind, a.since)).collect();
+
+	let mut banner = StyledString::new();
+	for (i, a) in visible.iter().enumerate() {
+		if i > 0 {
+			banner.append_plain("\n");
 		}
+		banner.append(theme::styled(
+			i18n::tf("mining.alert", &[&a.message, &format_duration(a.duration())]),
+			Status::Error,
+		));
+	}
+	if !visible.is_empty() {
+		banner.append_plain(i18n::t("mining.alert_clear"));
 	}
+	c.call_on_id("mining_alert_banner", |t: &mut TextView| {
+		t.set_content(banner);
+	});
 }
 
 /// Mining status view
 pub struct TUIMiningView;
 
+impl TUIMiningView {
+	/// Set the thresholds above which temperature, fan speed and power
+	/// readings are highlighted in the device table
+	pub fn set_hardware_warnings(config: &MinerConfig) {
+		let mut warnings = HARDWARE_WARNINGS.write().unwrap();
+		if let Some(t) = config.tui_temp_warning {
+			warnings.temperature = t;
+		}
+		if let Some(f) = config.tui_fan_warning {
+			warnings.fan_speed = f;
+		}
+		warnings.power_usage = config.tui_power_warning;
+	}
+
+	/// Set whether the device table is grouped by plugin, with a
+	/// collapsible aggregate row per plugin
+	pub fn set_grouping(config: &MinerConfig) {
+		DEVICE_GROUPING.write().unwrap().enabled = config.tui_group_by_plugin.unwrap_or(false);
+	}
+
+	/// Clear the alerts currently shown in the banner. They stay cleared
+	/// until their condition ends and occurs again
+	pub fn clear_alerts(c: &mut Cursive) {
+		{
+			let mut state = ALERT_BANNER.lock().unwrap();
+			let shown = state.shown.clone();
+			state.cleared.extend(shown);
+			state.shown.clear();
+		}
+		c.call_on_id("mining_alert_banner", |t: &mut TextView| {
+			t.set_content("");
+		});
+	}
+}
+
 impl TUIStatusListener for TUIMiningView {
 	/// Create the mining view
 	fn create() -> Box<View> {
 
 		let table_view =
-			TableView::<CuckooMinerDeviceStats, MiningDeviceColumn>::new()
-				.column(MiningDeviceColumn::Plugin, "Plugin", |c| {
+			TableView::<DeviceRow, MiningDeviceColumn>::new()
+				.column(MiningDeviceColumn::Plugin, i18n::t("mining.column_plugin"), |c| {
 					c.width_percent(15)
 				})
-				.column(MiningDeviceColumn::DeviceId, "Device ID", |c| {
+				.column(MiningDeviceColumn::DeviceId, i18n::t("mining.column_device_id"), |c| {
 					c.width_percent(10)
 				})
-				.column(MiningDeviceColumn::DeviceName, "Device Name", |c| {
-					c.width_percent(15)
+				.column(MiningDeviceColumn::DeviceName, i18n::t("mining.column_device_name"), |c| {
+					c.width_percent(10)
+				})
+				.column(MiningDeviceColumn::EdgeBits, i18n::t("mining.column_size"), |c| {
+					c.width_percent(5)
 				})
-				.column(MiningDeviceColumn::EdgeBits, "Size", |c| {
+				.column(MiningDeviceColumn::InUse, i18n::t("mining.column_in_use"), |c| {
 					c.width_percent(5)
 				})
-				.column(MiningDeviceColumn::InUse, "In Use", |c| c.width_percent(5))
-				.column(MiningDeviceColumn::ErrorStatus, "Status", |c| {
+				.column(MiningDeviceColumn::ErrorStatus, i18n::t("mining.column_status"), |c| {
 					c.width_percent(5)
 				})
-				.column(MiningDeviceColumn::LastGraphTime, "Graph Time", |c| {
+				.column(
+					MiningDeviceColumn::LastGraphTime,
+					i18n::t("mining.column_graph_time"),
+					|c| c.width_percent(10),
+				)
+				.column(MiningDeviceColumn::GraphsPerSecond, i18n::t("mining.column_gps"), |c| {
 					c.width_percent(10)
 				})
-				.column(MiningDeviceColumn::GraphsPerSecond, "GPS", |c| {
+				.column(
+					MiningDeviceColumn::AverageGraphsPerSecond,
+					i18n::t("mining.column_gps_5m"),
+					|c| c.width_percent(5),
+				)
+				.column(MiningDeviceColumn::Temperature, i18n::t("mining.column_temp"), |c| {
+					c.width_percent(5)
+				})
+				.column(MiningDeviceColumn::FanSpeed, i18n::t("mining.column_fan"), |c| {
+					c.width_percent(5)
+				})
+				.column(MiningDeviceColumn::PowerUsage, i18n::t("mining.column_power"), |c| {
+					c.width_percent(5)
+				})
+				.column(MiningDeviceColumn::ShareFairness, i18n::t("mining.column_fairness"), |c| {
 					c.width_percent(10)
-				});
+				})
+				.on_submit(|c, _row, index| toggle_group(c, index));
 
 		let status_view = LinearLayout::new(Orientation::Vertical)
 			.child(
 				LinearLayout::new(Orientation::Horizontal)
-					.child(TextView::new("Connection Status: Starting...").with_id("mining_server_status")),
+					.child(
+						TextView::new(i18n::t("mining.connection_starting"))
+							.with_id("mining_server_status"),
+					),
+			).child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(TextView::new(i18n::t("mining.last_sent")).with_id("last_message_sent")),
 			).child(
 				LinearLayout::new(Orientation::Horizontal)
-					.child(TextView::new("Last Message Sent:  ").with_id("last_message_sent")),
+					.child(
+						TextView::new(i18n::t("mining.last_received"))
+							.with_id("last_message_received"),
+					),
 			).child(
 				LinearLayout::new(Orientation::Horizontal)
-					.child(TextView::new("Last Message Received:  ").with_id("last_message_received")),
+					.child(
+						TextView::new(i18n::t("mining.traffic_label")).with_id("mining_traffic"),
+					),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(TextView::new(i18n::t("mining.shares_label")).with_id("mining_shares")),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(
+						TextView::new(i18n::t("mining.block_progress_label"))
+							.with_id("mining_block_progress"),
+					),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(
+						TextView::new(i18n::t("mining.latency_label")).with_id("mining_latency"),
+					),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(TextView::new(i18n::t("mining.status_label")).with_id("mining_status")),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(
+						TextView::new(i18n::t("mining.gps_averages_label"))
+							.with_id("mining_gps_averages"),
+					),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(
+						TextView::new(i18n::t("mining.efficiency_label"))
+							.with_id("mining_efficiency"),
+					),
+			)
+			.child(
+				LinearLayout::new(Orientation::Horizontal)
+					.child(TextView::new("").with_id("mining_schedule")),
 			)
 			.child(
 				LinearLayout::new(Orientation::Horizontal)
-					.child(TextView::new("Mining Status: ").with_id("mining_status")),
+					.child(TextView::new("").with_id("mining_desktop")),
 			)
 			.child(
 				LinearLayout::new(Orientation::Horizontal)
@@ -162,7 +599,7 @@ impl TUIStatusListener for TUIMiningView {
 			.child(status_view)
 			.child(BoxView::with_full_screen(
 				Dialog::around(table_view.with_id(TABLE_MINING_STATUS).min_size((50, 20)))
-					.title("Mining Devices"),
+					.title(i18n::t("mining.devices_title")),
 			))
 			.with_id("mining_device_view");
 
@@ -171,47 +608,172 @@ impl TUIStatusListener for TUIMiningView {
 			.with_id("mining_stack_view");
 
 		let mining_view = LinearLayout::new(Orientation::Vertical)
+			.child(TextView::new("").with_id("mining_alert_banner"))
+			.child(TextView::new("").with_id("mining_notice_banner"))
 			.child(view_stack);
 
 		Box::new(mining_view.with_id(VIEW_MINING))
 	}
 
 	/// update
-	fn update(c: &mut Cursive, stats: Arc<RwLock<stats::Stats>>) {
-		let stats = stats.read().unwrap();
+	fn update(c: &mut Cursive, stats: &stats::Stats) {
 		let client_stats = stats.client_stats.clone();
+		update_alert_banner(c, &stats.alerts);
+		let notice = match stats.client_stats.notices.back() {
+			Some(n) => theme::styled(
+				i18n::tf("mining.pool_notice", &[&units::date_time(n.time), &n.message]),
+				Status::Warning,
+			),
+			None => StyledString::new(),
+		};
+		c.call_on_id("mining_notice_banner", |t: &mut TextView| {
+			t.set_content(notice);
+		});
+		let connection_status = if stats.client_stats.connected {
+			Status::Ok
+		} else if stats.client_stats.connection_status.contains("Starting") {
+			Status::Warning
+		} else {
+			Status::Error
+		};
 		c.call_on_id("mining_server_status", |t: &mut TextView| {
-			t.set_content(stats.client_stats.connection_status.clone());
+			t.set_content(theme::styled(
+				stats.client_stats.connection_status.clone(),
+				connection_status,
+			));
 		});
-	
-		let (basic_mining_status, basic_network_info) = {
-			if stats.client_stats.connected {
-				if stats.mining_stats.combined_gps == 0.0 {
-					(
-						"Mining Status: Starting miner and awaiting first graph time...".to_string(),
-						" ".to_string(),
-					)
-				} else {
-					(
-						format!(
-							"Mining Status: Mining at height {} at {:.*} GPS",
-							stats.mining_stats.block_height, 4, stats.mining_stats.combined_gps
-						),
-						format!(
-							"Cuck(at)oo - Target Share Difficulty {}",
-							stats.mining_stats.target_difficulty.to_string()
-						),
-					)
-				}
+
+		let mut shares = theme::styled(
+			i18n::tf("mining.shares_accepted", &[&client_stats.shares_accepted]),
+			Status::Normal,
+		);
+		shares.append(theme::styled(
+			i18n::tf("mining.shares_rejected", &[&client_stats.shares_rejected]),
+			if client_stats.shares_rejected > 0 {
+				Status::Error
 			} else {
-				("Mining Status: Waiting for server".to_string(), "  ".to_string())
-			}
+				Status::Normal
+			},
+		));
+		shares.append(theme::styled(
+			i18n::tf(
+				"mining.shares_stale",
+				&[&client_stats.shares_stale, &stats.session_stats.best_share_difficulty],
+			),
+			Status::Normal,
+		));
+		let throttle = &client_stats.throttle;
+		if throttle.throttled {
+			let status = match throttle.cooldown_until {
+				Some(t) => i18n::tf("mining.throttled_cooldown", &[&units::clock(t)]),
+				None => i18n::tf("mining.throttled_queued", &[&throttle.queued]),
+			};
+			shares.append(theme::styled(status, Status::Warning));
+		}
+		c.call_on_id("mining_shares", |t: &mut TextView| {
+			t.set_content(shares);
+		});
+		let block_progress = match stats.block_progress() {
+			Some(p) => i18n::tf(
+				"mining.block_progress",
+				&[
+					&units::number(p.best_share_difficulty),
+					&units::number(p.network_difficulty),
+					&units::decimal(p.progress, 4),
+					&p.share_odds.map_or(String::new(), |o| {
+						i18n::tf("mining.solo_odds", &[&units::number(o.round() as u64)])
+					}),
+				],
+			),
+			None => i18n::t("mining.block_progress_unknown"),
 		};
+		c.call_on_id("mining_block_progress", |t: &mut TextView| {
+			t.set_content(block_progress);
+		});
+		let latencies = stats.timing.averages();
+		let ms = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{}ms", units::decimal(v, 0)));
+		c.call_on_id("mining_latency", |t: &mut TextView| {
+			t.set_content(i18n::tf(
+				"mining.latency",
+				&[
+					&ms(latencies.queue),
+					&ms(latencies.solve),
+					&ms(latencies.submit),
+					&ms(latencies.ack),
+					&ms(stats.client_stats.pool_rtt_ms),
+					&units::decimal(stats.job_switch_waste(), 2),
+				],
+			));
+		});
+	
+		let (basic_mining_status, basic_network_info) = stats.mining_status();
+		let mining_status =
+			if stats.client_stats.connected && stats.mining_stats.combined_gps != 0.0 {
+				Status::Ok
+			} else {
+				Status::Warning
+			};
 		
 		// device
 		c.call_on_id("mining_status", |t: &mut TextView| {
-			t.set_content(basic_mining_status);
+			t.set_content(theme::styled(basic_mining_status, mining_status));
+		});
+		let now = stats.clock_now();
+		let averages = stats.mining_stats.gps_history.averages(now);
+		c.call_on_id("mining_gps_averages", |t: &mut TextView| {
+			t.set_content(i18n::tf(
+				"mining.gps_averages",
+				&[
+					&units::gps_value(averages.avg_1m),
+					&units::gps_value(averages.avg_5m),
+					&units::gps_value(averages.avg_15m),
+					&units::gps_value(averages.avg_session),
+					&units::gps_value(averages.min),
+					&units::gps_value(averages.max),
+				],
+			));
 		});
+		let efficiency = stats.mining_stats.efficiency();
+		c.call_on_id("mining_efficiency", |t: &mut TextView| {
+			if efficiency.devices.is_empty() {
+				t.set_content(i18n::t("mining.efficiency_none"));
+				return;
+			}
+			let mut content = i18n::tf(
+				"mining.efficiency",
+				&[
+					&format!("{:.4}", efficiency.graphs_per_joule),
+					&format!("{:.1}", efficiency.power_usage),
+				],
+			);
+			if let Some(cost) = efficiency.daily_cost {
+				content.push_str(&i18n::tf("mining.efficiency_cost", &[&format!("{:.2}", cost)]));
+			}
+			t.set_content(content);
+		});
+		if let Some(ref schedule) = stats.mining_stats.schedule {
+			let next = match schedule.next_change {
+				Some(t) => units::weekday_time(t),
+				None => i18n::t("mining.schedule_never"),
+			};
+			let content = match schedule.window {
+				Some(ref w) => i18n::tf("mining.schedule_window", &[w, &next]),
+				None => i18n::tf("mining.schedule_paused", &[&next]),
+			};
+			c.call_on_id("mining_schedule", |t: &mut TextView| {
+				t.set_content(content);
+			});
+		}
+		if let Some(ref desktop) = stats.mining_stats.desktop {
+			let content = match desktop.reason {
+				Some(ref r) if desktop.throttled => i18n::tf("mining.desktop_throttled", &[r]),
+				Some(ref r) => i18n::tf("mining.desktop_paused", &[r]),
+				None => i18n::t("mining.desktop_idle"),
+			};
+			c.call_on_id("mining_desktop", |t: &mut TextView| {
+				t.set_content(content);
+			});
+		}
 		c.call_on_id("network_info", |t: &mut TextView| {
 			t.set_content(basic_network_info);
 		});
@@ -222,25 +784,74 @@ impl TUIStatusListener for TUIMiningView {
 		c.call_on_id("last_message_received", |t: &mut TextView| {
 			t.set_content(client_stats.last_message_received.clone());
 		});
+		let traffic = &client_stats.traffic;
+		let (send_rate, receive_rate) = traffic.rates(now);
+		c.call_on_id("mining_traffic", |t: &mut TextView| {
+			t.set_content(i18n::tf(
+				"mining.traffic",
+				&[
+					&units::bytes(traffic.bytes_sent as f64),
+					&units::number(traffic.messages_sent),
+					&units::bytes(send_rate),
+					&units::bytes(traffic.bytes_received as f64),
+					&units::number(traffic.messages_received),
+					&units::bytes(receive_rate),
+				],
+			));
+		});
 
 		let mining_stats = stats.mining_stats.clone();
-		let device_stats = mining_stats.device_stats;
+		let device_stats = mining_stats.device_stats.clone();
 
 		let mut flattened_device_stats = vec![];
+		let fairness = stats.session_stats.fairness();
 
 		if device_stats.is_some() {
 			let device_stats = device_stats.unwrap();
-			for p in device_stats.into_iter() {
+			let grouping = DEVICE_GROUPING.read().unwrap();
+			for (i, p) in device_stats.into_iter().enumerate() {
+				let collapsed = grouping.collapsed.contains(&i);
+				if grouping.enabled {
+					if let Some(r) = group_row(i, &p, &stats.session_stats, collapsed) {
+						flattened_device_stats.push(r);
+					}
+					if collapsed {
+						continue;
+					}
+				}
 				for d in p.into_iter() {
-					flattened_device_stats.push(d);
+					let hardware = mining_stats.hardware_for(i, &d.device_id).cloned();
+					let gps_averages = mining_stats.device_gps_averages(i, &d.device_id, now);
+					let fairness = fairness
+						.iter()
+						.find(|f| f.plugin == i && f.device_id == d.device_id)
+						.cloned();
+					let quarantine = mining_stats.quarantine_for(i, &d.device_id).cloned();
+					flattened_device_stats.push(DeviceRow {
+						plugin: i,
+						grouped: grouping.enabled,
+						stats: d,
+						hardware: hardware,
+						gps_averages: gps_averages,
+						fairness: fairness,
+						quarantine: quarantine,
+						summary: None,
+					});
 				}
 			}
 		}
 
 		let _ = c.call_on_id(
 			TABLE_MINING_STATUS,
-			|t: &mut TableView<CuckooMinerDeviceStats, MiningDeviceColumn>| {
+			|t: &mut TableView<DeviceRow, MiningDeviceColumn>| {
+				// keep the selection, so groups can be toggled between updates
+				let row = t.row();
 				t.set_items(flattened_device_stats);
+				if let Some(r) = row {
+					if r < t.len() {
+						t.set_selected_row(r);
+					}
+				}
 			},
 		);
 	}
@@ -24,6 +24,13 @@ pub mod ui;
 pub mod table;
 pub mod constants;
 pub mod menu;
+pub mod history;
+pub mod fleet;
+pub mod logging;
+pub mod messages;
 pub mod mining;
 pub mod version;
 pub mod types;
+pub mod theme;
+#[cfg(unix)]
+pub mod terminal;
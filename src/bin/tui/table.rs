@@ -57,11 +57,11 @@ use std::collections::HashMap;
 use cursive::With;
 use cursive::vec::Vec2;
 use cursive::align::HAlign;
-use cursive::theme::ColorStyle;
+use cursive::theme::{Color, ColorStyle, PaletteColor};
 use cursive::{Cursive, Printer};
 use cursive::direction::Direction;
 use cursive::view::{ScrollBase, View};
-use cursive::event::{Callback, Event, EventResult, Key};
+use cursive::event::{Callback, Event, EventResult, Key, MouseButton, MouseEvent};
 use cursive::theme::PaletteColor::*;
 
 /// A trait for displaying and sorting items inside a
@@ -78,6 +78,26 @@ where
 	fn cmp(&self, other: &Self, column: H) -> Ordering
 	where
 		Self: Sized;
+
+	/// Method returning an optional color for the item's cell in the
+	/// specified column from type `H`. Cells without a color are drawn
+	/// in the row's color.
+	fn to_column_color(&self, _column: H) -> Option<Color> {
+		None
+	}
+
+	/// Method returning the group the item belongs to, if any. When
+	/// sorting, items are kept together with the other items of their
+	/// group, with groups in ascending order.
+	fn group(&self) -> Option<usize> {
+		None
+	}
+
+	/// Method returning whether the item is the header (i.e. summary) row
+	/// of its group, which always sorts first within the group.
+	fn is_group_header(&self) -> bool {
+		false
+	}
 }
 
 /// View to select an item among a list, supporting multiple columns for sorting.
@@ -612,11 +632,18 @@ impl<T: TableViewItem<H>, H: Eq + Hash + Copy + Clone + 'static> TableView<T, H>
 
 			let mut rows_to_items = self.rows_to_items.clone();
 			rows_to_items.sort_by(|a, b| {
-				if order == Ordering::Less {
-					self.items[*a].cmp(&self.items[*b], column)
-				} else {
-					self.items[*b].cmp(&self.items[*a], column)
-				}
+				let (item_a, item_b) = (&self.items[*a], &self.items[*b]);
+				item_a
+					.group()
+					.cmp(&item_b.group())
+					.then_with(|| item_b.is_group_header().cmp(&item_a.is_group_header()))
+					.then_with(|| {
+						if order == Ordering::Less {
+							item_a.cmp(item_b, column)
+						} else {
+							item_b.cmp(item_a, column)
+						}
+					})
 			});
 			self.rows_to_items = rows_to_items;
 
@@ -624,10 +651,18 @@ impl<T: TableViewItem<H>, H: Eq + Hash + Copy + Clone + 'static> TableView<T, H>
 		}
 	}
 
-	fn draw_item(&self, printer: &Printer, i: usize) {
+	fn draw_item(&self, printer: &Printer, i: usize, highlighted: bool) {
 		self.draw_columns(printer, "┆ ", |printer, column| {
-			let value = self.items[self.rows_to_items[i]].to_column(column.column);
-			column.draw_row(printer, value.as_str());
+			let item = &self.items[self.rows_to_items[i]];
+			let value = item.to_column(column.column);
+			match item.to_column_color(column.column) {
+				Some(color) if !highlighted => {
+					printer.with_color(ColorStyle::new(color, PaletteColor::View), |printer| {
+						column.draw_row(printer, value.as_str());
+					});
+				}
+				_ => column.draw_row(printer, value.as_str()),
+			}
 		});
 	}
 
@@ -672,6 +707,31 @@ impl<T: TableViewItem<H>, H: Eq + Hash + Copy + Clone + 'static> TableView<T, H>
 		}
 	}
 
+	fn column_at(&self, x: usize) -> Option<usize> {
+		let mut column_offset = 0;
+		for (index, column) in self.columns.iter().enumerate() {
+			let column_end = column_offset + column.width + 3;
+			if x >= column_offset && x < column_end {
+				return Some(index);
+			}
+			column_offset = column_end;
+		}
+		None
+	}
+
+	fn on_sort_event(&self) -> EventResult {
+		if self.on_sort.is_some() {
+			let c = &self.columns[self.active_column()];
+			let column = c.column;
+			let order = c.order;
+
+			let cb = self.on_sort.clone().unwrap();
+			EventResult::Consumed(Some(Callback::from_fn(move |s| cb(s, column, order))))
+		} else {
+			EventResult::Consumed(None)
+		}
+	}
+
 	fn column_select(&mut self) {
 		let next = self.active_column();
 		let column = self.columns[next].column;
@@ -732,7 +792,7 @@ impl<T: TableViewItem<H> + 'static, H: Eq + Hash + Copy + Clone + 'static> View
 			};
 
 			printer.with_color(ColorStyle::from(color), |printer| {
-				self.draw_item(printer, i);
+				self.draw_item(printer, i, color != Primary);
 			});
 		});
 	}
@@ -842,20 +902,54 @@ impl<T: TableViewItem<H> + 'static, H: Eq + Hash + Copy + Clone + 'static> View
 				self.column_cancel();
 				self.focus = self.items.len() - 1;
 			}
+			Event::Mouse {
+				offset,
+				position,
+				event: MouseEvent::Press(MouseButton::Left),
+			} => {
+				let pos = match position.checked_sub(offset) {
+					Some(pos) => pos,
+					None => return EventResult::Ignored,
+				};
+				if pos.y == 0 {
+					// Clicking a column header sorts by it, clicking it
+					// again reverses the order
+					let index = match self.column_at(pos.x) {
+						Some(index) => index,
+						None => return EventResult::Ignored,
+					};
+					let active = self.active_column();
+					self.columns[active].selected = false;
+					self.columns[index].selected = true;
+					self.column_select();
+					self.column_cancel();
+					return self.on_sort_event();
+				} else if pos.y >= 2 {
+					let row = pos.y - 2 + self.scrollbase.start_line;
+					if row < self.items.len() {
+						self.column_cancel();
+						self.focus = row;
+					}
+				}
+			}
+			Event::Mouse {
+				event: MouseEvent::WheelUp,
+				..
+			} => {
+				self.column_cancel();
+				self.focus_up(1);
+			}
+			Event::Mouse {
+				event: MouseEvent::WheelDown,
+				..
+			} if !self.items.is_empty() => {
+				self.column_cancel();
+				self.focus_down(1);
+			}
 			Event::Key(Key::Enter) => {
 				if self.column_select {
 					self.column_select();
-
-					if self.on_sort.is_some() {
-						let c = &self.columns[self.active_column()];
-						let column = c.column;
-						let order = c.order;
-
-						let cb = self.on_sort.clone().unwrap();
-						return EventResult::Consumed(Some(Callback::from_fn(move |s| {
-							cb(s, column, order)
-						})));
-					}
+					return self.on_sort_event();
 				} else if !self.is_empty() && self.on_submit.is_some() {
 					let cb = self.on_submit.clone().unwrap();
 					let row = self.row().unwrap();
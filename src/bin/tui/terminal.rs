@@ -0,0 +1,74 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check the terminal can run the TUI before starting it. ncurses exits
+//! the process when it can't initialize the terminal, so this is the only
+//! chance to fall back to something else.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Directories ncurses looks for terminfo entries in, in its order
+fn terminfo_dirs() -> Vec<PathBuf> {
+	let mut dirs = vec![];
+	if let Ok(d) = env::var("TERMINFO") {
+		dirs.push(PathBuf::from(d));
+	}
+	if let Some(home) = env::var_os("HOME") {
+		dirs.push(Path::new(&home).join(".terminfo"));
+	}
+	let defaults = ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"];
+	match env::var("TERMINFO_DIRS") {
+		Ok(list) => {
+			for d in list.split(':') {
+				// an empty entry stands for the default directories
+				if d.is_empty() {
+					dirs.extend(defaults.iter().map(PathBuf::from));
+				} else {
+					dirs.push(PathBuf::from(d));
+				}
+			}
+		}
+		Err(_) => dirs.extend(defaults.iter().map(PathBuf::from)),
+	}
+	dirs
+}
+
+/// Whether there's a terminfo entry for the terminal type, filed under its
+/// first letter or (on macOS) that letter's hex code
+fn has_terminfo(term: &str) -> bool {
+	let first = match term.chars().next() {
+		Some(c) => c,
+		None => return false,
+	};
+	terminfo_dirs().iter().any(|d| {
+		d.join(first.to_string()).join(term).is_file()
+			|| d.join(format!("{:x}", first as u32)).join(term).is_file()
+	})
+}
+
+/// Check the TUI can start on this terminal, the reason if not
+pub fn check() -> Result<(), String> {
+	let term = match env::var("TERM") {
+		Ok(ref t) if !t.trim().is_empty() => t.trim().to_string(),
+		_ => return Err("TERM is not set".to_string()),
+	};
+	if term == "dumb" {
+		return Err("TERM is dumb".to_string());
+	}
+	if !has_terminfo(&term) {
+		return Err(format!("no terminfo entry for TERM {}", term));
+	}
+	Ok(())
+}
@@ -0,0 +1,145 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Color themes for the TUI, along with the palette used to colorize
+//! status lines and table cells
+
+use std::sync::RwLock;
+
+use cursive::theme::{BorderStyle, Color, Theme};
+use cursive::theme::PaletteColor::*;
+use cursive::theme::Color::*;
+use cursive::theme::BaseColor::*;
+use cursive::utils::markup::StyledString;
+
+/// Name of the theme used when none (or an unknown one) is configured
+pub const DEFAULT_THEME: &str = "dark";
+
+/// Status categories that are drawn in their own color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+	/// Everything is fine (connected, device OK)
+	Ok,
+	/// Paused, starting or otherwise waiting
+	Warning,
+	/// Errored devices, rejected shares, lost connection
+	Error,
+	/// Plain text, no particular status
+	Normal,
+}
+
+/// Colors used to draw each status under a given theme
+#[derive(Clone, Copy)]
+pub struct StatusPalette {
+	/// OK status
+	pub ok: Color,
+	/// Warning status
+	pub warning: Color,
+	/// Error status
+	pub error: Color,
+	/// Normal text
+	pub normal: Color,
+}
+
+impl StatusPalette {
+	fn dark() -> StatusPalette {
+		StatusPalette {
+			ok: Light(Green),
+			warning: Light(Yellow),
+			error: Light(Red),
+			normal: Dark(White),
+		}
+	}
+
+	fn light() -> StatusPalette {
+		StatusPalette {
+			ok: Dark(Green),
+			warning: Dark(Yellow),
+			error: Dark(Red),
+			normal: Dark(Black),
+		}
+	}
+
+	fn monochrome() -> StatusPalette {
+		StatusPalette {
+			ok: Dark(White),
+			warning: Dark(White),
+			error: Dark(White),
+			normal: Dark(White),
+		}
+	}
+
+	/// Color for the given status
+	pub fn color(&self, status: Status) -> Color {
+		match status {
+			Status::Ok => self.ok,
+			Status::Warning => self.warning,
+			Status::Error => self.error,
+			Status::Normal => self.normal,
+		}
+	}
+}
+
+lazy_static! {
+	/// Palette of the theme currently applied, so views can colorize
+	/// their content from within their update functions
+	static ref STATUS_PALETTE: RwLock<StatusPalette> = RwLock::new(StatusPalette::dark());
+}
+
+/// Apply the named theme (dark, light or monochrome) to the given cursive
+/// theme, and make its status palette the active one
+pub fn apply(theme: &mut Theme, name: &str) {
+	theme.shadow = false;
+	theme.borders = BorderStyle::Simple;
+	let palette = match name {
+		"light" => {
+			theme.palette[Background] = Light(White);
+			theme.palette[Shadow] = Light(White);
+			theme.palette[View] = Light(White);
+			theme.palette[Primary] = Dark(Black);
+			theme.palette[Highlight] = Dark(Blue);
+			theme.palette[HighlightInactive] = Light(Black);
+			StatusPalette::light()
+		}
+		"monochrome" => {
+			theme.palette[Background] = Dark(Black);
+			theme.palette[Shadow] = Dark(Black);
+			theme.palette[View] = Dark(Black);
+			theme.palette[Primary] = Dark(White);
+			theme.palette[Highlight] = Light(White);
+			theme.palette[HighlightInactive] = Light(Black);
+			StatusPalette::monochrome()
+		}
+		_ => {
+			theme.palette[Background] = Dark(Black);
+			theme.palette[Shadow] = Dark(Black);
+			theme.palette[View] = Dark(Black);
+			theme.palette[Primary] = Dark(White);
+			theme.palette[Highlight] = Dark(Cyan);
+			theme.palette[HighlightInactive] = Dark(Blue);
+			StatusPalette::dark()
+		}
+	};
+	*STATUS_PALETTE.write().unwrap() = palette;
+}
+
+/// Color of the given status in the active theme
+pub fn color(status: Status) -> Color {
+	STATUS_PALETTE.read().unwrap().color(status)
+}
+
+/// Text styled with the color of the given status
+pub fn styled<S: Into<String>>(text: S, status: Status) -> StyledString {
+	StyledString::styled(text.into(), color(status))
+}
@@ -14,7 +14,7 @@
 
 //! Types specific to the UI module
 
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
 use cursive::Cursive;
 use cursive::view::View;
@@ -23,8 +23,8 @@ use stats::Stats;
 /// Main message struct to communicate between the UI and
 /// the main process
 pub enum UIMessage {
-	/// Update mining status
-	UpdateStatus(Arc<RwLock<Stats>>),
+	/// Update mining status, from a snapshot of the stats
+	UpdateStatus(Arc<Stats>),
 }
 
 /// Trait for a UI element that recieves status update messages
@@ -34,5 +34,5 @@ pub trait TUIStatusListener {
 	/// create the view, to return to the main UI controller
 	fn create() -> Box<View>;
 	/// Update according to status update contents
-	fn update(c: &mut Cursive, stats: Arc<RwLock<Stats>>);
+	fn update(c: &mut Cursive, stats: &Stats);
 }
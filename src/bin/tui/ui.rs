@@ -15,24 +15,27 @@
 //! Basic TUI to better output the overall system status and status
 //! of various subsystems
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, RwLock};
-use time;
+use std::thread;
+use std::time::Duration;
 
 use cursive::Cursive;
-use cursive::theme::{BaseColor, BorderStyle, Color, Theme};
-use cursive::theme::PaletteColor::*;
-use cursive::theme::Color::*;
-use cursive::theme::BaseColor::*;
+use cursive::theme::{BaseColor, Color};
 use cursive::utils::markup::StyledString;
-use cursive::views::{LinearLayout, Panel, StackView, TextView, ViewBox};
+use cursive::views::{Dialog, LinearLayout, Panel, SelectView, StackView, TextView, ViewBox};
 use cursive::direction::Orientation;
 use cursive::traits::*;
 
-use tui::{menu, mining, version};
+use tui::{fleet, history, logging, menu, messages, mining, theme, version};
 use tui::types::*;
 use tui::constants::*;
 
+use config::MinerConfig;
+use i18n;
 use stats;
+use types::MinerMessage;
+use units;
 
 use built_info;
 
@@ -42,50 +45,53 @@ pub struct UI {
 	ui_rx: mpsc::Receiver<UIMessage>,
 	ui_tx: mpsc::Sender<UIMessage>,
 	controller_tx: mpsc::Sender<ControllerMessage>,
-}
-
-fn modify_theme(theme: &mut Theme) {
-	theme.shadow = false;
-	theme.borders = BorderStyle::Simple;
-	theme.palette[Background] = Dark(Black);
-	theme.palette[Shadow] = Dark(Black);
-	theme.palette[View] = Dark(Black);
-	theme.palette[Primary] = Dark(White);
-	theme.palette[Highlight] = Dark(Cyan);
-	theme.palette[HighlightInactive] = Dark(Blue);
-	// also secondary, tertiary, TitlePrimary, TitleSecondary
+	summary_shown: bool,
+	stats: Option<Arc<stats::Stats>>,
+	/// Blocks found as of the last update, none before the first
+	blocks_found: Option<u64>,
 }
 
 impl UI {
 	/// Create a new UI
-	pub fn new(controller_tx: mpsc::Sender<ControllerMessage>) -> UI {
+	pub fn new(
+		controller_tx: mpsc::Sender<ControllerMessage>,
+		miner_tx: mpsc::Sender<MinerMessage>,
+		config: &MinerConfig,
+	) -> UI {
 		let (ui_tx, ui_rx) = mpsc::channel::<UIMessage>();
 		let mut grin_ui = UI {
 			cursive: Cursive::new(),
 			ui_tx: ui_tx,
 			ui_rx: ui_rx,
 			controller_tx: controller_tx,
+			summary_shown: false,
+			stats: None,
+			blocks_found: None,
 		};
 
 		// Create UI objects, etc
+		mining::TUIMiningView::set_hardware_warnings(config);
+		mining::TUIMiningView::set_grouping(config);
 		let mining_view = mining::TUIMiningView::create();
 		let version_view = version::TUIVersionView::create();
+		let logging_view = logging::TUILoggingView::create();
+		let messages_view = messages::TUIMessagesView::create();
+		let history_view = history::TUIHistoryView::create();
+		let fleet_view = fleet::TUIFleetView::create();
 
 		let main_menu = menu::create();
 
 		let root_stack = StackView::new()
 			.layer(version_view)
+			.layer(logging_view)
+			.layer(messages_view)
+			.layer(history_view)
+			.layer(fleet_view)
 			.layer(mining_view)
 			.with_id(ROOT_STACK);
 
-		let mut title_string = StyledString::new();
-		title_string.append(StyledString::styled(
-			format!("Grin Miner Version {}", built_info::PKG_VERSION),
-			Color::Dark(BaseColor::Yellow),
-		));
-
 		let main_layer = LinearLayout::new(Orientation::Vertical)
-			.child(Panel::new(TextView::new(title_string)))
+			.child(Panel::new(TextView::new(title(None)).with_id(TEXT_TITLE)))
 			.child(
 				LinearLayout::new(Orientation::Horizontal)
 					.child(Panel::new(ViewBox::new(main_menu)))
@@ -93,18 +99,43 @@ impl UI {
 			);
 
 		//set theme
+		let theme_name = config
+			.tui_theme
+			.clone()
+			.unwrap_or(theme::DEFAULT_THEME.to_string());
 		let mut theme = grin_ui.cursive.current_theme().clone();
-		modify_theme(&mut theme);
+		theme::apply(&mut theme, &theme_name);
 		grin_ui.cursive.set_theme(theme);
 		grin_ui.cursive.add_layer(main_layer);
 
-		// Configure a callback (shutdown, for the first test)
+		// Quitting shows the session summary first
 		let controller_tx_clone = grin_ui.controller_tx.clone();
 		grin_ui.cursive.add_global_callback('q', move |_| {
 			controller_tx_clone
-				.send(ControllerMessage::Shutdown)
+				.send(ControllerMessage::ShowSummary)
 				.unwrap();
 		});
+		grin_ui.cursive.add_global_callback('c', |c| {
+			mining::TUIMiningView::clear_alerts(c);
+		});
+		grin_ui.cursive.add_global_callback('l', |c| {
+			logging::TUILoggingView::cycle_level(c);
+		});
+		let profiles = config.profile_names();
+		let profiles_tx = miner_tx.clone();
+		grin_ui.cursive.add_global_callback('p', move |c| {
+			show_profiles(c, &profiles, profiles_tx.clone());
+		});
+		let standby: Vec<(usize, String)> = config
+			.miner_plugin_config
+			.iter()
+			.enumerate()
+			.filter(|&(_, p)| !p.enabled.unwrap_or(true))
+			.map(|(i, p)| (i, format!("{} {}_{}", i, p.type_filter, p.edge_bits)))
+			.collect();
+		grin_ui.cursive.add_global_callback('s', move |c| {
+			show_standby(c, &standby, miner_tx.clone());
+		});
 		grin_ui.cursive.set_fps(4);
 		grin_ui
 	}
@@ -116,14 +147,32 @@ impl UI {
 			return false;
 		}
 
-		// Process any pending UI messages
+		// Process any pending UI messages, only the latest stats snapshot
+		// is worth drawing
+		let mut latest = None;
 		while let Some(message) = self.ui_rx.try_iter().next() {
 			match message {
-				UIMessage::UpdateStatus(update) => {
-					mining::TUIMiningView::update(&mut self.cursive, update.clone());
-					version::TUIVersionView::update(&mut self.cursive, update.clone());
+				UIMessage::UpdateStatus(update) => latest = Some(update),
+			}
+		}
+		if let Some(update) = latest {
+			mining::TUIMiningView::update(&mut self.cursive, &update);
+			version::TUIVersionView::update(&mut self.cursive, &update);
+			messages::TUIMessagesView::update(&mut self.cursive, &update);
+			history::TUIHistoryView::update(&mut self.cursive, &update);
+			fleet::TUIFleetView::update(&mut self.cursive, &update);
+			let rig = update.rig.name.clone();
+			self.cursive.call_on_id(TEXT_TITLE, |t: &mut TextView| {
+				t.set_content(title(rig.as_ref().map(|r| r.as_str())));
+			});
+			let blocks_found = update.client_stats.blocks_found;
+			if self.blocks_found.map_or(false, |b| blocks_found > b) {
+				if let Some(block) = update.blocks.last() {
+					show_block_found(&mut self.cursive, block);
 				}
 			}
+			self.blocks_found = Some(blocks_found);
+			self.stats = Some(update);
 		}
 
 		// Step the UI
@@ -131,39 +180,181 @@ impl UI {
 		true
 	}
 
+	/// Show the session summary, with the option to quit or go back to
+	/// mining. Asking again while the summary is shown quits.
+	pub fn show_summary(&mut self, summary: String) {
+		if self.summary_shown {
+			let _ = self.controller_tx.send(ControllerMessage::Shutdown);
+			return;
+		}
+		self.summary_shown = true;
+		let controller_tx = self.controller_tx.clone();
+		let controller_tx_back = self.controller_tx.clone();
+		self.cursive.add_layer(
+			Dialog::around(TextView::new(summary))
+				.title(i18n::t("ui.summary_title"))
+				.button(i18n::t("ui.quit"), move |_| {
+					let _ = controller_tx.send(ControllerMessage::Shutdown);
+				})
+				.button(i18n::t("ui.back"), move |s| {
+					s.pop_layer();
+					let _ = controller_tx_back.send(ControllerMessage::HideSummary);
+				}),
+		);
+	}
+
 	/// Stop the UI
 	pub fn stop(&mut self) {
 		self.cursive.quit();
 	}
 }
 
+/// Title bar text, with the rig name if there's one
+fn title(rig: Option<&str>) -> StyledString {
+	let text = match rig {
+		Some(rig) => i18n::tf("ui.title_rig", &[&built_info::PKG_VERSION, &rig]),
+		None => i18n::tf("ui.title", &[&built_info::PKG_VERSION]),
+	};
+	StyledString::styled(text, Color::Dark(BaseColor::Yellow))
+}
+
+/// Celebrate a block found
+fn show_block_found(c: &mut Cursive, block: &stats::FoundBlock) {
+	let mut lines = vec![
+		i18n::t("ui.block_found"),
+		String::new(),
+		i18n::tf("ui.block_height", &[&units::number(block.height)]),
+		i18n::tf(
+			"ui.block_hash",
+			&[&block.hash.clone().unwrap_or(i18n::t("ui.block_hash_unknown"))],
+		),
+		i18n::tf("ui.block_time", &[&units::date_time(block.time)]),
+	];
+	if let Some((plugin, ref device)) = block.device {
+		lines.push(i18n::tf("ui.block_found_by", &[&plugin, device]));
+	}
+	let text = StyledString::styled(lines.join("\n"), Color::Light(BaseColor::Green));
+	c.add_layer(
+		Dialog::around(TextView::new(text))
+			.title(i18n::t("ui.solo_mining"))
+			.dismiss_button(i18n::t("ui.back")),
+	);
+}
+
+/// Show the configured device profiles, switching to the one chosen
+fn show_profiles(c: &mut Cursive, profiles: &[String], miner_tx: mpsc::Sender<MinerMessage>) {
+	if profiles.is_empty() {
+		c.add_layer(
+			Dialog::around(TextView::new(i18n::t("ui.no_profiles")))
+				.title(i18n::t("ui.profiles_title"))
+				.dismiss_button(i18n::t("ui.back")),
+		);
+		return;
+	}
+	let mut select: SelectView<String> = SelectView::new();
+	for p in profiles {
+		select.add_item(p.clone(), p.clone());
+	}
+	select.set_on_submit(move |c, name: &String| {
+		let _ = miner_tx.send(MinerMessage::SwitchProfile(name.clone()));
+		c.pop_layer();
+	});
+	c.add_layer(
+		Dialog::around(select.min_width(30))
+			.title(i18n::t("ui.profiles_title"))
+			.dismiss_button(i18n::t("ui.back")),
+	);
+}
+
+/// Show the plugins configured in standby, activating the one chosen
+fn show_standby(
+	c: &mut Cursive,
+	plugins: &[(usize, String)],
+	miner_tx: mpsc::Sender<MinerMessage>,
+) {
+	if plugins.is_empty() {
+		c.add_layer(
+			Dialog::around(TextView::new(i18n::t("ui.no_standby")))
+				.title(i18n::t("ui.standby_title"))
+				.dismiss_button(i18n::t("ui.back")),
+		);
+		return;
+	}
+	let mut select: SelectView<usize> = SelectView::new();
+	for &(i, ref name) in plugins {
+		select.add_item(name.clone(), i);
+	}
+	select.set_on_submit(move |c, plugin: &usize| {
+		let _ = miner_tx.send(MinerMessage::Activate(Some(*plugin)));
+		c.pop_layer();
+	});
+	c.add_layer(
+		Dialog::around(select.min_width(30))
+			.title(i18n::t("ui.standby_title"))
+			.dismiss_button(i18n::t("ui.back")),
+	);
+}
+
 /// Controller message
 
 pub struct Controller {
 	rx: mpsc::Receiver<ControllerMessage>,
 	ui: UI,
+	refresh_interval: u64,
 }
 
 /// Controller Message
 pub enum ControllerMessage {
 	/// Shutdown
 	Shutdown,
+	/// Show the session summary before quitting
+	ShowSummary,
+	/// Summary was dismissed
+	HideSummary,
 }
 
 impl Controller {
 	/// Create a new controller
-	pub fn new() -> Result<Controller, String> {
+	pub fn new(
+		config: &MinerConfig,
+		miner_tx: mpsc::Sender<MinerMessage>,
+	) -> Result<Controller, String> {
 		let (tx, rx) = mpsc::channel::<ControllerMessage>();
 		Ok(Controller {
 			rx: rx,
-			ui: UI::new(tx.clone()),
+			ui: UI::new(tx.clone(), miner_tx, config),
+			refresh_interval: config
+				.tui_refresh_interval
+				.unwrap_or(DEFAULT_REFRESH_INTERVAL),
 		})
 	}
-	/// Run the controller
-	pub fn run(&mut self, stats: Arc<RwLock<stats::Stats>>) {
-		let stat_update_interval = 1;
-		let mut next_stat_update = time::get_time().sec + stat_update_interval;
+
+	/// Take a snapshot of the stats every refresh interval and hand it to
+	/// the UI, so the UI thread never holds the stats lock and can't hold
+	/// up the mining or stratum threads while drawing
+	fn start_snapshots(&self, stats: Arc<RwLock<stats::Stats>>) {
+		let ui_tx = self.ui.ui_tx.clone();
+		let refresh_interval = Duration::from_millis(self.refresh_interval);
+		let _ = thread::Builder::new()
+			.name("ui_stats".to_string())
+			.spawn(move || loop {
+				let snapshot = Arc::new(stats.read().unwrap().clone());
+				// UI has gone away
+				if ui_tx.send(UIMessage::UpdateStatus(snapshot)).is_err() {
+					break;
+				}
+				thread::sleep(refresh_interval);
+			});
+	}
+
+	/// Run the controller, until quit or the quit flag is raised
+	pub fn run(&mut self, stats: Arc<RwLock<stats::Stats>>, quit: Arc<AtomicBool>) {
+		self.start_snapshots(stats.clone());
 		while self.ui.step() {
+			if quit.load(Ordering::Relaxed) {
+				self.ui.stop();
+				return;
+			}
 			while let Some(message) = self.rx.try_iter().next() {
 				match message {
 					ControllerMessage::Shutdown => {
@@ -175,12 +366,18 @@ impl Controller {
 							.send(UIMessage::UpdateOutput("update".to_string()))
 							.unwrap();*/
 					}
+					ControllerMessage::ShowSummary => {
+						let summary = match self.ui.stats {
+							Some(ref s) => s.session_summary(),
+							None => stats.read().unwrap().session_summary(),
+						};
+						self.ui.show_summary(summary);
+					}
+					ControllerMessage::HideSummary => {
+						self.ui.summary_shown = false;
+					}
 				}
 			}
-			if time::get_time().sec > next_stat_update {
-				self.ui.ui_tx.send(UIMessage::UpdateStatus(stats.clone())).unwrap();
-				next_stat_update = time::get_time().sec + stat_update_interval;
-			}
 		}
 	}
 }
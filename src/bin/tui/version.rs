@@ -14,8 +14,6 @@
 
 //! Version and build info
 
-use std::sync::{Arc, RwLock};
-
 use cursive::Cursive;
 use cursive::view::View;
 use cursive::views::{BoxView, LinearLayout, TextView};
@@ -45,5 +43,5 @@ impl TUIStatusListener for TUIVersionView {
 	}
 
 	/// update
-	fn update(_c: &mut Cursive, _stats: Arc<RwLock<Stats>>) {}
+	fn update(_c: &mut Cursive, _stats: &Stats) {}
 }
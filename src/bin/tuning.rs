@@ -0,0 +1,133 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tuning cache, the best parameters the tune subcommand found for each
+//! plugin and device, filled in under a device's own parameters
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use serde_json;
+
+use config::types::CuckooMinerPluginConfig;
+use config::MinerConfig;
+use util::LOGGER;
+
+/// Tuning cache file, unless configured with tuning_cache
+pub const DEFAULT_TUNING_CACHE: &str = "grin-miner-tuning.json";
+
+/// Best parameters found for a plugin on a device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunedDevice {
+	pub type_filter: String,
+	pub edge_bits: u8,
+	pub device: u32,
+	pub device_name: String,
+	pub parameters: HashMap<String, u32>,
+	/// Graphs per second measured with them
+	pub gps: f64,
+	/// When they were found, in seconds since the epoch
+	pub timestamp: i64,
+}
+
+/// Path of the tuning cache
+pub fn cache_path(config: &MinerConfig) -> String {
+	config
+		.tuning_cache
+		.clone()
+		.unwrap_or(DEFAULT_TUNING_CACHE.to_string())
+}
+
+/// Read the tuning cache, empty if there's none or it's switched off
+/// (tuning_cache = "")
+pub fn load(config: &MinerConfig) -> Vec<TunedDevice> {
+	let path = cache_path(config);
+	if path.is_empty() {
+		return vec![];
+	}
+	let mut contents = String::new();
+	if File::open(&path)
+		.and_then(|mut f| f.read_to_string(&mut contents))
+		.is_err()
+	{
+		return vec![];
+	}
+	match serde_json::from_str(&contents) {
+		Ok(c) => c,
+		Err(e) => {
+			warn!(LOGGER, "Ignoring tuning cache {}: {}", path, e);
+			vec![]
+		}
+	}
+}
+
+/// Add (or replace) a device's tuned parameters in the tuning cache
+pub fn save(config: &MinerConfig, tuned: TunedDevice) -> Result<(), String> {
+	let path = cache_path(config);
+	let mut cache = load(config);
+	cache.retain(|t| {
+		!(t.type_filter == tuned.type_filter && t.edge_bits == tuned.edge_bits && t.device == tuned.device)
+	});
+	cache.push(tuned);
+	let json = serde_json::to_string_pretty(&cache).map_err(|e| e.to_string())?;
+	File::create(&path)
+		.and_then(|mut f| f.write_all(json.as_bytes()))
+		.map_err(|e| format!("Unable to write {}: {}", path, e))
+}
+
+/// Fill in the tuned parameters of each of the plugin's devices, where
+/// its device_parameters don't set them
+pub fn apply(index: usize, config: &mut CuckooMinerPluginConfig, cache: &[TunedDevice]) {
+	let tuned: Vec<&TunedDevice> = cache
+		.iter()
+		.filter(|t| t.type_filter == config.type_filter && t.edge_bits == config.edge_bits)
+		.collect();
+	if tuned.is_empty() {
+		return;
+	}
+	let mut devices = config.device_parameters.take().unwrap_or_default();
+	// every tuned device mines, but those deselected
+	for t in &tuned {
+		devices.entry(t.device.to_string()).or_insert_with(HashMap::new);
+	}
+	for (device, params) in devices.iter_mut() {
+		if params.get("USE_DEVICE") == Some(&0) {
+			continue;
+		}
+		let t = match tuned.iter().find(|t| t.device.to_string() == *device) {
+			Some(t) => t,
+			None => continue,
+		};
+		let mut applied = vec![];
+		for (name, value) in &t.parameters {
+			if !params.contains_key(name) {
+				params.insert(name.clone(), *value);
+				applied.push(format!("{}={}", name, value));
+			}
+		}
+		if !applied.is_empty() {
+			info!(
+				LOGGER,
+				"Cuckoo plugin {} device {}: tuned parameters for {} ({:.4} gps): {}",
+				index,
+				device,
+				t.device_name,
+				t.gps,
+				applied.join(", ")
+			);
+		}
+	}
+	config.device_parameters = Some(devices);
+}
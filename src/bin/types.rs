@@ -14,6 +14,8 @@
 
 use serde_json::Value;
 
+use config::MinerConfig;
+
 /// Types used for stratum
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -22,6 +24,13 @@ pub struct JobTemplate {
 	pub job_id: u64,
 	pub difficulty: u64,
 	pub pre_pow: String,
+	/// Proof size the job is for, from pools sending a job per proof
+	/// size; any size if not given
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub edge_bits: Option<u32>,
+	/// Difficulty a share needs to be a block, from pools sending it
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub network_difficulty: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -52,6 +61,20 @@ pub struct LoginParams {
 	pub login: String,
 	pub pass: String,
 	pub agent: String,
+	/// Algorithms mined, e.g. cuckaroo29, asking pools that support it
+	/// for jobs for each
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub algorithms: Option<Vec<String>>,
+	/// Name of the rig, for pools listing workers per rig
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub rig_id: Option<String>,
+	/// Submit signing schemes supported, offered when a worker token is
+	/// configured
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub signing: Option<Vec<String>>,
+	/// Share difficulty suggested, for pools letting miners choose it
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub difficulty: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -61,6 +84,12 @@ pub struct SubmitParams {
 	pub edge_bits: u32,
 	pub nonce: u64,
 	pub pow: Vec<u32>,
+	/// Signature of the share, when the pool asked for signed submits
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub signature: Option<String>,
+	/// Correlation id of the share, for pools showing it
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub correlation_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -76,9 +105,32 @@ pub struct WorkerStatus {
 /// Types used for internal communication from stratum client to miner
 #[derive(Serialize, Deserialize, Debug)]
 pub enum MinerMessage{
-	// Height, difficulty, pre_pow
-	ReceivedJob(u64, u64, u64, String),
+	// Height, job_id, difficulty, pre_pow, edge_bits the job is for if
+	// only one
+	ReceivedJob(u64, u64, u64, String, Option<u32>),
 	StopJob,
+	// Stop mining until resumed, jobs received meanwhile are kept
+	Pause,
+	Resume,
+	// plugin index, device id
+	PauseDevice(usize, u32),
+	ResumeDevice(usize, u32),
+	// plugin index, device id, paused or resumed for temperature, apart
+	// from the pauses above
+	ThermalPause(usize, u32),
+	ThermalResume(usize, u32),
+	// Start mining on the standby devices of the plugin, or of all
+	// plugins
+	Activate(Option<usize>),
+	// Re-query the plugin directory and restart the current job
+	ReloadPlugins,
+	// Apply a new plugin configuration, and restart the current job
+	UpdateConfig(MinerConfig),
+	// Switch to the named device profile, and restart the current job
+	SwitchProfile(String),
+	// Unload the plugins and reconnect, once the shares found are
+	// answered, for the given reason
+	Restart(String),
 	Shutdown,
 }
 
@@ -86,5 +138,9 @@ pub enum MinerMessage{
 pub enum ClientMessage{
 	// height, job_id, edge_bits, nonce, pow
 	FoundSolution(u64, u64, u32, u64, Vec<u32>),
+	// server address, login, password
+	SwitchPool(String, Option<String>, Option<String>),
+	// Drop the server connection and connect again
+	Reconnect,
 	Shutdown,
 }
@@ -0,0 +1,146 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Number, rate, data size and time formatting as configured (thousands
+//! separators, SI prefixes for graph rates, 12 or 24 hour clock), shared
+//! by the TUI, the console dashboard, the logs and the session reports
+
+use std::sync::RwLock;
+
+use time;
+
+use config::MinerConfig;
+
+/// Clock formats
+pub const CLOCK_24H: &str = "24h";
+pub const CLOCK_12H: &str = "12h";
+
+/// Formatting settings
+#[derive(Clone, Debug, Default)]
+struct Settings {
+	thousands_separator: Option<String>,
+	clock_12h: bool,
+	si_prefixes: bool,
+}
+
+lazy_static! {
+	static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+}
+
+/// Use the formatting settings of the config from now on
+pub fn configure(config: &MinerConfig) {
+	*SETTINGS.write().unwrap() = Settings {
+		thousands_separator: config.thousands_separator.clone().filter(|s| !s.is_empty()),
+		clock_12h: config.clock_format.as_ref().map_or(false, |c| c == CLOCK_12H),
+		si_prefixes: config.si_prefixes.unwrap_or(false),
+	};
+}
+
+/// Group the digits of an integer part with the separator
+fn group(digits: &str, separator: &str) -> String {
+	let mut out = String::new();
+	for (i, c) in digits.chars().enumerate() {
+		if i > 0 && (digits.len() - i) % 3 == 0 {
+			out.push_str(separator);
+		}
+		out.push(c);
+	}
+	out
+}
+
+/// An integer, e.g. a share count or difficulty
+pub fn number(n: u64) -> String {
+	match SETTINGS.read().unwrap().thousands_separator {
+		Some(ref sep) => group(&n.to_string(), sep),
+		None => n.to_string(),
+	}
+}
+
+/// A decimal with the given number of places
+pub fn decimal(v: f64, places: usize) -> String {
+	let s = format!("{:.*}", places, v);
+	let sep = match SETTINGS.read().unwrap().thousands_separator {
+		Some(ref sep) => sep.clone(),
+		None => return s,
+	};
+	let (sign, s) = if s.starts_with('-') {
+		("-", &s[1..])
+	} else {
+		("", &s[..])
+	};
+	match s.find('.') {
+		Some(i) => format!("{}{}{}", sign, group(&s[..i], &sep), &s[i..]),
+		None => format!("{}{}", sign, group(s, &sep)),
+	}
+}
+
+/// SI prefix and scaled value of a rate
+fn si(v: f64) -> (f64, &'static str) {
+	if !SETTINGS.read().unwrap().si_prefixes {
+		return (v, "");
+	}
+	for &(scale, prefix) in &[(1e9, "G"), (1e6, "M"), (1e3, "k")] {
+		if v.abs() >= scale {
+			return (v / scale, prefix);
+		}
+	}
+	(v, "")
+}
+
+/// A graph rate without its unit, for columns and lists headed GPS,
+/// e.g. 0.4512 or 12.3456k
+pub fn gps_value(v: f64) -> String {
+	let (v, prefix) = si(v);
+	format!("{}{}", decimal(v, 4), prefix)
+}
+
+/// A graph rate with its unit, e.g. 0.4512 GPS or 12.3456 kGPS
+pub fn gps(v: f64) -> String {
+	let (v, prefix) = si(v);
+	format!("{} {}GPS", decimal(v, 4), prefix)
+}
+
+/// An amount of data, e.g. 512 B or 12.3 KiB
+pub fn bytes(n: f64) -> String {
+	for &(scale, unit) in &[(1073741824.0, "GiB"), (1048576.0, "MiB"), (1024.0, "KiB")] {
+		if n >= scale {
+			return format!("{} {}", decimal(n / scale, 1), unit);
+		}
+	}
+	format!("{} B", decimal(n, 0))
+}
+
+fn strftime(secs: i64, format_24h: &str, format_12h: &str) -> String {
+	let format = if SETTINGS.read().unwrap().clock_12h {
+		format_12h
+	} else {
+		format_24h
+	};
+	time::strftime(format, &time::at(time::Timespec::new(secs, 0))).unwrap_or_default()
+}
+
+/// Date and time, e.g. 2018-11-30 14:05:09
+pub fn date_time(secs: i64) -> String {
+	strftime(secs, "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %I:%M:%S %p")
+}
+
+/// Time of day, e.g. 14:05:09
+pub fn clock(secs: i64) -> String {
+	strftime(secs, "%H:%M:%S", "%I:%M:%S %p")
+}
+
+/// Day of the week and time, e.g. Fri 14:05
+pub fn weekday_time(secs: i64) -> String {
+	strftime(secs, "%a %H:%M", "%a %I:%M %p")
+}
@@ -0,0 +1,310 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Self-update. A release is described by a manifest, signed with
+//! minisign, listing its files (the binary and plugins, by path relative to
+//! the binary's directory) with their checksums. The update subcommand
+//! downloads a newer release to the staging directory, and the files are
+//! swapped in the next time the miner starts, as the running binary and
+//! plugins can't be replaced.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use config::UpdateConfig;
+use http;
+use util::{self, LOGGER};
+
+/// Manifest of the release staged, in the staging directory
+const STAGED_MANIFEST: &str = "staged.json";
+
+/// A file of a release
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReleaseFile {
+	/// Path relative to the binary's directory, e.g. grin-miner or
+	/// plugins/cuckatoo_lean_cpu_compat_31.cuckooplugin
+	pub name: String,
+	pub url: String,
+	/// SHA-256 of the file, as hex
+	pub sha256: String,
+	/// Target triple the file is built for, any if not given
+	#[serde(default)]
+	pub target: Option<String>,
+}
+
+/// A release, as described by its manifest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+	pub version: String,
+	pub files: Vec<ReleaseFile>,
+}
+
+impl Manifest {
+	/// Files of the release for the given target
+	pub fn files_for(&self, target: &str) -> Vec<&ReleaseFile> {
+		self.files
+			.iter()
+			.filter(|f| f.target.as_ref().map_or(true, |t| t == target))
+			.collect()
+	}
+}
+
+/// Whether version a is newer than version b, comparing the numbers of
+/// e.g. 1.0.2 and 1.0.10
+pub fn is_newer(a: &str, b: &str) -> bool {
+	let parse = |v: &str| -> Vec<u64> {
+		v.trim_left_matches('v')
+			.split(|c| c == '.' || c == '-')
+			.map(|p| p.parse::<u64>().unwrap_or(0))
+			.collect()
+	};
+	parse(a) > parse(b)
+}
+
+fn download(url: &str) -> Result<Vec<u8>, String> {
+	match http::get_bytes(url, None) {
+		Ok((200, body)) => Ok(body),
+		Ok((code, _)) => Err(format!("Unable to download {}: HTTP error {}", url, code)),
+		Err(e) => Err(format!("Unable to download {}: {}", url, e)),
+	}
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+	}
+	File::create(path)
+		.and_then(|mut f| f.write_all(contents))
+		.map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+/// Whether the file name stays within the binary's directory
+fn valid_name(name: &str) -> bool {
+	let escapes = name.split(|c| c == '/' || c == '\\').any(|p| p == "..");
+	let path = Path::new(name);
+	!path.is_absolute() && !path.has_root() && !escapes
+}
+
+/// SHA-256 of the contents, as lowercase hex
+fn sha256_hex(contents: &[u8]) -> String {
+	util::to_hex(Sha256::digest(contents).to_vec())
+}
+
+/// Check the manifest's signature with minisign
+fn verify_signature(public_key: &str, manifest: &Path, signature: &Path) -> Result<(), String> {
+	let output = Command::new("minisign")
+		.arg("-V")
+		.arg("-P")
+		.arg(public_key)
+		.arg("-m")
+		.arg(manifest)
+		.arg("-x")
+		.arg(signature)
+		.output()
+		.map_err(|e| format!("Verifying the release needs minisign installed: {}", e))?;
+	if !output.status.success() {
+		return Err(format!(
+			"The release manifest's signature is invalid: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		));
+	}
+	Ok(())
+}
+
+/// Fetch the manifest of the latest release and verify its signature
+pub fn latest_release(config: &UpdateConfig) -> Result<Manifest, String> {
+	let url = config
+		.url
+		.as_ref()
+		.ok_or("No release url, set url in [update]".to_string())?;
+	let public_key = config
+		.public_key
+		.as_ref()
+		.ok_or("No public key to verify releases with, set public_key in [update]".to_string())?;
+	let manifest = download(url)?;
+	let signature = download(&format!("{}.minisig", url))?;
+	let dir = Path::new(&config.staging_dir);
+	let manifest_path = dir.join("manifest.json");
+	let signature_path = dir.join("manifest.json.minisig");
+	write_file(&manifest_path, &manifest)?;
+	write_file(&signature_path, &signature)?;
+	verify_signature(public_key, &manifest_path, &signature_path)?;
+	serde_json::from_slice(&manifest).map_err(|e| format!("Invalid release manifest: {}", e))
+}
+
+/// Download the files of the release for the target to the staging
+/// directory, checking them against the manifest, to be swapped in at the
+/// next start
+pub fn stage(config: &UpdateConfig, manifest: &Manifest, target: &str) -> Result<(), String> {
+	let files = manifest.files_for(target);
+	if files.is_empty() {
+		return Err(format!(
+			"Release {} has no files for {}",
+			manifest.version, target
+		));
+	}
+	let dir = Path::new(&config.staging_dir).join(&manifest.version);
+	for f in &files {
+		if !valid_name(&f.name) {
+			return Err(format!("Invalid file name in the release manifest: {}", f.name));
+		}
+		let contents = download(&f.url)?;
+		let sha256 = sha256_hex(&contents);
+		if sha256 != f.sha256.trim().to_lowercase() {
+			return Err(format!(
+				"Checksum mismatch for {}: expected {}, got {}",
+				f.name, f.sha256, sha256
+			));
+		}
+		write_file(&dir.join(&f.name), &contents)?;
+		println!("Downloaded {}", f.name);
+	}
+	let staged = Manifest {
+		version: manifest.version.clone(),
+		files: files.into_iter().cloned().collect(),
+	};
+	write_file(
+		&Path::new(&config.staging_dir).join(STAGED_MANIFEST),
+		serde_json::to_string_pretty(&staged).unwrap().as_bytes(),
+	)
+}
+
+/// Replace the file at the path with the staged one. The file in use is
+/// moved aside first, which works for a running binary too.
+fn swap_in(staged: &Path, path: &Path) -> Result<(), String> {
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+	}
+	if path.exists() {
+		let mut old = path.as_os_str().to_owned();
+		old.push(".old");
+		let old = PathBuf::from(old);
+		let _ = fs::remove_file(&old);
+		fs::rename(path, &old).map_err(|e| format!("Unable to move {} aside: {}", path.display(), e))?;
+	}
+	// the staging directory may be on another filesystem
+	fs::copy(staged, path).map_err(|e| format!("Unable to install {}: {}", path.display(), e))?;
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o755));
+	}
+	Ok(())
+}
+
+/// Swap in the release staged by the update subcommand, if there's one.
+/// Returns the version installed if the binary itself was replaced, the
+/// miner then has to be started again to run it.
+pub fn apply_staged(config: &UpdateConfig) -> Result<Option<String>, String> {
+	let staging = Path::new(&config.staging_dir);
+	let manifest_path = staging.join(STAGED_MANIFEST);
+	if !manifest_path.exists() {
+		return Ok(None);
+	}
+	let manifest: Manifest = fs::read(&manifest_path)
+		.map_err(|e| format!("{}", e))
+		.and_then(|m| serde_json::from_slice(&m).map_err(|e| format!("{}", e)))
+		.map_err(|e| format!("Invalid staged release {}: {}", manifest_path.display(), e))?;
+	let exe = env::current_exe().map_err(|e| format!("Unable to find the binary: {}", e))?;
+	let exe_dir = exe
+		.parent()
+		.ok_or("Unable to find the binary's directory".to_string())?
+		.to_path_buf();
+	// staged.json isn't signed, check everything again before installing
+	// any of it
+	let release_dir = staging.join(&manifest.version);
+	for f in &manifest.files {
+		if !valid_name(&f.name) {
+			return Err(format!("Invalid file name in the staged release: {}", f.name));
+		}
+		let staged = release_dir.join(&f.name);
+		let contents =
+			fs::read(&staged).map_err(|e| format!("Unable to read {}: {}", staged.display(), e))?;
+		let sha256 = sha256_hex(&contents);
+		if sha256 != f.sha256.trim().to_lowercase() {
+			return Err(format!(
+				"Checksum mismatch for staged {}: expected {}, got {}",
+				f.name, f.sha256, sha256
+			));
+		}
+	}
+	let mut binary_replaced = false;
+	for f in &manifest.files {
+		let path = exe_dir.join(&f.name);
+		swap_in(&release_dir.join(&f.name), &path)?;
+		info!(LOGGER, "Installed {} of release {}", f.name, manifest.version);
+		if path == exe {
+			binary_replaced = true;
+		}
+	}
+	let _ = fs::remove_file(&manifest_path);
+	let _ = fs::remove_dir_all(&release_dir);
+	warn!(LOGGER, "Updated to grin-miner {}", manifest.version);
+	Ok(if binary_replaced {
+		Some(manifest.version)
+	} else {
+		None
+	})
+}
+
+/// Run the binary again with the same arguments, in place of this process
+#[cfg(unix)]
+pub fn restart() -> Result<(), String> {
+	use std::os::unix::process::CommandExt;
+	let exe = env::current_exe().map_err(|e| format!("{}", e))?;
+	let e = Command::new(&exe).args(env::args().skip(1)).exec();
+	Err(format!("Unable to run {}: {}", exe.display(), e))
+}
+
+/// Run the binary again with the same arguments, exiting with its status
+/// once it's done
+#[cfg(windows)]
+pub fn restart() -> Result<(), String> {
+	let exe = env::current_exe().map_err(|e| format!("{}", e))?;
+	let status = Command::new(&exe)
+		.args(env::args().skip(1))
+		.status()
+		.map_err(|e| format!("Unable to run {}: {}", exe.display(), e))?;
+	::std::process::exit(status.code().unwrap_or(1));
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn versions() {
+		assert!(is_newer("1.0.10", "1.0.2"));
+		assert!(is_newer("v2.0.0", "1.9.9"));
+		assert!(is_newer("1.0.1", "1.0"));
+		assert!(!is_newer("1.0.0", "1.0.0"));
+		assert!(!is_newer("0.9.9", "1.0.0"));
+	}
+
+	#[test]
+	fn file_names() {
+		assert!(valid_name("grin-miner"));
+		assert!(valid_name("plugins/cuckatoo_lean_cpu_compat_31.cuckooplugin"));
+		assert!(!valid_name("../grin-miner"));
+		assert!(!valid_name("plugins/../../bin/sh"));
+		assert!(!valid_name("plugins\\..\\x"));
+		assert!(!valid_name("/usr/bin/grin-miner"));
+	}
+}
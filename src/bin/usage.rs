@@ -0,0 +1,175 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Anonymous usage statistics, for plugin authors to see which devices
+//! and plugins are mined with and how fast. Nothing is sent unless
+//! [usage_stats] is switched on. A report only holds the miner version,
+//! the OS, and per GPU model and plugin the number of devices and their
+//! average GPS: no device ids, addresses, pool, login or rig name.
+//! `--telemetry-status` shows the report as it would be sent.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use built_info;
+use config::{MinerConfig, UsageStatsConfig};
+use http;
+use serde_json;
+use stats::Stats;
+use util::cuckoo_miner::CuckooMinerDeviceStats;
+use util::LOGGER;
+
+/// Devices of a model mining with a plugin
+#[derive(Debug, Serialize)]
+pub struct DeviceUsage {
+	/// Device name, as the plugin reports it, e.g. GeForce GTX 1080 Ti
+	pub model: String,
+	/// Plugin, e.g. cuckatoo_lean_cuda_31
+	pub plugin: String,
+	pub count: u32,
+	/// Average GPS of the devices over the session, none until measured
+	pub avg_gps: Option<f64>,
+}
+
+/// A usage report, all that's sent
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+	pub version: String,
+	pub os: String,
+	pub arch: String,
+	pub devices: Vec<DeviceUsage>,
+}
+
+/// The report of the devices in use of each plugin, with the session
+/// GPS of each device as `gps` gives it, if measured
+pub fn report(
+	config: &MinerConfig,
+	plugins: &[Vec<CuckooMinerDeviceStats>],
+	gps: &dyn Fn(usize, &str) -> Option<f64>,
+) -> UsageReport {
+	// (model, plugin) -> (devices, gps sum, devices measured)
+	let mut groups: BTreeMap<(String, String), (u32, f64, u32)> = BTreeMap::new();
+	for (i, devices) in plugins.iter().enumerate() {
+		let plugin = match config.miner_plugin_config.get(i) {
+			Some(p) => format!("{}_{}", p.type_filter, p.edge_bits),
+			None => continue,
+		};
+		for d in devices.iter().filter(|d| d.in_use == 1) {
+			let group = groups
+				.entry((d.device_name.trim().to_string(), plugin.clone()))
+				.or_insert((0, 0.0, 0));
+			group.0 += 1;
+			if let Some(g) = gps(i, &d.device_id).filter(|g| *g > 0.0) {
+				group.1 += g;
+				group.2 += 1;
+			}
+		}
+	}
+	UsageReport {
+		version: built_info::PKG_VERSION.to_string(),
+		os: env::consts::OS.to_string(),
+		arch: env::consts::ARCH.to_string(),
+		devices: groups
+			.into_iter()
+			.map(|((model, plugin), (count, sum, measured))| DeviceUsage {
+				model: model,
+				plugin: plugin,
+				count: count,
+				// rounded, the exact figure tells nothing more
+				avg_gps: if measured == 0 {
+					None
+				} else {
+					Some((sum / measured as f64 * 100.0).round() / 100.0)
+				},
+			})
+			.collect(),
+	}
+}
+
+/// The report of the stats of a running miner
+pub fn report_from_stats(config: &MinerConfig, stats: &Stats) -> UsageReport {
+	let now = time::get_time().sec;
+	let plugins = stats.mining_stats.device_stats.clone().unwrap_or_default();
+	report(config, &plugins, &|plugin, device_id| {
+		stats
+			.mining_stats
+			.device_gps_averages(plugin, device_id, now)
+			.map(|a| a.avg_session)
+	})
+}
+
+/// Sends the usage statistics, when switched on
+pub struct UsageReporter {
+	config: UsageStatsConfig,
+	mining_config: MinerConfig,
+	stats: Arc<RwLock<Stats>>,
+}
+
+impl UsageReporter {
+	/// Create a new reporter
+	pub fn new(
+		config: UsageStatsConfig,
+		mining_config: MinerConfig,
+		stats: Arc<RwLock<Stats>>,
+	) -> UsageReporter {
+		UsageReporter {
+			config: config,
+			mining_config: mining_config,
+			stats: stats,
+		}
+	}
+
+	/// Start reporting in a new thread, the first report once an interval
+	/// has been mined
+	pub fn start(self) {
+		if self.config.url.is_empty() {
+			warn!(LOGGER, "Usage statistics switched on without a url, not sending them");
+			return;
+		}
+		info!(
+			LOGGER,
+			"Sending anonymous usage statistics to {} every {} hours (see --telemetry-status)",
+			self.config.url,
+			self.config.interval_hours
+		);
+		let _ = thread::Builder::new()
+			.name("usage_stats".to_string())
+			.spawn(move || loop {
+				thread::sleep(Duration::from_secs(self.config.interval_hours.max(1) * 3600));
+				if let Err(e) = self.send() {
+					warn!(LOGGER, "Unable to send usage statistics: {}", e);
+				}
+			});
+	}
+
+	fn send(&self) -> Result<(), String> {
+		let report = {
+			let stats = self.stats.read().unwrap();
+			report_from_stats(&self.mining_config, &stats)
+		};
+		let body = serde_json::to_string(&report).map_err(|e| format!("{}", e))?;
+		match http::post(&self.config.url, "application/json", &body)? {
+			c if c < 300 => {
+				debug!(LOGGER, "Sent usage statistics: {}", body);
+				Ok(())
+			}
+			c => Err(format!("status {}", c)),
+		}
+	}
+}
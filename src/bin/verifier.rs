@@ -0,0 +1,252 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Solution verifier, independent of the plugins: checks a solution is a
+//! 42-cycle in the cuckatoo or cuckaroo graph of its header, as the node
+//! does
+
+use chain::Chain;
+
+/// Length of a cycle
+pub const PROOF_SIZE: usize = 42;
+
+/// Edges of a cuckaroo siphash block
+const EDGE_BLOCK_BITS: u64 = 6;
+const EDGE_BLOCK_SIZE: u64 = 1 << EDGE_BLOCK_BITS;
+const EDGE_BLOCK_MASK: u64 = EDGE_BLOCK_SIZE - 1;
+
+/// Graph algorithm of a plugin, from its type filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+	Cuckatoo,
+	Cuckaroo,
+}
+
+impl Algorithm {
+	pub fn from_type_filter(type_filter: &str) -> Option<Algorithm> {
+		if type_filter.starts_with("cuckatoo") {
+			Some(Algorithm::Cuckatoo)
+		} else if type_filter.starts_with("cuckaroo") {
+			Some(Algorithm::Cuckaroo)
+		} else {
+			None
+		}
+	}
+}
+
+/// Siphash keys of a header, from its hash on the chain
+fn siphash_keys(chain: &dyn Chain, pre_pow: &[u8], nonce: u64) -> [u64; 4] {
+	let hash = chain.header_hash(pre_pow, nonce);
+	let mut keys = [0u64; 4];
+	for (i, k) in keys.iter_mut().enumerate() {
+		let mut bytes = [0u8; 8];
+		bytes.copy_from_slice(&hash[i * 8..i * 8 + 8]);
+		*k = u64::from_le_bytes(bytes);
+	}
+	keys
+}
+
+/// SipHash-2-4 state, initialised with the keys as they are
+struct SipHash24 {
+	v: [u64; 4],
+}
+
+impl SipHash24 {
+	fn new(keys: &[u64; 4]) -> SipHash24 {
+		SipHash24 { v: *keys }
+	}
+
+	fn round(&mut self) {
+		let v = &mut self.v;
+		v[0] = v[0].wrapping_add(v[1]);
+		v[2] = v[2].wrapping_add(v[3]);
+		v[1] = v[1].rotate_left(13);
+		v[3] = v[3].rotate_left(16);
+		v[1] ^= v[0];
+		v[3] ^= v[2];
+		v[0] = v[0].rotate_left(32);
+		v[2] = v[2].wrapping_add(v[1]);
+		v[0] = v[0].wrapping_add(v[3]);
+		v[1] = v[1].rotate_left(17);
+		v[3] = v[3].rotate_left(21);
+		v[1] ^= v[2];
+		v[3] ^= v[0];
+		v[2] = v[2].rotate_left(32);
+	}
+
+	fn hash24(&mut self, nonce: u64) {
+		self.v[3] ^= nonce;
+		self.round();
+		self.round();
+		self.v[0] ^= nonce;
+		self.v[2] ^= 0xff;
+		for _ in 0..4 {
+			self.round();
+		}
+	}
+
+	fn xor_lanes(&self) -> u64 {
+		self.v[0] ^ self.v[1] ^ self.v[2] ^ self.v[3]
+	}
+}
+
+fn siphash24(keys: &[u64; 4], nonce: u64) -> u64 {
+	let mut sip = SipHash24::new(keys);
+	sip.hash24(nonce);
+	sip.xor_lanes()
+}
+
+/// Cuckaroo edge: the siphash of its block, xored with the block's last
+fn siphash_block(keys: &[u64; 4], edge: u64) -> u64 {
+	let first = edge & !EDGE_BLOCK_MASK;
+	let mut sip = SipHash24::new(keys);
+	let mut block = [0u64; EDGE_BLOCK_SIZE as usize];
+	for (i, b) in block.iter_mut().enumerate() {
+		sip.hash24(first + i as u64);
+		*b = sip.xor_lanes();
+	}
+	let last = block[EDGE_BLOCK_MASK as usize];
+	if edge & EDGE_BLOCK_MASK == EDGE_BLOCK_MASK {
+		last
+	} else {
+		block[(edge & EDGE_BLOCK_MASK) as usize] ^ last
+	}
+}
+
+/// Check the edges (nonces) of a solution form a single 42-cycle in the
+/// graph of the header
+pub fn verify(
+	chain: &dyn Chain,
+	algorithm: Algorithm,
+	edge_bits: u8,
+	pre_pow: &[u8],
+	nonce: u64,
+	edges: &[u64],
+) -> Result<(), String> {
+	if edges.len() != PROOF_SIZE {
+		return Err(format!("{} edges, expected {}", edges.len(), PROOF_SIZE));
+	}
+	let keys = siphash_keys(chain, pre_pow, nonce);
+	let num_edges = 1u64 << edge_bits;
+	let edge_mask = num_edges - 1;
+	let mut uvs = [0u64; 2 * PROOF_SIZE];
+	let (mut xor0, mut xor1) = (0u64, 0u64);
+	for (n, &edge) in edges.iter().enumerate() {
+		if edge >= num_edges {
+			return Err(format!("edge {} too big", edge));
+		}
+		if n > 0 && edge <= edges[n - 1] {
+			return Err("edges not in ascending order".to_string());
+		}
+		let (u, v) = match algorithm {
+			Algorithm::Cuckatoo => (
+				siphash24(&keys, 2 * edge) & edge_mask,
+				siphash24(&keys, 2 * edge + 1) & edge_mask,
+			),
+			Algorithm::Cuckaroo => {
+				let sip = siphash_block(&keys, edge);
+				(sip & edge_mask, (sip >> 32) & edge_mask)
+			}
+		};
+		uvs[2 * n] = u;
+		uvs[2 * n + 1] = v;
+		xor0 ^= u;
+		xor1 ^= v;
+	}
+	if xor0 | xor1 != 0 {
+		return Err("endpoints don't match up".to_string());
+	}
+	// follow the cycle from the first edge, through each node shared by
+	// exactly two edges
+	let mut n = 0;
+	let mut i = 0;
+	loop {
+		let mut j = i;
+		let mut k = i;
+		loop {
+			k = (k + 2) % (2 * PROOF_SIZE);
+			if k == i {
+				break;
+			}
+			if uvs[k] == uvs[i] {
+				if j != i {
+					return Err("branch in cycle".to_string());
+				}
+				j = k;
+			}
+		}
+		if j == i {
+			return Err("cycle dead ends".to_string());
+		}
+		i = j ^ 1;
+		n += 1;
+		if i == 0 {
+			break;
+		}
+	}
+	if n == PROOF_SIZE {
+		Ok(())
+	} else {
+		Err(format!("cycle of length {}, not {}", n, PROOF_SIZE))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chain::Grin;
+
+	/// 42-cycles of the 12 edge bits graphs of an all zero header, nonce 157
+	const CUCKATOO_12: [u64; PROOF_SIZE] = [
+		33, 276, 525, 602, 630, 813, 1023, 1122, 1210, 1263, 1326, 1340, 1362, 1413, 1531, 1767,
+		1913, 1984, 2013, 2105, 2154, 2386, 2461, 2467, 2538, 2813, 2896, 3067, 3132, 3156, 3225,
+		3283, 3379, 3396, 3443, 3547, 3604, 3763, 3787, 3950, 4027, 4059,
+	];
+	const CUCKAROO_12: [u64; PROOF_SIZE] = [
+		31, 101, 223, 260, 313, 318, 530, 533, 584, 595, 744, 1181, 1274, 1413, 1442, 1469, 1534,
+		1591, 1616, 1625, 1765, 1825, 1949, 2228, 2382, 2499, 2528, 2739, 2843, 2852, 3078, 3095,
+		3155, 3327, 3427, 3537, 3542, 3568, 3632, 3784, 3895, 4013,
+	];
+
+	#[test]
+	fn test_siphash() {
+		assert_eq!(siphash24(&[1, 2, 3, 4], 10), 928382149599306901);
+		assert_eq!(siphash24(&[1, 2, 3, 4], 111), 10524991083049122233);
+		assert_eq!(siphash24(&[9, 7, 6, 7], 12), 1305683875471634734);
+		assert_eq!(siphash24(&[9, 7, 6, 7], 10), 11589833042187638814);
+	}
+
+	#[test]
+	fn test_verify() {
+		let header = [0u8; 80];
+		assert!(verify(&Grin, Algorithm::Cuckatoo, 12, &header, 157, &CUCKATOO_12).is_ok());
+		assert!(verify(&Grin, Algorithm::Cuckaroo, 12, &header, 157, &CUCKAROO_12).is_ok());
+		// another header's graph
+		assert!(verify(&Grin, Algorithm::Cuckatoo, 12, &header, 156, &CUCKATOO_12).is_err());
+		// the other algorithm's graph
+		assert!(verify(&Grin, Algorithm::Cuckaroo, 12, &header, 157, &CUCKATOO_12).is_err());
+	}
+
+	#[test]
+	fn test_verify_flipped_edge() {
+		let header = [0u8; 80];
+		let mut edges = CUCKATOO_12;
+		edges[5] ^= 1;
+		assert!(verify(&Grin, Algorithm::Cuckatoo, 12, &header, 157, &edges).is_err());
+		let mut edges = CUCKAROO_12;
+		edges[5] ^= 1;
+		assert!(verify(&Grin, Algorithm::Cuckaroo, 12, &header, 157, &edges).is_err());
+		assert!(verify(&Grin, Algorithm::Cuckatoo, 12, &header, 157, &CUCKATOO_12[1..]).is_err());
+	}
+}
@@ -0,0 +1,154 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check of the wallet a node builds its coinbases with, for solo mining.
+//! The node asks the wallet's foreign API for the coinbase of every block
+//! template, so a wallet that's down or misconfigured only shows once a
+//! block is found, and lost. The miner checks the wallet answers at
+//! startup instead, and fails fast if it doesn't: with the v2 API's
+//! check_version, building no coinbase. Wallets with only the v1 API are
+//! asked for a coinbase the way the node does, but with a fixed key id,
+//! so no new key is derived: they only record an unconfirmed output of
+//! that key at height 0, which never confirms.
+
+use serde_json::{self, Value};
+
+use http;
+
+/// Foreign API url of a wallet listener, as given in the node's config
+fn base_url(url: &str) -> Result<String, String> {
+	let url = url.trim().trim_right_matches('/');
+	let with_scheme = if url.starts_with("http://") || url.starts_with("https://") {
+		url.to_string()
+	} else if url.contains("://") {
+		return Err(format!("wallet_listener_url {} isn't an http(s) url", url));
+	} else {
+		format!("http://{}", url)
+	};
+	let authority = with_scheme.splitn(2, "://").nth(1).unwrap_or("");
+	if authority.is_empty() || authority.starts_with(':') || authority.contains('/') {
+		return Err(format!(
+			"wallet_listener_url {} should be the listener's address, e.g. \
+			 http://127.0.0.1:3415",
+			url
+		));
+	}
+	Ok(with_scheme)
+}
+
+/// The output and kernel of a coinbase, checked to be there
+fn check_coinbase(coinbase: &Value) -> Result<(), String> {
+	let commit = coinbase["output"]["commit"].as_str();
+	let excess = coinbase["kernel"]["excess"].as_str();
+	if commit.map_or(true, |c| c.is_empty()) || excess.map_or(true, |e| e.is_empty()) {
+		return Err(format!(
+			"the wallet answered without a coinbase output and kernel: {}",
+			coinbase
+		));
+	}
+	Ok(())
+}
+
+/// Key id of the coinbase v1 wallets are asked for, m/0/0
+const CHECK_KEY_ID: &str = "0200000000000000000000000000000000";
+
+/// Ask the wallet for its version through the v2 (JSON-RPC) foreign API,
+/// none if the wallet only has the v1 API
+fn check_version_v2(url: &str) -> Result<Option<()>, String> {
+	let request = json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": "check_version",
+		"params": []
+	});
+	let (code, body) = http::post_json(&format!("{}/v2/foreign", url), &request.to_string())?;
+	match code {
+		200 => {}
+		404 => return Ok(None),
+		c => return Err(format!("the wallet's foreign API answered HTTP {}", c)),
+	}
+	let response: Value =
+		serde_json::from_str(&body).map_err(|e| format!("invalid answer from the wallet: {}", e))?;
+	if !response["error"].is_null() {
+		return Err(format!("the wallet couldn't tell its version: {}", response["error"]));
+	}
+	let result = &response["result"];
+	if !result["Err"].is_null() {
+		return Err(format!("the wallet couldn't tell its version: {}", result["Err"]));
+	}
+	match result["Ok"]["foreign_api_version"].as_u64() {
+		Some(2) => Ok(Some(())),
+		Some(v) => Err(format!("the wallet has foreign API version {}, not 2", v)),
+		None => Err(format!("the wallet answered without its API version: {}", result)),
+	}
+}
+
+/// Ask the wallet for a coinbase of the fixed key through the v1 foreign
+/// API, as older nodes and wallets do
+fn build_coinbase_v1(url: &str) -> Result<(), String> {
+	let request = json!({"fees": 0, "height": 0, "key_id": CHECK_KEY_ID});
+	let (code, body) = http::post_json(
+		&format!("{}/v1/wallet/foreign/build_coinbase", url),
+		&request.to_string(),
+	)?;
+	if code != 200 {
+		return Err(format!("the wallet's foreign API answered HTTP {}: {}", code, body.trim()));
+	}
+	let coinbase: Value =
+		serde_json::from_str(&body).map_err(|e| format!("invalid answer from the wallet: {}", e))?;
+	check_coinbase(&coinbase)
+}
+
+/// Check the wallet listener answers the foreign API the node builds
+/// coinbases with, returning why not if it doesn't
+pub fn check(wallet_listener_url: &str) -> Result<(), String> {
+	let url = base_url(wallet_listener_url)?;
+	let result = match check_version_v2(&url) {
+		Ok(Some(())) => Ok(()),
+		Ok(None) => build_coinbase_v1(&url),
+		Err(e) => Err(e),
+	};
+	result.map_err(|e| {
+		format!(
+			"Solo mining wallet at {} can't be used for the coinbase, blocks found \
+			 would be lost: {}",
+			url, e
+		)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_base_url() {
+		assert_eq!(base_url("127.0.0.1:3415").unwrap(), "http://127.0.0.1:3415");
+		assert_eq!(base_url("http://127.0.0.1:3415/").unwrap(), "http://127.0.0.1:3415");
+		assert!(base_url("tcp://127.0.0.1:3415").is_err());
+		assert!(base_url("http://").is_err());
+		assert!(base_url("http://127.0.0.1:3415/v1/wallet").is_err());
+	}
+
+	#[test]
+	fn test_check_coinbase() {
+		let coinbase = json!({
+			"output": {"features": "Coinbase", "commit": "08a1", "proof": "bd"},
+			"kernel": {"features": "Coinbase", "excess": "09c3", "excess_sig": "66"},
+			"key_id": "0300"
+		});
+		assert!(check_coinbase(&coinbase).is_ok());
+		assert!(check_coinbase(&json!({"output": {}})).is_err());
+	}
+}
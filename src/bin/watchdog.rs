@@ -0,0 +1,131 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Share watchdog, triggering when no share has been accepted for a while
+//! although the miner is connected and mining, and running the recovery
+//! command configured or restarting mining internally
+
+use std::process::Command;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use clock;
+use config::WatchdogConfig;
+use stats::Stats;
+use types::MinerMessage;
+use util::LOGGER;
+
+/// Seconds between checks
+const CHECK_INTERVAL_SECS: u64 = 10;
+
+/// Runs the recovery action when shares stop being accepted
+pub struct ShareWatchdog {
+	config: WatchdogConfig,
+	stats: Arc<RwLock<Stats>>,
+	miner_tx: mpsc::Sender<MinerMessage>,
+}
+
+impl ShareWatchdog {
+	/// Create a new share watchdog
+	pub fn new(
+		config: WatchdogConfig,
+		stats: Arc<RwLock<Stats>>,
+		miner_tx: mpsc::Sender<MinerMessage>,
+	) -> ShareWatchdog {
+		ShareWatchdog {
+			config: config,
+			stats: stats,
+			miner_tx: miner_tx,
+		}
+	}
+
+	/// Start watching in a new thread
+	pub fn start(self) {
+		info!(
+			LOGGER,
+			"Share watchdog: {} after {} minutes without an accepted share",
+			match self.config.command {
+				Some(ref c) => format!("running {}", c),
+				None => "restarting mining".to_string(),
+			},
+			self.config.no_share_minutes
+		);
+		let _ = thread::Builder::new()
+			.name("watchdog".to_string())
+			.spawn(move || {
+				// time since which shares are expected
+				let mut since = clock::mono_secs();
+				let mut last_accepted = 0;
+				loop {
+					thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS));
+					let now = clock::mono_secs();
+					let (mining, accepted, server_url) = {
+						let stats = self.stats.read().unwrap();
+						(
+							stats.client_stats.connected && stats.client_stats.logged_in
+								&& !stats.mining_stats.paused,
+							stats.client_stats.shares_accepted,
+							stats.client_stats.server_url.clone(),
+						)
+					};
+					// not expected to find shares while disconnected or
+					// paused, the wait starts over
+					if !mining {
+						since = now;
+						continue;
+					}
+					if accepted != last_accepted {
+						last_accepted = accepted;
+						since = now;
+					}
+					if now - since < self.config.no_share_minutes as i64 * 60 {
+						continue;
+					}
+					let reason = format!(
+						"no share accepted for {} minutes while connected to {}",
+						(now - since) / 60,
+						server_url
+					);
+					self.trigger(&reason);
+					since = clock::mono_secs();
+				}
+			});
+	}
+
+	fn trigger(&self, reason: &str) {
+		self.stats.write().unwrap().history.watchdog_triggers += 1;
+		let command = match self.config.command {
+			Some(ref c) => c,
+			None => {
+				warn!(LOGGER, "Share watchdog triggered, {}, restarting mining", reason);
+				let _ = self
+					.miner_tx
+					.send(MinerMessage::Restart(format!("share watchdog, {}", reason)));
+				return;
+			}
+		};
+		warn!(LOGGER, "Share watchdog triggered, {}, running {}", reason, command);
+		let result = if cfg!(windows) {
+			Command::new("cmd").arg("/C").arg(command).status()
+		} else {
+			Command::new("sh").arg("-c").arg(command).status()
+		};
+		match result {
+			Ok(status) if status.success() => info!(LOGGER, "Watchdog command {} finished", command),
+			Ok(status) => error!(LOGGER, "Watchdog command {} failed: {}", command, status),
+			Err(e) => error!(LOGGER, "Unable to run watchdog command {}: {}", command, e),
+		}
+	}
+}
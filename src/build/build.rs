@@ -12,9 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Build hooks to spit out version+build time info
+//! Build hooks to spit out version+build time info, and the gRPC service
+//! code with the grpc feature
 
 extern crate built;
+#[cfg(feature = "grpc")]
+extern crate protoc_grpcio;
 
 use std::env;
 
@@ -26,4 +29,27 @@ fn main() {
 		env!("CARGO_MANIFEST_DIR"),
 		format!("{}{}", env::var("OUT_DIR").unwrap(), "/built.rs"),
 	).expect("Failed to acquire build-time information");
+	#[cfg(feature = "grpc")]
+	compile_protos();
+}
+
+/// grin_miner.rs and grin_miner_grpc.rs in OUT_DIR, from
+/// proto/grin_miner.proto
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+	use std::fs;
+
+	let out_dir = env::var("OUT_DIR").unwrap();
+	protoc_grpcio::compile_grpc_protos(&["grin_miner.proto"], &["proto"], &out_dir, None)
+		.expect("Failed to compile proto/grin_miner.proto");
+	// the generated files are include!d, which doesn't take inner attributes
+	for file in &["grin_miner.rs", "grin_miner_grpc.rs"] {
+		let path = format!("{}/{}", out_dir, file);
+		let code = fs::read_to_string(&path).unwrap();
+		let code: Vec<&str> = code
+			.lines()
+			.filter(|l| !l.starts_with("#![") && !l.starts_with("//!"))
+			.collect();
+		fs::write(&path, code.join("\n")).unwrap();
+	}
 }
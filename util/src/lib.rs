@@ -23,10 +23,12 @@
 
 extern crate backtrace;
 extern crate byteorder;
+extern crate flate2;
 extern crate rand;
 #[macro_use]
 extern crate slog;
 extern crate slog_async;
+extern crate slog_json;
 extern crate slog_term;
 
 #[macro_use]
@@ -42,11 +44,15 @@ pub use cuckoo_ as cuckoo_miner;
 
 // Logging related
 pub mod logger;
-pub use logger::{init_logger, init_test_logger, LOGGER};
+pub use logger::{device_logger, init_logger, init_test_logger, log_level, logging_config,
+                 recent_errors, recent_log_lines, set_log_level, set_log_levels, LOGGER};
 
 pub mod types;
 pub use types::{LogLevel, LoggingConfig};
 
+mod rotate;
+pub use rotate::RotatingFile;
+
 // other utils
 #[allow(unused_imports)]
 use std::ops::Deref;
@@ -12,16 +12,25 @@
 // limitations under the License.
 
 //! Logging wrapper to be used throughout all crates in the workspace
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
 use std::fs::OpenOptions;
+use std::io::Write;
 use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::ops::Deref;
-use slog::{Discard, Drain, Duplicate, Level, LevelFilter, Logger};
+use slog::{self, Discard, Drain, Duplicate, FnValue, Key, Level, Logger, Never, OwnedKVList,
+           PushFnValue, Record, Serializer, KV};
 use slog_term;
 use slog_async;
+use slog_json;
 
 use backtrace::Backtrace;
 use std::{panic, thread};
 
+use rotate::RotatingFile;
 use types::{LogLevel, LoggingConfig};
 
 fn convert_log_level(in_level: &LogLevel) -> Level {
@@ -35,7 +44,238 @@ fn convert_log_level(in_level: &LogLevel) -> Level {
 	}
 }
 
+fn convert_slog_level(in_level: Level) -> LogLevel {
+	match in_level {
+		Level::Info => LogLevel::Info,
+		Level::Critical => LogLevel::Critical,
+		Level::Warning => LogLevel::Warning,
+		Level::Debug => LogLevel::Debug,
+		Level::Trace => LogLevel::Trace,
+		Level::Error => LogLevel::Error,
+	}
+}
+
+/// Current stdout log level, as a slog level index
+static STDOUT_LEVEL: AtomicUsize = AtomicUsize::new(0);
+/// Current file log level, as a slog level index
+static FILE_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+/// Level filter whose level can be changed while the logger is running
+struct RuntimeLevelFilter<D: Drain> {
+	drain: D,
+	level: &'static AtomicUsize,
+}
+
+impl<D: Drain> Drain for RuntimeLevelFilter<D> {
+	type Ok = Option<D::Ok>;
+	type Err = D::Err;
+
+	fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+		let level = Level::from_usize(self.level.load(Ordering::Relaxed)).unwrap_or(Level::Info);
+		if record.level().is_at_least(level) {
+			self.drain.log(record, values).map(Some)
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+/// JSON lines drain, one object per record with the message as "event",
+/// followed by any key/values given at the logging call site
+fn json_drain<W: Write + Send + 'static>(w: W) -> slog_json::Json<W> {
+	slog_json::Json::new(w)
+		.set_newlines(true)
+		.add_key_value(o!(
+			"ts" => FnValue(|_: &Record| {
+				SystemTime::now()
+					.duration_since(UNIX_EPOCH)
+					.map(|d| d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1000000)
+					.unwrap_or(0)
+			}),
+			"level" => FnValue(|r: &Record| r.level().as_str()),
+			"event" => PushFnValue(|r: &Record, ser| ser.emit(r.msg())),
+			"module" => FnValue(|r: &Record| r.module()),
+		))
+		.build()
+}
+
+/// Picks out the value of the "device" key, if any
+struct DeviceSerializer(Option<String>);
+
+impl Serializer for DeviceSerializer {
+	fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+		if key == "device" {
+			self.0 = Some(format!("{}", val));
+		}
+		Ok(())
+	}
+}
+
+/// Device ("plugin:device") a record originates from, either given at the
+/// logging call site or by the logger (see device_logger)
+fn record_device(record: &Record, values: &OwnedKVList) -> Option<String> {
+	let mut serializer = DeviceSerializer(None);
+	let _ = values.serialize(record, &mut serializer);
+	let _ = record.kv().serialize(record, &mut serializer);
+	serializer.0
+}
+
+/// Drops records from devices not in the filter, records not attached to
+/// a device always pass
+struct DeviceFilter<D: Drain> {
+	drain: D,
+	devices: Option<Vec<String>>,
+}
+
+impl<D: Drain> Drain for DeviceFilter<D> {
+	type Ok = Option<D::Ok>;
+	type Err = D::Err;
+
+	fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+		if let Some(ref devices) = self.devices {
+			if let Some(device) = record_device(record, values) {
+				if !devices.contains(&device) {
+					return Ok(None);
+				}
+			}
+		}
+		self.drain.log(record, values).map(Some)
+	}
+}
+
+/// Path of the log file for a device, e.g. grin-miner-device-0-1.log
+fn device_log_path(path: &str, device: &str) -> String {
+	let device = device.replace(':', "-").replace('/', "_");
+	match path.rfind('.') {
+		Some(i) if !path[i..].contains('/') => {
+			format!("{}-device-{}{}", &path[..i], device, &path[i..])
+		}
+		_ => format!("{}-device-{}", path, device),
+	}
+}
+
+/// Writes records attached to a device into a log file per device
+struct DeviceFilesDrain {
+	path: String,
+	append: bool,
+	drains: Mutex<HashMap<String, slog_term::FullFormat<slog_term::PlainDecorator<File>>>>,
+}
+
+impl Drain for DeviceFilesDrain {
+	type Ok = ();
+	type Err = Never;
+
+	fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Never> {
+		let device = match record_device(record, values) {
+			Some(d) => d,
+			None => return Ok(()),
+		};
+		let mut drains = self.drains.lock().unwrap();
+		if !drains.contains_key(&device) {
+			let file = OpenOptions::new()
+				.create(true)
+				.write(true)
+				.append(self.append)
+				.truncate(!self.append)
+				.open(device_log_path(&self.path, &device));
+			match file {
+				Ok(f) => {
+					let decorator = slog_term::PlainDecorator::new(f);
+					drains.insert(device.clone(), slog_term::FullFormat::new(decorator).build());
+				}
+				Err(_) => return Ok(()),
+			}
+		}
+		let _ = drains[&device].log(record, values);
+		Ok(())
+	}
+}
+
+/// Child of the global logger attaching the given device to every record,
+/// for logging originating from a solver instance
+pub fn device_logger(plugin: usize, device: &str) -> Logger {
+	LOGGER.new(o!("device" => format!("{}:{}", plugin, device)))
+}
+
+/// Number of most recent error messages kept
+pub const RECENT_ERRORS_LEN: usize = 50;
+
+/// Drain keeping the most recent error (and critical) messages, regardless
+/// of the configured log levels
+struct ErrorHistoryDrain;
+
+impl Drain for ErrorHistoryDrain {
+	type Ok = ();
+	type Err = Never;
+
+	fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<(), Never> {
+		if record.level().is_at_least(Level::Error) {
+			let time = SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map(|d| d.as_secs() as i64)
+				.unwrap_or(0);
+			let mut errors = RECENT_ERRORS.lock().unwrap();
+			if errors.len() >= RECENT_ERRORS_LEN {
+				errors.pop_front();
+			}
+			errors.push_back((time, format!("{}", record.msg())));
+		}
+		Ok(())
+	}
+}
+
+/// Most recent error messages, with the time (seconds since epoch) they
+/// were logged at, oldest first
+pub fn recent_errors() -> Vec<(i64, String)> {
+	RECENT_ERRORS.lock().unwrap().iter().cloned().collect()
+}
+
+/// Number of most recent log lines kept, for crash reports
+pub const RECENT_LINES_LEN: usize = 200;
+
+/// Drain keeping the most recent log lines at the file log level
+struct RecentLinesDrain;
+
+impl Drain for RecentLinesDrain {
+	type Ok = ();
+	type Err = Never;
+
+	fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<(), Never> {
+		let level = Level::from_usize(FILE_LEVEL.load(Ordering::Relaxed)).unwrap_or(Level::Info);
+		if record.level().is_at_least(level) {
+			let time = SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map(|d| d.as_secs() as i64)
+				.unwrap_or(0);
+			// a panicking thread may hold the lock, never wait for it
+			if let Ok(mut lines) = RECENT_LINES.try_lock() {
+				if lines.len() >= RECENT_LINES_LEN {
+					lines.pop_front();
+				}
+				lines.push_back(format!("{} {} {}", time, record.level().as_short_str(), record.msg()));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Most recent log lines, oldest first
+pub fn recent_log_lines() -> Vec<String> {
+	match RECENT_LINES.try_lock() {
+		Ok(lines) => lines.iter().cloned().collect(),
+		Err(_) => vec![],
+	}
+}
+
 lazy_static! {
+	/// Most recent error messages
+	static ref RECENT_ERRORS: Mutex<VecDeque<(i64, String)>> =
+		Mutex::new(VecDeque::with_capacity(RECENT_ERRORS_LEN));
+
+	/// Most recent log lines
+	static ref RECENT_LINES: Mutex<VecDeque<String>> =
+		Mutex::new(VecDeque::with_capacity(RECENT_LINES_LEN));
+
 	/// Flag to observe whether logging was explicitly initialised (don't output otherwise)
 	static ref WAS_INIT: Mutex<bool> = Mutex::new(false);
 	/// Flag to observe whether tui is running, and we therefore don't want to attempt to write
@@ -50,16 +290,31 @@ lazy_static! {
 		let config = LOGGING_CONFIG.lock().unwrap();
 		let slog_level_stdout = convert_log_level(&config.stdout_log_level);
 		let slog_level_file = convert_log_level(&config.file_log_level);
+		FILE_LEVEL.store(slog_level_file.as_usize(), Ordering::Relaxed);
 		if config.tui_running.is_some() && config.tui_running.unwrap() {
 			let mut tui_running_ref = TUI_RUNNING.lock().unwrap();
 			*tui_running_ref = true;
 		}
 
+		let json_format = config.log_format.as_ref().map(|f| f == "json").unwrap_or(false);
+
 		//Terminal output drain
-		let terminal_decorator = slog_term::TermDecorator::new().build();
-		let terminal_drain = slog_term::FullFormat::new(terminal_decorator).build().fuse();
-		let terminal_drain = LevelFilter::new(terminal_drain, slog_level_stdout).fuse();
-		let mut terminal_drain = slog_async::Async::new(terminal_drain).build().fuse();
+		STDOUT_LEVEL.store(slog_level_stdout.as_usize(), Ordering::Relaxed);
+		let mut terminal_drain = if json_format {
+			let terminal_drain = RuntimeLevelFilter {
+				drain: json_drain(::std::io::stdout()).fuse(),
+				level: &STDOUT_LEVEL,
+			}.fuse();
+			slog_async::Async::new(terminal_drain).build().fuse()
+		} else {
+			let terminal_decorator = slog_term::TermDecorator::new().build();
+			let terminal_drain = slog_term::FullFormat::new(terminal_decorator).build().fuse();
+			let terminal_drain = RuntimeLevelFilter {
+				drain: terminal_drain,
+				level: &STDOUT_LEVEL,
+			}.fuse();
+			slog_async::Async::new(terminal_drain).build().fuse()
+		};
 		if !config.log_to_stdout || !was_init {
 			terminal_drain = slog_async::Async::new(Discard{}).build().fuse();
 		}
@@ -67,24 +322,60 @@ lazy_static! {
 		//File drain
 		let mut file_drain_final = slog_async::Async::new(Discard{}).build().fuse();
 		if config.log_to_file && was_init {
-			let file = OpenOptions::new()
-				.create(true)
-				.write(true)
-				.append(config.log_file_append)
-				.truncate(false)
-				.open(&config.log_file_path)
-				.unwrap();
-
-			let file_decorator = slog_term::PlainDecorator::new(file);
-			let file_drain = slog_term::FullFormat::new(file_decorator).build().fuse();
-			let file_drain = LevelFilter::new(file_drain, slog_level_file).fuse();
-			file_drain_final = slog_async::Async::new(file_drain).build().fuse();
+			let file = RotatingFile::open(
+				&config.log_file_path,
+				config.log_file_append,
+				config.log_max_size_mb.map(|s| s * 1024 * 1024),
+				config.log_max_age_hours.map(|h| Duration::from_secs(h * 3600)),
+				config.log_max_files.unwrap_or(5),
+				config.log_compress.unwrap_or(false),
+			).unwrap();
+
+			file_drain_final = if json_format {
+				let file_drain = RuntimeLevelFilter {
+					drain: json_drain(file).fuse(),
+					level: &FILE_LEVEL,
+				}.fuse();
+				slog_async::Async::new(file_drain).build().fuse()
+			} else {
+				let file_decorator = slog_term::PlainDecorator::new(file);
+				let file_drain = slog_term::FullFormat::new(file_decorator).build().fuse();
+				let file_drain = RuntimeLevelFilter {
+					drain: file_drain,
+					level: &FILE_LEVEL,
+				}.fuse();
+				slog_async::Async::new(file_drain).build().fuse()
+			};
+		}
+
+		//Per device files drain
+		let mut device_files_drain = slog_async::Async::new(Discard{}).build().fuse();
+		if config.log_to_file && was_init && config.log_device_files.unwrap_or(false) {
+			let device_drain = RuntimeLevelFilter {
+				drain: DeviceFilesDrain {
+					path: config.log_file_path.clone(),
+					append: config.log_file_append,
+					drains: Mutex::new(HashMap::new()),
+				},
+				level: &FILE_LEVEL,
+			}.fuse();
+			device_files_drain = slog_async::Async::new(device_drain).build().fuse();
 		}
 
-		//Compose file and terminal drains
+		//Compose file and terminal drains, filtered by device
 		let composite_drain = Duplicate::new(terminal_drain, file_drain_final).fuse();
+		let composite_drain = DeviceFilter {
+			drain: composite_drain,
+			devices: config.log_device_filter.clone(),
+		}.fuse();
+		let composite_drain = Duplicate::new(composite_drain, device_files_drain).fuse();
+		let composite_drain = Duplicate::new(composite_drain, ErrorHistoryDrain).fuse();
+		let composite_drain = Duplicate::new(composite_drain, RecentLinesDrain).fuse();
 
-		let log = Logger::root(composite_drain, o!());
+		let log = match config.log_rig.clone() {
+			Some(rig) => Logger::root(composite_drain, o!("rig" => rig)),
+			None => Logger::root(composite_drain, o!()),
+		};
 		log
 	};
 }
@@ -102,6 +393,36 @@ pub fn init_logger(config: Option<LoggingConfig>) {
 	send_panic_to_log();
 }
 
+/// Changes the level of stdout and file logging while running
+pub fn set_log_level(level: LogLevel) {
+	let level = convert_log_level(&level).as_usize();
+	STDOUT_LEVEL.store(level, Ordering::Relaxed);
+	FILE_LEVEL.store(level, Ordering::Relaxed);
+}
+
+/// Changes the stdout and file log levels separately while running
+pub fn set_log_levels(stdout_level: LogLevel, file_level: LogLevel) {
+	STDOUT_LEVEL.store(convert_log_level(&stdout_level).as_usize(), Ordering::Relaxed);
+	FILE_LEVEL.store(convert_log_level(&file_level).as_usize(), Ordering::Relaxed);
+}
+
+/// The logging configuration in use
+pub fn logging_config() -> LoggingConfig {
+	LOGGING_CONFIG.lock().unwrap().clone()
+}
+
+/// Returns the current file log level (or stdout log level if not
+/// logging to a file)
+pub fn log_level() -> LogLevel {
+	let log_to_file = LOGGING_CONFIG.lock().unwrap().log_to_file;
+	let level = if log_to_file {
+		FILE_LEVEL.load(Ordering::Relaxed)
+	} else {
+		STDOUT_LEVEL.load(Ordering::Relaxed)
+	};
+	convert_slog_level(Level::from_usize(level).unwrap_or(Level::Info))
+}
+
 /// Initializes the logger for unit and integration tests
 pub fn init_test_logger() {
 	let mut was_init_ref = WAS_INIT.lock().unwrap();
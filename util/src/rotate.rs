@@ -0,0 +1,151 @@
+// Copyright 2018 The Grin Developers
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log file writer with size and age based rotation, so long running rigs
+//! don't need an external logrotate
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+/// Log file which is moved to path.1 (path.1.gz if compressing) once it
+/// gets too big or too old, shifting older files up and keeping at most
+/// max_files of them
+pub struct RotatingFile {
+	path: String,
+	file: File,
+	size: u64,
+	opened: SystemTime,
+	max_size: Option<u64>,
+	max_age: Option<Duration>,
+	max_files: u32,
+	compress: bool,
+}
+
+impl RotatingFile {
+	/// Open (or create) the log file at path
+	pub fn open(
+		path: &str,
+		append: bool,
+		max_size: Option<u64>,
+		max_age: Option<Duration>,
+		max_files: u32,
+		compress: bool,
+	) -> io::Result<RotatingFile> {
+		let file = OpenOptions::new()
+			.create(true)
+			.write(true)
+			.append(append)
+			.truncate(!append)
+			.open(path)?;
+		let size = file.metadata()?.len();
+		Ok(RotatingFile {
+			path: path.to_string(),
+			file: file,
+			size: size,
+			opened: SystemTime::now(),
+			max_size: max_size,
+			max_age: max_age,
+			max_files: max_files,
+			compress: compress,
+		})
+	}
+
+	/// Bytes in the current file
+	pub fn size(&self) -> u64 {
+		self.size
+	}
+
+	/// Whether writing len more bytes rotates the file first
+	pub fn needs_rotation(&self, len: usize) -> bool {
+		if let Some(max_size) = self.max_size {
+			if self.size > 0 && self.size + len as u64 > max_size {
+				return true;
+			}
+		}
+		if let Some(max_age) = self.max_age {
+			if self.opened.elapsed().map(|e| e >= max_age).unwrap_or(false) {
+				return true;
+			}
+		}
+		false
+	}
+
+	/// Name of the rotated file with the given index
+	fn rotated_path(&self, index: u32) -> String {
+		if self.compress {
+			format!("{}.{}.gz", self.path, index)
+		} else {
+			format!("{}.{}", self.path, index)
+		}
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+		if self.max_files > 0 {
+			let _ = fs::remove_file(self.rotated_path(self.max_files));
+			for i in (1..self.max_files).rev() {
+				let from = self.rotated_path(i);
+				if Path::new(&from).exists() {
+					fs::rename(&from, self.rotated_path(i + 1))?;
+				}
+			}
+			if self.compress {
+				compress(&self.path, &self.rotated_path(1))?;
+			} else {
+				fs::rename(&self.path, self.rotated_path(1))?;
+			}
+		}
+		self.file = OpenOptions::new()
+			.create(true)
+			.write(true)
+			.truncate(true)
+			.open(&self.path)?;
+		self.size = 0;
+		self.opened = SystemTime::now();
+		Ok(())
+	}
+}
+
+/// Gzip the file at from into to
+fn compress(from: &str, to: &str) -> io::Result<()> {
+	let mut input = File::open(from)?;
+	let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+	io::copy(&mut input, &mut encoder)?;
+	encoder.finish()?;
+	Ok(())
+}
+
+impl Write for RotatingFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if self.needs_rotation(buf.len()) {
+			// keep logging to the current file if rotation fails
+			if let Err(e) = self.rotate() {
+				eprintln!("Unable to rotate log file {}: {}", self.path, e);
+				// back off until the limits are reached again
+				self.size = 0;
+				self.opened = SystemTime::now();
+			}
+		}
+		let written = self.file.write(buf)?;
+		self.size += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
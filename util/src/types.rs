@@ -15,7 +15,7 @@
 //! Logging configuration types
 
 /// Log level types, as slog's don't implement serialize
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum LogLevel {
 	/// Critical
 	Critical,
@@ -48,6 +48,23 @@ pub struct LoggingConfig {
 	pub log_file_append: bool,
 	/// Whether the tui is running (optional)
 	pub tui_running: Option<bool>,
+	/// Log output format, "text" (default) or "json"
+	pub log_format: Option<String>,
+	/// Rotate the log file once it reaches this size, in MB
+	pub log_max_size_mb: Option<u64>,
+	/// Rotate the log file once it has been written to for this many hours
+	pub log_max_age_hours: Option<u64>,
+	/// Number of rotated log files to keep (default 5)
+	pub log_max_files: Option<u32>,
+	/// Whether to gzip rotated log files
+	pub log_compress: Option<bool>,
+	/// Whether to also write the records of each device to its own file
+	pub log_device_files: Option<bool>,
+	/// Only show records of these devices ("plugin:device") in the main log
+	pub log_device_filter: Option<Vec<String>>,
+	/// Rig name attached to every record (set from the [rig] section)
+	#[serde(skip)]
+	pub log_rig: Option<String>,
 }
 
 impl Default for LoggingConfig {
@@ -60,6 +77,14 @@ impl Default for LoggingConfig {
 			log_file_path: String::from("grin.log"),
 			log_file_append: false,
 			tui_running: None,
+			log_format: None,
+			log_max_size_mb: None,
+			log_max_age_hours: None,
+			log_max_files: None,
+			log_compress: None,
+			log_device_files: None,
+			log_device_filter: None,
+			log_rig: None,
 		}
 	}
 }